@@ -0,0 +1,92 @@
+const ESC: u8 = 0x1B;
+
+/// Copy of `data` with ANSI escape sequences (colors, cursor moves, OSC
+/// titles and links) removed, or `None` if it contains no ESC byte, which
+/// one SIMD `memchr` pass settles for clean input.
+pub fn strip(data: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = memchr::memchr(ESC, data)?;
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..pos]);
+    loop {
+        pos += sequence_len(&data[pos..]);
+        match memchr::memchr(ESC, &data[pos..]) {
+            Some(off) => {
+                out.extend_from_slice(&data[pos..pos + off]);
+                pos += off;
+            }
+            None => {
+                out.extend_from_slice(&data[pos..]);
+                return Some(out);
+            }
+        }
+    }
+}
+
+/// Length of the sequence at the start of `s`, which begins with ESC. A
+/// sequence cut short never swallows the newline ending its record.
+fn sequence_len(s: &[u8]) -> usize {
+    match s.get(1) {
+        // CSI: parameter and intermediate bytes, then one final byte.
+        Some(b'[') => {
+            let mut i = 2;
+            while i < s.len() && (0x20..=0x3F).contains(&s[i]) {
+                i += 1;
+            }
+            if i < s.len() && (0x40..=0x7E).contains(&s[i]) {
+                i + 1
+            } else {
+                i
+            }
+        }
+        // OSC: ends at BEL or ESC \.
+        Some(b']') => {
+            let mut i = 2;
+            while i < s.len() && s[i] != b'\n' {
+                if s[i] == 0x07 {
+                    return i + 1;
+                }
+                if s[i] == ESC && s.get(i + 1) == Some(&b'\\') {
+                    return i + 2;
+                }
+                i += 1;
+            }
+            i
+        }
+        // nF, e.g. the charset designation ESC ( B.
+        Some(0x20..=0x2F) => {
+            let mut i = 2;
+            while i < s.len() && (0x20..=0x2F).contains(&s[i]) {
+                i += 1;
+            }
+            if i < s.len() && (0x30..=0x7E).contains(&s[i]) {
+                i + 1
+            } else {
+                i
+            }
+        }
+        Some(0x30..=0x7E) => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_sequences() {
+        assert_eq!(strip(b"2025-02-12T10:31:45Z INFO api ok\n"), None);
+
+        let colored = b"2025-02-12T10:31:45Z \x1b[1;31mERROR\x1b[0m api \x1b]8;;http://x\x07link\x1b]8;;\x1b\\ \x1b(Bdone\x1b7\n";
+        assert_eq!(
+            strip(colored).unwrap(),
+            b"2025-02-12T10:31:45Z ERROR api link done\n"
+        );
+
+        // Truncated sequences stop at the newline.
+        assert_eq!(
+            strip(b"a\x1b[31\nb\x1b]0;title\nc\x1b").unwrap(),
+            b"a\nb\nc"
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use crate::emit::{EmitRecord, FieldValue, unescape_json, write_json_string};
+use crate::format::LogFormat;
+use crate::orchestrator::parse_logs_pipelined;
+use crate::structured_orchestrator::parse_structured_mmap;
+use memmap2::Mmap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+/// Median throughput of one dataset. Baselines are stored one result per
+/// line as `{"name":...,"format":...,"records":...,"gbps":...}`, so they can
+/// be read back with the tool's own JSON parser and diffed in review.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub name: String,
+    pub format: LogFormat,
+    pub records: u64,
+    pub gbps: f64,
+}
+
+/// Parses `path` once to warm the page cache, then `runs` more times, and
+/// keeps the median. Results are keyed by file name so a baseline taken on
+/// one machine applies to the same dataset elsewhere.
+pub fn run_file(path: &str, num_threads: usize, runs: usize) -> io::Result<BenchResult> {
+    let file = File::open(path)?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let format = LogFormat::detect(&mmap[..mmap.len().min(4096)]);
+
+    let mut records = 0;
+    let mut samples = Vec::with_capacity(runs);
+    for run in 0..=runs.max(1) {
+        let start = Instant::now();
+        records = match format {
+            LogFormat::PlainText => parse_logs_pipelined(&mmap, num_threads).total_lines,
+            _ => parse_structured_mmap(&mmap, num_threads, Some(format)).total_records,
+        } as u64;
+        let secs = start.elapsed().as_secs_f64();
+        if run > 0 {
+            samples.push(mmap.len() as f64 / (1024.0 * 1024.0 * 1024.0) / secs.max(1e-9));
+        }
+    }
+    samples.sort_by(f64::total_cmp);
+
+    Ok(BenchResult {
+        name: Path::new(path)
+            .file_name()
+            .map_or_else(|| path.to_string(), |n| n.to_string_lossy().into_owned()),
+        format,
+        records,
+        gbps: samples[samples.len() / 2],
+    })
+}
+
+pub fn render_baseline(results: &[BenchResult]) -> String {
+    let mut out = Vec::new();
+    for result in results {
+        out.extend_from_slice(b"{\"name\":");
+        write_json_string(result.name.as_bytes(), &mut out);
+        out.extend_from_slice(
+            format!(
+                ",\"format\":\"{}\",\"records\":{},\"gbps\":{:.4}}}\n",
+                result.format.as_str(),
+                result.records,
+                result.gbps
+            )
+            .as_bytes(),
+        );
+    }
+    String::from_utf8(out).unwrap()
+}
+
+pub fn parse_baseline(text: &str) -> Result<Vec<BenchResult>, String> {
+    let parsed = parse_structured_mmap(text.as_bytes(), 1, Some(LogFormat::Json));
+    if parsed.malformed_lines > 0 {
+        return Err(format!("{} malformed lines", parsed.malformed_lines));
+    }
+    let mut results = Vec::new();
+    for batch in &parsed.batches {
+        for i in 0..batch.len {
+            let mut result = BenchResult {
+                name: String::new(),
+                format: LogFormat::PlainText,
+                records: 0,
+                gbps: f64::NAN,
+            };
+            batch.for_each_field(i, &mut |key, value| {
+                let text = match value {
+                    FieldValue::Escaped(v) => unescape_json(v),
+                    FieldValue::Text(v) | FieldValue::Literal(v) => {
+                        String::from_utf8_lossy(v).into_owned()
+                    }
+                    FieldValue::Timestamp(_) => return,
+                };
+                match key {
+                    b"name" => result.name = text,
+                    b"format" => {
+                        result.format = LogFormat::from_name(&text).unwrap_or(LogFormat::PlainText)
+                    }
+                    b"records" => result.records = text.parse().unwrap_or(0),
+                    b"gbps" => result.gbps = text.parse().unwrap_or(f64::NAN),
+                    _ => {}
+                }
+            });
+            if result.name.is_empty() || result.gbps.is_nan() || result.gbps <= 0.0 {
+                return Err(format!("entry {} needs a name and a positive gbps", i + 1));
+            }
+            results.push(result);
+        }
+    }
+    Ok(results)
+}
+
+/// Bytes from the start of the file used by the `--threads auto` sweep.
+const SWEEP_SAMPLE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Bytes from the start of each file `--detect` times parsers on.
+pub const DETECT_SAMPLE_BYTES: usize = SWEEP_SAMPLE_BYTES;
+
+/// Throughput of each thread count tried by [`sweep_threads`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadSweep {
+    pub samples: Vec<(usize, f64)>,
+    pub best: usize,
+}
+
+/// Thread counts worth trying up to `max_threads`: powers of two plus half
+/// and all of the cores. Half matters on multi-socket machines, where memory
+/// bandwidth often saturates well before every core is busy.
+pub fn sweep_candidates(max_threads: usize) -> Vec<usize> {
+    let max_threads = max_threads.max(1);
+    let mut candidates: Vec<usize> = std::iter::successors(Some(1usize), |n| n.checked_mul(2))
+        .take_while(|&n| n < max_threads)
+        .chain([max_threads.div_ceil(2), max_threads])
+        .collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+    candidates
+}
+
+/// Splits `data` into `parts` slices ending on line boundaries.
+fn split_lines(data: &[u8], parts: usize) -> Vec<&[u8]> {
+    let mut pieces = Vec::with_capacity(parts);
+    let mut start = 0;
+    for k in 1..parts {
+        let target = (data.len() * k / parts).max(start);
+        let end = match memchr::memchr(b'\n', &data[target..]) {
+            Some(off) => target + off + 1,
+            None => data.len(),
+        };
+        pieces.push(&data[start..end]);
+        start = end;
+    }
+    pieces.push(&data[start..]);
+    pieces
+}
+
+/// Parses the first chunks of `data` once per candidate thread count, each
+/// thread taking an equal share, and picks the fastest. A larger count only
+/// wins if it beats the best smaller one by more than 2%.
+pub fn sweep_threads(data: &[u8], format: LogFormat, max_threads: usize) -> ThreadSweep {
+    let sample = head_sample(data);
+    let parse = |piece: &[u8]| match format {
+        LogFormat::PlainText => {
+            parse_logs_pipelined(piece, 1);
+        }
+        _ => {
+            parse_structured_mmap(piece, 1, Some(format));
+        }
+    };
+
+    // Fault the sample in so the first candidate is not charged for it.
+    parse(sample);
+    let mut samples = Vec::new();
+    for threads in sweep_candidates(max_threads) {
+        let pieces = split_lines(sample, threads);
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            for piece in &pieces {
+                scope.spawn(|| parse(piece));
+            }
+        });
+        let secs = start.elapsed().as_secs_f64().max(1e-9);
+        samples.push((
+            threads,
+            sample.len() as f64 / (1024.0 * 1024.0 * 1024.0) / secs,
+        ));
+    }
+
+    let mut best = samples[0];
+    for &sample in &samples[1..] {
+        if sample.1 > best.1 * 1.02 {
+            best = sample;
+        }
+    }
+    ThreadSweep {
+        samples,
+        best: best.0,
+    }
+}
+
+/// The first [`SWEEP_SAMPLE_BYTES`] of `data`, cut after a line.
+fn head_sample(data: &[u8]) -> &[u8] {
+    let sample = &data[..data.len().min(SWEEP_SAMPLE_BYTES)];
+    match memchr::memrchr(b'\n', sample) {
+        Some(end) if sample.len() < data.len() => &sample[..=end],
+        _ => sample,
+    }
+}
+
+/// What parsing a file's head as one format achieved, for `--detect`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatBench {
+    pub format: LogFormat,
+    pub records: u64,
+    pub malformed: u64,
+    pub gbps: f64,
+}
+
+/// Parses the first chunks of `data` as each of `formats` on `num_threads`
+/// threads, keeping the faster of two runs. The sample is faulted in first
+/// so the first format is not charged for it.
+pub fn bench_formats(data: &[u8], formats: &[LogFormat], num_threads: usize) -> Vec<FormatBench> {
+    let sample = head_sample(data);
+    let parse = |format: LogFormat| match format {
+        LogFormat::PlainText => {
+            let result = parse_logs_pipelined(sample, num_threads);
+            (result.total_lines as u64, result.malformed_lines)
+        }
+        _ => {
+            let result = parse_structured_mmap(sample, num_threads, Some(format));
+            (result.total_records as u64, result.malformed_lines)
+        }
+    };
+
+    if let Some(&first) = formats.first() {
+        parse(first);
+    }
+    formats
+        .iter()
+        .map(|&format| {
+            let mut best = f64::INFINITY;
+            let mut counts = (0, 0);
+            for _ in 0..2 {
+                let start = Instant::now();
+                counts = parse(format);
+                best = best.min(start.elapsed().as_secs_f64());
+            }
+            FormatBench {
+                format,
+                records: counts.0,
+                malformed: counts.1,
+                gbps: sample.len() as f64 / (1024.0 * 1024.0 * 1024.0) / best.max(1e-9),
+            }
+        })
+        .collect()
+}
+
+/// `--detect`'s table of [`bench_formats`] results, marking the detected
+/// format and the fastest one that parsed most lines.
+pub struct FormatBenchTable<'a> {
+    pub results: &'a [FormatBench],
+    pub detected: LogFormat,
+}
+
+impl fmt::Display for FormatBenchTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let clean = |r: &FormatBench| r.malformed * 2 < r.records.max(1);
+        let fastest = self
+            .results
+            .iter()
+            .filter(|r| clean(r))
+            .max_by(|a, b| a.gbps.total_cmp(&b.gbps))
+            .map(|r| r.format);
+        writeln!(
+            f,
+            "  {:<12} {:>8} {:>12} {:>10}",
+            "parser", "GB/s", "records", "malformed"
+        )?;
+        for r in self.results {
+            let mut notes = Vec::new();
+            if r.format == self.detected {
+                notes.push("detected");
+            }
+            if Some(r.format) == fastest {
+                notes.push("fastest");
+            }
+            write!(
+                f,
+                "  {:<12} {:>8.2} {:>12} {:>9.1}%",
+                r.format.as_str(),
+                r.gbps,
+                r.records,
+                100.0 * r.malformed as f64 / r.records.max(1) as f64,
+            )?;
+            if !notes.is_empty() {
+                write!(f, " {}", notes.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `5%`, `5` or `0.05%`; the value is a percentage drop.
+pub fn parse_threshold(text: &str) -> Option<f64> {
+    text.trim()
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .ok()
+        .filter(|t| (0.0..100.0).contains(t))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    pub baseline: Option<f64>,
+    pub current: f64,
+    /// Throughput change against the baseline, in percent.
+    pub change_pct: f64,
+    pub regressed: bool,
+}
+
+pub fn compare(
+    baseline: &[BenchResult],
+    current: &[BenchResult],
+    threshold_pct: f64,
+) -> Vec<Comparison> {
+    current
+        .iter()
+        .map(|result| {
+            let base = baseline
+                .iter()
+                .find(|b| b.name == result.name)
+                .map(|b| b.gbps);
+            let change_pct = base.map_or(0.0, |b| (result.gbps - b) / b * 100.0);
+            Comparison {
+                name: result.name.clone(),
+                baseline: base,
+                current: result.gbps,
+                change_pct,
+                regressed: change_pct < -threshold_pct,
+            }
+        })
+        .collect()
+}
+
+pub struct ComparisonTable<'a>(pub &'a [Comparison]);
+
+impl fmt::Display for ComparisonTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  {:<28} {:>10} {:>10} {:>8}",
+            "dataset", "baseline", "current", "change"
+        )?;
+        for c in self.0 {
+            let baseline = c
+                .baseline
+                .map_or_else(|| "-".to_string(), |b| format!("{:.2}", b));
+            let status = match (c.baseline, c.regressed) {
+                (None, _) => "new",
+                (_, true) => "REGRESSED",
+                _ => "ok",
+            };
+            writeln!(
+                f,
+                "  {:<28} {:>10} {:>10.2} {:>+7.1}% {}",
+                c.name, baseline, c.current, c.change_pct, status
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(name: &str, gbps: f64) -> BenchResult {
+        BenchResult {
+            name: name.to_string(),
+            format: LogFormat::Json,
+            records: 10,
+            gbps,
+        }
+    }
+
+    #[test]
+    fn test_baseline_round_trip() {
+        let results = vec![result("a.jsonl", 3.25), result("b \"q\"\\\u{1}.log", 1.5)];
+        let text = render_baseline(&results);
+        assert_eq!(parse_baseline(&text).unwrap(), results);
+        assert!(parse_baseline("{\"name\":\"x\"}\n").is_err());
+        assert!(parse_baseline("not json\n").is_err());
+    }
+
+    #[test]
+    fn test_thread_sweep() {
+        assert_eq!(sweep_candidates(1), [1]);
+        assert_eq!(sweep_candidates(6), [1, 2, 3, 4, 6]);
+        assert_eq!(sweep_candidates(64), [1, 2, 4, 8, 16, 32, 64]);
+
+        let data = b"a\nbb\nccc\ndddd\n";
+        let pieces = split_lines(data, 3);
+        assert_eq!(pieces.concat(), data);
+        assert!(pieces.iter().all(|p| p.is_empty() || p.ends_with(b"\n")));
+
+        let sweep = sweep_threads(data, LogFormat::PlainText, 4);
+        assert_eq!(sweep.samples.len(), 3);
+        assert!(sweep_candidates(4).contains(&sweep.best));
+    }
+
+    #[test]
+    fn test_bench_formats() {
+        let data = b"{\"level\":\"info\",\"msg\":\"a\"}\n{\"level\":\"warn\",\"msg\":\"b\"}\n";
+        let results = bench_formats(data, &[LogFormat::Json, LogFormat::PlainText], 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].records, results[0].malformed), (2, 0));
+        assert_eq!(results[1].format, LogFormat::PlainText);
+        assert!(results.iter().all(|r| r.gbps > 0.0));
+
+        let results = [
+            FormatBench {
+                format: LogFormat::Json,
+                records: 100,
+                malformed: 0,
+                gbps: 2.0,
+            },
+            FormatBench {
+                format: LogFormat::Logfmt,
+                records: 100,
+                malformed: 90,
+                gbps: 5.0,
+            },
+        ];
+        let text = FormatBenchTable {
+            results: &results,
+            detected: LogFormat::Json,
+        }
+        .to_string();
+        assert!(
+            text.contains("  json             2.00          100       0.0% detected, fastest\n")
+        );
+        assert!(text.contains("  logfmt           5.00          100      90.0%\n"));
+    }
+
+    #[test]
+    fn test_compare_flags_regressions_past_threshold() {
+        assert_eq!(parse_threshold("5%"), Some(5.0));
+        assert_eq!(parse_threshold("2.5"), Some(2.5));
+        assert_eq!(parse_threshold("-1%"), None);
+
+        let baseline = [result("a", 4.0), result("b", 4.0)];
+        let current = [result("a", 3.9), result("b", 3.7), result("c", 1.0)];
+        let cmp = compare(&baseline, &current, 5.0);
+        assert!(!cmp[0].regressed);
+        assert!(cmp[1].regressed);
+        assert!((cmp[1].change_pct + 7.5).abs() < 1e-9);
+        assert_eq!(cmp[2].baseline, None);
+        assert!(!cmp[2].regressed);
+    }
+}
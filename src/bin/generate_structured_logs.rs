@@ -1,12 +1,25 @@
 use std::fs::File;
 use std::io::{BufWriter, Write};
 
+/// Records are at most this far short of an --align boundary when padded
+/// out to it; longer than any record, so none can step over a boundary.
+const PAD_SLACK: u64 = 512;
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 4 {
-        eprintln!("Usage: generate-structured-logs <size_mb> <output_file> <format>");
+        eprintln!(
+            "Usage: generate-structured-logs <size_mb> <output_file> <format> [--align <bytes>] [--jitter <bytes>]"
+        );
         eprintln!("  format: json | logfmt | csv | log");
+        eprintln!("  --align: pad records so a line ends exactly at every multiple of <bytes>");
+        eprintln!(
+            "  --jitter: move those line ends by up to ±<bytes>, cycling through each offset"
+        );
         eprintln!("Example: generate-structured-logs 1000 /tmp/test_1gb.jsonl json");
+        eprintln!(
+            "Example: generate-structured-logs 200 /tmp/chunks.log log --align 67108864 --jitter 3"
+        );
         std::process::exit(1);
     }
 
@@ -18,6 +31,26 @@ fn main() {
     let format = &args[3];
     let target_bytes = size_mb * 1024 * 1024;
 
+    let mut align: u64 = 0;
+    let mut jitter: u64 = 0;
+    let mut i = 4;
+    while i < args.len() {
+        let value = args.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+        match (args[i].as_str(), value) {
+            ("--align", Some(v)) if v > 0 => align = v,
+            ("--jitter", Some(v)) => jitter = v,
+            _ => {
+                eprintln!("Invalid option '{}'", args[i]);
+                std::process::exit(1);
+            }
+        }
+        i += 2;
+    }
+    if align > 0 && jitter >= align / 2 {
+        eprintln!("--jitter must be under half of --align");
+        std::process::exit(1);
+    }
+
     println!(
         "Generating {} MB {} log file: {}",
         size_mb, format, output_path
@@ -103,12 +136,16 @@ fn main() {
         "batch processing completed",
     ];
 
+    // Exact output offset, and the line end --align is steering towards.
+    let mut offset: u64 = 0;
+    let mut boundary: u64 = 0;
+    let mut boundary_index: u64 = 0;
+
     if format == "csv" {
-        writeln!(
-            writer,
-            "timestamp,level,component,message,request_id,latency_ms,status_code,user_id"
-        )
-        .unwrap();
+        let header =
+            "timestamp,level,component,message,request_id,latency_ms,status_code,user_id\n";
+        writer.write_all(header.as_bytes()).unwrap();
+        offset += header.len() as u64;
     }
 
     let mut bytes_written: u64 = 0;
@@ -155,10 +192,9 @@ fn main() {
             base_year, base_month, base_day, hour, minute, second
         );
 
-        let written = match format.as_str() {
+        let mut line = match format.as_str() {
             "json" | "jsonl" | "ndjson" => {
-                writeln!(
-                    writer,
+                format!(
                     r#"{{"timestamp":"{}","level":"{}","component":"{}","message":"{}","request_id":"{}","latency_ms":{},"status_code":{},"user_id":{}}}"#,
                     ts,
                     levels[level_idx],
@@ -171,8 +207,7 @@ fn main() {
                 )
             }
             "logfmt" => {
-                writeln!(
-                    writer,
+                format!(
                     r#"ts={} level={} component={} msg="{}" request_id={} latency_ms={} status_code={} user_id={}"#,
                     ts,
                     levels[level_idx],
@@ -185,8 +220,7 @@ fn main() {
                 )
             }
             "csv" => {
-                writeln!(
-                    writer,
+                format!(
                     "{},{},{},{},{},{},{},{}",
                     ts,
                     levels[level_idx],
@@ -200,8 +234,7 @@ fn main() {
             }
             "log" => {
                 let (msg1, msg2) = messages[level_idx][msg_idx];
-                writeln!(
-                    writer,
+                format!(
                     "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z {} {} {} {}",
                     base_year,
                     base_month,
@@ -221,10 +254,26 @@ fn main() {
             }
         };
 
-        if let Err(e) = written {
+        if align > 0 {
+            // Pad with trailing spaces once the next boundary is within
+            // reach of this record; every format ignores or keeps them.
+            let end = offset + line.len() as u64 + 1;
+            while boundary < end {
+                boundary_index += 1;
+                let spread = 2 * jitter + 1;
+                boundary = boundary_index * align + boundary_index % spread - jitter;
+            }
+            if boundary - end <= PAD_SLACK {
+                line.extend(std::iter::repeat_n(' ', (boundary - end) as usize));
+            }
+        }
+        line.push('\n');
+
+        if let Err(e) = writer.write_all(line.as_bytes()) {
             eprintln!("Error writing: {}", e);
             std::process::exit(1);
         }
+        offset += line.len() as u64;
 
         bytes_written += if format == "log" { 80 } else { 150 }; // approximate line length
         line_count += 1;
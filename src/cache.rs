@@ -0,0 +1,171 @@
+use crate::dedup::hash_line;
+use crate::store;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// Bytes hashed at the head of a file; with size and mtime this tells an
+/// unchanged file from one rewritten in place within the same second.
+const PREFIX_BYTES: usize = 64 * 1024;
+
+const MAGIC: &str = "pandora-cache-v1";
+
+/// What a cached result depends on: the file's size, mtime and head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSignature {
+    pub len: u64,
+    pub mtime_ns: u128,
+    pub prefix_hash: u64,
+}
+
+impl FileSignature {
+    pub fn of(path: &str) -> io::Result<FileSignature> {
+        let mut file = File::open(path)?;
+        let meta = file.metadata()?;
+        let mtime_ns = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let mut head = Vec::with_capacity(PREFIX_BYTES);
+        (&mut file)
+            .take(PREFIX_BYTES as u64)
+            .read_to_end(&mut head)?;
+        Ok(FileSignature {
+            len: meta.len(),
+            mtime_ns,
+            prefix_hash: hash_line(&head),
+        })
+    }
+}
+
+/// One file's report for one query, plus the numbers a multi-file run needs
+/// to carry on (`--limit` budget, per-format breakdown).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedReport {
+    pub format: String,
+    pub bytes: u64,
+    pub records: u64,
+    pub matched: u64,
+    pub parse_ms: f64,
+    pub total_ms: f64,
+    pub text: String,
+}
+
+/// Report cache in the `reports` directory of the [`store`] layout, one
+/// file per (file signature, query) pair.
+pub struct QueryCache {
+    dir: PathBuf,
+}
+
+impl QueryCache {
+    pub fn open_default() -> io::Result<QueryCache> {
+        QueryCache::open(store::cache_dir("reports")?)
+    }
+
+    pub fn open(dir: PathBuf) -> io::Result<QueryCache> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(QueryCache { dir })
+    }
+
+    fn entry_path(&self, sig: &FileSignature, query: &str) -> PathBuf {
+        let mut key = Vec::with_capacity(query.len() + 40);
+        key.extend_from_slice(&sig.len.to_le_bytes());
+        key.extend_from_slice(&sig.mtime_ns.to_le_bytes());
+        key.extend_from_slice(&sig.prefix_hash.to_le_bytes());
+        key.extend_from_slice(query.as_bytes());
+        self.dir.join(format!("{:016x}.report", hash_line(&key)))
+    }
+
+    pub fn get(&self, sig: &FileSignature, query: &str) -> Option<CachedReport> {
+        let entry = std::fs::read_to_string(self.entry_path(sig, query)).ok()?;
+        let (header, text) = entry.split_once('\n')?;
+        let (stored_query, text) = text.split_once('\n')?;
+        let mut parts = header.split(' ');
+        if parts.next()? != MAGIC
+            || parts.next()?.parse::<u64>().ok()? != sig.len
+            || parts.next()?.parse::<u128>().ok()? != sig.mtime_ns
+            || parts.next()?.parse::<u64>().ok()? != sig.prefix_hash
+            || stored_query != escape(query)
+        {
+            return None;
+        }
+        Some(CachedReport {
+            format: parts.next()?.to_string(),
+            bytes: parts.next()?.parse().ok()?,
+            records: parts.next()?.parse().ok()?,
+            matched: parts.next()?.parse().ok()?,
+            parse_ms: parts.next()?.parse().ok()?,
+            total_ms: parts.next()?.parse().ok()?,
+            text: text.to_string(),
+        })
+    }
+
+    /// Publishes the entry whole, so a concurrent reader sees either the
+    /// old entry or the new one.
+    pub fn put(&self, sig: &FileSignature, query: &str, report: &CachedReport) -> io::Result<()> {
+        let mut out = String::with_capacity(report.text.len() + query.len() + 128);
+        writeln!(
+            out,
+            "{} {} {} {} {} {} {} {} {} {}",
+            MAGIC,
+            sig.len,
+            sig.mtime_ns,
+            sig.prefix_hash,
+            report.format,
+            report.bytes,
+            report.records,
+            report.matched,
+            report.parse_ms,
+            report.total_ms
+        )
+        .unwrap();
+        writeln!(out, "{}", escape(query)).unwrap();
+        out.push_str(&report.text);
+        store::publish(&self.entry_path(sig, query), out.as_bytes())
+    }
+}
+
+/// Keeps the query on one line of the entry.
+fn escape(query: &str) -> String {
+    query.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip_and_invalidation() {
+        let dir = std::env::temp_dir().join(format!("pandora-cache-{}", std::process::id()));
+        let cache = QueryCache::open(dir.clone()).unwrap();
+        let sig = FileSignature {
+            len: 100,
+            mtime_ns: 1_700_000_000_000_000_000,
+            prefix_hash: 42,
+        };
+        let report = CachedReport {
+            format: "json".to_string(),
+            bytes: 100,
+            records: 3,
+            matched: 1,
+            parse_ms: 0.5,
+            total_ms: 1.25,
+            text: "line one\nline two\n".to_string(),
+        };
+        let query = "--where\nstatus >= 500";
+
+        assert_eq!(cache.get(&sig, query), None);
+        cache.put(&sig, query, &report).unwrap();
+        assert_eq!(cache.get(&sig, query), Some(report));
+        assert_eq!(cache.get(&sig, "--level error"), None);
+
+        let touched = FileSignature {
+            mtime_ns: sig.mtime_ns + 1,
+            ..sig
+        };
+        assert_eq!(cache.get(&touched, query), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+
+/// CPUs the enclosing cgroup lets this process use, rounded up; `None` when
+/// there is no CPU quota. Checks the cgroup v2 `cpu.max` and the v1
+/// `cpu.cfs_quota_us` of our cgroup and every ancestor, keeping the tightest.
+pub fn cpu_limit() -> Option<usize> {
+    let proc_cgroup = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+
+    let mut quota: Option<f64> = None;
+    for mount in cgroup_mounts(&mountinfo) {
+        let Some(path) = cgroup_path(&proc_cgroup, mount.v2) else {
+            continue;
+        };
+        let rel = if mount.root == "/" {
+            path
+        } else {
+            path.strip_prefix(mount.root).unwrap_or("/")
+        };
+        let mut dir = PathBuf::from(mount.mount_point);
+        dir.push(rel.trim_start_matches('/'));
+        for dir in dir.ancestors() {
+            let found = if mount.v2 {
+                read(dir, "cpu.max").and_then(|max| quota_v2(&max))
+            } else {
+                read(dir, "cpu.cfs_quota_us")
+                    .zip(read(dir, "cpu.cfs_period_us"))
+                    .and_then(|(q, p)| quota_v1(&q, &p))
+            };
+            if let Some(found) = found {
+                quota = Some(quota.map_or(found, |q| q.min(found)));
+            }
+            if dir == Path::new(mount.mount_point) {
+                break;
+            }
+        }
+    }
+    quota.map(|q| (q.ceil() as usize).max(1))
+}
+
+fn read(dir: &Path, leaf: &str) -> Option<String> {
+    std::fs::read_to_string(dir.join(leaf)).ok()
+}
+
+/// `cpu.max` holds `<quota> <period>` in microseconds, or `max <period>`.
+fn quota_v2(cpu_max: &str) -> Option<f64> {
+    let mut parts = cpu_max.split_whitespace();
+    let quota = parts.next()?.parse::<f64>().ok()?;
+    let period = parts.next()?.parse::<f64>().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+/// v1 reports an unlimited quota as `-1`.
+fn quota_v1(quota_us: &str, period_us: &str) -> Option<f64> {
+    let quota = quota_us.trim().parse::<i64>().ok()?;
+    let period = period_us.trim().parse::<i64>().ok()?;
+    (quota > 0 && period > 0).then(|| quota as f64 / period as f64)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct CgroupMount<'a> {
+    root: &'a str,
+    mount_point: &'a str,
+    v2: bool,
+}
+
+/// cgroup2 mounts and v1 mounts carrying the `cpu` controller.
+fn cgroup_mounts(mountinfo: &str) -> Vec<CgroupMount<'_>> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (mount, fs) = line.split_once(" - ")?;
+            let mount: Vec<&str> = mount.split(' ').collect();
+            let mut fs = fs.split(' ');
+            let v2 = match fs.next()? {
+                "cgroup2" => true,
+                "cgroup" => false,
+                _ => return None,
+            };
+            let options = fs.nth(1)?;
+            if !v2 && !options.split(',').any(|o| o == "cpu") {
+                return None;
+            }
+            Some(CgroupMount {
+                root: mount.get(3)?,
+                mount_point: mount.get(4)?,
+                v2,
+            })
+        })
+        .collect()
+}
+
+/// Our path in the v2 hierarchy (`0::<path>`) or the v1 `cpu` hierarchy.
+fn cgroup_path(proc_cgroup: &str, v2: bool) -> Option<&str> {
+    proc_cgroup.lines().find_map(|line| {
+        let mut parts = line.splitn(3, ':');
+        let (_, controllers, path) = (parts.next()?, parts.next()?, parts.next()?);
+        let matches = if v2 {
+            controllers.is_empty()
+        } else {
+            controllers.split(',').any(|c| c == "cpu")
+        };
+        matches.then_some(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_quota_sources() {
+        assert_eq!(quota_v2("250000 100000\n"), Some(2.5));
+        assert_eq!(quota_v2("max 100000\n"), None);
+        assert_eq!(quota_v1("-1\n", "100000\n"), None);
+        assert_eq!(quota_v1("50000\n", "100000\n"), Some(0.5));
+
+        let mountinfo = "\
+32 24 0:28 / /sys/fs/cgroup rw - tmpfs tmpfs rw,mode=755
+33 32 0:29 /kubepods /sys/fs/cgroup/cpu,cpuacct rw - cgroup cgroup rw,cpu,cpuacct
+36 32 0:32 / /sys/fs/cgroup/memory rw - cgroup cgroup rw,memory
+42 32 0:38 / /sys/fs/cgroup/unified rw - cgroup2 cgroup2 rw";
+        assert_eq!(
+            cgroup_mounts(mountinfo),
+            [
+                CgroupMount {
+                    root: "/kubepods",
+                    mount_point: "/sys/fs/cgroup/cpu,cpuacct",
+                    v2: false
+                },
+                CgroupMount {
+                    root: "/",
+                    mount_point: "/sys/fs/cgroup/unified",
+                    v2: true
+                },
+            ]
+        );
+
+        let proc_cgroup = "4:memory:/kubepods/pod1\n2:cpu,cpuacct:/kubepods/pod1/c1\n0::/app\n";
+        assert_eq!(cgroup_path(proc_cgroup, false), Some("/kubepods/pod1/c1"));
+        assert_eq!(cgroup_path(proc_cgroup, true), Some("/app"));
+        assert_eq!(cgroup_path("1:cpuset:/\n", false), None);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::data::LogBatch;
+use std::sync::OnceLock;
+
+/// Where `--component-rule` finds a plain-text record's component in its
+/// message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentRule {
+    /// A leading `worker:` or `sshd[42]:`; the component is taken off the
+    /// message.
+    Prefix,
+    /// `<key>=value` anywhere in the message, quoted or not; the message
+    /// is left whole.
+    Key(Vec<u8>),
+}
+
+impl ComponentRule {
+    /// Parses `prefix` or `key=<name>`.
+    pub fn parse(spec: &str) -> Result<ComponentRule, String> {
+        match spec.split_once('=') {
+            None if spec == "prefix" => Ok(ComponentRule::Prefix),
+            Some(("key", name)) if !name.is_empty() => {
+                Ok(ComponentRule::Key(name.as_bytes().to_vec()))
+            }
+            _ => Err(format!("expected 'prefix' or 'key=<name>', got '{}'", spec)),
+        }
+    }
+
+    /// Component and message as ranges of `text`.
+    fn extract(&self, text: &[u8]) -> Option<((usize, usize), (usize, usize))> {
+        match self {
+            ComponentRule::Prefix => {
+                if !text.first()?.is_ascii_alphabetic() {
+                    return None;
+                }
+                let end = text.iter().position(|&b| {
+                    !(b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'/'))
+                })?;
+                let mut colon = end;
+                if text[end] == b'[' {
+                    let close = end + 1 + text[end + 1..].iter().position(|&b| b == b']')?;
+                    if !text[end + 1..close].iter().all(u8::is_ascii_digit) {
+                        return None;
+                    }
+                    colon = close + 1;
+                }
+                if text.get(colon) != Some(&b':')
+                    || text
+                        .get(colon + 1)
+                        .is_some_and(|b| !b.is_ascii_whitespace())
+                {
+                    return None;
+                }
+                let mut rest = colon + 1;
+                while rest < text.len() && text[rest].is_ascii_whitespace() {
+                    rest += 1;
+                }
+                Some(((0, end), (rest, text.len())))
+            }
+            ComponentRule::Key(key) => {
+                let mut from = 0;
+                while let Some(at) = memchr::memmem::find(&text[from..], key) {
+                    let start = from + at;
+                    let value = start + key.len() + 1;
+                    from = start + 1;
+                    if (start > 0 && !text[start - 1].is_ascii_whitespace())
+                        || text.get(start + key.len()) != Some(&b'=')
+                    {
+                        continue;
+                    }
+                    let (value, end) = if text.get(value) == Some(&b'"') {
+                        let close = memchr::memchr(b'"', &text[value + 1..])?;
+                        (value + 1, value + 1 + close)
+                    } else {
+                        let len = text[value..]
+                            .iter()
+                            .position(u8::is_ascii_whitespace)
+                            .unwrap_or(text.len() - value);
+                        (value, value + len)
+                    };
+                    return (end > value).then_some(((value, end), (0, text.len())));
+                }
+                None
+            }
+        }
+    }
+}
+
+static RULES: OnceLock<Vec<ComponentRule>> = OnceLock::new();
+
+/// Rules from `--component-rule`, tried in order on every plain-text
+/// record; only the first call takes effect.
+pub fn set_rules(rules: Vec<ComponentRule>) {
+    let _ = RULES.set(rules);
+}
+
+/// Applies the configured rules to records `start..end` of `batch`.
+pub fn apply_configured(data: &[u8], batch: &mut LogBatch, start: usize, end: usize) {
+    if let Some(rules) = RULES.get().filter(|rules| !rules.is_empty()) {
+        apply(rules, data, batch, start, end);
+    }
+}
+
+/// Points the component of records `start..end` at what the first matching
+/// rule finds. Rules look at the message, or, when the layout took the
+/// component from the first word of free text (it runs straight into the
+/// message), at that word and the message together.
+pub fn apply(rules: &[ComponentRule], data: &[u8], batch: &mut LogBatch, start: usize, end: usize) {
+    for i in start..end.min(batch.len) {
+        if batch.line_lens[i] == 0 {
+            continue;
+        }
+        let component = batch.component_offsets[i] as usize;
+        let component_end = component + batch.component_lens[i] as usize;
+        let message = batch.message_offsets[i] as usize;
+        let message_end = message + batch.message_lens[i] as usize;
+        let guessed = batch.component_lens[i] > 0
+            && (component_end + 1 == message || component_end == message && message == message_end);
+        let text_start = if guessed { component } else { message };
+        let text = &data[text_start..message_end.max(text_start)];
+        let Some(((c, c_end), (m, m_end))) = rules.iter().find_map(|rule| rule.extract(text))
+        else {
+            continue;
+        };
+        batch.component_offsets[i] = (text_start + c) as u64;
+        batch.component_lens[i] = (c_end - c) as u32;
+        batch.message_offsets[i] = (text_start + m) as u64;
+        batch.message_lens[i] = (m_end - m) as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::parse_logs_pipelined;
+
+    #[test]
+    fn test_component_rules_promote_from_message() {
+        let data = b"2025-02-12T10:31:45Z INFO worker: picked job 7\n\
+                     2025-02-12T10:31:46Z INFO Starting server module=http port=80\n\
+                     2025-02-12T10:31:47Z WARN sshd[42]: bad password\n\
+                     [2025-02-12T10:31:48Z] [ERROR] [api] cache: miss\n\
+                     2025-02-12T10:31:49Z INFO api served http://x module=\"auth svc\"\n\
+                     2025-02-12T10:31:50Z INFO api 12:30 done\n";
+        let mut result = parse_logs_pipelined(data, 1);
+        let rules = vec![
+            ComponentRule::parse("prefix").unwrap(),
+            ComponentRule::parse("key=module").unwrap(),
+        ];
+        let batch = &mut result.batches[0];
+        apply(&rules, data, batch, 0, batch.len);
+
+        let parts: Vec<(&str, &str)> = (0..batch.len)
+            .map(|i| unsafe { (batch.component(i), batch.message(i)) })
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                ("worker", "picked job 7"),
+                ("http", "Starting server module=http port=80"),
+                ("sshd", "bad password"),
+                ("cache", "miss"),
+                ("auth svc", "api served http://x module=\"auth svc\""),
+                ("api", "12:30 done"),
+            ]
+        );
+        assert!(ComponentRule::parse("key=").is_err());
+        assert!(ComponentRule::parse("suffix").is_err());
+    }
+}
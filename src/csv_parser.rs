@@ -1,13 +1,17 @@
+use crate::orchestrator::chunk_len;
 use crate::structured::{FieldRef, StructuredBatch, well_known};
 
 pub struct CsvHeader {
     pub columns: Vec<(u64, u32)>,
+    /// Column names, owned so records can be keyed after the buffer holding
+    /// the header is gone.
+    pub names: Vec<Box<[u8]>>,
     pub well_known: Vec<well_known::WellKnownKind>,
 }
 
 impl CsvHeader {
     pub fn parse(data: &[u8]) -> Option<CsvHeader> {
-        let line_end = memchr::memchr(b'\n', data).unwrap_or(data.len());
+        let line_end = memchr::memchr(crate::simd_scan::record_sep(), data).unwrap_or(data.len());
         let header_line = &data[..line_end];
 
         let header_line = if header_line.last() == Some(&b'\r') {
@@ -21,6 +25,7 @@ impl CsvHeader {
         }
 
         let mut columns = Vec::new();
+        let mut names = Vec::new();
         let mut well_known_kinds = Vec::new();
         let mut pos = 0;
 
@@ -29,6 +34,7 @@ impl CsvHeader {
             let offset = unsafe { field.as_ptr().offset_from(data.as_ptr()) as u64 };
             let len = field.len() as u32;
             columns.push((offset, len));
+            names.push(field.into());
             well_known_kinds.push(well_known::classify_key(field));
             pos += 1;
         }
@@ -37,6 +43,7 @@ impl CsvHeader {
 
         Some(CsvHeader {
             columns,
+            names,
             well_known: well_known_kinds,
         })
     }
@@ -63,30 +70,30 @@ pub fn parse_csv_line(
     let mut i = 0;
     let len = line.len();
 
-    while i < len && col_idx < header.num_columns() {
+    // A separator ending the line still opens an (empty) last field.
+    let mut after_comma = false;
+    while (i < len || after_comma) && col_idx < header.num_columns() {
         let (val_start, val_end) = parse_csv_field(line, &mut i);
 
-        let (key_offset, key_len) = header.columns[col_idx];
         let field_idx = batch.fields.len() as u32;
 
-        batch.push_field(FieldRef {
-            key_offset,
-            key_len,
-            val_offset: base_offset + val_start as u64,
-            val_len: (val_end - val_start) as u32,
-        });
+        batch.push_keyed_field(
+            FieldRef {
+                key_offset: base_offset + val_start as u64,
+                key_len: 0,
+                val_offset: base_offset + val_start as u64,
+                val_len: (val_end - val_start) as u32,
+                key_id: 0,
+            },
+            &header.names[col_idx],
+        );
 
-        match header.well_known[col_idx] {
-            well_known::WellKnownKind::Timestamp => batch.set_well_known_timestamp(field_idx),
-            well_known::WellKnownKind::Level => batch.set_well_known_level(field_idx),
-            well_known::WellKnownKind::Message => batch.set_well_known_message(field_idx),
-            well_known::WellKnownKind::Component => batch.set_well_known_component(field_idx),
-            well_known::WellKnownKind::Other => {}
-        }
+        batch.set_well_known(header.well_known[col_idx], field_idx);
 
         col_idx += 1;
 
-        if i < len && line[i] == b',' {
+        after_comma = i < len && line[i] == b',';
+        if after_comma {
             i += 1;
         }
     }
@@ -137,13 +144,14 @@ pub fn parse_csv_lines_range(
     batch: &mut StructuredBatch,
 ) {
     let num_lines = line_starts.len();
+    let sep = crate::simd_scan::record_sep();
 
     for i in start_idx..end_idx {
         let line_start = line_starts[i] as usize;
         let line_end = if i + 1 < num_lines {
             let next = line_starts[i + 1] as usize;
-            if next > 0 && next <= data.len() && data[next - 1] == b'\n' {
-                if next > 1 && data[next - 2] == b'\r' {
+            if next > 0 && next <= data.len() && data[next - 1] == sep {
+                if !batch.firehose && next > 1 && data[next - 2] == b'\r' {
                     next - 2
                 } else {
                     next - 1
@@ -153,10 +161,10 @@ pub fn parse_csv_lines_range(
             }
         } else {
             let mut end = data.len();
-            if end > 0 && data[end - 1] == b'\n' {
+            if end > 0 && data[end - 1] == sep {
                 end -= 1;
             }
-            if end > 0 && data[end - 1] == b'\r' {
+            if !batch.firehose && end > 0 && data[end - 1] == b'\r' {
                 end -= 1;
             }
             end
@@ -167,7 +175,11 @@ pub fn parse_csv_lines_range(
         }
 
         let line = &data[line_start..line_end];
+        let records_before = batch.len;
         parse_csv_line(line, line_start as u64, header, batch);
+        if batch.new_record_fields(records_before) != Some(header.num_columns()) {
+            batch.mark_malformed(line_start as u64, line.len() as u32);
+        }
     }
 }
 
@@ -189,12 +201,179 @@ fn trim_csv_field(field: &[u8]) -> &[u8] {
 }
 
 pub fn header_end_offset(data: &[u8]) -> usize {
-    match memchr::memchr(b'\n', data) {
+    match memchr::memchr(crate::simd_scan::record_sep(), data) {
         Some(pos) => pos + 1,
         None => data.len(),
     }
 }
 
+/// Appends the content of a quoted cell to `out` with its doubled quotes
+/// undone.
+pub fn unquote(raw: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while let Some(at) = memchr::memchr(b'"', &raw[i..]) {
+        out.extend_from_slice(&raw[i..=i + at]);
+        i += at + 1;
+        if raw.get(i) == Some(&b'"') {
+            i += 1;
+        }
+    }
+    out.extend_from_slice(&raw[i..]);
+}
+
+/// Where a scan of CSV input stands. As in RFC 4180, a quote opens a
+/// quoted cell only at the start of a field; anywhere else it is a literal
+/// character, so a stray one cannot swallow the records after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuoteState {
+    /// At the start of a line or just past a comma.
+    FieldStart,
+    /// In an unquoted cell, or past the closing quote of a quoted one.
+    Unquoted,
+    Quoted,
+    /// Just past a quote in a quoted cell: it closes the cell unless
+    /// another quote follows.
+    QuoteSeen,
+}
+
+impl QuoteState {
+    const ALL: [QuoteState; 4] = [
+        QuoteState::FieldStart,
+        QuoteState::Unquoted,
+        QuoteState::Quoted,
+        QuoteState::QuoteSeen,
+    ];
+
+    #[inline]
+    fn step(self, b: u8, sep: u8) -> QuoteState {
+        match (self, b) {
+            (QuoteState::Quoted, b'"') => QuoteState::QuoteSeen,
+            (QuoteState::Quoted, _) => QuoteState::Quoted,
+            (QuoteState::QuoteSeen | QuoteState::FieldStart, b'"') => QuoteState::Quoted,
+            (_, b',') => QuoteState::FieldStart,
+            (_, b) if b == sep => QuoteState::FieldStart,
+            _ => QuoteState::Unquoted,
+        }
+    }
+
+    /// Runs over `data`, calling `record_end` with the offset just past each
+    /// separator outside quotes.
+    fn scan(mut self, data: &[u8], sep: u8, mut record_end: impl FnMut(usize)) -> QuoteState {
+        let mut next = 0;
+        for at in memchr::memchr3_iter(b'"', b',', sep, data) {
+            // Bytes other than the three all step alike, so one step
+            // covers a run of them.
+            if at > next {
+                self = self.step(data[next], sep);
+            }
+            let b = data[at];
+            if b == sep && self != QuoteState::Quoted {
+                record_end(at + 1);
+            }
+            self = self.step(b, sep);
+            next = at + 1;
+        }
+        if next < data.len() {
+            self = self.step(data[next], sep);
+        }
+        self
+    }
+}
+
+/// Drops the line starts in `line_starts` that fall inside a quoted cell,
+/// so an RFC 4180 cell spanning lines stays in one record. The first start
+/// must begin a record; the last, closing the range, is always kept.
+pub fn join_quoted_lines(data: &[u8], line_starts: &mut Vec<u64>) {
+    let Some(&end) = line_starts.last() else {
+        return;
+    };
+    let sep = crate::simd_scan::record_sep();
+    let mut state = QuoteState::FieldStart;
+    let mut prev = line_starts[0] as usize;
+    line_starts.retain(|&start| {
+        let start = start as usize;
+        if start > prev {
+            state = state.scan(&data[prev..start], sep, |_| {});
+            prev = start;
+        }
+        state != QuoteState::Quoted || start as u64 == end
+    });
+}
+
+/// Just past the last separator in `data` outside quotes, where `data`
+/// starts at a record start: the end of its last complete record.
+pub fn last_record_end(data: &[u8]) -> Option<usize> {
+    let mut last = None;
+    QuoteState::FieldStart.scan(data, crate::simd_scan::record_sep(), |end| last = Some(end));
+    last
+}
+
+/// Chunk boundaries about every `chunk_size` bytes, or ramping up to it
+/// under a match limit (see `chunk_len`), that never split a quoted cell.
+/// Each span between candidate offsets is scanned in parallel from every
+/// state it could start in; chaining those gives the state at each
+/// candidate, and from there the search for the next record start is
+/// local.
+pub fn record_boundaries(data: &[u8], chunk_size: usize, ramp: bool, threads: usize) -> Vec<usize> {
+    let candidates: Vec<usize> = (0..)
+        .scan(0, |pos, k| {
+            *pos += chunk_len(chunk_size, k, ramp);
+            Some(*pos)
+        })
+        .take_while(|&pos| pos < data.len())
+        .collect();
+    let sep = crate::simd_scan::record_sep();
+    let mut transitions = vec![QuoteState::ALL; candidates.len()];
+    let span = |i: usize| &data[if i == 0 { 0 } else { candidates[i - 1] }..candidates[i]];
+    let threads = threads.clamp(1, candidates.len().max(1));
+    std::thread::scope(|scope| {
+        let per_thread = candidates.len().div_ceil(threads).max(1);
+        for (t, out) in transitions.chunks_mut(per_thread).enumerate() {
+            scope.spawn(move || {
+                for (j, ends) in out.iter_mut().enumerate() {
+                    let span = span(t * per_thread + j);
+                    for end in ends.iter_mut() {
+                        *end = end.scan(span, sep, |_| {});
+                    }
+                }
+            });
+        }
+    });
+
+    let mut boundaries = vec![0];
+    let mut state = QuoteState::FieldStart;
+    for (&pos, ends) in candidates.iter().zip(&transitions) {
+        state = ends[state as usize];
+        // A cell longer than a chunk may carry the last search past `pos`.
+        if pos < *boundaries.last().unwrap() {
+            continue;
+        }
+        match next_record_start(data, pos, state, sep) {
+            Some(boundary) => boundaries.push(boundary),
+            None => break,
+        }
+    }
+    boundaries.push(data.len());
+    boundaries
+}
+
+/// The first record start after `pos`, given the scan state there.
+fn next_record_start(data: &[u8], pos: usize, state: QuoteState, sep: u8) -> Option<usize> {
+    let mut found = None;
+    let mut rest = &data[pos..];
+    let mut state = state;
+    // Scan in pieces so the search stops soon after the first record end.
+    while found.is_none() && !rest.is_empty() {
+        let (piece, tail) = rest.split_at(rest.len().min(64 * 1024));
+        let base = data.len() - rest.len();
+        state = state.scan(piece, sep, |end| {
+            found = found.or(Some(base + end));
+        });
+        rest = tail;
+    }
+    found.filter(|&start| start < data.len())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,6 +416,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_csv_correlation_slots() {
+        let data = b"hostname,pid,trace_id,span_id,message\nweb-1,4242,4bf92f35,00f067aa,ok\n";
+        let header = CsvHeader::parse(data).unwrap();
+        let data_start = header_end_offset(data);
+        let line = &data[data_start..data.len() - 1];
+
+        let mut batch = make_batch(data);
+        parse_csv_line(line, data_start as u64, &header, &mut batch);
+
+        unsafe {
+            assert_eq!(batch.host_value(0), Some("web-1"));
+            assert_eq!(batch.pid_value(0), Some("4242"));
+            assert_eq!(batch.trace_id_value(0), Some("4bf92f35"));
+            assert_eq!(batch.span_id_value(0), Some("00f067aa"));
+        }
+    }
+
     #[test]
     fn test_parse_csv_quoted_field() {
         let data = b"msg,level\n\"hello, world\",INFO\n";
@@ -287,4 +484,73 @@ mod tests {
             assert_eq!(batch.message_value(2), Some("third"));
         }
     }
+
+    #[test]
+    fn test_quoted_cells_spanning_lines() {
+        let mut data = Vec::new();
+        let mut starts = Vec::new();
+        for i in 0..200 {
+            starts.push(data.len());
+            let cell = match i % 3 {
+                0 => format!("\"line one\nline \"\"two\"\"\r\nthree {}\"", i),
+                1 => format!("\"\n{}\n\"", i),
+                _ => format!("plain {}", i),
+            };
+            data.extend_from_slice(format!("x,{},\"a,b\"\n", cell).as_bytes());
+        }
+
+        let mut line_starts = vec![0u64];
+        crate::simd_scan::scan_region(&data, 0, data.len() as u64, &mut line_starts);
+        line_starts.push(data.len() as u64);
+        join_quoted_lines(&data, &mut line_starts);
+        let mut expected: Vec<u64> = starts.iter().map(|&s| s as u64).collect();
+        expected.push(data.len() as u64);
+        assert_eq!(line_starts, expected);
+
+        for chunk_size in [1, 7, 64, 1000] {
+            for threads in [1, 3] {
+                let boundaries = record_boundaries(&data, chunk_size, false, threads);
+                assert_eq!(boundaries.first(), Some(&0));
+                assert_eq!(boundaries.last(), Some(&data.len()));
+                for b in &boundaries[1..boundaries.len() - 1] {
+                    assert!(starts.contains(b), "boundary {} splits a record", b);
+                }
+            }
+        }
+
+        let cut = starts[5] + 3;
+        assert_eq!(last_record_end(&data[..cut]), Some(starts[5]));
+        assert_eq!(last_record_end(b"a,\"open\n"), None);
+    }
+
+    #[test]
+    fn test_stray_quotes_do_not_open_cells() {
+        let mut data = b"id,msg\n".to_vec();
+        for i in 0..20_000 {
+            let msg = match i % 1000 {
+                7 => "5\" screen".to_string(),
+                8 => "\"quoted \"\"ok\"\"\"".to_string(),
+                _ => format!("row {}", i),
+            };
+            data.extend_from_slice(format!("{},{}\n", i, msg).as_bytes());
+        }
+        let result = crate::structured_orchestrator::parse_structured_mmap(
+            &data,
+            4,
+            Some(crate::format::LogFormat::Csv),
+        );
+        assert_eq!(result.total_records, 20_000);
+        assert_eq!(result.malformed_lines, 0);
+
+        let body = &data[header_end_offset(&data)..];
+        let mut line_starts = vec![0u64];
+        crate::simd_scan::scan_region(body, 0, body.len() as u64, &mut line_starts);
+        line_starts.push(body.len() as u64);
+        let lines = line_starts.len();
+        join_quoted_lines(body, &mut line_starts);
+        assert_eq!(line_starts.len(), lines);
+        assert_eq!(last_record_end(b"1,5\" screen\n2,x"), Some(12));
+        let boundaries = record_boundaries(body, 4096, false, 3);
+        assert!(boundaries.len() > 10);
+    }
 }
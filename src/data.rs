@@ -1,3 +1,5 @@
+use crate::format::LogFormat;
+use crate::simd_scan::KernelChoice;
 use std::fmt;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -27,6 +29,62 @@ impl LogLevel {
         }
     }
 
+    pub const COUNT: usize = 6;
+
+    pub const ALL: [LogLevel; LogLevel::COUNT] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+        LogLevel::Fatal,
+        LogLevel::Unknown,
+    ];
+
+    /// Case-insensitive level lookup for structured values, accepting the
+    /// common aliases (`warning`, `err`, `critical`, ...).
+    pub fn from_name(b: &[u8]) -> LogLevel {
+        let mut buf = [0u8; 16];
+        if b.is_empty() || b.len() > buf.len() {
+            return LogLevel::Unknown;
+        }
+        for (dst, src) in buf.iter_mut().zip(b) {
+            *dst = src.to_ascii_lowercase();
+        }
+        match &buf[..b.len()] {
+            b"debug" | b"trace" | b"dbg" => LogLevel::Debug,
+            b"info" | b"information" | b"notice" => LogLevel::Info,
+            b"warn" | b"warning" => LogLevel::Warn,
+            b"error" | b"err" => LogLevel::Error,
+            b"fatal" | b"critical" | b"crit" | b"panic" | b"emerg" | b"alert" => LogLevel::Fatal,
+            _ => LogLevel::Unknown,
+        }
+    }
+
+    /// Level of a syslog severity, 0 (`emerg`) to 7 (`debug`).
+    #[inline]
+    pub fn from_syslog_severity(severity: u8) -> LogLevel {
+        match severity {
+            0..=2 => LogLevel::Fatal,
+            3 => LogLevel::Error,
+            4 => LogLevel::Warn,
+            5 | 6 => LogLevel::Info,
+            7 => LogLevel::Debug,
+            _ => LogLevel::Unknown,
+        }
+    }
+
+    #[inline(always)]
+    pub fn slot(self) -> usize {
+        match self {
+            LogLevel::Debug => 0,
+            LogLevel::Info => 1,
+            LogLevel::Warn => 2,
+            LogLevel::Error => 3,
+            LogLevel::Fatal => 4,
+            LogLevel::Unknown => 5,
+        }
+    }
+
     #[inline]
     pub fn as_str(self) -> &'static str {
         match self {
@@ -46,6 +104,143 @@ impl fmt::Display for LogLevel {
     }
 }
 
+/// Per-level record counts, kept for every batch so level filters can skip
+/// whole chunks and level breakdowns never have to revisit records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelSummary {
+    pub counts: [u64; LogLevel::COUNT],
+}
+
+impl LevelSummary {
+    pub fn from_levels(levels: &[LogLevel]) -> Self {
+        let mut summary = LevelSummary::default();
+        for &level in levels {
+            summary.record(level);
+        }
+        summary
+    }
+
+    #[inline(always)]
+    pub fn record(&mut self, level: LogLevel) {
+        self.counts[level.slot()] += 1;
+    }
+
+    #[inline]
+    pub fn count(&self, level: LogLevel) -> u64 {
+        self.counts[level.slot()]
+    }
+
+    pub fn merge(&mut self, other: &LevelSummary) {
+        for (dst, src) in self.counts.iter_mut().zip(other.counts) {
+            *dst += src;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl fmt::Display for LevelSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for level in LogLevel::ALL {
+            let count = self.count(level);
+            if count > 0 {
+                writeln!(
+                    f,
+                    "  {:<8}         {:>10}           ",
+                    level.as_str(),
+                    count
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Timestamp bounds of a run of records in file order. `max_regression` is
+/// how far the most out-of-order record fell behind the newest timestamp
+/// before it; zero for time-sorted data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub min: u64,
+    pub max: u64,
+    pub max_regression: u64,
+}
+
+impl Default for TimeRange {
+    fn default() -> Self {
+        TimeRange {
+            min: u64::MAX,
+            max: 0,
+            max_regression: 0,
+        }
+    }
+}
+
+impl TimeRange {
+    /// Zero timestamps mark lines that did not parse and are skipped.
+    pub fn from_timestamps(timestamps: &[u64]) -> Self {
+        let mut range = TimeRange::default();
+        for &ts in timestamps {
+            if ts != 0 {
+                range.record(ts);
+            }
+        }
+        range
+    }
+
+    #[inline(always)]
+    pub fn record(&mut self, ts: u64) {
+        self.max_regression = self.max_regression.max(self.max.saturating_sub(ts));
+        self.min = self.min.min(ts);
+        self.max = self.max.max(ts);
+    }
+
+    /// Appends a range that follows `self` in the input.
+    pub fn merge(&mut self, later: &TimeRange) {
+        if later.is_empty() {
+            return;
+        }
+        self.max_regression = self
+            .max_regression
+            .max(later.max_regression)
+            .max(self.max.saturating_sub(later.min));
+        self.min = self.min.min(later.min);
+        self.max = self.max.max(later.max);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    pub fn span_secs(&self) -> u64 {
+        if self.is_empty() {
+            0
+        } else {
+            self.max - self.min
+        }
+    }
+}
+
+impl fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  Time span:       {:>10} s         ", self.span_secs())?;
+        writeln!(
+            f,
+            "  Max regression:  {:>10} s         ",
+            self.max_regression
+        )
+    }
+}
+
+/// Byte extent of a raw input line, relative to its batch's `data_ptr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSpan {
+    pub offset: u64,
+    pub len: u32,
+}
+
 #[repr(C, align(64))]
 pub struct LogBatch {
     pub timestamps: Vec<u64>,
@@ -60,9 +255,224 @@ pub struct LogBatch {
 
     pub message_lens: Vec<u32>,
 
+    pub level_summary: LevelSummary,
+
+    pub time_range: TimeRange,
+
+    pub line_offsets: Vec<u64>,
+
+    pub line_lens: Vec<u32>,
+
+    /// Lines that did not match the expected layout; they are still parsed
+    /// into best-effort records.
+    pub malformed: Vec<LineSpan>,
+
+    /// Where `data_ptr` sits in the orchestrator's input (file offset for
+    /// top-level parses).
+    pub input_offset: u64,
+
     pub data_ptr: *const u8,
 
+    /// Length of the buffer behind `data_ptr`, when the batch owner set it.
+    pub data_len: usize,
+
     pub len: usize,
+
+    /// Position of the input among those of the run.
+    pub file_id: u32,
+
+    /// Parsed from a transcoded or ANSI-stripped copy of the input, so
+    /// record offsets do not point into the file.
+    pub rewritten: bool,
+
+    /// Position, in parse order, of the chunk the batch came from.
+    pub chunk_seq: u64,
+
+    /// Line each record starts on, when parsed with
+    /// [`MatchControl::line_numbers`](crate::filter::MatchControl::line_numbers).
+    pub line_numbers: Vec<u64>,
+}
+
+/// Where a record came from, so anything derived from it can be linked
+/// back to its exact source bytes. `line_no` is 1-based and only known
+/// when lines were numbered; the byte extent is unknown for rewritten
+/// batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordProvenance {
+    pub file_id: u32,
+    pub byte_offset: Option<u64>,
+    pub byte_len: Option<u32>,
+    pub line_no: Option<u64>,
+}
+
+/// Record separators in `data`.
+pub fn count_lines(data: &[u8]) -> u64 {
+    memchr::memchr_iter(crate::simd_scan::record_sep(), data).count() as u64
+}
+
+/// Line each of the ascending `offsets` into `data` starts on, counting
+/// from `first_line` at `data[start]`.
+pub fn number_lines(data: &[u8], start: usize, first_line: u64, offsets: &[u64]) -> Vec<u64> {
+    let mut line = first_line;
+    let mut pos = start;
+    offsets
+        .iter()
+        .map(|&offset| {
+            let offset = (offset as usize).clamp(pos, data.len());
+            line += count_lines(&data[pos..offset]);
+            pos = offset;
+            line
+        })
+        .collect()
+}
+
+/// Common per-record view shared by plain and structured batches, used by the
+/// filtering and early-termination machinery.
+pub trait BatchRecords {
+    fn record_count(&self) -> usize;
+
+    fn record_level(&self, i: usize) -> LogLevel;
+
+    fn record_timestamp(&self, i: usize) -> Option<u64>;
+
+    fn record_component(&self, i: usize) -> Option<&[u8]>;
+
+    fn record_line(&self, i: usize) -> LineSpan;
+
+    fn malformed_lines(&self) -> &[LineSpan];
+
+    /// Whether record `i` was parsed from a line also flagged malformed.
+    #[inline]
+    fn is_malformed(&self, i: usize) -> bool {
+        let malformed = self.malformed_lines();
+        !malformed.is_empty() && {
+            let offset = self.record_line(i).offset;
+            malformed
+                .binary_search_by_key(&offset, |span| span.offset)
+                .is_ok()
+        }
+    }
+
+    fn input_offset(&self) -> u64;
+
+    fn data_ptr(&self) -> *const u8;
+
+    fn data_len(&self) -> usize;
+
+    fn line_bytes(&self, span: LineSpan) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(self.data_ptr().add(span.offset as usize), span.len as usize)
+        }
+    }
+
+    /// The record's source line including a trailing `\r` that the parser
+    /// left out of its extent, so CRLF input round-trips byte for byte.
+    fn record_raw(&self, i: usize) -> &[u8] {
+        let mut span = self.record_line(i);
+        let end = span.offset as usize + span.len as usize;
+        if end < self.data_len() && unsafe { *self.data_ptr().add(end) } == b'\r' {
+            span.len += 1;
+        }
+        self.line_bytes(span)
+    }
+
+    fn level_summary(&self) -> &LevelSummary;
+
+    fn file_id(&self) -> u32;
+
+    /// Whether the batch was parsed from a copy of the input whose offsets
+    /// do not point into the file.
+    fn rewritten(&self) -> bool;
+
+    fn chunk_seq(&self) -> u64;
+
+    fn line_no(&self, i: usize) -> Option<u64>;
+
+    fn provenance(&self, i: usize) -> RecordProvenance {
+        let span = (!self.rewritten()).then(|| self.record_line(i));
+        RecordProvenance {
+            file_id: self.file_id(),
+            byte_offset: span.map(|span| self.input_offset() + span.offset),
+            byte_len: span.map(|span| span.len),
+            line_no: self.line_no(i),
+        }
+    }
+}
+
+impl BatchRecords for LogBatch {
+    #[inline]
+    fn record_count(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn record_level(&self, i: usize) -> LogLevel {
+        self.levels[i]
+    }
+
+    #[inline]
+    fn record_timestamp(&self, i: usize) -> Option<u64> {
+        Some(self.timestamps[i]).filter(|&ts| ts != 0)
+    }
+
+    #[inline]
+    fn record_component(&self, i: usize) -> Option<&[u8]> {
+        let component = unsafe { self.component(i) };
+        (!component.is_empty()).then_some(component.as_bytes())
+    }
+
+    #[inline]
+    fn record_line(&self, i: usize) -> LineSpan {
+        LineSpan {
+            offset: self.line_offsets[i],
+            len: self.line_lens[i],
+        }
+    }
+
+    #[inline]
+    fn malformed_lines(&self) -> &[LineSpan] {
+        &self.malformed
+    }
+
+    #[inline]
+    fn input_offset(&self) -> u64 {
+        self.input_offset
+    }
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        self.data_len
+    }
+
+    #[inline]
+    fn data_ptr(&self) -> *const u8 {
+        self.data_ptr
+    }
+
+    #[inline]
+    fn level_summary(&self) -> &LevelSummary {
+        &self.level_summary
+    }
+
+    #[inline]
+    fn file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    #[inline]
+    fn rewritten(&self) -> bool {
+        self.rewritten
+    }
+
+    #[inline]
+    fn chunk_seq(&self) -> u64 {
+        self.chunk_seq
+    }
+
+    #[inline]
+    fn line_no(&self, i: usize) -> Option<u64> {
+        self.line_numbers.get(i).copied()
+    }
 }
 
 unsafe impl Send for LogBatch {}
@@ -77,8 +487,19 @@ impl LogBatch {
             component_lens: vec![0u32; capacity],
             message_offsets: vec![0u64; capacity],
             message_lens: vec![0u32; capacity],
+            line_offsets: vec![0u64; capacity],
+            line_lens: vec![0u32; capacity],
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed: Vec::new(),
+            input_offset: 0,
             data_ptr,
+            data_len: 0,
             len: capacity,
+            file_id: 0,
+            rewritten: false,
+            chunk_seq: 0,
+            line_numbers: Vec::new(),
         }
     }
 
@@ -111,14 +532,70 @@ impl LogBatch {
     }
 }
 
+/// Process-wide page faults from `getrusage`, all threads included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageFaults {
+    pub minor: u64,
+    pub major: u64,
+}
+
+impl PageFaults {
+    pub fn current() -> PageFaults {
+        #[cfg(unix)]
+        unsafe {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            if libc::getrusage(libc::RUSAGE_SELF, &mut usage) == 0 {
+                return PageFaults {
+                    minor: usage.ru_minflt as u64,
+                    major: usage.ru_majflt as u64,
+                };
+            }
+        }
+        PageFaults::default()
+    }
+
+    /// Faults taken since `start` was sampled.
+    pub fn since(self, start: PageFaults) -> PageFaults {
+        PageFaults {
+            minor: self.minor.saturating_sub(start.minor),
+            major: self.major.saturating_sub(start.major),
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.minor + self.major
+    }
+}
+
+/// Input bytes `start..end` of a chunk left out of the result because its
+/// parse panicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailedChunk {
+    pub start: u64,
+    pub end: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct ParseStats {
     pub total_bytes: u64,
     pub total_lines: u64,
     pub scan_time_ms: f64,
     pub parse_time_ms: f64,
+    /// Time spent serializing matches for export, summed over threads.
+    pub serialize_time_ms: f64,
+    /// Time the sinks spent writing while this input was parsed.
+    pub sink_time_ms: f64,
     pub total_time_ms: f64,
     pub threads_used: usize,
+    /// Kernel the newline scans ran with (`--simd`).
+    pub simd: KernelChoice,
+    pub levels: LevelSummary,
+    pub time_range: TimeRange,
+    pub malformed_lines: u64,
+    /// Bytes of `total_bytes` skipped as NUL holes.
+    pub hole_bytes: u64,
+    pub failed_chunks: Vec<FailedChunk>,
+    pub page_faults: PageFaults,
 }
 
 impl ParseStats {
@@ -154,8 +631,46 @@ impl fmt::Display for ParseStats {
             "  Total bytes:     {:>10.2} GB        ",
             self.total_bytes as f64 / (1024.0 * 1024.0 * 1024.0)
         )?;
+        if self.hole_bytes > 0 {
+            writeln!(
+                f,
+                "    └─ holes:      {:>10.2} GB        ",
+                self.hole_bytes as f64 / (1024.0 * 1024.0 * 1024.0)
+            )?;
+            writeln!(
+                f,
+                "    └─ data:       {:>10.2} GB        ",
+                (self.total_bytes - self.hole_bytes) as f64 / (1024.0 * 1024.0 * 1024.0)
+            )?;
+        }
         writeln!(f, "  Total lines:     {:>10}           ", self.total_lines)?;
         writeln!(f, "  Threads used:    {:>10}           ", self.threads_used)?;
+        writeln!(f, "  SIMD kernel:     {:>10}", self.simd.to_string())?;
+        if self.levels.total() > 0 {
+            writeln!(f, "╠══════════════════════════════════════╣")?;
+            write!(f, "{}", self.levels)?;
+        }
+        if self.malformed_lines > 0 {
+            writeln!(
+                f,
+                "  Malformed lines: {:>10}           ",
+                self.malformed_lines
+            )?;
+        }
+        if !self.failed_chunks.is_empty() {
+            writeln!(
+                f,
+                "  Failed chunks:   {:>10}           ",
+                self.failed_chunks.len()
+            )?;
+            for chunk in &self.failed_chunks {
+                writeln!(f, "    └─ bytes {}..{}", chunk.start, chunk.end)?;
+            }
+        }
+        if !self.time_range.is_empty() {
+            writeln!(f, "╠══════════════════════════════════════╣")?;
+            write!(f, "{}", self.time_range)?;
+        }
         writeln!(f, "╠══════════════════════════════════════╣")?;
         writeln!(
             f,
@@ -177,6 +692,18 @@ impl fmt::Display for ParseStats {
             "    └─ throughput: {:>8.2} GB/s       ",
             self.parse_throughput_gbps()
         )?;
+        if self.serialize_time_ms > 0.0 || self.sink_time_ms > 0.0 {
+            writeln!(
+                f,
+                "  Serialize:       {:>8.1} ms         ",
+                self.serialize_time_ms
+            )?;
+            writeln!(
+                f,
+                "  Sink:            {:>8.1} ms         ",
+                self.sink_time_ms
+            )?;
+        }
         writeln!(f, "╠══════════════════════════════════════╣")?;
         writeln!(
             f,
@@ -188,14 +715,107 @@ impl fmt::Display for ParseStats {
             "     Throughput:   {:>8.2} GB/s       ",
             self.throughput_gbps()
         )?;
+        if self.page_faults.total() > 0 {
+            writeln!(
+                f,
+                "  Page faults:     {:>10}           ",
+                self.page_faults.total()
+            )?;
+            writeln!(
+                f,
+                "    └─ major:      {:>10}           ",
+                self.page_faults.major
+            )?;
+        }
         writeln!(f, "╚══════════════════════════════════════╝")?;
         Ok(())
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FormatTotals {
+    pub inputs: u64,
+    pub records: u64,
+    pub bytes: u64,
+    pub parse_time_ms: f64,
+    pub total_time_ms: f64,
+}
+
+/// Records, bytes and time per input format, in first-seen order, so mixed
+/// runs show which format dominates cost.
+#[derive(Debug, Clone, Default)]
+pub struct FormatBreakdown {
+    pub formats: Vec<(LogFormat, FormatTotals)>,
+}
+
+impl FormatBreakdown {
+    pub fn record(
+        &mut self,
+        format: LogFormat,
+        bytes: u64,
+        records: u64,
+        parse_time_ms: f64,
+        total_time_ms: f64,
+    ) {
+        let totals = match self.formats.iter().position(|(f, _)| *f == format) {
+            Some(k) => &mut self.formats[k].1,
+            None => {
+                self.formats.push((format, FormatTotals::default()));
+                &mut self.formats.last_mut().unwrap().1
+            }
+        };
+        totals.inputs += 1;
+        totals.records += records;
+        totals.bytes += bytes;
+        totals.parse_time_ms += parse_time_ms;
+        totals.total_time_ms += total_time_ms;
+    }
+
+    pub fn total_time_ms(&self) -> f64 {
+        self.formats.iter().map(|(_, t)| t.total_time_ms).sum()
+    }
+}
+
+impl fmt::Display for FormatBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total_ms = self.total_time_ms();
+        writeln!(
+            f,
+            "  {:<10} {:>6} {:>12} {:>10} {:>10} {:>9} {:>6}",
+            "Format", "Inputs", "Records", "MB", "Parse ms", "GB/s", "Time"
+        )?;
+        for (format, t) in &self.formats {
+            let secs = t.total_time_ms / 1000.0;
+            let gbps = if secs > 0.0 {
+                t.bytes as f64 / (1024.0 * 1024.0 * 1024.0) / secs
+            } else {
+                0.0
+            };
+            let share = if total_ms > 0.0 {
+                t.total_time_ms / total_ms * 100.0
+            } else {
+                0.0
+            };
+            writeln!(
+                f,
+                "  {:<10} {:>6} {:>12} {:>10.1} {:>10.1} {:>9.2} {:>5.1}%",
+                format.as_str(),
+                t.inputs,
+                t.records,
+                t.bytes as f64 / (1024.0 * 1024.0),
+                t.parse_time_ms,
+                gbps,
+                share
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::simd_scan::{Kernel, KernelPick};
 
     #[test]
     fn test_log_level_from_bytes() {
@@ -208,6 +828,50 @@ mod tests {
         assert_eq!(LogLevel::from_bytes(b"TRACE"), LogLevel::Unknown);
     }
 
+    #[test]
+    fn test_log_level_from_name() {
+        assert_eq!(LogLevel::from_name(b"info"), LogLevel::Info);
+        assert_eq!(LogLevel::from_name(b"WARNING"), LogLevel::Warn);
+        assert_eq!(LogLevel::from_name(b"Err"), LogLevel::Error);
+        assert_eq!(LogLevel::from_name(b"critical"), LogLevel::Fatal);
+        assert_eq!(LogLevel::from_name(b"trace"), LogLevel::Debug);
+        assert_eq!(LogLevel::from_name(b"verbose"), LogLevel::Unknown);
+        assert_eq!(LogLevel::from_name(b""), LogLevel::Unknown);
+    }
+
+    #[test]
+    fn test_level_summary() {
+        let levels = [
+            LogLevel::Info,
+            LogLevel::Info,
+            LogLevel::Error,
+            LogLevel::Unknown,
+        ];
+        let mut summary = LevelSummary::from_levels(&levels);
+        assert_eq!(summary.count(LogLevel::Info), 2);
+        assert_eq!(summary.count(LogLevel::Error), 1);
+        assert_eq!(summary.count(LogLevel::Fatal), 0);
+
+        summary.merge(&LevelSummary::from_levels(&[LogLevel::Fatal]));
+        assert_eq!(summary.total(), 5);
+        assert!(format!("{}", summary).contains("Fatal"));
+    }
+
+    #[test]
+    fn test_time_range_tracks_regression() {
+        let mut range = TimeRange::from_timestamps(&[100, 0, 105, 102, 110]);
+        assert_eq!((range.min, range.max), (100, 110));
+        assert_eq!(range.max_regression, 3);
+
+        range.merge(&TimeRange::from_timestamps(&[104, 120]));
+        assert_eq!((range.min, range.max), (100, 120));
+        assert_eq!(range.max_regression, 6);
+
+        range.merge(&TimeRange::default());
+        assert_eq!(range.span_secs(), 20);
+        assert!(TimeRange::default().is_empty());
+    }
+
     #[test]
     fn test_log_batch_creation() {
         let data = [0u8; 100];
@@ -224,11 +888,53 @@ mod tests {
             total_lines: 4_000_000,
             scan_time_ms: 200.0,
             parse_time_ms: 300.0,
+            serialize_time_ms: 120.0,
+            sink_time_ms: 0.0,
             total_time_ms: 500.0,
             threads_used: 8,
+            simd: KernelChoice {
+                kernel: Kernel::Avx2,
+                pick: KernelPick::Calibrated,
+            },
+            levels: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed_lines: 0,
+            hole_bytes: 0,
+            failed_chunks: vec![FailedChunk {
+                start: 4096,
+                end: 8192,
+            }],
+            page_faults: PageFaults {
+                minor: 262_144,
+                major: 12,
+            },
         };
         assert!((stats.throughput_gbps() - 2.0).abs() < 0.01);
         let display = format!("{}", stats);
         assert!(display.contains("PANDORA'S LOGS"));
+        assert!(display.contains("Page faults:         262156"));
+        assert!(display.contains("Failed chunks:            1"));
+        assert!(display.contains("bytes 4096..8192"));
+        assert!(display.contains("Serialize:          120.0 ms"));
+        assert!(display.contains("SIMD kernel:     avx2 (calibrated)"));
+    }
+
+    #[test]
+    fn test_format_breakdown_accumulates_per_format() {
+        let mut breakdown = FormatBreakdown::default();
+        breakdown.record(LogFormat::Json, 1000, 10, 2.0, 4.0);
+        breakdown.record(LogFormat::PlainText, 500, 20, 1.0, 1.0);
+        breakdown.record(LogFormat::Json, 3000, 30, 3.0, 5.0);
+
+        assert_eq!(breakdown.formats.len(), 2);
+        let (format, json) = breakdown.formats[0];
+        assert_eq!(format, LogFormat::Json);
+        assert_eq!((json.inputs, json.records, json.bytes), (2, 40, 4000));
+        assert_eq!(json.total_time_ms, 9.0);
+        assert_eq!(breakdown.total_time_ms(), 10.0);
+
+        let text = breakdown.to_string();
+        assert!(text.contains(" 90.0%"), "{}", text);
+        assert!(text.contains(" 10.0%"), "{}", text);
     }
 }
@@ -0,0 +1,310 @@
+//! Decompresses gzip and zstd input while streaming. Rotated logs are often
+//! compressed one file at a time and concatenated afterwards, so a stream is
+//! read as a sequence of members, gzip or zstd each, decoded back to back;
+//! where each one starts is kept for reporting.
+
+use flate2::bufread::GzDecoder;
+use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+use ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+use std::io::{self, BufRead, BufReader, Read};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression of the member `head` starts with, if any. Skippable
+    /// zstd frames count as zstd.
+    pub fn detect(head: &[u8]) -> Option<Compression> {
+        if head.starts_with(&GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if head.starts_with(&ZSTD_MAGIC) || is_skippable_frame(head) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+/// Skippable zstd frames carry metadata; their magic is 0x184D2A5?.
+fn is_skippable_frame(head: &[u8]) -> bool {
+    head.len() >= 4 && head[0] & 0xf0 == 0x50 && head[1..4] == [0x2a, 0x4d, 0x18]
+}
+
+/// Where a member starts, in the compressed input and in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Member {
+    pub compression: Compression,
+    pub compressed_offset: u64,
+    pub offset: u64,
+}
+
+/// A reader that counts the bytes taken from it.
+struct Counted<R> {
+    inner: BufReader<R>,
+    taken: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.taken += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for Counted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.taken += amt as u64;
+    }
+}
+
+enum Stage<R> {
+    Between(Counted<R>),
+    Gzip(Box<GzDecoder<Counted<R>>>),
+    Zstd(Counted<R>),
+    Plain(Counted<R>),
+    Done,
+}
+
+/// Decodes every member of a compressed stream in turn; a stream that does
+/// not start with one is passed through as is. Decoding stops at the first
+/// error or at data after a member that starts no member, which then reads
+/// as the end of the input; [`error`](Decompressor::error) and
+/// [`trailing`](Decompressor::trailing) tell the two apart from a clean end.
+pub struct Decompressor<R> {
+    stage: Stage<R>,
+    frame: FrameDecoder,
+    out: u64,
+    members: Vec<Member>,
+    trailing: Option<u64>,
+    error: Option<io::Error>,
+}
+
+impl<R: Read> Decompressor<R> {
+    pub fn new(inner: R) -> Decompressor<R> {
+        Decompressor {
+            stage: Stage::Between(Counted {
+                inner: BufReader::with_capacity(256 * 1024, inner),
+                taken: 0,
+            }),
+            frame: FrameDecoder::new(),
+            out: 0,
+            members: Vec::new(),
+            trailing: None,
+            error: None,
+        }
+    }
+}
+
+impl<R> Decompressor<R> {
+    /// Members started so far, in stream order.
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    /// Compressed offset of data after the last member that starts no
+    /// member of its own.
+    pub fn trailing(&self) -> Option<u64> {
+        self.trailing
+    }
+
+    /// What stopped decoding early, e.g. a truncated member.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Decoded bytes so far.
+    pub fn offset(&self) -> u64 {
+        self.out
+    }
+}
+
+impl<R: Read> Decompressor<R> {
+    fn step(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match std::mem::replace(&mut self.stage, Stage::Done) {
+                Stage::Done => return Ok(0),
+                Stage::Between(mut source) => {
+                    let head = source.fill_buf()?;
+                    let Some(compression) = Compression::detect(head) else {
+                        if self.members.is_empty() {
+                            self.stage = Stage::Plain(source);
+                        } else if !head.is_empty() {
+                            self.trailing = Some(source.taken);
+                        }
+                        continue;
+                    };
+                    let member = Member {
+                        compression,
+                        compressed_offset: source.taken,
+                        offset: self.out,
+                    };
+                    if compression == Compression::Gzip {
+                        self.members.push(member);
+                        self.stage = Stage::Gzip(Box::new(GzDecoder::new(source)));
+                        continue;
+                    }
+                    match self.frame.reset(&mut source) {
+                        Ok(()) => {
+                            self.members.push(member);
+                            self.stage = Stage::Zstd(source);
+                        }
+                        Err(FrameDecoderError::ReadFrameHeaderError(
+                            ReadFrameHeaderError::SkipFrame { length, .. },
+                        )) => {
+                            let skipped =
+                                io::copy(&mut (&mut source).take(length as u64), &mut io::sink())?;
+                            if skipped < length as u64 {
+                                return Err(io::ErrorKind::UnexpectedEof.into());
+                            }
+                            self.stage = Stage::Between(source);
+                        }
+                        Err(e) => return Err(io::Error::other(e)),
+                    }
+                }
+                Stage::Gzip(mut decoder) => {
+                    let n = decoder.read(buf)?;
+                    if n > 0 {
+                        self.stage = Stage::Gzip(decoder);
+                        return Ok(n);
+                    }
+                    self.stage = Stage::Between(decoder.into_inner());
+                }
+                Stage::Zstd(mut source) => {
+                    while self.frame.can_collect() < buf.len() && !self.frame.is_finished() {
+                        let wanted = buf.len() - self.frame.can_collect();
+                        self.frame
+                            .decode_blocks(&mut source, BlockDecodingStrategy::UptoBytes(wanted))
+                            .map_err(io::Error::other)?;
+                    }
+                    let n = self.frame.read(buf)?;
+                    if n > 0 {
+                        self.stage = Stage::Zstd(source);
+                        return Ok(n);
+                    }
+                    self.stage = Stage::Between(source);
+                }
+                Stage::Plain(mut source) => {
+                    let n = source.read(buf)?;
+                    self.stage = Stage::Plain(source);
+                    return Ok(n);
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.step(buf) {
+            Ok(n) => {
+                self.out += n as u64;
+                Ok(n)
+            }
+            Err(e) => {
+                self.stage = Stage::Done;
+                self.error = Some(e);
+                Ok(0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zstd(data: &[u8]) -> Vec<u8> {
+        ruzstd::encoding::compress_to_vec(data, ruzstd::encoding::CompressionLevel::Fastest)
+    }
+
+    #[test]
+    fn test_decompressor_reads_every_member() {
+        let first = b"{\"n\":1}\n".repeat(5000);
+        let second = b"{\"n\":2}\n".repeat(3);
+        let third = b"{\"n\":3}\n".repeat(700);
+        let mut stream = gzip(&first);
+        let second_at = stream.len() as u64;
+        stream.extend(zstd(&second));
+        // A skippable frame holds no output.
+        stream.extend([0x50, 0x2a, 0x4d, 0x18, 2, 0, 0, 0, 0xaa, 0xbb]);
+        let third_at = stream.len() as u64;
+        stream.extend(gzip(&third));
+
+        assert_eq!(Compression::detect(&stream), Some(Compression::Gzip));
+        let mut decoder = Decompressor::new(&stream[..]);
+        let mut out = Vec::new();
+        decoder.read_to_end(&mut out).unwrap();
+        assert_eq!(out, [&first[..], &second, &third].concat());
+        let starts: Vec<_> = decoder
+            .members()
+            .iter()
+            .map(|m| (m.compression, m.compressed_offset, m.offset))
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                (Compression::Gzip, 0, 0),
+                (Compression::Zstd, second_at, first.len() as u64),
+                (
+                    Compression::Gzip,
+                    third_at,
+                    (first.len() + second.len()) as u64
+                ),
+            ]
+        );
+        assert!(decoder.error().is_none() && decoder.trailing().is_none());
+
+        // Trailing junk and a truncated member end the input early, saying so.
+        let mut junk = gzip(&second);
+        let junk_at = junk.len() as u64;
+        junk.extend(b"tail");
+        let mut decoder = Decompressor::new(&junk[..]);
+        out.clear();
+        decoder.read_to_end(&mut out).unwrap();
+        assert_eq!(out, second);
+        assert_eq!(decoder.trailing(), Some(junk_at));
+
+        // Uncompressed input passes through.
+        let mut decoder = Decompressor::new(&first[..]);
+        out.clear();
+        decoder.read_to_end(&mut out).unwrap();
+        assert_eq!(out, first);
+        assert!(decoder.members().is_empty());
+
+        let truncated = gzip(&first);
+        let mut decoder = Decompressor::new(&truncated[..truncated.len() / 2]);
+        out.clear();
+        decoder.read_to_end(&mut out).unwrap();
+        assert!(out.len() < first.len() && first.starts_with(&out));
+        assert!(decoder.error().is_some());
+    }
+}
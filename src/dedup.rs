@@ -0,0 +1,252 @@
+use crate::data::BatchRecords;
+use crate::expr::{Expr, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Locations kept per duplicate group; the count keeps going past this.
+const MAX_LOCATIONS: usize = 8;
+
+const PRIME_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+
+/// Fast non-cryptographic 64-bit hash over a raw line. Lines are grouped by
+/// (hash, length), so collisions would also need equal lengths to merge.
+#[inline]
+pub fn hash_line(line: &[u8]) -> u64 {
+    let mut h = PRIME_1 ^ (line.len() as u64).wrapping_mul(PRIME_2);
+    let mut words = line.chunks_exact(8);
+    for word in &mut words {
+        let k = u64::from_le_bytes(word.try_into().unwrap());
+        h = (h ^ k
+            .wrapping_mul(PRIME_2)
+            .rotate_left(31)
+            .wrapping_mul(PRIME_1))
+        .rotate_left(27)
+        .wrapping_mul(PRIME_1);
+    }
+    let mut tail = [0u8; 8];
+    let rest = words.remainder();
+    tail[..rest.len()].copy_from_slice(rest);
+    h ^= u64::from_le_bytes(tail).wrapping_mul(PRIME_1);
+    fmix64(h)
+}
+
+#[inline]
+pub fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    h ^ (h >> 33)
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    count: u64,
+    /// Smallest input offsets seen, kept sorted.
+    locations: Vec<u64>,
+    /// Copy of the line, taken once the group has a second member.
+    text: Option<Box<[u8]>>,
+}
+
+impl Group {
+    fn add(&mut self, offset: u64) {
+        self.count += 1;
+        let at = self.locations.partition_point(|&o| o < offset);
+        if at < MAX_LOCATIONS {
+            self.locations.insert(at, offset);
+            self.locations.truncate(MAX_LOCATIONS);
+        }
+    }
+
+    fn merge(&mut self, other: Group) {
+        self.count += other.count;
+        for offset in other.locations {
+            let at = self.locations.partition_point(|&o| o < offset);
+            if at < MAX_LOCATIONS {
+                self.locations.insert(at, offset);
+            }
+        }
+        self.locations.truncate(MAX_LOCATIONS);
+    }
+}
+
+/// Collects exact-duplicate lines across batches. Safe to feed from the
+/// pipeline's batch callback: each batch is grouped locally and merged under
+/// one lock.
+#[derive(Default)]
+pub struct DuplicateFinder {
+    groups: Mutex<HashMap<(u64, u32), Group>>,
+}
+
+impl DuplicateFinder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_records<B: BatchRecords>(&self, batch: &B, records: &[u32]) {
+        let mut local: HashMap<(u64, u32), (Group, &[u8])> = HashMap::new();
+        for &i in records {
+            let span = batch.record_line(i as usize);
+            if span.len == 0 {
+                continue;
+            }
+            let line = batch.line_bytes(span);
+            local
+                .entry((hash_line(line), span.len))
+                .or_insert_with(|| (Group::default(), line))
+                .0
+                .add(batch.input_offset() + span.offset);
+        }
+
+        let mut groups = self.groups.lock().unwrap();
+        for (key, (group, line)) in local {
+            let entry = groups.entry(key).or_default();
+            entry.merge(group);
+            if entry.count > 1 && entry.text.is_none() {
+                entry.text = Some(line.into());
+            }
+        }
+    }
+
+    pub fn report(&self) -> DuplicateReport {
+        let groups = self.groups.lock().unwrap();
+        let unique_lines = groups.len() as u64;
+        let lines = groups.values().map(|g| g.count).sum();
+        let mut duplicates: Vec<Duplicate> = groups
+            .values()
+            .filter(|g| g.count > 1)
+            .map(|g| Duplicate {
+                count: g.count,
+                locations: g.locations.clone(),
+                line: g.text.as_deref().unwrap_or_default().to_vec(),
+            })
+            .collect();
+        duplicates.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.locations.first().cmp(&b.locations.first()))
+        });
+        DuplicateReport {
+            lines,
+            unique_lines,
+            duplicates,
+            filtered: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub count: u64,
+    /// Input byte offsets of the first few copies, ascending.
+    pub locations: Vec<u64>,
+    pub line: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct DuplicateReport {
+    pub lines: u64,
+    pub unique_lines: u64,
+    /// Groups with more than one copy, most repeated first.
+    pub duplicates: Vec<Duplicate>,
+    /// Groups dropped by [`DuplicateReport::retain`].
+    pub filtered: usize,
+}
+
+impl DuplicateReport {
+    /// Lines that repeat an earlier line.
+    pub fn redundant_lines(&self) -> u64 {
+        self.lines - self.unique_lines
+    }
+
+    /// Keeps the groups for which `having` holds; it sees their `count`.
+    pub fn retain(&mut self, having: &Expr) {
+        let before = self.duplicates.len();
+        self.duplicates.retain(|dup| {
+            having
+                .eval(&|name| (name == b"count").then_some(Value::Num(dup.count as f64)))
+                .truthy()
+        });
+        self.filtered += before - self.duplicates.len();
+    }
+}
+
+impl fmt::Display for DuplicateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Duplicates: {} distinct lines repeated, {} redundant of {} lines",
+            self.duplicates.len(),
+            self.redundant_lines(),
+            self.lines
+        )?;
+        if self.filtered > 0 {
+            write!(f, " ({} more fail --having)", self.filtered)?;
+        }
+        writeln!(f)?;
+        for dup in self.duplicates.iter().take(20) {
+            let line = String::from_utf8_lossy(&dup.line);
+            let shown: String = line.chars().take(100).collect();
+            let offsets: Vec<String> = dup.locations.iter().map(u64::to_string).collect();
+            let more = if dup.count > dup.locations.len() as u64 {
+                ", ..."
+            } else {
+                ""
+            };
+            writeln!(f, "  {:>8}x  {}", dup.count, shown)?;
+            writeln!(f, "            at bytes {}{}", offsets.join(", "), more)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_hash_line_distinguishes_tails() {
+        assert_eq!(hash_line(b"hello world"), hash_line(b"hello world"));
+        assert_ne!(hash_line(b"hello world"), hash_line(b"hello worle"));
+        assert_ne!(hash_line(b"abcdefgh"), hash_line(b"abcdefgh\0"));
+        assert_ne!(hash_line(b""), hash_line(b"\0"));
+    }
+
+    #[test]
+    fn test_duplicate_finder_counts_and_locations() {
+        let data = b"a=1\nb=2\na=1\nc=3\na=1\nb=2\n";
+        let finder = DuplicateFinder::new();
+        let add = |batch: &StructuredBatch, records: &[u32]| finder.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data, 1, Some(LogFormat::Logfmt), &control);
+
+        let report = finder.report();
+        assert_eq!(report.lines, 6);
+        assert_eq!(report.unique_lines, 3);
+        assert_eq!(report.redundant_lines(), 3);
+        assert_eq!(
+            report.duplicates,
+            vec![
+                Duplicate {
+                    count: 3,
+                    locations: vec![0, 8, 16],
+                    line: b"a=1".to_vec(),
+                },
+                Duplicate {
+                    count: 2,
+                    locations: vec![4, 20],
+                    line: b"b=2".to_vec(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::emit::write_json_string;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Severity of the tool's own diagnostics, most severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warn => "warn",
+            Severity::Info => "info",
+            Severity::Debug => "debug",
+        }
+    }
+
+    pub fn more_verbose(self) -> Severity {
+        Severity::from_u8(self as u8 + 1)
+    }
+
+    fn from_u8(n: u8) -> Severity {
+        match n {
+            0 => Severity::Error,
+            1 => Severity::Warn,
+            2 => Severity::Info,
+            _ => Severity::Debug,
+        }
+    }
+}
+
+static MAX_SEVERITY: AtomicU8 = AtomicU8::new(Severity::Warn as u8);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Most verbose severity that is still written; `--quiet` lowers it to
+/// errors, each `-v` raises it one step.
+pub fn set_max_severity(severity: Severity) {
+    MAX_SEVERITY.store(severity as u8, Ordering::Relaxed);
+}
+
+pub fn max_severity() -> Severity {
+    Severity::from_u8(MAX_SEVERITY.load(Ordering::Relaxed))
+}
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn enabled(severity: Severity) -> bool {
+    severity as u8 <= MAX_SEVERITY.load(Ordering::Relaxed)
+}
+
+/// One diagnostic line: `warn: <message>`, or
+/// `{"level":"warn","message":"..."}` in JSON mode.
+pub fn render(severity: Severity, message: &str, json: bool) -> Vec<u8> {
+    let mut line = Vec::with_capacity(message.len() + 32);
+    if json {
+        line.extend_from_slice(b"{\"level\":\"");
+        line.extend_from_slice(severity.as_str().as_bytes());
+        line.extend_from_slice(b"\",\"message\":");
+        write_json_string(message.as_bytes(), &mut line);
+        line.push(b'}');
+    } else {
+        line.extend_from_slice(severity.as_str().as_bytes());
+        line.extend_from_slice(b": ");
+        line.extend_from_slice(message.as_bytes());
+    }
+    line.push(b'\n');
+    line
+}
+
+/// Writes a diagnostic to stderr if `severity` is enabled. Diagnostics
+/// always go to stderr so they never mix with data on stdout.
+pub fn log(severity: Severity, args: std::fmt::Arguments<'_>) {
+    if !enabled(severity) {
+        return;
+    }
+    let line = render(severity, &args.to_string(), JSON.load(Ordering::Relaxed));
+    let _ = std::io::stderr().lock().write_all(&line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_text_and_json() {
+        assert_eq!(
+            render(Severity::Warn, "Unknown format 'x'", false),
+            b"warn: Unknown format 'x'\n"
+        );
+        assert_eq!(
+            render(Severity::Error, "bad \"path\"", true),
+            b"{\"level\":\"error\",\"message\":\"bad \\\"path\\\"\"}\n"
+        );
+        assert!(Severity::Error < Severity::Debug);
+    }
+}
@@ -0,0 +1,600 @@
+use crate::data::{LevelSummary, LogLevel, TimeRange};
+use crate::diag::{self, Severity};
+use crate::fds;
+use crate::format::LogFormat;
+use crate::orchestrator::parse_logs_pipelined;
+use crate::structured_orchestrator::parse_structured_mmap;
+use memmap2::Mmap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// Coordinator <-> `pandoras-logs worker` protocol, over one TCP connection
+// per worker. Both sides first send `MAGIC`; after that every message is a
+// frame: u32 little-endian payload length, then the payload. Integers in
+// payloads are LEB128 varints, strings a varint length and the bytes.
+//
+//   request   1 path start end             parse the lines starting in
+//                                          [start, end) of path
+//   response  0 bytes records malformed
+//               level-count counts... min max max_regression
+//           | 1 message                    the range could not be parsed
+//
+// Workers read the file themselves, so it must be at the same path on
+// every machine (a shared mount); only summaries cross the network.
+
+const MAGIC: &[u8; 4] = b"PDW1";
+
+/// Largest frame either side accepts.
+const MAX_FRAME: u32 = 1 << 20;
+
+const REQUEST_PARSE: u8 = 1;
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_ERR: u8 = 1;
+
+/// Bytes per range handed to a worker.
+pub const RANGE_BYTES: u64 = 256 * 1024 * 1024;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What parsing a range produced; ranges merge in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RangeSummary {
+    pub bytes: u64,
+    pub records: u64,
+    pub malformed: u64,
+    pub levels: LevelSummary,
+    pub time_range: TimeRange,
+}
+
+impl RangeSummary {
+    pub fn merge(&mut self, later: &RangeSummary) {
+        self.bytes += later.bytes;
+        self.records += later.records;
+        self.malformed += later.malformed;
+        self.levels.merge(&later.levels);
+        self.time_range.merge(&later.time_range);
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn bad_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads a payload back in the order it was written.
+struct Payload<'a>(&'a [u8]);
+
+impl Payload<'_> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let (&b, rest) = self
+            .0
+            .split_first()
+            .ok_or_else(|| bad_data("short frame"))?;
+        self.0 = rest;
+        Ok(b)
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b < 0x80 {
+                return Ok(v);
+            }
+        }
+        Err(bad_data("varint too long"))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.varint()? as usize;
+        if len > self.0.len() {
+            return Err(bad_data("short frame"));
+        }
+        let (s, rest) = self.0.split_at(len);
+        self.0 = rest;
+        String::from_utf8(s.to_vec()).map_err(|_| bad_data("string is not utf-8"))
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, s: &str) {
+    put_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_frame(out: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    out.write_all(&(payload.len() as u32).to_le_bytes())?;
+    out.write_all(payload)?;
+    out.flush()
+}
+
+/// `None` on a clean end of stream between frames.
+fn read_frame(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len);
+    if len > MAX_FRAME {
+        return Err(bad_data("frame too large"));
+    }
+    let mut payload = vec![0u8; len as usize];
+    input.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    stream.write_all(MAGIC)?;
+    let mut magic = [0u8; 4];
+    stream.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(bad_data("peer is not a pandora worker"));
+    }
+    Ok(())
+}
+
+fn encode_summary(summary: &RangeSummary) -> Vec<u8> {
+    let mut out = vec![RESPONSE_OK];
+    for v in [summary.bytes, summary.records, summary.malformed] {
+        put_varint(&mut out, v);
+    }
+    put_varint(&mut out, LogLevel::COUNT as u64);
+    for level in LogLevel::ALL {
+        put_varint(&mut out, summary.levels.count(level));
+    }
+    let range = summary.time_range;
+    for v in [range.min, range.max, range.max_regression] {
+        put_varint(&mut out, v);
+    }
+    out
+}
+
+fn decode_response(payload: &[u8]) -> io::Result<Result<RangeSummary, String>> {
+    let mut p = Payload(payload);
+    match p.byte()? {
+        RESPONSE_OK => {
+            let mut summary = RangeSummary {
+                bytes: p.varint()?,
+                records: p.varint()?,
+                malformed: p.varint()?,
+                ..Default::default()
+            };
+            // Levels this build does not know are dropped, not misfiled.
+            let levels = p.varint()? as usize;
+            for slot in 0..levels {
+                let count = p.varint()?;
+                if let Some(level) = LogLevel::ALL.get(slot) {
+                    summary.levels.counts[level.slot()] = count;
+                }
+            }
+            summary.time_range = TimeRange {
+                min: p.varint()?,
+                max: p.varint()?,
+                max_regression: p.varint()?,
+            };
+            Ok(Ok(summary))
+        }
+        RESPONSE_ERR => Ok(Err(p.string()?)),
+        _ => Err(bad_data("unknown response")),
+    }
+}
+
+/// Byte range of the lines that start in `[start, end)`, so adjacent
+/// ranges split a file into whole lines with none lost or repeated.
+pub fn line_range(data: &[u8], start: u64, end: u64) -> (usize, usize) {
+    let after_line = |pos: u64| -> usize {
+        let pos = pos as usize;
+        if pos == 0 {
+            return 0;
+        }
+        if pos >= data.len() {
+            return data.len();
+        }
+        memchr::memchr(b'\n', &data[pos - 1..]).map_or(data.len(), |nl| pos + nl)
+    };
+    let (begin, stop) = (after_line(start), after_line(end));
+    (begin, stop.max(begin))
+}
+
+/// Parses the lines starting in `[start, end)` of `path`. The format is
+/// detected from the head of the file, so every range agrees on it.
+pub fn parse_range(path: &Path, start: u64, end: u64, threads: usize) -> io::Result<RangeSummary> {
+    let file = File::open(path)?;
+    let data = unsafe { Mmap::map(&file)? };
+    let format = LogFormat::detect(&data[..data.len().min(4096)]);
+    if format == LogFormat::Csv {
+        return Err(io::Error::other("csv input cannot be split into ranges"));
+    }
+    let (begin, stop) = line_range(&data, start, end);
+    let range = &data[begin..stop];
+    Ok(match format {
+        LogFormat::PlainText => {
+            let result = parse_logs_pipelined(range, threads);
+            RangeSummary {
+                bytes: range.len() as u64,
+                records: result.total_lines as u64,
+                malformed: result.malformed_lines,
+                levels: result.level_summary,
+                time_range: result.time_range,
+            }
+        }
+        _ => {
+            let result = parse_structured_mmap(range, threads, Some(format));
+            RangeSummary {
+                bytes: range.len() as u64,
+                records: result.total_records as u64,
+                malformed: result.malformed_lines,
+                levels: result.level_summary,
+                time_range: result.time_range,
+            }
+        }
+    })
+}
+
+/// File descriptors a worker connection holds: the stream, its clone for
+/// reading, and the file being parsed.
+pub const FDS_PER_CONNECTION: usize = 3;
+
+/// Wait after an accept that failed for want of descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// `pandoras-logs worker`: answers parse requests for files under `root`,
+/// one thread per coordinator connection, until killed. Past
+/// `max_connections` at once, further coordinators wait in the listen
+/// backlog until one disconnects.
+pub fn serve(
+    listener: TcpListener,
+    root: &Path,
+    threads: usize,
+    max_connections: usize,
+) -> io::Result<()> {
+    let root = std::fs::canonicalize(root)?;
+    let active = (Mutex::new(0usize), Condvar::new());
+    std::thread::scope(|scope| {
+        loop {
+            {
+                let (count, freed) = &active;
+                let mut count = count.lock().unwrap();
+                while *count >= max_connections.max(1) {
+                    count = freed.wait(count).unwrap();
+                }
+            }
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if fds::is_exhausted(&e) => {
+                    diag::log(
+                        Severity::Warn,
+                        format_args!("Worker accept failed: {}", fds::explain(e)),
+                    );
+                    std::thread::sleep(ACCEPT_BACKOFF);
+                    continue;
+                }
+                Err(e) => {
+                    diag::log(Severity::Warn, format_args!("Worker accept failed: {}", e));
+                    continue;
+                }
+            };
+            *active.0.lock().unwrap() += 1;
+            let (root, active) = (&root, &active);
+            scope.spawn(move || {
+                let peer = stream
+                    .peer_addr()
+                    .map(|a| a.to_string())
+                    .unwrap_or_default();
+                if let Err(e) = serve_connection(stream, root, threads) {
+                    diag::log(
+                        Severity::Warn,
+                        format_args!("Connection from {} failed: {}", peer, fds::explain(e)),
+                    );
+                }
+                *active.0.lock().unwrap() -= 1;
+                active.1.notify_one();
+            });
+        }
+    })
+}
+
+fn serve_connection(mut stream: TcpStream, root: &Path, threads: usize) -> io::Result<()> {
+    handshake(&mut stream)?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut output = BufWriter::new(stream);
+    while let Some(frame) = read_frame(&mut input)? {
+        let mut p = Payload(&frame);
+        if p.byte()? != REQUEST_PARSE {
+            return Err(bad_data("unknown request"));
+        }
+        let (path, start, end) = (p.string()?, p.varint()?, p.varint()?);
+        let result = std::fs::canonicalize(&path).and_then(|path| {
+            if !path.starts_with(root) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("'{}' is outside the worker root", path.display()),
+                ));
+            }
+            diag::log(
+                Severity::Info,
+                format_args!("Parsing bytes {}..{} of '{}'", start, end, path.display()),
+            );
+            parse_range(&path, start, end, threads)
+        });
+        let response = match result {
+            Ok(summary) => encode_summary(&summary),
+            Err(e) => {
+                let mut out = vec![RESPONSE_ERR];
+                put_string(&mut out, &e.to_string());
+                out
+            }
+        };
+        write_frame(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+/// One coordinator connection to a worker.
+struct WorkerConn {
+    input: BufReader<TcpStream>,
+    output: BufWriter<TcpStream>,
+}
+
+impl WorkerConn {
+    fn connect(addr: &str) -> io::Result<WorkerConn> {
+        let addr = std::net::ToSocketAddrs::to_socket_addrs(addr)?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address did not resolve"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        handshake(&mut stream)?;
+        Ok(WorkerConn {
+            input: BufReader::new(stream.try_clone()?),
+            output: BufWriter::new(stream),
+        })
+    }
+
+    /// The outer error means the connection is unusable; the inner one
+    /// that the worker could not parse the range.
+    fn parse(
+        &mut self,
+        path: &str,
+        start: u64,
+        end: u64,
+    ) -> io::Result<Result<RangeSummary, String>> {
+        let mut request = vec![REQUEST_PARSE];
+        put_string(&mut request, path);
+        put_varint(&mut request, start);
+        put_varint(&mut request, end);
+        write_frame(&mut self.output, &request)?;
+        let frame = read_frame(&mut self.input)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "worker closed the connection")
+        })?;
+        decode_response(&frame)
+    }
+}
+
+/// Result of [`distribute`]: the merged summary and how many ranges the
+/// coordinator had to parse itself because no worker could.
+#[derive(Debug, Default)]
+pub struct Distributed {
+    pub summary: RangeSummary,
+    pub workers: usize,
+    pub ranges: usize,
+    pub local_ranges: usize,
+    pub total_time_ms: f64,
+}
+
+impl Distributed {
+    pub fn throughput_gbps(&self) -> f64 {
+        if self.total_time_ms <= 0.0 {
+            return 0.0;
+        }
+        (self.summary.bytes as f64 / (1024.0 * 1024.0 * 1024.0)) / (self.total_time_ms / 1000.0)
+    }
+}
+
+impl fmt::Display for Distributed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let summary = &self.summary;
+        writeln!(f, "╔══════════════════════════════════════════╗")?;
+        writeln!(f, "   PANDORA'S LOGS — DISTRIBUTED PARSE      ")?;
+        writeln!(f, "╠══════════════════════════════════════════╣")?;
+        writeln!(
+            f,
+            "  Total bytes:   {:>10.2} GB              ",
+            summary.bytes as f64 / (1024.0 * 1024.0 * 1024.0)
+        )?;
+        writeln!(
+            f,
+            "  Total records: {:>10}                 ",
+            summary.records
+        )?;
+        writeln!(f, "  Workers:       {:>10}                 ", self.workers)?;
+        writeln!(
+            f,
+            "  Ranges:        {:>10} ({} local)       ",
+            self.ranges, self.local_ranges
+        )?;
+        if summary.levels.total() > 0 {
+            writeln!(f, "╠══════════════════════════════════════════╣")?;
+            write!(f, "{}", summary.levels)?;
+        }
+        if summary.malformed > 0 {
+            writeln!(
+                f,
+                "  Malformed:     {:>10}                 ",
+                summary.malformed
+            )?;
+        }
+        if !summary.time_range.is_empty() {
+            writeln!(f, "╠══════════════════════════════════════════╣")?;
+            write!(f, "{}", summary.time_range)?;
+        }
+        writeln!(f, "╠══════════════════════════════════════════╣")?;
+        writeln!(
+            f,
+            "  Total time:    {:>8.1} ms               ",
+            self.total_time_ms
+        )?;
+        writeln!(
+            f,
+            "  Throughput:    {:>8.2} GB/s             ",
+            self.throughput_gbps()
+        )?;
+        writeln!(f, "╚══════════════════════════════════════════╝")?;
+        Ok(())
+    }
+}
+
+/// Splits `path` into `range_bytes` ranges and spreads them over `workers`.
+/// A worker that fails is dropped and its range goes back on the queue;
+/// ranges no worker could take are parsed locally.
+pub fn distribute(
+    path: &Path,
+    workers: &[String],
+    range_bytes: u64,
+    threads: usize,
+) -> io::Result<Distributed> {
+    let started = Instant::now();
+    let path = std::fs::canonicalize(path)?;
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not utf-8"))?;
+    let len = std::fs::metadata(&path)?.len();
+    let ranges: Vec<(u64, u64)> = (0..len.div_ceil(range_bytes.max(1)))
+        .map(|k| (k * range_bytes, ((k + 1) * range_bytes).min(len)))
+        .collect();
+    let queue = Mutex::new((0..ranges.len()).collect::<VecDeque<usize>>());
+    let results: Mutex<Vec<Option<RangeSummary>>> = Mutex::new(vec![None; ranges.len()]);
+
+    std::thread::scope(|scope| {
+        for addr in workers {
+            let (queue, results, ranges) = (&queue, &results, &ranges);
+            scope.spawn(move || {
+                let mut conn = match WorkerConn::connect(addr) {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        diag::log(
+                            Severity::Warn,
+                            format_args!("Worker {} unavailable: {}", addr, e),
+                        );
+                        return;
+                    }
+                };
+                loop {
+                    let Some(k) = queue.lock().unwrap().pop_front() else {
+                        return;
+                    };
+                    let (start, end) = ranges[k];
+                    match conn.parse(path_str, start, end) {
+                        Ok(Ok(summary)) => results.lock().unwrap()[k] = Some(summary),
+                        Ok(Err(message)) => {
+                            diag::log(
+                                Severity::Warn,
+                                format_args!(
+                                    "Worker {} could not parse range {}: {}",
+                                    addr, k, message
+                                ),
+                            );
+                            queue.lock().unwrap().push_back(k);
+                            return;
+                        }
+                        Err(e) => {
+                            diag::log(
+                                Severity::Warn,
+                                format_args!("Worker {} failed: {}", addr, e),
+                            );
+                            queue.lock().unwrap().push_back(k);
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let mut done = Distributed {
+        workers: workers.len(),
+        ranges: ranges.len(),
+        ..Default::default()
+    };
+    for (k, result) in results.into_inner().unwrap().into_iter().enumerate() {
+        let summary = match result {
+            Some(summary) => summary,
+            None => {
+                done.local_ranges += 1;
+                parse_range(&path, ranges[k].0, ranges[k].1, threads)?
+            }
+        };
+        done.summary.merge(&summary);
+    }
+    done.total_time_ms = started.elapsed().as_secs_f64() * 1000.0;
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_over_workers_match_local_parse() {
+        let dir = std::env::temp_dir().join(format!("pandora-dist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let mut data = String::new();
+        for i in 0..2000 {
+            let level = ["INFO", "WARN", "ERROR"][i % 3];
+            data += &format!(
+                "2025-02-12T10:{:02}:{:02}Z {} api request {} done\n",
+                i / 60 % 60,
+                i % 60,
+                level,
+                i
+            );
+        }
+        std::fs::write(&path, &data).unwrap();
+
+        assert_eq!(line_range(b"ab\ncd\nef\n", 0, 4), (0, 6));
+        assert_eq!(line_range(b"ab\ncd\nef\n", 4, 6), (6, 6));
+        assert_eq!(line_range(b"ab\ncd\nef\n", 6, 9), (6, 9));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let good = listener.local_addr().unwrap().to_string();
+        let root = dir.clone();
+        std::thread::spawn(move || serve(listener, &root, 1, 4));
+        let dead = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dead_addr = dead.local_addr().unwrap().to_string();
+        drop(dead);
+
+        let local = parse_range(&path, 0, data.len() as u64, 1).unwrap();
+        assert_eq!(local.records, 2000);
+        let done = distribute(&path, &[good, dead_addr], 7_001, 1).unwrap();
+        assert_eq!(done.ranges, data.len().div_ceil(7_001));
+        assert_eq!(done.local_ranges, 0);
+        assert_eq!(done.summary, local);
+
+        // A worker refuses files outside its root; the coordinator then
+        // parses the ranges itself.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let fenced = listener.local_addr().unwrap().to_string();
+        let elsewhere = dir.join("elsewhere");
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::thread::spawn(move || serve(listener, &elsewhere, 1, 4));
+        let done = distribute(&path, &[fenced], 50_000, 1).unwrap();
+        assert_eq!(done.local_ranges, done.ranges);
+        assert_eq!(done.summary, local);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1615 @@
+use crate::data::{BatchRecords, LevelSummary, LogBatch, RecordProvenance};
+use crate::expr::{Derivation, derive_fields};
+use crate::format::LogFormat;
+use crate::manifest::Xxh64;
+use crate::numbers::{self, NumberStyle};
+use crate::structured::StructuredBatch;
+use crate::syslog::Pri;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A serialized run of records from one batch (NDJSON, or the untouched
+/// source lines for `EmitFormat::RawFiltered`), shared by every sink it is
+/// fanned out to.
+#[derive(Debug, Default)]
+pub struct EmitChunk {
+    pub ndjson: Vec<u8>,
+    pub records: u64,
+    pub levels: LevelSummary,
+    /// Output partition every record in the chunk belongs to, when splitting.
+    pub partition: Option<Vec<u8>>,
+    /// Set when `EmitRules::collect_stats` asks for it.
+    pub stats: Option<ChunkStats>,
+}
+
+/// Time range and output fields of a run of records, for
+/// `--dataset-manifest`. Fields are in first-seen order, each with the
+/// number of records that have it; other emit formats than NDJSON only
+/// gather the time range.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkStats {
+    pub time_range: Option<(u64, u64)>,
+    pub fields: Vec<(Vec<u8>, u64)>,
+}
+
+impl ChunkStats {
+    pub fn add_time(&mut self, ts: u64) {
+        self.time_range = Some(match self.time_range {
+            Some((first, last)) => (first.min(ts), last.max(ts)),
+            None => (ts, ts),
+        });
+    }
+
+    pub fn add_field(&mut self, name: &[u8], records: u64) {
+        match self.fields.iter_mut().find(|(field, _)| field == name) {
+            Some((_, n)) => *n += records,
+            None => self.fields.push((name.to_vec(), records)),
+        }
+    }
+
+    pub fn merge(&mut self, other: &ChunkStats) {
+        if let Some((first, last)) = other.time_range {
+            self.add_time(first);
+            self.add_time(last);
+        }
+        for (name, records) in &other.fields {
+            self.add_field(name, *records);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldValue<'a> {
+    /// Unescaped text.
+    Text(&'a [u8]),
+    /// String content that is already JSON-escaped in the source.
+    Escaped(&'a [u8]),
+    /// A JSON number, boolean or null.
+    Literal(&'a [u8]),
+    Timestamp(u64),
+}
+
+pub trait EmitRecord: BatchRecords {
+    fn for_each_field(&self, i: usize, f: &mut dyn FnMut(&[u8], FieldValue<'_>));
+
+    /// Calls `f` with the first field of record `i` named `name`, if any.
+    fn visit_field(&self, i: usize, name: &[u8], f: &mut dyn FnMut(FieldValue<'_>)) {
+        let mut found = false;
+        self.for_each_field(i, &mut |key, value| {
+            if !found && key == name {
+                found = true;
+                f(value);
+            }
+        });
+    }
+
+    /// Like `visit_field`, but a name starting with `/` is a JSON Pointer:
+    /// its first token names the field and the rest is looked up in that
+    /// field's raw JSON value, without flattening the record.
+    fn visit_path(&self, i: usize, path: &[u8], f: &mut dyn FnMut(FieldValue<'_>)) {
+        let Some((name, rest)) = crate::pointer::first_token(path) else {
+            return self.visit_field(i, path, f);
+        };
+        self.visit_field(i, &name, &mut |value| match value {
+            _ if rest.is_empty() => f(value),
+            FieldValue::Text(raw) | FieldValue::Literal(raw) => {
+                if let Some(inner) = crate::pointer::resolve(raw, rest) {
+                    f(inner);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    /// [`schema_id`](crate::schemas::schema_id) of record `i`'s keys.
+    fn schema_id(&self, i: usize) -> u64 {
+        let mut keys = Vec::new();
+        self.for_each_field(i, &mut |key, _| keys.push(key.to_vec()));
+        crate::schemas::schema_id(keys.iter().map(Vec::as_slice))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Int,
+    Float,
+    Bool,
+    Timestamp,
+    String,
+}
+
+impl ValueKind {
+    pub fn parse(name: &str) -> Option<ValueKind> {
+        match name {
+            "int" | "integer" => Some(ValueKind::Int),
+            "float" | "double" => Some(ValueKind::Float),
+            "bool" | "boolean" => Some(ValueKind::Bool),
+            "timestamp" | "time" => Some(ValueKind::Timestamp),
+            "string" | "str" => Some(ValueKind::String),
+            _ => None,
+        }
+    }
+}
+
+/// A declared output type. Values that fail to coerce are written as `null`
+/// and counted in `failures`.
+#[derive(Debug)]
+pub struct FieldType {
+    pub field: Vec<u8>,
+    pub kind: ValueKind,
+    pub failures: AtomicU64,
+}
+
+/// How `--record-id` derives a record's ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordIdKind {
+    /// `<input head hash>-<byte offset>`: the same bytes of the same input
+    /// get the same ID on every run, also after rotation renames the file.
+    Offset,
+    /// A hash of the record's bytes; identical records share an ID.
+    Hash,
+}
+
+/// A stable ID written to `field` of every exported record, so exporting
+/// the same input again overwrites instead of duplicating downstream.
+/// `source` is the hash of the current input's head, for `Offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordId {
+    pub field: Vec<u8>,
+    pub kind: RecordIdKind,
+    pub source: u64,
+}
+
+impl RecordId {
+    /// Parses `field`, `field:offset` or `field:hash`.
+    pub fn parse(spec: &str) -> Result<RecordId, String> {
+        let (field, kind) = match spec.rsplit_once(':') {
+            Some((field, "offset")) => (field, RecordIdKind::Offset),
+            Some((field, "hash")) => (field, RecordIdKind::Hash),
+            Some((_, kind)) => return Err(format!("unknown ID kind '{}'", kind)),
+            None => (spec, RecordIdKind::Offset),
+        };
+        if field.is_empty() {
+            return Err("expected a field name".to_string());
+        }
+        Ok(RecordId {
+            field: field.as_bytes().to_vec(),
+            kind,
+            source: 0,
+        })
+    }
+
+    fn write<B: EmitRecord>(&self, batch: &B, i: usize, out: &mut Vec<u8>) {
+        let id = match self.kind {
+            RecordIdKind::Offset => format!(
+                "{:016x}-{}",
+                self.source,
+                batch.input_offset() + batch.record_line(i).offset
+            ),
+            RecordIdKind::Hash => format!("{:016x}", Xxh64::oneshot(batch.record_raw(i))),
+        };
+        write_json_string(id.as_bytes(), out);
+    }
+}
+
+/// A naming convention for exported fields: well-known fields and a few
+/// common HTTP and service ones are renamed to their Elastic Common Schema
+/// or OpenTelemetry log attribute names, so output lines up with existing
+/// dashboards. Other fields keep their source names, as do fields whose
+/// schema name another field of the record already has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schema {
+    Ecs,
+    Otel,
+}
+
+impl Schema {
+    pub fn parse(name: &str) -> Option<Schema> {
+        match name {
+            "ecs" => Some(Schema::Ecs),
+            "otel" | "opentelemetry" => Some(Schema::Otel),
+            _ => None,
+        }
+    }
+
+    /// The schema's name for a source field, if it has one.
+    pub fn field_name(self, key: &[u8]) -> Option<&'static [u8]> {
+        use crate::structured::well_known::{WellKnownKind, classify_key};
+        let ecs = self == Schema::Ecs;
+        let name: &'static [u8] = match classify_key(key) {
+            WellKnownKind::Timestamp if ecs => b"@timestamp",
+            WellKnownKind::Timestamp => b"timestamp",
+            WellKnownKind::Level if ecs => b"log.level",
+            WellKnownKind::Level => b"severity_text",
+            WellKnownKind::Message if ecs => b"message",
+            WellKnownKind::Message => b"body",
+            WellKnownKind::Component if ecs => b"log.logger",
+            WellKnownKind::Component => b"scope.name",
+            WellKnownKind::Host => b"host.name",
+            WellKnownKind::Pid => b"process.pid",
+            WellKnownKind::TraceId if ecs => b"trace.id",
+            WellKnownKind::TraceId => b"trace_id",
+            WellKnownKind::SpanId if ecs => b"span.id",
+            WellKnownKind::SpanId => b"span_id",
+            WellKnownKind::Other => match key {
+                b"status" | b"status_code" | b"http_status" => b"http.response.status_code",
+                b"method" | b"http_method" => b"http.request.method",
+                b"path" | b"uri" => b"url.path",
+                b"url" if ecs => b"url.original",
+                b"url" => b"url.full",
+                b"user_agent" | b"ua" => b"user_agent.original",
+                b"client_ip" | b"remote_addr" if ecs => b"client.ip",
+                b"client_ip" | b"remote_addr" => b"client.address",
+                b"service" | b"service_name" => b"service.name",
+                b"error" | b"err" if ecs => b"error.message",
+                b"error" | b"err" => b"exception.message",
+                _ => return None,
+            },
+        };
+        Some(name)
+    }
+
+    /// Every name [`field_name`](Schema::field_name) gives out.
+    fn names(self) -> &'static [&'static [u8]] {
+        match self {
+            Schema::Ecs => &[
+                b"@timestamp",
+                b"log.level",
+                b"message",
+                b"log.logger",
+                b"host.name",
+                b"process.pid",
+                b"trace.id",
+                b"span.id",
+                b"http.response.status_code",
+                b"http.request.method",
+                b"url.path",
+                b"url.original",
+                b"user_agent.original",
+                b"client.ip",
+                b"service.name",
+                b"error.message",
+            ],
+            Schema::Otel => &[
+                b"timestamp",
+                b"severity_text",
+                b"body",
+                b"scope.name",
+                b"host.name",
+                b"process.pid",
+                b"trace_id",
+                b"span_id",
+                b"http.response.status_code",
+                b"http.request.method",
+                b"url.path",
+                b"url.full",
+                b"user_agent.original",
+                b"client.address",
+                b"service.name",
+                b"exception.message",
+            ],
+        }
+    }
+}
+
+/// What a [`Schema`] makes of each source key, worked out once per chunk
+/// instead of per record, and the schema names one record already uses.
+/// A name goes to the first field mapping to it, unless the record has a
+/// field of that name itself; other fields keep their source names, so no
+/// key is written twice.
+#[derive(Debug, Default)]
+struct SchemaNames {
+    keys: HashMap<Vec<u8>, KeyNames>,
+    taken: Vec<&'static [u8]>,
+}
+
+/// A source key's schema name, and the key itself when it is one.
+#[derive(Debug, Clone, Copy)]
+struct KeyNames {
+    renamed: Option<&'static [u8]>,
+    own: Option<&'static [u8]>,
+}
+
+impl SchemaNames {
+    fn lookup(&mut self, schema: Schema, key: &[u8]) -> KeyNames {
+        if let Some(&names) = self.keys.get(key) {
+            return names;
+        }
+        let own = schema.names().iter().copied().find(|name| *name == key);
+        let names = KeyNames {
+            renamed: schema.field_name(key),
+            own,
+        };
+        self.keys.insert(key.to_vec(), names);
+        names
+    }
+
+    /// Starts record `i`, reserving the schema names its keys already have.
+    fn start_record<B: EmitRecord>(
+        &mut self,
+        schema: Schema,
+        batch: &B,
+        i: usize,
+        rules: &EmitRules,
+    ) {
+        self.taken.clear();
+        batch.for_each_field(i, &mut |key, _| {
+            if rules.renames.iter().all(|(from, _)| from != key)
+                && rules.derive.iter().all(|d| d.name != key)
+                && let Some(own) = self.lookup(schema, key).own
+            {
+                self.taken.push(own);
+            }
+        });
+    }
+
+    /// `key`'s schema name, if no other field of the record has it.
+    fn rename(&mut self, schema: Schema, key: &[u8]) -> Option<&'static [u8]> {
+        let name = self
+            .lookup(schema, key)
+            .renamed
+            .filter(|&name| name != key)?;
+        if self.taken.contains(&name) {
+            return None;
+        }
+        self.taken.push(name);
+        Some(name)
+    }
+}
+
+/// Export-time rewrites: `renames` maps source keys to output keys,
+/// `inject` adds static fields unless the record already has that key,
+/// `types` coerces values by output key and `derive` appends computed fields
+/// (replacing a source field of the same name). `source_fields` are added
+/// like `inject` but describe the current input file, e.g. its pod.
+/// `record_id` replaces any source field of its name, as does `provenance`,
+/// the name of an object linking the record to its source bytes, and
+/// `schema_id`, the field for the ID of the record's key set. `schema`
+/// names the fields `renames` leaves alone.
+#[derive(Debug, Default)]
+pub struct EmitRules {
+    pub renames: Vec<(Vec<u8>, Vec<u8>)>,
+    pub inject: Vec<(Vec<u8>, Vec<u8>)>,
+    pub source_fields: Vec<(Vec<u8>, Vec<u8>)>,
+    pub types: Vec<FieldType>,
+    pub derive: Vec<Derivation>,
+    pub record_id: Option<RecordId>,
+    pub provenance: Option<Vec<u8>>,
+    pub schema_id: Option<Vec<u8>>,
+    pub schema: Option<Schema>,
+    /// Gather `ChunkStats` into every chunk.
+    pub collect_stats: bool,
+}
+
+impl EmitRules {
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+            && self.inject.is_empty()
+            && self.types.is_empty()
+            && self.derive.is_empty()
+            && self.record_id.is_none()
+            && self.provenance.is_none()
+            && self.schema_id.is_none()
+            && self.schema.is_none()
+    }
+
+    pub fn set_record_id(&mut self, spec: &str) -> Result<(), String> {
+        self.record_id = Some(RecordId::parse(spec)?);
+        Ok(())
+    }
+
+    pub fn set_provenance(&mut self, field: &str) -> Result<(), String> {
+        if field.is_empty() {
+            return Err("expected a field name".to_string());
+        }
+        self.provenance = Some(field.as_bytes().to_vec());
+        Ok(())
+    }
+
+    pub fn set_schema_id(&mut self, field: &str) -> Result<(), String> {
+        if field.is_empty() {
+            return Err("expected a field name".to_string());
+        }
+        self.schema_id = Some(field.as_bytes().to_vec());
+        Ok(())
+    }
+
+    pub fn set_schema(&mut self, name: &str) -> Result<(), String> {
+        self.schema = Some(
+            Schema::parse(name).ok_or_else(|| format!("expected ecs or otel, got '{}'", name))?,
+        );
+        Ok(())
+    }
+
+    /// Parses a `name=expression` derived field.
+    pub fn add_derive(&mut self, rule: &str) -> Result<(), String> {
+        self.derive.push(Derivation::parse(rule)?);
+        Ok(())
+    }
+
+    /// Parses a comma-separated `field:kind` list.
+    pub fn add_types(&mut self, list: &str) -> Result<(), String> {
+        for decl in list.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (field, kind) = decl
+                .rsplit_once(':')
+                .ok_or_else(|| format!("expected field:type, got '{}'", decl))?;
+            let kind = ValueKind::parse(kind).ok_or_else(|| format!("unknown type '{}'", kind))?;
+            self.types.push(FieldType {
+                field: field.as_bytes().to_vec(),
+                kind,
+                failures: AtomicU64::new(0),
+            });
+        }
+        Ok(())
+    }
+
+    /// Fields with at least one failed coercion.
+    pub fn coercion_failures(&self) -> impl Iterator<Item = (&[u8], u64)> {
+        self.types
+            .iter()
+            .map(|t| (t.field.as_slice(), t.failures.load(Ordering::Relaxed)))
+            .filter(|&(_, n)| n > 0)
+    }
+
+    /// Parses a `from=to` rename.
+    pub fn add_rename(&mut self, rule: &str) -> Result<(), String> {
+        let (from, to) = split_rule(rule)?;
+        self.renames.push((from, to));
+        Ok(())
+    }
+
+    /// Parses a `key=value` static field.
+    pub fn add_inject(&mut self, rule: &str) -> Result<(), String> {
+        let (key, value) = split_rule(rule)?;
+        self.inject.push((key, value));
+        Ok(())
+    }
+
+    /// Rules file: one `rename from=to`, `set key=value`, `type field:kind`,
+    /// `derive name=expr`, `id <field>`, `provenance <field>`,
+    /// `schema-id <field>` or `schema ecs|otel` per line;
+    /// blank lines and `#` comments are ignored.
+    pub fn load(&mut self, text: &str) -> Result<(), String> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once(char::is_whitespace) {
+                Some(("rename", rule)) => self.add_rename(rule.trim()),
+                Some(("set", rule)) => self.add_inject(rule.trim()),
+                Some(("type", rule)) => self.add_types(rule.trim()),
+                Some(("derive", rule)) => self.add_derive(rule.trim()),
+                Some(("id", spec)) => self.set_record_id(spec.trim()),
+                Some(("provenance", field)) => self.set_provenance(field.trim()),
+                Some(("schema-id", field)) => self.set_schema_id(field.trim()),
+                Some(("schema", name)) => self.set_schema(name.trim()),
+                _ => Err(format!(
+                    "expected 'rename', 'set', 'type', 'derive', 'id', 'provenance', \
+                     'schema-id' or 'schema', got '{}'",
+                    line
+                )),
+            };
+            result.map_err(|e| format!("line {}: {}", n + 1, e))?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn output_key<'k>(&'k self, key: &'k [u8], names: &mut SchemaNames) -> &'k [u8] {
+        match self.renames.iter().find(|(from, _)| from == key) {
+            Some((_, to)) => to,
+            None => self
+                .schema
+                .and_then(|schema| names.rename(schema, key))
+                .unwrap_or(key),
+        }
+    }
+}
+
+fn split_rule(rule: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    match rule.split_once('=') {
+        Some((key, value)) if !key.is_empty() => {
+            Ok((key.as_bytes().to_vec(), value.as_bytes().to_vec()))
+        }
+        _ => Err(format!("expected key=value, got '{}'", rule)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitFormat {
+    Ndjson,
+    /// Original line bytes of matching records, for byte-exact provenance.
+    RawFiltered,
+    /// `<byte offset>\t<length>` of each matching record in its input,
+    /// for tools that slice the file themselves.
+    Offsets,
+    /// No per-record output; a single `RecordDigest` line at the end.
+    Hash,
+}
+
+impl EmitFormat {
+    pub fn parse(name: &str) -> Option<EmitFormat> {
+        match name {
+            "ndjson" | "json" => Some(EmitFormat::Ndjson),
+            "raw-filtered" | "raw" => Some(EmitFormat::RawFiltered),
+            "offsets" => Some(EmitFormat::Offsets),
+            "hash" => Some(EmitFormat::Hash),
+            _ => None,
+        }
+    }
+}
+
+pub fn emit_chunk<B: EmitRecord>(
+    batch: &B,
+    records: &[u32],
+    rules: &EmitRules,
+    format: EmitFormat,
+) -> EmitChunk {
+    let mut chunk = match format {
+        EmitFormat::Ndjson => return ndjson_chunk(batch, records, rules),
+        EmitFormat::RawFiltered => raw_chunk(batch, records),
+        EmitFormat::Offsets => offsets_chunk(batch, records),
+        EmitFormat::Hash => return EmitChunk::default(),
+    };
+    if rules.collect_stats {
+        let mut stats = ChunkStats::default();
+        for &i in records {
+            if let Some(ts) = batch.record_timestamp(i as usize) {
+                stats.add_time(ts);
+            }
+        }
+        chunk.stats = Some(stats);
+    }
+    chunk
+}
+
+/// An order-independent digest of parsed records for `--emit hash`: the
+/// wrapping sum of each record's xxh64 over its source line and its fields
+/// as default-rules NDJSON. Batch boundaries, thread count and emit order
+/// leave it unchanged; a changed span, key or value does not.
+#[derive(Debug, Default)]
+pub struct RecordDigest {
+    sum: AtomicU64,
+    records: AtomicU64,
+}
+
+impl RecordDigest {
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let rules = EmitRules::default();
+        let mut keys = Vec::new();
+        let mut names = SchemaNames::default();
+        let mut buf = Vec::with_capacity(512);
+        let mut sum = 0u64;
+        for &i in records {
+            let i = i as usize;
+            buf.clear();
+            buf.extend_from_slice(batch.record_raw(i));
+            buf.push(b'\n');
+            write_ndjson_record(batch, i, &rules, &mut keys, &mut names, &mut buf);
+            sum = sum.wrapping_add(Xxh64::oneshot(&buf));
+        }
+        self.sum.fetch_add(sum, Ordering::Relaxed);
+        self.records
+            .fetch_add(records.len() as u64, Ordering::Relaxed);
+    }
+
+    pub fn digest(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records.load(Ordering::Relaxed)
+    }
+
+    /// The single NDJSON line `--emit hash` writes.
+    pub fn to_ndjson(&self) -> Vec<u8> {
+        format!(
+            "{{\"algorithm\":\"xxh64-sum\",\"digest\":\"{:016x}\",\"records\":{}}}\n",
+            self.digest(),
+            self.records()
+        )
+        .into_bytes()
+    }
+}
+
+/// Input offset and length of `records`, one per line; the length leaves
+/// out the line terminator. A rewritten batch has no offsets into the input
+/// and gives an empty chunk.
+pub fn offsets_chunk<B: BatchRecords>(batch: &B, records: &[u32]) -> EmitChunk {
+    if batch.rewritten() {
+        return EmitChunk::default();
+    }
+    let mut chunk = EmitChunk {
+        ndjson: Vec::with_capacity(records.len() * 16),
+        ..EmitChunk::default()
+    };
+    for &i in records {
+        let i = i as usize;
+        let line = batch.record_line(i);
+        let _ = writeln!(
+            chunk.ndjson,
+            "{}\t{}",
+            batch.input_offset() + line.offset,
+            line.len
+        );
+        chunk.levels.record(batch.record_level(i));
+    }
+    chunk.records = records.len() as u64;
+    chunk
+}
+
+/// Source lines of `records`, each ended by the record separator; emit
+/// rules do not apply.
+pub fn raw_chunk<B: BatchRecords>(batch: &B, records: &[u32]) -> EmitChunk {
+    let mut chunk = EmitChunk::default();
+    let bytes = records
+        .iter()
+        .map(|&i| batch.record_line(i as usize).len as usize + 1)
+        .sum();
+    chunk.ndjson.reserve(bytes);
+    for &i in records {
+        let i = i as usize;
+        chunk.ndjson.extend_from_slice(batch.record_raw(i));
+        chunk.ndjson.push(crate::simd_scan::record_sep());
+        chunk.levels.record(batch.record_level(i));
+    }
+    chunk.records = records.len() as u64;
+    chunk
+}
+
+pub fn ndjson_chunk<B: EmitRecord>(batch: &B, records: &[u32], rules: &EmitRules) -> EmitChunk {
+    let mut chunk = EmitChunk {
+        ndjson: Vec::with_capacity(records.len() * 160),
+        ..EmitChunk::default()
+    };
+    let mut keys = Vec::new();
+    let mut names = SchemaNames::default();
+    let mut stats = rules.collect_stats.then(ChunkStats::default);
+    for &i in records {
+        let i = i as usize;
+        write_ndjson_record(batch, i, rules, &mut keys, &mut names, &mut chunk.ndjson);
+        chunk.ndjson.push(b'\n');
+        chunk.levels.record(batch.record_level(i));
+        if let Some(stats) = &mut stats {
+            if let Some(ts) = batch.record_timestamp(i) {
+                stats.add_time(ts);
+            }
+            for key in &keys {
+                stats.add_field(key, 1);
+            }
+        }
+    }
+    chunk.records = records.len() as u64;
+    chunk.stats = stats;
+    chunk
+}
+
+fn write_ndjson_record<B: EmitRecord>(
+    batch: &B,
+    i: usize,
+    rules: &EmitRules,
+    keys: &mut Vec<Vec<u8>>,
+    names: &mut SchemaNames,
+    out: &mut Vec<u8>,
+) {
+    let track_keys =
+        !rules.inject.is_empty() || !rules.source_fields.is_empty() || rules.collect_stats;
+    keys.clear();
+    if let Some(schema) = rules.schema {
+        names.start_record(schema, batch, i, rules);
+    }
+    out.push(b'{');
+    let mut first = true;
+    batch.for_each_field(i, &mut |key, value| {
+        if rules.derive.iter().any(|d| d.name == key) {
+            return;
+        }
+        let source = key;
+        let key = rules.output_key(key, names);
+        if rules.record_id.as_ref().is_some_and(|id| id.field == key)
+            || rules.provenance.as_deref() == Some(key)
+            || rules.schema_id.as_deref() == Some(key)
+        {
+            return;
+        }
+        if track_keys {
+            keys.push(key.to_vec());
+        }
+        match rules.types.iter().find(|t| t.field == key) {
+            Some(field_type) => {
+                write_key(key, &mut first, out);
+                let style = numbers::style_of(source);
+                if !write_coerced(value, field_type.kind, style, out) {
+                    field_type.failures.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            None => write_field(key, value, &mut first, out),
+        }
+    });
+    if !rules.derive.is_empty() {
+        for (name, value) in derive_fields(batch, i, &rules.derive) {
+            let key = rules.output_key(name, names);
+            if track_keys {
+                keys.push(key.to_vec());
+            }
+            write_key(key, &mut first, out);
+            value.write_json(out);
+        }
+    }
+    for (key, value) in rules.inject.iter().chain(&rules.source_fields) {
+        if !keys.contains(key) {
+            write_field(key, FieldValue::Text(value), &mut first, out);
+            if rules.collect_stats {
+                keys.push(key.clone());
+            }
+        }
+    }
+    if let Some(id) = &rules.record_id {
+        write_key(&id.field, &mut first, out);
+        id.write(batch, i, out);
+        if rules.collect_stats {
+            keys.push(id.field.clone());
+        }
+    }
+    if let Some(field) = &rules.provenance {
+        write_key(field, &mut first, out);
+        write_provenance(batch.provenance(i), out);
+        if rules.collect_stats {
+            keys.push(field.clone());
+        }
+    }
+    if let Some(field) = &rules.schema_id {
+        write_key(field, &mut first, out);
+        let _ = write!(out, "\"{:016x}\"", batch.schema_id(i));
+        if rules.collect_stats {
+            keys.push(field.clone());
+        }
+    }
+    out.push(b'}');
+}
+
+fn write_provenance(provenance: RecordProvenance, out: &mut Vec<u8>) {
+    let RecordProvenance {
+        file_id,
+        byte_offset,
+        byte_len,
+        line_no,
+    } = provenance;
+    let _ = write!(out, "{{\"file\":{},\"offset\":", file_id);
+    match byte_offset.zip(byte_len) {
+        Some((offset, len)) => {
+            let _ = write!(out, "{},\"len\":{}", offset, len);
+        }
+        None => out.extend_from_slice(b"null,\"len\":null"),
+    }
+    if let Some(line) = line_no {
+        let _ = write!(out, ",\"line\":{}", line);
+    }
+    out.push(b'}');
+}
+
+fn write_key(key: &[u8], first: &mut bool, out: &mut Vec<u8>) {
+    if !std::mem::take(first) {
+        out.push(b',');
+    }
+    write_json_string(key, out);
+    out.push(b':');
+}
+
+fn write_field(key: &[u8], value: FieldValue<'_>, first: &mut bool, out: &mut Vec<u8>) {
+    write_key(key, first, out);
+    write_value(value, out);
+}
+
+fn write_value(value: FieldValue<'_>, out: &mut Vec<u8>) {
+    match value {
+        FieldValue::Text(text) => write_json_string(text, out),
+        FieldValue::Escaped(text) => {
+            out.push(b'"');
+            out.extend_from_slice(text);
+            out.push(b'"');
+        }
+        FieldValue::Literal(literal) => out.extend_from_slice(literal),
+        FieldValue::Timestamp(ts) => {
+            out.push(b'"');
+            write_rfc3339(ts, out);
+            out.push(b'"');
+        }
+    }
+}
+
+/// Writes `value` as `kind`, or `null` (returning false) when it does not
+/// convert. Text numbers are read in `style`; JSON numbers as they are.
+fn write_coerced(
+    value: FieldValue<'_>,
+    kind: ValueKind,
+    style: NumberStyle,
+    out: &mut Vec<u8>,
+) -> bool {
+    let text = match value {
+        FieldValue::Timestamp(ts) => match kind {
+            ValueKind::Int => {
+                let _ = write!(out, "{}", ts);
+                return true;
+            }
+            ValueKind::Float | ValueKind::Bool => {
+                out.extend_from_slice(b"null");
+                return false;
+            }
+            ValueKind::Timestamp | ValueKind::String => {
+                write_value(value, out);
+                return true;
+            }
+        },
+        FieldValue::Text(text) | FieldValue::Escaped(text) | FieldValue::Literal(text) => text,
+    };
+    let style = match value {
+        FieldValue::Literal(_) => NumberStyle::Plain,
+        _ => style,
+    };
+
+    let written = match kind {
+        ValueKind::String => {
+            match value {
+                FieldValue::Literal(literal) => write_json_string(literal, out),
+                _ => write_value(value, out),
+            }
+            true
+        }
+        ValueKind::Int => numbers::parse::<i64>(text, style)
+            .or_else(|| crate::parser::parse_timestamp(text).map(|ts| ts as i64))
+            .map(|n| write!(out, "{}", n))
+            .is_some(),
+        ValueKind::Float => numbers::parse::<f64>(text, style)
+            .filter(|f| f.is_finite())
+            .map(|f| write!(out, "{}", f))
+            .is_some(),
+        ValueKind::Bool => {
+            let b = match text.to_ascii_lowercase().as_slice() {
+                b"true" | b"1" | b"yes" | b"y" | b"on" => Some(true),
+                b"false" | b"0" | b"no" | b"n" | b"off" => Some(false),
+                _ => None,
+            };
+            b.map(|b| out.extend_from_slice(if b { b"true" } else { b"false" }))
+                .is_some()
+        }
+        ValueKind::Timestamp => crate::parser::parse_timestamp_value(text)
+            .map(|ts| write_value(FieldValue::Timestamp(ts), out))
+            .is_some(),
+    };
+    if !written {
+        out.extend_from_slice(b"null");
+    }
+    written
+}
+
+impl EmitRecord for LogBatch {
+    fn for_each_field(&self, i: usize, f: &mut dyn FnMut(&[u8], FieldValue<'_>)) {
+        if self.timestamps[i] != 0 {
+            f(b"timestamp", FieldValue::Timestamp(self.timestamps[i]));
+        }
+        let level = self.levels[i].as_str().to_ascii_lowercase();
+        f(b"level", FieldValue::Text(level.as_bytes()));
+        let line = unsafe { raw_bytes(self.data_ptr, self.line_offsets[i], self.line_lens[i]) };
+        if let Some((pri, _)) = Pri::prefix(line) {
+            f(
+                b"facility",
+                FieldValue::Text(pri.facility_name().as_bytes()),
+            );
+            f(
+                b"severity",
+                FieldValue::Text(pri.severity_name().as_bytes()),
+            );
+        }
+        f(
+            b"component",
+            FieldValue::Text(unsafe { self.component(i) }.as_bytes()),
+        );
+        f(
+            b"message",
+            FieldValue::Text(unsafe { self.message(i) }.as_bytes()),
+        );
+    }
+
+    /// One of four key sets, by whether the record has a timestamp and a
+    /// syslog priority, so nothing is collected per record.
+    fn schema_id(&self, i: usize) -> u64 {
+        static IDS: OnceLock<[[u64; 2]; 2]> = OnceLock::new();
+        let ids = IDS.get_or_init(|| {
+            [false, true].map(|pri| {
+                [false, true].map(|timestamp| {
+                    let keys: [&[u8]; 6] = [
+                        b"timestamp",
+                        b"level",
+                        b"facility",
+                        b"severity",
+                        b"component",
+                        b"message",
+                    ];
+                    let has = |key: &[u8]| match key {
+                        b"timestamp" => timestamp,
+                        b"facility" | b"severity" => pri,
+                        _ => true,
+                    };
+                    crate::schemas::schema_id(keys.into_iter().filter(|key| has(key)))
+                })
+            })
+        });
+        let line = unsafe { raw_bytes(self.data_ptr, self.line_offsets[i], self.line_lens[i]) };
+        let pri = Pri::prefix(line).is_some();
+        ids[usize::from(pri)][usize::from(self.timestamps[i] != 0)]
+    }
+}
+
+impl EmitRecord for StructuredBatch {
+    fn for_each_field(&self, i: usize, f: &mut dyn FnMut(&[u8], FieldValue<'_>)) {
+        let mut scratch = Vec::new();
+        for field in self.record_fields(i) {
+            f(
+                self.keys.name(field.key_id),
+                structured_value(self, field.val_offset, field.val_len, &mut scratch),
+            );
+        }
+    }
+
+    /// Reads hot keys from their value column and compares interned key
+    /// ids for the rest.
+    fn visit_field(&self, i: usize, name: &[u8], f: &mut dyn FnMut(FieldValue<'_>)) {
+        let Some(key_id) = self.keys.get(name) else {
+            return;
+        };
+        let value = match self.column_for_key(key_id) {
+            Some(column) => column.get(i),
+            None => self
+                .field_by_key(i, key_id)
+                .map(|field| (field.val_offset, field.val_len)),
+        };
+        if let Some((offset, len)) = value {
+            f(structured_value(self, offset, len, &mut Vec::new()));
+        }
+    }
+
+    /// The interned schema's ID when the batch tagged schemas, otherwise
+    /// hashed from the record's keys.
+    #[inline]
+    fn schema_id(&self, i: usize) -> u64 {
+        match self.schema_ids.get(i) {
+            Some(&schema) => self.schemas.id(schema),
+            None => crate::schemas::schema_id(
+                self.record_fields(i)
+                    .iter()
+                    .map(|field| self.keys.name(field.key_id)),
+            ),
+        }
+    }
+}
+
+/// Record value at `offset`. Quoted JSON strings stay escaped; quoted CSV
+/// and logfmt values are unquoted into `scratch` when they hold escapes, so
+/// every `Text` is raw text whatever the input format.
+fn structured_value<'a>(
+    batch: &'a StructuredBatch,
+    offset: u64,
+    len: u32,
+    scratch: &'a mut Vec<u8>,
+) -> FieldValue<'a> {
+    let value = unsafe { raw_bytes(batch.data_ptr, offset, len) };
+    let quoted = batch.format != LogFormat::FixedWidth
+        && offset > 0
+        && unsafe { *batch.data_ptr.add(offset as usize - 1) } == b'"';
+    if !quoted {
+        // Nested JSON objects and arrays keep their structure.
+        let nested = batch.format == LogFormat::Json
+            && matches!(
+                (value.first(), value.last()),
+                (Some(b'{'), Some(b'}')) | (Some(b'['), Some(b']'))
+            );
+        return if nested || is_json_literal(value) {
+            FieldValue::Literal(value)
+        } else {
+            FieldValue::Text(value)
+        };
+    }
+    let unquote: fn(&[u8], &mut Vec<u8>) = match batch.format {
+        LogFormat::Json => return FieldValue::Escaped(value),
+        LogFormat::Csv if value.contains(&b'"') => crate::csv_parser::unquote,
+        LogFormat::Logfmt | LogFormat::PlainText if value.contains(&b'\\') => {
+            crate::logfmt_parser::unquote
+        }
+        _ => return FieldValue::Text(value),
+    };
+    scratch.clear();
+    unquote(value, scratch);
+    FieldValue::Text(scratch)
+}
+
+unsafe fn raw_bytes<'a>(data_ptr: *const u8, offset: u64, len: u32) -> &'a [u8] {
+    unsafe { std::slice::from_raw_parts(data_ptr.add(offset as usize), len as usize) }
+}
+
+/// `true`, `false`, `null` or a number in JSON's grammar:
+/// `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`.
+fn is_json_literal(value: &[u8]) -> bool {
+    if matches!(value, b"true" | b"false" | b"null") {
+        return true;
+    }
+    let digits = |rest: &[u8]| rest.iter().take_while(|b| b.is_ascii_digit()).count();
+    let mut rest = value.strip_prefix(b"-").unwrap_or(value);
+    let int = digits(rest);
+    if int == 0 || (rest[0] == b'0' && int > 1) {
+        return false;
+    }
+    rest = &rest[int..];
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let n = digits(fraction);
+        if n == 0 {
+            return false;
+        }
+        rest = &fraction[n..];
+    }
+    if let Some(exponent) = rest.strip_prefix(b"e").or_else(|| rest.strip_prefix(b"E")) {
+        let exponent = exponent
+            .strip_prefix(b"+")
+            .or_else(|| exponent.strip_prefix(b"-"))
+            .unwrap_or(exponent);
+        let n = digits(exponent);
+        if n == 0 {
+            return false;
+        }
+        rest = &exponent[n..];
+    }
+    rest.is_empty()
+}
+
+pub fn write_json_string(bytes: &[u8], out: &mut Vec<u8>) {
+    out.push(b'"');
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        let escape: &[u8] = match b {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0..=0x1F => b"",
+            _ => continue,
+        };
+        out.extend_from_slice(&bytes[start..i]);
+        if escape.is_empty() {
+            let _ = write!(out, "\\u{:04x}", b);
+        } else {
+            out.extend_from_slice(escape);
+        }
+        start = i + 1;
+    }
+    out.extend_from_slice(&bytes[start..]);
+    out.push(b'"');
+}
+
+/// Resolves the escapes of a JSON string body. Invalid UTF-8, unknown
+/// escapes and unpaired surrogates decode to U+FFFD.
+pub fn unescape_json(raw: &[u8]) -> String {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        let Some(at) = memchr::memchr(b'\\', &raw[i..]) else {
+            out.extend_from_slice(&raw[i..]);
+            break;
+        };
+        out.extend_from_slice(&raw[i..i + at]);
+        i += at + 1;
+        let Some(&escape) = raw.get(i) else {
+            break;
+        };
+        i += 1;
+        let c = match escape {
+            b'n' => '\n',
+            b't' => '\t',
+            b'r' => '\r',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'"' | b'\\' | b'/' => escape as char,
+            b'u' => {
+                let high = hex4(raw, i);
+                i += 4;
+                match high {
+                    Some(high @ 0xd800..=0xdbff) if raw.get(i..i + 2) == Some(b"\\u") => {
+                        match hex4(raw, i + 2) {
+                            Some(low @ 0xdc00..=0xdfff) => {
+                                i += 6;
+                                let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                                char::from_u32(code).unwrap_or('\u{fffd}')
+                            }
+                            _ => '\u{fffd}',
+                        }
+                    }
+                    Some(code) => char::from_u32(code).unwrap_or('\u{fffd}'),
+                    None => '\u{fffd}',
+                }
+            }
+            _ => '\u{fffd}',
+        };
+        out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+    String::from_utf8(out).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+fn hex4(raw: &[u8], at: usize) -> Option<u32> {
+    let digits = std::str::from_utf8(raw.get(at..at + 4)?).ok()?;
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Formats epoch seconds as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn write_rfc3339(ts: u64, out: &mut Vec<u8>) {
+    let days = (ts / 86400) as i64;
+    let secs = ts % 86400;
+
+    // Civil-from-days (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let _ = write!(
+        out,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::parse_logs_pipelined;
+    use crate::structured_orchestrator::parse_structured_mmap;
+
+    #[test]
+    fn test_write_rfc3339_round_trips() {
+        for ts in [0u64, 951_782_400, 1_739_356_305, 4_102_444_799] {
+            let mut out = Vec::new();
+            write_rfc3339(ts, &mut out);
+            assert_eq!(crate::parser::parse_timestamp(&out), Some(ts));
+        }
+    }
+
+    #[test]
+    fn test_write_json_string_escapes() {
+        let mut out = Vec::new();
+        write_json_string(b"a\"b\\c\nd\x01", &mut out);
+        assert_eq!(out, br#""a\"b\\c\nd\u0001""#);
+    }
+
+    #[test]
+    fn test_plain_ndjson() {
+        let data = b"2025-02-12T10:31:45Z WARN api-server slow \"upstream\"\n";
+        let result = parse_logs_pipelined(data, 1);
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &EmitRules::default());
+
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"timestamp\":\"2025-02-12T10:31:45Z\",\"level\":\"warn\",\
+             \"component\":\"api-server\",\"message\":\"slow \\\"upstream\\\"\"}\n"
+        );
+        assert_eq!(chunk.records, 1);
+    }
+
+    #[test]
+    fn test_raw_filtered_is_byte_exact() {
+        let data = b"2025-02-12T10:31:45Z WARN api  two  spaces\r\n\
+                     2025-02-12T10:31:46Z INFO api skipped\n\
+                     2025-02-12T10:31:47Z ERROR api last";
+        let result = parse_logs_pipelined(data, 1);
+        let chunk = raw_chunk(&result.batches[0], &[0, 2]);
+        assert_eq!(
+            chunk.ndjson,
+            b"2025-02-12T10:31:45Z WARN api  two  spaces\r\n\
+              2025-02-12T10:31:47Z ERROR api last\n"
+        );
+
+        let json = b"{\"level\":\"info\", \"msg\":\"a\"}\r\n{\"level\":\"warn\"}\n";
+        let result = parse_structured_mmap(json, 1, Some(LogFormat::Json));
+        let chunk = raw_chunk(&result.batches[0], &[0, 1]);
+        assert_eq!(chunk.ndjson, json);
+        assert_eq!(chunk.records, 2);
+    }
+
+    #[test]
+    fn test_record_digest_ignores_record_order() {
+        let data = b"{\"level\":\"info\",\"n\":1}\n{\"level\":\"warn\"}\n{\"level\":\"error\",\"s\":\"x\"}\n";
+        let digest_of = |data: &[u8], threads: usize, order: &[u32]| {
+            let digest = RecordDigest::default();
+            let result = parse_structured_mmap(data, threads, Some(LogFormat::Json));
+            for batch in &result.batches {
+                let records: Vec<u32> = order
+                    .iter()
+                    .copied()
+                    .filter(|&i| (i as usize) < batch.len)
+                    .collect();
+                digest.add_records(batch, &records);
+            }
+            (digest.digest(), digest.records())
+        };
+        let forward = digest_of(data, 1, &[0, 1, 2]);
+        assert_eq!(forward.1, 3);
+        assert_eq!(digest_of(data, 1, &[2, 0, 1]), forward);
+
+        let changed = b"{\"level\":\"info\",\"n\":2}\n{\"level\":\"warn\"}\n{\"level\":\"error\",\"s\":\"x\"}\n";
+        assert_ne!(digest_of(changed, 1, &[0, 1, 2]), forward);
+        let line = String::from_utf8(RecordDigest::default().to_ndjson()).unwrap();
+        assert!(line.starts_with("{\"algorithm\":\"xxh64-sum\",\"digest\":\"0000000000000000\""));
+    }
+
+    #[test]
+    fn test_offsets_slice_the_input() {
+        let data = b"{\"level\":\"info\"}\r\n{\"level\":\"warn\", \"n\": 2}\n{\"level\":\"error\"}";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Json));
+        let chunk = offsets_chunk(&result.batches[0], &[1, 2]);
+        let text = String::from_utf8(chunk.ndjson).unwrap();
+        assert_eq!(text, "18\t24\n43\t17\n");
+        for line in text.lines() {
+            let (offset, len) = line.split_once('\t').unwrap();
+            let (offset, len): (usize, usize) = (offset.parse().unwrap(), len.parse().unwrap());
+            assert!(data[offset..offset + len].starts_with(b"{") && data[offset + len - 1] == b'}');
+        }
+    }
+
+    #[test]
+    fn test_offsets_skip_transcoded_input() {
+        use crate::encoding::{Encoding, Transcoder};
+        use crate::filter::MatchControl;
+        use crate::structured_orchestrator::parse_structured_read_with;
+
+        let data = b"\xef\xbb\xbf{\"level\":\"info\"}\r\n{\"level\":\"warn\"}\r\n";
+        let control = MatchControl {
+            transcoded: true,
+            ..MatchControl::default()
+        };
+        let mut reader = Transcoder::new(&data[..], Encoding::detect(data));
+        let result = parse_structured_read_with(&mut reader, 1, Some(LogFormat::Json), &control);
+        let batch = &result.batches[0];
+        assert!(batch.rewritten);
+        let chunk = offsets_chunk(batch, &[0, 1]);
+        assert!(chunk.ndjson.is_empty());
+        assert_eq!(chunk.records, 0);
+    }
+
+    #[test]
+    fn test_structured_ndjson_keeps_value_types() {
+        let data = b"level=info msg=\"a \\\"b\\\"\" latency_ms=42 ok=true id=007\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &EmitRules::default());
+
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"level\":\"info\",\"msg\":\"a \\\"b\\\"\",\"latency_ms\":42,\"ok\":true,\"id\":\"007\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_quoted_csv_and_logfmt_values_emit_valid_json() {
+        let parse = |data: &[u8], format| {
+            let result = parse_structured_mmap(data, 1, Some(format));
+            let chunk = ndjson_chunk(&result.batches[0], &[0], &EmitRules::default());
+            serde_json::from_slice::<serde_json::Value>(&chunk.ndjson).unwrap()
+        };
+
+        let csv = parse(
+            b"msg,path,n\n\"say \"\"hi\"\"\",\"C:\\tmp\\new\",\"7\"\n",
+            LogFormat::Csv,
+        );
+        assert_eq!(csv["msg"], "say \"hi\"");
+        assert_eq!(csv["path"], "C:\\tmp\\new");
+        assert_eq!(csv["n"], "7");
+
+        let logfmt = parse(
+            b"msg=\"a \\\"b\\\" c\" path=\"C:\\\\x\\q\" tab=\"1\\t2\"\n",
+            LogFormat::Logfmt,
+        );
+        assert_eq!(logfmt["msg"], "a \"b\" c");
+        assert_eq!(logfmt["path"], "C:\\x\\q");
+        assert_eq!(logfmt["tab"], "1\t2");
+
+        let json = parse(b"{\"msg\":\"a \\\"b\\\" \\u00e9\"}\n", LogFormat::Json);
+        assert_eq!(json["msg"], "a \"b\" \u{e9}");
+    }
+
+    #[test]
+    fn test_numbers_follow_the_json_grammar() {
+        let data = b"a=1. b=0. c=1.e3 d=.5 e=01 f=+1 g=-0.5e+3 h=0 i=2E7 j=-\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &EmitRules::default());
+        assert_eq!(
+            String::from_utf8(chunk.ndjson.clone()).unwrap(),
+            "{\"a\":\"1.\",\"b\":\"0.\",\"c\":\"1.e3\",\"d\":\".5\",\"e\":\"01\",\"f\":\"+1\",\
+             \"g\":-0.5e+3,\"h\":0,\"i\":2E7,\"j\":\"-\"}\n"
+        );
+        serde_json::from_slice::<serde_json::Value>(&chunk.ndjson).unwrap();
+    }
+
+    #[test]
+    fn test_nested_json_round_trips() {
+        let data =
+            b"{\"user\":{\"name\":\"bob\",\"tags\":[1,\"x\"]},\"ids\":[3, {\"k\":null}],\"n\":1}\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Json));
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &EmitRules::default());
+        let out: serde_json::Value = serde_json::from_slice(&chunk.ndjson).unwrap();
+        let source: serde_json::Value = serde_json::from_slice(data).unwrap();
+        assert_eq!(out, source);
+        assert_eq!(out["user"]["name"], "bob");
+        assert_eq!(out["ids"][1]["k"], serde_json::Value::Null);
+
+        // Braces in other formats are text.
+        let data = b"ctx={} n=1\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &EmitRules::default());
+        assert_eq!(chunk.ndjson, b"{\"ctx\":\"{}\",\"n\":1}\n");
+    }
+
+    #[test]
+    fn test_emit_rules_rename_and_inject() {
+        let data = b"ts=2025-02-12T10:31:45Z msg=hello env=staging\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+
+        let mut rules = EmitRules::default();
+        rules.add_rename("msg=message").unwrap();
+        rules
+            .load("# normalize\nrename ts=timestamp\nset env=prod\nset team=core\n")
+            .unwrap();
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &rules);
+
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"timestamp\":\"2025-02-12T10:31:45Z\",\"message\":\"hello\",\
+             \"env\":\"staging\",\"team\":\"core\"}\n"
+        );
+        assert!(rules.load("drop x").is_err());
+        assert!(rules.add_rename("=x").is_err());
+    }
+
+    #[test]
+    fn test_emit_rules_derive_fields() {
+        let data = b"level=error latency_ms=2500 slow=maybe\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+
+        let mut rules = EmitRules::default();
+        rules.add_derive("duration_s=latency_ms/1000").unwrap();
+        rules
+            .load("derive is_err=level in (error,fatal)\nderive slow=duration_s > 1\n")
+            .unwrap();
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &rules);
+
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"level\":\"error\",\"latency_ms\":2500,\"duration_s\":2.5,\
+             \"is_err\":true,\"slow\":true}\n"
+        );
+        assert!(rules.add_derive("bad=1 +").is_err());
+    }
+
+    #[test]
+    fn test_emit_rules_record_id() {
+        let data = b"msg=a _id=old
+msg=b
+msg=a _id=old
+";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+
+        let mut rules = EmitRules::default();
+        rules.load("id _id").unwrap();
+        rules.record_id.as_mut().unwrap().source = 0xabc;
+        let chunk = ndjson_chunk(&result.batches[0], &[0, 1], &rules);
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"msg\":\"a\",\"_id\":\"0000000000000abc-0\"}\n\
+             {\"msg\":\"b\",\"_id\":\"0000000000000abc-14\"}\n"
+        );
+
+        rules.set_record_id("doc_id:hash").unwrap();
+        let chunk = ndjson_chunk(&result.batches[0], &[0, 2], &rules);
+        let text = String::from_utf8(chunk.ndjson).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], lines[1]);
+        assert!(lines[0].ends_with(&format!(
+            "\"doc_id\":\"{:016x}\"}}",
+            Xxh64::oneshot(b"msg=a _id=old")
+        )));
+        assert!(rules.set_record_id("id:uuid").is_err());
+        assert!(rules.set_record_id(":hash").is_err());
+    }
+
+    #[test]
+    fn test_emit_rules_provenance() {
+        use crate::filter::MatchControl;
+        use crate::structured_orchestrator::parse_structured_mmap_with;
+
+        let data = b"msg=a src=x\n\nmsg=b\r\nmsg=c\n";
+        let control = MatchControl {
+            file_id: 2,
+            line_numbers: true,
+            ..MatchControl::default()
+        };
+        let result = parse_structured_mmap_with(data, 1, Some(LogFormat::Logfmt), &control);
+        let batch = &result.batches[0];
+        assert_eq!(
+            batch.provenance(1),
+            RecordProvenance {
+                file_id: 2,
+                byte_offset: Some(13),
+                byte_len: Some(5),
+                line_no: Some(3),
+            }
+        );
+
+        let mut rules = EmitRules::default();
+        rules.load("provenance src").unwrap();
+        let chunk = ndjson_chunk(batch, &[0, 2], &rules);
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"msg\":\"a\",\"src\":{\"file\":2,\"offset\":0,\"len\":11,\"line\":1}}\n\
+             {\"msg\":\"c\",\"src\":{\"file\":2,\"offset\":20,\"len\":5,\"line\":4}}\n"
+        );
+
+        // A BOM and CRLF lines transcode to different offsets than the file's.
+        let data = b"\xef\xbb\xbfmsg=a\r\nmsg=b\r\n";
+        let control = MatchControl {
+            transcoded: true,
+            line_numbers: true,
+            ..MatchControl::default()
+        };
+        let mut reader =
+            crate::encoding::Transcoder::new(&data[..], crate::encoding::Encoding::detect(data));
+        let result = crate::structured_orchestrator::parse_structured_read_with(
+            &mut reader,
+            1,
+            Some(LogFormat::Logfmt),
+            &control,
+        );
+        let chunk = ndjson_chunk(&result.batches[0], &[1], &rules);
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"msg\":\"b\",\"src\":{\"file\":0,\"offset\":null,\"len\":null,\"line\":2}}\n"
+        );
+    }
+
+    #[test]
+    fn test_emit_rules_schema_id() {
+        let data = b"msg=a sid=x n=1\nn=2 msg=b\nmsg=c\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+        let batch = &result.batches[0];
+        assert_eq!(
+            batch.schema_id(1),
+            crate::schemas::schema_id([&b"msg"[..], b"n"])
+        );
+
+        let mut rules = EmitRules::default();
+        rules.load("schema-id sid").unwrap();
+        let text = String::from_utf8(ndjson_chunk(batch, &[0, 1, 2], &rules).ndjson).unwrap();
+        let ids: Vec<String> = text
+            .lines()
+            .map(|line| {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                value["sid"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(ids[1], format!("{:016x}", batch.schema_id(1)));
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[1], ids[2]);
+        assert!(text.starts_with("{\"msg\":\"a\",\"n\":1,\"sid\":"));
+        assert!(rules.set_schema_id("").is_err());
+
+        let data = b"<34>2025-02-12T10:31:45Z ERROR [db] down\nplain line\n";
+        let result = parse_logs_pipelined(data, 1);
+        let batch = &result.batches[0];
+        for i in 0..2 {
+            let mut keys = Vec::new();
+            batch.for_each_field(i, &mut |key, _| keys.push(key.to_vec()));
+            let keys = crate::schemas::schema_id(keys.iter().map(Vec::as_slice));
+            assert_eq!(batch.schema_id(i), keys);
+        }
+        assert_ne!(batch.schema_id(0), batch.schema_id(1));
+    }
+
+    #[test]
+    fn test_emit_rules_schema() {
+        let data = b"ts=2025-02-12T10:31:45Z level=warn msg=slow status=503 trace_id=ab x=1\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+
+        let mut rules = EmitRules::default();
+        rules.load("schema ecs\nrename msg=note").unwrap();
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &rules);
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"@timestamp\":\"2025-02-12T10:31:45Z\",\"log.level\":\"warn\",\"note\":\"slow\",\
+             \"http.response.status_code\":503,\"trace.id\":\"ab\",\"x\":1}\n"
+        );
+
+        let mut rules = EmitRules::default();
+        rules.set_schema("otel").unwrap();
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &rules);
+        assert!(String::from_utf8(chunk.ndjson).unwrap().starts_with(
+            "{\"timestamp\":\"2025-02-12T10:31:45Z\",\"severity_text\":\"warn\",\"body\":\"slow\""
+        ));
+        assert!(rules.set_schema("gelf").is_err());
+
+        // One field per schema name; the rest keep their own.
+        let data = b"ts=1 time=2 msg=a message=b level=warn severity=3\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &rules);
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"timestamp\":1,\"time\":2,\"body\":\"a\",\"message\":\"b\",\
+             \"severity_text\":\"warn\",\"severity\":3}\n"
+        );
+        rules.set_schema("ecs").unwrap();
+        let chunk = ndjson_chunk(&result.batches[0], &[0], &rules);
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"@timestamp\":1,\"time\":2,\"msg\":\"a\",\"message\":\"b\",\
+             \"log.level\":\"warn\",\"severity\":3}\n"
+        );
+
+        for schema in [Schema::Ecs, Schema::Otel] {
+            for key in [
+                "ts", "level", "msg", "logger", "host", "pid", "trace_id", "span_id",
+            ]
+            .into_iter()
+            .chain([
+                "status",
+                "method",
+                "path",
+                "url",
+                "ua",
+                "client_ip",
+                "service",
+                "err",
+            ]) {
+                let name = schema.field_name(key.as_bytes()).unwrap();
+                assert!(schema.names().contains(&name), "{}", key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_emit_rules_type_coercion() {
+        let data = b"latency_ms=42 ok=yes ts=\"2025-02-12 10:31:45\" ratio=0.5 id=7\n\
+                     latency_ms=slow ok=maybe ts=never ratio=x id=8\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+
+        let mut rules = EmitRules::default();
+        rules
+            .add_types("latency_ms:int, ok:bool,ts:timestamp,ratio:float")
+            .unwrap();
+        rules.load("type id:string").unwrap();
+        let chunk = ndjson_chunk(&result.batches[0], &[0, 1], &rules);
+
+        assert_eq!(
+            String::from_utf8(chunk.ndjson).unwrap(),
+            "{\"latency_ms\":42,\"ok\":true,\"ts\":\"2025-02-12T10:31:45Z\",\"ratio\":0.5,\"id\":\"7\"}\n\
+             {\"latency_ms\":null,\"ok\":null,\"ts\":null,\"ratio\":null,\"id\":\"8\"}\n"
+        );
+        let failures: Vec<_> = rules.coercion_failures().collect();
+        assert_eq!(
+            failures,
+            vec![
+                (b"latency_ms".as_slice(), 1),
+                (b"ok".as_slice(), 1),
+                (b"ts".as_slice(), 1),
+                (b"ratio".as_slice(), 1)
+            ]
+        );
+        assert!(rules.add_types("x:decimal").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use std::io::{self, Read};
+
+/// Text encodings recognised in input files. Everything other than UTF-8 is
+/// transcoded to UTF-8 before scanning, so parsers only ever see UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Encoding {
+    /// Detects the encoding from the first bytes of a file: a BOM if there is
+    /// one, then NUL bytes in alternating positions for BOM-less UTF-16, then
+    /// invalid UTF-8 for Latin-1.
+    pub fn detect(head: &[u8]) -> Encoding {
+        if head.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return Encoding::Utf8Bom;
+        }
+        if head.starts_with(&[0xFF, 0xFE]) {
+            return Encoding::Utf16Le;
+        }
+        if head.starts_with(&[0xFE, 0xFF]) {
+            return Encoding::Utf16Be;
+        }
+
+        let pairs = head.len() / 2;
+        if pairs >= 2 {
+            let even_nuls = head.iter().step_by(2).filter(|&&b| b == 0).count();
+            let odd_nuls = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+            // ASCII text in UTF-16 has a NUL in every other byte.
+            if odd_nuls * 10 >= pairs * 9 && even_nuls * 10 < pairs {
+                return Encoding::Utf16Le;
+            }
+            if even_nuls * 10 >= pairs * 9 && odd_nuls * 10 < pairs {
+                return Encoding::Utf16Be;
+            }
+        }
+
+        match std::str::from_utf8(head) {
+            // A multi-byte sequence cut off by the end of the head is fine.
+            Err(e) if e.error_len().is_some() => Encoding::Latin1,
+            _ => Encoding::Utf8,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 (BOM)",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "Latin-1",
+        }
+    }
+
+    pub fn needs_transcoding(&self) -> bool {
+        *self != Encoding::Utf8
+    }
+
+    fn bom_len(&self, head: &[u8]) -> usize {
+        match self {
+            Encoding::Utf8Bom => 3,
+            Encoding::Utf16Le if head.starts_with(&[0xFF, 0xFE]) => 2,
+            Encoding::Utf16Be if head.starts_with(&[0xFE, 0xFF]) => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// Transcodes a complete buffer, e.g. the head used for format detection.
+pub fn transcode(data: &[u8], encoding: Encoding) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let _ = Transcoder::new(data, encoding).read_to_end(&mut out);
+    out
+}
+
+/// Reads `inner` as `encoding` and yields UTF-8, dropping any BOM. Invalid
+/// UTF-16 (lone surrogates) becomes U+FFFD; partial code units and
+/// surrogate pairs split across reads are carried over.
+pub struct Transcoder<R: Read> {
+    inner: R,
+    encoding: Encoding,
+    raw: Vec<u8>,
+    /// Raw bytes read but not yet decoded (an odd byte or a high surrogate).
+    pending: usize,
+    out: Vec<u8>,
+    out_pos: usize,
+    started: bool,
+    eof: bool,
+}
+
+const RAW_BLOCK: usize = 64 * 1024;
+
+impl<R: Read> Transcoder<R> {
+    pub fn new(inner: R, encoding: Encoding) -> Transcoder<R> {
+        Transcoder {
+            inner,
+            encoding,
+            raw: vec![0; RAW_BLOCK + 4],
+            pending: 0,
+            out: Vec::with_capacity(RAW_BLOCK * 2),
+            out_pos: 0,
+            started: false,
+            eof: false,
+        }
+    }
+
+    /// Reads and decodes the next raw block into `out`.
+    fn fill(&mut self) -> io::Result<()> {
+        self.out.clear();
+        self.out_pos = 0;
+        let n = loop {
+            match self.inner.read(&mut self.raw[self.pending..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                r => break r?,
+            }
+        };
+        if n == 0 {
+            self.eof = true;
+        }
+        let avail = self.pending + n;
+        let mut start = 0;
+        if !self.started && (avail >= 3 || self.eof) {
+            self.started = true;
+            start = self.encoding.bom_len(&self.raw[..avail]);
+        } else if !self.started {
+            self.pending = avail;
+            return Ok(());
+        }
+
+        let consumed = match self.encoding {
+            Encoding::Utf8 | Encoding::Utf8Bom => {
+                self.out.extend_from_slice(&self.raw[start..avail]);
+                avail
+            }
+            Encoding::Latin1 => {
+                for &b in &self.raw[start..avail] {
+                    if b < 0x80 {
+                        self.out.push(b);
+                    } else {
+                        self.out.push(0xC0 | (b >> 6));
+                        self.out.push(0x80 | (b & 0x3F));
+                    }
+                }
+                avail
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let big_endian = self.encoding == Encoding::Utf16Be;
+                self.decode_utf16(start, avail, big_endian)
+            }
+        };
+
+        let rest = avail - consumed;
+        self.raw.copy_within(consumed..avail, 0);
+        self.pending = rest;
+        if self.eof && rest > 0 {
+            // Trailing odd byte or unpaired high surrogate.
+            self.out.extend_from_slice("\u{FFFD}".as_bytes());
+            self.pending = 0;
+        }
+        Ok(())
+    }
+
+    /// Decodes `raw[start..end]`, returning how far it got. A trailing odd
+    /// byte or high surrogate is left for the next read unless at EOF.
+    fn decode_utf16(&mut self, start: usize, end: usize, big_endian: bool) -> usize {
+        let unit = |b: &[u8]| {
+            if big_endian {
+                u16::from_be_bytes([b[0], b[1]])
+            } else {
+                u16::from_le_bytes([b[0], b[1]])
+            }
+        };
+        let mut even_end = start + (end - start) / 2 * 2;
+        if !self.eof && even_end - start >= 2 {
+            let last = unit(&self.raw[even_end - 2..even_end]);
+            if (0xD800..0xDC00).contains(&last) {
+                even_end -= 2;
+            }
+        }
+        let units = self.raw[start..even_end].chunks_exact(2).map(unit);
+        let mut buf = [0u8; 4];
+        for c in char::decode_utf16(units) {
+            let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.out
+                .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        even_end
+    }
+}
+
+impl<R: Read> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.out_pos == self.out.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = buf.len().min(self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(s: &str, bom: bool) -> Vec<u8> {
+        let mut out = if bom { vec![0xFF, 0xFE] } else { vec![] };
+        out.extend(s.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        out
+    }
+
+    #[test]
+    fn test_detect_and_transcode() {
+        let text = "level=info msg=\"caf\u{e9} \u{1F600}\"\n";
+        assert_eq!(Encoding::detect(text.as_bytes()), Encoding::Utf8);
+
+        let le = utf16le(text, true);
+        assert_eq!(Encoding::detect(&le), Encoding::Utf16Le);
+        assert_eq!(transcode(&le, Encoding::Utf16Le), text.as_bytes());
+
+        let bare = utf16le(text, false);
+        assert_eq!(Encoding::detect(&bare), Encoding::Utf16Le);
+
+        let be: Vec<u8> = text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+        assert_eq!(Encoding::detect(&be), Encoding::Utf16Be);
+        assert_eq!(transcode(&be, Encoding::Utf16Be), text.as_bytes());
+
+        let latin1 = b"user=Jos\xe9 ok\n";
+        assert_eq!(Encoding::detect(latin1), Encoding::Latin1);
+        assert_eq!(
+            transcode(latin1, Encoding::Latin1),
+            "user=Jos\u{e9} ok\n".as_bytes()
+        );
+
+        let bom = b"\xEF\xBB\xBFa=1\n";
+        assert_eq!(Encoding::detect(bom), Encoding::Utf8Bom);
+        assert_eq!(transcode(bom, Encoding::Utf8Bom), b"a=1\n");
+
+        // Surrogate pairs and odd bytes split across tiny reads.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(3);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        let mut out = String::new();
+        Transcoder::new(Trickle(&le), Encoding::Utf16Le)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, text);
+    }
+}
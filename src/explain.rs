@@ -0,0 +1,164 @@
+use crate::filter::MatchControl;
+use crate::format::LogFormat;
+use crate::simd_scan;
+use crate::structured::{FieldRef, StructuredBatch, WellKnownFields};
+use crate::structured_orchestrator::parse_structured_mmap_with;
+use std::fmt;
+use std::mem::size_of;
+
+/// Bytes `--explain` reads from the head of each file to estimate from.
+pub const SAMPLE_BYTES: usize = 256 * 1024;
+
+/// The SIMD kernel each hot loop dispatches to on this CPU, for `format`.
+pub fn kernels(format: LogFormat) -> Vec<(&'static str, &'static str)> {
+    let tier = simd_scan::simd_capability()
+        .split(" (")
+        .next()
+        .unwrap_or("Scalar");
+    let mut kernels = vec![("newline scan", tier)];
+    if format == LogFormat::PlainText {
+        #[cfg(target_arch = "x86_64")]
+        let avx2 = is_x86_feature_detected!("avx2");
+        #[cfg(not(target_arch = "x86_64"))]
+        let avx2 = false;
+        kernels.push((
+            "timestamps, 4 lines at once",
+            if avx2 { "AVX2" } else { "Scalar" },
+        ));
+    }
+    kernels
+}
+
+/// Record count and memory a file's parse should need, scaled up from its
+/// first [`SAMPLE_BYTES`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Estimate {
+    pub records: u64,
+    pub fields: u64,
+    /// The input itself: mapped under `--mmap`, read into memory otherwise.
+    pub input_bytes: u64,
+    /// Per-record offsets, levels, fields and value columns.
+    pub index_bytes: u64,
+}
+
+impl Estimate {
+    /// `head` is the start of a `file_size`-byte file in `format`.
+    pub fn of(head: &[u8], file_size: u64, format: LogFormat, hot_columns: usize) -> Estimate {
+        // Only whole lines count, unless the whole file fits in the sample.
+        let head = if head.len() as u64 >= file_size {
+            head
+        } else {
+            memchr::memrchr(b'\n', head).map_or(head, |p| &head[..=p])
+        };
+        if head.is_empty() {
+            return Estimate::default();
+        }
+        let (records, fields, columns) = if format == LogFormat::PlainText {
+            let lines =
+                simd_scan::count_newlines_in_region(head) + u64::from(!head.ends_with(b"\n"));
+            (lines, 0, 0)
+        } else {
+            let control = MatchControl::<StructuredBatch> {
+                hot_columns,
+                ..Default::default()
+            };
+            let result = parse_structured_mmap_with(head, 1, Some(format), &control);
+            let columns = result.batches.first().map_or(0, |b| b.columns.len());
+            (
+                result.total_records as u64,
+                result.total_fields as u64,
+                columns,
+            )
+        };
+
+        let scale = file_size as f64 / head.len() as f64;
+        let records = (records as f64 * scale).round() as u64;
+        let fields = (fields as f64 * scale).round() as u64;
+        // Every record also has a line start from the newline scan; the
+        // rest mirrors `LogBatch` and `StructuredBatch`.
+        let per_record = size_of::<u64>()
+            + if format == LogFormat::PlainText {
+                size_of::<u64>() * 4 + size_of::<u32>() * 3 + 1
+            } else {
+                size_of::<WellKnownFields>()
+                    + size_of::<u64>()
+                    + size_of::<u32>() * 2
+                    + 1
+                    + columns * (size_of::<u64>() + size_of::<u32>())
+            };
+        let index_bytes = records * per_record as u64 + fields * size_of::<FieldRef>() as u64;
+        Estimate {
+            records,
+            fields,
+            input_bytes: file_size,
+            index_bytes,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.input_bytes + self.index_bytes
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= 1024.0 * MB {
+        format!("{:.2} GB", bytes as f64 / (1024.0 * MB))
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB)
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "~{} records", self.records)?;
+        if self.fields > 0 {
+            write!(f, ", ~{} fields", self.fields)?;
+        }
+        write!(
+            f,
+            "; {} input + {} record index",
+            format_bytes(self.input_bytes),
+            format_bytes(self.index_bytes)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_scales_the_sample() {
+        let line = b"{\"level\":\"info\",\"msg\":\"ok\",\"n\":1}\n";
+        let head: Vec<u8> = line
+            .iter()
+            .copied()
+            .cycle()
+            .take(line.len() * 100)
+            .collect();
+        let file_size = head.len() as u64 * 10;
+
+        let estimate = Estimate::of(&head, file_size, LogFormat::Json, 8);
+        assert_eq!(estimate.records, 1000);
+        assert_eq!(estimate.fields, 3000);
+        assert_eq!(estimate.input_bytes, file_size);
+        assert!(estimate.index_bytes >= 3000 * size_of::<FieldRef>() as u64);
+
+        // A cut-off last line is left out of the sample.
+        let mut cut = head.clone();
+        cut.extend_from_slice(b"{\"level\":");
+        assert_eq!(
+            Estimate::of(&cut, file_size, LogFormat::Json, 8).records,
+            1000
+        );
+
+        let plain = b"2025-02-12T10:31:45Z INFO api: started\n".repeat(50);
+        let estimate = Estimate::of(&plain, plain.len() as u64 * 4, LogFormat::PlainText, 8);
+        assert_eq!((estimate.records, estimate.fields), (200, 0));
+        assert_eq!(
+            Estimate::of(b"", 100, LogFormat::Json, 8),
+            Estimate::default()
+        );
+    }
+}
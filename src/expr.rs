@@ -0,0 +1,640 @@
+use crate::emit::{EmitRecord, FieldValue, unescape_json};
+use crate::ip::{Cidr, parse_ipv4};
+use crate::numbers::{self, NumberStyle};
+
+/// Result of evaluating an expression. Strings that look like numbers take
+/// part in arithmetic and numeric comparisons.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(Vec<u8>),
+}
+
+impl Value {
+    /// Text in a field with a `--numbers` style is a number when it reads as
+    /// one in that style.
+    fn from_field(value: FieldValue<'_>, style: NumberStyle) -> Value {
+        match value {
+            FieldValue::Escaped(v) if v.contains(&b'\\') => {
+                Value::from_field(FieldValue::Text(unescape_json(v).as_bytes()), style)
+            }
+            FieldValue::Text(v) | FieldValue::Escaped(v) if style != NumberStyle::Plain => {
+                numbers::parse(v, style).map_or_else(|| Value::Str(v.to_vec()), Value::Num)
+            }
+            FieldValue::Text(v) | FieldValue::Escaped(v) => Value::Str(v.to_vec()),
+            FieldValue::Literal(b"true") => Value::Bool(true),
+            FieldValue::Literal(b"false") => Value::Bool(false),
+            FieldValue::Literal(b"null") => Value::Null,
+            FieldValue::Literal(v) => {
+                parse_num(v).map_or_else(|| Value::Str(v.to_vec()), Value::Num)
+            }
+            FieldValue::Timestamp(ts) => Value::Num(ts as f64),
+        }
+    }
+
+    pub fn as_num(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Str(s) => parse_num(s),
+            Value::Bool(b) => Some(*b as u8 as f64),
+            Value::Null => None,
+        }
+    }
+
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Num(n) => *n != 0.0 && !n.is_nan(),
+            Value::Str(s) => !s.is_empty(),
+        }
+    }
+
+    pub fn write_json(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Null => out.extend_from_slice(b"null"),
+            Value::Bool(b) => out.extend_from_slice(if *b { b"true" } else { b"false" }),
+            Value::Num(n) if !n.is_finite() => out.extend_from_slice(b"null"),
+            Value::Num(n) if n.fract() == 0.0 && n.abs() < 9.0e15 => {
+                out.extend_from_slice((*n as i64).to_string().as_bytes())
+            }
+            Value::Num(n) => out.extend_from_slice(n.to_string().as_bytes()),
+            Value::Str(s) => crate::emit::write_json_string(s, out),
+        }
+    }
+}
+
+fn parse_num(bytes: &[u8]) -> Option<f64> {
+    std::str::from_utf8(bytes).ok()?.trim().parse().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Lit(Value),
+    Field(Vec<u8>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+    In(Box<Expr>, Vec<Value>),
+    /// `x in 10.0.0.0/8` or `x in (10.0.0.0/8, 192.168.1.5)`: the value
+    /// is read as a dotted quad; anything else is not in the ranges.
+    InCidr(Box<Expr>, Vec<Cidr>),
+    /// `x?`: the record has the field, even if its value is null.
+    Exists(Vec<u8>),
+}
+
+impl Expr {
+    /// Grammar, loosest first: `or`, `and`, comparisons (`== != < <= > >=`,
+    /// `=` for `==`, and `x in (a, b)` or `x not in (a, b)`, where IPv4
+    /// addresses and CIDR ranges match by address), `+ -`, `* / %`, unary
+    /// `-`/`not`, then numbers, quoted strings, IPv4 addresses, field names
+    /// (`x?` when the field is present, `x!` when it is missing), JSON
+    /// Pointers such as `/context/user` and parentheses.
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(tok) => Err(format!("unexpected {:?}", tok)),
+        }
+    }
+
+    /// `lookup` resolves field names; unknown fields are `Null`.
+    pub fn eval(&self, lookup: &dyn Fn(&[u8]) -> Option<Value>) -> Value {
+        match self {
+            Expr::Lit(v) => v.clone(),
+            Expr::Field(name) => lookup(name).unwrap_or(Value::Null),
+            Expr::Exists(name) => Value::Bool(lookup(name).is_some()),
+            Expr::Neg(e) => e
+                .eval(lookup)
+                .as_num()
+                .map_or(Value::Null, |n| Value::Num(-n)),
+            Expr::Not(e) => Value::Bool(!e.eval(lookup).truthy()),
+            Expr::In(e, items) => {
+                let v = e.eval(lookup);
+                Value::Bool(items.iter().any(|item| equal(&v, item)))
+            }
+            Expr::InCidr(e, ranges) => {
+                let addr = match e.eval(lookup) {
+                    Value::Str(s) => parse_ipv4(s.trim_ascii()),
+                    _ => None,
+                };
+                Value::Bool(addr.is_some_and(|addr| ranges.iter().any(|r| r.contains(addr))))
+            }
+            Expr::Bin(BinOp::And, a, b) => {
+                Value::Bool(a.eval(lookup).truthy() && b.eval(lookup).truthy())
+            }
+            Expr::Bin(BinOp::Or, a, b) => {
+                Value::Bool(a.eval(lookup).truthy() || b.eval(lookup).truthy())
+            }
+            Expr::Bin(op, a, b) => binary(*op, a.eval(lookup), b.eval(lookup)),
+        }
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Str(x), Value::Str(y)) => x == y,
+        (Value::Null, Value::Null) => true,
+        (Value::Null, _) | (_, Value::Null) => false,
+        _ => match (a.as_num(), b.as_num()) {
+            (Some(x), Some(y)) => x == y,
+            _ => false,
+        },
+    }
+}
+
+fn binary(op: BinOp, a: Value, b: Value) -> Value {
+    let ordering = || match (&a, &b) {
+        (Value::Str(x), Value::Str(y)) if parse_num(x).is_none() || parse_num(y).is_none() => {
+            Some(x.cmp(y))
+        }
+        _ => a.as_num()?.partial_cmp(&b.as_num()?),
+    };
+    match op {
+        BinOp::Eq => Value::Bool(equal(&a, &b)),
+        BinOp::Ne => Value::Bool(!equal(&a, &b)),
+        BinOp::Lt => Value::Bool(ordering().is_some_and(|o| o.is_lt())),
+        BinOp::Le => Value::Bool(ordering().is_some_and(|o| o.is_le())),
+        BinOp::Gt => Value::Bool(ordering().is_some_and(|o| o.is_gt())),
+        BinOp::Ge => Value::Bool(ordering().is_some_and(|o| o.is_ge())),
+        _ => {
+            let (Some(x), Some(y)) = (a.as_num(), b.as_num()) else {
+                return Value::Null;
+            };
+            Value::Num(match op {
+                BinOp::Add => x + y,
+                BinOp::Sub => x - y,
+                BinOp::Mul => x * y,
+                BinOp::Div if y == 0.0 => return Value::Null,
+                BinOp::Div => x / y,
+                BinOp::Rem if y == 0.0 => return Value::Null,
+                BinOp::Rem => x % y,
+                _ => unreachable!(),
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Str(Vec<u8>),
+    Ident(Vec<u8>),
+    /// A dotted quad, possibly with a `/prefix`.
+    Ip(String),
+    Op(&'static str),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    const OPS: [&str; 17] = [
+        "==", "!=", "<=", ">=", "<", ">", "=", "+", "-", "*", "/", "%", "(", ")", ",", "!", "?",
+    ];
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    'next: while i < bytes.len() {
+        let b = bytes[i];
+        if b.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if b == b'"' || b == b'\'' {
+            let end = bytes[i + 1..]
+                .iter()
+                .position(|&c| c == b)
+                .ok_or("unterminated string")?;
+            tokens.push(Token::Str(bytes[i + 1..i + 1 + end].to_vec()));
+            i += end + 2;
+            continue;
+        }
+        if b.is_ascii_digit() || (b == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)) {
+            let mut len = bytes[i..]
+                .iter()
+                .position(|c| !(c.is_ascii_digit() || *c == b'.'))
+                .unwrap_or(bytes.len() - i);
+            if bytes[i..i + len].iter().filter(|&&c| c == b'.').count() == 3 {
+                if bytes.get(i + len) == Some(&b'/') {
+                    len += 1 + bytes[i + len + 1..]
+                        .iter()
+                        .position(|c| !c.is_ascii_digit())
+                        .unwrap_or(bytes.len() - i - len - 1);
+                }
+                tokens.push(Token::Ip(text[i..i + len].to_string()));
+                i += len;
+                continue;
+            }
+            let n = text[i..i + len]
+                .parse()
+                .map_err(|_| format!("bad number '{}'", &text[i..i + len]))?;
+            tokens.push(Token::Num(n));
+            i += len;
+            continue;
+        }
+        if b.is_ascii_alphabetic() || b == b'_' || b == b'@' {
+            let len = bytes[i..]
+                .iter()
+                .position(|c| !(c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'@')))
+                .unwrap_or(bytes.len() - i);
+            tokens.push(Token::Ident(bytes[i..i + len].to_vec()));
+            i += len;
+            continue;
+        }
+        // A `/` where an operand is expected starts a JSON Pointer, which
+        // runs to the next space or comparison.
+        let operand_expected = match tokens.last() {
+            Some(Token::Ident(word)) => [&b"and"[..], b"or", b"not", b"in"]
+                .iter()
+                .any(|kw| word.eq_ignore_ascii_case(kw)),
+            last => !matches!(
+                last,
+                Some(Token::Num(_) | Token::Str(_) | Token::Ip(_) | Token::Op(")" | "?"))
+            ),
+        };
+        if b == b'/' && operand_expected {
+            let len = bytes[i..]
+                .iter()
+                .position(|c| {
+                    c.is_ascii_whitespace()
+                        || matches!(c, b'=' | b'!' | b'?' | b'<' | b'>' | b'(' | b')' | b',')
+                })
+                .unwrap_or(bytes.len() - i);
+            tokens.push(Token::Ident(bytes[i..i + len].to_vec()));
+            i += len;
+            continue;
+        }
+        for op in OPS {
+            if text[i..].starts_with(op) {
+                tokens.push(Token::Op(op));
+                i += op.len();
+                continue 'next;
+            }
+        }
+        return Err(format!("unexpected character '{}'", b as char));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn eat_op(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if let Some(Token::Ident(id)) = self.peek()
+            && id.eq_ignore_ascii_case(word.as_bytes())
+        {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.and()?;
+        while self.eat_word("or") {
+            lhs = Expr::Bin(BinOp::Or, Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.comparison()?;
+        while self.eat_word("and") {
+            lhs = Expr::Bin(BinOp::And, Box::new(lhs), Box::new(self.comparison()?));
+        }
+        Ok(lhs)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let lhs = self.sum()?;
+        let negated = matches!(
+            (self.peek(), self.tokens.get(self.pos + 1)),
+            (Some(Token::Ident(not)), Some(Token::Ident(kw)))
+                if not.eq_ignore_ascii_case(b"not") && kw.eq_ignore_ascii_case(b"in")
+        );
+        if negated {
+            self.pos += 1;
+        }
+        if self.eat_word("in") {
+            let list = self.in_list(lhs)?;
+            return Ok(if negated {
+                Expr::Not(Box::new(list))
+            } else {
+                list
+            });
+        }
+        for (op, bin) in [
+            ("==", BinOp::Eq),
+            ("!=", BinOp::Ne),
+            ("<=", BinOp::Le),
+            (">=", BinOp::Ge),
+            ("<", BinOp::Lt),
+            (">", BinOp::Gt),
+            ("=", BinOp::Eq),
+        ] {
+            if self.eat_op(op) {
+                return Ok(Expr::Bin(bin, Box::new(lhs), Box::new(self.sum()?)));
+            }
+        }
+        Ok(lhs)
+    }
+
+    /// The list after `in`, tested against `lhs`.
+    fn in_list(&mut self, lhs: Expr) -> Result<Expr, String> {
+        if let Some(Token::Ip(range)) = self.peek() {
+            let range = Cidr::parse(range)?;
+            self.pos += 1;
+            return Ok(Expr::InCidr(Box::new(lhs), vec![range]));
+        }
+        if !self.eat_op("(") {
+            return Err("expected '(' or an IPv4 range after 'in'".to_string());
+        }
+        let (mut items, mut ranges) = (Vec::new(), Vec::new());
+        loop {
+            match self.next() {
+                Some(Token::Num(n)) => items.push(Value::Num(n)),
+                Some(Token::Str(s)) | Some(Token::Ident(s)) => items.push(Value::Str(s)),
+                Some(Token::Ip(range)) => ranges.push(Cidr::parse(&range)?),
+                other => return Err(format!("unexpected {:?} in list", other)),
+            }
+            if self.eat_op(")") {
+                break;
+            }
+            if !self.eat_op(",") {
+                return Err("expected ',' or ')' in list".to_string());
+            }
+        }
+        match (items.is_empty(), ranges.is_empty()) {
+            (_, true) => Ok(Expr::In(Box::new(lhs), items)),
+            (true, false) => Ok(Expr::InCidr(Box::new(lhs), ranges)),
+            (false, false) => Err("a list cannot mix IPv4 ranges and other values".to_string()),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = if self.eat_op("+") {
+                BinOp::Add
+            } else if self.eat_op("-") {
+                BinOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat_op("*") {
+                BinOp::Mul
+            } else if self.eat_op("/") {
+                BinOp::Div
+            } else if self.eat_op("%") {
+                BinOp::Rem
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat_op("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat_op("!") || self.eat_word("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Lit(Value::Num(n))),
+            Some(Token::Str(s)) => Ok(Expr::Lit(Value::Str(s))),
+            Some(Token::Ip(ip)) if !ip.contains('/') => Ok(Expr::Lit(Value::Str(ip.into_bytes()))),
+            Some(Token::Ip(range)) => Err(format!("CIDR range '{}' needs 'in'", range)),
+            Some(Token::Ident(id)) => Ok(match id.as_slice() {
+                b"true" => Expr::Lit(Value::Bool(true)),
+                b"false" => Expr::Lit(Value::Bool(false)),
+                b"null" => Expr::Lit(Value::Null),
+                _ if self.eat_op("?") => Expr::Exists(id),
+                _ if self.eat_op("!") => Expr::Not(Box::new(Expr::Exists(id))),
+                _ => Expr::Field(id),
+            }),
+            Some(Token::Op("(")) => {
+                let inner = self.or()?;
+                if !self.eat_op(")") {
+                    return Err("expected ')'".to_string());
+                }
+                Ok(inner)
+            }
+            Some(tok) => Err(format!("unexpected {:?}", tok)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// A named field computed per record, e.g. `duration_s=latency_ms/1000`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derivation {
+    pub name: Vec<u8>,
+    pub expr: Expr,
+}
+
+impl Derivation {
+    pub fn parse(rule: &str) -> Result<Derivation, String> {
+        match rule.split_once('=') {
+            Some((name, expr)) if !name.trim().is_empty() => Ok(Derivation {
+                name: name.trim().as_bytes().to_vec(),
+                expr: Expr::parse(expr)?,
+            }),
+            _ => Err(format!("expected name=expression, got '{}'", rule)),
+        }
+    }
+}
+
+/// Value of field `name` of record `i`: derived fields computed so far win
+/// over source fields.
+pub fn record_field<B: EmitRecord>(
+    batch: &B,
+    i: usize,
+    derived: &[(&[u8], Value)],
+    name: &[u8],
+) -> Option<Value> {
+    if let Some((_, value)) = derived.iter().find(|(n, _)| *n == name) {
+        return Some(value.clone());
+    }
+    let mut found = None;
+    let style = numbers::style_of(name);
+    batch.visit_path(i, name, &mut |value| {
+        found = Some(Value::from_field(value, style))
+    });
+    found
+}
+
+/// Evaluates `derivations` in order against record `i`; later ones can use
+/// earlier results.
+pub fn derive_fields<'d, B: EmitRecord>(
+    batch: &B,
+    i: usize,
+    derivations: &'d [Derivation],
+) -> Vec<(&'d [u8], Value)> {
+    let mut derived: Vec<(&[u8], Value)> = Vec::with_capacity(derivations.len());
+    for d in derivations {
+        let value = d.expr.eval(&|name| record_field(batch, i, &derived, name));
+        derived.push((&d.name, value));
+    }
+    derived
+}
+
+/// Whether record `i` satisfies `filter`, with derived fields in scope.
+pub fn record_matches<B: EmitRecord>(
+    batch: &B,
+    i: usize,
+    filter: &Expr,
+    derivations: &[Derivation],
+) -> bool {
+    let derived = derive_fields(batch, i, derivations);
+    filter
+        .eval(&|name| record_field(batch, i, &derived, name))
+        .truthy()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::LogFormat;
+    use crate::structured_orchestrator::parse_structured_mmap;
+
+    fn eval(text: &str) -> Value {
+        Expr::parse(text).unwrap().eval(&|name| match name {
+            b"latency_ms" => Some(Value::Str(b"1500".to_vec())),
+            b"level" => Some(Value::Str(b"error".to_vec())),
+            b"client" => Some(Value::Str(b"10.0.9.9".to_vec())),
+            b"/context/user" => Some(Value::Str(b"john".to_vec())),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_expression_evaluation() {
+        assert_eq!(eval("latency_ms / 1000"), Value::Num(1.5));
+        assert_eq!(eval("1 + 2 * 3 - -1"), Value::Num(8.0));
+        assert_eq!(eval("(1 + 2) * 3 % 4"), Value::Num(1.0));
+        assert_eq!(eval("level in (error, fatal)"), Value::Bool(true));
+        assert_eq!(eval("level in ('warn')"), Value::Bool(false));
+        assert_eq!(eval("latency_ms > 999 and not missing"), Value::Bool(true));
+        assert_eq!(
+            eval("missing == null or level != \"error\""),
+            Value::Bool(true)
+        );
+        assert_eq!(eval("missing + 1"), Value::Null);
+        assert_eq!(eval("1 / 0"), Value::Null);
+        assert_eq!(eval("level < 'f'"), Value::Bool(true));
+        assert_eq!(eval("client in 10.0.0.0/8"), Value::Bool(true));
+        assert_eq!(eval("client in 10.1.0.0/16"), Value::Bool(false));
+        assert_eq!(
+            eval("client in (192.168.0.0/16, 10.0.9.9)"),
+            Value::Bool(true)
+        );
+        assert_eq!(eval("client == 10.0.9.9"), Value::Bool(true));
+        assert_eq!(eval("level in 10.0.0.0/8"), Value::Bool(false));
+        assert_eq!(eval("missing in 0.0.0.0/0"), Value::Bool(false));
+        assert_eq!(eval("latency_ms / 1000 > 1.2"), Value::Bool(true));
+        assert_eq!(eval("/context/user='john'"), Value::Bool(true));
+        assert_eq!(
+            eval("/context/user != 'jane' and level = 'error'"),
+            Value::Bool(true)
+        );
+        assert_eq!(eval("latency_ms / 1000 / 3"), Value::Num(0.5));
+        assert_eq!(eval("level = 'error' and request_id!"), Value::Bool(true));
+        assert_eq!(eval("level? and not latency_ms!"), Value::Bool(true));
+        assert_eq!(eval("request_id? or /context/user!"), Value::Bool(false));
+        assert_eq!(eval("level not in (warn, info)"), Value::Bool(true));
+        assert_eq!(eval("missing not in (x)"), Value::Bool(true));
+        assert_eq!(eval("client not in 10.0.0.0/8"), Value::Bool(false));
+        assert_eq!(eval("level!='error'"), Value::Bool(false));
+
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("a in b").is_err());
+        assert!(Expr::parse("'open").is_err());
+        assert!(Expr::parse("a ; b").is_err());
+        assert!(Expr::parse("ip in 10.0.0.0/40").is_err());
+        assert!(Expr::parse("ip in (10.0.0.0/8, web)").is_err());
+        assert!(Expr::parse("ip == 10.0.0.0/8").is_err());
+        assert!(Expr::parse("a not b").is_err());
+    }
+
+    #[test]
+    fn test_derivations_chain_over_records() {
+        let data = b"level=error latency_ms=2500\nlevel=info latency_ms=20\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Logfmt));
+        let batch = &result.batches[0];
+        let derivations = [
+            Derivation::parse("duration_s=latency_ms/1000").unwrap(),
+            Derivation::parse("slow = duration_s >= 1").unwrap(),
+            Derivation::parse("is_err=level in (error,fatal)").unwrap(),
+        ];
+
+        let derived = derive_fields(batch, 0, &derivations);
+        assert_eq!(derived[0], (&b"duration_s"[..], Value::Num(2.5)));
+        assert_eq!(derived[1], (&b"slow"[..], Value::Bool(true)));
+        assert_eq!(derived[2], (&b"is_err"[..], Value::Bool(true)));
+
+        let filter = Expr::parse("slow or is_err").unwrap();
+        assert!(record_matches(batch, 0, &filter, &derivations));
+        assert!(!record_matches(batch, 1, &filter, &derivations));
+        assert!(Derivation::parse("=1").is_err());
+    }
+
+    #[test]
+    fn test_escaped_values_compare_unescaped() {
+        let data = b"{\"msg\":\"a\\\"b\",\"path\":\"C:\\\\tmp\"}\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Json));
+        let batch = &result.batches[0];
+        let matches = |text: &str| record_matches(batch, 0, &Expr::parse(text).unwrap(), &[]);
+        assert!(matches("msg = 'a\"b'"));
+        assert!(matches("path = 'C:\\tmp'"));
+        assert!(!matches("msg = 'a\\\"b'"));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::data::BatchRecords;
+use crate::emit::write_json_string;
+use crate::manifest::Xxh64;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Bytes copied from the input per read.
+const COPY_CHUNK: usize = 1 << 20;
+
+/// A run of input bytes copied to the extract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// Where the run starts in its input file.
+    pub offset: u64,
+    pub len: u64,
+    pub records: u64,
+    /// Where the run starts in the extract.
+    pub output_offset: u64,
+    pub checksum: u64,
+}
+
+struct Output<W> {
+    out: W,
+    /// Each input with its size and the regions taken from it.
+    sources: Vec<(String, u64, Vec<Region>)>,
+    bytes: u64,
+    records: u64,
+    whole: Xxh64,
+}
+
+/// `--extract-bytes`: copies the byte ranges of the input that hold matched
+/// records, unchanged, into one file, and describes where each range came
+/// from in a manifest. Records next to each other in the input end up in one
+/// region; each region ends with its last record's line terminator.
+pub struct ByteExtract<W: Write = BufWriter<File>> {
+    output: String,
+    /// Start, end and record count of the ranges matched in the current
+    /// input, in no particular order.
+    pending: Mutex<Vec<(u64, u64, u64)>>,
+    out: Mutex<Output<W>>,
+}
+
+impl ByteExtract {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Ok(ByteExtract::new(
+            BufWriter::new(File::create(path)?),
+            &path.display().to_string(),
+        ))
+    }
+}
+
+impl<W: Write> ByteExtract<W> {
+    /// `output` names the extract in the manifest.
+    pub fn new(out: W, output: &str) -> Self {
+        ByteExtract {
+            output: output.to_string(),
+            pending: Mutex::new(Vec::new()),
+            out: Mutex::new(Output {
+                out,
+                sources: Vec::new(),
+                bytes: 0,
+                records: 0,
+                whole: Xxh64::new(0),
+            }),
+        }
+    }
+
+    pub fn add_records<B: BatchRecords>(&self, batch: &B, records: &[u32]) {
+        let mut local: Vec<(u64, u64, u64)> = Vec::new();
+        for &i in records {
+            let i = i as usize;
+            let start = batch.input_offset() + batch.record_line(i).offset;
+            // Past the line terminator; clamped to the file when copied.
+            let end = start + batch.record_raw(i).len() as u64 + 1;
+            match local.last_mut() {
+                Some(last) if last.1 == start => {
+                    last.1 = end;
+                    last.2 += 1;
+                }
+                _ => local.push((start, end, 1)),
+            }
+        }
+        self.pending.lock().unwrap().extend(local);
+    }
+
+    /// Copies what was matched in `file` since the last call into the
+    /// extract, merging touching ranges; `source` names it in the manifest.
+    /// Returns the number of regions written.
+    pub fn copy_regions(&self, source: &str, file: &File) -> io::Result<usize> {
+        let mut ranges = std::mem::take(&mut *self.pending.lock().unwrap());
+        ranges.sort_unstable();
+        let size = file.metadata()?.len();
+        let mut merged: Vec<(u64, u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end, records) in ranges {
+            let end = end.min(size);
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => {
+                    last.1 = last.1.max(end);
+                    last.2 += records;
+                }
+                _ => merged.push((start, end, records)),
+            }
+        }
+
+        let mut state = self.out.lock().unwrap();
+        let state = &mut *state;
+        let mut regions = Vec::with_capacity(merged.len());
+        let mut buf = vec![0u8; COPY_CHUNK];
+        for (start, end, records) in merged {
+            let mut checksum = Xxh64::new(0);
+            let mut at = start;
+            while at < end {
+                let n = ((end - at) as usize).min(COPY_CHUNK);
+                file.read_exact_at(&mut buf[..n], at)?;
+                state.out.write_all(&buf[..n])?;
+                checksum.update(&buf[..n]);
+                state.whole.update(&buf[..n]);
+                at += n as u64;
+            }
+            regions.push(Region {
+                offset: start,
+                len: end - start,
+                records,
+                output_offset: state.bytes,
+                checksum: checksum.digest(),
+            });
+            state.bytes += end - start;
+            state.records += records;
+        }
+        let count = regions.len();
+        if count > 0 {
+            state.sources.push((source.to_string(), size, regions));
+        }
+        Ok(count)
+    }
+
+    /// Flushes the extract and writes its manifest to `manifest`.
+    pub fn finish(self, manifest: &mut impl Write) -> io::Result<ExtractSummary> {
+        let mut state = self.out.into_inner().unwrap();
+        state.out.flush()?;
+        let mut json = Vec::new();
+        json.extend_from_slice(b"{\"output\":");
+        write_json_string(self.output.as_bytes(), &mut json);
+        json.extend_from_slice(
+            format!(
+                ",\"algorithm\":\"xxh64\",\"bytes\":{},\"records\":{},\"checksum\":\"{:016x}\",\"sources\":[",
+                state.bytes,
+                state.records,
+                state.whole.digest()
+            )
+            .as_bytes(),
+        );
+        let mut regions = 0;
+        for (n, (source, size, source_regions)) in state.sources.iter().enumerate() {
+            if n > 0 {
+                json.push(b',');
+            }
+            json.extend_from_slice(b"\n{\"source\":");
+            write_json_string(source.as_bytes(), &mut json);
+            json.extend_from_slice(format!(",\"size\":{},\"regions\":[", size).as_bytes());
+            for (k, region) in source_regions.iter().enumerate() {
+                if k > 0 {
+                    json.push(b',');
+                }
+                json.extend_from_slice(
+                    format!(
+                        "\n{{\"offset\":{},\"len\":{},\"records\":{},\"output_offset\":{},\"checksum\":\"{:016x}\"}}",
+                        region.offset,
+                        region.len,
+                        region.records,
+                        region.output_offset,
+                        region.checksum
+                    )
+                    .as_bytes(),
+                );
+            }
+            json.extend_from_slice(b"]}");
+            regions += source_regions.len();
+        }
+        json.extend_from_slice(b"]}\n");
+        manifest.write_all(&json)?;
+        manifest.flush()?;
+        Ok(ExtractSummary {
+            bytes: state.bytes,
+            records: state.records,
+            regions,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractSummary {
+    pub bytes: u64,
+    pub records: u64,
+    pub regions: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::LogLevel;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_extract_copies_matched_regions() {
+        let levels = ["info", "error", "error", "info", "info", "error"];
+        let mut data = String::new();
+        for (n, level) in levels.iter().enumerate() {
+            data.push_str(&format!(
+                "ts=2025-02-12T10:00:0{}Z level={} n={}\n",
+                n, level, n
+            ));
+        }
+        data.pop();
+        let path = std::env::temp_dir().join(format!("pandora-extract-{}.log", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+
+        let extract = ByteExtract::new(Vec::new(), "slice.bin");
+        let add = |batch: &StructuredBatch, records: &[u32]| {
+            let errors: Vec<u32> = records
+                .iter()
+                .copied()
+                .filter(|&i| batch.record_level(i as usize) == LogLevel::Error)
+                .collect();
+            extract.add_records(batch, &errors);
+        };
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data.as_bytes(), 1, Some(LogFormat::Logfmt), &control);
+        let regions = extract
+            .copy_regions("app.log", &File::open(&path).unwrap())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(regions, 2);
+
+        let mut manifest = Vec::new();
+        let state = extract.out.lock().unwrap();
+        let copied = String::from_utf8(state.out.clone()).unwrap();
+        let sources = state.sources.clone();
+        drop(state);
+        let lines: Vec<&str> = data.split_inclusive('\n').collect();
+        assert_eq!(copied, [lines[1], lines[2], lines[5]].concat());
+        let regions = &sources[0].2;
+        assert_eq!(regions[0].offset, lines[0].len() as u64);
+        assert_eq!(regions[0].records, 2);
+        assert_eq!(regions[1].len, lines[5].len() as u64);
+        assert_eq!(regions[1].output_offset, regions[0].len);
+
+        let summary = extract.finish(&mut manifest).unwrap();
+        assert_eq!(
+            summary,
+            ExtractSummary {
+                bytes: copied.len() as u64,
+                records: 3,
+                regions: 2
+            }
+        );
+        let manifest = String::from_utf8(manifest).unwrap();
+        assert!(manifest.starts_with("{\"output\":\"slice.bin\",\"algorithm\":\"xxh64\""));
+        assert!(manifest.contains("{\"source\":\"app.log\",\"size\":"));
+    }
+}
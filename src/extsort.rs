@@ -0,0 +1,409 @@
+use crate::data::LogLevel;
+use crate::emit::{ChunkStats, EmitChunk, EmitFormat, EmitRecord, EmitRules, emit_chunk};
+use crate::sink::Tee;
+use crate::split::SplitKey;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Memory for buffered records unless `--sort-mem` says otherwise.
+pub const DEFAULT_SORT_MEM_MB: usize = 256;
+
+/// Runs merged at once; more runs are merged in several passes so the open
+/// file count stays bounded.
+const MERGE_FAN_IN: usize = 64;
+
+/// Serialized bytes per chunk handed to the sinks after the merge.
+const OUT_CHUNK_BYTES: usize = 1 << 20;
+
+/// Partition length written for records without one.
+const NO_PARTITION: u32 = u32::MAX;
+
+/// One emitted record. Records sort by timestamp, then by input offset;
+/// records without a timestamp sort first.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    timestamp: u64,
+    offset: u64,
+    level: u8,
+    partition: Option<Vec<u8>>,
+    /// Serialized record with its terminator.
+    line: Vec<u8>,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.line.len() + self.partition.as_ref().map_or(0, Vec::len) + 64
+    }
+
+    fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.timestamp.to_le_bytes())?;
+        out.write_all(&self.offset.to_le_bytes())?;
+        out.write_all(&[self.level])?;
+        match &self.partition {
+            Some(p) => {
+                out.write_all(&(p.len() as u32).to_le_bytes())?;
+                out.write_all(p)?;
+            }
+            None => out.write_all(&NO_PARTITION.to_le_bytes())?,
+        }
+        out.write_all(&(self.line.len() as u32).to_le_bytes())?;
+        out.write_all(&self.line)
+    }
+
+    /// `None` at the end of a run.
+    fn read_from(input: &mut impl Read) -> io::Result<Option<Entry>> {
+        let mut head = [0u8; 17];
+        match input.read_exact(&mut head) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut len = [0u8; 4];
+        input.read_exact(&mut len)?;
+        let partition = match u32::from_le_bytes(len) {
+            NO_PARTITION => None,
+            n => {
+                let mut p = vec![0; n as usize];
+                input.read_exact(&mut p)?;
+                Some(p)
+            }
+        };
+        input.read_exact(&mut len)?;
+        let mut line = vec![0; u32::from_le_bytes(len) as usize];
+        input.read_exact(&mut line)?;
+        Ok(Some(Entry {
+            timestamp: u64::from_le_bytes(head[..8].try_into().unwrap()),
+            offset: u64::from_le_bytes(head[8..16].try_into().unwrap()),
+            level: head[16],
+            partition,
+            line,
+        }))
+    }
+}
+
+struct Run {
+    reader: BufReader<File>,
+    path: PathBuf,
+}
+
+impl Run {
+    fn open(path: PathBuf) -> io::Result<Run> {
+        Ok(Run {
+            reader: BufReader::with_capacity(256 * 1024, File::open(&path)?),
+            path,
+        })
+    }
+}
+
+/// K-way merge over sorted runs and an already sorted in-memory tail.
+fn merge(
+    runs: Vec<PathBuf>,
+    tail: Vec<Entry>,
+    out: &mut dyn FnMut(Entry) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut readers = runs
+        .into_iter()
+        .map(Run::open)
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut tail = tail.into_iter();
+    let tail_source = readers.len();
+    let mut heap = BinaryHeap::new();
+    for (source, run) in readers.iter_mut().enumerate() {
+        if let Some(entry) = Entry::read_from(&mut run.reader)? {
+            heap.push(Reverse((entry, source)));
+        }
+    }
+    if let Some(entry) = tail.next() {
+        heap.push(Reverse((entry, tail_source)));
+    }
+    while let Some(Reverse((entry, source))) = heap.pop() {
+        let next = match readers.get_mut(source) {
+            Some(run) => Entry::read_from(&mut run.reader)?,
+            None => tail.next(),
+        };
+        if let Some(next) = next {
+            heap.push(Reverse((next, source)));
+        }
+        out(entry)?;
+    }
+    for run in readers {
+        let _ = fs::remove_file(&run.path);
+    }
+    Ok(())
+}
+
+fn run_path(dir: &Path, next_run: &mut usize) -> PathBuf {
+    *next_run += 1;
+    dir.join(format!("run-{:06}.bin", next_run))
+}
+
+fn write_run(mut buffer: Vec<Entry>, path: &Path) -> io::Result<()> {
+    buffer.sort_unstable();
+    let mut out = BufWriter::with_capacity(256 * 1024, File::create(path)?);
+    for entry in &buffer {
+        entry.write_to(&mut out)?;
+    }
+    out.flush()
+}
+
+#[derive(Default)]
+struct State {
+    buffer: Vec<Entry>,
+    buffered_bytes: usize,
+    runs: Vec<PathBuf>,
+    next_run: usize,
+    records: u64,
+    /// Stats of every chunk added, sent with the last chunk out.
+    stats: Option<ChunkStats>,
+    error: Option<io::Error>,
+}
+
+/// `--sort-time`: holds emitted records back and hands them to the sinks in
+/// timestamp order once every file is parsed. Records are buffered up to a
+/// memory budget, then sorted and spilled to a run file; `finish` merges
+/// the runs, in several passes when there are many.
+pub struct ExternalSort {
+    dir: PathBuf,
+    budget: usize,
+    state: Mutex<State>,
+}
+
+impl ExternalSort {
+    /// Spills into a fresh directory under `parent`, removed by `finish`.
+    pub fn new(parent: &Path, budget_bytes: usize) -> io::Result<ExternalSort> {
+        let dir = parent.join(format!("pandora-sort-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(ExternalSort {
+            dir,
+            budget: budget_bytes.max(1),
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    pub fn add_records<B: EmitRecord>(
+        &self,
+        batch: &B,
+        records: &[u32],
+        rules: &EmitRules,
+        format: EmitFormat,
+        split_key: Option<&SplitKey>,
+    ) {
+        if records.is_empty() {
+            return;
+        }
+        let mut key = Vec::new();
+        let mut local = Vec::with_capacity(records.len());
+        let mut stats: Option<ChunkStats> = None;
+        let mut bytes = 0;
+        // One record at a time: raw records may hold newlines and end with
+        // the record separator, so the chunk cannot be split afterwards.
+        for i in records {
+            let chunk = emit_chunk(batch, std::slice::from_ref(i), rules, format);
+            if chunk.records == 0 {
+                continue;
+            }
+            if let Some(chunk_stats) = &chunk.stats {
+                stats.get_or_insert_default().merge(chunk_stats);
+            }
+            let i = *i as usize;
+            let partition = split_key.map(|split| {
+                split.write_key(batch, i, &mut key);
+                key.clone()
+            });
+            let entry = Entry {
+                timestamp: batch.record_timestamp(i).unwrap_or(0),
+                offset: batch.input_offset() + batch.record_line(i).offset,
+                level: batch.record_level(i) as u8,
+                partition,
+                line: chunk.ndjson,
+            };
+            bytes += entry.size();
+            local.push(entry);
+        }
+
+        // A full buffer is sorted and written outside the lock, so other
+        // workers keep buffering meanwhile.
+        let full = {
+            let mut state = self.state.lock().unwrap();
+            state.records += local.len() as u64;
+            if let Some(stats) = &stats {
+                state.stats.get_or_insert_default().merge(stats);
+            }
+            state.buffered_bytes += bytes;
+            state.buffer.append(&mut local);
+            (state.buffered_bytes >= self.budget).then(|| {
+                state.buffered_bytes = 0;
+                let path = run_path(&self.dir, &mut state.next_run);
+                (std::mem::take(&mut state.buffer), path)
+            })
+        };
+        if let Some((buffer, path)) = full {
+            let written = write_run(buffer, &path);
+            let mut state = self.state.lock().unwrap();
+            match written {
+                Ok(()) => state.runs.push(path),
+                Err(e) => {
+                    state.error.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    /// Merges everything buffered and spilled into `tee` in timestamp order,
+    /// grouping consecutive records of one partition into a chunk. Returns
+    /// the number of records sent and of runs spilled.
+    pub fn finish(self, tee: &Tee) -> io::Result<SortSummary> {
+        let mut state = self.state.into_inner().unwrap();
+        let result = (|| {
+            if let Some(e) = state.error.take() {
+                return Err(e);
+            }
+            let spilled = state.runs.len();
+            // Collapse the oldest runs until one pass can merge the rest.
+            while state.runs.len() > MERGE_FAN_IN {
+                let group: Vec<PathBuf> = state.runs.drain(..MERGE_FAN_IN).collect();
+                let path = run_path(&self.dir, &mut state.next_run);
+                let mut out = BufWriter::with_capacity(256 * 1024, File::create(&path)?);
+                merge(group, Vec::new(), &mut |entry| entry.write_to(&mut out))?;
+                out.flush()?;
+                state.runs.push(path);
+            }
+
+            let mut tail = std::mem::take(&mut state.buffer);
+            tail.sort_unstable();
+            let mut chunk = EmitChunk::default();
+            merge(std::mem::take(&mut state.runs), tail, &mut |entry| {
+                if chunk.records > 0
+                    && (chunk.partition != entry.partition || chunk.ndjson.len() >= OUT_CHUNK_BYTES)
+                {
+                    tee.send(std::mem::take(&mut chunk));
+                }
+                chunk.partition = entry.partition;
+                chunk.ndjson.extend_from_slice(&entry.line);
+                chunk.records += 1;
+                let level = LogLevel::ALL
+                    .into_iter()
+                    .find(|&l| l as u8 == entry.level)
+                    .unwrap_or(LogLevel::Unknown);
+                chunk.levels.record(level);
+                Ok(())
+            })?;
+            if chunk.records > 0 {
+                chunk.stats = state.stats.take();
+                tee.send(chunk);
+            }
+            Ok(SortSummary {
+                records: state.records,
+                runs: spilled,
+            })
+        })();
+        let _ = fs::remove_dir_all(&self.dir);
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortSummary {
+    pub records: u64,
+    /// Runs spilled to disk; 0 when everything fit the memory budget.
+    pub runs: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::sink::{SinkSpec, Tee};
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_external_sort_spills_and_merges() {
+        // Timestamps cycle so every chunk and run is out of order.
+        let mut data = String::new();
+        for n in 0..3000u64 {
+            let secs = (n * 7919) % 3000;
+            data.push_str(&format!(
+                "ts=2025-02-12T{:02}:{:02}:{:02}Z n={}\n",
+                secs / 3600,
+                secs % 3600 / 60,
+                secs % 60,
+                n
+            ));
+        }
+        let dir = std::env::temp_dir().join(format!("pandora-extsort-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let sorter = ExternalSort::new(&dir, 2048).unwrap();
+        let rules = EmitRules::default();
+        // Small slices and a tiny budget give well over one merge pass of runs.
+        let add = |batch: &StructuredBatch, records: &[u32]| {
+            for part in records.chunks(10) {
+                sorter.add_records(batch, part, &rules, EmitFormat::RawFiltered, None);
+            }
+        };
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data.as_bytes(), 4, Some(LogFormat::Logfmt), &control);
+
+        let out = dir.join("sorted.ndjson");
+        let spec = SinkSpec::parse(&format!("file:{}", out.display())).unwrap();
+        let tee = Tee::spawn(vec![spec], 4);
+        let summary = sorter.finish(&tee).unwrap();
+        tee.finish();
+        assert_eq!(summary.records, 3000);
+        assert!(summary.runs > MERGE_FAN_IN, "{} runs", summary.runs);
+
+        let text = fs::read_to_string(&out).unwrap();
+        let times: Vec<&str> = text.lines().map(|l| &l[3..23]).collect();
+        assert_eq!(times.len(), 3000);
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+        assert!(!fs::read_dir(&dir).unwrap().any(|e| {
+            e.unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with("pandora-sort-")
+        }));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sort_keeps_multiline_records_whole() {
+        let data = "ts,msg\n\
+                    2025-02-12T10:00:03Z,\"three\nlines\nhere\"\n\
+                    2025-02-12T10:00:01Z,one\n\
+                    2025-02-12T10:00:02Z,\"two\nlines\"\n";
+        let dir =
+            std::env::temp_dir().join(format!("pandora-extsort-multiline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let sorter = ExternalSort::new(&dir, 1 << 20).unwrap();
+        let rules = EmitRules::default();
+        let add = |batch: &StructuredBatch, records: &[u32]| {
+            sorter.add_records(batch, records, &rules, EmitFormat::RawFiltered, None);
+        };
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data.as_bytes(), 1, Some(LogFormat::Csv), &control);
+
+        let out = dir.join("sorted.csv");
+        let spec = SinkSpec::parse(&format!("file:{}", out.display())).unwrap();
+        let tee = Tee::spawn(vec![spec], 4);
+        assert_eq!(sorter.finish(&tee).unwrap().records, 3);
+        tee.finish();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "2025-02-12T10:00:01Z,one\n\
+             2025-02-12T10:00:02Z,\"two\nlines\"\n\
+             2025-02-12T10:00:03Z,\"three\nlines\nhere\"\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
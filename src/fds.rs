@@ -0,0 +1,182 @@
+use std::fmt;
+use std::io;
+
+/// Descriptors kept back from every budget for stdio, the input being
+/// parsed, its index and cache files, and the odd report file.
+pub const RESERVED: usize = 16;
+
+/// The process's soft `RLIMIT_NOFILE`, first raised towards the hard limit
+/// when it is below `wanted` (most shells leave it at 1024 where far more
+/// is allowed). `None` wants as many as the hard limit allows.
+pub fn raise_limit(wanted: Option<usize>) -> Option<usize> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None;
+    }
+    let target = wanted.map_or(limit.rlim_max, |n| {
+        libc::rlim_t::try_from(n).map_or(limit.rlim_max, |n| n.min(limit.rlim_max))
+    });
+    if limit.rlim_cur < target {
+        let raised = libc::rlimit {
+            rlim_cur: target,
+            ..limit
+        };
+        if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &raised) } == 0 {
+            limit = raised;
+        }
+    }
+    Some(usize::try_from(limit.rlim_cur).unwrap_or(usize::MAX))
+}
+
+/// Descriptors this process has open, from `/proc/self/fd`.
+pub fn open_count() -> Option<usize> {
+    // The directory handle itself is one of the entries.
+    Some(
+        std::fs::read_dir("/proc/self/fd")
+            .ok()?
+            .count()
+            .saturating_sub(1),
+    )
+}
+
+/// Whether `e` means the process (`EMFILE`) or the system (`ENFILE`) ran
+/// out of file descriptors.
+pub fn is_exhausted(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+}
+
+/// `e`, with how many descriptors are open and what to change when it
+/// says they ran out.
+pub fn explain(e: io::Error) -> io::Error {
+    if !is_exhausted(&e) {
+        return e;
+    }
+    let open = open_count().map_or_else(|| "?".to_string(), |n| n.to_string());
+    io::Error::new(
+        e.kind(),
+        format!(
+            "{} ({} descriptors open; lower --max-open-files, or raise --max-fds and ulimit -n)",
+            e, open
+        ),
+    )
+}
+
+/// Descriptors of a run, handed out up front to whatever holds them for
+/// the whole run so a short budget fails at start rather than with
+/// `EMFILE` midway.
+#[derive(Debug, Clone)]
+pub struct FdBudget {
+    limit: usize,
+    claims: Vec<(String, usize)>,
+}
+
+impl FdBudget {
+    pub fn new(limit: usize) -> FdBudget {
+        FdBudget {
+            limit,
+            claims: vec![("stdio and inputs".to_string(), RESERVED)],
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn free(&self) -> usize {
+        let claimed: usize = self.claims.iter().map(|(_, n)| n).sum();
+        self.limit.saturating_sub(claimed)
+    }
+
+    /// Takes `n` descriptors for `what`, or says why they are not there.
+    pub fn claim(&mut self, what: &str, n: usize) -> Result<(), BudgetError> {
+        if n > self.free() {
+            return Err(BudgetError {
+                what: what.to_string(),
+                needed: n,
+                budget: self.clone(),
+            });
+        }
+        if n > 0 {
+            self.claims.push((what.to_string(), n));
+        }
+        Ok(())
+    }
+
+    /// Takes as many of `want` descriptors as are free, at least `min`, for
+    /// a consumer that can make do with fewer.
+    pub fn claim_up_to(
+        &mut self,
+        what: &str,
+        min: usize,
+        want: usize,
+    ) -> Result<usize, BudgetError> {
+        let granted = want.min(self.free()).max(min);
+        self.claim(what, granted)?;
+        Ok(granted)
+    }
+}
+
+#[derive(Debug)]
+pub struct BudgetError {
+    what: String,
+    needed: usize,
+    budget: FdBudget,
+}
+
+impl fmt::Display for BudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let claims: Vec<String> = self
+            .budget
+            .claims
+            .iter()
+            .map(|(what, n)| format!("{} {}", n, what))
+            .collect();
+        write!(
+            f,
+            "{} needs {} file descriptor(s) but only {} of {} are left ({})",
+            self.what,
+            self.needed,
+            self.budget.free(),
+            self.budget.limit,
+            claims.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fd_budget() {
+        let mut budget = FdBudget::new(RESERVED + 10);
+        budget.claim("sinks", 3).unwrap();
+        assert_eq!(budget.free(), 7);
+        assert_eq!(budget.claim_up_to("partitions", 1, 64).unwrap(), 7);
+        assert_eq!(budget.free(), 0);
+
+        let err = budget.claim("--follow", 1).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "--follow needs 1 file descriptor(s) but only 0 of 26 are left \
+             (16 stdio and inputs, 3 sinks, 7 partitions)"
+        );
+        assert!(
+            FdBudget::new(RESERVED)
+                .claim_up_to("partitions", 1, 64)
+                .is_err()
+        );
+
+        let exhausted = io::Error::from_raw_os_error(libc::EMFILE);
+        assert!(is_exhausted(&exhausted));
+        assert!(explain(exhausted).to_string().contains("--max-fds"));
+        let other = io::Error::from_raw_os_error(libc::ENOENT);
+        assert!(!explain(other).to_string().contains("--max-fds"));
+        let current = raise_limit(Some(1)).unwrap();
+        assert!(current > 0);
+        assert_eq!(raise_limit(Some(current)), Some(current));
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::time::SystemTime;
+
+/// What identifies an input between opening it and finishing the parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileIdentity {
+    pub dev: u64,
+    pub ino: u64,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileIdentity {
+    pub fn of(meta: &Metadata) -> FileIdentity {
+        #[cfg(unix)]
+        let (dev, ino) = {
+            use std::os::unix::fs::MetadataExt;
+            (meta.dev(), meta.ino())
+        };
+        #[cfg(not(unix))]
+        let (dev, ino) = (0, 0);
+        FileIdentity {
+            dev,
+            ino,
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        }
+    }
+}
+
+/// How an input changed while it was parsed. Results always describe the
+/// file as it was when opened, i.e. bytes `[0, covered)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    /// Appended to, as live logs are; the new tail was not parsed.
+    Grew {
+        covered: u64,
+        now: u64,
+    },
+    Shrank {
+        covered: u64,
+        now: u64,
+    },
+    /// The path now names another file, typically after rotation.
+    Replaced {
+        covered: u64,
+    },
+    /// Same size but modified in place.
+    Rewritten {
+        covered: u64,
+    },
+}
+
+impl FileChange {
+    /// Compares the identity at open with the open descriptor now and with
+    /// whatever the path resolves to now (`None` if it is gone).
+    pub fn detect(
+        opened: &FileIdentity,
+        fd_now: &FileIdentity,
+        path_now: Option<&FileIdentity>,
+    ) -> Option<FileChange> {
+        let covered = opened.len;
+        if fd_now.len > covered {
+            Some(FileChange::Grew {
+                covered,
+                now: fd_now.len,
+            })
+        } else if fd_now.len < covered {
+            Some(FileChange::Shrank {
+                covered,
+                now: fd_now.len,
+            })
+        } else if path_now.is_none_or(|p| (p.dev, p.ino) != (opened.dev, opened.ino)) {
+            Some(FileChange::Replaced { covered })
+        } else if fd_now.modified != opened.modified {
+            Some(FileChange::Rewritten { covered })
+        } else {
+            None
+        }
+    }
+
+    /// Re-checks `file` (still open) and `path` against `opened`.
+    pub fn check(path: &str, file: &File, opened: &FileIdentity) -> Option<FileChange> {
+        let fd_now = FileIdentity::of(&file.metadata().ok()?);
+        let path_now = std::fs::metadata(path).ok().map(|m| FileIdentity::of(&m));
+        FileChange::detect(opened, &fd_now, path_now.as_ref())
+    }
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FileChange::Grew { covered, now } => write!(
+                f,
+                "grew from {} to {} bytes during the run; results cover bytes 0-{}, \
+                 bytes {}-{} were not parsed",
+                covered, now, covered, covered, now
+            ),
+            FileChange::Shrank { covered, now } => write!(
+                f,
+                "shrank from {} to {} bytes during the run; results cover bytes 0-{}",
+                covered, now, now
+            ),
+            FileChange::Replaced { covered } => write!(
+                f,
+                "was replaced during the run; results cover bytes 0-{} of the \
+                 original file",
+                covered
+            ),
+            FileChange::Rewritten { covered } => write!(
+                f,
+                "was modified in place during the run; bytes 0-{} may mix old \
+                 and new contents",
+                covered
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_file_changes() {
+        let opened = FileIdentity {
+            dev: 1,
+            ino: 42,
+            len: 1000,
+            modified: Some(SystemTime::UNIX_EPOCH),
+        };
+        let same = opened;
+        assert_eq!(FileChange::detect(&opened, &same, Some(&same)), None);
+
+        let grown = FileIdentity {
+            len: 1500,
+            ..opened
+        };
+        assert_eq!(
+            FileChange::detect(&opened, &grown, Some(&grown)),
+            Some(FileChange::Grew {
+                covered: 1000,
+                now: 1500
+            })
+        );
+
+        let rotated = FileIdentity {
+            ino: 43,
+            len: 0,
+            ..opened
+        };
+        assert_eq!(
+            FileChange::detect(&opened, &same, Some(&rotated)),
+            Some(FileChange::Replaced { covered: 1000 })
+        );
+        assert_eq!(
+            FileChange::detect(&opened, &same, None),
+            Some(FileChange::Replaced { covered: 1000 })
+        );
+
+        let touched = FileIdentity {
+            modified: Some(SystemTime::now()),
+            ..opened
+        };
+        assert_eq!(
+            FileChange::detect(&opened, &touched, Some(&touched)),
+            Some(FileChange::Rewritten { covered: 1000 })
+        );
+    }
+}
@@ -0,0 +1,664 @@
+use crate::data::{BatchRecords, LevelSummary, LogLevel};
+use crate::emit::EmitChunk;
+use crate::fixed_parser::FixedLayout;
+use crate::index::SparseIndex;
+use crate::readahead::Readahead;
+use crate::reorder::Reorder;
+use crate::structured::{DEFAULT_HOT_COLUMNS, RecordLimits};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelFilter {
+    Exactly(LogLevel),
+    AtLeast(LogLevel),
+}
+
+impl LevelFilter {
+    /// Parses `error` (exact match) or `error+` (error and more severe).
+    pub fn parse(spec: &str) -> Option<LevelFilter> {
+        let (name, at_least) = match spec.strip_suffix('+') {
+            Some(name) => (name, true),
+            None => (spec, false),
+        };
+        let level = LogLevel::from_name(name.as_bytes());
+        if level == LogLevel::Unknown {
+            return None;
+        }
+        Some(if at_least {
+            LevelFilter::AtLeast(level)
+        } else {
+            LevelFilter::Exactly(level)
+        })
+    }
+
+    #[inline(always)]
+    pub fn matches(self, level: LogLevel) -> bool {
+        match self {
+            LevelFilter::Exactly(want) => level == want,
+            LevelFilter::AtLeast(min) => level != LogLevel::Unknown && level.slot() >= min.slot(),
+        }
+    }
+
+    /// Number of matching records according to a batch summary, without
+    /// touching the records themselves.
+    pub fn count_in(self, summary: &LevelSummary) -> u64 {
+        LogLevel::ALL
+            .iter()
+            .filter(|&&level| self.matches(level))
+            .map(|&level| summary.count(level))
+            .sum()
+    }
+}
+
+impl std::fmt::Display for LevelFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LevelFilter::Exactly(level) => write!(f, "{}", level),
+            LevelFilter::AtLeast(level) => write!(f, "{}+", level),
+        }
+    }
+}
+
+/// Match budget shared by all workers. Once `limit` matches have been
+/// claimed, workers stop picking up new chunks.
+#[derive(Debug)]
+pub struct MatchLimit {
+    limit: u64,
+    matched: AtomicU64,
+}
+
+impl MatchLimit {
+    pub fn new(limit: u64) -> Self {
+        MatchLimit {
+            limit,
+            matched: AtomicU64::new(0),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        MatchLimit::new(u64::MAX)
+    }
+
+    #[inline]
+    pub fn is_reached(&self) -> bool {
+        self.matched.load(Ordering::Relaxed) >= self.limit
+    }
+
+    /// Claims one match; returns `false` once the budget is exhausted.
+    #[inline]
+    pub fn try_claim(&self) -> bool {
+        self.matched.fetch_add(1, Ordering::Relaxed) < self.limit
+    }
+
+    #[inline]
+    pub fn add(&self, n: u64) {
+        self.matched.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn matched(&self) -> u64 {
+        self.matched.load(Ordering::Relaxed).min(self.limit)
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        (self.limit != u64::MAX).then_some(self.limit)
+    }
+}
+
+/// Input bytes and records parsed so far, updated by the workers as they
+/// finish chunks, and a flag that stops them picking up new ones.
+#[derive(Debug, Default)]
+pub struct ParseProgress {
+    bytes: AtomicU64,
+    records: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl ParseProgress {
+    #[inline]
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_records(&self, records: u64) {
+        self.records.fetch_add(records, Ordering::Relaxed);
+    }
+
+    #[allow(dead_code)]
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    #[allow(dead_code)]
+    pub fn records(&self) -> u64 {
+        self.records.load(Ordering::Relaxed)
+    }
+
+    #[allow(dead_code)]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Per-level keep rates for `--sample-by-level`; unlisted levels keep every
+/// record. Whether a record is kept depends only on its byte offset in the
+/// input, so reruns with any thread count keep the same records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelSampler {
+    thresholds: [u64; LogLevel::COUNT],
+}
+
+impl LevelSampler {
+    /// Parses `info=0.01,debug=0.001,error=1.0`.
+    pub fn parse(spec: &str) -> Result<LevelSampler, String> {
+        let mut thresholds = [u64::MAX; LogLevel::COUNT];
+        for rule in spec.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let (name, rate) = rule
+                .split_once('=')
+                .ok_or_else(|| format!("expected level=rate, got '{}'", rule))?;
+            let level = LogLevel::from_name(name.trim().as_bytes());
+            if level == LogLevel::Unknown {
+                return Err(format!("unknown level '{}'", name));
+            }
+            let rate: f64 = rate
+                .trim()
+                .parse()
+                .ok()
+                .filter(|r| (0.0..=1.0).contains(r))
+                .ok_or_else(|| format!("rate must be between 0 and 1, got '{}'", rate))?;
+            thresholds[level.slot()] = if rate >= 1.0 {
+                u64::MAX
+            } else {
+                (rate * u64::MAX as f64) as u64
+            };
+        }
+        Ok(LevelSampler { thresholds })
+    }
+
+    #[inline]
+    pub fn keep(&self, level: LogLevel, offset: u64) -> bool {
+        let threshold = self.thresholds[level.slot()];
+        threshold == u64::MAX || crate::dedup::fmix64(offset) < threshold
+    }
+}
+
+pub type RecordCallback<'a, B> = dyn Fn(&B, usize) + Sync + 'a;
+
+pub type BatchCallback<'a, B> = dyn Fn(&B, &[u32]) + Sync + 'a;
+
+pub type RejectCallback<'a> = dyn Fn(u64, &[u8]) + Sync + 'a;
+
+pub type RecordPredicate<'a, B> = dyn Fn(&B, usize) -> bool + Sync + 'a;
+
+/// Per-record hooks applied by the orchestrators after each chunk is parsed.
+/// `on_match` is invoked from worker threads for every record that passes
+/// the filters, at most `limit` times in total; `on_batch` then receives the
+/// indices of those records once per batch. With `reverse`, chunks are
+/// dispatched from the end of the input and records are visited newest-first.
+/// `since` assumes time-ordered input: mmap parsing seeks straight to the
+/// first record at or after it, and earlier stragglers are not matched.
+/// `on_reject` receives every malformed line with its byte offset in the
+/// input handed to the orchestrator, regardless of filters and limits.
+/// `predicate` is an extra per-record filter (e.g. a `--where` expression)
+/// and `sample` drops a share of records per level before anything else
+/// sees them. Structured parsers enforce `record_limits` while building
+/// records. `on_emit` serializes each batch's matches for export after
+/// `on_batch` has seen them, on `serialize_threads` workers of its own where
+/// the result does not keep the batch (see [`with_serializers`](MatchControl::with_serializers)).
+pub struct MatchControl<'a, B> {
+    pub level: Option<LevelFilter>,
+    pub since: Option<u64>,
+    /// Sidecar index narrowing the `since` seek (`--index`).
+    pub index: Option<&'a SparseIndex>,
+    pub predicate: Option<&'a RecordPredicate<'a, B>>,
+    pub sample: Option<LevelSampler>,
+    pub record_limits: RecordLimits,
+    /// Page-cache hints issued ahead of each mmap chunk.
+    pub readahead: Option<Readahead>,
+    /// Parse chunks with ANSI escape sequences removed (`--strip-ansi`).
+    pub strip_ansi: bool,
+    /// The input is a UTF-8 copy of the file (`--encoding`), so its byte
+    /// offsets do not point into the file.
+    pub transcoded: bool,
+    /// How many of the most frequent keys get value columns (`--columns`).
+    pub hot_columns: usize,
+    /// Column layout for `LogFormat::FixedWidth` input.
+    pub fixed_layout: Option<&'a FixedLayout>,
+    /// Parse structured input down to field extents only (`--firehose`);
+    /// see [`StructuredBatch::firehose`](crate::structured::StructuredBatch::firehose).
+    pub firehose: bool,
+    /// Intern each structured record's key set (`--schema-report`, a
+    /// `schema-id` field); see
+    /// [`StructuredBatch::tag_schemas`](crate::structured::StructuredBatch::tag_schemas).
+    pub tag_schemas: bool,
+    /// Stamped on every batch, for [`BatchRecords::provenance`].
+    pub file_id: u32,
+    /// Number the line each record starts on, at the cost of counting the
+    /// input's lines up front.
+    pub line_numbers: bool,
+    /// Counts what has been parsed, and cancels the parse when asked to.
+    pub progress: Option<&'a ParseProgress>,
+    /// Holds emitted chunks until every earlier chunk is done (`--ordered`).
+    pub ordered: Option<&'a Reorder<'a, EmitChunk>>,
+    /// Keep every parsed batch in the result. Off when sinks consume each
+    /// batch as it is parsed, so memory stays flat however large the input;
+    /// see [`retains`](MatchControl::retains).
+    pub retain_batches: bool,
+    pub limit: MatchLimit,
+    pub on_match: Option<&'a RecordCallback<'a, B>>,
+    pub on_batch: Option<&'a BatchCallback<'a, B>>,
+    pub on_emit: Option<&'a BatchCallback<'a, B>>,
+    /// Threads running `on_emit` off the parse threads; 0 runs it inline.
+    pub serialize_threads: usize,
+    pub on_reject: Option<&'a RejectCallback<'a>>,
+    pub reverse: bool,
+}
+
+impl<B> Default for MatchControl<'_, B> {
+    fn default() -> Self {
+        MatchControl {
+            level: None,
+            since: None,
+            index: None,
+            predicate: None,
+            sample: None,
+            record_limits: RecordLimits::default(),
+            readahead: None,
+            strip_ansi: false,
+            transcoded: false,
+            hot_columns: DEFAULT_HOT_COLUMNS,
+            fixed_layout: None,
+            firehose: false,
+            tag_schemas: false,
+            file_id: 0,
+            line_numbers: false,
+            progress: None,
+            ordered: None,
+            retain_batches: true,
+            limit: MatchLimit::unlimited(),
+            on_match: None,
+            on_batch: None,
+            on_emit: None,
+            serialize_threads: 0,
+            on_reject: None,
+            reverse: false,
+        }
+    }
+}
+
+/// The serialization workers of one parse, fed batches that the result
+/// does not keep along with the indices of their matches.
+pub struct Handoff<B> {
+    jobs: SyncSender<(B, Vec<u32>)>,
+}
+
+impl<B: BatchRecords> MatchControl<'_, B> {
+    /// Hints the page cache before a worker parses `data[start..end]`,
+    /// where `data` begins at file offset `base`.
+    #[inline]
+    pub fn prefetch(&self, base: u64, start: usize, end: usize) {
+        if let Some(readahead) = self.readahead {
+            readahead.chunk(base + start as u64, base + end as u64, self.reverse);
+        }
+    }
+
+    /// Whether the result keeps the batches of chunk `seq`: all of them,
+    /// or without `retain_batches` only the first chunk's, for sampling.
+    /// Totals still count every batch.
+    #[inline]
+    pub fn retains(&self, seq: u64) -> bool {
+        self.retain_batches || seq == 0
+    }
+
+    #[inline]
+    pub fn should_stop(&self) -> bool {
+        self.limit.is_reached()
+            || crate::shutdown::interrupted()
+            || self.progress.is_some_and(ParseProgress::is_cancelled)
+    }
+
+    /// Waits until chunk `seq` may be parsed under `--ordered`.
+    #[inline]
+    pub fn wait_turn(&self, seq: u64) {
+        if let Some(ordered) = self.ordered {
+            ordered.wait_turn(seq, || self.should_stop());
+        }
+    }
+
+    /// Marks chunk `seq` visited, releasing what `--ordered` held for it
+    /// once its turn comes.
+    #[inline]
+    pub fn chunk_done(&self, seq: u64) {
+        if let Some(ordered) = self.ordered {
+            ordered.done(seq);
+        }
+    }
+
+    /// Counts `bytes` of input as parsed, for [`ParseProgress`].
+    #[inline]
+    pub fn advance(&self, bytes: usize) {
+        if let Some(progress) = self.progress {
+            progress.add_bytes(bytes as u64);
+        }
+    }
+
+    #[inline]
+    pub fn matches(&self, batch: &B, i: usize) -> bool {
+        self.sample.is_none_or(|sample| {
+            let offset = batch.input_offset() + batch.record_line(i).offset;
+            sample.keep(batch.record_level(i), offset)
+        }) && self.level.is_none_or(|f| f.matches(batch.record_level(i)))
+            && self
+                .since
+                .is_none_or(|since| batch.record_timestamp(i).is_none_or(|ts| ts >= since))
+            && self.predicate.is_none_or(|predicate| predicate(batch, i))
+    }
+
+    pub fn reject(&self, batch: &B) {
+        if let Some(on_reject) = self.on_reject {
+            for &span in batch.malformed_lines() {
+                on_reject(batch.input_offset() + span.offset, batch.line_bytes(span));
+            }
+        }
+    }
+
+    /// A chunk already parsed when Ctrl-C arrives is still visited, so its
+    /// matches reach the sinks. Returns the indices of the matches for
+    /// [`emit`](MatchControl::emit), collected only when there is an
+    /// `on_batch` or `on_emit` to see them.
+    pub fn visit(&self, batch: &B) -> Vec<u32> {
+        if let Some(progress) = self.progress {
+            progress.add_records(batch.record_count() as u64);
+        }
+        if self.limit.is_reached() {
+            return Vec::new();
+        }
+
+        let collect = self.on_batch.is_some() || self.on_emit.is_some();
+        if self.on_match.is_none() && !collect {
+            let per_record =
+                self.since.is_some() || self.predicate.is_some() || self.sample.is_some();
+            let matched = match (per_record, self.level) {
+                (true, _) => (0..batch.record_count())
+                    .filter(|&i| self.matches(batch, i))
+                    .count() as u64,
+                (false, Some(filter)) => filter.count_in(batch.level_summary()),
+                (false, None) => batch.record_count() as u64,
+            };
+            self.limit.add(matched);
+            return Vec::new();
+        }
+
+        if let Some(filter) = self.level
+            && filter.count_in(batch.level_summary()) == 0
+        {
+            return Vec::new();
+        }
+
+        let n = batch.record_count();
+        let mut matched = Vec::new();
+        for k in 0..n {
+            let i = if self.reverse { n - 1 - k } else { k };
+            // Rejected lines go to `on_reject`, never to the sinks too.
+            if !batch.is_malformed(i) && self.matches(batch, i) {
+                if !self.limit.try_claim() {
+                    break;
+                }
+                if let Some(on_match) = self.on_match {
+                    on_match(batch, i);
+                }
+                if collect {
+                    matched.push(i as u32);
+                }
+            }
+        }
+        if let Some(on_batch) = self.on_batch
+            && !matched.is_empty()
+        {
+            on_batch(batch, &matched);
+        }
+        matched
+    }
+
+    /// Serializes the `matched` records of `batch` on the parse thread.
+    #[inline]
+    pub fn serialize(&self, batch: &B, matched: &[u32]) {
+        if let Some(on_emit) = self.on_emit
+            && !matched.is_empty()
+        {
+            on_emit(batch, matched);
+        }
+    }
+
+    /// Serializes the `matched` records of `batch`, the batch of chunk `seq`,
+    /// and gives it back when the result keeps it. A batch the result drops
+    /// goes to `handoff` instead, if there is one: pass `None` for a batch
+    /// whose input is freed before the parse returns.
+    pub fn emit(
+        &self,
+        handoff: Option<&Handoff<B>>,
+        batch: B,
+        matched: Vec<u32>,
+        seq: u64,
+    ) -> Option<B> {
+        let keep = self.retains(seq);
+        match handoff {
+            Some(handoff) if !keep && !matched.is_empty() && self.on_emit.is_some() => {
+                let _ = handoff.jobs.send((batch, matched));
+                None
+            }
+            _ => {
+                self.serialize(&batch, &matched);
+                keep.then_some(batch)
+            }
+        }
+    }
+}
+
+impl<B: BatchRecords + Send> MatchControl<'_, B> {
+    /// Runs `parse` with the `serialize_threads` workers of this parse, and
+    /// joins them before returning, so every batch handed to them is written
+    /// out while its input is still mapped. Under `--ordered` a chunk's
+    /// output must be queued before the next chunk's turn, so it is
+    /// serialized inline.
+    pub fn with_serializers<R>(&self, parse: impl FnOnce(Option<&Handoff<B>>) -> R) -> R {
+        let Some(on_emit) = self.on_emit else {
+            return parse(None);
+        };
+        if self.serialize_threads == 0 || self.ordered.is_some() {
+            return parse(None);
+        }
+        let (jobs, queue) = mpsc::sync_channel::<(B, Vec<u32>)>(self.serialize_threads * 2);
+        // Shared by the workers alone, so were they all to die, sends would
+        // fail rather than block the parse.
+        let queue = Arc::new(Mutex::new(queue));
+        thread::scope(|scope| {
+            for _ in 0..self.serialize_threads {
+                let queue = Arc::clone(&queue);
+                scope.spawn(move || {
+                    loop {
+                        let job = queue.lock().unwrap().recv();
+                        let Ok((batch, matched)) = job else {
+                            break;
+                        };
+                        on_emit(&batch, &matched);
+                    }
+                });
+            }
+            drop(queue);
+            parse(Some(&Handoff { jobs }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_filter_parse() {
+        assert_eq!(
+            LevelFilter::parse("error+"),
+            Some(LevelFilter::AtLeast(LogLevel::Error))
+        );
+        assert_eq!(
+            LevelFilter::parse("WARN"),
+            Some(LevelFilter::Exactly(LogLevel::Warn))
+        );
+        assert_eq!(LevelFilter::parse("bogus+"), None);
+    }
+
+    #[test]
+    fn test_level_filter_matches() {
+        let filter = LevelFilter::AtLeast(LogLevel::Warn);
+        assert!(!filter.matches(LogLevel::Info));
+        assert!(filter.matches(LogLevel::Warn));
+        assert!(filter.matches(LogLevel::Fatal));
+        assert!(!filter.matches(LogLevel::Unknown));
+
+        let exact = LevelFilter::Exactly(LogLevel::Info);
+        assert!(exact.matches(LogLevel::Info));
+        assert!(!exact.matches(LogLevel::Error));
+    }
+
+    #[test]
+    fn test_level_filter_count_in_summary() {
+        let summary = LevelSummary::from_levels(&[
+            LogLevel::Info,
+            LogLevel::Error,
+            LogLevel::Fatal,
+            LogLevel::Unknown,
+        ]);
+        assert_eq!(LevelFilter::AtLeast(LogLevel::Error).count_in(&summary), 2);
+        assert_eq!(LevelFilter::Exactly(LogLevel::Debug).count_in(&summary), 0);
+    }
+
+    #[test]
+    fn test_match_limit() {
+        let limit = MatchLimit::new(2);
+        assert!(!limit.is_reached());
+        assert!(limit.try_claim());
+        assert!(limit.try_claim());
+        assert!(!limit.try_claim());
+        assert!(limit.is_reached());
+        assert_eq!(limit.matched(), 2);
+        assert_eq!(MatchLimit::unlimited().limit(), None);
+    }
+
+    #[test]
+    fn test_match_control_callback_respects_limit() {
+        use crate::data::LogBatch;
+        use std::sync::Mutex;
+
+        let data = [0u8; 4];
+        let mut batch = LogBatch::new(4, data.as_ptr());
+        batch.levels = vec![
+            LogLevel::Error,
+            LogLevel::Info,
+            LogLevel::Error,
+            LogLevel::Fatal,
+        ];
+        batch.level_summary = LevelSummary::from_levels(&batch.levels);
+
+        let seen = Mutex::new(Vec::new());
+        let record = |_: &LogBatch, i: usize| seen.lock().unwrap().push(i);
+        let control = MatchControl {
+            level: Some(LevelFilter::AtLeast(LogLevel::Error)),
+            since: None,
+            index: None,
+            predicate: None,
+            sample: None,
+            record_limits: RecordLimits::default(),
+            readahead: None,
+            strip_ansi: false,
+            transcoded: false,
+            hot_columns: 0,
+            fixed_layout: None,
+            firehose: false,
+            tag_schemas: false,
+            file_id: 0,
+            line_numbers: false,
+            progress: None,
+            ordered: None,
+            retain_batches: true,
+            limit: MatchLimit::new(2),
+            on_match: Some(&record),
+            on_batch: None,
+            on_emit: None,
+            serialize_threads: 0,
+            on_reject: None,
+            reverse: false,
+        };
+        control.visit(&batch);
+
+        assert_eq!(*seen.lock().unwrap(), vec![0, 2]);
+        assert!(control.should_stop());
+
+        seen.lock().unwrap().clear();
+        let reversed = MatchControl {
+            level: Some(LevelFilter::AtLeast(LogLevel::Error)),
+            since: None,
+            index: None,
+            predicate: None,
+            sample: None,
+            record_limits: RecordLimits::default(),
+            readahead: None,
+            strip_ansi: false,
+            transcoded: false,
+            hot_columns: 0,
+            fixed_layout: None,
+            firehose: false,
+            tag_schemas: false,
+            file_id: 0,
+            line_numbers: false,
+            progress: None,
+            ordered: None,
+            retain_batches: true,
+            limit: MatchLimit::new(2),
+            on_match: Some(&record),
+            on_batch: None,
+            on_emit: None,
+            serialize_threads: 0,
+            on_reject: None,
+            reverse: true,
+        };
+        reversed.visit(&batch);
+        assert_eq!(*seen.lock().unwrap(), vec![3, 2]);
+
+        let batched = Mutex::new(Vec::new());
+        let on_batch =
+            |_: &LogBatch, matched: &[u32]| batched.lock().unwrap().push(matched.to_vec());
+        let control = MatchControl {
+            level: Some(LevelFilter::AtLeast(LogLevel::Error)),
+            limit: MatchLimit::new(2),
+            on_batch: Some(&on_batch),
+            ..MatchControl::default()
+        };
+        control.visit(&batch);
+        assert_eq!(*batched.lock().unwrap(), vec![vec![0, 2]]);
+    }
+
+    #[test]
+    fn test_level_sampler_rates() {
+        let sampler = LevelSampler::parse("info=0, debug=0.1,error=1.0").unwrap();
+        let offsets = (0..100_000u64).map(|n| n * 97);
+        let kept = |level| offsets.clone().filter(|&o| sampler.keep(level, o)).count();
+
+        assert_eq!(kept(LogLevel::Info), 0);
+        assert_eq!(kept(LogLevel::Error), 100_000);
+        assert_eq!(kept(LogLevel::Warn), 100_000);
+        assert!((9_000..11_000).contains(&kept(LogLevel::Debug)));
+
+        assert!(LevelSampler::parse("info=2").is_err());
+        assert!(LevelSampler::parse("loud=0.5").is_err());
+        assert!(LevelSampler::parse("info").is_err());
+    }
+}
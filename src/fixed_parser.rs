@@ -0,0 +1,226 @@
+//! Fixed-width records: every column sits at the same byte range of every
+//! record, so fields are sliced straight out of the line with no scanning.
+//! With a record length, records are framed by arithmetic too, and the
+//! input is never searched for separators at all.
+
+use crate::structured::{FieldRef, StructuredBatch, well_known};
+
+/// One column: bytes `start..end` of each record, or `start..` for the last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedColumn {
+    pub name: Box<[u8]>,
+    pub start: usize,
+    pub end: Option<usize>,
+    pub well_known: well_known::WellKnownKind,
+}
+
+/// Columns of a fixed-width input (`--fixed-columns`) and, for inputs with
+/// no record separator, the length of every record (`--fixed-record-len`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixedLayout {
+    pub columns: Vec<FixedColumn>,
+    pub record_len: Option<usize>,
+}
+
+impl FixedLayout {
+    /// Parses a comma-separated `name:start-end` list of 0-based byte
+    /// ranges, end exclusive; `name:start-` runs to the end of the record.
+    pub fn parse(spec: &str) -> Result<FixedLayout, String> {
+        let mut columns: Vec<FixedColumn> = Vec::new();
+        for decl in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (name, range) = decl
+                .rsplit_once(':')
+                .filter(|(name, _)| !name.is_empty())
+                .ok_or_else(|| format!("expected name:start-end, got '{}'", decl))?;
+            let (start, end) = range
+                .split_once('-')
+                .ok_or_else(|| format!("expected start-end, got '{}'", range))?;
+            let start: usize = start
+                .parse()
+                .map_err(|_| format!("invalid column start '{}'", start))?;
+            let end = match end {
+                "" => None,
+                end => match end.parse::<usize>() {
+                    Ok(end) if end > start => Some(end),
+                    _ => return Err(format!("invalid column end '{}' in '{}'", end, decl)),
+                },
+            };
+            if let Some(last) = columns.last()
+                && last.end.is_none_or(|end| end > start)
+            {
+                return Err(format!(
+                    "column '{}' overlaps the one before it; list columns in order",
+                    name
+                ));
+            }
+            columns.push(FixedColumn {
+                name: name.as_bytes().into(),
+                start,
+                end,
+                well_known: well_known::classify_key(name.as_bytes()),
+            });
+        }
+        if columns.is_empty() {
+            return Err("expected at least one column".to_string());
+        }
+        Ok(FixedLayout {
+            columns,
+            record_len: None,
+        })
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Record starts of `data[start..end]` for length-framed records, with
+    /// `end` closing the last, as `scan_region` would find them.
+    pub fn record_starts(&self, start: usize, end: usize, line_starts: &mut Vec<u64>) -> bool {
+        let Some(len) = self.record_len else {
+            return false;
+        };
+        line_starts.extend((start..end).step_by(len).map(|s| s as u64));
+        line_starts.push(end as u64);
+        true
+    }
+
+    /// Where a chunk that should end near `pos` ends: the next record
+    /// boundary when records are length-framed.
+    pub fn record_boundary(&self, pos: usize) -> Option<usize> {
+        self.record_len.map(|len| pos.next_multiple_of(len))
+    }
+}
+
+/// Slices one record into its columns; columns starting past the end of a
+/// short line are left out and the line is counted as malformed.
+#[inline]
+pub fn parse_fixed_line(
+    line: &[u8],
+    base_offset: u64,
+    layout: &FixedLayout,
+    batch: &mut StructuredBatch,
+) {
+    if line.is_empty() {
+        return;
+    }
+
+    batch.begin_record(base_offset, line.len() as u32);
+    for column in &layout.columns {
+        if column.start >= line.len() {
+            break;
+        }
+        let end = column.end.map_or(line.len(), |end| end.min(line.len()));
+        let (val_start, val_end) = if batch.firehose {
+            (column.start, end)
+        } else {
+            trim_padding(line, column.start, end)
+        };
+        let field_idx = batch.fields.len() as u32;
+        batch.push_keyed_field(
+            FieldRef {
+                key_offset: base_offset + val_start as u64,
+                key_len: 0,
+                val_offset: base_offset + val_start as u64,
+                val_len: (val_end - val_start) as u32,
+                key_id: 0,
+            },
+            &column.name,
+        );
+        batch.set_well_known(column.well_known, field_idx);
+    }
+    batch.end_record();
+}
+
+#[inline]
+fn trim_padding(line: &[u8], mut start: usize, mut end: usize) -> (usize, usize) {
+    while start < end && line[start] == b' ' {
+        start += 1;
+    }
+    while end > start && line[end - 1] == b' ' {
+        end -= 1;
+    }
+    (start, end)
+}
+
+pub fn parse_fixed_lines_range(
+    data: &[u8],
+    line_starts: &[u64],
+    start_idx: usize,
+    end_idx: usize,
+    layout: &FixedLayout,
+    batch: &mut StructuredBatch,
+) {
+    let sep = crate::simd_scan::record_sep();
+    for i in start_idx..end_idx {
+        let line_start = line_starts[i] as usize;
+        let mut line_end = line_starts
+            .get(i + 1)
+            .map_or(data.len(), |&next| next as usize);
+        if line_end > line_start && data[line_end - 1] == sep {
+            line_end -= 1;
+        }
+        if !batch.firehose && line_end > line_start && data[line_end - 1] == b'\r' {
+            line_end -= 1;
+        }
+        if line_start >= data.len() || line_start >= line_end {
+            continue;
+        }
+
+        let line = &data[line_start..line_end];
+        let records_before = batch.len;
+        parse_fixed_line(line, line_start as u64, layout, batch);
+        if batch.new_record_fields(records_before) != Some(layout.num_columns()) {
+            batch.mark_malformed(line_start as u64, line.len() as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fixed_layout_and_lines() {
+        assert!(FixedLayout::parse("a:0-4,b:2-6").is_err());
+        assert!(FixedLayout::parse("a:0-,b:4-6").is_err());
+        assert!(FixedLayout::parse("a:4-4").is_err());
+        assert!(FixedLayout::parse("").is_err());
+
+        let mut layout = FixedLayout::parse("timestamp:0-20, level:21-26,msg:27-").unwrap();
+        let data = b"2025-02-12T10:31:45Z ERROR disk full   \n\
+                     2025-02-12T10:31:46Z INFO  ok\n\
+                     2025-02-12T10:31:47Z\n";
+        let mut line_starts = vec![0u64];
+        crate::simd_scan::scan_region(data, 0, data.len() as u64, &mut line_starts);
+        line_starts.push(data.len() as u64);
+        let mut batch = StructuredBatch::with_capacity(3, 9, data.as_ptr());
+        parse_fixed_lines_range(data, &line_starts, 0, 3, &layout, &mut batch);
+
+        assert_eq!(batch.len, 3);
+        let values = |i: usize| -> Vec<(&str, &str)> {
+            batch
+                .record_fields(i)
+                .iter()
+                .map(|f| unsafe { (batch.field_key(f), batch.field_value(f)) })
+                .collect()
+        };
+        assert_eq!(
+            values(0),
+            [
+                ("timestamp", "2025-02-12T10:31:45Z"),
+                ("level", "ERROR"),
+                ("msg", "disk full")
+            ]
+        );
+        assert_eq!(values(1)[1], ("level", "INFO"));
+        assert_eq!(values(2), [("timestamp", "2025-02-12T10:31:47Z")]);
+        assert_eq!(batch.malformed.len(), 1);
+
+        // Length framing finds the same records without scanning.
+        layout.record_len = Some(10);
+        let mut starts = Vec::new();
+        assert!(layout.record_starts(20, 45, &mut starts));
+        assert_eq!(starts, [20, 30, 40, 45]);
+        assert_eq!(layout.record_boundary(21), Some(30));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::filewatch::FileIdentity;
+use crate::simd_scan::record_sep;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Most bytes read per poll, so a burst is handed on in pieces.
+const MAX_READ: u64 = 64 * 1024 * 1024;
+
+/// What [`Follower::poll`] saw besides new lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowEvent {
+    Appended,
+    /// The path names a new file (rotation); reading restarted at its start.
+    Reopened,
+    /// The file shrank below what was read; reading restarted at 0.
+    Truncated,
+}
+
+/// Reads lines appended to a file after the initial parse, like `tail -F`:
+/// only whole lines are handed on, and rotation and truncation restart
+/// reading from the top of the current file.
+pub struct Follower {
+    path: String,
+    file: File,
+    identity: FileIdentity,
+    offset: u64,
+    /// Start of a line whose newline has not been written yet.
+    pending: Vec<u8>,
+}
+
+impl Follower {
+    pub fn new(path: &str, file: File, offset: u64) -> io::Result<Follower> {
+        let identity = FileIdentity::of(&file.metadata()?);
+        Ok(Follower {
+            path: path.to_string(),
+            file,
+            identity,
+            offset,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Whole lines appended since the last call, possibly none.
+    pub fn poll(&mut self, lines: &mut Vec<u8>) -> io::Result<FollowEvent> {
+        lines.clear();
+        let mut event = FollowEvent::Appended;
+        let now = FileIdentity::of(&self.file.metadata()?);
+        if now.len < self.offset {
+            event = FollowEvent::Truncated;
+            self.restart();
+        } else if now.len == self.offset
+            && std::fs::metadata(&self.path).is_ok_and(|meta| {
+                let named = FileIdentity::of(&meta);
+                (named.dev, named.ino) != (self.identity.dev, self.identity.ino)
+            })
+        {
+            // Only switch once the old file is drained, so lines written
+            // just before the rename are not lost.
+            self.file = File::open(&self.path)?;
+            self.identity = FileIdentity::of(&self.file.metadata()?);
+            event = FollowEvent::Reopened;
+            self.restart();
+        }
+
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let before = self.pending.len();
+        (&mut self.file)
+            .take(MAX_READ)
+            .read_to_end(&mut self.pending)?;
+        self.offset += (self.pending.len() - before) as u64;
+
+        if let Some(last_nl) = memchr::memrchr(record_sep(), &self.pending) {
+            lines.extend_from_slice(&self.pending[..=last_nl]);
+            self.pending.drain(..=last_nl);
+        }
+        Ok(event)
+    }
+
+    fn restart(&mut self) {
+        self.offset = 0;
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_follower_hands_on_whole_lines() {
+        let dir = std::env::temp_dir().join(format!("pandora-follow-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let path_str = path.to_str().unwrap();
+        std::fs::write(&path, "old 1\nold 2\n").unwrap();
+
+        let file = File::open(&path).unwrap();
+        let mut follower = Follower::new(path_str, file, 12).unwrap();
+        let mut lines = Vec::new();
+        assert_eq!(follower.poll(&mut lines).unwrap(), FollowEvent::Appended);
+        assert!(lines.is_empty());
+
+        let mut out = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        out.write_all(b"new 1\nnew 2 par").unwrap();
+        follower.poll(&mut lines).unwrap();
+        assert_eq!(lines, b"new 1\n");
+        out.write_all(b"tial\n").unwrap();
+        follower.poll(&mut lines).unwrap();
+        assert_eq!(lines, b"new 2 partial\n");
+
+        std::fs::write(&path, "cut\n").unwrap();
+        assert_eq!(follower.poll(&mut lines).unwrap(), FollowEvent::Truncated);
+        assert_eq!(lines, b"cut\n");
+
+        std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+        std::fs::write(&path, "rotated\n").unwrap();
+        assert_eq!(follower.poll(&mut lines).unwrap(), FollowEvent::Reopened);
+        assert_eq!(lines, b"rotated\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -7,6 +7,9 @@ pub enum LogFormat {
     Logfmt,
 
     Csv,
+
+    /// Columns at fixed byte ranges; never detected, needs a layout.
+    FixedWidth,
 }
 
 impl LogFormat {
@@ -28,7 +31,8 @@ impl LogFormat {
             }
         }
 
-        let first_line_end = memchr::memchr(b'\n', trimmed).unwrap_or(trimmed.len());
+        let sep = crate::simd_scan::record_sep();
+        let first_line_end = memchr::memchr(sep, trimmed).unwrap_or(trimmed.len());
         let first_line = &trimmed[..first_line_end];
 
         if detect_logfmt(first_line) {
@@ -42,12 +46,25 @@ impl LogFormat {
         LogFormat::PlainText
     }
 
+    /// Accepts the `--format` spellings and [`LogFormat::as_str`] names.
+    pub fn from_name(name: &str) -> Option<LogFormat> {
+        match name {
+            "json" | "ndjson" | "jsonl" => Some(LogFormat::Json),
+            "logfmt" => Some(LogFormat::Logfmt),
+            "csv" => Some(LogFormat::Csv),
+            "fixed-width" | "fixed" => Some(LogFormat::FixedWidth),
+            "plain" | "text" | "plain-text" => Some(LogFormat::PlainText),
+            _ => None,
+        }
+    }
+
     pub fn as_str(self) -> &'static str {
         match self {
             LogFormat::PlainText => "plain-text",
             LogFormat::Json => "json",
             LogFormat::Logfmt => "logfmt",
             LogFormat::Csv => "csv",
+            LogFormat::FixedWidth => "fixed-width",
         }
     }
 }
@@ -72,9 +89,12 @@ fn skip_whitespace_and_bom(data: &[u8]) -> &[u8] {
     &data[i..]
 }
 
+// Needs at least two pairs and no more bare words than pairs, so a plain
+// line with a `time=... level=...` prefix stays plain text.
 fn detect_logfmt(line: &[u8]) -> bool {
     let mut i = 0;
     let mut kv_count = 0;
+    let mut bare_count = 0;
 
     while i < line.len() {
         while i < line.len() && line[i] == b' ' {
@@ -90,6 +110,7 @@ fn detect_logfmt(line: &[u8]) -> bool {
             while i < line.len() && line[i] != b' ' {
                 i += 1;
             }
+            bare_count += usize::from(i > key_start);
             continue;
         }
 
@@ -113,12 +134,9 @@ fn detect_logfmt(line: &[u8]) -> bool {
         }
 
         kv_count += 1;
-        if kv_count >= 2 {
-            return true;
-        }
     }
 
-    false
+    kv_count >= 2 && bare_count <= kv_count
 }
 
 #[inline(always)]
@@ -127,6 +145,7 @@ fn is_logfmt_key_char(b: u8) -> bool {
 }
 
 fn detect_csv(first_line: &[u8], all_data: &[u8]) -> bool {
+    let sep = crate::simd_scan::record_sep();
     let comma_count = first_line.iter().filter(|&&b| b == b',').count();
     if comma_count < 2 {
         return false;
@@ -142,7 +161,7 @@ fn detect_csv(first_line: &[u8], all_data: &[u8]) -> bool {
 
     let after_first = if first_line.len() < all_data.len() {
         let rest = &all_data[first_line.len()..];
-        let start = if !rest.is_empty() && rest[0] == b'\n' {
+        let start = if !rest.is_empty() && rest[0] == sep {
             1
         } else if rest.len() >= 2 && rest[0] == b'\r' && rest[1] == b'\n' {
             2
@@ -154,7 +173,7 @@ fn detect_csv(first_line: &[u8], all_data: &[u8]) -> bool {
         return false;
     };
 
-    let second_line_end = memchr::memchr(b'\n', after_first).unwrap_or(after_first.len());
+    let second_line_end = memchr::memchr(sep, after_first).unwrap_or(after_first.len());
     let second_line = &after_first[..second_line_end];
     let second_comma_count = second_line.iter().filter(|&&b| b == b',').count();
 
@@ -195,6 +214,12 @@ mod tests {
             LogFormat::detect(b"ts=2025-02-12T10:31:45Z level=info component=api-server"),
             LogFormat::Logfmt
         );
+        assert_eq!(
+            LogFormat::detect(
+                b"time=\"2025-02-12T10:31:45Z\" level=error api-server pool exhausted"
+            ),
+            LogFormat::PlainText
+        );
     }
 
     #[test]
@@ -0,0 +1,177 @@
+//! Entry points for the cargo-fuzz targets under `fuzz/`. Each parses any
+//! bytes and panics only when the parser itself panics or a record points
+//! outside the buffer its batch reads from.
+
+use crate::format::LogFormat;
+use crate::orchestrator::parse_logs_pipelined;
+use crate::structured::StructuredBatch;
+use crate::structured_orchestrator::parse_structured_mmap;
+
+pub fn json(data: &[u8]) {
+    structured(data, LogFormat::Json);
+}
+
+pub fn logfmt(data: &[u8]) {
+    structured(data, LogFormat::Logfmt);
+}
+
+pub fn csv(data: &[u8]) {
+    structured(data, LogFormat::Csv);
+}
+
+pub fn plain(data: &[u8]) {
+    let result = parse_logs_pipelined(data, 1);
+    for batch in &result.batches {
+        let end = readable(data, batch.data_ptr, batch.data_len);
+        for i in 0..batch.len {
+            check(end, "line", batch.line_offsets[i], batch.line_lens[i]);
+            check(
+                end,
+                "component",
+                batch.component_offsets[i],
+                batch.component_lens[i],
+            );
+            check(
+                end,
+                "message",
+                batch.message_offsets[i],
+                batch.message_lens[i],
+            );
+        }
+        for span in &batch.malformed {
+            check(end, "malformed line", span.offset, span.len);
+        }
+    }
+}
+
+/// Detects the format, then parses with it, as the CLI does without
+/// `--format`.
+pub fn detect(data: &[u8]) {
+    match LogFormat::detect(data) {
+        LogFormat::PlainText => plain(data),
+        format => structured(data, format),
+    }
+}
+
+fn structured(data: &[u8], format: LogFormat) {
+    let result = parse_structured_mmap(data, 1, Some(format));
+    for batch in &result.batches {
+        check_structured(data, batch);
+    }
+}
+
+fn check_structured(data: &[u8], batch: &StructuredBatch) {
+    let end = readable(data, batch.data_ptr, batch.data_len);
+    assert_eq!(
+        batch.field_starts.len(),
+        batch.len + 1,
+        "field_starts out of step"
+    );
+    for i in 0..batch.len {
+        check(end, "line", batch.line_offsets[i], batch.line_lens[i]);
+        let (first, last) = (batch.field_starts[i], batch.field_starts[i + 1]);
+        assert!(
+            first <= last && last as usize <= batch.fields.len(),
+            "record {} has fields {}..{} of {}",
+            i,
+            first,
+            last,
+            batch.fields.len()
+        );
+    }
+    for field in &batch.fields {
+        check(end, "key", field.key_offset, field.key_len);
+        check(end, "value", field.val_offset, field.val_len);
+        assert!(
+            (field.key_id as usize) < batch.keys.len(),
+            "key id {} not interned",
+            field.key_id
+        );
+    }
+    for span in &batch.malformed {
+        check(end, "malformed line", span.offset, span.len);
+    }
+}
+
+/// How many bytes a batch may read past `ptr`: the rest of `data` when the
+/// batch reads from it, or the length of the copy it was given otherwise.
+fn readable(data: &[u8], ptr: *const u8, len: usize) -> u64 {
+    let start = data.as_ptr() as usize;
+    match (ptr as usize).checked_sub(start) {
+        Some(at) if at <= data.len() => (data.len() - at) as u64,
+        _ => len as u64,
+    }
+}
+
+fn check(end: u64, what: &str, offset: u64, len: u32) {
+    assert!(
+        offset
+            .checked_add(len as u64)
+            .is_some_and(|stop| stop <= end),
+        "{} {}+{} is outside the {} readable bytes",
+        what,
+        offset,
+        len,
+        end
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Short runs of the bytes the parsers branch on, so random inputs
+    /// reach deep into each grammar.
+    fn random_input(seed: &mut u64, len: usize) -> Vec<u8> {
+        const ALPHABET: &[u8] = b"{}[]\":,=\\ \t\r\n\x00\xffaZ09-T.+_e";
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            let r = *seed;
+            match r % 8 {
+                0 => out.extend_from_slice(b"2025-02-12T10:31:45Z "),
+                1 => out.extend_from_slice(b"{\"level\":\"error\",\"msg\":\""),
+                2 => out.extend_from_slice(b"level=warn msg=\""),
+                _ => out.push(ALPHABET[(r >> 8) as usize % ALPHABET.len()]),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_parsers_survive_random_input() {
+        let mut seed = 0x9e37_79b9_7f4a_7c15;
+        for round in 0..400 {
+            let data = random_input(&mut seed, round * 3 % 700);
+            json(&data);
+            logfmt(&data);
+            csv(&data);
+            plain(&data);
+            detect(&data);
+        }
+        let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+        for target in std::fs::read_dir(corpus).unwrap() {
+            for seed in std::fs::read_dir(target.unwrap().path()).unwrap() {
+                detect(&std::fs::read(seed.unwrap().path()).unwrap());
+            }
+        }
+        for seed in [
+            &b""[..],
+            b"\n\n\r\n",
+            b"{\"a\":",
+            b"{\"a\":\"\\",
+            b"a,b\n\"unterminated",
+            b"k=\"v",
+            b"[2025-02-12T10:31:45Z] [",
+            b"\xef\xbb\xbf{\"level\":1}\n{",
+        ] {
+            json(seed);
+            logfmt(seed);
+            csv(seed);
+            plain(seed);
+            detect(seed);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::data::BatchRecords;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Bucket `k` holds gaps of `[2^(k-1), 2^k)` seconds; bucket 0 is a zero gap.
+pub const GAP_BUCKETS: usize = 24;
+
+/// Silent periods kept for the report; the count keeps going past this.
+const MAX_SILENCES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GapHistogram {
+    pub counts: [u64; GAP_BUCKETS],
+    pub max_gap: u64,
+}
+
+impl Default for GapHistogram {
+    fn default() -> Self {
+        GapHistogram {
+            counts: [0; GAP_BUCKETS],
+            max_gap: 0,
+        }
+    }
+}
+
+impl GapHistogram {
+    #[inline]
+    pub fn bucket(gap: u64) -> usize {
+        ((u64::BITS - gap.leading_zeros()) as usize).min(GAP_BUCKETS - 1)
+    }
+
+    #[inline]
+    pub fn record(&mut self, gap: u64) {
+        self.counts[Self::bucket(gap)] += 1;
+        self.max_gap = self.max_gap.max(gap);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Largest gap (in seconds) the bucket holding quantile `q` can contain,
+    /// capped at the largest gap seen.
+    pub fn quantile_bound(&self, q: f64) -> u64 {
+        let target = (self.total() as f64 * q).ceil() as u64;
+        let mut seen = 0;
+        for (k, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target.max(1) {
+                return ((1u64 << k) - 1).min(self.max_gap);
+            }
+        }
+        self.max_gap
+    }
+}
+
+fn bucket_label(k: usize) -> String {
+    match k {
+        0 => "0s".to_string(),
+        1 => "1s".to_string(),
+        _ if k == GAP_BUCKETS - 1 => format!(">={}s", 1u64 << (k - 1)),
+        _ => format!("{}-{}s", 1u64 << (k - 1), (1u64 << k) - 1),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Silence {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Inter-arrival gaps of timestamped records in file order, overall and per
+/// component. A gap is measured from the newest timestamp seen so far, so
+/// slightly out-of-order records do not count as silence.
+#[derive(Debug)]
+pub struct GapReport {
+    pub threshold: u64,
+    pub overall: GapHistogram,
+    pub components: HashMap<Vec<u8>, GapHistogram>,
+    /// Gaps longer than `threshold`, in file order.
+    pub silences: Vec<Silence>,
+    pub silence_count: u64,
+    newest: Option<u64>,
+    component_newest: HashMap<Vec<u8>, u64>,
+}
+
+impl GapReport {
+    pub fn new(threshold: u64) -> Self {
+        GapReport {
+            threshold,
+            overall: GapHistogram::default(),
+            components: HashMap::new(),
+            silences: Vec::new(),
+            silence_count: 0,
+            newest: None,
+            component_newest: HashMap::new(),
+        }
+    }
+
+    pub fn from_batches<'b, B: BatchRecords + 'b>(
+        batches: impl IntoIterator<Item = &'b B>,
+        threshold: u64,
+    ) -> Self {
+        let mut report = GapReport::new(threshold);
+        for batch in batches {
+            for i in 0..batch.record_count() {
+                if let Some(ts) = batch.record_timestamp(i) {
+                    report.record(ts, batch.record_component(i));
+                }
+            }
+        }
+        report
+    }
+
+    pub fn record(&mut self, ts: u64, component: Option<&[u8]>) {
+        if let Some(newest) = self.newest {
+            let gap = ts.saturating_sub(newest);
+            self.overall.record(gap);
+            if gap > self.threshold {
+                self.silence_count += 1;
+                if self.silences.len() < MAX_SILENCES {
+                    self.silences.push(Silence {
+                        start: newest,
+                        end: ts,
+                    });
+                }
+            }
+        }
+        self.newest = Some(self.newest.map_or(ts, |n| n.max(ts)));
+
+        let component = component.unwrap_or(b"-");
+        match self.component_newest.get_mut(component) {
+            Some(newest) => {
+                self.components
+                    .entry(component.to_vec())
+                    .or_default()
+                    .record(ts.saturating_sub(*newest));
+                *newest = (*newest).max(ts);
+            }
+            None => {
+                self.component_newest.insert(component.to_vec(), ts);
+            }
+        }
+    }
+
+    /// Components by their longest gap, longest first.
+    pub fn quietest_components(&self) -> Vec<(&[u8], &GapHistogram)> {
+        let mut components: Vec<_> = self
+            .components
+            .iter()
+            .map(|(name, hist)| (name.as_slice(), hist))
+            .collect();
+        components.sort_by(|a, b| b.1.max_gap.cmp(&a.1.max_gap).then(a.0.cmp(b.0)));
+        components
+    }
+}
+
+impl fmt::Display for GapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.overall.total();
+        writeln!(
+            f,
+            "Gaps: {} between records, p50 <= {} s, p99 <= {} s, max {} s",
+            total,
+            self.overall.quantile_bound(0.5),
+            self.overall.quantile_bound(0.99),
+            self.overall.max_gap
+        )?;
+        for (k, &count) in self.overall.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let width = (count * 40).div_ceil(total.max(1)) as usize;
+            writeln!(
+                f,
+                "  {:>14} | {:>10} | {}",
+                bucket_label(k),
+                count,
+                "#".repeat(width)
+            )?;
+        }
+
+        writeln!(
+            f,
+            "  Silent periods over {} s: {}",
+            self.threshold, self.silence_count
+        )?;
+        let mut longest = self.silences.clone();
+        longest.sort_by(|a, b| {
+            (b.end - b.start)
+                .cmp(&(a.end - a.start))
+                .then(a.start.cmp(&b.start))
+        });
+        for silence in longest.iter().take(10) {
+            let mut start = Vec::new();
+            let mut end = Vec::new();
+            crate::emit::write_rfc3339(silence.start, &mut start);
+            crate::emit::write_rfc3339(silence.end, &mut end);
+            writeln!(
+                f,
+                "    {} -> {} ({} s)",
+                String::from_utf8_lossy(&start),
+                String::from_utf8_lossy(&end),
+                silence.end - silence.start
+            )?;
+        }
+
+        for (name, hist) in self.quietest_components().into_iter().take(10) {
+            writeln!(
+                f,
+                "  {:>20} | {:>8} gaps | p50 <= {} s | max {} s",
+                String::from_utf8_lossy(name),
+                hist.total(),
+                hist.quantile_bound(0.5),
+                hist.max_gap
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gap_buckets() {
+        assert_eq!(GapHistogram::bucket(0), 0);
+        assert_eq!(GapHistogram::bucket(1), 1);
+        assert_eq!(GapHistogram::bucket(2), 2);
+        assert_eq!(GapHistogram::bucket(3), 2);
+        assert_eq!(GapHistogram::bucket(4), 3);
+        assert_eq!(GapHistogram::bucket(u64::MAX), GAP_BUCKETS - 1);
+        assert_eq!(bucket_label(3), "4-7s");
+
+        let mut hist = GapHistogram::default();
+        for gap in [1, 1, 5, 40] {
+            hist.record(gap);
+        }
+        assert_eq!(hist.quantile_bound(0.5), 1);
+        assert_eq!(hist.quantile_bound(0.75), 7);
+        assert_eq!(hist.quantile_bound(1.0), 40);
+    }
+
+    #[test]
+    fn test_gap_report_flags_silences() {
+        let mut report = GapReport::new(60);
+        for (ts, component) in [
+            (1000, &b"api"[..]),
+            (1001, b"db"),
+            (1001, b"api"),
+            (999, b"db"),
+            (1200, b"api"),
+            (1201, b"db"),
+        ] {
+            report.record(ts, Some(component));
+        }
+
+        assert_eq!(report.overall.total(), 5);
+        assert_eq!(report.overall.max_gap, 199);
+        // The straggler at 999 is a zero gap, not a backwards one.
+        assert_eq!(report.overall.counts[0], 2);
+        assert_eq!(report.silence_count, 1);
+        assert_eq!(
+            report.silences,
+            [Silence {
+                start: 1001,
+                end: 1200
+            }]
+        );
+
+        let quietest = report.quietest_components();
+        assert_eq!(quietest[0].0, b"db");
+        assert_eq!(quietest[0].1.max_gap, 200);
+        assert_eq!(quietest[1].0, b"api");
+        assert_eq!(quietest[1].1.max_gap, 199);
+    }
+}
@@ -0,0 +1,613 @@
+use crate::emit::{EmitRecord, FieldValue, unescape_json, write_rfc3339};
+use crate::expr::{Expr, Value};
+use crate::numbers::{self, NumberStyle};
+use crate::sketch::QuantileSketch;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Children shown per node of the hierarchical report; the rest are summed
+/// into one line.
+const MAX_CHILDREN: usize = 10;
+
+/// Length prefix marking a record without the field.
+const ABSENT: u32 = u32::MAX;
+
+/// Appends one part of a composite key: a little-endian `u32` length, then
+/// the value. All parts of a key live in one allocation.
+fn push_part(key: &mut Vec<u8>, value: Option<&[u8]>) {
+    match value {
+        Some(v) => {
+            key.extend_from_slice(&(v.len() as u32).to_le_bytes());
+            key.extend_from_slice(v);
+        }
+        None => key.extend_from_slice(&ABSENT.to_le_bytes()),
+    }
+}
+
+fn split_parts(mut key: &[u8]) -> Vec<Option<Vec<u8>>> {
+    let mut parts = Vec::new();
+    while key.len() >= 4 {
+        let len = u32::from_le_bytes(key[..4].try_into().unwrap());
+        key = &key[4..];
+        if len == ABSENT {
+            parts.push(None);
+        } else {
+            parts.push(Some(key[..len as usize].to_vec()));
+            key = &key[len as usize..];
+        }
+    }
+    parts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggFn {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Quantile(f64),
+}
+
+/// A numeric aggregate computed per group (`--agg latency_ms:p99`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agg {
+    pub field: Vec<u8>,
+    pub func: AggFn,
+    /// The function as written, e.g. `p99`.
+    name: String,
+    /// Index of `field` in [`GroupStats::fields`].
+    slot: usize,
+}
+
+impl Agg {
+    /// Parses `field:fn[,field:fn...]` where `fn` is sum, avg, min, max or
+    /// a percentile such as p50, p99 or p99.9.
+    pub fn parse_list(spec: &str) -> Result<Vec<Agg>, String> {
+        spec.split(',')
+            .map(|item| {
+                let (field, name) = item
+                    .trim()
+                    .rsplit_once(':')
+                    .ok_or_else(|| format!("expected field:fn, got '{}'", item))?;
+                let name = name.to_ascii_lowercase();
+                let func = match name.as_str() {
+                    "sum" => AggFn::Sum,
+                    "avg" | "mean" => AggFn::Avg,
+                    "min" => AggFn::Min,
+                    "max" => AggFn::Max,
+                    p => match p.strip_prefix('p').and_then(|n| n.parse::<f64>().ok()) {
+                        Some(pct) if (0.0..=100.0).contains(&pct) => AggFn::Quantile(pct / 100.0),
+                        _ => return Err(format!("unknown aggregate '{}'", name)),
+                    },
+                };
+                if field.is_empty() {
+                    return Err(format!("missing field in '{}'", item));
+                }
+                Ok(Agg {
+                    field: field.as_bytes().to_vec(),
+                    func,
+                    name,
+                    slot: 0,
+                })
+            })
+            .collect()
+    }
+
+    /// Column title, e.g. `p99(latency_ms)`.
+    pub fn label(&self) -> String {
+        format!("{}({})", self.name, String::from_utf8_lossy(&self.field))
+    }
+
+    /// Name `--having` knows the value by, e.g. `p99_latency_ms`.
+    pub fn having_name(&self) -> String {
+        format!("{}_{}", self.name, String::from_utf8_lossy(&self.field))
+    }
+
+    /// `None` when no record of the group had a numeric value.
+    pub fn value(&self, stats: &GroupStats) -> Option<f64> {
+        let field = stats.fields.get(self.slot).filter(|f| f.n > 0)?;
+        match self.func {
+            AggFn::Sum => Some(field.sum),
+            AggFn::Avg => Some(field.sum / field.n as f64),
+            AggFn::Min => Some(field.min),
+            AggFn::Max => Some(field.max),
+            AggFn::Quantile(q) => field.sketch.as_ref()?.quantile(q),
+        }
+    }
+}
+
+/// Numeric values of one aggregated field within a group.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldSummary {
+    pub n: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Kept only for fields with a percentile aggregate.
+    pub sketch: Option<QuantileSketch>,
+}
+
+impl FieldSummary {
+    fn add(&mut self, v: f64) {
+        if self.n == 0 {
+            (self.min, self.max) = (v, v);
+        } else {
+            self.min = self.min.min(v);
+            self.max = self.max.max(v);
+        }
+        self.n += 1;
+        self.sum += v;
+        if let Some(sketch) = &mut self.sketch {
+            sketch.add(v);
+        }
+    }
+
+    fn merge(&mut self, other: &FieldSummary) {
+        if other.n == 0 {
+            return;
+        }
+        if self.n == 0 {
+            *self = other.clone();
+            return;
+        }
+        self.n += other.n;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        if let (Some(sketch), Some(theirs)) = (&mut self.sketch, &other.sketch) {
+            sketch.merge(theirs);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupStats {
+    pub count: u64,
+    /// One entry per distinct aggregated field.
+    pub fields: Vec<FieldSummary>,
+}
+
+impl GroupStats {
+    pub fn merge(&mut self, other: &GroupStats) {
+        self.count += other.count;
+        if self.fields.is_empty() {
+            self.fields = other.fields.clone();
+        } else {
+            for (field, theirs) in self.fields.iter_mut().zip(&other.fields) {
+                field.merge(theirs);
+            }
+        }
+    }
+}
+
+/// Computes [`GroupStats`] for a set of aggregates, reading each
+/// aggregated field once per record however many aggregates use it.
+#[derive(Debug, Clone, Default)]
+pub struct Aggregator {
+    pub aggs: Vec<Agg>,
+    /// Distinct aggregated fields and whether any needs a sketch.
+    fields: Vec<(Vec<u8>, bool)>,
+}
+
+impl Aggregator {
+    pub fn new(mut aggs: Vec<Agg>) -> Aggregator {
+        let mut fields: Vec<(Vec<u8>, bool)> = Vec::new();
+        for agg in &mut aggs {
+            let quantile = matches!(agg.func, AggFn::Quantile(_));
+            agg.slot = match fields.iter().position(|(f, _)| *f == agg.field) {
+                Some(slot) => slot,
+                None => {
+                    fields.push((agg.field.clone(), false));
+                    fields.len() - 1
+                }
+            };
+            fields[agg.slot].1 |= quantile;
+        }
+        Aggregator { aggs, fields }
+    }
+
+    pub fn new_stats(&self) -> GroupStats {
+        GroupStats {
+            count: 0,
+            fields: self
+                .fields
+                .iter()
+                .map(|(_, sketch)| FieldSummary {
+                    sketch: sketch.then(QuantileSketch::default),
+                    ..FieldSummary::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Counts record `i` into `stats` and adds its aggregated values.
+    pub fn add_record<B: EmitRecord>(&self, batch: &B, i: usize, stats: &mut GroupStats) {
+        stats.count += 1;
+        for ((name, _), summary) in self.fields.iter().zip(&mut stats.fields) {
+            let style = numbers::style_of(name);
+            batch.visit_path(i, name, &mut |field| {
+                let number = match field {
+                    FieldValue::Text(v) | FieldValue::Escaped(v) => numbers::parse(v, style),
+                    FieldValue::Literal(v) => numbers::parse(v, NumberStyle::Plain),
+                    FieldValue::Timestamp(ts) => Some(ts as f64),
+                };
+                if let Some(v) = number.filter(|v: &f64| v.is_finite()) {
+                    summary.add(v);
+                }
+            });
+        }
+    }
+}
+
+/// Counts matched records per combination of field values
+/// (`--group-by component,level,status`) and aggregates numeric fields
+/// per group. Like the duplicate finder, each batch is grouped locally and
+/// merged under one lock.
+pub struct GroupBy {
+    keys: Vec<Vec<u8>>,
+    aggregator: Aggregator,
+    groups: Mutex<HashMap<Box<[u8]>, GroupStats>>,
+}
+
+impl GroupBy {
+    pub fn new(keys: Vec<Vec<u8>>, aggs: Vec<Agg>) -> GroupBy {
+        GroupBy {
+            keys,
+            aggregator: Aggregator::new(aggs),
+            groups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Parses a comma-separated key list; `None` if any name is empty.
+    pub fn parse_keys(spec: &str) -> Option<Vec<Vec<u8>>> {
+        spec.split(',')
+            .map(|k| k.trim())
+            .map(|k| (!k.is_empty()).then(|| k.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn write_key<B: EmitRecord>(
+        &self,
+        batch: &B,
+        i: usize,
+        key: &mut Vec<u8>,
+        value: &mut Vec<u8>,
+    ) {
+        key.clear();
+        for name in &self.keys {
+            let mut found = false;
+            value.clear();
+            batch.visit_path(i, name, &mut |field| {
+                found = true;
+                match field {
+                    FieldValue::Escaped(v) if v.contains(&b'\\') => {
+                        value.extend_from_slice(unescape_json(v).as_bytes())
+                    }
+                    FieldValue::Text(v) | FieldValue::Escaped(v) | FieldValue::Literal(v) => {
+                        value.extend_from_slice(v)
+                    }
+                    FieldValue::Timestamp(ts) => write_rfc3339(ts, value),
+                }
+            });
+            push_part(key, found.then_some(&value[..]));
+        }
+    }
+
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let mut local: HashMap<Vec<u8>, GroupStats> = HashMap::new();
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        for &i in records {
+            self.write_key(batch, i as usize, &mut key, &mut value);
+            let stats = match local.get_mut(&key[..]) {
+                Some(stats) => stats,
+                None => local
+                    .entry(key.clone())
+                    .or_insert_with(|| self.aggregator.new_stats()),
+            };
+            self.aggregator.add_record(batch, i as usize, stats);
+        }
+
+        let mut groups = self.groups.lock().unwrap();
+        for (key, stats) in local {
+            groups
+                .entry(key.into_boxed_slice())
+                .or_default()
+                .merge(&stats);
+        }
+    }
+
+    pub fn report(&self) -> GroupReport {
+        let groups = self.groups.lock().unwrap();
+        let mut rows: Vec<Group> = groups
+            .iter()
+            .map(|(key, stats)| Group {
+                values: split_parts(key),
+                stats: stats.clone(),
+            })
+            .collect();
+        rows.sort_by(|a, b| a.values.cmp(&b.values));
+        GroupReport {
+            keys: self
+                .keys
+                .iter()
+                .map(|k| String::from_utf8_lossy(k).into_owned())
+                .collect(),
+            aggs: self.aggregator.aggs.clone(),
+            total: rows.iter().map(|g| g.stats.count).sum(),
+            groups: rows,
+            filtered: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    /// One value per key, `None` where the record lacks the field.
+    pub values: Vec<Option<Vec<u8>>>,
+    pub stats: GroupStats,
+}
+
+impl Group {
+    /// What a `--having` expression sees: `count`, the key fields and the
+    /// aggregates, e.g. `p99_latency_ms`.
+    fn lookup(&self, keys: &[String], aggs: &[Agg], name: &[u8]) -> Option<Value> {
+        if name == b"count" {
+            return Some(Value::Num(self.stats.count as f64));
+        }
+        if let Some(k) = keys.iter().position(|key| key.as_bytes() == name) {
+            return self.values[k].clone().map(Value::Str);
+        }
+        let agg = aggs.iter().find(|a| a.having_name().as_bytes() == name)?;
+        agg.value(&self.stats).map(Value::Num)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GroupReport {
+    pub keys: Vec<String>,
+    pub aggs: Vec<Agg>,
+    pub total: u64,
+    /// Sorted by their values.
+    pub groups: Vec<Group>,
+    /// Groups dropped by [`GroupReport::retain`].
+    pub filtered: usize,
+}
+
+impl GroupReport {
+    /// Keeps the groups for which `having` holds, e.g. `count > 1000`.
+    /// Percentages stay relative to all records.
+    pub fn retain(&mut self, having: &Expr) {
+        let before = self.groups.len();
+        let (keys, aggs) = (&self.keys, &self.aggs);
+        self.groups
+            .retain(|g| having.eval(&|name| g.lookup(keys, aggs, name)).truthy());
+        self.filtered += before - self.groups.len();
+    }
+
+    /// Writes the level-`depth` nodes under `groups`, which share their
+    /// first `depth` values, largest first.
+    fn write_level(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        groups: &[Group],
+        depth: usize,
+    ) -> fmt::Result {
+        let mut nodes: Vec<(&[Group], GroupStats)> = Vec::new();
+        let mut start = 0;
+        while start < groups.len() {
+            let value = &groups[start].values[depth];
+            let len = groups[start..]
+                .iter()
+                .take_while(|g| g.values[depth] == *value)
+                .count();
+            let members = &groups[start..start + len];
+            let mut stats = GroupStats::default();
+            members.iter().for_each(|g| stats.merge(&g.stats));
+            nodes.push((members, stats));
+            start += len;
+        }
+        nodes.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.count));
+
+        let indent = 2 * (depth + 1);
+        for (members, stats) in nodes.iter().take(MAX_CHILDREN) {
+            let value = match &members[0].values[depth] {
+                Some(v) => String::from_utf8_lossy(v).into_owned(),
+                None => "(none)".to_string(),
+            };
+            let shown: String = value
+                .chars()
+                .take(42_usize.saturating_sub(indent))
+                .collect();
+            write!(
+                f,
+                "{:indent$}{:<width$} {:>10} {:>6.1}%",
+                "",
+                shown,
+                stats.count,
+                100.0 * stats.count as f64 / self.total.max(1) as f64,
+                width = 44_usize.saturating_sub(indent)
+            )?;
+            for agg in &self.aggs {
+                match agg.value(stats) {
+                    Some(v) => write!(f, " {:>16.2}", v)?,
+                    None => write!(f, " {:>16}", "-")?,
+                }
+            }
+            writeln!(f)?;
+            if depth + 1 < self.keys.len() {
+                self.write_level(f, members, depth + 1)?;
+            }
+        }
+        if nodes.len() > MAX_CHILDREN {
+            let rest = &nodes[MAX_CHILDREN..];
+            let count: u64 = rest.iter().map(|(_, s)| s.count).sum();
+            writeln!(
+                f,
+                "{:indent$}{:<width$} {:>10}",
+                "",
+                format!("({} more)", rest.len()),
+                count,
+                width = 44_usize.saturating_sub(indent)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for GroupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Group by {}: {} groups, {} records",
+            self.keys.join(", "),
+            self.groups.len(),
+            self.total
+        )?;
+        if self.filtered > 0 {
+            write!(f, " ({} more groups fail --having)", self.filtered)?;
+        }
+        writeln!(f)?;
+        if !self.aggs.is_empty() {
+            write!(
+                f,
+                "  {:<42} {:>10} {:>7}",
+                self.keys.join(" > "),
+                "count",
+                "%"
+            )?;
+            for agg in &self.aggs {
+                write!(f, " {:>16}", agg.label())?;
+            }
+            writeln!(f)?;
+        }
+        self.write_level(f, &self.groups, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_group_by_composite_keys() {
+        let data = b"component=api level=error status=500\n\
+                     component=api level=error status=503\n\
+                     component=api level=info status=200\n\
+                     component=db level=error status=500\n\
+                     component=api level=error status=500\n\
+                     component=api level=info\n";
+        let keys = GroupBy::parse_keys("component, level,status").unwrap();
+        let group_by = GroupBy::new(keys, vec![]);
+        let add = |batch: &StructuredBatch, records: &[u32]| group_by.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data, 1, Some(LogFormat::Logfmt), &control);
+
+        let report = group_by.report();
+        assert_eq!(report.total, 6);
+        let counts: Vec<(Vec<Option<&[u8]>>, u64)> = report
+            .groups
+            .iter()
+            .map(|g| {
+                (
+                    g.values.iter().map(|v| v.as_deref()).collect(),
+                    g.stats.count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (vec![Some(&b"api"[..]), Some(b"error"), Some(b"500")], 2),
+                (vec![Some(&b"api"[..]), Some(b"error"), Some(b"503")], 1),
+                (vec![Some(&b"api"[..]), Some(b"info"), None], 1),
+                (vec![Some(&b"api"[..]), Some(b"info"), Some(b"200")], 1),
+                (vec![Some(&b"db"[..]), Some(b"error"), Some(b"500")], 1),
+            ]
+        );
+
+        let text = report.to_string();
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines[0],
+            "Group by component, level, status: 5 groups, 6 records"
+        );
+        assert!(lines[1].starts_with("  api ") && lines[1].ends_with("5   83.3%"));
+        assert!(lines[2].starts_with("    error ") && lines[2].ends_with("3   50.0%"));
+        assert!(lines[3].starts_with("      500 ") && lines[3].ends_with("2   33.3%"));
+        assert!(GroupBy::parse_keys("a,,b").is_none());
+
+        let mut report = group_by.report();
+        report.retain(&Expr::parse("count > 1 or status == 503").unwrap());
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.filtered, 3);
+        assert_eq!(report.groups[1].values[2].as_deref(), Some(&b"503"[..]));
+        assert!(report.to_string().starts_with(
+            "Group by component, level, status: 2 groups, 6 records (3 more groups fail --having)\n"
+        ));
+
+        // Escapes are resolved, so one value written two ways is one group.
+        let json = b"{\"msg\":\"a\\\"b\"}\n{\"msg\":\"a\\u0022b\"}\n";
+        let group_by = GroupBy::new(GroupBy::parse_keys("msg").unwrap(), vec![]);
+        let add = |batch: &StructuredBatch, records: &[u32]| group_by.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(json, 1, Some(LogFormat::Json), &control);
+        let report = group_by.report();
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].values[0].as_deref(), Some(&b"a\"b"[..]));
+    }
+
+    #[test]
+    fn test_group_aggregates() {
+        let mut data = Vec::new();
+        for n in 1..=1000 {
+            let path = if n % 4 == 0 { "/slow" } else { "/fast" };
+            let latency = if n % 4 == 0 { n } else { n % 10 };
+            data.extend_from_slice(format!("path={} latency_ms={}\n", path, latency).as_bytes());
+        }
+        data.extend_from_slice(b"path=/fast latency_ms=n/a\n");
+        let aggs = Agg::parse_list("latency_ms:p99,latency_ms:avg,latency_ms:max").unwrap();
+        assert!(Agg::parse_list("latency_ms:p101").is_err());
+        assert!(Agg::parse_list("latency_ms").is_err());
+        let group_by = GroupBy::new(vec![b"path".to_vec()], aggs);
+        let add = |batch: &StructuredBatch, records: &[u32]| group_by.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(&data, 2, Some(LogFormat::Logfmt), &control);
+
+        let mut report = group_by.report();
+        let values = |g: &Group| -> Vec<f64> {
+            report
+                .aggs
+                .iter()
+                .map(|a| a.value(&g.stats).unwrap())
+                .collect()
+        };
+        let fast = values(&report.groups[0]);
+        assert_eq!(report.groups[0].stats.count, 751);
+        assert!((fast[0] - 9.0).abs() <= 0.1 && (fast[1] - 4.666).abs() < 0.01 && fast[2] == 9.0);
+        let slow = values(&report.groups[1]);
+        assert!((slow[0] - 990.0).abs() <= 10.0 && slow[1] == 502.0 && slow[2] == 1000.0);
+
+        let text = report.to_string();
+        let header = text.lines().nth(1).unwrap();
+        assert!(header.contains("p99(latency_ms)") && header.contains("avg(latency_ms)"));
+
+        report.retain(&Expr::parse("p99_latency_ms > 100").unwrap());
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].values[0].as_deref(), Some(&b"/slow"[..]));
+    }
+}
@@ -0,0 +1,264 @@
+//! Background parsing for embedders that cannot block on a parse, such as
+//! a GUI or a server: [`start`] maps a file and parses it on its own thread,
+//! and the returned [`ParseHandle`] reports progress, cancels the workers
+//! and hands over the result once they are done.
+
+use crate::data::LogBatch;
+use crate::filter::{MatchControl, ParseProgress};
+use crate::format::LogFormat;
+use crate::orchestrator::{PipelineResult, parse_logs_pipelined_with};
+use crate::structured::StructuredBatch;
+use crate::structured_orchestrator::{
+    SCHEMA_SAMPLE_BYTES, StructuredPipelineResult, parse_structured_mmap_with, redetect_format,
+};
+use crate::values::{OwnedRecord, ValueCache};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+pub type PlainBatchFn = dyn Fn(&LogBatch, &[u32]) + Send + Sync;
+
+pub type StructuredBatchFn = dyn Fn(&StructuredBatch, &[u32]) + Send + Sync;
+
+/// How [`start`] parses. The callbacks receive each batch's records as
+/// soon as a worker has parsed them, from the worker threads; `on_done`
+/// runs on the parse thread once the result is ready to [`ParseHandle::wait`]
+/// for, to wake an event loop. Callbacks that keep records past the call
+/// copy them out with a [`ValueCache`] per thread; after the parse,
+/// [`ParseOutput::owned_records`] does the same for the whole output.
+pub struct ParseOptions {
+    pub threads: usize,
+    /// Detected from the head of the file when unset.
+    pub format: Option<LogFormat>,
+    pub on_plain: Option<Box<PlainBatchFn>>,
+    pub on_structured: Option<Box<StructuredBatchFn>>,
+    pub on_done: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            format: None,
+            on_plain: None,
+            on_structured: None,
+            on_done: None,
+        }
+    }
+}
+
+/// What a parse read so far, against the size of its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub bytes: u64,
+    pub total_bytes: u64,
+    pub records: u64,
+}
+
+pub enum ParseResult {
+    Plain(PipelineResult),
+    Structured(StructuredPipelineResult),
+}
+
+/// A finished parse. Its batches point into the mapping it keeps alive.
+pub struct ParseOutput {
+    pub result: ParseResult,
+    pub format: LogFormat,
+    /// Set when the parse was cancelled before reading the whole input.
+    pub cancelled: bool,
+    _mmap: Option<Mmap>,
+}
+
+impl ParseOutput {
+    /// Every kept record copied out of the mapping, in batch order. One
+    /// [`ValueCache`] serves the whole output, so repeated keys and small
+    /// values share one allocation.
+    pub fn owned_records(&self) -> Vec<OwnedRecord> {
+        let mut cache = ValueCache::default();
+        match &self.result {
+            ParseResult::Plain(result) => result
+                .batches
+                .iter()
+                .flat_map(|batch| (0..batch.len).map(move |i| (batch, i)))
+                .map(|(batch, i)| cache.record(batch, i))
+                .collect(),
+            ParseResult::Structured(result) => result
+                .batches
+                .iter()
+                .flat_map(|batch| (0..batch.len).map(move |i| (batch, i)))
+                .map(|(batch, i)| cache.record(batch, i))
+                .collect(),
+        }
+    }
+}
+
+pub struct ParseHandle {
+    progress: Arc<ParseProgress>,
+    total_bytes: u64,
+    thread: JoinHandle<ParseOutput>,
+}
+
+impl ParseHandle {
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes: self.progress.bytes(),
+            total_bytes: self.total_bytes,
+            records: self.progress.records(),
+        }
+    }
+
+    /// Stops the workers once their current chunks are parsed; what they
+    /// parsed so far is still returned by [`ParseHandle::wait`].
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Blocks until the parse is done, re-raising a worker's panic.
+    pub fn wait(self) -> ParseOutput {
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+/// Maps `path` and starts parsing it in the background.
+pub fn start(path: &Path, options: ParseOptions) -> io::Result<ParseHandle> {
+    let file = File::open(path)?;
+    let mmap = match file.metadata()?.len() {
+        0 => None,
+        _ => Some(unsafe { Mmap::map(&file)? }),
+    };
+    let total_bytes = mmap.as_ref().map_or(0, |mmap| mmap.len() as u64);
+    let progress = Arc::new(ParseProgress::default());
+    let shared = Arc::clone(&progress);
+    let thread = thread::Builder::new()
+        .name("pandora-parse".to_string())
+        .spawn(move || parse(mmap, options, &shared))?;
+    Ok(ParseHandle {
+        progress,
+        total_bytes,
+        thread,
+    })
+}
+
+fn parse(mmap: Option<Mmap>, options: ParseOptions, progress: &ParseProgress) -> ParseOutput {
+    let data: &[u8] = mmap.as_deref().unwrap_or_default();
+    let format = options.format.unwrap_or_else(|| {
+        let detected = LogFormat::detect(&data[..data.len().min(4096)]);
+        redetect_format(&data[..data.len().min(SCHEMA_SAMPLE_BYTES)], detected).unwrap_or(detected)
+    });
+    let result = if format == LogFormat::PlainText {
+        let control = MatchControl {
+            progress: Some(progress),
+            on_batch: options.on_plain.as_deref().map(|f| f as _),
+            ..MatchControl::default()
+        };
+        ParseResult::Plain(parse_logs_pipelined_with(data, options.threads, &control))
+    } else {
+        let control = MatchControl {
+            progress: Some(progress),
+            on_batch: options.on_structured.as_deref().map(|f| f as _),
+            ..MatchControl::default()
+        };
+        ParseResult::Structured(parse_structured_mmap_with(
+            data,
+            options.threads,
+            Some(format),
+            &control,
+        ))
+    };
+    let output = ParseOutput {
+        result,
+        format,
+        cancelled: progress.is_cancelled() && progress.bytes() < data.len() as u64,
+        _mmap: mmap,
+    };
+    if let Some(on_done) = options.on_done {
+        on_done();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_parse_handle_reports_progress_and_cancels() {
+        let mut data = Vec::new();
+        for i in 0..1000 {
+            data.extend_from_slice(format!("level=info msg=\"request {}\"\n", i).as_bytes());
+        }
+        let path = std::env::temp_dir().join(format!("pandora-handle-{}", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+
+        let matched = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        let (seen, finished) = (Arc::clone(&matched), Arc::clone(&done));
+        let options = ParseOptions {
+            threads: 2,
+            on_structured: Some(Box::new(move |_, records| {
+                seen.fetch_add(records.len(), Ordering::Relaxed);
+            })),
+            on_done: Some(Box::new(move || {
+                finished.fetch_add(1, Ordering::Relaxed);
+            })),
+            ..ParseOptions::default()
+        };
+        let handle = start(&path, options).unwrap();
+        let total_bytes = handle.progress().total_bytes;
+        let output = handle.wait();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output.format, LogFormat::Logfmt);
+        assert!(!output.cancelled);
+        assert_eq!(total_bytes, data.len() as u64);
+        assert_eq!(matched.load(Ordering::Relaxed), 1000);
+        assert_eq!(done.load(Ordering::Relaxed), 1);
+
+        // A cancelled parse stops before the next chunk.
+        let progress = ParseProgress::default();
+        progress.cancel();
+        let control = MatchControl {
+            progress: Some(&progress),
+            ..MatchControl::default()
+        };
+        let result = parse_structured_mmap_with(&data, 1, Some(LogFormat::Logfmt), &control);
+        assert_eq!(result.total_records, 0);
+        assert_eq!(progress.records(), 0);
+    }
+
+    #[test]
+    fn test_owned_records_share_repeated_values() {
+        use crate::values::OwnedValue;
+
+        let data = b"{\"status\":\"200\",\"ts\":\"2025-02-12T10:31:45Z\"}\n\
+                     {\"status\":\"200\",\"ts\":\"2025-02-12T10:31:46Z\"}\n";
+        let path = std::env::temp_dir().join(format!("pandora-owned-{}", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let options = ParseOptions {
+            threads: 1,
+            ..ParseOptions::default()
+        };
+        let output = start(&path, options).unwrap().wait();
+        std::fs::remove_file(&path).unwrap();
+
+        let records = output.owned_records();
+        assert_eq!(records.len(), 2);
+        let (OwnedValue::Text(a), OwnedValue::Text(b)) = (&records[0][0].1, &records[1][0].1)
+        else {
+            panic!("status is text");
+        };
+        assert_eq!(&**a, "200");
+        assert!(Arc::ptr_eq(a, b));
+        assert!(Arc::ptr_eq(&records[0][1].0, &records[1][1].0));
+    }
+}
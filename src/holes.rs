@@ -0,0 +1,78 @@
+/// Zero-filled stretches shorter than this are left to the parser. Holes in
+/// sparse or preallocated files are whole pages, and real log data never
+/// contains a page worth of NULs.
+const PAGE: usize = 4096;
+
+/// Splits `data[start..end]` around runs of NULs that cover at least one
+/// whole page, returning the non-zero segments in file order and the number
+/// of NUL bytes skipped. Pages are probed by their first byte, so data with
+/// no holes costs one load per page.
+pub fn data_segments(data: &[u8], start: usize, end: usize) -> (Vec<(usize, usize)>, u64) {
+    let mut segments = Vec::with_capacity(1);
+    let mut skipped = 0u64;
+    let mut seg_start = start;
+    let mut page = start.next_multiple_of(PAGE);
+    while page + PAGE <= end {
+        if data[page] != 0 || !is_zero(&data[page..page + PAGE]) {
+            page += PAGE;
+            continue;
+        }
+        let mut hole_end = page + PAGE;
+        while hole_end + PAGE <= end && is_zero(&data[hole_end..hole_end + PAGE]) {
+            hole_end += PAGE;
+        }
+        let mut hole_start = page;
+        while hole_start > seg_start && data[hole_start - 1] == 0 {
+            hole_start -= 1;
+        }
+        while hole_end < end && data[hole_end] == 0 {
+            hole_end += 1;
+        }
+        if hole_start > seg_start {
+            segments.push((seg_start, hole_start));
+        }
+        skipped += (hole_end - hole_start) as u64;
+        seg_start = hole_end;
+        page = hole_end.next_multiple_of(PAGE);
+    }
+    if seg_start < end {
+        segments.push((seg_start, end));
+    }
+    (segments, skipped)
+}
+
+#[inline]
+fn is_zero(page: &[u8]) -> bool {
+    page.chunks_exact(64)
+        .all(|block| block.iter().fold(0u8, |acc, &b| acc | b) == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_segments_skip_nul_runs() {
+        let mut data = b"a=1\nb=2\n".to_vec();
+        data.resize(3 * PAGE + 10, 0);
+        data.extend_from_slice(b"c=3\n");
+        let (segments, skipped) = data_segments(&data, 0, data.len());
+        assert_eq!(segments, [(0, 8), (3 * PAGE + 10, 3 * PAGE + 14)]);
+        assert_eq!(skipped, 3 * PAGE as u64 + 2);
+
+        // Short NUL runs stay in the data.
+        let mut data = vec![b'x'; 2 * PAGE];
+        data[PAGE + 5..PAGE + 100].fill(0);
+        assert_eq!(
+            data_segments(&data, 0, data.len()),
+            (vec![(0, 2 * PAGE)], 0)
+        );
+
+        let zeros = vec![0u8; 2 * PAGE];
+        assert_eq!(
+            data_segments(&zeros, 0, zeros.len()),
+            (vec![], 2 * PAGE as u64)
+        );
+        assert_eq!(data_segments(&zeros, 10, 20), (vec![(10, 20)], 0));
+    }
+}
@@ -0,0 +1,359 @@
+use crate::emit::{EmitRecord, FieldValue, write_rfc3339};
+use crate::group::{Agg, GroupBy, GroupReport};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// Field names tried, in order, for each part of a request.
+const STATUS_FIELDS: &[&str] = &[
+    "status",
+    "status_code",
+    "http_status",
+    "response_status",
+    "http.status_code",
+    "http.response.status_code",
+];
+const PATH_FIELDS: &[&str] = &[
+    "path",
+    "request_path",
+    "uri",
+    "request_uri",
+    "url",
+    "url.path",
+    "http.target",
+    "http.url",
+];
+const LATENCY_FIELDS: &[&str] = &[
+    "latency_ms",
+    "duration_ms",
+    "response_time_ms",
+    "elapsed_ms",
+    "request_time",
+    "response_time",
+    "latency",
+    "duration",
+];
+
+/// Paths listed, most requested first.
+const TOP_PATHS: usize = 10;
+
+/// Most rows in the error-rate timeline; minutes are merged to fit.
+const TIMELINE_ROWS: usize = 24;
+
+/// Timeline steps tried, shortest first; longer spans use whole days.
+const STEP_MINUTES: [u64; 10] = [1, 2, 5, 10, 15, 30, 60, 120, 360, 720];
+
+/// The fields an access log names its status, path and latency with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpFields {
+    pub status: String,
+    pub path: Option<String>,
+    pub latency: Option<String>,
+}
+
+impl HttpFields {
+    /// Picks the first known name of each part that record `i` has; `None`
+    /// without a status field.
+    pub fn detect<B: EmitRecord>(batch: &B, i: usize) -> Option<HttpFields> {
+        let find = |names: &[&str]| {
+            names
+                .iter()
+                .find(|name| {
+                    let mut found = false;
+                    batch.visit_field(i, name.as_bytes(), &mut |_| found = true);
+                    found
+                })
+                .map(|name| name.to_string())
+        };
+        Some(HttpFields {
+            status: find(STATUS_FIELDS)?,
+            path: find(PATH_FIELDS),
+            latency: find(LATENCY_FIELDS),
+        })
+    }
+}
+
+struct Groups {
+    fields: HttpFields,
+    /// Requests per status code.
+    statuses: GroupBy,
+    /// Requests and latency per path.
+    paths: Option<GroupBy>,
+}
+
+/// `--http-summary`: requests by status class, the busiest paths with their
+/// p99 latency, and the 5xx rate over time. Status and path counts are
+/// `--group-by` groups; field names are taken from the first record with
+/// a status field.
+#[derive(Default)]
+pub struct HttpSummary {
+    groups: OnceLock<Groups>,
+    /// Requests and 5xx responses per minute of record time.
+    minutes: Mutex<BTreeMap<u64, (u64, u64)>>,
+}
+
+fn status_code<B: EmitRecord>(batch: &B, i: usize, field: &[u8]) -> Option<u16> {
+    let mut code = None;
+    batch.visit_field(i, field, &mut |value| {
+        if let FieldValue::Text(v) | FieldValue::Escaped(v) | FieldValue::Literal(v) = value {
+            code = std::str::from_utf8(v)
+                .ok()
+                .and_then(|s| s.trim().parse().ok());
+        }
+    });
+    code
+}
+
+impl HttpSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn groups<B: EmitRecord>(&self, batch: &B, records: &[u32]) -> Option<&Groups> {
+        if let Some(groups) = self.groups.get() {
+            return Some(groups);
+        }
+        let fields = records
+            .iter()
+            .find_map(|&i| HttpFields::detect(batch, i as usize))?;
+        Some(self.groups.get_or_init(|| {
+            let aggs = match &fields.latency {
+                Some(latency) => Agg::parse_list(&format!("{}:p99", latency)).unwrap_or_default(),
+                None => vec![],
+            };
+            Groups {
+                statuses: GroupBy::new(vec![fields.status.as_bytes().to_vec()], vec![]),
+                paths: fields
+                    .path
+                    .as_ref()
+                    .map(|path| GroupBy::new(vec![path.as_bytes().to_vec()], aggs)),
+                fields,
+            }
+        }))
+    }
+
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let Some(groups) = self.groups(batch, records) else {
+            return;
+        };
+        groups.statuses.add_records(batch, records);
+        if let Some(paths) = &groups.paths {
+            paths.add_records(batch, records);
+        }
+
+        let status = groups.fields.status.as_bytes();
+        let mut local: BTreeMap<u64, (u64, u64)> = BTreeMap::new();
+        for &i in records {
+            let Some(ts) = batch.record_timestamp(i as usize) else {
+                continue;
+            };
+            let minute = local.entry(ts - ts % 60).or_default();
+            minute.0 += 1;
+            minute.1 += u64::from(status_code(batch, i as usize, status).is_some_and(|c| c >= 500));
+        }
+        let mut minutes = self.minutes.lock().unwrap();
+        for (start, (total, errors)) in local {
+            let minute = minutes.entry(start).or_default();
+            minute.0 += total;
+            minute.1 += errors;
+        }
+    }
+
+    pub fn report(&self) -> HttpReport {
+        let Some(groups) = self.groups.get() else {
+            return HttpReport::default();
+        };
+        let statuses = groups.statuses.report();
+        let mut classes = [0u64; 6];
+        for group in &statuses.groups {
+            let class = group.values[0]
+                .as_deref()
+                .and_then(|v| std::str::from_utf8(v).ok())
+                .and_then(|s| s.trim().parse::<u16>().ok())
+                .map(|code| code / 100)
+                .filter(|class| (1..=5).contains(class))
+                .map_or(0, usize::from);
+            classes[class] += group.stats.count;
+        }
+
+        let minutes = self.minutes.lock().unwrap();
+        let span = match (minutes.keys().next(), minutes.keys().next_back()) {
+            (Some(first), Some(last)) => last - first + 60,
+            _ => 60,
+        };
+        let step = STEP_MINUTES
+            .iter()
+            .map(|m| m * 60)
+            .find(|step| span.div_ceil(*step) <= TIMELINE_ROWS as u64)
+            .unwrap_or_else(|| span.div_ceil(TIMELINE_ROWS as u64).div_ceil(86400) * 86400);
+        let mut timeline: Vec<(u64, u64, u64)> = Vec::new();
+        for (&start, &(total, errors)) in minutes.iter() {
+            let start = start - start % step;
+            match timeline.last_mut() {
+                Some(row) if row.0 == start => {
+                    row.1 += total;
+                    row.2 += errors;
+                }
+                _ => timeline.push((start, total, errors)),
+            }
+        }
+
+        HttpReport {
+            fields: Some(groups.fields.clone()),
+            requests: statuses.total,
+            classes,
+            paths: groups.paths.as_ref().map(GroupBy::report),
+            step,
+            timeline,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HttpReport {
+    /// `None` when no record had a status field.
+    pub fields: Option<HttpFields>,
+    pub requests: u64,
+    /// Requests per status class: index 1-5 for 1xx-5xx, 0 for the rest.
+    pub classes: [u64; 6],
+    pub paths: Option<GroupReport>,
+    /// Seconds per timeline row.
+    pub step: u64,
+    /// Row start, requests and 5xx responses.
+    pub timeline: Vec<(u64, u64, u64)>,
+}
+
+impl fmt::Display for HttpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(fields) = &self.fields else {
+            return writeln!(
+                f,
+                "HTTP summary: no record has a status field ({})",
+                STATUS_FIELDS.join(", ")
+            );
+        };
+        writeln!(
+            f,
+            "HTTP summary: {} requests (status: {}, path: {}, latency: {})",
+            self.requests,
+            fields.status,
+            fields.path.as_deref().unwrap_or("-"),
+            fields.latency.as_deref().unwrap_or("-")
+        )?;
+        let pct = |n: u64| 100.0 * n as f64 / self.requests.max(1) as f64;
+        for (class, &count) in self.classes.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let label = match class {
+                0 => "other".to_string(),
+                c => format!("{}xx", c),
+            };
+            writeln!(f, "  {:<8} {:>10} {:>6.1}%", label, count, pct(count))?;
+        }
+
+        if let Some(paths) = &self.paths {
+            let mut groups: Vec<_> = paths.groups.iter().collect();
+            groups.sort_by_key(|g| std::cmp::Reverse(g.stats.count));
+            writeln!(f, "  Top paths:")?;
+            for group in groups.iter().take(TOP_PATHS) {
+                let path = match &group.values[0] {
+                    Some(v) => String::from_utf8_lossy(v).into_owned(),
+                    None => "(none)".to_string(),
+                };
+                let shown: String = path.chars().take(40).collect();
+                write!(f, "    {:<40} {:>10}", shown, group.stats.count)?;
+                if let Some(agg) = paths.aggs.first() {
+                    match agg.value(&group.stats) {
+                        Some(v) => write!(f, "  {} {:.1}", agg.label(), v)?,
+                        None => write!(f, "  {} -", agg.label())?,
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+
+        if !self.timeline.is_empty() {
+            writeln!(f, "  5xx rate per {} min:", self.step / 60)?;
+            for &(start, total, errors) in &self.timeline {
+                let mut time = Vec::new();
+                write_rfc3339(start, &mut time);
+                let rate = 100.0 * errors as f64 / total.max(1) as f64;
+                writeln!(
+                    f,
+                    "    {} {:>10} {:>8} {:>6.2}% {}",
+                    String::from_utf8_lossy(&time),
+                    total,
+                    errors,
+                    rate,
+                    "#".repeat((rate / 2.5).ceil() as usize)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_http_summary_from_access_log() {
+        let mut data = String::new();
+        for n in 0..200u64 {
+            let (path, status) = match n % 10 {
+                0 => ("/login", 500),
+                1 | 2 => ("/login", 302),
+                3 => ("/missing", 404),
+                _ => ("/", 200),
+            };
+            let secs = n * 30;
+            data.push_str(&format!(
+                "{{\"timestamp\":\"2025-02-12T{:02}:{:02}:{:02}Z\",\"path\":\"{}\",\"status\":{},\"duration_ms\":{}}}\n",
+                secs / 3600,
+                secs % 3600 / 60,
+                secs % 60,
+                path,
+                status,
+                n % 100
+            ));
+        }
+        let summary = HttpSummary::new();
+        let add = |batch: &StructuredBatch, records: &[u32]| summary.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data.as_bytes(), 1, Some(LogFormat::Json), &control);
+
+        let report = summary.report();
+        assert_eq!(
+            report.fields,
+            Some(HttpFields {
+                status: "status".to_string(),
+                path: Some("path".to_string()),
+                latency: Some("duration_ms".to_string()),
+            })
+        );
+        assert_eq!(report.requests, 200);
+        assert_eq!(report.classes, [0, 0, 120, 40, 20, 20]);
+        let paths = report.paths.as_ref().unwrap();
+        assert_eq!(paths.groups.len(), 3);
+        assert_eq!(report.timeline.iter().map(|r| r.1).sum::<u64>(), 200);
+        assert_eq!(report.timeline.iter().map(|r| r.2).sum::<u64>(), 20);
+        assert!(report.timeline.len() <= TIMELINE_ROWS);
+
+        let text = report.to_string();
+        assert!(text.contains("  5xx              20   10.0%"), "{}", text);
+        assert!(text.contains("    /login "), "{}", text);
+        assert!(text.contains("p99(duration_ms)"), "{}", text);
+
+        let empty = HttpSummary::new().report();
+        assert!(empty.to_string().starts_with("HTTP summary: no record"));
+    }
+}
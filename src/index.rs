@@ -0,0 +1,289 @@
+use crate::csv_parser::CsvHeader;
+use crate::dedup::hash_line;
+use crate::format::LogFormat;
+use crate::seek::{line_timestamp, seek_to_time};
+use crate::simd_scan::record_sep;
+use crate::store;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+/// Distance between checkpoints.
+const STRIDE: u64 = 1024 * 1024;
+
+/// Bytes at the head of the file that identify it across appends.
+const PREFIX_BYTES: u64 = 64 * 1024;
+
+/// Bytes just before the indexed end that must still be there for the
+/// file to count as appended to rather than rewritten.
+const TAIL_BYTES: u64 = 4096;
+
+const MAGIC: &str = "pidx-v1";
+
+/// First line boundary at or after a multiple of `STRIDE` and the first
+/// timestamp on a line starting within the following stride, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub offset: u64,
+    pub first_ts: Option<u64>,
+}
+
+/// Sparse time index of a log file, kept in a `.pidx` file (see
+/// [`store::index_path`]). It
+/// covers bytes `[0, indexed_len)`, which end at a newline; when the file
+/// has only grown since, [`SparseIndex::update`] indexes just the new tail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseIndex {
+    pub format: LogFormat,
+    pub indexed_len: u64,
+    prefix_hash: u64,
+    tail_hash: u64,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+/// What [`SparseIndex::update`] had to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexUpdate {
+    Unchanged,
+    /// The file grew; only bytes from the old end were indexed.
+    Extended {
+        from: u64,
+    },
+    /// New, rewritten or truncated file, or another format.
+    Rebuilt,
+}
+
+impl SparseIndex {
+    pub fn new(format: LogFormat) -> SparseIndex {
+        SparseIndex {
+            format,
+            indexed_len: 0,
+            prefix_hash: hash_line(&[]),
+            tail_hash: hash_line(&[]),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn build(data: &[u8], format: LogFormat) -> SparseIndex {
+        let mut index = SparseIndex::new(format);
+        index.extend(data);
+        index
+    }
+
+    /// Brings the index up to date with `data`, the whole file as it is now.
+    pub fn update(&mut self, data: &[u8], format: LogFormat) -> IndexUpdate {
+        if format != self.format || !self.is_prefix_of(data) {
+            *self = SparseIndex::build(data, format);
+            return IndexUpdate::Rebuilt;
+        }
+        let from = self.indexed_len;
+        self.extend(data);
+        if self.indexed_len == from {
+            IndexUpdate::Unchanged
+        } else {
+            IndexUpdate::Extended { from }
+        }
+    }
+
+    fn is_prefix_of(&self, data: &[u8]) -> bool {
+        let len = self.indexed_len;
+        (data.len() as u64) >= len
+            && hash_line(&data[..len.min(PREFIX_BYTES) as usize]) == self.prefix_hash
+            && hash_line(&data[len.saturating_sub(TAIL_BYTES) as usize..len as usize])
+                == self.tail_hash
+    }
+
+    fn extend(&mut self, data: &[u8]) {
+        let Some(last_nl) = memchr::memrchr(record_sep(), data) else {
+            return;
+        };
+        let end = last_nl as u64 + 1;
+        if end <= self.indexed_len {
+            return;
+        }
+        let csv_header = match self.format {
+            LogFormat::Csv => CsvHeader::parse(data),
+            _ => None,
+        };
+
+        // Checkpoints whose stride ran past the old end may have missed
+        // their first timestamp; redo them with the appended bytes.
+        while self
+            .checkpoints
+            .last()
+            .is_some_and(|cp| cp.offset + STRIDE > self.indexed_len)
+        {
+            self.checkpoints.pop();
+        }
+        let mut target = self
+            .checkpoints
+            .last()
+            .map_or(0, |cp| (cp.offset / STRIDE + 1) * STRIDE);
+        while target < end {
+            let offset = match target {
+                0 => 0,
+                _ => match memchr::memchr(record_sep(), &data[target as usize - 1..end as usize]) {
+                    Some(off) => target + off as u64,
+                    None => break,
+                },
+            };
+            if offset >= end {
+                break;
+            }
+            let window_end = (offset + STRIDE).min(end);
+            self.checkpoints.push(Checkpoint {
+                offset,
+                first_ts: first_timestamp(
+                    &data[offset as usize..window_end as usize],
+                    self.format,
+                    csv_header.as_ref(),
+                ),
+            });
+            target = (offset / STRIDE + 1) * STRIDE;
+        }
+
+        self.indexed_len = end;
+        self.prefix_hash = hash_line(&data[..end.min(PREFIX_BYTES) as usize]);
+        self.tail_hash = hash_line(&data[end.saturating_sub(TAIL_BYTES) as usize..end as usize]);
+    }
+
+    /// [`seek_to_time`] over `data`, which starts at `input_offset` in the
+    /// indexed file, binary-searching only the stretch between the two
+    /// checkpoints around `ts`.
+    pub fn seek_to_time(
+        &self,
+        data: &[u8],
+        input_offset: u64,
+        format: LogFormat,
+        csv_header: Option<&CsvHeader>,
+        ts: u64,
+    ) -> usize {
+        let local = |offset: u64| (offset.saturating_sub(input_offset) as usize).min(data.len());
+        if format != self.format {
+            return seek_to_time(data, format, csv_header, ts);
+        }
+        let mut lo = 0;
+        let mut hi = data.len();
+        for (k, cp) in self.checkpoints.iter().enumerate() {
+            match cp.first_ts {
+                Some(t) if t < ts => lo = local(cp.offset),
+                Some(_) => {
+                    if let Some(next) = self.checkpoints.get(k + 1) {
+                        hi = local(next.offset);
+                    }
+                    break;
+                }
+                None => {}
+            }
+        }
+        lo + seek_to_time(&data[lo..hi.max(lo)], format, csv_header, ts)
+    }
+
+    pub fn load(path: &Path) -> Option<SparseIndex> {
+        let text = std::fs::read_to_string(path).ok()?;
+        let mut lines = text.lines();
+        let mut header = lines.next()?.split(' ');
+        if header.next()? != MAGIC {
+            return None;
+        }
+        let mut index = SparseIndex {
+            format: LogFormat::from_name(header.next()?)?,
+            indexed_len: header.next()?.parse().ok()?,
+            prefix_hash: header.next()?.parse().ok()?,
+            tail_hash: header.next()?.parse().ok()?,
+            checkpoints: Vec::new(),
+        };
+        for line in lines {
+            let (offset, ts) = line.split_once(' ')?;
+            index.checkpoints.push(Checkpoint {
+                offset: offset.parse().ok()?,
+                first_ts: match ts {
+                    "-" => None,
+                    ts => Some(ts.parse().ok()?),
+                },
+            });
+        }
+        Some(index)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = format!(
+            "{} {} {} {} {}\n",
+            MAGIC,
+            self.format.as_str(),
+            self.indexed_len,
+            self.prefix_hash,
+            self.tail_hash
+        );
+        for cp in &self.checkpoints {
+            match cp.first_ts {
+                Some(ts) => writeln!(text, "{} {}", cp.offset, ts),
+                None => writeln!(text, "{} -", cp.offset),
+            }
+            .unwrap();
+        }
+        store::publish(path, text.as_bytes())
+    }
+}
+
+fn first_timestamp(
+    window: &[u8],
+    format: LogFormat,
+    csv_header: Option<&CsvHeader>,
+) -> Option<u64> {
+    window
+        .split(|&b| b == record_sep())
+        .find_map(|line| line_timestamp(line, format, csv_header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(from: u64, lines: u64) -> String {
+        (from..from + lines)
+            .map(|n| {
+                format!(
+                    "2025-02-12T{:02}:{:02}:{:02}Z INFO api request {:06} done\n",
+                    n / 3600 % 24,
+                    n / 60 % 60,
+                    n % 60,
+                    n
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_index_extends_appended_files() {
+        let mut data = log(0, 40_000).into_bytes();
+        let mut index = SparseIndex::build(&data, LogFormat::PlainText);
+        assert!(index.checkpoints.len() > 1);
+        assert_eq!(index.indexed_len, data.len() as u64);
+
+        let ts = crate::parser::parse_timestamp(b"2025-02-12T08:00:00Z").unwrap();
+        let expected = seek_to_time(&data, LogFormat::PlainText, None, ts);
+        assert_eq!(
+            index.seek_to_time(&data, 0, LogFormat::PlainText, None, ts),
+            expected
+        );
+
+        let old_len = data.len() as u64;
+        data.extend_from_slice(log(40_000, 30_000).as_bytes());
+        assert_eq!(
+            index.update(&data, LogFormat::PlainText),
+            IndexUpdate::Extended { from: old_len }
+        );
+        assert_eq!(index, SparseIndex::build(&data, LogFormat::PlainText));
+        assert_eq!(
+            index.update(&data, LogFormat::PlainText),
+            IndexUpdate::Unchanged
+        );
+
+        data[10] = b'9';
+        assert_eq!(
+            index.update(&data, LogFormat::PlainText),
+            IndexUpdate::Rebuilt
+        );
+    }
+}
@@ -0,0 +1,109 @@
+/// Parses a dotted-quad IPv4 address. The loop has no early exits and keeps
+/// its state in a few registers, so it compiles to straight-line code over
+/// at most 15 bytes; validity is checked once at the end.
+#[inline]
+pub fn parse_ipv4(text: &[u8]) -> Option<u32> {
+    if !(7..=15).contains(&text.len()) {
+        return None;
+    }
+    let mut addr = 0u32;
+    let mut octet = 0u32;
+    let mut digits = 0u32;
+    let mut dots = 0u32;
+    let mut bad = false;
+    for &b in text {
+        let digit = b.wrapping_sub(b'0');
+        let is_dot = b == b'.';
+        bad |= !is_dot && digit > 9;
+        // A dot closes the octet: it must have 1-3 digits and fit a byte.
+        bad |= is_dot & (digits == 0 || octet > 255);
+        addr = if is_dot { (addr << 8) | octet } else { addr };
+        octet = if is_dot {
+            0
+        } else {
+            octet.wrapping_mul(10).wrapping_add(u32::from(digit))
+        };
+        digits = if is_dot { 0 } else { digits + 1 };
+        bad |= digits > 3;
+        dots += u32::from(is_dot);
+    }
+    (!bad && dots == 3 && digits > 0 && octet <= 255).then_some((addr << 8) | octet)
+}
+
+/// An IPv4 network such as `10.0.0.0/8`; a bare address is a `/32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    pub network: u32,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(text: &str) -> Result<Cidr, String> {
+        let (addr, prefix) = match text.split_once('/') {
+            Some((addr, prefix)) => (
+                addr,
+                prefix
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|p| *p <= 32)
+                    .ok_or_else(|| format!("bad prefix length in '{}'", text))?,
+            ),
+            None => (text, 32),
+        };
+        let addr =
+            parse_ipv4(addr.as_bytes()).ok_or_else(|| format!("bad IPv4 address '{}'", text))?;
+        Ok(Cidr {
+            network: addr & Self::mask(prefix),
+            prefix,
+        })
+    }
+
+    fn mask(prefix: u8) -> u32 {
+        u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn contains(&self, addr: u32) -> bool {
+        addr & Self::mask(self.prefix) == self.network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ipv4_and_cidr() {
+        assert_eq!(parse_ipv4(b"10.1.2.3"), Some(0x0A01_0203));
+        assert_eq!(parse_ipv4(b"255.255.255.255"), Some(u32::MAX));
+        assert_eq!(parse_ipv4(b"0.0.0.0"), Some(0));
+        for bad in [
+            &b"256.1.1.1"[..],
+            b"1.2.3",
+            b"1.2.3.4.5",
+            b"1..2.3",
+            b"1.2.3.",
+            b".1.2.3",
+            b"1.2.3.4a",
+            b"1.2.3.1000",
+            b"0001.2.3.4",
+            b"123456789012345",
+            b"",
+        ] {
+            assert_eq!(parse_ipv4(bad), None, "{}", String::from_utf8_lossy(bad));
+        }
+
+        let private = Cidr::parse("10.0.0.0/8").unwrap();
+        assert!(private.contains(parse_ipv4(b"10.200.3.4").unwrap()));
+        assert!(!private.contains(parse_ipv4(b"11.0.0.1").unwrap()));
+        // Host bits in the network are ignored.
+        let lan = Cidr::parse("192.168.1.77/24").unwrap();
+        assert_eq!(lan.network, parse_ipv4(b"192.168.1.0").unwrap());
+        assert!(lan.contains(parse_ipv4(b"192.168.1.255").unwrap()));
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(12345));
+        let host = Cidr::parse("1.2.3.4").unwrap();
+        assert!(host.contains(0x0102_0304) && !host.contains(0x0102_0305));
+        assert!(Cidr::parse("1.2.3.4/33").is_err());
+        assert!(Cidr::parse("1.2.3/8").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::simd_scan::{self, Kernel};
 use crate::structured::{FieldRef, StructuredBatch, well_known};
 
 #[cfg(target_arch = "x86_64")]
@@ -87,6 +88,19 @@ fn resolve_escapes(quote_mask: u64, bs_mask: u64) -> u64 {
     result
 }
 
+/// `resolve_escapes` for one 64-byte block of a longer string. `escaped`
+/// says whether the previous block ended in an odd run of backslashes, so
+/// the first byte here is escaped, and is updated for the next block.
+#[inline(always)]
+fn resolve_block_escapes(mut quote_mask: u64, mut bs_mask: u64, escaped: &mut bool) -> u64 {
+    if *escaped {
+        quote_mask &= !1;
+        bs_mask &= !1;
+    }
+    *escaped = (!bs_mask).leading_zeros() & 1 == 1;
+    resolve_escapes(quote_mask, bs_mask)
+}
+
 #[inline]
 pub fn parse_json_line(line: &[u8], base_offset: u64, batch: &mut StructuredBatch) {
     let len = line.len();
@@ -107,9 +121,7 @@ pub fn parse_json_line(line: &[u8], base_offset: u64, batch: &mut StructuredBatc
     let record_field_base = batch.fields.len() as u32;
 
     loop {
-        while i < len && is_json_whitespace(line[i]) {
-            i += 1;
-        }
+        i = skip_whitespace(line, i);
 
         if i >= len || line[i] == b'}' {
             break;
@@ -121,34 +133,23 @@ pub fn parse_json_line(line: &[u8], base_offset: u64, batch: &mut StructuredBatc
         }
 
         if line[i] != b'"' {
-            while i < len && line[i] != b',' && line[i] != b'}' {
-                i += 1;
-            }
+            i = find_class(line, i, CLASS_SEPARATOR);
             continue;
         }
 
         let key_start = i + 1; // after opening quote
         i += 1;
-        while i < len && line[i] != b'"' {
-            if line[i] == b'\\' {
-                i += 1; // skip escaped char
-            }
-            i += 1;
-        }
+        skip_string_body(line, &mut i);
         let key_end = i;
         if i < len {
             i += 1; // skip closing quote
         }
 
-        while i < len && is_json_whitespace(line[i]) {
-            i += 1;
-        }
+        i = skip_whitespace(line, i);
         if i < len && line[i] == b':' {
             i += 1;
         }
-        while i < len && is_json_whitespace(line[i]) {
-            i += 1;
-        }
+        i = skip_whitespace(line, i);
 
         let (val_start, val_end) = parse_json_value(line, &mut i);
 
@@ -159,30 +160,17 @@ pub fn parse_json_line(line: &[u8], base_offset: u64, batch: &mut StructuredBatc
             key_len: (key_end - key_start) as u32,
             val_offset: base_offset + val_start as u64,
             val_len: (val_end - val_start) as u32,
+            key_id: 0,
         };
 
         batch.push_field(field);
 
-        let key_bytes = &line[key_start..key_end];
-        match well_known::classify_key(key_bytes) {
-            well_known::WellKnownKind::Timestamp => {
-                batch.set_well_known_timestamp(field_idx);
-            }
-            well_known::WellKnownKind::Level => {
-                batch.set_well_known_level(field_idx);
-            }
-            well_known::WellKnownKind::Message => {
-                batch.set_well_known_message(field_idx);
-            }
-            well_known::WellKnownKind::Component => {
-                batch.set_well_known_component(field_idx);
-            }
-            well_known::WellKnownKind::Other => {}
+        if !batch.firehose {
+            let key_bytes = &line[key_start..key_end];
+            batch.set_well_known(well_known::classify_key(key_bytes), field_idx);
         }
 
-        while i < len && is_json_whitespace(line[i]) {
-            i += 1;
-        }
+        i = skip_whitespace(line, i);
         if i < len && line[i] == b',' {
             i += 1;
         }
@@ -207,12 +195,7 @@ fn parse_json_value(line: &[u8], i: &mut usize) -> (usize, usize) {
         b'"' => {
             let val_start = *i + 1;
             *i += 1;
-            while *i < len && line[*i] != b'"' {
-                if line[*i] == b'\\' {
-                    *i += 1; // skip escaped char
-                }
-                *i += 1;
-            }
+            skip_string_body(line, i);
             let val_end = *i;
             if *i < len {
                 *i += 1; // skip closing quote
@@ -229,17 +212,13 @@ fn parse_json_value(line: &[u8], i: &mut usize) -> (usize, usize) {
                     b'}' => depth -= 1,
                     b'"' => {
                         *i += 1;
-                        while *i < len && line[*i] != b'"' {
-                            if line[*i] == b'\\' {
-                                *i += 1;
-                            }
-                            *i += 1;
-                        }
+                        skip_string_body(line, i);
                     }
                     _ => {}
                 }
                 *i += 1;
             }
+            *i = (*i).min(len);
             (val_start, *i)
         }
         b'[' => {
@@ -252,24 +231,18 @@ fn parse_json_value(line: &[u8], i: &mut usize) -> (usize, usize) {
                     b']' => depth -= 1,
                     b'"' => {
                         *i += 1;
-                        while *i < len && line[*i] != b'"' {
-                            if line[*i] == b'\\' {
-                                *i += 1;
-                            }
-                            *i += 1;
-                        }
+                        skip_string_body(line, i);
                     }
                     _ => {}
                 }
                 *i += 1;
             }
+            *i = (*i).min(len);
             (val_start, *i)
         }
         _ => {
             let val_start = *i;
-            while *i < len && !is_json_value_terminator(line[*i]) {
-                *i += 1;
-            }
+            *i = find_class(line, *i, CLASS_TERMINATOR);
             let mut val_end = *i;
             while val_end > val_start && is_json_whitespace(line[val_end - 1]) {
                 val_end -= 1;
@@ -279,29 +252,200 @@ fn parse_json_value(line: &[u8], i: &mut usize) -> (usize, usize) {
     }
 }
 
+/// Moves `i` from inside a string to its closing quote, or to the end of
+/// the line when the string is not closed.
+#[inline(always)]
+fn skip_string_body(line: &[u8], i: &mut usize) {
+    while *i < line.len() && line[*i] != b'"' {
+        if line[*i] == b'\\' {
+            *i += 1; // skip escaped char
+        }
+        *i += 1;
+    }
+    *i = (*i).min(line.len());
+}
+
 #[inline(always)]
 fn is_json_whitespace(b: u8) -> bool {
     b == b' ' || b == b'\t' || b == b'\r' || b == b'\n'
 }
 
+// Byte classes for the pshufb lookup: a byte is in a class when the bits
+// its low nibble selects from `CLASS_LO` and its high nibble from
+// `CLASS_HI` share one. Bytes 0x80 and up select nothing.
+const CLASS_CONTROL: u8 = 1; // '\t' '\n' '\r'
+const CLASS_SPACE: u8 = 2;
+const CLASS_COMMA: u8 = 4;
+const CLASS_BRACKET: u8 = 8; // ']'
+const CLASS_BRACE: u8 = 16; // '}'
+
+const CLASS_WHITESPACE: u8 = CLASS_CONTROL | CLASS_SPACE;
+const CLASS_SEPARATOR: u8 = CLASS_COMMA | CLASS_BRACE;
+const CLASS_TERMINATOR: u8 = CLASS_WHITESPACE | CLASS_COMMA | CLASS_BRACKET | CLASS_BRACE;
+
+const CLASS_LO: [u8; 16] = [
+    CLASS_SPACE,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    CLASS_CONTROL,
+    CLASS_CONTROL,
+    0,
+    CLASS_COMMA,
+    CLASS_CONTROL | CLASS_BRACKET | CLASS_BRACE,
+    0,
+    0,
+];
+
+const CLASS_HI: [u8; 16] = [
+    CLASS_CONTROL,
+    0,
+    CLASS_SPACE | CLASS_COMMA,
+    0,
+    0,
+    CLASS_BRACKET,
+    0,
+    CLASS_BRACE,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+];
+
 #[inline(always)]
-fn is_json_value_terminator(b: u8) -> bool {
-    b == b',' || b == b'}' || b == b']' || is_json_whitespace(b)
+fn byte_class(b: u8) -> u8 {
+    CLASS_LO[(b & 0x0f) as usize] & CLASS_HI[(b >> 4) as usize]
 }
 
-#[allow(dead_code)]
+/// Skips JSON whitespace from `i`. Compact lines have none, so only a run
+/// of padding pays for the vector probe.
+#[inline(always)]
+fn skip_whitespace(line: &[u8], i: usize) -> usize {
+    if i < line.len() && is_json_whitespace(line[i]) {
+        skip_class(line, i, CLASS_WHITESPACE)
+    } else {
+        i
+    }
+}
+
+/// First byte at or after `i` outside `class`, or the end of the line.
 #[inline]
-pub fn find_string_end_simd(data: &[u8], start: usize) -> usize {
+fn skip_class(line: &[u8], i: usize, class: u8) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    if line.len() - i >= 16
+        && simd_scan::active_kernel() != Kernel::Scalar
+        && is_x86_feature_detected!("avx2")
+    {
+        return unsafe { scan_class_avx2(line, i, class, false) };
+    }
+    scan_class_scalar(line, i, class, false)
+}
+
+/// First byte at or after `i` in `class`, or the end of the line.
+#[inline]
+fn find_class(line: &[u8], i: usize, class: u8) -> usize {
     #[cfg(target_arch = "x86_64")]
+    if line.len() - i >= 16
+        && simd_scan::active_kernel() != Kernel::Scalar
+        && is_x86_feature_detected!("avx2")
     {
-        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
-            return unsafe { find_string_end_avx512(data, start) };
+        return unsafe { scan_class_avx2(line, i, class, true) };
+    }
+    scan_class_scalar(line, i, class, true)
+}
+
+fn scan_class_scalar(line: &[u8], mut i: usize, class: u8, stop_in: bool) -> usize {
+    while i < line.len() && (byte_class(line[i]) & class != 0) != stop_in {
+        i += 1;
+    }
+    i
+}
+
+/// Classifies 32 bytes at a time, then 16, with two pshufb lookups each,
+/// and finishes the last few bytes in scalar.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn scan_class_avx2(line: &[u8], mut i: usize, class: u8, stop_in: bool) -> usize {
+    unsafe {
+        use std::arch::x86_64::*;
+
+        let lo_table = _mm_loadu_si128(CLASS_LO.as_ptr() as *const __m128i);
+        let hi_table = _mm_loadu_si128(CLASS_HI.as_ptr() as *const __m128i);
+        let nibble = _mm_set1_epi8(0x0f);
+        let wanted = _mm_set1_epi8(class as i8);
+        let classify = |v: __m128i| {
+            let lo = _mm_shuffle_epi8(lo_table, _mm_and_si128(v, nibble));
+            let hi = _mm_shuffle_epi8(hi_table, _mm_and_si128(_mm_srli_epi16(v, 4), nibble));
+            let hit = _mm_and_si128(_mm_and_si128(lo, hi), wanted);
+            let outside = _mm_movemask_epi8(_mm_cmpeq_epi8(hit, _mm_setzero_si128())) as u32;
+            if stop_in { !outside & 0xffff } else { outside }
+        };
+
+        let lo_table32 = _mm256_broadcastsi128_si256(lo_table);
+        let hi_table32 = _mm256_broadcastsi128_si256(hi_table);
+        let nibble32 = _mm256_set1_epi8(0x0f);
+        let wanted32 = _mm256_set1_epi8(class as i8);
+        while i + 32 <= line.len() {
+            let v = _mm256_loadu_si256(line.as_ptr().add(i) as *const __m256i);
+            let lo = _mm256_shuffle_epi8(lo_table32, _mm256_and_si256(v, nibble32));
+            let hi = _mm256_shuffle_epi8(
+                hi_table32,
+                _mm256_and_si256(_mm256_srli_epi16(v, 4), nibble32),
+            );
+            let hit = _mm256_and_si256(_mm256_and_si256(lo, hi), wanted32);
+            let outside =
+                _mm256_movemask_epi8(_mm256_cmpeq_epi8(hit, _mm256_setzero_si256())) as u32;
+            let stops = if stop_in { !outside } else { outside };
+            if stops != 0 {
+                return i + stops.trailing_zeros() as usize;
+            }
+            i += 32;
         }
-        if is_x86_feature_detected!("avx2") {
-            return unsafe { find_string_end_avx2(data, start) };
+        if i + 16 <= line.len() {
+            let stops = classify(_mm_loadu_si128(line.as_ptr().add(i) as *const __m128i));
+            if stops != 0 {
+                return i + stops.trailing_zeros() as usize;
+            }
+            i += 16;
         }
     }
-    find_string_end_scalar(data, start)
+    scan_class_scalar(line, i, class, stop_in)
+}
+
+/// `skip_whitespace` with a given kernel; `None` when the CPU lacks it or
+/// the kernel has no such variant. Padding runs are rarely longer than a
+/// 32-byte vector, so there is no AVX-512 one.
+pub fn skip_whitespace_with(kernel: Kernel, line: &[u8], i: usize) -> Option<usize> {
+    if !kernel.supported() {
+        return None;
+    }
+    match kernel {
+        Kernel::Scalar => Some(scan_class_scalar(line, i, CLASS_WHITESPACE, false)),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => Some(unsafe { scan_class_avx2(line, i, CLASS_WHITESPACE, false) }),
+        _ => None,
+    }
+}
+
+#[allow(dead_code)]
+#[inline]
+pub fn find_string_end_simd(data: &[u8], start: usize) -> usize {
+    match simd_scan::active_kernel() {
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx512 => unsafe { find_string_end_avx512(data, start) },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { find_string_end_avx2(data, start) },
+        _ => find_string_end_scalar(data, start),
+    }
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -310,16 +454,17 @@ unsafe fn find_string_end_avx2(data: &[u8], start: usize) -> usize {
     let mut pos = start;
     let len = data.len();
 
+    let mut escaped = false;
     while pos + 64 <= len {
         let (q_mask, bs_mask) = unsafe { find_quote_mask_avx2(data.as_ptr().add(pos), 64) };
-        let real_quotes = resolve_escapes(q_mask, bs_mask);
+        let real_quotes = resolve_block_escapes(q_mask, bs_mask, &mut escaped);
         if real_quotes != 0 {
             return pos + real_quotes.trailing_zeros() as usize;
         }
         pos += 64;
     }
 
-    find_string_end_scalar(data, pos)
+    find_string_end_scalar(data, pos + escaped as usize)
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -328,16 +473,34 @@ unsafe fn find_string_end_avx512(data: &[u8], start: usize) -> usize {
     let mut pos = start;
     let len = data.len();
 
+    let mut escaped = false;
     while pos + 64 <= len {
         let (q_mask, bs_mask) = unsafe { find_quote_mask_avx512(data.as_ptr().add(pos), 64) };
-        let real_quotes = resolve_escapes(q_mask, bs_mask);
+        let real_quotes = resolve_block_escapes(q_mask, bs_mask, &mut escaped);
         if real_quotes != 0 {
             return pos + real_quotes.trailing_zeros() as usize;
         }
         pos += 64;
     }
 
-    find_string_end_scalar(data, pos)
+    find_string_end_scalar(data, pos + escaped as usize)
+}
+
+/// `find_string_end_simd` with a given kernel; `None` when the CPU lacks
+/// it.
+pub fn find_string_end_with(kernel: Kernel, data: &[u8], start: usize) -> Option<usize> {
+    if !kernel.supported() {
+        return None;
+    }
+    match kernel {
+        Kernel::Scalar => Some(find_string_end_scalar(data, start)),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => Some(unsafe { find_string_end_avx2(data, start) }),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx512 => Some(unsafe { find_string_end_avx512(data, start) }),
+        #[cfg(not(target_arch = "x86_64"))]
+        _ => None,
+    }
 }
 
 fn find_string_end_scalar(data: &[u8], start: usize) -> usize {
@@ -360,13 +523,14 @@ pub fn parse_json_lines_range(
     batch: &mut StructuredBatch,
 ) {
     let num_lines = line_starts.len();
+    let sep = crate::simd_scan::record_sep();
 
     for i in start_idx..end_idx {
         let line_start = line_starts[i] as usize;
         let line_end = if i + 1 < num_lines {
             let next = line_starts[i + 1] as usize;
-            if next > 0 && next <= data.len() && data[next - 1] == b'\n' {
-                if next > 1 && data[next - 2] == b'\r' {
+            if next > 0 && next <= data.len() && data[next - 1] == sep {
+                if !batch.firehose && next > 1 && data[next - 2] == b'\r' {
                     next - 2
                 } else {
                     next - 1
@@ -388,7 +552,12 @@ pub fn parse_json_lines_range(
             continue;
         }
 
+        let records_before = batch.len;
         parse_json_line(line, line_start as u64, batch);
+        let closed = line.iter().rev().find(|&&b| !is_json_whitespace(b)) == Some(&b'}');
+        if !closed || batch.len == records_before {
+            batch.mark_malformed(line_start as u64, line.len() as u32);
+        }
     }
 }
 
@@ -468,6 +637,7 @@ fn extract_mask_positions_raw(mask: u64, base: u64, output: &mut Vec<u64>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::structured::EmptyPolicy;
 
     fn make_batch(data: &[u8]) -> StructuredBatch {
         StructuredBatch::with_capacity(16, 64, data.as_ptr())
@@ -510,6 +680,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_correlation_slots() {
+        let line = br#"{"msg":"ok","hostname":"web-1","pid":4242,"trace_id":"4bf92f3577b34da6","span_id":"00f067aa0ba902b7"}"#;
+        let mut batch = make_batch(line);
+
+        parse_json_line(line, 0, &mut batch);
+
+        unsafe {
+            assert_eq!(batch.host_value(0), Some("web-1"));
+            assert_eq!(batch.pid_value(0), Some("4242"));
+            assert_eq!(batch.trace_id_value(0), Some("4bf92f3577b34da6"));
+            assert_eq!(batch.span_id_value(0), Some("00f067aa0ba902b7"));
+        }
+    }
+
+    #[test]
+    fn test_numeric_epoch_timestamp() {
+        use crate::data::BatchRecords;
+
+        let line = br#"{"ts":1739356305123,"level":"info"}"#;
+        let mut batch = make_batch(line);
+
+        parse_json_line(line, 0, &mut batch);
+
+        assert_eq!(batch.len, 1);
+        assert_eq!(batch.record_timestamp(0), Some(1739356305));
+        assert!(!batch.time_range.is_empty());
+    }
+
     #[test]
     fn test_parse_json_with_numbers() {
         let line = br#"{"latency_ms":42,"status":200,"success":true}"#;
@@ -634,11 +833,18 @@ mod tests {
     fn test_parse_json_empty_object() {
         let line = b"{}";
         let mut batch = make_batch(line);
+        batch.limits.empty = EmptyPolicy::Keep;
 
         parse_json_line(line, 0, &mut batch);
 
         assert_eq!(batch.len, 1);
         assert_eq!(batch.field_count(0), 0);
+
+        // By default a record without fields is a malformed line.
+        let mut batch = make_batch(line);
+        parse_json_line(line, 0, &mut batch);
+        assert_eq!(batch.len, 0);
+        assert_eq!(batch.malformed.len(), 1);
     }
 
     #[test]
@@ -658,6 +864,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_padded_json_matches_compact() {
+        let compact = br#"{"level":"info","n":42,"ok":true,"tags":["a","b"],"msg":"hi"}"#;
+        let padded = b"{\n    \"level\" :\t\"info\" ,\r\n    \"n\":                    42                                  ,\n    \"ok\":true  ,  \"tags\" : [\"a\",\"b\"],\n\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\"msg\"    :    \"hi\"                                   }";
+        let fields = |line: &[u8]| {
+            let mut batch = make_batch(line);
+            parse_json_line(line, 0, &mut batch);
+            batch
+                .record_fields(0)
+                .iter()
+                .map(|f| unsafe {
+                    (
+                        batch.field_key(f).to_string(),
+                        batch.field_value(f).to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fields(padded), fields(compact));
+
+        // Every class and kernel agrees with a byte-at-a-time scan.
+        for class in [CLASS_WHITESPACE, CLASS_SEPARATOR, CLASS_TERMINATOR] {
+            for stop_in in [false, true] {
+                for start in 0..padded.len() {
+                    let expected = (start..padded.len())
+                        .find(|&i| {
+                            let b = padded[i];
+                            let is_in = match class {
+                                CLASS_WHITESPACE => is_json_whitespace(b),
+                                CLASS_SEPARATOR => b == b',' || b == b'}',
+                                _ => is_json_whitespace(b) || b",}]".contains(&b),
+                            };
+                            is_in == stop_in
+                        })
+                        .unwrap_or(padded.len());
+                    assert_eq!(scan_class_scalar(padded, start, class, stop_in), expected);
+                    #[cfg(target_arch = "x86_64")]
+                    if Kernel::Avx2.supported() {
+                        let got = unsafe { scan_class_avx2(padded, start, class, stop_in) };
+                        assert_eq!(got, expected, "class {} from {}", class, start);
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_find_string_end_scalar() {
         let data = br#"hello world" rest"#;
@@ -684,6 +936,17 @@ mod tests {
         assert_eq!(result, 0); // quote at position 1 is escaped by backslash at position 0
     }
 
+    #[test]
+    fn test_resolve_block_escapes_carries_backslash() {
+        let mut escaped = false;
+        assert_eq!(resolve_block_escapes(0, 1 << 63, &mut escaped), 0);
+        assert!(escaped);
+        // The quote opening the next block is escaped; the one after is not.
+        assert_eq!(resolve_block_escapes(0b101, 0, &mut escaped), 0b100);
+        assert!(!escaped);
+        assert_eq!(resolve_block_escapes(0b1, 0, &mut escaped), 0b1);
+    }
+
     #[test]
     fn test_base_offset_propagation() {
         let line = br#"{"key":"value"}"#;
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+/// Pod identity encoded in a kubelet container log name:
+/// `/var/log/containers/<pod>_<namespace>_<container>-<id>.log`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PodMetadata {
+    pub pod: String,
+    pub namespace: String,
+    pub container: String,
+    pub container_id: String,
+}
+
+impl PodMetadata {
+    /// Parses the file name of `path`; `None` unless it follows the kubelet
+    /// convention, including the 64-hex-digit container id.
+    pub fn from_path(path: &str) -> Option<PodMetadata> {
+        let name = Path::new(path).file_name()?.to_str()?;
+        let stem = name.strip_suffix(".log")?;
+        let mut parts = stem.split('_');
+        let (pod, namespace, rest) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() {
+            return None;
+        }
+        let (container, id) = rest.rsplit_once('-')?;
+        let valid = |s: &str| {
+            !s.is_empty()
+                && s.bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'.')
+        };
+        if !valid(pod)
+            || !valid(namespace)
+            || !valid(container)
+            || id.len() != 64
+            || !id.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return None;
+        }
+        Some(PodMetadata {
+            pod: pod.to_string(),
+            namespace: namespace.to_string(),
+            container: container.to_string(),
+            container_id: id.to_string(),
+        })
+    }
+
+    /// Record fields to attach, in output order.
+    pub fn fields(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        [
+            ("k8s.pod", &self.pod),
+            ("k8s.namespace", &self.namespace),
+            ("k8s.container", &self.container),
+            ("k8s.container_id", &self.container_id),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "3f9a7c1e5b2d4a6f8e0c9b7a5d3f1e2c4b6a8d0e9f7c5a3b1d2e4f6a8c0b9d7e";
+
+    #[test]
+    fn test_pod_metadata_from_container_log_name() {
+        let path = format!("/var/log/containers/api-7d9f8b-x2k4q_payments_istio-proxy-{ID}.log");
+        let meta = PodMetadata::from_path(&path).unwrap();
+        assert_eq!(meta.pod, "api-7d9f8b-x2k4q");
+        assert_eq!(meta.namespace, "payments");
+        assert_eq!(meta.container, "istio-proxy");
+        assert_eq!(meta.container_id, ID);
+        assert_eq!(
+            meta.fields()[1],
+            (b"k8s.namespace".to_vec(), b"payments".to_vec())
+        );
+
+        assert_eq!(PodMetadata::from_path("/var/log/app.log"), None);
+        assert_eq!(PodMetadata::from_path(&format!("a_b_c-{ID}.txt")), None);
+        assert_eq!(PodMetadata::from_path(&format!("a_b_c_d-{ID}.log")), None);
+        assert_eq!(PodMetadata::from_path("a_b_c-deadbeef.log"), None);
+        assert_eq!(PodMetadata::from_path(&format!("A_b_c-{ID}.log")), None);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+/// Field names stored once each and numbered densely, so records carry a
+/// `u32` id per field instead of re-reading and re-hashing the key bytes.
+#[derive(Debug, Clone, Default)]
+pub struct KeyTable {
+    names: Vec<Box<[u8]>>,
+    ids: HashMap<Box<[u8]>, u32>,
+}
+
+impl KeyTable {
+    pub fn intern(&mut self, name: &[u8]) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.into());
+        self.ids.insert(name.into(), id);
+        id
+    }
+
+    /// Like [`KeyTable::intern`], but tries `hint` first: consecutive records
+    /// usually share a layout, so the field at the same position of the
+    /// previous record mostly has the same key and no hashing is needed.
+    #[inline]
+    pub fn intern_hinted(&mut self, name: &[u8], hint: Option<u32>) -> u32 {
+        match hint {
+            Some(id) if *self.names[id as usize] == *name => id,
+            _ => self.intern(name),
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, name: &[u8]) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    #[inline]
+    pub fn name(&self, id: u32) -> &[u8] {
+        &self.names[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Names in id order.
+    pub fn names(&self) -> impl Iterator<Item = &[u8]> {
+        self.names.iter().map(|n| &**n)
+    }
+
+    /// Interns all of `other`'s names, returning the id here of each of
+    /// `other`'s ids. Folding every batch's table into one gives a global
+    /// table for the whole result.
+    pub fn merge(&mut self, other: &KeyTable) -> Vec<u32> {
+        other.names().map(|name| self.intern(name)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_table_interning() {
+        let mut table = KeyTable::default();
+        let level = table.intern(b"level");
+        let msg = table.intern(b"msg");
+        assert_eq!((level, msg), (0, 1));
+        assert_eq!(table.intern(b"level"), level);
+        assert_eq!(table.intern_hinted(b"msg", Some(msg)), msg);
+        assert_eq!(table.intern_hinted(b"ts", Some(msg)), 2);
+        assert_eq!(table.get(b"ts"), Some(2));
+        assert_eq!(table.get(b"nope"), None);
+        assert_eq!(table.name(msg), b"msg");
+
+        let mut other = KeyTable::default();
+        other.intern(b"ts");
+        other.intern(b"request_id");
+        let remap = table.merge(&other);
+        assert_eq!(remap, [2, 3]);
+        assert_eq!(
+            table.names().collect::<Vec<_>>(),
+            [&b"level"[..], b"msg", b"ts", b"request_id"]
+        );
+    }
+}
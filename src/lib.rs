@@ -1,10 +1,67 @@
+pub mod ansi;
+pub mod bench;
+pub mod cgroup;
+pub mod component;
 pub mod csv_parser;
 pub mod data;
+pub mod decompress;
+pub mod dedup;
+pub mod diag;
+pub mod distributed;
+pub mod emit;
+pub mod encoding;
+pub mod explain;
+pub mod expr;
+pub mod extract;
+pub mod extsort;
+pub mod fds;
+pub mod filewatch;
+pub mod filter;
+pub mod fixed_parser;
+pub mod follow;
 pub mod format;
+pub mod fuzz;
+pub mod gaps;
+pub mod group;
+pub mod handle;
+pub mod holes;
+pub mod http_summary;
+pub mod index;
+pub mod ip;
 pub mod json_parser;
+pub mod k8s;
+pub mod keys;
 pub mod logfmt_parser;
+pub mod manifest;
+pub mod metric_rules;
+pub mod numbers;
 pub mod orchestrator;
+pub mod ordering;
 pub mod parser;
+pub mod pinning;
+pub mod pipeline;
+pub mod pointer;
+pub mod readahead;
+pub mod rejects;
+pub mod remote_write;
+pub mod reorder;
+pub mod replay;
+pub mod rolling;
+pub mod schemas;
+pub mod secrets;
+pub mod seek;
+pub mod selftest;
+pub mod shutdown;
+pub mod sigbus;
 pub mod simd_scan;
+pub mod sink;
+pub mod sizes;
+pub mod sketch;
+pub mod split;
+pub mod store;
 pub mod structured;
 pub mod structured_orchestrator;
+pub mod syslog;
+pub mod triage;
+pub mod tz;
+pub mod values;
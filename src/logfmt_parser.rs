@@ -33,6 +33,7 @@ pub fn parse_logfmt_line(line: &[u8], base_offset: u64, batch: &mut StructuredBa
                     key_len: (key_end - key_start) as u32,
                     val_offset: base_offset + key_end as u64,
                     val_len: 0,
+                    key_id: 0,
                 });
 
                 let key_bytes = &line[key_start..key_end];
@@ -52,6 +53,8 @@ pub fn parse_logfmt_line(line: &[u8], base_offset: u64, batch: &mut StructuredBa
                 }
                 i += 1;
             }
+            // A trailing backslash escapes past the end of the line.
+            i = i.min(len);
             let ve = i;
             if i < len {
                 i += 1; // skip closing quote
@@ -71,6 +74,7 @@ pub fn parse_logfmt_line(line: &[u8], base_offset: u64, batch: &mut StructuredBa
             key_len: (key_end - key_start) as u32,
             val_offset: base_offset + val_start as u64,
             val_len: (val_end - val_start) as u32,
+            key_id: 0,
         });
 
         let key_bytes = &line[key_start..key_end];
@@ -82,13 +86,30 @@ pub fn parse_logfmt_line(line: &[u8], base_offset: u64, batch: &mut StructuredBa
 
 #[inline]
 fn classify_and_set(key_bytes: &[u8], field_idx: u32, batch: &mut StructuredBatch) {
-    match well_known::classify_key(key_bytes) {
-        well_known::WellKnownKind::Timestamp => batch.set_well_known_timestamp(field_idx),
-        well_known::WellKnownKind::Level => batch.set_well_known_level(field_idx),
-        well_known::WellKnownKind::Message => batch.set_well_known_message(field_idx),
-        well_known::WellKnownKind::Component => batch.set_well_known_component(field_idx),
-        well_known::WellKnownKind::Other => {}
+    if batch.firehose {
+        return;
     }
+    batch.set_well_known(well_known::classify_key(key_bytes), field_idx);
+}
+
+/// Appends the content of a quoted value to `out` with `\"`, `\\`, `\n`,
+/// `\r` and `\t` resolved; any other escape keeps its backslash.
+pub fn unquote(raw: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while let Some(at) = memchr::memchr(b'\\', &raw[i..]) {
+        out.extend_from_slice(&raw[i..i + at]);
+        i += at + 1;
+        match raw.get(i) {
+            Some(b'n') => out.push(b'\n'),
+            Some(b'r') => out.push(b'\r'),
+            Some(b't') => out.push(b'\t'),
+            Some(&b @ (b'"' | b'\\')) => out.push(b),
+            Some(&b) => out.extend_from_slice(&[b'\\', b]),
+            None => out.push(b'\\'),
+        }
+        i += 1;
+    }
+    out.extend_from_slice(&raw[i.min(raw.len())..]);
 }
 
 pub fn parse_logfmt_lines_range(
@@ -99,13 +120,14 @@ pub fn parse_logfmt_lines_range(
     batch: &mut StructuredBatch,
 ) {
     let num_lines = line_starts.len();
+    let sep = crate::simd_scan::record_sep();
 
     for i in start_idx..end_idx {
         let line_start = line_starts[i] as usize;
         let line_end = if i + 1 < num_lines {
             let next = line_starts[i + 1] as usize;
-            if next > 0 && next <= data.len() && data[next - 1] == b'\n' {
-                if next > 1 && data[next - 2] == b'\r' {
+            if next > 0 && next <= data.len() && data[next - 1] == sep {
+                if !batch.firehose && next > 1 && data[next - 2] == b'\r' {
                     next - 2
                 } else {
                     next - 1
@@ -127,7 +149,14 @@ pub fn parse_logfmt_lines_range(
             continue;
         }
 
-        parse_logfmt_line(line, line_start as u64, batch);
+        if memchr::memchr(b'=', line).is_some() {
+            parse_logfmt_line(line, line_start as u64, batch);
+        } else {
+            // Bare words pair no key with a value: an empty record, left to
+            // the `EmptyPolicy`.
+            batch.begin_record(line_start as u64, line.len() as u32);
+            batch.end_record();
+        }
     }
 }
 
@@ -231,6 +260,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_logfmt_correlation_slots() {
+        let line = b"msg=ok host=web-1 pid=4242 traceId=4bf92f35 span.id=00f067aa";
+        let mut batch = make_batch(line);
+
+        parse_logfmt_line(line, 0, &mut batch);
+
+        unsafe {
+            assert_eq!(batch.host_value(0), Some("web-1"));
+            assert_eq!(batch.pid_value(0), Some("4242"));
+            assert_eq!(batch.trace_id_value(0), Some("4bf92f35"));
+            assert_eq!(batch.span_id_value(0), Some("00f067aa"));
+        }
+    }
+
     #[test]
     fn test_parse_logfmt_escaped_quote() {
         let line = br#"msg="said \"hello\"" level=info"#;
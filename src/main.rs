@@ -1,319 +1,3851 @@
+mod ansi;
+mod bench;
+mod cache;
+mod cgroup;
+mod component;
 mod csv_parser;
 mod data;
+mod decompress;
+mod dedup;
+mod diag;
+mod distributed;
+mod emit;
+mod encoding;
+mod explain;
+mod expr;
+mod extract;
+mod extsort;
+mod fds;
+mod filewatch;
+mod filter;
+mod fixed_parser;
+mod follow;
 mod format;
+mod gaps;
+mod group;
+mod holes;
+mod http_summary;
+mod index;
+mod ip;
 mod json_parser;
+mod k8s;
+mod keys;
 mod logfmt_parser;
+mod manifest;
+mod metric_rules;
+mod numbers;
 mod orchestrator;
+mod ordering;
 mod parser;
+mod pinning;
+mod pipeline;
+mod pointer;
+mod readahead;
+mod rejects;
+mod remote_write;
+mod reorder;
+mod replay;
+mod rolling;
+mod schemas;
+mod secrets;
+mod seek;
+mod selftest;
+mod shutdown;
+mod sigbus;
 mod simd_scan;
+mod sink;
+mod sizes;
+mod sketch;
+mod split;
+mod store;
 mod structured;
 mod structured_orchestrator;
+mod syslog;
+mod triage;
+mod tz;
 
-use data::ParseStats;
+use cache::{CachedReport, FileSignature, QueryCache};
+use component::ComponentRule;
+use data::{BatchRecords, FailedChunk, FormatBreakdown, LogBatch, PageFaults, ParseStats};
+use decompress::{Compression, Decompressor};
+use dedup::DuplicateFinder;
+use diag::Severity;
+use emit::{EmitChunk, EmitFormat, EmitRecord, EmitRules, RecordDigest, emit_chunk};
+use encoding::{Encoding, Transcoder};
+use expr::Expr;
+use extract::ByteExtract;
+use extsort::ExternalSort;
+use fds::FdBudget;
+use filewatch::{FileChange, FileIdentity};
+use filter::{
+    BatchCallback, LevelFilter, LevelSampler, MatchControl, MatchLimit, RecordPredicate,
+    RejectCallback,
+};
+use fixed_parser::FixedLayout;
+use follow::{FollowEvent, Follower};
 use format::LogFormat;
-use memmap2::Mmap;
+use gaps::GapReport;
+use group::{Agg, GroupBy};
+use http_summary::HttpSummary;
+use index::{IndexUpdate, SparseIndex};
+use k8s::PodMetadata;
+use manifest::{DatasetSink, ManifestSink, ParseTotals, Xxh64};
+use memmap2::{Mmap, MmapOptions};
+use metric_rules::MetricRules;
+use ordering::OrderingReport;
+use pinning::{CpuTopo, PinSpec};
+use readahead::Readahead;
+use rejects::RejectWriter;
+use remote_write::RemoteWrite;
+use reorder::Reorder;
+use replay::Pacer;
+use rolling::{RollingWindows, Timeline};
+use schemas::SchemaCounts;
+use secrets::SecretScanner;
+use simd_scan::{Kernel, KernelPick};
+use sink::{Overflow, SinkSpec, Tee};
+use sizes::ValueSizes;
+use split::{PartitionLayout, SplitKey, SplitSink, TimeBucket, split_chunks};
 use std::borrow::Cow;
 use std::fs::File;
-use std::time::Instant;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use store::FileLock;
+use structured::{EmptyPolicy, GuardPolicy, RecordLimits, StructuredBatch, well_known};
+use triage::Triage;
+use tz::TimeZone;
+
+// Human-readable output moves to stderr when stdout carries NDJSON.
+static REPORT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+// Per-file report text collected for --cache while a file is processed.
+static REPORT_CAPTURE: Mutex<Option<String>> = Mutex::new(None);
+
+macro_rules! report {
+    ($($arg:tt)*) => {
+        report_fmt(format_args!($($arg)*))
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        diag::log(diag::Severity::Error, format_args!($($arg)*))
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        diag::log(diag::Severity::Warn, format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        diag::log(diag::Severity::Info, format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        diag::log(diag::Severity::Debug, format_args!($($arg)*))
+    };
+}
+
+macro_rules! reportln {
+    () => {
+        report!("\n")
+    };
+    ($($arg:tt)*) => {
+        report_fmt(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+fn report_fmt(args: std::fmt::Arguments<'_>) {
+    if let Some(text) = REPORT_CAPTURE.lock().unwrap().as_mut() {
+        let _ = std::fmt::Write::write_fmt(text, args);
+    }
+    if REPORT_TO_STDERR.load(Ordering::Relaxed) {
+        eprint!("{}", args)
+    } else {
+        print!("{}", args)
+    }
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args = expand_pipeline(std::env::args().collect());
+
+    if args.get(1).is_some_and(|a| a == "bench") {
+        std::process::exit(run_bench(&args[2..]));
+    }
+    if args.get(1).is_some_and(|a| a == "worker") {
+        std::process::exit(run_worker(&args[2..]));
+    }
+    if args.get(1).is_some_and(|a| a == "replay") {
+        std::process::exit(run_replay(&args[2..]));
+    }
+    if args.get(1).is_some_and(|a| a == "selftest") {
+        std::process::exit(run_selftest(&args[2..]));
+    }
 
     if args.len() < 2 {
         eprintln!("╔══════════════════════════════════════════════╗");
         eprintln!("         PANDORA'S LOGS — SIMD Parser          ");
         eprintln!("╠══════════════════════════════════════════════╣");
-        eprintln!("  Usage: pandoras-logs <file> [threads]        ");
+        eprintln!("  Usage: pandoras-logs <file>... [threads]     ");
+        eprintln!("         [--pipeline <file.toml>]              ");
+        eprintln!("         [--threads <n>|auto]                  ");
+        eprintln!("         [--pin <cpus>|all] [--pin-no-smt]     ");
+        eprintln!("         [--pin-socket <n>]                    ");
         eprintln!("         [--mmap] [--format <fmt>]             ");
+        eprintln!("         [--fixed-columns <name:start-end,...>]");
+        eprintln!("         [--fixed-record-len <bytes>]          ");
+        eprintln!("         [--mmap-populate] [--hugepages]       ");
+        eprintln!("         [--readahead <MB>] [--strip-ansi]     ");
+        eprintln!("         [--record-sep newline|nul]            ");
+        eprintln!("         [--firehose] [--verify-scan]          ");
+        eprintln!("         [--simd auto|avx512|avx2|scalar]      ");
+        eprintln!("         [--level <lvl>[+]] [--limit <n>]      ");
+        eprintln!("         [--reverse] [--since <time>]          ");
+        eprintln!("         [--where <expr>] [--derive k=expr]... ");
+        eprintln!("         [--sample-by-level lvl=rate,...]      ");
+        eprintln!("         [--max-record-bytes <n>]              ");
+        eprintln!("         [--max-fields <n>] [--columns <k>]    ");
+        eprintln!("         [--well-known <slot>=<key>]...        ");
+        eprintln!("         [--component-rule prefix|key=<k>]...  ");
+        eprintln!("         [--max-value-len <n>]                 ");
+        eprintln!("         [--guard-policy truncate|drop|error]  ");
+        eprintln!("         [--empty-records keep|drop|malformed] ");
+        eprintln!("         [-q] [-v|-vv] [--log-format json]     ");
+        eprintln!("         [--check-ordering] [--rejects <path>] ");
+        eprintln!("         [--extract-bytes <path>]              ");
+        eprintln!("         [--gap-analysis] [--gap-threshold <s>]");
+        eprintln!("         [--sink <spec>]... [--sink-queue <n>] ");
+        eprintln!("         [--serialize-threads <n>]             ");
+        eprintln!("         [--rename a=b]... [--set k=v]...      ");
+        eprintln!("         [--types f:kind,...]                  ");
+        eprintln!("         [--numbers f:plain|grouped|eu,...]    ");
+        eprintln!("         [--record-id <field>[:offset|:hash]]  ");
+        eprintln!("         [--provenance <field>]                ");
+        eprintln!("         [--schema-id <field>]                 ");
+        eprintln!("         [--schema ecs|otel]                   ");
+        eprintln!("         [--emit-rules <path>]                 ");
+        eprintln!("         [--emit ndjson|raw-filtered|offsets|  ");
+        eprintln!("                 hash]                         ");
+        eprintln!("         [--find-duplicates] [--manifest]      ");
+        eprintln!("         [--dataset-manifest]                  ");
+        eprintln!("         [--value-sizes] [--scan-secrets]      ");
+        eprintln!("         [--schema-report]                     ");
+        eprintln!("         [--http-summary]                      ");
+        eprintln!("         [--triage] [--triage-top <n>]         ");
+        eprintln!("         [--group-by <field>,...]              ");
+        eprintln!("         [--agg <field>:<fn>,...]              ");
+        eprintln!("         [--having <expr>]                     ");
+        eprintln!("         [--split-by <field> --output-dir <d>] ");
+        eprintln!("         [--partition-by hour|day]             ");
+        eprintln!("         [--partition-layout flat|hive]        ");
+        eprintln!("         [--tz <zone>]                         ");
+        eprintln!("         [--max-open-files <n>] [--max-fds <n>]");
+        eprintln!("         [--sort-time] [--sort-mem <MB>]       ");
+        eprintln!("         [--ordered]                           ");
+        eprintln!("         [--sort-dir <dir>]                    ");
+        eprintln!("         [--cache] [--index]                   ");
+        eprintln!("         [--follow] [--follow-interval <s>]    ");
+        eprintln!("         [--remote-write <url>]                ");
+        eprintln!("         [--remote-write-step <s>]             ");
+        eprintln!("         [--metric-rules <file>] [--metric-out <path>]");
+        eprintln!("         [--workers <host:port>,...]           ");
+        eprintln!("         [--explain] [--detect]                ");
         eprintln!("                                               ");
         eprintln!("  Arguments:                                   ");
-        eprintln!("    <file>     Path to log file                ");
+        eprintln!("    <file>     Path to log file; several files ");
+        eprintln!("               get a per-format breakdown      ");
+        eprintln!("               (kubelet <pod>_<ns>_<ctr>-<id>.log");
+        eprintln!("               names add k8s.* fields on export)");
+        eprintln!("               - reads stdin; gzip and zstd    ");
+        eprintln!("               input, concatenated members too,");
+        eprintln!("               is decompressed                 ");
         eprintln!("    [threads]  Number of parse threads         ");
-        eprintln!("               (default: all CPU cores)        ");
+        eprintln!("               (default: all CPU cores, capped ");
+        eprintln!("               by any cgroup CPU quota)        ");
+        eprintln!("    --threads  <n> or auto: try a few counts on");
+        eprintln!("               the first chunks, keep fastest  ");
+        eprintln!("    --pin      Pin workers to CPUs, e.g. 0-15, ");
+        eprintln!("               0,2,4 or all; --pin-no-smt keeps");
+        eprintln!("               one thread per core and         ");
+        eprintln!("               --pin-socket one socket         ");
         eprintln!("    --mmap     Use memory-map instead of       ");
         eprintln!("               streaming I/O (higher RSS)      ");
+        eprintln!("    --mmap-populate  Pre-fault the mapping    ");
+        eprintln!("    --hugepages  Ask for transparent hugepages");
+        eprintln!("    --readahead  Prefetch this many MB ahead  ");
+        eprintln!("               of each worker's chunk          ");
+        eprintln!("               (all three imply --mmap)        ");
+        eprintln!("    --strip-ansi  Drop terminal color and     ");
+        eprintln!("               cursor escapes before parsing   ");
+        eprintln!("               (not with --emit offsets or     ");
+        eprintln!("               raw-filtered)                   ");
         eprintln!("    --format   Force log format:               ");
-        eprintln!("               auto, plain, json, logfmt, csv  ");
-        eprintln!("               (default: auto-detect)          ");
+        eprintln!("               auto, plain, json, logfmt, csv, ");
+        eprintln!("               fixed-width (default: auto)     ");
+        eprintln!("    --fixed-columns  Fixed-width byte ranges,  ");
+        eprintln!("               0-based and end-exclusive; the  ");
+        eprintln!("               last may be open ('msg:27-').   ");
+        eprintln!("               Implies --format fixed-width    ");
+        eprintln!("    --fixed-record-len  Records are exactly    ");
+        eprintln!("               <bytes> long, terminator        ");
+        eprintln!("               included; framed without a scan ");
+        eprintln!("    --record-sep  Byte ending each record:     ");
+        eprintln!("               newline (default) or nul, for   ");
+        eprintln!("               NUL-framed multi-line records   ");
+        eprintln!("    --firehose  Field extents only: no levels, ");
+        eprintln!("               timestamps, well-known keys or  ");
+        eprintln!("               CR trimming (structured input)  ");
+        eprintln!("    --verify-scan  Check each chunk's scanned  ");
+        eprintln!("               line starts against a count of  ");
+        eprintln!("               record separators; exit 1 if    ");
+        eprintln!("               they disagree                   ");
+        eprintln!("    --simd     Scan kernel (default: widest);  ");
+        eprintln!("               auto times AVX-512 against AVX2 ");
+        eprintln!("               on the first file and keeps the ");
+        eprintln!("               faster, for CPUs that downclock ");
+        eprintln!("    --level    Only report records at a level; ");
+        eprintln!("               'error+' includes more severe   ");
+        eprintln!("    --limit    Stop after <n> matching records ");
+        eprintln!("               (or --head), parsing chunks in  ");
+        eprintln!("               file order until enough match   ");
+        eprintln!("    --reverse  Newest records first, reading  ");
+        eprintln!("               from the end (implies --mmap)   ");
+        eprintln!("    --since    Seek a time-ordered file to the ");
+        eprintln!("               first record at/after an RFC3339");
+        eprintln!("               time or epoch (implies --mmap)  ");
+        eprintln!("    --index    Keep a <file>.pidx time index to");
+        eprintln!("               speed up --since; a grown file  ");
+        eprintln!("               only has its new tail indexed   ");
+        eprintln!("    --where    Keep records where an expression");
+        eprintln!("               holds, e.g. 'status >= 500 and  ");
+        eprintln!("               level in (error,fatal)'; IPv4   ");
+        eprintln!("               fields match ranges with 'ip in ");
+        eprintln!("               10.0.0.0/8' or 'ip in (a/n, b)' ");
+        eprintln!("               and nested values by JSON       ");
+        eprintln!("               Pointer, e.g. '/ctx/user=\"x\"'. ");
+        eprintln!("               'x?' keeps records with field x,");
+        eprintln!("               'x!' those without it and 'not  ");
+        eprintln!("               in (a, b)' those not in a list  ");
+        eprintln!("    --derive   Add a computed field, usable in ");
+        eprintln!("               --where and on export, e.g.     ");
+        eprintln!("               'duration_s=latency_ms/1000'    ");
+        eprintln!("    --sample-by-level  Keep a share of records");
+        eprintln!("               per level, e.g. 'info=0.01,     ");
+        eprintln!("               debug=0.001'; others kept (the  ");
+        eprintln!("               choice is stable across reruns) ");
+        eprintln!("    --max-record-bytes, --max-fields,         ");
+        eprintln!("    --max-value-len  Per-record limits for    ");
+        eprintln!("               json, logfmt and csv input      ");
+        eprintln!("    --columns  Give the <k> most frequent keys");
+        eprintln!("               value columns for fast lookups  ");
+        eprintln!("               (default: 8, 0 disables)        ");
+        eprintln!("    --well-known  Treat <key> as the timestamp,");
+        eprintln!("               level, message, component, host,");
+        eprintln!("               pid, trace_id or span_id field  ");
+        eprintln!("    --component-rule  Take plain-text records' ");
+        eprintln!("               component from the message: a   ");
+        eprintln!("               leading 'worker:' (prefix) or   ");
+        eprintln!("               <k>=value (key=<k>); first match");
+        eprintln!("               wins                            ");
+        eprintln!("    --cache    Reuse a file's report when it and");
+        eprintln!("               the arguments are unchanged     ");
+        eprintln!("               (kept in ~/.cache/pandora)      ");
+        eprintln!("    --follow   Keep reading the last file and  ");
+        eprintln!("               print 1m/5m/15m counts per level");
+        eprintln!("               and component every interval    ");
+        eprintln!("               (--follow-interval, default 10s)");
+        eprintln!("    --remote-write  Push counts per level and ");
+        eprintln!("               component (and --agg values) per");
+        eprintln!("               step of record time to a        ");
+        eprintln!("               Prometheus remote-write URL     ");
+        eprintln!("               (--remote-write-step, default 60s)");
+        eprintln!("    --metric-rules  Counters and summaries from");
+        eprintln!("               rules such as 'counter name     ");
+        eprintln!("               labels=(method,status) with path',");
+        eprintln!("               printed in Prometheus text format");
+        eprintln!("               (or written to --metric-out)    ");
+        eprintln!("    --workers  Spread each file over remote   ");
+        eprintln!("               workers in 256 MB ranges and    ");
+        eprintln!("               merge their record, level and   ");
+        eprintln!("               time summaries (files must be on");
+        eprintln!("               a shared mount at the same path)");
+        eprintln!("    --explain  Print the plan (format, SIMD   ");
+        eprintln!("               kernels, chunks, filters, sinks)");
+        eprintln!("               and estimated memory, then exit ");
+        eprintln!("               without parsing                 ");
+        eprintln!("    --detect   Time a parse of the first 256 MB");
+        eprintln!("               with each format that fits and  ");
+        eprintln!("               each thread count, report GB/s, ");
+        eprintln!("               then exit                       ");
+        eprintln!("    --guard-policy  On a limit: truncate the  ");
+        eprintln!("               record (default), drop it, or   ");
+        eprintln!("               drop it and exit with an error  ");
+        eprintln!("    --empty-records  Records without fields   ");
+        eprintln!("               ({{}} or bare words in logfmt): ");
+        eprintln!("               keep them, drop them, or drop   ");
+        eprintln!("               them as malformed lines (default)");
+        eprintln!("    -q, -v     Only errors / also info (-vv:   ");
+        eprintln!("               debug) on stderr; warnings are  ");
+        eprintln!("               shown by default                ");
+        eprintln!("    --log-format  'json' writes diagnostics as");
+        eprintln!("               {{\"level\",\"message\"}} lines    ");
+        eprintln!("    --check-ordering  Report out-of-order      ");
+        eprintln!("               records and per-component skew  ");
+        eprintln!("    --gap-analysis  Histogram of gaps between ");
+        eprintln!("               records; flag silences longer   ");
+        eprintln!("               than --gap-threshold (60 s)     ");
+        eprintln!("    --rejects  Write malformed lines with their");
+        eprintln!("               file offsets to <path>          ");
+        eprintln!("    --extract-bytes  Copy the input bytes of   ");
+        eprintln!("               matched records, unchanged, to  ");
+        eprintln!("               <path>, and where they came from");
+        eprintln!("               to <path>.manifest.json         ");
+        eprintln!("    --pipeline  Read sources, filters,        ");
+        eprintln!("               transforms and sinks from a TOML");
+        eprintln!("               file; keys are the flag names   ");
+        eprintln!("               and flags after it override it  ");
+        eprintln!("    --sink     Emit matching records as NDJSON:");
+        eprintln!("               stdout, file:<path>, metrics or ");
+        eprintln!("               metrics:<path>; add ',drop' or  ");
+        eprintln!("               ',block' for a full queue,      ");
+        eprintln!("               ',retries=<n>' (default 3) for  ");
+        eprintln!("               transient errors and            ");
+        eprintln!("               ',dead-letter=<path>' to keep   ");
+        eprintln!("               chunks the sink rejects         ");
+        eprintln!("    --sink-queue  Chunks buffered per sink     ");
+        eprintln!("               (default: 16)                   ");
+        eprintln!("    --serialize-threads  Threads serializing   ");
+        eprintln!("               records for sinks off the parse ");
+        eprintln!("               threads; 0 serializes inline    ");
+        eprintln!("               (default: a quarter of threads) ");
+        eprintln!("    --rename   Rename a field on export        ");
+        eprintln!("    --set      Add a static field on export    ");
+        eprintln!("    --types    Coerce exported fields: int,    ");
+        eprintln!("               float, bool, timestamp, string  ");
+        eprintln!("    --numbers  How a field writes numbers as   ");
+        eprintln!("               text, for --types, --agg and    ");
+        eprintln!("               --where: grouped (1,234.5 or    ");
+        eprintln!("               1_000) or eu (1.234,5)          ");
+        eprintln!("    --record-id  Add a stable ID to exported   ");
+        eprintln!("               records so re-exports overwrite:");
+        eprintln!("               a head hash and byte offset     ");
+        eprintln!("               (default) or a hash of the line ");
+        eprintln!("    --provenance  Add the input number, byte   ");
+        eprintln!("               offset, length and line of each ");
+        eprintln!("               exported record as a field      ");
+        eprintln!("    --schema-id  Add the ID of each exported   ");
+        eprintln!("               record's key set as a field     ");
+        eprintln!("    --schema   Name exported fields after the  ");
+        eprintln!("               Elastic Common Schema or        ");
+        eprintln!("               OpenTelemetry conventions       ");
+        eprintln!("    --emit-rules  File of 'rename a=b',        ");
+        eprintln!("               'set k=v', 'type f:kind',       ");
+        eprintln!("               'derive k=expr', 'id <field>',  ");
+        eprintln!("               'provenance <field>',           ");
+        eprintln!("               'schema-id <field>' and         ");
+        eprintln!("               'schema ecs|otel' lines         ");
+        eprintln!("    --emit     Sink output: ndjson (default) or");
+        eprintln!("               raw-filtered, the untouched     ");
+        eprintln!("               source lines of matches, or     ");
+        eprintln!("               offsets, an '<offset>\\t<length>'");
+        eprintln!("               line per match, or hash, one    ");
+        eprintln!("               order-independent digest of all ");
+        eprintln!("               matched records and their fields");
+        eprintln!("    --find-duplicates  Report exact duplicate ");
+        eprintln!("               lines, counts and offsets       ");
+        eprintln!("    --value-sizes  Value length distribution ");
+        eprintln!("               per key and of whole records,   ");
+        eprintln!("               for sizing columns and limits   ");
+        eprintln!("    --schema-report  Records per distinct key  ");
+        eprintln!("               set, to spot divergent shapes   ");
+        eprintln!("    --scan-secrets  Flag values that look like");
+        eprintln!("               API keys or tokens (known       ");
+        eprintln!("               prefixes, JWTs, private keys,   ");
+        eprintln!("               long random strings) per field  ");
+        eprintln!("    --http-summary  Requests by status class, ");
+        eprintln!("               top paths with p99 latency and  ");
+        eprintln!("               the 5xx rate over time, for     ");
+        eprintln!("               structured access logs          ");
+        eprintln!("    --triage   List the most suspicious records");
+        eprintln!("               (level, words like panic, OOM or");
+        eprintln!("               timeout, error bursts) with the ");
+        eprintln!("               lines around them; --triage-top ");
+        eprintln!("               sets how many (default: 20)     ");
+        eprintln!("    --group-by Count matches per combination of");
+        eprintln!("               field values, e.g. 'component,  ");
+        eprintln!("               level,status', as a tree        ");
+        eprintln!("    --agg      Per-group sum, avg, min, max or ");
+        eprintln!("               percentile (p50, p99, ...) of a ");
+        eprintln!("               numeric field, e.g.             ");
+        eprintln!("               'latency_ms:p99' (about 1% error)");
+        eprintln!("    --having   Only show groups and duplicates ");
+        eprintln!("               where an expression over count  ");
+        eprintln!("               (and group fields) holds, e.g.  ");
+        eprintln!("               'count > 1000' or, with --agg,  ");
+        eprintln!("               'p99_latency_ms > 500'          ");
+        eprintln!("    --manifest Write <path>.manifest.json with ");
+        eprintln!("               chunk ranges, record counts and ");
+        eprintln!("               xxh64 checksums per file: sink  ");
+        eprintln!("    --dataset-manifest  Write _manifest.json   ");
+        eprintln!("               next to each file: sink and in  ");
+        eprintln!("               --output-dir: records, time     ");
+        eprintln!("               range, levels, fields and parse ");
+        eprintln!("               totals of what was exported     ");
+        eprintln!("    --split-by Write one <dir>/<value>.ndjson  ");
+        eprintln!("               per distinct value of a field   ");
+        eprintln!("    --partition-by  Write one file per hour or ");
+        eprintln!("               day of record time (UTC or --tz)");
+        eprintln!("    --tz       Zone for partition and remote-  ");
+        eprintln!("               write steps, e.g. America/      ");
+        eprintln!("               New_York or +05:30 (default UTC)");
+        eprintln!("    --partition-layout  'hive' writes         ");
+        eprintln!("               dt=<day>/hour=<hh>/part.ndjson  ");
+        eprintln!("    --max-open-files  Split files kept open    ");
+        eprintln!("               at once, LRU (default: 64)      ");
+        eprintln!("    --max-fds  File descriptors the run may use");
+        eprintln!("               (default: the raised ulimit -n);");
+        eprintln!("               sinks, --follow and the like get");
+        eprintln!("               theirs at start, split files the");
+        eprintln!("               rest, or the run stops up front ");
+        eprintln!("    --sort-time  Write sink and split output in");
+        eprintln!("               timestamp order; past --sort-mem");
+        eprintln!("               (default: 256 MB) sorted runs go");
+        eprintln!("               to --sort-dir (default: $TMPDIR)");
+        eprintln!("    --ordered  Write sink and split output in  ");
+        eprintln!("               input order as workers finish,  ");
+        eprintln!("               holding a few chunks at a time  ");
+        eprintln!("                                               ");
+        eprintln!("  Usage: pandoras-logs bench <file>...         ");
+        eprintln!("         [--baseline <json>] [--runs <n>]      ");
+        eprintln!("         [--fail-threshold <pct>] [--threads <n>]");
+        eprintln!("         [--save-baseline <json>]              ");
+        eprintln!("    Median throughput per file over --runs     ");
+        eprintln!("    (default 5); exits 1 when a file is slower ");
+        eprintln!("    than --baseline by more than the threshold ");
+        eprintln!("    (default 5%)                               ");
+        eprintln!("                                               ");
+        eprintln!("  Usage: pandoras-logs worker [--listen <addr>] ");
+        eprintln!("         [--root <dir>] [--threads <n>]        ");
+        eprintln!("         [--max-fds <n>]                       ");
+        eprintln!("    Parse ranges for --workers coordinators;   ");
+        eprintln!("    only files under --root (default .) are    ");
+        eprintln!("    served; listens on 127.0.0.1:7460 unless   ");
+        eprintln!("    --listen gives a reachable address         ");
+        eprintln!("    Coordinators past what --max-fds allows    ");
+        eprintln!("    (default: ulimit -n) wait to be served     ");
+        eprintln!("                                               ");
+        eprintln!("  Usage: pandoras-logs selftest                ");
+        eprintln!("         [--bench-mb <n>]                      ");
+        eprintln!("    Check the scalar/AVX2/AVX-512 kernels this ");
+        eprintln!("    CPU supports against reference vectors and ");
+        eprintln!("    print GB/s for each (over 32 MB by         ");
+        eprintln!("    default); exits 1 when any kernel disagrees");
+        eprintln!("                                               ");
+        eprintln!("  Usage: pandoras-logs replay <file>...        ");
+        eprintln!("         [--speed <n>x] [--sink <spec>]...     ");
+        eprintln!("         [--format <fmt>]                      ");
+        eprintln!("    Re-emit records as NDJSON, spaced out by   ");
+        eprintln!("    their timestamps divided by --speed        ");
+        eprintln!("    (default 1x); to stdout without --sink     ");
         eprintln!("╚══════════════════════════════════════════════╝");
         std::process::exit(1);
     }
 
-    let default_threads = std::thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(1);
+    shutdown::install();
+    sigbus::install();
+
+    let cpu_limit = cgroup::cpu_limit();
+    let default_threads = default_parallelism(cpu_limit);
+
+    let mut file_paths: Vec<&str> = Vec::new();
+    let mut num_threads = default_threads;
+    let mut auto_threads = false;
+    let mut simd_auto = false;
+    let mut threads_explicit = false;
+    let mut pin_spec: Option<PinSpec> = None;
+    let mut use_mmap = false;
+    let mut mmap_populate = false;
+    let mut hugepages = false;
+    let mut readahead_mb: Option<u64> = None;
+    let mut strip_ansi = false;
+    let mut firehose = false;
+    let mut hot_columns = structured::DEFAULT_HOT_COLUMNS;
+    let mut fixed_columns: Option<&str> = None;
+    let mut fixed_record_len: Option<usize> = None;
+    let mut well_known_keys: Vec<(Box<[u8]>, well_known::WellKnownKind)> = Vec::new();
+    let mut number_styles: numbers::FieldStyles = Vec::new();
+    let mut component_rules = Vec::new();
+    let mut format_hint: Option<LogFormat> = None;
+    let mut level_filter: Option<LevelFilter> = None;
+    let mut limit: Option<u64> = None;
+    let mut reverse = false;
+    let mut since: Option<u64> = None;
+    let mut check_ordering = false;
+    let mut gap_threshold: Option<u64> = None;
+    let mut find_duplicates = false;
+    let mut value_sizes = false;
+    let mut schema_report = false;
+    let mut scan_secrets = false;
+    let mut http_summary = false;
+    let mut triage_top: Option<usize> = None;
+    let mut group_keys: Option<Vec<Vec<u8>>> = None;
+    let mut having: Option<Expr> = None;
+    let mut aggs: Vec<Agg> = Vec::new();
+    let mut write_manifest = false;
+    let mut dataset_manifest = false;
+    let mut split_key: Option<SplitKey> = None;
+    let mut output_dir: Option<&str> = None;
+    let mut max_open_files = 64;
+    let mut max_fds: Option<usize> = None;
+    let mut sort_time = false;
+    let mut ordered = false;
+    let mut sort_mem_mb = extsort::DEFAULT_SORT_MEM_MB;
+    let mut sort_dir: Option<&str> = None;
+    let mut partition_layout = PartitionLayout::Flat;
+    let mut tz = TimeZone::utc();
+    let mut rejects_path: Option<&str> = None;
+    let mut extract_path: Option<&str> = None;
+    let mut sink_queue = 16;
+    let mut serialize_threads: Option<usize> = None;
+    let mut emit_rules = EmitRules::default();
+    let mut emit_format = EmitFormat::Ndjson;
+    let mut where_expr: Option<Expr> = None;
+    let mut sample: Option<LevelSampler> = None;
+    let mut record_limits = RecordLimits::default();
+    let mut use_cache = false;
+    let mut use_index = false;
+    let mut follow = false;
+    let mut follow_interval = Duration::from_secs(10);
+    let mut remote_write: Option<RemoteWrite> = None;
+    let mut remote_write_step = 60;
+    let mut metric_rules: Option<MetricRules> = None;
+    let mut metric_out: Option<String> = None;
+    let mut workers: Vec<String> = Vec::new();
+    // Sink specs are opened only once the run is known to write locally:
+    // not under --explain, --detect or --workers.
+    let explain = args.iter().any(|a| a == "--explain");
+    let detect = args.iter().any(|a| a == "--detect");
+    let mut sink_args: Vec<&str> = Vec::new();
+
+    // Diagnostic settings come first so warnings about other flags honor them.
+    for (n, arg) in args.iter().enumerate().skip(1) {
+        match arg.as_str() {
+            "-q" | "--quiet" => diag::set_max_severity(Severity::Error),
+            "-v" | "--verbose" => diag::set_max_severity(diag::max_severity().more_verbose()),
+            "-vv" => diag::set_max_severity(Severity::Debug),
+            "--log-format" => diag::set_json(args.get(n + 1).is_some_and(|f| f == "json")),
+            _ => {}
+        }
+    }
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--mmap" => {
+                use_mmap = true;
+            }
+            "--mmap-populate" => {
+                use_mmap = true;
+                mmap_populate = true;
+            }
+            "--hugepages" => {
+                use_mmap = true;
+                hugepages = true;
+            }
+            "--strip-ansi" => {
+                strip_ansi = true;
+            }
+            "--firehose" => {
+                firehose = true;
+            }
+            "--verify-scan" => {
+                simd_scan::set_verify_scan(true);
+            }
+            "--simd" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].as_str() {
+                        "auto" => simd_auto = true,
+                        name => match Kernel::from_name(name) {
+                            Some(kernel) if simd_scan::set_kernel(kernel, KernelPick::Forced) => {}
+                            Some(_) => warn!(
+                                "This CPU cannot run the {} kernel, using {}",
+                                name,
+                                Kernel::widest().name()
+                            ),
+                            None => {
+                                error!(
+                                    "Invalid --simd '{}', expected auto, avx512, avx2 or scalar",
+                                    name
+                                );
+                                std::process::exit(1);
+                            }
+                        },
+                    }
+                }
+            }
+            "--readahead" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<u64>() {
+                        Ok(mb) => {
+                            use_mmap = true;
+                            readahead_mb = Some(mb);
+                        }
+                        Err(_) => warn!("Invalid --readahead '{}', ignoring", args[i]),
+                    }
+                }
+            }
+            "--pin" => {
+                i += 1;
+                if i < args.len() {
+                    match PinSpec::parse_cpus(&args[i]) {
+                        Ok(cpus) => pin_spec.get_or_insert_default().cpus = cpus,
+                        Err(e) => warn!("Invalid --pin '{}': {}, not pinning", args[i], e),
+                    }
+                }
+            }
+            "--pin-no-smt" => pin_spec.get_or_insert_default().no_smt = true,
+            "--pin-socket" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<u32>() {
+                        Ok(socket) => pin_spec.get_or_insert_default().socket = Some(socket),
+                        Err(_) => warn!("Invalid --pin-socket '{}', ignoring", args[i]),
+                    }
+                }
+            }
+            "--reverse" => {
+                reverse = true;
+                use_mmap = true;
+            }
+            "--format" => {
+                i += 1;
+                if i < args.len() {
+                    format_hint = LogFormat::from_name(&args[i]);
+                    if format_hint.is_none() && args[i] != "auto" {
+                        warn!("Unknown format '{}', using auto-detect", args[i]);
+                    }
+                }
+            }
+            "--record-sep" => {
+                i += 1;
+                if i < args.len() {
+                    match simd_scan::parse_record_sep(&args[i]) {
+                        Some(sep) => simd_scan::set_record_sep(sep),
+                        None => {
+                            error!(
+                                "Invalid --record-sep '{}': expected newline or nul",
+                                args[i]
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--level" => {
+                i += 1;
+                if i < args.len() {
+                    level_filter = LevelFilter::parse(&args[i]);
+                    if level_filter.is_none() {
+                        warn!("Unknown level '{}', ignoring level filter", args[i]);
+                    }
+                }
+            }
+            "--threads" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].as_str() {
+                        "auto" => {
+                            auto_threads = true;
+                            threads_explicit = true;
+                        }
+                        text => match text.parse::<usize>() {
+                            Ok(n) if n > 0 => {
+                                num_threads = n;
+                                auto_threads = false;
+                                threads_explicit = true;
+                            }
+                            _ => warn!("Invalid --threads '{}', using {}", text, num_threads),
+                        },
+                    }
+                }
+            }
+            "--limit" | "--head" => {
+                i += 1;
+                if i < args.len() {
+                    limit = args[i].parse::<u64>().ok();
+                    if limit.is_none() {
+                        warn!("Invalid limit '{}', ignoring", args[i]);
+                    }
+                }
+            }
+            "--rejects" => {
+                i += 1;
+                if i < args.len() {
+                    rejects_path = Some(&args[i]);
+                }
+            }
+            "--extract-bytes" => {
+                i += 1;
+                if i < args.len() {
+                    extract_path = Some(&args[i]);
+                }
+            }
+            "--sink" => {
+                i += 1;
+                if i < args.len() {
+                    if !explain && args[i].starts_with("stdout") {
+                        REPORT_TO_STDERR.store(true, Ordering::Relaxed);
+                    }
+                    sink_args.push(&args[i]);
+                }
+            }
+            "--sink-queue" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(n) if n > 0 => sink_queue = n,
+                        _ => warn!("Invalid sink queue '{}', using {}", args[i], sink_queue),
+                    }
+                }
+            }
+            "--serialize-threads" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(n) => serialize_threads = Some(n),
+                        Err(_) => warn!("Invalid --serialize-threads '{}', ignoring", args[i]),
+                    }
+                }
+            }
+            "--rename" | "--set" | "--types" | "--derive" | "--record-id" | "--provenance"
+            | "--schema-id" | "--schema" | "--emit-rules" => {
+                let flag = args[i].as_str();
+                i += 1;
+                if i < args.len() {
+                    let result = match flag {
+                        "--rename" => emit_rules.add_rename(&args[i]),
+                        "--set" => emit_rules.add_inject(&args[i]),
+                        "--types" => emit_rules.add_types(&args[i]),
+                        "--derive" => emit_rules.add_derive(&args[i]),
+                        "--record-id" => emit_rules.set_record_id(&args[i]),
+                        "--provenance" => emit_rules.set_provenance(&args[i]),
+                        "--schema-id" => emit_rules.set_schema_id(&args[i]),
+                        "--schema" => emit_rules.set_schema(&args[i]),
+                        _ => std::fs::read_to_string(&args[i])
+                            .map_err(|e| e.to_string())
+                            .and_then(|text| emit_rules.load(&text)),
+                    };
+                    if let Err(e) = result {
+                        error!("Invalid {} '{}': {}", flag, args[i], e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--numbers" => {
+                i += 1;
+                if i < args.len() {
+                    match numbers::parse_styles(&args[i]) {
+                        Ok(styles) => number_styles.extend(styles),
+                        Err(e) => {
+                            error!("Invalid --numbers '{}': {}", args[i], e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--check-ordering" => {
+                check_ordering = true;
+                use_mmap = true;
+            }
+            "--gap-analysis" => {
+                gap_threshold.get_or_insert(60);
+                use_mmap = true;
+            }
+            "--gap-threshold" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<u64>() {
+                        Ok(secs) => gap_threshold = Some(secs),
+                        Err(_) => warn!("Invalid --gap-threshold '{}', using 60", args[i]),
+                    }
+                    use_mmap = true;
+                }
+            }
+            "--find-duplicates" => {
+                find_duplicates = true;
+            }
+            "--value-sizes" => {
+                value_sizes = true;
+            }
+            "--schema-report" => {
+                schema_report = true;
+            }
+            "--scan-secrets" => {
+                scan_secrets = true;
+            }
+            "--http-summary" => {
+                http_summary = true;
+            }
+            "--triage" => {
+                triage_top.get_or_insert(triage::DEFAULT_TOP);
+            }
+            "--triage-top" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(n) if n > 0 => triage_top = Some(n),
+                        _ => warn!(
+                            "Invalid --triage-top '{}', using {}",
+                            args[i],
+                            triage::DEFAULT_TOP
+                        ),
+                    }
+                }
+            }
+            "--agg" => {
+                i += 1;
+                if i < args.len() {
+                    match Agg::parse_list(&args[i]) {
+                        Ok(list) => aggs.extend(list),
+                        Err(e) => {
+                            error!("Invalid --agg '{}': {}", args[i], e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--having" => {
+                i += 1;
+                if i < args.len() {
+                    match Expr::parse(&args[i]) {
+                        Ok(expr) => having = Some(expr),
+                        Err(e) => {
+                            error!("Invalid --having '{}': {}", args[i], e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--group-by" => {
+                i += 1;
+                if i < args.len() {
+                    group_keys = GroupBy::parse_keys(&args[i]);
+                    if group_keys.is_none() {
+                        warn!("Invalid --group-by '{}', ignoring it", args[i]);
+                    }
+                }
+            }
+            "--manifest" => {
+                write_manifest = true;
+            }
+            "--dataset-manifest" => {
+                dataset_manifest = true;
+            }
+            "--cache" => {
+                use_cache = true;
+            }
+            "--explain" | "--detect" => {}
+            "--index" => {
+                use_index = true;
+            }
+            "--follow" => {
+                follow = true;
+            }
+            "--follow-interval" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<u64>() {
+                        Ok(secs) if secs > 0 => follow_interval = Duration::from_secs(secs),
+                        _ => warn!("Invalid --follow-interval '{}', using 10", args[i]),
+                    }
+                }
+            }
+            "--remote-write" => {
+                i += 1;
+                if i < args.len() {
+                    match RemoteWrite::new(&args[i]) {
+                        Ok(remote) => remote_write = Some(remote),
+                        Err(e) => {
+                            error!("Invalid --remote-write: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--metric-rules" => {
+                i += 1;
+                if i < args.len() {
+                    match std::fs::read_to_string(&args[i])
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| MetricRules::parse(&text))
+                    {
+                        Ok(rules) => metric_rules = Some(rules),
+                        Err(e) => {
+                            error!("Invalid --metric-rules '{}': {}", args[i], e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--workers" => {
+                i += 1;
+                if i < args.len() {
+                    workers.extend(
+                        args[i]
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|w| !w.is_empty())
+                            .map(String::from),
+                    );
+                }
+            }
+            "--metric-out" => {
+                i += 1;
+                if i < args.len() {
+                    metric_out = Some(args[i].clone());
+                }
+            }
+            "--remote-write-step" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<u64>() {
+                        Ok(secs) if secs > 0 => remote_write_step = secs,
+                        _ => warn!("Invalid --remote-write-step '{}', using 60", args[i]),
+                    }
+                }
+            }
+            "--emit" => {
+                i += 1;
+                if i < args.len() {
+                    match EmitFormat::parse(&args[i]) {
+                        Some(format) => emit_format = format,
+                        None => {
+                            error!(
+                                "Invalid --emit '{}': expected ndjson, raw-filtered, offsets or hash",
+                                args[i]
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--split-by" => {
+                i += 1;
+                if i < args.len() {
+                    split_key = Some(SplitKey::Field(args[i].as_bytes().to_vec()));
+                }
+            }
+            "--partition-by" => {
+                i += 1;
+                if i < args.len() {
+                    match TimeBucket::parse(&args[i]) {
+                        Some(bucket) => split_key = Some(SplitKey::Time(bucket, TimeZone::utc())),
+                        None => {
+                            error!("Invalid --partition-by '{}': expected hour or day", args[i]);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--tz" => {
+                i += 1;
+                if i < args.len() {
+                    match TimeZone::parse(&args[i]) {
+                        Ok(zone) => tz = zone,
+                        Err(e) => {
+                            error!("Invalid --tz: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--partition-layout" => {
+                i += 1;
+                if i < args.len() {
+                    match PartitionLayout::parse(&args[i]) {
+                        Some(layout) => partition_layout = layout,
+                        None => {
+                            error!(
+                                "Invalid --partition-layout '{}': expected flat or hive",
+                                args[i]
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--output-dir" => {
+                i += 1;
+                if i < args.len() {
+                    output_dir = Some(&args[i]);
+                }
+            }
+            "--fixed-columns" => {
+                i += 1;
+                if i < args.len() {
+                    fixed_columns = Some(&args[i]);
+                }
+            }
+            "--fixed-record-len" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(n) if n > 0 => fixed_record_len = Some(n),
+                        _ => {
+                            error!("Invalid --fixed-record-len '{}'", args[i]);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--columns" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(n) => hot_columns = n,
+                        _ => warn!(
+                            "Invalid --columns '{}', using {}",
+                            args[i],
+                            structured::DEFAULT_HOT_COLUMNS
+                        ),
+                    }
+                }
+            }
+            "--well-known" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].split_once('=').and_then(|(slot, key)| {
+                        Some((well_known::WellKnownKind::from_slot(slot)?, key))
+                    }) {
+                        Some((kind, key)) if !key.is_empty() => well_known_keys
+                            .push((key.to_ascii_lowercase().into_bytes().into(), kind)),
+                        _ => warn!(
+                            "Invalid --well-known '{}', expected <slot>=<key> with slot one of timestamp, level, message, component, host, pid, trace_id, span_id",
+                            args[i]
+                        ),
+                    }
+                }
+            }
+            "--component-rule" => {
+                i += 1;
+                if i < args.len() {
+                    match ComponentRule::parse(&args[i]) {
+                        Ok(rule) => component_rules.push(rule),
+                        Err(e) => warn!("Invalid --component-rule: {}, ignoring it", e),
+                    }
+                }
+            }
+            "--sort-time" => {
+                sort_time = true;
+            }
+            "--ordered" => {
+                ordered = true;
+            }
+            "--sort-mem" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(mb) if mb > 0 => sort_mem_mb = mb,
+                        _ => warn!("Invalid --sort-mem '{}', using {}", args[i], sort_mem_mb),
+                    }
+                }
+            }
+            "--sort-dir" => {
+                i += 1;
+                if i < args.len() {
+                    sort_dir = Some(&args[i]);
+                }
+            }
+            "--max-open-files" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(n) if n > 0 => max_open_files = n,
+                        _ => warn!("Invalid --max-open-files '{}', using 64", args[i]),
+                    }
+                }
+            }
+            "--max-fds" => {
+                i += 1;
+                if i < args.len() {
+                    match args[i].parse::<usize>() {
+                        Ok(n) if n > 0 => max_fds = Some(n),
+                        _ => {
+                            error!("Invalid --max-fds '{}'", args[i]);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--where" => {
+                i += 1;
+                if i < args.len() {
+                    match Expr::parse(&args[i]) {
+                        Ok(expr) => {
+                            where_expr = Some(expr);
+                            use_mmap = true;
+                        }
+                        Err(e) => {
+                            error!("Invalid --where '{}': {}", args[i], e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--max-record-bytes" | "--max-fields" | "--max-value-len" => {
+                let flag = args[i].as_str();
+                i += 1;
+                if i < args.len() {
+                    let n = match args[i].parse::<u32>() {
+                        Ok(n) if n > 0 => n,
+                        _ => {
+                            error!("Invalid {} '{}'", flag, args[i]);
+                            std::process::exit(1);
+                        }
+                    };
+                    match flag {
+                        "--max-record-bytes" => record_limits.max_record_bytes = n,
+                        "--max-fields" => record_limits.max_fields = n,
+                        _ => record_limits.max_value_len = n,
+                    }
+                }
+            }
+            "--guard-policy" => {
+                i += 1;
+                if i < args.len() {
+                    match GuardPolicy::parse(&args[i]) {
+                        Some(policy) => record_limits.policy = policy,
+                        None => warn!("Unknown --guard-policy '{}', using truncate", args[i]),
+                    }
+                }
+            }
+            "--empty-records" => {
+                i += 1;
+                if i < args.len() {
+                    match EmptyPolicy::parse(&args[i]) {
+                        Some(policy) => record_limits.empty = policy,
+                        None => {
+                            error!(
+                                "Unknown --empty-records '{}', expected keep, drop or malformed",
+                                args[i]
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--sample-by-level" => {
+                i += 1;
+                if i < args.len() {
+                    match LevelSampler::parse(&args[i]) {
+                        Ok(sampler) => sample = Some(sampler),
+                        Err(e) => {
+                            error!("Invalid --sample-by-level '{}': {}", args[i], e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            "--since" => {
+                i += 1;
+                if i < args.len() {
+                    since = parser::parse_timestamp(args[i].as_bytes())
+                        .or_else(|| args[i].parse::<u64>().ok());
+                    if since.is_none() {
+                        warn!("Invalid time '{}', ignoring --since", args[i]);
+                    } else {
+                        use_mmap = true;
+                    }
+                }
+            }
+            "-q" | "--quiet" | "-v" | "--verbose" | "-vv" => {}
+            "--log-format" => {
+                i += 1;
+                if i < args.len() && !matches!(args[i].as_str(), "text" | "json") {
+                    warn!("Unknown --log-format '{}', using text", args[i]);
+                }
+            }
+            arg if arg.starts_with("--") => warn!("Ignoring unknown option '{}'", arg),
+            arg => {
+                if file_paths.is_empty() {
+                    file_paths.push(arg);
+                } else if let Ok(n) = arg.parse::<usize>() {
+                    num_threads = n;
+                    auto_threads = false;
+                    threads_explicit = true;
+                } else {
+                    file_paths.push(arg);
+                }
+            }
+        }
+        i += 1;
+    }
+    well_known::set_overrides(well_known_keys);
+    numbers::set_styles(number_styles);
+    component::set_rules(component_rules);
+    // --tz may come after --partition-by.
+    if let Some(SplitKey::Time(_, zone)) = &mut split_key {
+        *zone = tz.clone();
+    } else if !tz.is_utc() && remote_write.is_none() {
+        warn!("--tz has no effect without --partition-by or --remote-write");
+    }
+    // Parsed after the overrides so its columns classify like CSV headers.
+    let fixed_layout = fixed_columns.map(|spec| match FixedLayout::parse(spec) {
+        Ok(layout) => FixedLayout {
+            record_len: fixed_record_len,
+            ..layout
+        },
+        Err(e) => {
+            error!("Invalid --fixed-columns: {}", e);
+            std::process::exit(1);
+        }
+    });
+    match (&fixed_layout, format_hint) {
+        (Some(_), None) => format_hint = Some(LogFormat::FixedWidth),
+        (Some(_), Some(format)) if format != LogFormat::FixedWidth => {
+            warn!("--fixed-columns only applies to --format fixed-width, ignoring it")
+        }
+        (None, Some(LogFormat::FixedWidth)) => {
+            error!("--format fixed-width needs --fixed-columns");
+            std::process::exit(1);
+        }
+        (None, _) if fixed_record_len.is_some() => {
+            warn!("--fixed-record-len needs --fixed-columns, ignoring it")
+        }
+        _ => {}
+    }
+
+    if firehose && (level_filter.is_some() || since.is_some() || sample.is_some()) {
+        warn!(
+            "--firehose leaves records without levels or timestamps; --level, --since and --sample-by-level match nothing"
+        );
+    }
+
+    let chunk_mb = std::env::var("PANDORA_CHUNK_MB")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v >= 1)
+        .unwrap_or(64);
+
+    // --explain reads only the head of each input and exits before any
+    // output file is created.
+    if explain {
+        if file_paths.is_empty() {
+            error!("Missing <file> argument");
+            std::process::exit(1);
+        }
+        let flag_value = |flag: &str| {
+            args.iter()
+                .rposition(|a| a == flag)
+                .and_then(|n| args.get(n + 1))
+                .map(String::as_str)
+        };
+        let threads = if auto_threads {
+            format!("auto (best of up to {})", num_threads)
+        } else {
+            num_threads.to_string()
+        };
+        let pinned = match pin_spec
+            .as_ref()
+            .map(|spec| spec.select(&CpuTopo::online()))
+        {
+            Some(cpus) if !cpus.is_empty() => format!("CPUs {}", pinning::format_cpu_list(&cpus)),
+            _ => "off".to_string(),
+        };
+        reportln!();
+        reportln!("╔════════════════════════════════════════════════════╗");
+        reportln!("       PANDORA'S LOGS — EXPLAIN (nothing is parsed)  ");
+        reportln!("╠════════════════════════════════════════════════════╣");
+        reportln!("  SIMD:   {:<42} ", simd_scan::simd_capability());
+        reportln!("  Threads:{:<42} ", threads);
+        reportln!("  Pinned: {:<42} ", pinned);
+        reportln!("  Chunks: {:<42} ", format!("{} MB", chunk_mb));
+        reportln!("╚════════════════════════════════════════════════════╝");
+
+        let mut peak = 0;
+        for &file_path in &file_paths {
+            reportln!("\nInput: {}", file_path);
+            let file_size = match std::fs::metadata(file_path) {
+                Ok(meta) => meta.len(),
+                Err(e) => {
+                    reportln!("  cannot open: {}", e);
+                    continue;
+                }
+            };
+            let mut head = Vec::new();
+            if let Ok(file) = File::open(file_path) {
+                use std::io::Read;
+                let _ = file
+                    .take(explain::SAMPLE_BYTES as u64)
+                    .read_to_end(&mut head);
+            }
+            let encoding = Encoding::detect(&head);
+            if encoding.needs_transcoding() {
+                head = encoding::transcode(&head, encoding);
+            }
+            let format = format_hint.unwrap_or_else(|| LogFormat::detect(&head));
+            let mapped = use_mmap && !encoding.needs_transcoding();
+            reportln!(
+                "  Format:  {} ({}), {}",
+                format,
+                if format_hint.is_some() {
+                    "forced"
+                } else {
+                    "detected"
+                },
+                encoding.as_str()
+            );
+            reportln!(
+                "  Read:    {}{}",
+                if mapped { "mmap" } else { "streaming" },
+                match (reverse, encoding.needs_transcoding()) {
+                    (_, true) => ", transcoded to UTF-8",
+                    (true, _) => ", newest chunk first",
+                    _ => "",
+                }
+            );
+            let chunk_bytes = chunk_mb as u64 * 1024 * 1024;
+            let chunks = file_size.div_ceil(chunk_bytes).max(1);
+            reportln!(
+                "  Chunks:  {} of up to {} MB, {} parse thread(s)",
+                chunks,
+                chunk_mb,
+                num_threads.min(chunks as usize)
+            );
+            let kernels: Vec<String> = explain::kernels(format)
+                .iter()
+                .map(|(kernel, tier)| format!("{} {}", kernel, tier))
+                .collect();
+            reportln!("  Kernels: {}", kernels.join(", "));
+            let estimate = explain::Estimate::of(&head, file_size, format, hot_columns);
+            reportln!(
+                "  Size:    {} ({})",
+                explain::format_bytes(file_size),
+                estimate
+            );
+            // Streaming keeps every chunk it read, plus one read buffer.
+            let read_buffer = if mapped {
+                0
+            } else {
+                chunk_bytes.min(file_size)
+            };
+            peak = peak.max(estimate.total_bytes() + read_buffer);
+        }
+
+        reportln!("\nFilters, in the order each record meets them:");
+        let mut any_filter = false;
+        if let Some(time) = flag_value("--since").filter(|_| since.is_some()) {
+            any_filter = true;
+            reportln!(
+                "  --since {}: {} to the first record at or after it, then per record",
+                time,
+                if use_index {
+                    "seek with the .pidx index"
+                } else {
+                    "binary-search seek"
+                }
+            );
+        }
+        if let Some(rates) = flag_value("--sample-by-level").filter(|_| sample.is_some()) {
+            any_filter = true;
+            reportln!(
+                "  --sample-by-level {}: per record, in the parse workers",
+                rates
+            );
+        }
+        if let Some(level) = level_filter {
+            any_filter = true;
+            let per_record = since.is_some() || where_expr.is_some() || sample.is_some();
+            if per_record || !sink_args.is_empty() {
+                reportln!(
+                    "  --level {}: chunks without such records are skipped, then per record",
+                    level
+                );
+            } else {
+                reportln!(
+                    "  --level {}: counted from each chunk's level summary, no per-record check",
+                    level
+                );
+            }
+        }
+        if let Some(expr) = flag_value("--where").filter(|_| where_expr.is_some()) {
+            any_filter = true;
+            reportln!("  --where {}: per record, in the parse workers", expr);
+        }
+        if let Some(limit) = limit {
+            any_filter = true;
+            reportln!(
+                "  --limit {}: workers stop taking chunks after {} matches, across all files",
+                limit,
+                limit
+            );
+        }
+        if !any_filter {
+            reportln!("  none, every record matches");
+        }
+
+        reportln!("\nOutputs:");
+        for spec in &sink_args {
+            reportln!("  sink {} (queue of {} chunks)", spec, sink_queue);
+        }
+        match (&split_key, output_dir) {
+            (Some(SplitKey::Field(field)), Some(dir)) => reportln!(
+                "  one file per '{}' value under {}/ (at most {} open)",
+                String::from_utf8_lossy(field),
+                dir,
+                max_open_files
+            ),
+            (Some(SplitKey::Time(..)), Some(dir)) => reportln!(
+                "  one file per {} under {}/ (at most {} open)",
+                flag_value("--partition-by").unwrap_or("time bucket"),
+                dir,
+                max_open_files
+            ),
+            _ => {}
+        }
+        if sort_time {
+            reportln!(
+                "  in timestamp order: records are held until the end, past {} MB in sorted runs under {}",
+                sort_mem_mb,
+                sort_dir.map_or_else(
+                    || std::env::temp_dir().display().to_string(),
+                    str::to_string
+                )
+            );
+        }
+        if write_manifest {
+            reportln!("  a .manifest.json next to each file: sink");
+        }
+        if dataset_manifest {
+            reportln!("  a _manifest.json next to each file: sink and in --output-dir");
+        }
+        if let Some(path) = rejects_path {
+            reportln!("  malformed lines to {}", path);
+        }
+        if let Some(path) = extract_path {
+            reportln!("  matched input bytes to {} (+ .manifest.json)", path);
+        }
+        for (enabled, what) in [
+            (group_keys.is_some(), "--group-by report"),
+            (find_duplicates, "duplicate report"),
+            (value_sizes, "value size report"),
+            (schema_report, "schema report"),
+            (scan_secrets, "secret scan report"),
+            (http_summary, "HTTP summary"),
+            (triage_top.is_some(), "triage report"),
+            (emit_format == EmitFormat::Hash, "--emit hash digest"),
+            (check_ordering, "ordering report"),
+            (gap_threshold.is_some(), "gap report"),
+            (metric_rules.is_some(), "--metric-rules exposition"),
+            (remote_write.is_some(), "Prometheus remote-write pushes"),
+            (follow, "rolling windows while following the last file"),
+        ] {
+            if enabled {
+                reportln!("  {}", what);
+            }
+        }
+        reportln!("  the parse summary");
+        if !workers.is_empty() {
+            reportln!(
+                "  (--workers: ranges go to {} workers and only summaries come back)",
+                workers.len()
+            );
+        }
+
+        reportln!(
+            "\nEstimated peak memory: {} (the largest input plus its record index{})",
+            explain::format_bytes(peak),
+            if group_keys.is_some() || find_duplicates {
+                "; --group-by and --find-duplicates grow with distinct values"
+            } else {
+                ""
+            }
+        );
+        std::process::exit(0);
+    }
+
+    // --detect times parsers on the head of each input and, like --explain,
+    // exits before any output file is created.
+    if detect {
+        if file_paths.is_empty() {
+            error!("Missing <file> argument");
+            std::process::exit(1);
+        }
+        for &file_path in &file_paths {
+            reportln!("\nInput: {}", file_path);
+            let mut decoder = match File::open(file_path) {
+                Ok(file) => Decompressor::new(file),
+                Err(e) => {
+                    reportln!("  cannot open: {}", e);
+                    continue;
+                }
+            };
+            let mut head = Vec::new();
+            {
+                use std::io::Read;
+                let _ = (&mut decoder)
+                    .take(bench::DETECT_SAMPLE_BYTES as u64)
+                    .read_to_end(&mut head);
+            }
+            if head.is_empty() {
+                reportln!("  empty, nothing to time");
+                continue;
+            }
+            let encoding = Encoding::detect(&head);
+            if encoding.needs_transcoding() {
+                head = encoding::transcode(&head, encoding);
+            }
+            if strip_ansi && let Some(clean) = ansi::strip(&head) {
+                head = clean;
+            }
+            let mut detected = format_hint.unwrap_or_else(|| LogFormat::detect(&head));
+            if format_hint.is_none()
+                && let Some(format) = structured_orchestrator::redetect_format(&head, detected)
+            {
+                detected = format;
+            }
+            reportln!(
+                "  Format:  {} ({}), {}{}",
+                detected,
+                if format_hint.is_some() {
+                    "forced"
+                } else {
+                    "detected"
+                },
+                encoding.as_str(),
+                decoder
+                    .members()
+                    .first()
+                    .map_or_else(String::new, |m| format!(
+                        ", {} compressed",
+                        m.compression.as_str()
+                    ))
+            );
+            reportln!(
+                "  Sample:  {}, parsed on {} thread(s)",
+                explain::format_bytes(head.len() as u64),
+                num_threads
+            );
+
+            let mut candidates = structured_orchestrator::plausible_formats(&head);
+            if detected != LogFormat::FixedWidth && !candidates.contains(&detected) {
+                candidates.insert(0, detected);
+            }
+            let results = bench::bench_formats(&head, &candidates, num_threads);
+            report!(
+                "{}",
+                bench::FormatBenchTable {
+                    results: &results,
+                    detected,
+                }
+            );
+            if detected != LogFormat::FixedWidth {
+                let sweep = bench::sweep_threads(&head, detected, num_threads);
+                let samples: Vec<String> = sweep
+                    .samples
+                    .iter()
+                    .map(|(threads, gbps)| format!("{}: {:.2}", threads, gbps))
+                    .collect();
+                reportln!(
+                    "  Threads: {} fastest for {} (GB/s by thread count: {})",
+                    sweep.best,
+                    detected,
+                    samples.join(", ")
+                );
+            }
+        }
+        std::process::exit(0);
+    }
+
+    // Without an explicit count, pinned runs use one worker per pinned CPU.
+    let mut max_threads = default_threads;
+    let pinned_cpus = pin_spec.map(|spec| {
+        let mut cpus = spec.select(&CpuTopo::online());
+        // Pinning more CPUs than the cgroup quota only adds throttling.
+        if let (None, Some(limit)) = (&spec.cpus, cpu_limit) {
+            cpus.truncate(limit);
+        }
+        cpus
+    });
+    match &pinned_cpus {
+        Some(cpus) if cpus.is_empty() => warn!("--pin matches no usable CPUs, not pinning"),
+        Some(cpus) => {
+            max_threads = cpus.len();
+            if !threads_explicit {
+                num_threads = cpus.len();
+            }
+            pinning::set_cpus(cpus.clone());
+        }
+        None => {}
+    }
+    let pin_str = match &pinned_cpus {
+        Some(cpus) if !cpus.is_empty() => format!("CPUs {}", pinning::format_cpu_list(cpus)),
+        _ => "off".to_string(),
+    };
+
+    // Workers send back summaries, not records, so a distributed run
+    // reports counts and nothing that needs the records themselves. It
+    // starts before any local output is opened.
+    if !workers.is_empty() {
+        if file_paths.is_empty() {
+            error!("Missing <file> argument");
+            std::process::exit(1);
+        }
+        if level_filter.is_some()
+            || since.is_some()
+            || group_keys.is_some()
+            || find_duplicates
+            || metric_rules.is_some()
+        {
+            warn!(
+                "--workers only reports record, level and time summaries; other options are ignored"
+            );
+        }
+        if !sink_args.is_empty()
+            || output_dir.is_some()
+            || rejects_path.is_some()
+            || extract_path.is_some()
+            || remote_write.is_some()
+            || write_manifest
+            || dataset_manifest
+        {
+            warn!(
+                "--workers writes no local output; --sink, --output-dir, --rejects, \
+                 --extract-bytes, --remote-write and manifests are ignored"
+            );
+        }
+        std::process::exit(run_distributed(&file_paths, &workers, num_threads));
+    }
+
+    let mut sinks: Vec<SinkSpec> = Vec::with_capacity(sink_args.len());
+    for spec in &sink_args {
+        match SinkSpec::parse(spec) {
+            Ok(spec) => sinks.push(spec),
+            Err(e) => {
+                error!("Invalid sink '{}': {}", spec, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Descriptors held for the whole run are claimed now, so a budget too
+    // small fails here instead of with EMFILE partway through.
+    let mut fd_budget = FdBudget::new(match (max_fds, fds::raise_limit(max_fds)) {
+        (Some(max), Some(limit)) if max > limit => {
+            warn!(
+                "--max-fds {} is above the open-file limit of {}",
+                max, limit
+            );
+            limit
+        }
+        (Some(max), _) => max,
+        (None, limit) => limit.unwrap_or(1024),
+    });
+    let sink_files: usize = sinks
+        .iter()
+        .map(|spec| usize::from(spec.name.contains(':')) + usize::from(spec.dead_letter.is_some()))
+        .sum();
+    for (what, n) in [
+        ("--sink", sink_files),
+        ("--rejects", usize::from(rejects_path.is_some())),
+        ("--extract-bytes", usize::from(extract_path.is_some())),
+        ("--follow", usize::from(follow)),
+        ("--remote-write", usize::from(remote_write.is_some())),
+    ] {
+        if let Err(e) = fd_budget.claim(what, n) {
+            error!("{}; raise --max-fds or ulimit -n", e);
+            std::process::exit(1);
+        }
+    }
+    if split_key.is_some() && output_dir.is_some() {
+        match fd_budget.claim_up_to("--output-dir", 1, max_open_files) {
+            Ok(granted) if granted < max_open_files => {
+                warn!(
+                    "--max-fds {} leaves room for {} open split files, not {}",
+                    fd_budget.limit(),
+                    granted,
+                    max_open_files
+                );
+                max_open_files = granted;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("{}; raise --max-fds or ulimit -n", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match (&split_key, output_dir) {
+        (Some(_), Some(dir)) => {
+            let sink = SplitSink::new(dir, partition_layout, max_open_files).unwrap_or_else(|e| {
+                error!("Cannot create output directory '{}': {}", dir, e);
+                std::process::exit(1);
+            });
+            sinks.push(SinkSpec::new(
+                format!("split:{}", dir),
+                Box::new(sink),
+                Overflow::Block,
+            ));
+        }
+        (Some(_), None) => {
+            error!("--split-by and --partition-by need --output-dir");
+            std::process::exit(1);
+        }
+        (None, Some(_)) => {
+            warn!("--output-dir has no effect without --split-by or --partition-by")
+        }
+        (None, None) => {}
+    }
+
+    if write_manifest {
+        if !sinks.iter().any(|spec| spec.name.starts_with("file:")) {
+            warn!("--manifest only applies to file: sinks");
+        }
+        sinks = sinks
+            .into_iter()
+            .map(|spec| {
+                let Some(path) = spec.name.strip_prefix("file:") else {
+                    return spec;
+                };
+                let manifest_path = format!("{}.manifest.json", path);
+                let out = File::create(&manifest_path).unwrap_or_else(|e| {
+                    error!("Cannot create manifest '{}': {}", manifest_path, e);
+                    std::process::exit(1);
+                });
+                SinkSpec {
+                    sink: Box::new(ManifestSink::new(spec.sink, path, BufWriter::new(out))),
+                    ..spec
+                }
+            })
+            .collect();
+    }
+
+    let parse_totals = ParseTotals::default();
+    if dataset_manifest {
+        emit_rules.collect_stats = true;
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        sinks = sinks
+            .into_iter()
+            .map(|spec| {
+                let (artifact, dir) = match spec.name.split_once(':') {
+                    Some(("file", path)) => (
+                        path,
+                        Path::new(path)
+                            .parent()
+                            .filter(|dir| !dir.as_os_str().is_empty())
+                            .unwrap_or(Path::new(".")),
+                    ),
+                    Some(("split", dir)) => (dir, Path::new(dir)),
+                    _ => return spec,
+                };
+                if dirs.iter().any(|seen| seen == dir) {
+                    warn!(
+                        "--dataset-manifest: {} shares its directory with another sink; \
+                         only the first gets a _manifest.json",
+                        spec.name
+                    );
+                    return spec;
+                }
+                dirs.push(dir.to_path_buf());
+                let manifest_path = dir.join("_manifest.json");
+                let out = File::create(&manifest_path).unwrap_or_else(|e| {
+                    error!(
+                        "Cannot create manifest '{}': {}",
+                        manifest_path.display(),
+                        e
+                    );
+                    std::process::exit(1);
+                });
+                let sink = DatasetSink::new(
+                    spec.sink,
+                    artifact,
+                    BufWriter::new(out),
+                    Arc::clone(&parse_totals),
+                );
+                SinkSpec {
+                    sink: Box::new(sink),
+                    ..spec
+                }
+            })
+            .collect();
+        if dirs.is_empty() {
+            warn!("--dataset-manifest only applies to file: sinks and --output-dir");
+        }
+    }
+
+    if file_paths.is_empty() {
+        error!("Missing <file> argument");
+        std::process::exit(1);
+    }
+
+    let mode_str = if use_mmap { "mmap" } else { "streaming" };
+
+    if let Some(limit) = cpu_limit {
+        debug!("cgroup CPU quota allows {} CPUs", limit);
+    }
+    debug!(
+        "{} threads, {} MB chunks, {} mode, sink queue {}",
+        num_threads, chunk_mb, mode_str, sink_queue
+    );
+
+    let rejects = rejects_path.map(|path| {
+        RejectWriter::create(path, file_paths[0]).unwrap_or_else(|e| {
+            error!("Cannot create rejects file '{}': {}", path, e);
+            std::process::exit(1);
+        })
+    });
+    let write_reject = |offset: u64, line: &[u8]| {
+        if let Some(rejects) = &rejects {
+            rejects.write(offset, line);
+        }
+    };
+    let on_reject: Option<&RejectCallback> = rejects.is_some().then_some(&write_reject);
+    // Stripped lines no longer line up with the input bytes.
+    if extract_path.is_some() && strip_ansi {
+        warn!("--extract-bytes cannot be combined with --strip-ansi, ignoring it");
+        extract_path = None;
+    }
+    if strip_ansi && matches!(emit_format, EmitFormat::Offsets | EmitFormat::RawFiltered) {
+        error!("--emit offsets and --emit raw-filtered cannot be combined with --strip-ansi");
+        std::process::exit(1);
+    }
+    let extract = extract_path.map(|path| {
+        ByteExtract::create(path).unwrap_or_else(|e| {
+            error!("Cannot create extract file '{}': {}", path, e);
+            std::process::exit(1);
+        })
+    });
+
+    if !emit_rules.is_empty() && sinks.is_empty() {
+        warn!("Export rules have no effect without --sink");
+    } else if !emit_rules.is_empty() && emit_format != EmitFormat::Ndjson {
+        warn!("Export rules only apply to --emit ndjson");
+    }
+    if !aggs.is_empty() && group_keys.is_none() && !follow && remote_write.is_none() {
+        warn!("--agg needs --group-by, --follow or --remote-write, ignoring it");
+    }
+    if having.is_some() && group_keys.is_none() && !find_duplicates {
+        warn!("--having only applies to --group-by and --find-duplicates");
+    }
+    if metric_out.is_some() && metric_rules.is_none() {
+        warn!("--metric-out needs --metric-rules, ignoring it");
+    }
+    if use_index && since.is_none() {
+        warn!("--index only speeds up --since, ignoring it");
+    }
+    // Only the printed report is cached, so runs writing anything else
+    // (sinks, rejects, duplicate tracking across files) always parse.
+    if use_cache
+        && (!sinks.is_empty()
+            || rejects.is_some()
+            || extract.is_some()
+            || find_duplicates
+            || value_sizes
+            || schema_report
+            || scan_secrets
+            || http_summary
+            || triage_top.is_some()
+            || group_keys.is_some()
+            || follow
+            || remote_write.is_some()
+            || metric_rules.is_some()
+            || emit_format == EmitFormat::Hash)
+    {
+        warn!(
+            "--cache is ignored with --sink, --split-by, --rejects, --extract-bytes, --find-duplicates, --value-sizes, --schema-report, --scan-secrets, --http-summary, --triage, --group-by, --follow, --remote-write, --metric-rules or --emit hash"
+        );
+        use_cache = false;
+    }
+    let query_cache = use_cache
+        .then(|| {
+            QueryCache::open_default()
+                .inspect_err(|e| warn!("Cannot open the query cache, not caching: {}", e))
+                .ok()
+        })
+        .flatten();
+    let query = std::iter::once(env!("CARGO_PKG_VERSION"))
+        .chain(
+            args[1..]
+                .iter()
+                .map(String::as_str)
+                .filter(|a| *a != "--cache" && !file_paths.contains(a)),
+        )
+        .collect::<Vec<_>>()
+        .join("\0");
+
+    let tee = Tee::spawn(sinks, sink_queue);
+    let sorter = if !sort_time {
+        None
+    } else if tee.is_empty() {
+        warn!("--sort-time only orders --sink and --split-by output, ignoring it");
+        None
+    } else if follow {
+        warn!("--sort-time cannot wait for the end of a followed file, ignoring it");
+        None
+    } else {
+        let parent = sort_dir.map_or_else(std::env::temp_dir, std::path::PathBuf::from);
+        match ExternalSort::new(&parent, sort_mem_mb * 1024 * 1024) {
+            Ok(sorter) => Some(sorter),
+            Err(e) => {
+                error!(
+                    "Cannot create a sort directory in '{}': {}",
+                    parent.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        }
+    };
+    let reorder = if !ordered {
+        None
+    } else if tee.is_empty() {
+        warn!("--ordered only orders --sink and --split-by output, ignoring it");
+        None
+    } else if sorter.is_some() {
+        warn!("--sort-time already orders the output, ignoring --ordered");
+        None
+    } else {
+        Some(Reorder::new(
+            num_threads * reorder::WINDOW_PER_THREAD,
+            |chunk| tee.send(chunk),
+        ))
+    };
+    let duplicates = find_duplicates.then(DuplicateFinder::new);
+    let sizes = value_sizes.then(ValueSizes::new);
+    let schemas = schema_report.then(SchemaCounts::new);
+    let secrets = scan_secrets.then(SecretScanner::new);
+    let http = http_summary.then(HttpSummary::new);
+    let triage = triage_top.map(Triage::new);
+    // Without a sink the digest is the only thing on stdout, so CI can
+    // compare it directly.
+    let digest = (emit_format == EmitFormat::Hash).then(RecordDigest::default);
+    if digest.is_some() && tee.is_empty() {
+        REPORT_TO_STDERR.store(true, Ordering::Relaxed);
+    }
+    // --follow keeps reading the last file once it is parsed; only records
+    // arriving from then on count toward the rolling windows.
+    let follow_path = file_paths.last().copied().filter(|_| follow);
+    if follow && file_paths.len() > 1 {
+        warn!("--follow only follows the last file");
+    }
+    let windows = follow.then(|| RollingWindows::new(aggs.clone()));
+    let following = AtomicBool::new(false);
+    let timeline = remote_write
+        .as_ref()
+        .map(|_| Timeline::new(remote_write_step, aggs.clone(), tz.clone()));
+    // Pushes go through a sink lane of their own, for its retries.
+    let remote_tee = remote_write.as_ref().map(|remote| {
+        let sink = SinkSpec::new(
+            remote.url().to_string(),
+            Box::new(remote.sink()),
+            Overflow::Block,
+        );
+        Tee::spawn(vec![sink], 4)
+    });
+    // In follow mode, each tick prints the windows and pushes the steps
+    // that ended a step ago (later records for them are dropped).
+    let follow_tick = || {
+        let now = unix_now();
+        if let Some(windows) = &windows {
+            report!("\n{}", windows.report(now));
+        }
+        if let (Some(remote), Some(tee), Some(timeline)) = (&remote_write, &remote_tee, &timeline) {
+            push_timeline(remote, tee, timeline, now.saturating_sub(timeline.step()));
+        }
+    };
+    let group_by = group_keys.map(|keys| GroupBy::new(keys, aggs));
+
+    // --limit is one budget across all input files.
+    let mut remaining = limit;
+    let mut breakdown = FormatBreakdown::default();
+    let mut guard_error: Option<(&str, u64)> = None;
+
+    for (file_id, &file_path) in file_paths.iter().enumerate() {
+        if remaining == Some(0) || shutdown::interrupted() {
+            break;
+        }
+        if let Some(rejects) = &rejects {
+            rejects.set_source(file_path);
+        }
+
+        // `-` reads standard input, front to back like a compressed file.
+        let piped = file_path == "-";
+        let mut file = match File::open(if piped { "/dev/stdin" } else { file_path }) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Cannot open '{}': {}", file_path, e);
+                continue;
+            }
+        };
+
+        let opened = FileIdentity::of(&file.metadata().unwrap());
+        let file_size = opened.len as usize;
+        syslog::set_year_reference(
+            opened
+                .modified
+                .filter(|_| !piped)
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |age| age.as_secs()),
+        );
+
+        if file_size == 0 && !piped {
+            reportln!("{} is empty. Nothing to parse.", file_path);
+            continue;
+        }
+
+        let signature = query_cache
+            .as_ref()
+            .and_then(|_| FileSignature::of(file_path).ok());
+        if let (Some(cache), Some(sig)) = (&query_cache, &signature)
+            && let Some(hit) = cache.get(sig, &query)
+        {
+            info!(
+                "{}: unchanged since the cached run, reusing its report",
+                file_path
+            );
+            report!("{}", hit.text);
+            remaining = remaining.map(|n| n.saturating_sub(hit.matched));
+            if let Some(format) = LogFormat::from_name(&hit.format) {
+                breakdown.record(format, hit.bytes, hit.records, hit.parse_ms, hit.total_ms);
+            }
+            continue;
+        }
+        *REPORT_CAPTURE.lock().unwrap() = signature.map(|_| String::new());
+        let cache_entry: Option<CachedReport>;
+
+        let pod = PodMetadata::from_path(file_path);
+        emit_rules.source_fields = pod.as_ref().map(PodMetadata::fields).unwrap_or_default();
+
+        let mut peek_buf = vec![0u8; 4096.min(file_size)];
+        if !piped {
+            use std::io::Read;
+            let _ = File::open(file_path).and_then(|mut f| f.read(&mut peek_buf));
+        }
+        // Compressed and piped input can only be read once: its decoded head
+        // is kept for detection and replayed ahead of the rest.
+        let compression = Compression::detect(&peek_buf);
+        let mut decoder = match (piped || compression.is_some())
+            .then(|| file.try_clone())
+            .transpose()
+        {
+            Ok(source) => source.map(Decompressor::new),
+            Err(e) => {
+                error!("Cannot read '{}': {}", file_path, e);
+                continue;
+            }
+        };
+        let mut replay = None;
+        if let Some(decoder) = &mut decoder {
+            use std::io::Read;
+            let mut head = Vec::new();
+            let _ = decoder
+                .take(structured_orchestrator::SCHEMA_SAMPLE_BYTES as u64)
+                .read_to_end(&mut head);
+            if head.is_empty() {
+                reportln!("{} is empty. Nothing to parse.", file_path);
+                continue;
+            }
+            peek_buf = head[..head.len().min(4096)].to_vec();
+            replay = Some(head);
+        }
+        if let Some(member) = decoder.as_ref().and_then(|d| d.members().first()) {
+            info!(
+                "{}: {} compressed, decompressing",
+                file_path,
+                member.compression.as_str()
+            );
+        }
+        // Record IDs hash the head before transcoding, which appends do not
+        // change once the file is past it.
+        if let Some(id) = &mut emit_rules.record_id {
+            id.source = Xxh64::oneshot(&peek_buf);
+        }
+        let encoding = Encoding::detect(&peek_buf);
+        let transcoding = encoding.needs_transcoding();
+        if transcoding {
+            info!(
+                "{}: {} encoding, transcoding to UTF-8",
+                file_path,
+                encoding.as_str()
+            );
+            peek_buf = encoding::transcode(&peek_buf, encoding);
+        }
+        let mut detected_format = format_hint.unwrap_or_else(|| LogFormat::detect(&peek_buf));
+        if format_hint.is_none() {
+            let mut head = replay.clone().unwrap_or_else(|| peek_buf.clone());
+            if !transcoding && replay.is_none() && file_size > head.len() {
+                use std::io::Read;
+                head = Vec::new();
+                let _ = File::open(file_path).and_then(|f| {
+                    f.take(structured_orchestrator::SCHEMA_SAMPLE_BYTES as u64)
+                        .read_to_end(&mut head)
+                });
+            }
+            if strip_ansi && let Some(clean) = ansi::strip(&head) {
+                head = clean;
+            }
+            if let Some(format) = structured_orchestrator::redetect_format(&head, detected_format) {
+                warn!(
+                    "{}: most of the first records do not parse as {}, re-detected as {}",
+                    file_path, detected_format, format
+                );
+                detected_format = format;
+            }
+        }
+        let mode_str = if let Some(decoder) = &decoder {
+            match decoder.members().first() {
+                Some(member) if member.compression == Compression::Gzip => "streaming (gzip)",
+                Some(_) => "streaming (zstd)",
+                None => "streaming (piped)",
+            }
+        } else if transcoding {
+            "streaming (transcoded)"
+        } else {
+            mode_str
+        };
+
+        info!(
+            "{}: {} format ({})",
+            file_path,
+            detected_format,
+            if format_hint.is_some() {
+                "forced"
+            } else {
+                "detected"
+            }
+        );
+        let is_structured = detected_format != LogFormat::PlainText;
+        if firehose && !is_structured {
+            warn!("{}: --firehose only applies to structured input", file_path);
+        }
+        let file_extract = extract
+            .as_ref()
+            .filter(|_| !transcoding && decoder.is_none());
+        if extract.is_some() && transcoding {
+            warn!(
+                "{}: transcoded input has no matching bytes, not extracting from it",
+                file_path
+            );
+        } else if extract.is_some() && decoder.is_some() {
+            warn!(
+                "{}: decompressed or piped input cannot be re-read, not extracting from it",
+                file_path
+            );
+        }
+        if emit_format == EmitFormat::Offsets && transcoding {
+            warn!(
+                "{}: transcoded input has no matching offsets, not emitting them",
+                file_path
+            );
+        }
+
+        // The sweep runs on the first file only; its pick holds for the rest.
+        if auto_threads && !transcoding && decoder.is_none() {
+            auto_threads = false;
+            if let Ok(mmap) = unsafe { Mmap::map(&file) } {
+                let sweep = bench::sweep_threads(&mmap, detected_format, max_threads);
+                for (threads, gbps) in &sweep.samples {
+                    debug!("--threads auto: {} threads, {:.2} GB/s", threads, gbps);
+                }
+                info!("--threads auto: using {} threads", sweep.best);
+                num_threads = sweep.best;
+            }
+        }
+        // Calibration too runs on the first file only.
+        if simd_auto {
+            simd_auto = false;
+            let mapped = (!transcoding && decoder.is_none())
+                .then(|| unsafe { Mmap::map(&file) }.ok())
+                .flatten();
+            let head = mapped.as_deref().or(replay.as_deref()).unwrap_or(&peek_buf);
+            let head = &head[..head.len().min(simd_scan::CALIBRATION_BYTES)];
+            match simd_scan::calibrate(head) {
+                Some(calibration) => {
+                    let kernel = calibration.faster();
+                    simd_scan::set_kernel(kernel, KernelPick::Calibrated);
+                    debug!(
+                        "--simd auto: avx512 {:.2} GB/s, avx2 {:.2} GB/s",
+                        calibration.avx512_gbps, calibration.avx2_gbps
+                    );
+                    if kernel == Kernel::Avx2 {
+                        warn!(
+                            "AVX-512 scans ran at {:.2} GB/s against {:.2} GB/s for AVX2 on this CPU, likely from downclocking; using AVX2",
+                            calibration.avx512_gbps, calibration.avx2_gbps
+                        );
+                    } else {
+                        info!("--simd auto: using avx512");
+                    }
+                }
+                None => debug!(
+                    "--simd auto: nothing to calibrate, using {}",
+                    simd_scan::active_kernel().name()
+                ),
+            }
+        }
+
+        reportln!();
+        reportln!("╔════════════════════════════════════════════════════╗");
+        reportln!("       PANDORA'S LOGS — SIMD Log Parser             ");
+        reportln!("╠════════════════════════════════════════════════════╣");
+        reportln!("  SIMD:   {:<42} ", simd_scan::simd_capability());
+        reportln!("  Threads:{:<42} ", num_threads);
+        reportln!("  Pinned: {:<42} ", pin_str);
+        reportln!("  Mode:   {:<42} ", mode_str);
+        reportln!("  Format: {:<42} ", detected_format);
+        if transcoding {
+            reportln!("  Encoding:{:<41} ", encoding.as_str());
+        }
+        reportln!("  File:   {:<42} ", file_path);
+        if let Some(pod) = &pod {
+            reportln!(
+                "  Pod:    {:<42} ",
+                format!("{}/{} ({})", pod.namespace, pod.pod, pod.container)
+            );
+        }
+        reportln!("╚════════════════════════════════════════════════════╝");
+        reportln!();
+        reportln!(
+            "File size: {:.2} GB ({} bytes)",
+            file_size as f64 / (1024.0 * 1024.0 * 1024.0),
+            file_size
+        );
+
+        reportln!(
+            "\nFused Pipeline: Scan+Parse ({} threads, {} MB chunks, {}, {})...",
+            num_threads,
+            chunk_mb,
+            mode_str,
+            detected_format
+        );
+
+        let new_limit = || remaining.map_or_else(MatchLimit::unlimited, MatchLimit::new);
+        let mmap_holder = if use_mmap && decoder.is_some() {
+            warn!(
+                "'{}' is compressed or piped, streaming it instead of --mmap",
+                file_path
+            );
+            None
+        } else if use_mmap && transcoding {
+            warn!(
+                "'{}' is {} and must be transcoded, streaming it instead of --mmap",
+                file_path,
+                encoding.as_str()
+            );
+            None
+        } else if use_mmap {
+            map_input(&file, mmap_populate, hugepages)
+                .inspect_err(|e| {
+                    warn!(
+                        "Cannot memory-map '{}' ({}), streaming it instead",
+                        file_path, e
+                    )
+                })
+                .ok()
+        } else {
+            None
+        };
+        if let Some(mmap) = &mmap_holder {
+            sigbus::watch(mmap);
+        }
+        let time_index = match (&mmap_holder, since) {
+            (Some(mmap), Some(_)) if use_index => {
+                Some(load_time_index(file_path, mmap, detected_format))
+            }
+            _ => None,
+        };
+        let readahead = readahead_mb
+            .filter(|_| mmap_holder.is_some())
+            .map(|mb| Readahead::new(&file, mb * 1024 * 1024));
+        // Decompressed or piped input, head first, transcoded if need be.
+        let mut reader: Option<Box<dyn std::io::Read + '_>> =
+            decoder.as_mut().zip(replay).map(|(decoder, head)| {
+                let input = std::io::Read::chain(io::Cursor::new(head), decoder);
+                if transcoding {
+                    Box::new(Transcoder::new(input, encoding)) as Box<dyn std::io::Read>
+                } else {
+                    Box::new(input)
+                }
+            });
+
+        let total_start = Instant::now();
+        let faults_start = PageFaults::current();
+        let sink_start = tee.busy_time();
+
+        if is_structured {
+            let aggregate = |batch: &StructuredBatch, matched: &[u32]| {
+                if let Some(duplicates) = &duplicates {
+                    duplicates.add_records(batch, matched);
+                }
+                if let Some(group_by) = &group_by {
+                    group_by.add_records(batch, matched);
+                }
+                if let Some(sizes) = &sizes {
+                    sizes.add_records(batch, matched);
+                }
+                if let Some(schemas) = &schemas {
+                    schemas.add_records(batch, matched);
+                }
+                if let Some(secrets) = &secrets {
+                    secrets.add_records(batch, matched);
+                }
+                if let Some(http) = &http {
+                    http.add_records(batch, matched);
+                }
+                if let Some(triage) = &triage {
+                    triage.add_records(batch, matched);
+                }
+                if let Some(extract) = file_extract {
+                    extract.add_records(batch, matched);
+                }
+                if let Some(windows) = &windows
+                    && following.load(Ordering::Relaxed)
+                {
+                    windows.add_records(batch, matched, unix_now());
+                }
+                if let Some(timeline) = &timeline {
+                    timeline.add_records(batch, matched);
+                }
+                if let Some(rules) = &metric_rules {
+                    rules.add_records(batch, matched);
+                }
+            };
+            let serialize_ns = AtomicU64::new(0);
+            let serialize = |batch: &StructuredBatch, matched: &[u32]| {
+                let start = Instant::now();
+                if let Some(digest) = &digest {
+                    digest.add_records(batch, matched);
+                } else if let Some(sorter) = &sorter {
+                    sorter.add_records(
+                        batch,
+                        matched,
+                        &emit_rules,
+                        emit_format,
+                        split_key.as_ref(),
+                    );
+                } else {
+                    emit_batch(
+                        &tee,
+                        reorder.as_ref(),
+                        batch,
+                        matched,
+                        &emit_rules,
+                        emit_format,
+                        split_key.as_ref(),
+                    );
+                }
+                serialize_ns.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            };
+            let on_emit: Option<&BatchCallback<StructuredBatch>> =
+                (!tee.is_empty() || digest.is_some()).then_some(&serialize);
+            let on_batch: Option<&BatchCallback<StructuredBatch>> = (duplicates.is_some()
+                || group_by.is_some()
+                || sizes.is_some()
+                || schemas.is_some()
+                || secrets.is_some()
+                || http.is_some()
+                || triage.is_some()
+                || file_extract.is_some()
+                || windows.is_some()
+                || timeline.is_some()
+                || metric_rules.is_some())
+            .then_some(&aggregate);
+            let matches_where = |batch: &StructuredBatch, i: usize| {
+                where_expr
+                    .as_ref()
+                    .is_none_or(|filter| expr::record_matches(batch, i, filter, &emit_rules.derive))
+            };
+            let predicate: Option<&RecordPredicate<StructuredBatch>> =
+                where_expr.is_some().then_some(&matches_where);
+            let control = MatchControl {
+                level: level_filter,
+                since,
+                index: time_index.as_ref(),
+                predicate,
+                sample,
+                record_limits,
+                readahead,
+                strip_ansi,
+                transcoded: transcoding,
+                hot_columns,
+                fixed_layout: fixed_layout.as_ref(),
+                firehose,
+                tag_schemas: schemas.is_some() || emit_rules.schema_id.is_some(),
+                file_id: file_id as u32,
+                line_numbers: emit_rules.provenance.is_some(),
+                progress: None,
+                ordered: reorder.as_ref(),
+                retain_batches: tee.is_empty() || check_ordering || gap_threshold.is_some(),
+                limit: new_limit(),
+                on_match: None,
+                on_batch,
+                on_emit,
+                serialize_threads: serialize_threads.unwrap_or(num_threads.div_ceil(4)),
+                on_reject,
+                reverse,
+            };
+            let result = match (&mmap_holder, reader.as_mut()) {
+                (Some(mmap), _) => structured_orchestrator::parse_structured_mmap_with(
+                    mmap,
+                    num_threads,
+                    Some(detected_format),
+                    &control,
+                ),
+                (None, Some(reader)) => structured_orchestrator::parse_structured_read_with(
+                    reader,
+                    num_threads,
+                    Some(detected_format),
+                    &control,
+                ),
+                (None, None) if transcoding => structured_orchestrator::parse_structured_read_with(
+                    &mut Transcoder::new(&file, encoding),
+                    num_threads,
+                    Some(detected_format),
+                    &control,
+                ),
+                (None, None) => structured_orchestrator::parse_structured_streamed_with(
+                    &mut file,
+                    file_size as u64,
+                    num_threads,
+                    Some(detected_format),
+                    &control,
+                ),
+            };
+            drop(reader);
+            if let Some(reorder) = &reorder {
+                reorder.finish();
+            }
+            if let Some(decoder) = &decoder {
+                report_members(file_path, decoder);
+            } else {
+                report_changes(
+                    file_path,
+                    &file,
+                    &opened,
+                    mmap_holder.is_some(),
+                    result.parsed_bytes,
+                );
+            }
+            if let Some(batch) = result.batches.first()
+                && !batch.columns.is_empty()
+            {
+                let names: Vec<_> = batch
+                    .columns
+                    .iter()
+                    .map(|c| String::from_utf8_lossy(&c.name))
+                    .collect();
+                debug!("{}: value columns for {}", file_path, names.join(", "));
+            }
+
+            let total_elapsed = total_start.elapsed();
+            let total_ms = total_elapsed.as_secs_f64() * 1000.0;
+            let interrupted = shutdown::interrupted();
+            let parsed_size = if interrupted {
+                result.parsed_bytes
+            } else {
+                decoder
+                    .as_ref()
+                    .map_or(file_size as u64, Decompressor::offset)
+            };
+            let throughput =
+                (parsed_size as f64 / (1024.0 * 1024.0 * 1024.0)) / total_elapsed.as_secs_f64();
+
+            report_failed_chunks(file_path, &result.failed_chunks);
+            reportln!(
+                "  Processed {} records ({} fields) in {:.1} ms ({:.2} GB/s)",
+                result.total_records,
+                result.total_fields,
+                total_ms,
+                throughput
+            );
+
+            reportln!();
+            let stats = structured::StructuredParseStats {
+                total_bytes: parsed_size,
+                total_records: result.total_records as u64,
+                total_fields: result.total_fields as u64,
+                distinct_keys: result.distinct_keys,
+                scan_time_ms: result.scan_time_ms,
+                parse_time_ms: result.parse_time_ms,
+                serialize_time_ms: serialize_ns.load(Ordering::Relaxed) as f64 / 1e6,
+                sink_time_ms: tee.busy_time().saturating_sub(sink_start).as_secs_f64() * 1000.0,
+                total_time_ms: total_ms,
+                threads_used: num_threads,
+                simd: simd_scan::kernel_choice(),
+                format: detected_format.as_str(),
+                levels: result.level_summary,
+                time_range: result.time_range,
+                malformed_lines: result.malformed_lines,
+                hole_bytes: result.hole_bytes,
+                failed_chunks: result.failed_chunks.clone(),
+                page_faults: PageFaults::current().since(faults_start),
+                guard: result.guard,
+                empty_policy: record_limits.empty,
+            };
+            report!("{}", stats);
+            if interrupted {
+                print_partial_marker(parsed_size, file_size as u64);
+            }
+
+            print_match_summary(&control);
+            remaining = remaining.map(|n| n - control.limit.matched());
+            breakdown.record(
+                detected_format,
+                parsed_size,
+                result.total_records as u64,
+                result.parse_time_ms,
+                total_ms,
+            );
+            cache_entry = (!interrupted).then(|| CachedReport {
+                format: detected_format.as_str().to_string(),
+                bytes: parsed_size,
+                records: result.total_records as u64,
+                matched: control.limit.matched(),
+                parse_ms: result.parse_time_ms,
+                total_ms,
+                text: String::new(),
+            });
+
+            if let Some(offset) = stats.guard.first_error {
+                guard_error = Some((file_path, offset));
+                break;
+            }
+
+            if check_ordering {
+                print_ordering_report(&result.batches, reverse);
+            }
+            if let Some(threshold) = gap_threshold {
+                print_gap_report(&result.batches, reverse, threshold);
+            }
+
+            let mut samples = Vec::with_capacity(10);
+            for batch in &result.batches {
+                if let Some(filter) = level_filter
+                    && filter.count_in(&batch.level_summary) == 0
+                {
+                    continue;
+                }
+                for k in 0..batch.len {
+                    let i = if reverse { batch.len - 1 - k } else { k };
+                    if control.matches(batch, i) {
+                        samples.push((batch, i));
+                        if samples.len() == 10 {
+                            break;
+                        }
+                    }
+                }
+                if samples.len() == 10 {
+                    break;
+                }
+            }
+
+            if !samples.is_empty() {
+                reportln!("\nSample structured records:");
+                reportln!(
+                    "─────────────────────────────────────────────────────────────────────────"
+                );
+                for (n, (batch, i)) in samples.into_iter().enumerate() {
+                    unsafe {
+                        let ts = batch.timestamp_value(i).unwrap_or("-");
+                        let lvl = batch.level_value(i).unwrap_or("-");
+                        let comp = batch.component_value(i).unwrap_or("-");
+                        let msg = batch.message_value(i).unwrap_or("-");
+                        let field_count = batch.field_count(i);
+
+                        reportln!(
+                            "  [{:>4}] {} | {:>7} | {:>20} | {} ({} fields)",
+                            n,
+                            truncate_str(ts, 24),
+                            truncate_str(lvl, 7),
+                            truncate_str(comp, 20),
+                            truncate_str(msg, 40),
+                            field_count
+                        );
+                    }
+                }
+                reportln!(
+                    "─────────────────────────────────────────────────────────────────────────"
+                );
+            }
+
+            reportln!(
+                "\nParsed {} structured records at {:.2} GB/s\n",
+                result.total_records,
+                stats.throughput_gbps()
+            );
+
+            if follow_path == Some(file_path) {
+                if detected_format == LogFormat::Csv {
+                    warn!("--follow does not support csv input");
+                } else if fixed_record_len.is_some() {
+                    warn!("--follow does not support --fixed-record-len input");
+                } else {
+                    following.store(true, Ordering::Relaxed);
+                    follow_file(
+                        file_path,
+                        &file,
+                        opened.len,
+                        follow_interval,
+                        &follow_tick,
+                        |lines| {
+                            structured_orchestrator::parse_structured_mmap_with(
+                                lines,
+                                num_threads,
+                                Some(detected_format),
+                                &control,
+                            );
+                            !control.limit.is_reached()
+                        },
+                    );
+                }
+            }
+        } else {
+            let aggregate = |batch: &LogBatch, matched: &[u32]| {
+                if let Some(duplicates) = &duplicates {
+                    duplicates.add_records(batch, matched);
+                }
+                if let Some(group_by) = &group_by {
+                    group_by.add_records(batch, matched);
+                }
+                if let Some(sizes) = &sizes {
+                    sizes.add_records(batch, matched);
+                }
+                if let Some(schemas) = &schemas {
+                    schemas.add_records(batch, matched);
+                }
+                if let Some(secrets) = &secrets {
+                    secrets.add_records(batch, matched);
+                }
+                if let Some(http) = &http {
+                    http.add_records(batch, matched);
+                }
+                if let Some(triage) = &triage {
+                    triage.add_records(batch, matched);
+                }
+                if let Some(extract) = file_extract {
+                    extract.add_records(batch, matched);
+                }
+                if let Some(windows) = &windows
+                    && following.load(Ordering::Relaxed)
+                {
+                    windows.add_records(batch, matched, unix_now());
+                }
+                if let Some(timeline) = &timeline {
+                    timeline.add_records(batch, matched);
+                }
+                if let Some(rules) = &metric_rules {
+                    rules.add_records(batch, matched);
+                }
+            };
+            let serialize_ns = AtomicU64::new(0);
+            let serialize = |batch: &LogBatch, matched: &[u32]| {
+                let start = Instant::now();
+                if let Some(digest) = &digest {
+                    digest.add_records(batch, matched);
+                } else if let Some(sorter) = &sorter {
+                    sorter.add_records(
+                        batch,
+                        matched,
+                        &emit_rules,
+                        emit_format,
+                        split_key.as_ref(),
+                    );
+                } else {
+                    emit_batch(
+                        &tee,
+                        reorder.as_ref(),
+                        batch,
+                        matched,
+                        &emit_rules,
+                        emit_format,
+                        split_key.as_ref(),
+                    );
+                }
+                serialize_ns.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            };
+            let on_emit: Option<&BatchCallback<LogBatch>> =
+                (!tee.is_empty() || digest.is_some()).then_some(&serialize);
+            let on_batch: Option<&BatchCallback<LogBatch>> = (duplicates.is_some()
+                || group_by.is_some()
+                || sizes.is_some()
+                || schemas.is_some()
+                || secrets.is_some()
+                || http.is_some()
+                || triage.is_some()
+                || file_extract.is_some()
+                || windows.is_some()
+                || timeline.is_some()
+                || metric_rules.is_some())
+            .then_some(&aggregate);
+            let matches_where = |batch: &LogBatch, i: usize| {
+                where_expr
+                    .as_ref()
+                    .is_none_or(|filter| expr::record_matches(batch, i, filter, &emit_rules.derive))
+            };
+            let predicate: Option<&RecordPredicate<LogBatch>> =
+                where_expr.is_some().then_some(&matches_where);
+            let control = MatchControl {
+                level: level_filter,
+                since,
+                index: time_index.as_ref(),
+                predicate,
+                sample,
+                record_limits,
+                readahead,
+                strip_ansi,
+                transcoded: transcoding,
+                hot_columns,
+                fixed_layout: None,
+                firehose: false,
+                tag_schemas: false,
+                file_id: file_id as u32,
+                line_numbers: emit_rules.provenance.is_some(),
+                progress: None,
+                ordered: reorder.as_ref(),
+                retain_batches: tee.is_empty() || check_ordering || gap_threshold.is_some(),
+                limit: new_limit(),
+                on_match: None,
+                on_batch,
+                on_emit,
+                serialize_threads: serialize_threads.unwrap_or(num_threads.div_ceil(4)),
+                on_reject,
+                reverse,
+            };
+            let result = match (&mmap_holder, reader.as_mut()) {
+                (Some(mmap), _) => {
+                    orchestrator::parse_logs_pipelined_with(mmap, num_threads, &control)
+                }
+                (None, Some(reader)) => {
+                    orchestrator::parse_logs_read_with(reader, num_threads, &control)
+                }
+                (None, None) if transcoding => orchestrator::parse_logs_read_with(
+                    &mut Transcoder::new(&file, encoding),
+                    num_threads,
+                    &control,
+                ),
+                (None, None) => orchestrator::parse_logs_streamed_with(
+                    &mut file,
+                    file_size as u64,
+                    num_threads,
+                    &control,
+                ),
+            };
+            drop(reader);
+            if let Some(reorder) = &reorder {
+                reorder.finish();
+            }
+            if let Some(decoder) = &decoder {
+                report_members(file_path, decoder);
+            } else {
+                report_changes(
+                    file_path,
+                    &file,
+                    &opened,
+                    mmap_holder.is_some(),
+                    result.parsed_bytes,
+                );
+            }
+
+            let total_elapsed = total_start.elapsed();
+            let total_ms = total_elapsed.as_secs_f64() * 1000.0;
+
+            let num_lines = result.total_lines;
+            let interrupted = shutdown::interrupted();
+            let parsed_size = if interrupted {
+                result.parsed_bytes
+            } else {
+                decoder
+                    .as_ref()
+                    .map_or(file_size as u64, Decompressor::offset)
+            };
+            let throughput =
+                (parsed_size as f64 / (1024.0 * 1024.0 * 1024.0)) / total_elapsed.as_secs_f64();
+            report_failed_chunks(file_path, &result.failed_chunks);
+            reportln!(
+                "  Processed {} lines in {:.1} ms ({:.2} GB/s)",
+                num_lines,
+                total_ms,
+                throughput
+            );
+
+            reportln!();
+            let stats = ParseStats {
+                total_bytes: parsed_size,
+                total_lines: num_lines as u64,
+                scan_time_ms: result.scan_time_ms,
+                parse_time_ms: result.parse_time_ms,
+                serialize_time_ms: serialize_ns.load(Ordering::Relaxed) as f64 / 1e6,
+                sink_time_ms: tee.busy_time().saturating_sub(sink_start).as_secs_f64() * 1000.0,
+                total_time_ms: total_ms,
+                threads_used: num_threads,
+                simd: simd_scan::kernel_choice(),
+                levels: result.level_summary,
+                time_range: result.time_range,
+                malformed_lines: result.malformed_lines,
+                hole_bytes: result.hole_bytes,
+                failed_chunks: result.failed_chunks.clone(),
+                page_faults: PageFaults::current().since(faults_start),
+            };
+            report!("{}", stats);
+            if interrupted {
+                print_partial_marker(parsed_size, file_size as u64);
+            }
+
+            print_match_summary(&control);
+            remaining = remaining.map(|n| n - control.limit.matched());
+            breakdown.record(
+                detected_format,
+                parsed_size,
+                num_lines as u64,
+                result.parse_time_ms,
+                total_ms,
+            );
+            cache_entry = (!interrupted).then(|| CachedReport {
+                format: detected_format.as_str().to_string(),
+                bytes: parsed_size,
+                records: num_lines as u64,
+                matched: control.limit.matched(),
+                parse_ms: result.parse_time_ms,
+                total_ms,
+                text: String::new(),
+            });
+
+            if check_ordering {
+                print_ordering_report(&result.batches, reverse);
+            }
+            if let Some(threshold) = gap_threshold {
+                print_gap_report(&result.batches, reverse, threshold);
+            }
+
+            let mut samples = Vec::with_capacity(10);
+            for batch in &result.batches {
+                if let Some(filter) = level_filter
+                    && filter.count_in(&batch.level_summary) == 0
+                {
+                    continue;
+                }
+                for k in 0..batch.len {
+                    let i = if reverse { batch.len - 1 - k } else { k };
+                    if control.matches(batch, i) {
+                        samples.push((batch, i));
+                        if samples.len() == 10 {
+                            break;
+                        }
+                    }
+                }
+                if samples.len() == 10 {
+                    break;
+                }
+            }
+
+            if !samples.is_empty() {
+                reportln!("\nSample log records:");
+                reportln!(
+                    "─────────────────────────────────────────────────────────────────────────"
+                );
+                for (n, (batch, i)) in samples.into_iter().enumerate() {
+                    unsafe {
+                        reportln!(
+                            "  [{:>4}] {} | {:>7} | {:>20} | {}",
+                            n,
+                            batch.timestamps[i],
+                            batch.levels[i],
+                            batch.component(i),
+                            truncate_str(batch.message(i), 60)
+                        );
+                    }
+                }
+                reportln!(
+                    "─────────────────────────────────────────────────────────────────────────"
+                );
+            }
+
+            reportln!(
+                "\nParsed {} log records at {:.2} GB/s\n",
+                num_lines,
+                stats.throughput_gbps()
+            );
 
-    let mut file_path: Option<&str> = None;
-    let mut num_threads = default_threads;
-    let mut use_mmap = false;
-    let mut format_hint: Option<LogFormat> = None;
+            if follow_path == Some(file_path) {
+                following.store(true, Ordering::Relaxed);
+                follow_file(
+                    file_path,
+                    &file,
+                    opened.len,
+                    follow_interval,
+                    &follow_tick,
+                    |lines| {
+                        orchestrator::parse_logs_pipelined_with(lines, num_threads, &control);
+                        !control.limit.is_reached()
+                    },
+                );
+            }
+        }
 
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--mmap" => {
-                use_mmap = true;
+        if let Some(extract) = file_extract
+            && let Err(e) = extract.copy_regions(file_path, &file)
+        {
+            error!("Cannot extract matched bytes of '{}': {}", file_path, e);
+        }
+
+        // A file that changed while it was parsed would be cached under a
+        // signature its report does not describe.
+        let text = REPORT_CAPTURE.lock().unwrap().take();
+        if let (Some(cache), Some(sig), Some(mut entry), Some(text)) =
+            (&query_cache, signature, cache_entry, text)
+            && FileSignature::of(file_path).is_ok_and(|now| now == sig)
+        {
+            entry.text = text;
+            if let Err(e) = cache.put(&sig, &query, &entry) {
+                warn!("Cannot write the query cache: {}", e);
             }
-            "--format" => {
-                i += 1;
-                if i < args.len() {
-                    format_hint = match args[i].as_str() {
-                        "json" | "ndjson" | "jsonl" => Some(LogFormat::Json),
-                        "logfmt" => Some(LogFormat::Logfmt),
-                        "csv" => Some(LogFormat::Csv),
-                        "plain" | "text" | "plain-text" => Some(LogFormat::PlainText),
-                        "auto" => None,
-                        other => {
-                            eprintln!("Unknown format '{}', using auto-detect", other);
-                            None
-                        }
-                    };
+        }
+    }
+    *REPORT_CAPTURE.lock().unwrap() = None;
+
+    if let Some(duplicates) = &duplicates {
+        let mut report = duplicates.report();
+        if let Some(having) = &having {
+            report.retain(having);
+        }
+        report!("\n{}", report);
+    }
+    if let Some(group_by) = &group_by {
+        let mut report = group_by.report();
+        if let Some(having) = &having {
+            report.retain(having);
+        }
+        report!("\n{}", report);
+    }
+    if let Some(sizes) = &sizes {
+        report!("\n{}", sizes.report());
+    }
+    if let Some(schemas) = &schemas {
+        report!("\n{}", schemas.report());
+    }
+    if let Some(secrets) = &secrets {
+        report!("\n{}", secrets.report());
+    }
+    if let Some(http) = &http {
+        report!("\n{}", http.report());
+    }
+    if let Some(triage) = &triage {
+        report!("\n{}", triage.report());
+    }
+    if file_paths.len() > 1 {
+        report!("\nPer-format breakdown:\n{}", breakdown);
+    }
+    if let Some(rules) = &metric_rules {
+        let text = rules.exposition();
+        match &metric_out {
+            Some(path) => {
+                if let Err(e) = std::fs::write(path, text) {
+                    error!("Cannot write metrics to '{}': {}", path, e);
                 }
             }
-            arg => {
-                if file_path.is_none() {
-                    file_path = Some(arg);
-                } else if let Ok(n) = arg.parse::<usize>() {
-                    num_threads = n;
-                } else {
-                    eprintln!("Invalid argument: '{}', ignoring", arg);
-                }
+            None => report!("\n{}", text),
+        }
+    }
+    if let (Some(remote), Some(tee), Some(timeline)) = (&remote_write, &remote_tee, &timeline) {
+        push_timeline(remote, tee, timeline, u64::MAX);
+        if timeline.late() > 0 {
+            warn!(
+                "{} records arrived after their --remote-write step was pushed and were dropped",
+                timeline.late()
+            );
+        }
+    }
+
+    if let Some(digest) = &digest {
+        if tee.is_empty() {
+            print!("{}", String::from_utf8_lossy(&digest.to_ndjson()));
+        } else {
+            tee.send(EmitChunk {
+                ndjson: digest.to_ndjson(),
+                ..EmitChunk::default()
+            });
+        }
+    }
+    if let Some(sorter) = sorter {
+        match sorter.finish(&tee) {
+            Ok(summary) => info!(
+                "Sorted {} records by time ({} runs spilled)",
+                summary.records, summary.runs
+            ),
+            Err(e) => error!("--sort-time failed: {}", e),
+        }
+    }
+    drop(reorder);
+    *parse_totals.lock().unwrap() = breakdown.clone();
+    let remote_reports = remote_tee.map_or_else(Vec::new, Tee::finish);
+    for report in tee.finish().into_iter().chain(remote_reports) {
+        match report.error {
+            Some(e) => error!("Sink {} failed: {}", report.name, e),
+            None if report.chunks_dropped > 0 => warn!(
+                "Sink {} fell behind: dropped {} of {} chunks",
+                report.name,
+                report.chunks_dropped,
+                report.chunks_dropped + report.chunks_written
+            ),
+            None => {}
+        }
+        if report.retries > 0 {
+            info!("Sink {} retried {} writes", report.name, report.retries);
+        }
+        debug!(
+            "Sink {} spent {:.1} ms writing {} chunks",
+            report.name,
+            report.busy.as_secs_f64() * 1000.0,
+            report.chunks_written
+        );
+        if let Some(path) = &report.dead_letter_path
+            && report.dead_lettered > 0
+        {
+            warn!(
+                "Sink {} rejected {} chunks; they were written to '{}'",
+                report.name, report.dead_lettered, path
+            );
+        }
+    }
+
+    for (field, failures) in emit_rules.coercion_failures() {
+        warn!(
+            "Type coercion failed for {} values of '{}' (written as null)",
+            failures,
+            String::from_utf8_lossy(field)
+        );
+    }
+
+    if let (Some(extract), Some(path)) = (extract, extract_path) {
+        let manifest_path = format!("{}.manifest.json", path);
+        let summary =
+            File::create(&manifest_path).and_then(|mut manifest| extract.finish(&mut manifest));
+        match summary {
+            Ok(summary) => reportln!(
+                "Extracted {} bytes of {} records in {} regions to {} ({})",
+                summary.bytes,
+                summary.records,
+                summary.regions,
+                path,
+                manifest_path
+            ),
+            Err(e) => error!("Cannot write extract '{}': {}", path, e),
+        }
+    }
+
+    if let (Some(rejects), Some(path)) = (rejects, rejects_path) {
+        let (written, failed) = (rejects.written(), rejects.failed());
+        if let Err(e) = rejects.finish() {
+            error!("Cannot write rejects file '{}': {}", path, e);
+        } else if failed > 0 {
+            error!("Failed to write {} malformed lines to '{}'", failed, path);
+        }
+        reportln!("Wrote {} malformed lines to {}", written, path);
+    }
+
+    if shutdown::interrupted() {
+        std::process::exit(130);
+    }
+
+    if simd_scan::verify_scan() {
+        match simd_scan::scan_mismatches() {
+            (0, _) => reportln!("Scan verified: every chunk matched its separator count"),
+            (mismatches, first) => {
+                error!(
+                    "--verify-scan: {} scanned chunks disagree with their separator count, the first at byte {}",
+                    mismatches,
+                    first.unwrap_or(0)
+                );
+                std::process::exit(1);
             }
         }
-        i += 1;
     }
 
-    let file_path = file_path.unwrap_or_else(|| {
-        eprintln!("Missing <file> argument");
+    if let Some((path, offset)) = guard_error {
+        error!(
+            "Record at byte {} of '{}' exceeds the record limits (--guard-policy error)",
+            offset, path
+        );
         std::process::exit(1);
-    });
-
-    let mode_str = if use_mmap { "mmap" } else { "streaming" };
+    }
+}
 
-    let file = File::open(file_path).unwrap_or_else(|e| {
-        eprintln!("Error opening '{}': {}", file_path, e);
+/// Replaces `--pipeline <file>` with the flags the file stands for, in
+/// place, so flags after it still override the file.
+fn expand_pipeline(args: Vec<String>) -> Vec<String> {
+    let Some(at) = args.iter().position(|a| a == "--pipeline") else {
+        return args;
+    };
+    let Some(path) = args.get(at + 1) else {
+        error!("--pipeline needs a file");
         std::process::exit(1);
-    });
+    };
+    let expanded = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| pipeline::pipeline_args(&text))
+        .unwrap_or_else(|e| {
+            error!("Invalid --pipeline '{}': {}", path, e);
+            std::process::exit(1);
+        });
+    let mut out = args[..at].to_vec();
+    out.extend(expanded);
+    out.extend_from_slice(&args[at + 2..]);
+    expand_pipeline(out)
+}
 
-    let file_size = file.metadata().unwrap().len() as usize;
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
 
-    if file_size == 0 {
-        println!("File is empty. Nothing to parse.");
+/// Queues the `--remote-write` steps that end by `until` on `tee`.
+fn push_timeline(remote: &RemoteWrite, tee: &Tee, timeline: &Timeline, until: u64) {
+    let steps = timeline.drain(until);
+    if steps.is_empty() {
         return;
     }
+    let requests = remote.requests(&steps, timeline.aggs());
+    let samples: u64 = requests.iter().map(|request| request.records).sum();
+    for request in requests {
+        tee.send(request);
+    }
+    info!("Pushing {} samples to {}", samples, remote.url());
+}
+
+/// `--follow`: hands whole lines appended to `path` after byte `offset` to
+/// `parse` and calls `tick` every `interval`, until Ctrl-C or `parse`
+/// returns false (the `--limit` is reached).
+fn follow_file(
+    path: &str,
+    file: &File,
+    offset: u64,
+    interval: Duration,
+    tick: &dyn Fn(),
+    mut parse: impl FnMut(&[u8]) -> bool,
+) {
+    const POLL: Duration = Duration::from_millis(250);
+
+    let mut follower = match file
+        .try_clone()
+        .and_then(|file| Follower::new(path, file, offset))
+    {
+        Ok(follower) => follower,
+        Err(e) => {
+            error!("Cannot follow '{}': {}", path, fds::explain(e));
+            return;
+        }
+    };
+    info!("Following '{}', Ctrl-C to stop", path);
+    let mut lines = Vec::new();
+    let mut next_report = Instant::now() + interval;
+    while !shutdown::interrupted() {
+        match follower.poll(&mut lines) {
+            Ok(FollowEvent::Appended) => {}
+            Ok(FollowEvent::Reopened) => info!("'{}' was rotated, following the new file", path),
+            Ok(FollowEvent::Truncated) => info!("'{}' was truncated, reading it again", path),
+            Err(e) => {
+                error!("Cannot follow '{}': {}", path, fds::explain(e));
+                break;
+            }
+        }
+        if !lines.is_empty() && !parse(&lines) {
+            break;
+        }
+        if Instant::now() >= next_report {
+            tick();
+            next_report += interval;
+        }
+        if lines.is_empty() {
+            std::thread::sleep(POLL);
+        }
+    }
+    tick();
+}
+
+/// Loads the file's `.pidx` and brings it up to date, indexing only what
+/// was appended when the file has just grown since the index was written.
+fn load_time_index(path: &str, data: &[u8], format: LogFormat) -> SparseIndex {
+    let index_path = match store::index_path(path) {
+        Ok(index_path) => index_path,
+        Err(e) => {
+            warn!("No place to keep an index of '{}': {}", path, e);
+            return SparseIndex::build(data, format);
+        }
+    };
+    // Held until the update is published: another process indexing the
+    // same file finishes first, and this one starts from its result.
+    let _lock = FileLock::exclusive(&index_path)
+        .inspect_err(|e| debug!("Cannot lock '{}': {}", index_path.display(), e))
+        .ok();
+    let mut index = SparseIndex::load(&index_path).unwrap_or_else(|| SparseIndex::new(format));
+    match index.update(data, format) {
+        IndexUpdate::Unchanged => {
+            debug!("{}: index is up to date", path);
+            return index;
+        }
+        IndexUpdate::Extended { from: 0 } => info!("{}: building index", path),
+        IndexUpdate::Extended { from } => info!("{}: indexing from byte {}", path, from),
+        IndexUpdate::Rebuilt => info!("{}: file was rewritten, rebuilding index", path),
+    }
+    if let Err(e) = index.save(&index_path) {
+        warn!("Cannot write index '{}': {}", index_path.display(), e);
+    }
+    index
+}
+
+/// Sends matched records to the sinks, one chunk per partition when
+/// splitting.
+/// Sends what `batch` emits to the sinks, or under `--ordered` holds it
+/// until the chunks before the batch's are done.
+fn emit_batch<B: EmitRecord>(
+    tee: &Tee,
+    ordered: Option<&Reorder<EmitChunk>>,
+    batch: &B,
+    matched: &[u32],
+    rules: &EmitRules,
+    format: EmitFormat,
+    split_key: Option<&SplitKey>,
+) {
+    let send = |chunk| match ordered {
+        Some(reorder) => reorder.push(batch.chunk_seq(), chunk),
+        None => tee.send(chunk),
+    };
+    match split_key {
+        Some(key) => split_chunks(batch, matched, rules, format, key)
+            .into_iter()
+            .for_each(send),
+        None => send(emit_chunk(batch, matched, rules, format)),
+    }
+}
 
-    let detected_format = if let Some(fmt) = format_hint {
-        fmt
+fn print_ordering_report<B: BatchRecords>(batches: &[B], reverse: bool) {
+    let report = if reverse {
+        OrderingReport::from_batches(batches.iter().rev())
     } else {
-        let mut peek_file = File::open(file_path).unwrap();
-        let mut peek_buf = vec![0u8; 4096.min(file_size)];
-        use std::io::Read;
-        let _ = peek_file.read(&mut peek_buf);
-        LogFormat::detect(&peek_buf)
+        OrderingReport::from_batches(batches)
     };
+    report!("\n{}", report);
+}
 
-    let is_structured = detected_format != LogFormat::PlainText;
-
-    println!();
-    println!("╔════════════════════════════════════════════════════╗");
-    println!("       PANDORA'S LOGS — SIMD Log Parser             ");
-    println!("╠════════════════════════════════════════════════════╣");
-    println!("  SIMD:   {:<42} ", simd_scan::simd_capability());
-    println!("  Threads:{:<42} ", num_threads);
-    println!("  Mode:   {:<42} ", mode_str);
-    println!("  Format: {:<42} ", detected_format);
-    println!("  File:   {:<42} ", file_path);
-    println!("╚════════════════════════════════════════════════════╝");
-    println!();
-    println!(
-        "File size: {:.2} GB ({} bytes)",
-        file_size as f64 / (1024.0 * 1024.0 * 1024.0),
-        file_size
-    );
+fn print_gap_report<B: BatchRecords>(batches: &[B], reverse: bool, threshold: u64) {
+    let report = if reverse {
+        GapReport::from_batches(batches.iter().rev(), threshold)
+    } else {
+        GapReport::from_batches(batches, threshold)
+    };
+    report!("\n{}", report);
+}
 
-    let chunk_mb = std::env::var("PANDORA_CHUNK_MB")
-        .ok()
-        .and_then(|v| v.parse::<usize>().ok())
-        .filter(|v| *v >= 1)
-        .unwrap_or(64);
+/// Warns when the input changed while it was parsed, e.g. appended to or
+/// rotated, saying which bytes the results cover. A mapped input truncated
+/// mid-run reads as zeros past the cut instead of raising SIGBUS; a streamed
+/// one that grew is read on to its new end.
+fn report_changes(path: &str, file: &File, opened: &FileIdentity, mapped: bool, parsed: u64) {
+    let fault = if mapped { sigbus::unwatch() } else { None };
+    if let Some(offset) = fault {
+        warn!(
+            "'{}' was truncated while mapped; results cover bytes 0-{}, later \
+             bytes read as zeros",
+            path, offset
+        );
+        return;
+    }
+    match FileChange::check(path, file, opened) {
+        Some(FileChange::Grew { covered, now }) if !mapped => warn!(
+            "'{}' grew from {} to {} bytes during the run; streaming read on, \
+             results cover bytes 0-{}",
+            path, covered, now, parsed
+        ),
+        Some(change) => warn!("'{}' {}", path, change),
+        None => {}
+    }
+}
 
-    println!(
-        "\nFused Pipeline: Scan+Parse ({} threads, {} MB chunks, {}, {})...",
-        num_threads, chunk_mb, mode_str, detected_format
-    );
+/// Warns about each chunk left out because its parse panicked.
+fn report_failed_chunks(path: &str, failed: &[FailedChunk]) {
+    for chunk in failed {
+        warn!(
+            "'{}': parsing bytes {}..{} panicked twice, skipping them",
+            path, chunk.start, chunk.end
+        );
+    }
+}
+
+/// Says how many members a compressed input had and whether decoding
+/// stopped before its end.
+fn report_members<R>(path: &str, decoder: &Decompressor<R>) {
+    let members = decoder.members();
+    if members.len() > 1 {
+        info!(
+            "{}: {} concatenated members, {} bytes decompressed",
+            path,
+            members.len(),
+            decoder.offset()
+        );
+    }
+    for member in members {
+        debug!(
+            "{}: {} member at byte {} starts output byte {}",
+            path,
+            member.compression.as_str(),
+            member.compressed_offset,
+            member.offset
+        );
+    }
+    if let Some(e) = decoder.error() {
+        warn!(
+            "'{}' stopped decompressing after {} bytes: {}",
+            path,
+            decoder.offset(),
+            e
+        );
+    } else if let Some(at) = decoder.trailing() {
+        warn!(
+            "'{}' has data at byte {} that starts no gzip or zstd member, ignoring it",
+            path, at
+        );
+    }
+}
 
-    let total_start = Instant::now();
+/// Maps an input for parsing. `populate` pre-faults the whole mapping up
+/// front (MAP_POPULATE) and `hugepages` asks for transparent hugepages; with
+/// both, the advice goes in first and MADV_POPULATE_READ does the faulting.
+fn map_input(file: &File, populate: bool, hugepages: bool) -> io::Result<Mmap> {
+    let mut options = MmapOptions::new();
+    if populate && !(hugepages && cfg!(target_os = "linux")) {
+        options.populate();
+    }
+    let mmap = unsafe { options.map(file)? };
 
-    if is_structured {
-        let mmap_holder;
-        let result = if use_mmap {
-            mmap_holder = Some(unsafe { Mmap::map(&file) }.unwrap_or_else(|e| {
-                eprintln!("Error memory-mapping '{}': {}", file_path, e);
-                std::process::exit(1);
-            }));
-            let mmap = mmap_holder.as_ref().unwrap();
-
-            #[cfg(unix)]
-            unsafe {
-                libc::madvise(
-                    mmap.as_ptr() as *mut libc::c_void,
-                    mmap.len(),
-                    libc::MADV_SEQUENTIAL,
+    #[cfg(unix)]
+    unsafe {
+        let ptr = mmap.as_ptr() as *mut libc::c_void;
+        libc::madvise(ptr, mmap.len(), libc::MADV_SEQUENTIAL);
+        #[cfg(target_os = "linux")]
+        if hugepages {
+            if libc::madvise(ptr, mmap.len(), libc::MADV_HUGEPAGE) != 0 {
+                warn!(
+                    "--hugepages: madvise failed: {}",
+                    io::Error::last_os_error()
+                );
+            }
+            if populate && libc::madvise(ptr, mmap.len(), libc::MADV_POPULATE_READ) != 0 {
+                warn!(
+                    "--mmap-populate: kernel cannot pre-fault ({}), faulting lazily",
+                    io::Error::last_os_error()
                 );
             }
+        }
+    }
+    Ok(mmap)
+}
 
-            structured_orchestrator::parse_structured_mmap(mmap, num_threads, format_hint)
-        } else {
-            mmap_holder = None;
-            let mut f = file;
-            structured_orchestrator::parse_structured_streamed(
-                &mut f,
-                file_size as u64,
-                num_threads,
-                format_hint,
-            )
-        };
-        let _ = &mmap_holder; // ensure mmap lives until here
+/// Online CPUs in our affinity mask, capped by the cgroup CPU quota so a
+/// CPU-limited container does not start more workers than it can run.
+fn default_parallelism(cpu_limit: Option<usize>) -> usize {
+    let cpus = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    cpu_limit.map_or(cpus, |limit| cpus.min(limit))
+}
 
-        let total_elapsed = total_start.elapsed();
-        let total_ms = total_elapsed.as_secs_f64() * 1000.0;
-        let throughput =
-            (file_size as f64 / (1024.0 * 1024.0 * 1024.0)) / total_elapsed.as_secs_f64();
+/// `bench` subcommand; returns the process exit code.
+/// `--workers`: one distributed parse per file; the coordinator parses
+/// ranges itself only when no worker can.
+fn run_distributed(file_paths: &[&str], workers: &[String], threads: usize) -> i32 {
+    let mut status = 0;
+    for &path in file_paths {
+        info!("{}: spreading over {} workers", path, workers.len());
+        match distributed::distribute(
+            std::path::Path::new(path),
+            workers,
+            distributed::RANGE_BYTES,
+            threads,
+        ) {
+            Ok(done) => {
+                if done.local_ranges > 0 {
+                    warn!(
+                        "{}: {} of {} ranges were parsed locally",
+                        path, done.local_ranges, done.ranges
+                    );
+                }
+                report!("{}", done);
+            }
+            Err(e) => {
+                error!("Cannot parse '{}': {}", path, e);
+                status = 1;
+            }
+        }
+    }
+    status
+}
 
-        println!(
-            "  Processed {} records ({} fields) in {:.1} ms ({:.2} GB/s)",
-            result.total_records, result.total_fields, total_ms, throughput
-        );
+fn run_worker(args: &[String]) -> i32 {
+    let mut listen = "127.0.0.1:7460".to_string();
+    let mut root = ".".to_string();
+    let mut threads = default_parallelism(cgroup::cpu_limit());
+    let mut max_fds = None;
 
-        println!();
-        let stats = structured::StructuredParseStats {
-            total_bytes: file_size as u64,
-            total_records: result.total_records as u64,
-            total_fields: result.total_fields as u64,
-            scan_time_ms: result.scan_time_ms,
-            parse_time_ms: result.parse_time_ms,
-            total_time_ms: total_ms,
-            threads_used: num_threads,
-            format: detected_format.as_str(),
-        };
-        print!("{}", stats);
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match (args[i].as_str(), value) {
+            ("--listen", Some(addr)) => listen = addr.clone(),
+            ("--max-fds", Some(text)) => match text.parse::<usize>() {
+                Ok(n) if n > 0 => max_fds = Some(n),
+                _ => {
+                    error!("Invalid --max-fds '{}'", text);
+                    return 1;
+                }
+            },
+            ("--root", Some(dir)) => root = dir.clone(),
+            ("--threads", Some(text)) => match text.parse::<usize>() {
+                Ok(n) if n > 0 => threads = n,
+                _ => warn!("Invalid --threads '{}', using {}", text, threads),
+            },
+            ("-v" | "--verbose", _) => {
+                diag::set_max_severity(diag::max_severity().more_verbose());
+                i += 1;
+                continue;
+            }
+            (arg, _) => {
+                warn!("Ignoring unknown worker option '{}'", arg);
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
 
-        if let Some(first_batch) = result.batches.first() {
-            let sample_count = first_batch.len.min(10);
-            if sample_count > 0 {
-                println!("\nSample structured records:");
-                println!(
-                    "─────────────────────────────────────────────────────────────────────────"
-                );
-                for i in 0..sample_count {
-                    unsafe {
-                        let ts = first_batch.timestamp_value(i).unwrap_or("-");
-                        let lvl = first_batch.level_value(i).unwrap_or("-");
-                        let comp = first_batch.component_value(i).unwrap_or("-");
-                        let msg = first_batch.message_value(i).unwrap_or("-");
-                        let field_count = first_batch.field_count(i);
+    let listener = match std::net::TcpListener::bind(&listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Cannot listen on {}: {}", listen, e);
+            return 1;
+        }
+    };
+    let limit = fds::raise_limit(max_fds).unwrap_or(1024);
+    let budget = FdBudget::new(max_fds.map_or(limit, |max| max.min(limit)));
+    let max_connections = budget.free() / distributed::FDS_PER_CONNECTION;
+    if max_connections == 0 {
+        error!(
+            "--max-fds {} leaves no room for a connection ({} per connection after {} reserved)",
+            budget.limit(),
+            distributed::FDS_PER_CONNECTION,
+            fds::RESERVED
+        );
+        return 1;
+    }
+    info!(
+        "Worker listening on {}, serving files under '{}', up to {} connections at once",
+        listen, root, max_connections
+    );
+    match distributed::serve(
+        listener,
+        std::path::Path::new(&root),
+        threads,
+        max_connections,
+    ) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Worker failed: {}", e);
+            1
+        }
+    }
+}
 
-                        println!(
-                            "  [{:>4}] {} | {:>7} | {:>20} | {} ({} fields)",
-                            i,
-                            truncate_str(ts, 24),
-                            truncate_str(lvl, 7),
-                            truncate_str(comp, 20),
-                            truncate_str(msg, 40),
-                            field_count
-                        );
-                    }
+fn run_bench(args: &[String]) -> i32 {
+    let mut files = Vec::new();
+    let mut baseline_path = None;
+    let mut save_path = None;
+    let mut threshold = 5.0;
+    let mut runs = 5;
+    let mut threads = default_parallelism(cgroup::cpu_limit());
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match (args[i].as_str(), value) {
+            ("--baseline", Some(path)) => baseline_path = Some(path),
+            ("--save-baseline", Some(path)) => save_path = Some(path),
+            ("--fail-threshold", Some(text)) => match bench::parse_threshold(text) {
+                Some(t) => threshold = t,
+                None => {
+                    error!("Invalid --fail-threshold '{}'", text);
+                    return 2;
                 }
-                println!(
-                    "─────────────────────────────────────────────────────────────────────────"
-                );
+            },
+            ("--runs", Some(text)) => match text.parse::<usize>() {
+                Ok(n) if n > 0 => runs = n,
+                _ => warn!("Invalid --runs '{}', using {}", text, runs),
+            },
+            ("--threads", Some(text)) => match text.parse::<usize>() {
+                Ok(n) if n > 0 => threads = n,
+                _ => warn!("Invalid --threads '{}', using {}", text, threads),
+            },
+            (arg, _) if arg.starts_with("--") => {
+                warn!("Ignoring unknown bench option '{}'", arg);
+                i += 1;
+                continue;
+            }
+            (file, _) => {
+                files.push(file);
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    if files.is_empty() {
+        error!("bench needs at least one <file>");
+        return 2;
+    }
+
+    let baseline = match baseline_path.map(std::fs::read_to_string) {
+        None => Vec::new(),
+        Some(Ok(text)) => match bench::parse_baseline(&text) {
+            Ok(baseline) => baseline,
+            Err(e) => {
+                error!("Invalid baseline '{}': {}", baseline_path.unwrap(), e);
+                return 2;
             }
+        },
+        Some(Err(e)) => {
+            error!("Cannot read baseline '{}': {}", baseline_path.unwrap(), e);
+            return 2;
         }
+    };
 
-        println!(
-            "\nParsed {} structured records at {:.2} GB/s\n",
-            result.total_records,
-            stats.throughput_gbps()
-        );
-    } else {
-        let mmap_holder;
-        let result = if use_mmap {
-            mmap_holder = Some(unsafe { Mmap::map(&file) }.unwrap_or_else(|e| {
-                eprintln!("Error memory-mapping '{}': {}", file_path, e);
-                std::process::exit(1);
-            }));
-            let mmap = mmap_holder.as_ref().unwrap();
-
-            #[cfg(unix)]
-            unsafe {
-                libc::madvise(
-                    mmap.as_ptr() as *mut libc::c_void,
-                    mmap.len(),
-                    libc::MADV_SEQUENTIAL,
+    let mut results = Vec::with_capacity(files.len());
+    for path in files {
+        match bench::run_file(path, threads, runs) {
+            Ok(result) => {
+                info!(
+                    "{}: {} records, {:.2} GB/s",
+                    result.name, result.records, result.gbps
                 );
+                results.push(result);
+            }
+            Err(e) => {
+                error!("Cannot benchmark '{}': {}", path, e);
+                return 2;
             }
+        }
+    }
 
-            orchestrator::parse_logs_pipelined(mmap, num_threads)
-        } else {
-            mmap_holder = None;
-            let mut f = file;
-            orchestrator::parse_logs_streamed(&mut f, file_size as u64, num_threads)
-        };
-        let _ = &mmap_holder; // ensure mmap lives until here
+    let comparisons = bench::compare(&baseline, &results, threshold);
+    println!(
+        "Throughput (GB/s, median of {} runs, {} threads, fail below -{}%):",
+        runs, threads, threshold
+    );
+    print!("{}", bench::ComparisonTable(&comparisons));
 
-        let total_elapsed = total_start.elapsed();
-        let total_ms = total_elapsed.as_secs_f64() * 1000.0;
+    if let Some(path) = save_path
+        && let Err(e) = std::fs::write(path, bench::render_baseline(&results))
+    {
+        error!("Cannot write baseline '{}': {}", path, e);
+        return 2;
+    }
 
-        let num_lines = result.total_lines;
-        let throughput =
-            (file_size as f64 / (1024.0 * 1024.0 * 1024.0)) / total_elapsed.as_secs_f64();
-        println!(
-            "  Processed {} lines in {:.1} ms ({:.2} GB/s)",
-            num_lines, total_ms, throughput
+    let regressed = comparisons.iter().filter(|c| c.regressed).count();
+    if regressed > 0 {
+        error!(
+            "{} of {} datasets regressed by more than {}%",
+            regressed,
+            comparisons.len(),
+            threshold
         );
+        return 1;
+    }
+    0
+}
+
+fn run_replay(args: &[String]) -> i32 {
+    let mut files = Vec::new();
+    let mut sinks = Vec::new();
+    let mut speed = 1.0;
+    let mut format_hint = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match (args[i].as_str(), value) {
+            ("--speed", Some(text)) => match replay::parse_speed(text) {
+                Some(s) => speed = s,
+                None => {
+                    error!("Invalid --speed '{}'", text);
+                    return 2;
+                }
+            },
+            ("--sink", Some(spec)) => match SinkSpec::parse(spec) {
+                Ok(spec) => sinks.push(spec),
+                Err(e) => {
+                    error!("Invalid sink '{}': {}", spec, e);
+                    return 2;
+                }
+            },
+            ("--format", Some(name)) => {
+                format_hint = LogFormat::from_name(name);
+                if format_hint.is_none() && name != "auto" {
+                    warn!("Unknown format '{}', using auto-detect", name);
+                }
+            }
+            ("-v" | "--verbose", _) => {
+                diag::set_max_severity(diag::max_severity().more_verbose());
+                i += 1;
+                continue;
+            }
+            (arg, _) if arg.starts_with("--") => {
+                warn!("Ignoring unknown replay option '{}'", arg);
+                i += 1;
+                continue;
+            }
+            (file, _) => {
+                files.push(file);
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    if files.is_empty() {
+        error!("replay needs at least one <file>");
+        return 2;
+    }
+    if sinks.is_empty() {
+        sinks.push(SinkSpec::parse("stdout").expect("stdout is a valid sink"));
+    }
+    shutdown::install();
 
-        println!();
-        let stats = ParseStats {
-            total_bytes: file_size as u64,
-            total_lines: num_lines as u64,
-            scan_time_ms: result.scan_time_ms,
-            parse_time_ms: result.parse_time_ms,
-            total_time_ms: total_ms,
-            threads_used: num_threads,
+    let tee = Tee::spawn(sinks, 16);
+    let rules = EmitRules::default();
+    let mut pacer = Pacer::new(speed);
+    let mut status = 0;
+    for path in files {
+        let mmap = match File::open(path).and_then(|file| unsafe { Mmap::map(&file) }) {
+            Ok(mmap) => mmap,
+            Err(e) => {
+                error!("Cannot open '{}': {}", path, e);
+                status = 1;
+                continue;
+            }
         };
-        print!("{}", stats);
+        let format =
+            format_hint.unwrap_or_else(|| LogFormat::detect(&mmap[..mmap.len().min(4096)]));
+        info!("{}: replaying {} records at {}x", path, format, speed);
+        let pacer = Mutex::new(&mut pacer);
+        // One thread keeps the batches, and so the records, in file order.
+        if format == LogFormat::PlainText {
+            let replay = |batch: &LogBatch, records: &[u32]| {
+                pacer.lock().unwrap().pace(batch, records, &mut |due| {
+                    tee.send(emit_chunk(batch, due, &rules, EmitFormat::Ndjson))
+                });
+            };
+            let control = MatchControl {
+                on_batch: Some(&replay),
+                ..Default::default()
+            };
+            orchestrator::parse_logs_pipelined_with(&mmap, 1, &control);
+        } else {
+            let replay = |batch: &StructuredBatch, records: &[u32]| {
+                pacer.lock().unwrap().pace(batch, records, &mut |due| {
+                    tee.send(emit_chunk(batch, due, &rules, EmitFormat::Ndjson))
+                });
+            };
+            let control = MatchControl {
+                on_batch: Some(&replay),
+                ..Default::default()
+            };
+            structured_orchestrator::parse_structured_mmap_with(&mmap, 1, Some(format), &control);
+        }
+        if shutdown::interrupted() {
+            status = 130;
+            break;
+        }
+    }
+    for report in tee.finish() {
+        if let Some(e) = report.error {
+            error!("Sink {} failed: {}", report.name, e);
+            status = 1;
+        }
+    }
+    status
+}
 
-        if let Some(first_batch) = result.batches.first() {
-            let sample_count = first_batch.len.min(10);
-            if sample_count > 0 {
-                println!("\nSample log records:");
-                println!(
-                    "─────────────────────────────────────────────────────────────────────────"
-                );
-                for i in 0..sample_count {
-                    unsafe {
-                        println!(
-                            "  [{:>4}] {} | {:>7} | {:>20} | {}",
-                            i,
-                            first_batch.timestamps[i],
-                            first_batch.levels[i],
-                            first_batch.component(i),
-                            truncate_str(first_batch.message(i), 60)
-                        );
-                    }
+fn run_selftest(args: &[String]) -> i32 {
+    let mut bench_bytes = selftest::DEFAULT_BENCH_BYTES;
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--bench-mb", Some(mb)) => match mb.parse::<usize>() {
+                Ok(mb) => bench_bytes = mb << 20,
+                Err(_) => {
+                    error!("Invalid --bench-mb '{}'", mb);
+                    return 2;
                 }
-                println!(
-                    "─────────────────────────────────────────────────────────────────────────"
-                );
+            },
+            (arg, _) => {
+                warn!("Ignoring unknown selftest option '{}'", arg);
+                i += 1;
+                continue;
             }
         }
+        i += 2;
+    }
+    let report = selftest::run(bench_bytes);
+    print!("{}", report);
+    if report.passed() { 0 } else { 1 }
+}
 
-        println!(
-            "\nParsed {} log records at {:.2} GB/s\n",
-            num_lines,
-            stats.throughput_gbps()
-        );
+fn print_partial_marker(parsed: u64, file_size: u64) {
+    reportln!(
+        "\n*** PARTIAL RESULT: interrupted after {} of {} bytes ({:.1}%) ***",
+        parsed,
+        file_size,
+        parsed as f64 * 100.0 / file_size.max(1) as f64
+    );
+}
+
+fn print_match_summary<B: BatchRecords>(control: &MatchControl<B>) {
+    let limit = &control.limit;
+    if control.level.is_none()
+        && control.since.is_none()
+        && control.predicate.is_none()
+        && control.sample.is_none()
+        && limit.limit().is_none()
+    {
+        return;
+    }
+    report!("\nMatched {} records", limit.matched());
+    if let Some(filter) = control.level {
+        report!(" at level {}", filter);
+    }
+    if let Some(ts) = control.since {
+        report!(" since {}", ts);
+    }
+    if control.predicate.is_some() {
+        report!(" matching --where");
+    }
+    if control.sample.is_some() {
+        report!(" after level sampling");
+    }
+    if let Some(n) = limit.limit()
+        && limit.is_reached()
+    {
+        report!(" (stopped early at --limit {})", n);
     }
+    reportln!();
 }
 
 fn truncate_str(s: &str, max_len: usize) -> Cow<'_, str> {
@@ -0,0 +1,485 @@
+use crate::data::{FormatBreakdown, LevelSummary, LogLevel};
+use crate::emit::{ChunkStats, EmitChunk, write_json_string, write_rfc3339};
+use crate::sink::Sink;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+const P1: u64 = 0x9E37_79B1_85EB_CA87;
+const P2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const P3: u64 = 0x1656_67B1_9E37_79F9;
+const P4: u64 = 0x85EB_CA77_C2B2_AE63;
+const P5: u64 = 0x27D4_EB2F_1656_67C5;
+
+#[inline]
+fn round(acc: u64, lane: u64) -> u64 {
+    acc.wrapping_add(lane.wrapping_mul(P2))
+        .rotate_left(31)
+        .wrapping_mul(P1)
+}
+
+#[inline]
+fn merge_round(acc: u64, val: u64) -> u64 {
+    (acc ^ round(0, val)).wrapping_mul(P1).wrapping_add(P4)
+}
+
+#[inline]
+fn read_u64(b: &[u8]) -> u64 {
+    u64::from_le_bytes(b[..8].try_into().unwrap())
+}
+
+/// Streaming XXH64, so manifests can be checked with stock `xxhsum -H64`.
+#[derive(Clone)]
+pub struct Xxh64 {
+    seed: u64,
+    acc: [u64; 4],
+    buf: [u8; 32],
+    buf_len: usize,
+    total_len: u64,
+}
+
+impl Xxh64 {
+    pub fn new(seed: u64) -> Self {
+        Xxh64 {
+            seed,
+            acc: [
+                seed.wrapping_add(P1).wrapping_add(P2),
+                seed.wrapping_add(P2),
+                seed,
+                seed.wrapping_sub(P1),
+            ],
+            buf: [0; 32],
+            buf_len: 0,
+            total_len: 0,
+        }
+    }
+
+    pub fn oneshot(data: &[u8]) -> u64 {
+        let mut h = Xxh64::new(0);
+        h.update(data);
+        h.digest()
+    }
+
+    fn stripe(&mut self, block: &[u8]) {
+        for (k, acc) in self.acc.iter_mut().enumerate() {
+            *acc = round(*acc, read_u64(&block[k * 8..]));
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        if self.buf_len > 0 {
+            let take = (32 - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + take].copy_from_slice(&data[..take]);
+            self.buf_len += take;
+            data = &data[take..];
+            if self.buf_len < 32 {
+                return;
+            }
+            let block = self.buf;
+            self.stripe(&block);
+            self.buf_len = 0;
+        }
+        let mut blocks = data.chunks_exact(32);
+        for block in &mut blocks {
+            self.stripe(block);
+        }
+        let rest = blocks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    pub fn digest(&self) -> u64 {
+        let [a1, a2, a3, a4] = self.acc;
+        let mut h = if self.total_len >= 32 {
+            let h = a1
+                .rotate_left(1)
+                .wrapping_add(a2.rotate_left(7))
+                .wrapping_add(a3.rotate_left(12))
+                .wrapping_add(a4.rotate_left(18));
+            [a1, a2, a3, a4].into_iter().fold(h, merge_round)
+        } else {
+            self.seed.wrapping_add(P5)
+        };
+        h = h.wrapping_add(self.total_len);
+
+        let mut tail = &self.buf[..self.buf_len];
+        while tail.len() >= 8 {
+            h = (h ^ round(0, read_u64(tail)))
+                .rotate_left(27)
+                .wrapping_mul(P1)
+                .wrapping_add(P4);
+            tail = &tail[8..];
+        }
+        if tail.len() >= 4 {
+            let k = u32::from_le_bytes(tail[..4].try_into().unwrap()) as u64;
+            h = (h ^ k.wrapping_mul(P1))
+                .rotate_left(23)
+                .wrapping_mul(P2)
+                .wrapping_add(P3);
+            tail = &tail[4..];
+        }
+        for &b in tail {
+            h = (h ^ (b as u64).wrapping_mul(P5))
+                .rotate_left(11)
+                .wrapping_mul(P1);
+        }
+
+        h ^= h >> 33;
+        h = h.wrapping_mul(P2);
+        h ^= h >> 29;
+        h = h.wrapping_mul(P3);
+        h ^ (h >> 32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkEntry {
+    pub offset: u64,
+    pub len: u64,
+    pub records: u64,
+    pub checksum: u64,
+}
+
+/// Wraps an output sink and records, for every chunk it writes, the byte
+/// range it landed at, its record count and its XXH64. The manifest is
+/// written as one JSON document when the sink finishes, after the data has
+/// been flushed.
+pub struct ManifestSink<W: Write + Send> {
+    inner: Box<dyn Sink>,
+    output: String,
+    out: W,
+    chunks: Vec<ChunkEntry>,
+    whole: Xxh64,
+    bytes: u64,
+    records: u64,
+}
+
+impl<W: Write + Send> ManifestSink<W> {
+    /// `output` names the data file the manifest describes.
+    pub fn new(inner: Box<dyn Sink>, output: &str, out: W) -> Self {
+        ManifestSink {
+            inner,
+            output: output.to_string(),
+            out,
+            chunks: Vec::new(),
+            whole: Xxh64::new(0),
+            bytes: 0,
+            records: 0,
+        }
+    }
+
+    fn render(&self) -> Vec<u8> {
+        let mut json = Vec::with_capacity(128 + self.chunks.len() * 80);
+        json.extend_from_slice(b"{\"output\":");
+        write_json_string(self.output.as_bytes(), &mut json);
+        json.extend_from_slice(
+            format!(
+                ",\"algorithm\":\"xxh64\",\"bytes\":{},\"records\":{},\"checksum\":\"{:016x}\",\"chunks\":[",
+                self.bytes,
+                self.records,
+                self.whole.digest()
+            )
+            .as_bytes(),
+        );
+        for (n, chunk) in self.chunks.iter().enumerate() {
+            if n > 0 {
+                json.push(b',');
+            }
+            json.extend_from_slice(
+                format!(
+                    "\n{{\"offset\":{},\"len\":{},\"records\":{},\"checksum\":\"{:016x}\"}}",
+                    chunk.offset, chunk.len, chunk.records, chunk.checksum
+                )
+                .as_bytes(),
+            );
+        }
+        json.extend_from_slice(b"]}\n");
+        json
+    }
+}
+
+impl<W: Write + Send> Sink for ManifestSink<W> {
+    fn open(&mut self) -> io::Result<()> {
+        self.inner.open()
+    }
+
+    fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()> {
+        if chunk.ndjson.is_empty() {
+            return Ok(());
+        }
+        self.inner.write_batch(chunk)?;
+        self.chunks.push(ChunkEntry {
+            offset: self.bytes,
+            len: chunk.ndjson.len() as u64,
+            records: chunk.records,
+            checksum: Xxh64::oneshot(&chunk.ndjson),
+        });
+        self.whole.update(&chunk.ndjson);
+        self.bytes += chunk.ndjson.len() as u64;
+        self.records += chunk.records;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.inner.close()?;
+        let json = self.render();
+        self.out.write_all(&json)?;
+        self.out.flush()
+    }
+}
+
+/// Parse totals of the run, filled in by the caller before the sinks close.
+pub type ParseTotals = Arc<Mutex<FormatBreakdown>>;
+
+/// Wraps an output sink and, when it finishes, writes a `_manifest.json`
+/// summary of what it produced: record and byte counts, time range, level
+/// distribution, output fields and the parse totals behind them, so the
+/// dataset can be validated and discovered without reading it.
+pub struct DatasetSink<W: Write + Send> {
+    inner: Box<dyn Sink>,
+    artifact: String,
+    out: W,
+    parse: ParseTotals,
+    bytes: u64,
+    records: u64,
+    levels: LevelSummary,
+    stats: ChunkStats,
+}
+
+impl<W: Write + Send> DatasetSink<W> {
+    /// `artifact` names the file or directory the manifest describes.
+    pub fn new(inner: Box<dyn Sink>, artifact: &str, out: W, parse: ParseTotals) -> Self {
+        DatasetSink {
+            inner,
+            artifact: artifact.to_string(),
+            out,
+            parse,
+            bytes: 0,
+            records: 0,
+            levels: LevelSummary::default(),
+            stats: ChunkStats::default(),
+        }
+    }
+
+    fn render(&self) -> Vec<u8> {
+        let mut json = Vec::with_capacity(512 + self.stats.fields.len() * 48);
+        json.extend_from_slice(b"{\"artifact\":");
+        write_json_string(self.artifact.as_bytes(), &mut json);
+        json.extend_from_slice(
+            format!(
+                ",\"records\":{},\"bytes\":{},\"time_range\":",
+                self.records, self.bytes
+            )
+            .as_bytes(),
+        );
+        match self.stats.time_range {
+            Some((first, last)) => {
+                json.extend_from_slice(b"{\"start\":\"");
+                write_rfc3339(first, &mut json);
+                json.extend_from_slice(b"\",\"end\":\"");
+                write_rfc3339(last, &mut json);
+                json.extend_from_slice(b"\"}");
+            }
+            None => json.extend_from_slice(b"null"),
+        }
+        json.extend_from_slice(b",\"levels\":{");
+        for (n, level) in LogLevel::ALL.into_iter().enumerate() {
+            if n > 0 {
+                json.push(b',');
+            }
+            json.extend_from_slice(
+                format!(
+                    "\"{}\":{}",
+                    level.as_str().to_ascii_lowercase(),
+                    self.levels.count(level)
+                )
+                .as_bytes(),
+            );
+        }
+        json.extend_from_slice(b"},\"schema\":[");
+        for (n, (field, records)) in self.stats.fields.iter().enumerate() {
+            if n > 0 {
+                json.push(b',');
+            }
+            json.extend_from_slice(b"{\"field\":");
+            write_json_string(field, &mut json);
+            json.extend_from_slice(format!(",\"records\":{}}}", records).as_bytes());
+        }
+        json.extend_from_slice(b"],\"parse\":[");
+        let parse = self.parse.lock().unwrap();
+        for (n, (format, totals)) in parse.formats.iter().enumerate() {
+            if n > 0 {
+                json.push(b',');
+            }
+            json.extend_from_slice(
+                format!(
+                    "{{\"format\":\"{}\",\"inputs\":{},\"records\":{},\"bytes\":{},\"parse_ms\":{:.1}}}",
+                    format.as_str(),
+                    totals.inputs,
+                    totals.records,
+                    totals.bytes,
+                    totals.parse_time_ms
+                )
+                .as_bytes(),
+            );
+        }
+        json.extend_from_slice(b"]}\n");
+        json
+    }
+}
+
+impl<W: Write + Send> Sink for DatasetSink<W> {
+    fn open(&mut self) -> io::Result<()> {
+        self.inner.open()
+    }
+
+    fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()> {
+        self.inner.write_batch(chunk)?;
+        self.bytes += chunk.ndjson.len() as u64;
+        self.records += chunk.records;
+        self.levels.merge(&chunk.levels);
+        if let Some(stats) = &chunk.stats {
+            self.stats.merge(stats);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.inner.close()?;
+        let json = self.render();
+        self.out.write_all(&json)?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{LevelSummary, LogLevel};
+    use crate::sink::WriterSink;
+
+    #[test]
+    fn test_xxh64_reference_values() {
+        assert_eq!(Xxh64::oneshot(b""), 0xEF46_DB37_51D8_E999);
+        assert_eq!(Xxh64::oneshot(b"a"), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(Xxh64::oneshot(b"abc"), 0x44BC_2CF5_AD77_0999);
+
+        let data: Vec<u8> = (0..200u32).map(|i| (i * 7) as u8).collect();
+        let mut streamed = Xxh64::new(0);
+        for piece in data.chunks(13) {
+            streamed.update(piece);
+        }
+        assert_eq!(streamed.digest(), Xxh64::oneshot(&data));
+    }
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dataset_manifest_summarizes_output() {
+        use crate::emit::{EmitRules, ndjson_chunk};
+        use crate::format::LogFormat;
+        use crate::structured_orchestrator::parse_structured_mmap;
+
+        let data = b"{\"ts\":\"2025-02-12T10:31:45Z\",\"level\":\"error\",\"a\":1}\n\
+                     {\"ts\":\"2025-02-12T09:00:00Z\",\"level\":\"info\"}\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Json));
+        let rules = EmitRules {
+            collect_stats: true,
+            ..EmitRules::default()
+        };
+        let parse = ParseTotals::default();
+        let manifest = Shared::default();
+        let mut sink = DatasetSink::new(
+            Box::new(WriterSink(Shared::default())),
+            "out.ndjson",
+            manifest.clone(),
+            Arc::clone(&parse),
+        );
+        sink.write_batch(&ndjson_chunk(&result.batches[0], &[0], &rules))
+            .unwrap();
+        sink.write_batch(&ndjson_chunk(&result.batches[0], &[1], &rules))
+            .unwrap();
+        parse
+            .lock()
+            .unwrap()
+            .record(LogFormat::Json, data.len() as u64, 2, 1.0, 2.0);
+        sink.close().unwrap();
+
+        let manifest = String::from_utf8(manifest.0.lock().unwrap().clone()).unwrap();
+        assert!(manifest.starts_with(
+            "{\"artifact\":\"out.ndjson\",\"records\":2,\"bytes\":97,\"time_range\":\
+             {\"start\":\"2025-02-12T09:00:00"
+        ));
+        assert!(manifest.contains("\"error\":1,"));
+        assert!(manifest.contains(
+            "\"schema\":[{\"field\":\"ts\",\"records\":2},{\"field\":\"level\",\"records\":2},\
+             {\"field\":\"a\",\"records\":1}]"
+        ));
+        assert!(manifest.contains("\"parse\":[{\"format\":\"json\",\"inputs\":1,\"records\":2,"));
+    }
+
+    #[test]
+    fn test_manifest_ranges_cover_output() {
+        let data = Shared::default();
+        let manifest = Shared::default();
+        let mut sink = ManifestSink::new(
+            Box::new(WriterSink(data.clone())),
+            "out.ndjson",
+            manifest.clone(),
+        );
+        for lines in ["{\"a\":1}\n{\"a\":2}\n", "{\"a\":3}\n"] {
+            let records = lines.lines().count() as u64;
+            let chunk = EmitChunk {
+                ndjson: lines.as_bytes().to_vec(),
+                records,
+                levels: LevelSummary::from_levels(&vec![LogLevel::Info; records as usize]),
+                partition: None,
+                stats: None,
+            };
+            sink.write_batch(&chunk).unwrap();
+        }
+        let entries = sink.chunks.clone();
+        sink.close().unwrap();
+
+        let data = data.0.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            (entries[0].offset, entries[0].len, entries[0].records),
+            (0, 16, 2)
+        );
+        assert_eq!(
+            (entries[1].offset, entries[1].len, entries[1].records),
+            (16, 8, 1)
+        );
+        for entry in &entries {
+            let range = entry.offset as usize..(entry.offset + entry.len) as usize;
+            assert_eq!(entry.checksum, Xxh64::oneshot(&data[range]));
+        }
+
+        let manifest = String::from_utf8(manifest.0.lock().unwrap().clone()).unwrap();
+        assert!(manifest.starts_with(
+            "{\"output\":\"out.ndjson\",\"algorithm\":\"xxh64\",\"bytes\":24,\"records\":3,"
+        ));
+        assert!(manifest.contains(&format!("\"checksum\":\"{:016x}\"", Xxh64::oneshot(&data))));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::emit::EmitRecord;
+use crate::expr::{Expr, record_matches};
+use crate::group::{Agg, GroupBy};
+use std::fmt::Write as _;
+
+/// Quantiles reported for `summary` rules.
+const QUANTILES: [(&str, &str); 3] = [("0.5", "p50"), ("0.9", "p90"), ("0.99", "p99")];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Matching records, or the sum of `value=` over them.
+    Counter,
+    /// Quantiles, sum and count of `value=`.
+    Summary,
+}
+
+/// One line of a `--metric-rules` file:
+///
+/// ```text
+/// counter http_requests_total labels=(method,status) with path
+/// counter bytes_sent_total value=bytes labels=(component)
+/// summary request_latency_ms value=latency_ms labels=(component) where level == "error"
+/// ```
+///
+/// `with <field>` keeps records that have the field, `where` takes an
+/// expression over the record for the rest of the line.
+pub struct MetricRule {
+    pub kind: MetricKind,
+    pub name: String,
+    labels: Vec<String>,
+    value: Option<Vec<u8>>,
+    with: Vec<Vec<u8>>,
+    filter: Option<Expr>,
+    groups: GroupBy,
+}
+
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+impl MetricRule {
+    pub fn parse(line: &str) -> Result<MetricRule, String> {
+        let (head, filter) = match line.split_once(" where ") {
+            Some((head, expr)) => (head, Some(Expr::parse(expr)?)),
+            None => (line, None),
+        };
+        // `labels=(a, b)` may contain spaces.
+        let mut tokens = Vec::new();
+        let mut rest = head.trim();
+        while !rest.is_empty() {
+            let end = match rest.strip_prefix("labels=(") {
+                Some(inner) => inner.find(')').map(|p| p + "labels=()".len()),
+                None => Some(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+            }
+            .ok_or("unclosed labels=(")?;
+            tokens.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+
+        let mut tokens = tokens.into_iter();
+        let kind = match tokens.next() {
+            Some("counter") => MetricKind::Counter,
+            Some("summary") => MetricKind::Summary,
+            other => {
+                return Err(format!(
+                    "expected counter or summary, got '{}'",
+                    other.unwrap_or("")
+                ));
+            }
+        };
+        let name = tokens.next().ok_or("missing metric name")?.to_string();
+        if !valid_name(&name) {
+            return Err(format!("invalid metric name '{}'", name));
+        }
+        let (mut labels, mut value, mut with) = (Vec::new(), None, Vec::new());
+        while let Some(token) = tokens.next() {
+            if let Some(list) = token
+                .strip_prefix("labels=(")
+                .and_then(|l| l.strip_suffix(')'))
+            {
+                labels = list
+                    .split(',')
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect();
+            } else if let Some(field) = token.strip_prefix("value=") {
+                value = Some(field.as_bytes().to_vec());
+            } else if token == "with" {
+                with.push(
+                    tokens
+                        .next()
+                        .ok_or("missing field after 'with'")?
+                        .as_bytes()
+                        .to_vec(),
+                );
+            } else {
+                return Err(format!("unexpected '{}'", token));
+            }
+        }
+
+        let aggs = match (kind, &value) {
+            (MetricKind::Counter, None) => vec![],
+            (MetricKind::Counter, Some(field)) => {
+                Agg::parse_list(&format!("{}:sum", String::from_utf8_lossy(field)))?
+            }
+            (MetricKind::Summary, Some(field)) => {
+                let field = String::from_utf8_lossy(field);
+                let list: Vec<String> = std::iter::once("sum")
+                    .chain(QUANTILES.iter().map(|(_, agg)| *agg))
+                    .map(|agg| format!("{}:{}", field, agg))
+                    .collect();
+                Agg::parse_list(&list.join(","))?
+            }
+            (MetricKind::Summary, None) => return Err("summary needs value=<field>".to_string()),
+        };
+        let keys = labels.iter().map(|l| l.as_bytes().to_vec()).collect();
+        Ok(MetricRule {
+            kind,
+            name,
+            labels,
+            value,
+            with,
+            filter,
+            groups: GroupBy::new(keys, aggs),
+        })
+    }
+
+    fn selects<B: EmitRecord>(&self, batch: &B, i: usize) -> bool {
+        self.with.iter().all(|field| {
+            let mut found = false;
+            batch.visit_field(i, field, &mut |_| found = true);
+            found
+        }) && self
+            .filter
+            .as_ref()
+            .is_none_or(|filter| record_matches(batch, i, filter, &[]))
+    }
+
+    /// Appends the rule's series in Prometheus text exposition format.
+    fn write_exposition(&self, out: &mut String) {
+        let report = self.groups.report();
+        let kind = match self.kind {
+            MetricKind::Counter => "counter",
+            MetricKind::Summary => "summary",
+        };
+        let _ = writeln!(out, "# TYPE {} {}", self.name, kind);
+        for group in &report.groups {
+            let mut labels = String::new();
+            for (name, value) in self.labels.iter().zip(&group.values) {
+                if !labels.is_empty() {
+                    labels.push(',');
+                }
+                let name: String = name
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                let value = String::from_utf8_lossy(value.as_deref().unwrap_or_default())
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                let _ = write!(labels, "{}=\"{}\"", name, value);
+            }
+            let braced = |extra: &str| {
+                let all = [labels.as_str(), extra]
+                    .into_iter()
+                    .filter(|l| !l.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                if all.is_empty() {
+                    String::new()
+                } else {
+                    format!("{{{}}}", all)
+                }
+            };
+            let value = |i: usize| report.aggs[i].value(&group.stats);
+            match self.kind {
+                MetricKind::Counter if self.value.is_none() => {
+                    let _ = writeln!(out, "{}{} {}", self.name, braced(""), group.stats.count);
+                }
+                MetricKind::Counter => {
+                    let _ = writeln!(
+                        out,
+                        "{}{} {}",
+                        self.name,
+                        braced(""),
+                        value(0).unwrap_or(0.0)
+                    );
+                }
+                MetricKind::Summary => {
+                    for (i, (quantile, _)) in QUANTILES.iter().enumerate() {
+                        if let Some(v) = value(i + 1) {
+                            let label = format!("quantile=\"{}\"", quantile);
+                            let _ = writeln!(out, "{}{} {}", self.name, braced(&label), v);
+                        }
+                    }
+                    let count = group.stats.fields.first().map_or(0, |f| f.n);
+                    let _ = writeln!(
+                        out,
+                        "{}_sum{} {}",
+                        self.name,
+                        braced(""),
+                        value(0).unwrap_or(0.0)
+                    );
+                    let _ = writeln!(out, "{}_count{} {}", self.name, braced(""), count);
+                }
+            }
+        }
+    }
+}
+
+/// Rules from a `--metric-rules` file, evaluated over matched records
+/// during the parse. Lines starting with `#` are comments.
+pub struct MetricRules {
+    pub rules: Vec<MetricRule>,
+}
+
+impl MetricRules {
+    pub fn parse(text: &str) -> Result<MetricRules, String> {
+        let rules = text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(n, line)| MetricRule::parse(line).map_err(|e| format!("line {}: {}", n + 1, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(MetricRules { rules })
+    }
+
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let mut selected = Vec::with_capacity(records.len());
+        for rule in &self.rules {
+            selected.clear();
+            selected.extend(
+                records
+                    .iter()
+                    .copied()
+                    .filter(|&i| rule.selects(batch, i as usize)),
+            );
+            if !selected.is_empty() {
+                rule.groups.add_records(batch, &selected);
+            }
+        }
+    }
+
+    pub fn exposition(&self) -> String {
+        let mut out = String::new();
+        for rule in &self.rules {
+            rule.write_exposition(&mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_rules_emit_exposition() {
+        let data = br#"{"level":"info","method":"GET","status":200,"path":"/a","ms":10}
+{"level":"info","method":"GET","status":200,"path":"/b","ms":30}
+{"level":"error","method":"POST","status":500,"path":"/a","ms":250}
+{"level":"info","method":"GET","status":200,"ms":5}
+{"level":"info","method":"G\"ET\\","status":404,"path":"/c","ms":0}
+"#;
+        let rules = MetricRules::parse(
+            "# request metrics\n\
+             counter http_requests_total labels=(method, status) with path\n\
+             \n\
+             counter ms_total value=ms\n\
+             summary error_ms value=ms labels=(method) where level == \"error\"\n",
+        )
+        .unwrap();
+        let add = |batch: &StructuredBatch, records: &[u32]| rules.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data, 1, Some(LogFormat::Json), &control);
+
+        let text = rules.exposition();
+        assert!(text.contains("# TYPE http_requests_total counter\n"));
+        assert!(text.contains("http_requests_total{method=\"GET\",status=\"200\"} 2\n"));
+        assert!(text.contains("http_requests_total{method=\"POST\",status=\"500\"} 1\n"));
+        // JSON escapes are resolved before the exposition format's own.
+        assert!(text.contains(r#"http_requests_total{method="G\"ET\\",status="404"} 1"#));
+        assert!(text.contains("ms_total 295\n"));
+        assert!(text.contains("# TYPE error_ms summary\n"));
+        assert!(text.contains("error_ms_count{method=\"POST\"} 1\n"));
+        assert!(text.contains("error_ms_sum{method=\"POST\"} 250\n"));
+        assert!(text.contains("error_ms{method=\"POST\",quantile=\"0.99\"} 2"));
+        assert!(!text.contains("GET\"} 3"));
+
+        for (rule, error) in [
+            ("gauge x", "expected counter or summary"),
+            ("counter 9x", "invalid metric name"),
+            ("summary lat", "summary needs value="),
+            ("counter x labels=(a", "unclosed labels=("),
+            ("counter x by y", "unexpected 'by'"),
+        ] {
+            let err = MetricRules::parse(&format!("\n{}", rule)).err().unwrap();
+            assert!(
+                err.starts_with("line 2: ") && err.contains(error),
+                "{}",
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Longest number text read with a tolerant style.
+const MAX_LEN: usize = 64;
+
+/// How a field writes its numbers when they are text rather than JSON
+/// numbers (`--numbers latency:grouped,betrag:european`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberStyle {
+    /// What Rust parses: `1234.5`, `-1e3`.
+    Plain,
+    /// `.` for decimals, `,` `'` or space between groups of three digits and
+    /// `_` anywhere between digits: `1,234,567.8`, `1_000`.
+    Grouped,
+    /// `,` for decimals, `.` `'` or space between groups of three digits and
+    /// `_` anywhere between digits: `1.234.567,8`, `1 000,5`.
+    European,
+}
+
+impl NumberStyle {
+    pub fn parse(name: &str) -> Option<NumberStyle> {
+        match name.to_ascii_lowercase().as_str() {
+            "plain" => Some(NumberStyle::Plain),
+            "grouped" | "us" => Some(NumberStyle::Grouped),
+            "european" | "eu" => Some(NumberStyle::European),
+            _ => None,
+        }
+    }
+
+    fn decimal(self) -> u8 {
+        match self {
+            NumberStyle::European => b',',
+            _ => b'.',
+        }
+    }
+
+    fn thousands(self) -> &'static [u8] {
+        match self {
+            NumberStyle::Plain => b"",
+            NumberStyle::Grouped => b",' ",
+            NumberStyle::European => b".' ",
+        }
+    }
+}
+
+/// Field names and their styles.
+pub type FieldStyles = Vec<(Box<[u8]>, NumberStyle)>;
+
+static STYLES: OnceLock<FieldStyles> = OnceLock::new();
+
+/// Per-field styles from `--numbers`; only the first call takes effect.
+pub fn set_styles(styles: FieldStyles) {
+    let _ = STYLES.set(styles);
+}
+
+/// Parses a comma-separated `field:style` list.
+pub fn parse_styles(list: &str) -> Result<FieldStyles, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|decl| !decl.is_empty())
+        .map(|decl| {
+            let (field, style) = decl
+                .rsplit_once(':')
+                .filter(|(field, _)| !field.is_empty())
+                .ok_or_else(|| format!("expected field:style, got '{}'", decl))?;
+            let style = NumberStyle::parse(style)
+                .ok_or_else(|| format!("unknown number style '{}'", style))?;
+            Ok((field.as_bytes().into(), style))
+        })
+        .collect()
+}
+
+/// The style configured for `field`, plain when there is none.
+pub fn style_of(field: &[u8]) -> NumberStyle {
+    STYLES
+        .get()
+        .and_then(|styles| styles.iter().find(|(name, _)| **name == *field))
+        .map_or(NumberStyle::Plain, |&(_, style)| style)
+}
+
+/// `text` read as a number written in `style`. Separators must sit between
+/// digits of the integer part, and `,` `.` `'` and space must each start a
+/// group of exactly three, so `1,5` is not taken for fifteen.
+pub fn parse<T: FromStr>(text: &[u8], style: NumberStyle) -> Option<T> {
+    let text = std::str::from_utf8(text).ok()?.trim();
+    if style == NumberStyle::Plain {
+        return text.parse().ok();
+    }
+    let bytes = text.as_bytes();
+    if bytes.len() > MAX_LEN {
+        return None;
+    }
+    let mut buf = [0u8; MAX_LEN];
+    let mut len = 0;
+    // Digits since the last separator, and whether that one needs three.
+    let mut run = 0;
+    let mut grouped = false;
+    let mut in_fraction = false;
+    for (k, &b) in bytes.iter().enumerate() {
+        let between_digits = k > 0
+            && bytes[k - 1].is_ascii_digit()
+            && bytes.get(k + 1).is_some_and(u8::is_ascii_digit);
+        let out = if b.is_ascii_digit() {
+            run += 1;
+            b
+        } else if b == b'_' && between_digits && !in_fraction {
+            if grouped && run != 3 {
+                return None;
+            }
+            (run, grouped) = (0, false);
+            continue;
+        } else if style.thousands().contains(&b) && between_digits && !in_fraction {
+            if grouped && run != 3 {
+                return None;
+            }
+            (run, grouped) = (0, true);
+            continue;
+        } else if b == style.decimal() {
+            if in_fraction || (grouped && run != 3) {
+                return None;
+            }
+            (in_fraction, grouped) = (true, false);
+            b'.'
+        } else if matches!(b, b'e' | b'E') {
+            if grouped && run != 3 {
+                return None;
+            }
+            (in_fraction, grouped) = (true, false);
+            b
+        } else if matches!(b, b'.' | b',' | b'\'' | b' ' | b'_') {
+            return None;
+        } else {
+            b
+        };
+        buf[len] = out;
+        len += 1;
+    }
+    if grouped && run != 3 {
+        return None;
+    }
+    std::str::from_utf8(&buf[..len]).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_styles() {
+        use NumberStyle::*;
+        assert_eq!(parse::<f64>(b" 1234.5 ", Plain), Some(1234.5));
+        assert_eq!(parse::<f64>(b"1,234", Plain), None);
+
+        assert_eq!(parse::<i64>(b"1,234,567", Grouped), Some(1_234_567));
+        assert_eq!(parse::<f64>(b"-1,234.25", Grouped), Some(-1234.25));
+        assert_eq!(parse::<i64>(b"1_000", Grouped), Some(1000));
+        assert_eq!(parse::<i64>(b"10_00", Grouped), Some(1000));
+        assert_eq!(parse::<i64>(b"1'000'000", Grouped), Some(1_000_000));
+        assert_eq!(parse::<f64>(b"2.5e3", Grouped), Some(2500.0));
+        assert_eq!(parse::<f64>(b"1,5", Grouped), None);
+        assert_eq!(parse::<f64>(b"1,2345", Grouped), None);
+        assert_eq!(parse::<f64>(b",123", Grouped), None);
+        assert_eq!(parse::<f64>(b"1,,234", Grouped), None);
+        assert_eq!(parse::<f64>(b"1.234.5", Grouped), None);
+
+        assert_eq!(parse::<f64>(b"1.234.567,89", European), Some(1_234_567.89));
+        assert_eq!(parse::<f64>(b"1 000,5", European), Some(1000.5));
+        assert_eq!(parse::<f64>(b"0,25", European), Some(0.25));
+        assert_eq!(parse::<i64>(b"1.234", European), Some(1234));
+        assert_eq!(parse::<f64>(b"1.5", European), None);
+        assert_eq!(parse::<f64>(b"1,2,3", European), None);
+
+        let styles = parse_styles("amount:eu, bytes:grouped").unwrap();
+        assert_eq!(styles[0], (b"amount"[..].into(), European));
+        assert_eq!(styles[1].1, Grouped);
+        assert!(parse_styles("amount").is_err());
+        assert!(parse_styles(":eu").is_err());
+        assert!(parse_styles("amount:roman").is_err());
+    }
+}
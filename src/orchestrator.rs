@@ -1,10 +1,17 @@
-use crate::data::LogBatch;
+use crate::ansi;
+use crate::data::{FailedChunk, LevelSummary, LogBatch, TimeRange, count_lines, number_lines};
+use crate::filter::MatchControl;
+use crate::format::LogFormat;
+use crate::holes;
 use crate::parser::parse_lines_range;
+use crate::pinning;
+use crate::seek::seek_to_time;
 use crate::simd_scan;
-use core_affinity::CoreId;
-use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, Read};
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
 use std::time::Instant;
 
@@ -13,6 +20,15 @@ pub struct PipelineResult {
     pub total_lines: usize,
     pub scan_time_ms: f64,
     pub parse_time_ms: f64,
+    pub level_summary: LevelSummary,
+    pub time_range: TimeRange,
+    pub malformed_lines: u64,
+    /// Input bytes parsed; short of the input when the parse stopped early.
+    pub parsed_bytes: u64,
+    /// NUL-filled bytes skipped as holes rather than parsed.
+    pub hole_bytes: u64,
+    /// Chunks whose parse panicked, in input order; their records are missing.
+    pub failed_chunks: Vec<FailedChunk>,
 
     pub _backing_data: Vec<Vec<u8>>,
 }
@@ -24,74 +40,184 @@ pub struct StreamingResult {
     pub parse_time_ms: f64,
 }
 
-#[derive(Clone, Copy, Debug)]
-struct CpuTopoEntry {
-    core: CoreId,
-    package_id: Option<u32>,
-    core_id: Option<u32>,
-}
-
-fn read_topology_u32(cpu_id: usize, leaf: &str) -> Option<u32> {
-    let path = format!("/sys/devices/system/cpu/cpu{cpu_id}/topology/{leaf}");
-    std::fs::read_to_string(path)
-        .ok()?
-        .trim()
-        .parse::<u32>()
-        .ok()
+/// Parts of chunk `data[start..end]` left after skipping NUL holes, in the
+/// order they are parsed (newest-first when reversing), and the bytes skipped.
+pub(crate) fn chunk_segments(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    reverse: bool,
+) -> (Vec<(usize, usize)>, u64) {
+    let (mut segments, skipped) = holes::data_segments(data, start, end);
+    if reverse {
+        segments.reverse();
+    }
+    (segments, skipped)
 }
 
-fn choose_pinned_cores(worker_threads: usize, core_ids: &[CoreId]) -> Vec<CoreId> {
-    if worker_threads == 0 || core_ids.is_empty() {
-        return Vec::new();
+/// Order in which chunks are dispatched; newest-first when reversing.
+pub(crate) fn chunk_order(num_chunks: usize, reverse: bool) -> Vec<usize> {
+    if reverse {
+        (0..num_chunks).rev().collect()
+    } else {
+        (0..num_chunks).collect()
     }
+}
 
-    let topo: Vec<CpuTopoEntry> = core_ids
+/// Line each chunk starts on, given `first_line` at `data[0]`. The chunks'
+/// lines are counted up front, a share of them per thread, so that chunks
+/// parsed in any order can number their records.
+pub(crate) fn chunk_first_lines(
+    data: &[u8],
+    boundaries: &[usize],
+    first_line: u64,
+    threads: usize,
+) -> Vec<u64> {
+    let num_chunks = boundaries.len() - 1;
+    let mut counts = vec![0u64; num_chunks];
+    let share = num_chunks.div_ceil(threads.max(1)).max(1);
+    thread::scope(|scope| {
+        for (n, counts) in counts.chunks_mut(share).enumerate() {
+            scope.spawn(move || {
+                for (k, count) in counts.iter_mut().enumerate() {
+                    let i = n * share + k;
+                    *count = count_lines(&data[boundaries[i]..boundaries[i + 1]]);
+                }
+            });
+        }
+    });
+    counts
         .iter()
-        .copied()
-        .map(|core| CpuTopoEntry {
-            core,
-            package_id: read_topology_u32(core.id, "physical_package_id"),
-            core_id: read_topology_u32(core.id, "core_id"),
+        .scan(first_line, |line, &count| {
+            let first = *line;
+            *line += count;
+            Some(first)
         })
-        .collect();
+        .collect()
+}
 
-    let mut by_package: HashMap<Option<u32>, Vec<CpuTopoEntry>> = HashMap::new();
-    for entry in topo {
-        by_package.entry(entry.package_id).or_default().push(entry);
-    }
+/// Runs a chunk's `parse`, once more if it panics. A chunk that panics both
+/// times gives `None`, so a parser bug on one region of the input costs that
+/// region's records rather than the whole run.
+pub(crate) fn parse_isolated<T>(parse: impl Fn() -> T) -> Option<T> {
+    (0..2).find_map(|_| panic::catch_unwind(AssertUnwindSafe(&parse)).ok())
+}
 
-    let mut packages: Vec<Vec<CpuTopoEntry>> = by_package.into_values().collect();
-    packages.sort_by_key(|entries| std::cmp::Reverse(entries.len()));
+/// Size of the first chunk under a match limit. Chunks double from here up
+/// to the configured size, so a `--limit` query satisfied near the start of
+/// a huge input parses only a little of it.
+const HEAD_CHUNK_SIZE: usize = 1024 * 1024;
 
-    let mut selected = Vec::with_capacity(worker_threads);
-    let mut used_core_ids: HashSet<(Option<u32>, Option<u32>)> = HashSet::new();
+/// Length of chunk `k`: `chunk_size`, or when `ramp`ing for a match limit,
+/// [`HEAD_CHUNK_SIZE`] doubled `k` times, up to `chunk_size`.
+pub(crate) fn chunk_len(chunk_size: usize, k: usize, ramp: bool) -> usize {
+    if ramp {
+        chunk_size.min(HEAD_CHUNK_SIZE << k.min(32))
+    } else {
+        chunk_size
+    }
+}
 
-    for entries in &packages {
-        for entry in entries {
-            let key = (entry.package_id, entry.core_id);
-            if used_core_ids.contains(&key) {
-                continue;
-            }
-            used_core_ids.insert(key);
-            selected.push(entry.core);
-            if selected.len() >= worker_threads {
-                return selected;
-            }
+/// Hands workers the chunks they parse as `(seq, chunk)` pairs, `seq` being
+/// the chunk's position in parse order. Each worker gets its own contiguous
+/// run of chunks or, under a match limit or `--ordered`, takes the next
+/// chunk in parse order whenever it is free: a limit then stops the parse
+/// right after the chunks that satisfy it, and little ordered output waits
+/// on earlier chunks.
+pub(crate) struct ChunkDispatch {
+    order: Vec<usize>,
+    runs: Option<Vec<Range<usize>>>,
+    next: AtomicUsize,
+}
+
+impl ChunkDispatch {
+    pub(crate) fn new(
+        num_chunks: usize,
+        worker_threads: usize,
+        reverse: bool,
+        in_order: bool,
+    ) -> Self {
+        let runs = (!in_order).then(|| {
+            (0..worker_threads)
+                .map(|w| w * num_chunks / worker_threads..(w + 1) * num_chunks / worker_threads)
+                .collect()
+        });
+        ChunkDispatch {
+            order: chunk_order(num_chunks, reverse),
+            runs,
+            next: AtomicUsize::new(0),
         }
     }
 
-    for entries in &packages {
-        for entry in entries {
-            if !selected.iter().any(|c| c.id == entry.core.id) {
-                selected.push(entry.core);
-                if selected.len() >= worker_threads {
-                    return selected;
-                }
-            }
-        }
+    /// The chunks worker `worker_idx` parses, in the order it parses them.
+    pub(crate) fn worker(&self, worker_idx: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut run = self.runs.as_ref().map(|runs| runs[worker_idx].clone());
+        std::iter::from_fn(move || {
+            let seq = match &mut run {
+                Some(run) => run.next()?,
+                None => Some(self.next.fetch_add(1, Ordering::Relaxed))
+                    .filter(|&seq| seq < self.order.len())?,
+            };
+            Some((seq, self.order[seq]))
+        })
     }
+}
 
-    selected
+/// Sets where `batch`, parsed from `data[start..]`, came from: its input
+/// and, when numbering, the line of each record given the line `data[start]`
+/// is on.
+fn stamp(
+    batch: &mut LogBatch,
+    control: &MatchControl<'_, LogBatch>,
+    data: &[u8],
+    start: usize,
+    first_line: Option<u64>,
+) {
+    batch.file_id = control.file_id;
+    batch.rewritten = control.transcoded || control.strip_ansi;
+    if let Some(first_line) = first_line {
+        batch.line_numbers =
+            number_lines(data, start, first_line, &batch.line_offsets[..batch.len]);
+    }
+}
+
+/// Totals over every batch parsed, added up as batches come so that the
+/// result need not keep them (`MatchControl::retain_batches`).
+#[derive(Default)]
+struct Totals {
+    lines: usize,
+    levels: LevelSummary,
+    malformed: u64,
+    /// Input bytes of the chunks parsed.
+    bytes: u64,
+}
+
+impl Totals {
+    fn add(&mut self, batch: &LogBatch) {
+        self.lines += batch.len;
+        self.levels.merge(&batch.level_summary);
+        self.malformed += batch.malformed.len() as u64;
+    }
+
+    fn merge(&mut self, other: &Totals) {
+        self.lines += other.lines;
+        self.levels.merge(&other.levels);
+        self.malformed += other.malformed;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Batches come back newest-first under `--reverse`; time ranges must be
+/// merged in file order.
+fn merge_time_ranges(ranges: &[TimeRange], reverse: bool) -> TimeRange {
+    let mut range = TimeRange::default();
+    let mut merge = |other: &TimeRange| range.merge(other);
+    if reverse {
+        ranges.iter().rev().for_each(&mut merge);
+    } else {
+        ranges.iter().for_each(&mut merge);
+    }
+    range
 }
 
 fn parse_chunk(data: &[u8], start: usize, end: usize, data_len: u64) -> (LogBatch, f64, f64) {
@@ -101,17 +227,58 @@ fn parse_chunk(data: &[u8], start: usize, end: usize, data_len: u64) -> (LogBatc
     let mut line_starts = Vec::with_capacity(estimated + 2);
     line_starts.push(start as u64);
     simd_scan::scan_region(chunk, start as u64, data_len, &mut line_starts);
-    line_starts.push(end as u64);
+    // A chunk other than the last ends after a newline, which the scan
+    // already turned into the closing line start.
+    if line_starts.last() != Some(&(end as u64)) {
+        line_starts.push(end as u64);
+    }
     let scan_ms = scan_start.elapsed().as_secs_f64() * 1000.0;
 
     let num_lines = line_starts.len() - 1;
     let parse_start = Instant::now();
     let mut batch = LogBatch::new(num_lines, data.as_ptr());
+    batch.data_len = data.len();
     parse_lines_range(data, &line_starts, 0, num_lines, &mut batch);
+    batch.level_summary = LevelSummary::from_levels(&batch.levels);
+    batch.time_range = TimeRange::from_timestamps(&batch.timestamps[..batch.len]);
     let parse_ms = parse_start.elapsed().as_secs_f64() * 1000.0;
     (batch, scan_ms, parse_ms)
 }
 
+/// Parses `data[start..end]`, which begins on line `first_line` when
+/// numbering, or a copy of it without ANSI escapes under `--strip-ansi`
+/// when it has any; the copy is returned so it outlives the batch pointing
+/// into it.
+fn parse_segment(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    base_offset: u64,
+    first_line: Option<u64>,
+    control: &MatchControl<'_, LogBatch>,
+) -> (LogBatch, f64, f64, Option<Vec<u8>>) {
+    let strip_start = Instant::now();
+    let clean = control
+        .strip_ansi
+        .then(|| ansi::strip(&data[start..end]))
+        .flatten();
+    let strip_ms = strip_start.elapsed().as_secs_f64() * 1000.0;
+    match clean {
+        Some(clean) => {
+            let (mut batch, scan_ms, parse_ms) = parse_owned_chunk(&clean);
+            batch.input_offset = base_offset + start as u64;
+            stamp(&mut batch, control, &clean, 0, first_line);
+            (batch, strip_ms + scan_ms, parse_ms, Some(clean))
+        }
+        None => {
+            let (mut batch, scan_ms, parse_ms) = parse_chunk(data, start, end, data.len() as u64);
+            batch.input_offset = base_offset;
+            stamp(&mut batch, control, data, start, first_line);
+            (batch, strip_ms + scan_ms, parse_ms, None)
+        }
+    }
+}
+
 #[allow(dead_code)]
 fn parse_chunk_streaming(
     data: &[u8],
@@ -123,13 +290,43 @@ fn parse_chunk_streaming(
     (batch.len, scan_ms, parse_ms)
 }
 
-pub fn parse_logs_pipelined(data: &[u8], _num_threads: usize) -> PipelineResult {
+#[allow(dead_code)]
+pub fn parse_logs_pipelined(data: &[u8], num_threads: usize) -> PipelineResult {
+    parse_logs_pipelined_with(data, num_threads, &MatchControl::default())
+}
+
+pub fn parse_logs_pipelined_with(
+    data: &[u8],
+    _num_threads: usize,
+    control: &MatchControl<'_, LogBatch>,
+) -> PipelineResult {
+    let seek_offset = match control.since {
+        Some(ts) => match control.index {
+            Some(index) => index.seek_to_time(data, 0, LogFormat::PlainText, None, ts),
+            None => seek_to_time(data, LogFormat::PlainText, None, ts),
+        },
+        None => 0,
+    };
+    let skipped_lines = if control.line_numbers {
+        count_lines(&data[..seek_offset])
+    } else {
+        0
+    };
+    control.advance(seek_offset);
+    let data = &data[seek_offset..];
+    let base_offset = seek_offset as u64;
     if data.is_empty() {
         return PipelineResult {
             batches: vec![],
             total_lines: 0,
             scan_time_ms: 0.0,
             parse_time_ms: 0.0,
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed_lines: 0,
+            parsed_bytes: 0,
+            hole_bytes: 0,
+            failed_chunks: vec![],
             _backing_data: vec![],
         };
     }
@@ -141,14 +338,15 @@ pub fn parse_logs_pipelined(data: &[u8], _num_threads: usize) -> PipelineResult
         .unwrap_or(64);
     let chunk_size = chunk_mb * 1024 * 1024;
 
+    let ramp = control.limit.limit().is_some() && !control.reverse;
     let mut boundaries = vec![0usize];
-    let mut pos = chunk_size;
+    let mut pos = chunk_len(chunk_size, 0, ramp);
     while pos < data.len() {
-        match memchr::memchr(b'\n', &data[pos..]) {
+        match memchr::memchr(simd_scan::record_sep(), &data[pos..]) {
             Some(off) => {
                 let boundary = pos + off + 1;
                 boundaries.push(boundary);
-                pos = boundary + chunk_size;
+                pos = boundary + chunk_len(chunk_size, boundaries.len() - 1, ramp);
             }
             None => break,
         }
@@ -156,102 +354,225 @@ pub fn parse_logs_pipelined(data: &[u8], _num_threads: usize) -> PipelineResult
     boundaries.push(data.len());
 
     let num_chunks = boundaries.len() - 1;
-    let data_len = data.len() as u64;
 
     let requested_threads = _num_threads.max(1);
     let worker_threads = requested_threads.min(num_chunks.max(1));
+    let first_lines = control
+        .line_numbers
+        .then(|| chunk_first_lines(data, &boundaries, 1 + skipped_lines, requested_threads));
+    let first_line = |chunk: usize, start: usize| {
+        first_lines
+            .as_ref()
+            .map(|lines| lines[chunk] + count_lines(&data[boundaries[chunk]..start]))
+    };
 
     if worker_threads == 1 || num_chunks <= 1 {
-        let mut batches = Vec::with_capacity(num_chunks);
-        let mut scan_time_ms = 0.0_f64;
-        let mut parse_time_ms = 0.0_f64;
-        for i in 0..num_chunks {
-            let start = boundaries[i];
-            let end = boundaries[i + 1];
-            let (batch, scan_ms, parse_ms) = parse_chunk(data, start, end, data_len);
-            scan_time_ms += scan_ms;
-            parse_time_ms += parse_ms;
-            batches.push(batch);
-        }
-        let total_lines = batches.iter().map(|b| b.len).sum();
-        return PipelineResult {
-            batches,
-            total_lines,
-            scan_time_ms,
-            parse_time_ms,
-            _backing_data: vec![],
-        };
-    }
-
-    let mut assignments: Vec<Vec<(usize, usize, usize)>> = vec![Vec::new(); worker_threads];
-    for (worker_idx, assignment) in assignments.iter_mut().enumerate() {
-        let start_chunk = (worker_idx * num_chunks) / worker_threads;
-        let end_chunk = ((worker_idx + 1) * num_chunks) / worker_threads;
-        for i in start_chunk..end_chunk {
-            assignment.push((i, boundaries[i], boundaries[i + 1]));
-        }
+        return control.with_serializers(|handoff| {
+            let mut batches = Vec::with_capacity(num_chunks);
+            let mut ranges = Vec::with_capacity(num_chunks);
+            let mut totals = Totals::default();
+            let mut scan_time_ms = 0.0_f64;
+            let mut parse_time_ms = 0.0_f64;
+            let mut hole_bytes = 0;
+            let mut failed_chunks = Vec::new();
+            let mut backing_data = Vec::new();
+            for (seq, i) in chunk_order(num_chunks, control.reverse)
+                .into_iter()
+                .enumerate()
+            {
+                if control.should_stop() {
+                    break;
+                }
+                control.prefetch(base_offset, boundaries[i], boundaries[i + 1]);
+                let (segments, skipped) =
+                    chunk_segments(data, boundaries[i], boundaries[i + 1], control.reverse);
+                hole_bytes += skipped;
+                for (start, end) in segments {
+                    let Some((mut batch, scan_ms, parse_ms, stripped)) = parse_isolated(|| {
+                        parse_segment(data, start, end, base_offset, first_line(i, start), control)
+                    }) else {
+                        failed_chunks.push(FailedChunk {
+                            start: base_offset + start as u64,
+                            end: base_offset + end as u64,
+                        });
+                        continue;
+                    };
+                    batch.chunk_seq = seq as u64;
+                    scan_time_ms += scan_ms;
+                    parse_time_ms += parse_ms;
+                    let matched = control.visit(&batch);
+                    control.reject(&batch);
+                    totals.add(&batch);
+                    ranges.push(batch.time_range);
+                    let handoff = handoff.filter(|_| stripped.is_none());
+                    if let Some(batch) = control.emit(handoff, batch, matched, seq as u64) {
+                        backing_data.extend(stripped);
+                        batches.push(batch);
+                    }
+                }
+                control.chunk_done(seq as u64);
+                control.advance(boundaries[i + 1] - boundaries[i]);
+                totals.bytes += (boundaries[i + 1] - boundaries[i]) as u64;
+            }
+            PipelineResult {
+                batches,
+                total_lines: totals.lines,
+                scan_time_ms,
+                parse_time_ms,
+                level_summary: totals.levels,
+                time_range: merge_time_ranges(&ranges, control.reverse),
+                malformed_lines: totals.malformed,
+                parsed_bytes: totals.bytes,
+                hole_bytes,
+                failed_chunks,
+                _backing_data: backing_data,
+            }
+        });
     }
 
-    let core_ids = core_affinity::get_core_ids().unwrap_or_default();
-    let enable_pinning = std::env::var("PANDORA_ENABLE_PINNING")
-        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
-        .unwrap_or(false);
-    let pinned_cores = if enable_pinning {
-        choose_pinned_cores(worker_threads, &core_ids)
-    } else {
-        Vec::new()
-    };
-    let mut ordered_batches: Vec<Option<LogBatch>> = (0..num_chunks).map(|_| None).collect();
+    let dispatch = ChunkDispatch::new(
+        num_chunks,
+        worker_threads,
+        control.reverse,
+        control.limit.limit().is_some() || control.ordered.is_some(),
+    );
+
+    // Per chunk, each batch's time range and the batch when kept.
+    let mut ordered: Vec<Vec<(TimeRange, Option<LogBatch>)>> =
+        (0..num_chunks).map(|_| Vec::new()).collect();
+    let mut totals = Totals::default();
     let mut scan_time_ms = 0.0_f64;
     let mut parse_time_ms = 0.0_f64;
+    let mut hole_bytes = 0;
+    let mut failed_chunks = Vec::new();
+    let mut backing_data = Vec::new();
+
+    let boundaries = &boundaries;
+    control.with_serializers(|handoff| {
+        thread::scope(|scope| {
+            let mut handles = Vec::with_capacity(worker_threads);
+            for worker_idx in 0..worker_threads {
+                let dispatch = &dispatch;
+                let worker_core = pinning::core_for_worker(worker_idx);
+
+                handles.push(scope.spawn(move || {
+                    if let Some(core) = worker_core {
+                        let _ = core_affinity::set_for_current(core);
+                    }
+
+                    let mut local = Vec::new();
+                    let mut worker_totals = Totals::default();
+                    let mut worker_scan_ms = 0.0_f64;
+                    let mut worker_parse_ms = 0.0_f64;
+                    let mut worker_holes = 0;
+                    let mut worker_failed = Vec::new();
+                    let mut worker_backing = Vec::new();
+                    for (seq, chunk_idx) in dispatch.worker(worker_idx) {
+                        control.wait_turn(seq as u64);
+                        if control.should_stop() {
+                            break;
+                        }
+                        let (start, end) = (boundaries[chunk_idx], boundaries[chunk_idx + 1]);
+                        control.prefetch(base_offset, start, end);
+                        let (segments, skipped) = chunk_segments(data, start, end, control.reverse);
+                        worker_holes += skipped;
+                        for (start, end) in segments {
+                            let Some((mut batch, chunk_scan_ms, chunk_parse_ms, stripped)) =
+                                parse_isolated(|| {
+                                    parse_segment(
+                                        data,
+                                        start,
+                                        end,
+                                        base_offset,
+                                        first_line(chunk_idx, start),
+                                        control,
+                                    )
+                                })
+                            else {
+                                worker_failed.push(FailedChunk {
+                                    start: base_offset + start as u64,
+                                    end: base_offset + end as u64,
+                                });
+                                continue;
+                            };
+                            batch.chunk_seq = seq as u64;
+                            worker_scan_ms += chunk_scan_ms;
+                            worker_parse_ms += chunk_parse_ms;
+                            let matched = control.visit(&batch);
+                            control.reject(&batch);
+                            worker_totals.add(&batch);
+                            let range = batch.time_range;
+                            let handoff = handoff.filter(|_| stripped.is_none());
+                            let kept = control.emit(handoff, batch, matched, seq as u64);
+                            if kept.is_some() {
+                                worker_backing.extend(stripped);
+                            }
+                            local.push((chunk_idx, range, kept));
+                        }
+                        control.chunk_done(seq as u64);
+                        control.advance(end - start);
+                        worker_totals.bytes += (end - start) as u64;
+                    }
+                    (
+                        local,
+                        worker_totals,
+                        worker_scan_ms,
+                        worker_parse_ms,
+                        worker_holes,
+                        worker_failed,
+                        worker_backing,
+                    )
+                }));
+            }
 
-    thread::scope(|scope| {
-        let mut handles = Vec::with_capacity(worker_threads);
-        for (worker_idx, worker_chunks) in assignments.into_iter().enumerate() {
-            let worker_core = pinned_cores.get(worker_idx).copied();
-
-            handles.push(scope.spawn(move || {
-                if let Some(core) = worker_core {
-                    let _ = core_affinity::set_for_current(core);
-                }
-
-                let mut local = Vec::with_capacity(worker_chunks.len());
-                let mut worker_scan_ms = 0.0_f64;
-                let mut worker_parse_ms = 0.0_f64;
-                for (chunk_idx, start, end) in worker_chunks {
-                    let (batch, chunk_scan_ms, chunk_parse_ms) =
-                        parse_chunk(data, start, end, data_len);
-                    worker_scan_ms += chunk_scan_ms;
-                    worker_parse_ms += chunk_parse_ms;
-                    local.push((chunk_idx, batch));
+            for handle in handles {
+                let (
+                    worker_results,
+                    worker_totals,
+                    worker_scan_ms,
+                    worker_parse_ms,
+                    worker_holes,
+                    worker_failed,
+                    worker_backing,
+                ) = handle.join().expect("worker thread panicked");
+                totals.merge(&worker_totals);
+                scan_time_ms = scan_time_ms.max(worker_scan_ms);
+                parse_time_ms = parse_time_ms.max(worker_parse_ms);
+                hole_bytes += worker_holes;
+                failed_chunks.extend(worker_failed);
+                backing_data.extend(worker_backing);
+                for (chunk_idx, range, batch) in worker_results {
+                    ordered[chunk_idx].push((range, batch));
                 }
-                (local, worker_scan_ms, worker_parse_ms)
-            }));
-        }
-
-        for handle in handles {
-            let (worker_results, worker_scan_ms, worker_parse_ms) =
-                handle.join().expect("worker thread panicked");
-            scan_time_ms = scan_time_ms.max(worker_scan_ms);
-            parse_time_ms = parse_time_ms.max(worker_parse_ms);
-            for (chunk_idx, batch) in worker_results {
-                ordered_batches[chunk_idx] = Some(batch);
             }
-        }
+        });
     });
 
-    let mut batches = Vec::with_capacity(num_chunks);
-    for batch in ordered_batches.into_iter().flatten() {
-        batches.push(batch);
+    // Each chunk's batches are already in parse order, newest-first when
+    // reversing, so only the chunks themselves need flipping.
+    if control.reverse {
+        ordered.reverse();
+    }
+    failed_chunks.sort_unstable_by_key(|chunk| chunk.start);
+    let mut batches = Vec::new();
+    let mut ranges = Vec::with_capacity(num_chunks);
+    for (range, batch) in ordered.into_iter().flatten() {
+        ranges.push(range);
+        batches.extend(batch);
     }
 
-    let total_lines = batches.iter().map(|b| b.len).sum();
     PipelineResult {
         batches,
-        total_lines,
+        total_lines: totals.lines,
         scan_time_ms,
         parse_time_ms,
-        _backing_data: vec![],
+        level_summary: totals.levels,
+        time_range: merge_time_ranges(&ranges, control.reverse),
+        malformed_lines: totals.malformed,
+        parsed_bytes: totals.bytes,
+        hole_bytes,
+        failed_chunks,
+        _backing_data: backing_data,
     }
 }
 
@@ -282,30 +603,42 @@ fn parse_owned_chunk(data: &[u8]) -> (LogBatch, f64, f64) {
     let num_lines = line_starts.len() - 1;
     let parse_start = Instant::now();
     let mut batch = LogBatch::new(num_lines, data.as_ptr());
+    batch.data_len = data.len();
     parse_lines_range(data, &line_starts, 0, num_lines, &mut batch);
+    batch.level_summary = LevelSummary::from_levels(&batch.levels);
+    batch.time_range = TimeRange::from_timestamps(&batch.timestamps[..batch.len]);
     let parse_ms = parse_start.elapsed().as_secs_f64() * 1000.0;
 
     (batch, scan_ms, parse_ms)
 }
 
-pub fn parse_logs_streamed(file: &mut File, file_size: u64, _num_threads: usize) -> PipelineResult {
+#[allow(dead_code)]
+pub fn parse_logs_streamed(file: &mut File, file_size: u64, num_threads: usize) -> PipelineResult {
+    parse_logs_streamed_with(file, file_size, num_threads, &MatchControl::default())
+}
+
+pub fn parse_logs_streamed_with(
+    file: &mut File,
+    file_size: u64,
+    _num_threads: usize,
+    control: &MatchControl<'_, LogBatch>,
+) -> PipelineResult {
     if file_size == 0 {
         return PipelineResult {
             batches: vec![],
             total_lines: 0,
             scan_time_ms: 0.0,
             parse_time_ms: 0.0,
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed_lines: 0,
+            parsed_bytes: 0,
+            hole_bytes: 0,
+            failed_chunks: vec![],
             _backing_data: vec![],
         };
     }
 
-    let segment_size = std::env::var("PANDORA_CHUNK_MB")
-        .ok()
-        .and_then(|v| v.parse::<usize>().ok())
-        .filter(|v| *v >= 1)
-        .map(|mb| mb * 1024 * 1024)
-        .unwrap_or(STREAM_SEGMENT_SIZE);
-
     #[cfg(unix)]
     unsafe {
         use std::os::unix::io::AsRawFd;
@@ -317,17 +650,42 @@ pub fn parse_logs_streamed(file: &mut File, file_size: u64, _num_threads: usize)
         );
     }
 
+    parse_logs_read_with(file, _num_threads, control)
+}
+
+/// Streams plain-text records from any reader, e.g. a transcoder.
+pub fn parse_logs_read_with(
+    reader: &mut impl Read,
+    _num_threads: usize,
+    control: &MatchControl<'_, LogBatch>,
+) -> PipelineResult {
+    let segment_size = std::env::var("PANDORA_CHUNK_MB")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v >= 1)
+        .map(|mb| mb * 1024 * 1024)
+        .unwrap_or(STREAM_SEGMENT_SIZE);
+
     let mut read_buf = vec![0u8; segment_size];
     let mut leftover: Vec<u8> = Vec::new();
 
     let mut result_batches: Vec<LogBatch> = Vec::new();
     let mut backing_data: Vec<Vec<u8>> = Vec::new();
-    let mut total_lines = 0usize;
+    let mut totals = Totals::default();
     let mut total_scan_ms = 0.0_f64;
     let mut total_parse_ms = 0.0_f64;
+    let mut time_range = TimeRange::default();
+    let mut hole_bytes = 0u64;
+    let mut failed_chunks = Vec::new();
+    let mut consumed = 0u64;
+    let mut line = 1u64;
+    let mut seq = 0u64;
 
     loop {
-        let bytes_read = read_full(file, &mut read_buf).unwrap_or(0);
+        if control.should_stop() {
+            break;
+        }
+        let bytes_read = read_full(reader, &mut read_buf).unwrap_or(0);
         let at_eof = bytes_read < segment_size;
 
         let mut work_buf: Vec<u8> = if leftover.is_empty() {
@@ -348,8 +706,10 @@ pub fn parse_logs_streamed(file: &mut File, file_size: u64, _num_threads: usize)
         let complete_end = if at_eof {
             work_buf.len()
         } else {
-            match memchr::memrchr(b'\n', &work_buf) {
+            match memchr::memrchr(simd_scan::record_sep(), &work_buf) {
                 Some(pos) => pos + 1,
+                // A hole ends no line; cut there rather than buffer it all.
+                None if work_buf.ends_with(&[0]) => work_buf.len(),
                 None => {
                     leftover = work_buf;
                     continue;
@@ -368,23 +728,69 @@ pub fn parse_logs_streamed(file: &mut File, file_size: u64, _num_threads: usize)
             }
             continue;
         }
+        let input_len = work_buf.len();
+        if control.strip_ansi
+            && let Some(clean) = ansi::strip(&work_buf)
+        {
+            work_buf = clean;
+        }
 
-        let (batch, scan_ms, parse_ms) = parse_owned_chunk(&work_buf);
-        total_lines += batch.len;
-        total_scan_ms += scan_ms;
-        total_parse_ms += parse_ms;
-
-        if result_batches.is_empty() {
-            result_batches.push(batch);
+        let (segments, skipped) = holes::data_segments(&work_buf, 0, work_buf.len());
+        hole_bytes += skipped;
+        let mut keep = false;
+        for (start, end) in segments {
+            let Some((mut batch, scan_ms, parse_ms)) =
+                parse_isolated(|| parse_owned_chunk(&work_buf[start..end]))
+            else {
+                failed_chunks.push(FailedChunk {
+                    start: consumed + start as u64,
+                    end: consumed + end as u64,
+                });
+                continue;
+            };
+            batch.input_offset = consumed + start as u64;
+            let first_line = control
+                .line_numbers
+                .then(|| line + count_lines(&work_buf[..start]));
+            stamp(&mut batch, control, &work_buf[start..end], 0, first_line);
+            batch.chunk_seq = seq;
+            let matched = control.visit(&batch);
+            control.serialize(&batch, &matched);
+            control.reject(&batch);
+            totals.add(&batch);
+            total_scan_ms += scan_ms;
+            total_parse_ms += parse_ms;
+            time_range.merge(&batch.time_range);
+
+            if result_batches.is_empty() {
+                result_batches.push(batch);
+                keep = true;
+            }
+        }
+        control.chunk_done(seq);
+        seq += 1;
+        control.advance(input_len);
+        totals.bytes += input_len as u64;
+        consumed += work_buf.len() as u64;
+        if control.line_numbers {
+            line += count_lines(&work_buf);
+        }
+        if keep {
             backing_data.push(work_buf);
         }
     }
 
     PipelineResult {
         batches: result_batches,
-        total_lines,
+        total_lines: totals.lines,
         scan_time_ms: total_scan_ms,
         parse_time_ms: total_parse_ms,
+        level_summary: totals.levels,
+        time_range,
+        malformed_lines: totals.malformed,
+        parsed_bytes: totals.bytes,
+        hole_bytes,
+        failed_chunks,
         _backing_data: backing_data,
     }
 }
@@ -409,7 +815,7 @@ pub fn parse_logs_pipelined_streaming(data: &[u8], _num_threads: usize) -> Strea
     let mut boundaries = vec![0usize];
     let mut pos = chunk_size;
     while pos < data.len() {
-        match memchr::memchr(b'\n', &data[pos..]) {
+        match memchr::memchr(simd_scan::record_sep(), &data[pos..]) {
             Some(off) => {
                 let boundary = pos + off + 1;
                 boundaries.push(boundary);
@@ -454,16 +860,6 @@ pub fn parse_logs_pipelined_streaming(data: &[u8], _num_threads: usize) -> Strea
         }
     }
 
-    let core_ids = core_affinity::get_core_ids().unwrap_or_default();
-    let enable_pinning = std::env::var("PANDORA_ENABLE_PINNING")
-        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
-        .unwrap_or(false);
-    let pinned_cores = if enable_pinning {
-        choose_pinned_cores(worker_threads, &core_ids)
-    } else {
-        Vec::new()
-    };
-
     let mut total_lines = 0usize;
     let mut scan_time_ms = 0.0_f64;
     let mut parse_time_ms = 0.0_f64;
@@ -471,7 +867,7 @@ pub fn parse_logs_pipelined_streaming(data: &[u8], _num_threads: usize) -> Strea
     thread::scope(|scope| {
         let mut handles = Vec::with_capacity(worker_threads);
         for (worker_idx, worker_chunks) in assignments.into_iter().enumerate() {
-            let worker_core = pinned_cores.get(worker_idx).copied();
+            let worker_core = pinning::core_for_worker(worker_idx);
 
             handles.push(scope.spawn(move || {
                 if let Some(core) = worker_core {
@@ -522,6 +918,8 @@ mod tests {
 
         let result = parse_logs_pipelined(data, 2);
         assert_eq!(result.total_lines, 3);
+        assert_eq!(result.level_summary.count(LogLevel::Warn), 1);
+        assert_eq!(result.batches[0].level_summary.count(LogLevel::Error), 1);
 
         let first = &result.batches[0];
         assert_eq!(first.levels[0], LogLevel::Info);
@@ -560,6 +958,183 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pipelined_parse_stops_at_limit() {
+        use crate::filter::MatchLimit;
+
+        let mut data = Vec::new();
+        for _ in 0..1000 {
+            data.extend_from_slice(b"2025-02-12T10:31:45Z INFO api-server request_id=abc123\n");
+        }
+
+        let control = MatchControl {
+            limit: MatchLimit::new(5),
+            ..MatchControl::default()
+        };
+        let result = parse_logs_pipelined_with(&data, 1, &control);
+
+        assert!(control.should_stop());
+        assert_eq!(control.limit.matched(), 5);
+        assert_eq!(result.batches.len(), 1);
+
+        let rerun = parse_logs_pipelined_with(&data, 1, &control);
+        assert!(rerun.batches.is_empty());
+    }
+
+    #[test]
+    fn test_limit_parses_leading_chunks_only() {
+        use crate::filter::MatchLimit;
+
+        // 8 MB: chunks of 1, 2, 4 and 1 MB under a limit.
+        let line = b"2025-02-12T10:31:45Z INFO api-server request_id=abc123\n";
+        let data = line.repeat(8 * HEAD_CHUNK_SIZE / line.len());
+        let control = MatchControl {
+            limit: MatchLimit::new(10),
+            ..MatchControl::default()
+        };
+        let result = parse_logs_pipelined_with(&data, 2, &control);
+        assert_eq!(control.limit.matched(), 10);
+        assert_eq!(result.batches[0].input_offset, 0);
+        assert!(result.total_lines <= 3 * HEAD_CHUNK_SIZE / line.len() + 2);
+
+        let run = ChunkDispatch::new(5, 2, false, false);
+        assert_eq!(run.worker(1).collect::<Vec<_>>(), [(2, 2), (3, 3), (4, 4)]);
+        let in_order = ChunkDispatch::new(3, 2, true, true);
+        assert_eq!(in_order.worker(0).next(), Some((0, 2)));
+        assert_eq!(in_order.worker(1).collect::<Vec<_>>(), [(1, 1), (2, 0)]);
+        assert_eq!(chunk_len(64 << 20, 2, true), 4 << 20);
+        assert_eq!(chunk_len(64 << 20, 40, true), 64 << 20);
+    }
+
+    #[test]
+    fn test_unretained_batches_still_counted() {
+        let line = b"2025-02-12T10:31:45Z ERROR api-server disk full\n";
+        let data = line.repeat(3 * HEAD_CHUNK_SIZE / line.len());
+        let lines = data.len() / line.len();
+        for threads in [1, 4] {
+            // A limit ramps chunks up from 1 MB, so the input spans several.
+            let control = MatchControl {
+                retain_batches: false,
+                limit: crate::filter::MatchLimit::new(u64::MAX - 1),
+                ..MatchControl::default()
+            };
+            let result = parse_logs_pipelined_with(&data, threads, &control);
+            assert!(result.batches.iter().all(|b| b.chunk_seq == 0));
+            assert!(result.batches.iter().map(|b| b.len).sum::<usize>() < lines);
+            assert_eq!(result.total_lines, lines);
+            assert_eq!(result.level_summary.count(LogLevel::Error), lines as u64);
+            assert_eq!(result.time_range.min, 1739356305);
+            assert_eq!(result.parsed_bytes, data.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_unretained_batches_serialized_off_parse_threads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let line = b"2025-02-12T10:31:45Z ERROR api-server disk full\n";
+        let data = line.repeat(3 * HEAD_CHUNK_SIZE / line.len());
+        let lines = data.len() / line.len();
+        let caller = std::thread::current().id();
+        for threads in [1, 4] {
+            let emitted = AtomicUsize::new(0);
+            let off_caller = AtomicUsize::new(0);
+            let serialize = |batch: &LogBatch, matched: &[u32]| {
+                assert!(matched.iter().all(|&i| (i as usize) < batch.len));
+                emitted.fetch_add(matched.len(), Ordering::Relaxed);
+                if std::thread::current().id() != caller {
+                    off_caller.fetch_add(matched.len(), Ordering::Relaxed);
+                }
+            };
+            let control = MatchControl {
+                retain_batches: false,
+                limit: crate::filter::MatchLimit::new(u64::MAX - 1),
+                on_emit: Some(&serialize),
+                serialize_threads: 2,
+                ..MatchControl::default()
+            };
+            let result = parse_logs_pipelined_with(&data, threads, &control);
+            assert_eq!(result.total_lines, lines);
+            assert_eq!(emitted.load(Ordering::Relaxed), lines);
+            assert!(off_caller.load(Ordering::Relaxed) > 0);
+        }
+    }
+
+    #[test]
+    fn test_chunk_order() {
+        assert_eq!(chunk_order(3, false), vec![0, 1, 2]);
+        assert_eq!(chunk_order(3, true), vec![2, 1, 0]);
+        assert!(chunk_order(0, true).is_empty());
+    }
+
+    #[test]
+    fn test_pipelined_reverse_visits_newest_first() {
+        use crate::filter::MatchLimit;
+        use std::sync::Mutex;
+
+        let data = b"2025-02-12T10:31:45Z INFO a first\n\
+                     2025-02-12T10:31:46Z INFO b second\n\
+                     2025-02-12T10:31:47Z INFO c third\n";
+        let seen = Mutex::new(Vec::new());
+        let on_match = |batch: &LogBatch, i: usize| seen.lock().unwrap().push(batch.timestamps[i]);
+        let control = MatchControl {
+            limit: MatchLimit::new(2),
+            on_match: Some(&on_match),
+            reverse: true,
+            ..MatchControl::default()
+        };
+        parse_logs_pipelined_with(data, 1, &control);
+
+        assert_eq!(*seen.lock().unwrap(), vec![1739356307, 1739356306]);
+    }
+
+    #[test]
+    fn test_pipelined_strip_ansi() {
+        let data = b"2025-02-12T10:31:45Z \x1b[31mERROR\x1b[0m \x1b[1mapi-server\x1b[0m boom\n\
+                     2025-02-12T10:31:46Z INFO auth-service ok\n";
+        let control = MatchControl {
+            strip_ansi: true,
+            ..MatchControl::default()
+        };
+        let result = parse_logs_pipelined_with(data, 1, &control);
+        let batch = &result.batches[0];
+        assert_eq!(batch.levels[0], LogLevel::Error);
+        unsafe {
+            assert_eq!(batch.component(0), "api-server");
+            assert_eq!(batch.component(1), "auth-service");
+        }
+        assert_eq!(result._backing_data.len(), 1);
+    }
+
+    #[test]
+    fn test_pipelined_since_skips_older_records() {
+        let data = b"2025-02-12T10:31:45Z INFO a first\n\
+                     2025-02-12T10:31:46Z INFO b second\n\
+                     2025-02-12T10:31:47Z INFO c third\n";
+        let control = MatchControl {
+            since: Some(1739356306),
+            ..MatchControl::default()
+        };
+        let result = parse_logs_pipelined_with(data, 1, &control);
+
+        assert_eq!(result.total_lines, 2);
+        assert_eq!(result.batches[0].timestamps[..2], [1739356306, 1739356307]);
+        assert_eq!(control.limit.matched(), 2);
+    }
+
+    #[test]
+    fn test_pipelined_time_range() {
+        let data = b"2025-02-12T10:31:45Z INFO a first\n\
+                     2025-02-12T10:31:50Z INFO b second\n\
+                     2025-02-12T10:31:47Z INFO c third\n";
+        let result = parse_logs_pipelined(data, 1);
+
+        assert_eq!(result.time_range.min, 1739356305);
+        assert_eq!(result.time_range.max, 1739356310);
+        assert_eq!(result.time_range.max_regression, 3);
+        assert_eq!(result.batches[0].time_range, result.time_range);
+    }
+
     #[test]
     fn test_pipelined_parse_large() {
         let mut data = Vec::new();
@@ -576,4 +1151,22 @@ mod tests {
             assert_eq!(first.component(0), "api-server");
         }
     }
+
+    #[test]
+    fn test_parse_isolated_retries_once() {
+        let attempts = std::cell::Cell::new(0);
+        let flaky = parse_isolated(|| {
+            attempts.set(attempts.get() + 1);
+            assert!(attempts.get() > 1, "first attempt fails");
+            7
+        });
+        assert_eq!((flaky, attempts.get()), (Some(7), 2));
+
+        attempts.set(0);
+        let broken = parse_isolated(|| {
+            attempts.set(attempts.get() + 1);
+            panic!("always fails")
+        });
+        assert_eq!((broken, attempts.get()), (None::<()>, 2));
+    }
 }
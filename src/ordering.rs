@@ -0,0 +1,155 @@
+use crate::data::BatchRecords;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComponentSkew {
+    pub records: u64,
+    pub out_of_order: u64,
+    /// Furthest this component's records fell behind the newest timestamp
+    /// written by any component before them.
+    pub max_lag: u64,
+}
+
+/// Chronological-order check over records in file order. A record is out of
+/// order when it is older than the newest timestamp seen before it.
+#[derive(Debug, Default)]
+pub struct OrderingReport {
+    pub records: u64,
+    pub out_of_order: u64,
+    pub largest_backwards_jump: u64,
+    /// Position (in file order, counting every record) of the largest jump.
+    pub largest_jump_at: u64,
+    pub components: HashMap<Vec<u8>, ComponentSkew>,
+    position: u64,
+    newest: u64,
+    previous: Option<u64>,
+}
+
+impl OrderingReport {
+    pub fn from_batches<'b, B: BatchRecords + 'b>(
+        batches: impl IntoIterator<Item = &'b B>,
+    ) -> Self {
+        let mut report = OrderingReport::default();
+        for batch in batches {
+            for i in 0..batch.record_count() {
+                match batch.record_timestamp(i) {
+                    Some(ts) => report.record(ts, batch.record_component(i)),
+                    None => report.position += 1,
+                }
+            }
+        }
+        report
+    }
+
+    pub fn record(&mut self, ts: u64, component: Option<&[u8]>) {
+        let lag = self.newest.saturating_sub(ts);
+        if let Some(previous) = self.previous
+            && previous > ts
+            && previous - ts > self.largest_backwards_jump
+        {
+            self.largest_backwards_jump = previous - ts;
+            self.largest_jump_at = self.position;
+        }
+
+        let skew = self
+            .components
+            .entry(component.unwrap_or(b"-").to_vec())
+            .or_default();
+        skew.records += 1;
+        if lag > 0 {
+            self.out_of_order += 1;
+            skew.out_of_order += 1;
+            skew.max_lag = skew.max_lag.max(lag);
+        }
+
+        self.records += 1;
+        self.position += 1;
+        self.newest = self.newest.max(ts);
+        self.previous = Some(ts);
+    }
+
+    /// Components ordered by how far behind they ran, worst first.
+    pub fn worst_components(&self) -> Vec<(&[u8], &ComponentSkew)> {
+        let mut components: Vec<_> = self
+            .components
+            .iter()
+            .filter(|(_, skew)| skew.out_of_order > 0)
+            .map(|(name, skew)| (name.as_slice(), skew))
+            .collect();
+        components.sort_by(|a, b| b.1.max_lag.cmp(&a.1.max_lag).then(a.0.cmp(b.0)));
+        components
+    }
+}
+
+impl fmt::Display for OrderingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Ordering: {} of {} timestamped records out of order",
+            self.out_of_order, self.records
+        )?;
+        if self.largest_backwards_jump > 0 {
+            writeln!(
+                f,
+                "  Largest backwards jump: {} s at record {}",
+                self.largest_backwards_jump, self.largest_jump_at
+            )?;
+        }
+        for (name, skew) in self.worst_components().into_iter().take(10) {
+            writeln!(
+                f,
+                "  {:>20} | {:>8} of {:>8} behind | max lag {} s",
+                String::from_utf8_lossy(name),
+                skew.out_of_order,
+                skew.records,
+                skew.max_lag
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering_report() {
+        let mut report = OrderingReport::default();
+        for (ts, component) in [
+            (100, "api"),
+            (105, "api"),
+            (101, "db"),
+            (106, "api"),
+            (102, "db"),
+            (107, "api"),
+        ] {
+            report.record(ts, Some(component.as_bytes()));
+        }
+
+        assert_eq!(report.records, 6);
+        assert_eq!(report.out_of_order, 2);
+        assert_eq!(report.largest_backwards_jump, 4);
+        assert_eq!(report.largest_jump_at, 2);
+
+        let worst = report.worst_components();
+        assert_eq!(worst.len(), 1);
+        assert_eq!(worst[0].0, b"db");
+        assert_eq!(worst[0].1.max_lag, 4);
+        assert_eq!(worst[0].1.records, 2);
+    }
+
+    #[test]
+    fn test_ordering_report_from_batches() {
+        let data = b"2025-02-12T10:31:45Z INFO api a\n\
+                     2025-02-12T10:31:50Z INFO api b\n\
+                     2025-02-12T10:31:47Z WARN worker c\n";
+        let result = crate::orchestrator::parse_logs_pipelined(data, 1);
+        let report = OrderingReport::from_batches(&result.batches);
+
+        assert_eq!(report.out_of_order, 1);
+        assert_eq!(report.largest_backwards_jump, 3);
+        assert_eq!(report.components[b"worker".as_slice()].max_lag, 3);
+    }
+}
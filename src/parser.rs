@@ -1,11 +1,54 @@
-use crate::data::{LogBatch, LogLevel};
+use crate::data::{LineSpan, LogBatch, LogLevel};
+use crate::simd_scan::Kernel;
+use crate::syslog::Pri;
 
-#[inline(always)]
-fn parse_timestamp_fast(b: &[u8]) -> u64 {
-    if b.len() < 20 {
-        return 0;
+/// Parses a `YYYY-MM-DDTHH:MM:SS` timestamp after checking the layout (a
+/// space is accepted in place of `T`).
+#[inline]
+pub fn parse_timestamp(b: &[u8]) -> Option<u64> {
+    const DIGITS: [usize; 14] = [0, 1, 2, 3, 5, 6, 8, 9, 11, 12, 14, 15, 17, 18];
+    if b.len() < 19
+        || b[4] != b'-'
+        || b[7] != b'-'
+        || !matches!(b[10], b'T' | b' ')
+        || b[13] != b':'
+        || b[16] != b':'
+        || DIGITS.iter().any(|&i| !b[i].is_ascii_digit())
+    {
+        return None;
     }
+    Some(timestamp_from_layout(b))
+}
+
+/// Parses a structured timestamp value: the `parse_timestamp` layout or a
+/// Unix epoch number. Epochs in seconds, milliseconds, microseconds and
+/// nanoseconds are told apart by magnitude; any fraction is dropped.
+#[inline]
+pub fn parse_timestamp_value(b: &[u8]) -> Option<u64> {
+    parse_timestamp(b).or_else(|| parse_epoch(b))
+}
+
+#[inline]
+fn parse_epoch(b: &[u8]) -> Option<u64> {
+    let int = match memchr::memchr(b'.', b) {
+        Some(dot) if b[dot + 1..].iter().all(u8::is_ascii_digit) => &b[..dot],
+        Some(_) => return None,
+        None => b,
+    };
+    if int.is_empty() || int.len() > 19 || !int.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let n = int.iter().fold(0u64, |n, &d| n * 10 + u64::from(d - b'0'));
+    Some(match n {
+        0..100_000_000_000 => n,
+        100_000_000_000..100_000_000_000_000 => n / 1_000,
+        100_000_000_000_000..100_000_000_000_000_000 => n / 1_000_000,
+        _ => n / 1_000_000_000,
+    })
+}
 
+#[inline(always)]
+fn timestamp_from_layout(b: &[u8]) -> u64 {
     let year = swar_parse_4(b, 0) as i64;
     let month = swar_parse_2(b, 5);
     let day = swar_parse_2(b, 8);
@@ -14,6 +57,11 @@ fn parse_timestamp_fast(b: &[u8]) -> u64 {
     let min = (hms / 100) % 100;
     let sec = hms % 100;
 
+    epoch_seconds(year, month, day, hour, min, sec)
+}
+
+#[inline(always)]
+fn epoch_seconds(year: i64, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> u64 {
     let mut days = 0i64;
     let y = year - 1970;
     days += y * 365;
@@ -71,6 +119,145 @@ fn is_leap_year(y: i64) -> bool {
     (y % 4 == 0 && y % 100 != 0) || (y % 400 == 0)
 }
 
+// Parses four `YYYY-MM-DDTHH:MM:SS` prefixes at once. Each line must be at
+// least 19 bytes long. Returns a lane mask of the timestamps whose separators
+// and digits matched the layout; other lanes are left untouched in `out`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn parse_timestamps_x4_avx2(lines: [&[u8]; 4], out: &mut [u64; 4]) -> u8 {
+    unsafe {
+        use std::arch::x86_64::*;
+
+        // Low load covers bytes 0..16, high load covers bytes 3..19 so the
+        // seconds digits (17, 18) land in lanes 14 and 15.
+        let load = |a: &[u8], b: &[u8], off: usize| {
+            _mm256_set_m128i(
+                _mm_loadu_si128(b.as_ptr().add(off) as *const __m128i),
+                _mm_loadu_si128(a.as_ptr().add(off) as *const __m128i),
+            )
+        };
+
+        let zero = b'0' as i8;
+        let sep_lo = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            0, 0, 0, 0, b'-' as i8, 0, 0, b'-' as i8, 0, 0, b'T' as i8, 0, 0, b':' as i8, 0, 0,
+        ));
+        let sep_lo_mask = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            0, 0, 0, 0, -1, 0, 0, -1, 0, 0, -1, 0, 0, -1, 0, 0,
+        ));
+        let sep_hi = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, b':' as i8, 0, 0,
+        ));
+        let sep_hi_mask = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, -1, 0, 0,
+        ));
+        let digit_lo_mask = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            -1, -1, -1, -1, 0, -1, -1, 0, -1, -1, 0, -1, -1, 0, -1, -1,
+        ));
+        let digit_hi_mask = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, -1, -1,
+        ));
+        let gather_lo = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            0, 1, 2, 3, 5, 6, 8, 9, 11, 12, 14, 15, -1, -1, -1, -1,
+        ));
+        let gather_hi = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 14, 15, -1, -1,
+        ));
+        let weights = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+            10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1, 10, 1,
+        ));
+        let nine = _mm256_set1_epi8(9);
+        let zeros = _mm256_set1_epi8(zero);
+
+        let mut valid = 0u8;
+        for pair in 0..2 {
+            let a = lines[pair * 2];
+            let b = lines[pair * 2 + 1];
+            let lo = load(a, b, 0);
+            let hi = load(a, b, 3);
+
+            let sep_ok = _mm256_and_si256(
+                _mm256_cmpeq_epi8(_mm256_and_si256(lo, sep_lo_mask), sep_lo),
+                _mm256_cmpeq_epi8(_mm256_and_si256(hi, sep_hi_mask), sep_hi),
+            );
+
+            let d_lo = _mm256_sub_epi8(lo, zeros);
+            let d_hi = _mm256_sub_epi8(hi, zeros);
+            let lo_ok = _mm256_cmpeq_epi8(_mm256_max_epu8(d_lo, nine), nine);
+            let hi_ok = _mm256_cmpeq_epi8(_mm256_max_epu8(d_hi, nine), nine);
+            let digits_ok = _mm256_and_si256(
+                _mm256_or_si256(
+                    lo_ok,
+                    _mm256_andnot_si256(digit_lo_mask, _mm256_set1_epi8(-1)),
+                ),
+                _mm256_or_si256(
+                    hi_ok,
+                    _mm256_andnot_si256(digit_hi_mask, _mm256_set1_epi8(-1)),
+                ),
+            );
+
+            let ok = _mm256_movemask_epi8(_mm256_and_si256(sep_ok, digits_ok)) as u32;
+
+            let digits = _mm256_or_si256(
+                _mm256_shuffle_epi8(d_lo, gather_lo),
+                _mm256_shuffle_epi8(d_hi, gather_hi),
+            );
+            let pairs = _mm256_maddubs_epi16(digits, weights);
+
+            let mut parts = [0u16; 16];
+            _mm256_storeu_si256(parts.as_mut_ptr() as *mut __m256i, pairs);
+
+            for lane in 0..2 {
+                if (ok >> (lane * 16)) & 0xFFFF != 0xFFFF {
+                    continue;
+                }
+                let p = &parts[lane * 8..lane * 8 + 8];
+                let year = p[0] as i64 * 100 + p[1] as i64;
+                out[pair * 2 + lane] = epoch_seconds(
+                    year,
+                    p[2] as u32,
+                    p[3] as u32,
+                    p[4] as u32,
+                    p[5] as u32,
+                    p[6] as u32,
+                );
+                valid |= 1 << (pair * 2 + lane);
+            }
+        }
+        valid
+    }
+}
+
+/// Four `YYYY-MM-DDTHH:MM:SS` prefixes parsed with a given kernel, as the
+/// plain parser batches them; the scalar kernel parses each on its own.
+/// Returns the lane mask of valid timestamps, or `None` when the CPU lacks
+/// the kernel or it has no such variant.
+pub fn parse_timestamps_x4_with(
+    kernel: Kernel,
+    lines: [&[u8]; 4],
+    out: &mut [u64; 4],
+) -> Option<u8> {
+    if !kernel.supported() || lines.iter().any(|line| line.len() < 19) {
+        return None;
+    }
+    match kernel {
+        Kernel::Scalar => {
+            let mut valid = 0;
+            for (k, line) in lines.iter().enumerate() {
+                if line[10] == b'T'
+                    && let Some(ts) = parse_timestamp(line)
+                {
+                    out[k] = ts;
+                    valid |= 1 << k;
+                }
+            }
+            Some(valid)
+        }
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => Some(unsafe { parse_timestamps_x4_avx2(lines, out) }),
+        _ => None,
+    }
+}
+
 #[inline(always)]
 fn find_first_3_spaces(line: &[u8]) -> [usize; 3] {
     let mut result = [usize::MAX; 3];
@@ -87,9 +274,343 @@ fn find_first_3_spaces(line: &[u8]) -> [usize; 3] {
     result
 }
 
+/// Line layouts the plain parser recognises. Each line is checked on its
+/// own, so a file may mix them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlainLayout {
+    /// `2025-02-12T10:31:45Z ERROR api-server message`
+    Spaced,
+    /// `[2025-02-12 10:31:45] [ERROR] [api-server] message`
+    Bracketed,
+    /// `time="2025-02-12T10:31:45Z" level=error component=api-server message`
+    Labeled,
+    /// `<11>1 2025-02-12T10:31:45Z host api-server - - - message`, or the
+    /// older `<11>Feb 12 10:31:45 host api-server[42]: message`
+    Syslog,
+}
+
+impl PlainLayout {
+    #[inline]
+    pub fn detect(line: &[u8]) -> PlainLayout {
+        match line.first() {
+            None | Some(b'0'..=b'9') => PlainLayout::Spaced,
+            Some(b'[') => PlainLayout::Bracketed,
+            Some(b'<') if Pri::prefix(line).is_some() => PlainLayout::Syslog,
+            Some(_) if prefix_label(line, 0).is_some() => PlainLayout::Labeled,
+            Some(_) => PlainLayout::Spaced,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Slot {
+    Timestamp,
+    Level,
+    Component,
+    Message,
+}
+
+const PREFIX_LABELS: &[(&[u8], Slot)] = &[
+    (b"time", Slot::Timestamp),
+    (b"ts", Slot::Timestamp),
+    (b"timestamp", Slot::Timestamp),
+    (b"level", Slot::Level),
+    (b"lvl", Slot::Level),
+    (b"severity", Slot::Level),
+    (b"component", Slot::Component),
+    (b"logger", Slot::Component),
+    (b"module", Slot::Component),
+    (b"service", Slot::Component),
+    (b"msg", Slot::Message),
+    (b"message", Slot::Message),
+];
+
+/// The slot named by a `label=` at `pos`, and where its value starts.
+fn prefix_label(line: &[u8], pos: usize) -> Option<(Slot, usize)> {
+    let rest = &line[pos..];
+    PREFIX_LABELS.iter().find_map(|&(label, slot)| {
+        (rest.len() > label.len() && rest.starts_with(label) && rest[label.len()] == b'=')
+            .then_some((slot, pos + label.len() + 1))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Bracketed,
+    Labeled(Slot),
+    Bare,
+}
+
+/// Reads the prefix token at `pos`: `[value]`, `label=value`,
+/// `label="value"` or a bare word. Returns its kind, the value range and
+/// where the following token starts.
+fn prefix_token(line: &[u8], pos: usize) -> (TokenKind, usize, usize, usize) {
+    let word_end =
+        |from: usize| memchr::memchr(b' ', &line[from..]).map_or(line.len(), |p| from + p);
+    let (kind, start, end, after) = if line[pos] == b'[' {
+        match memchr::memchr(b']', &line[pos + 1..]) {
+            Some(p) => (TokenKind::Bracketed, pos + 1, pos + 1 + p, pos + 2 + p),
+            None => (TokenKind::Bracketed, pos + 1, line.len(), line.len()),
+        }
+    } else if let Some((slot, value)) = prefix_label(line, pos) {
+        if line.get(value) == Some(&b'"') {
+            let mut i = value + 1;
+            while i < line.len() && line[i] != b'"' {
+                i += if line[i] == b'\\' { 2 } else { 1 };
+            }
+            let end = i.min(line.len());
+            (
+                TokenKind::Labeled(slot),
+                value + 1,
+                end,
+                (end + 1).min(line.len()),
+            )
+        } else {
+            let end = word_end(value);
+            (TokenKind::Labeled(slot), value, end, end)
+        }
+    } else {
+        let end = word_end(pos);
+        (TokenKind::Bare, pos, end, end)
+    };
+    let mut next = after;
+    while next < line.len() && line[next] == b' ' {
+        next += 1;
+    }
+    (kind, start, end, next)
+}
+
+/// Parses a `Bracketed` or `Labeled` line. Tokens fill the timestamp, level
+/// and component slots in order unless their label names another one; a
+/// bare word only counts as the component after a bare level, so
+/// `[ts] [ERROR] disk full` has no component.
+fn parse_line_prefixed(line: &[u8], index: usize, batch: &mut LogBatch, base_offset: u64) {
+    let mut timestamp = None;
+    let mut level = LogLevel::Unknown;
+    let mut component = (line.len(), line.len());
+    let mut message = (line.len(), line.len());
+    let mut next_slot = Slot::Timestamp;
+    let mut prev_bare = true;
+    let mut pos = 0;
+
+    while pos < line.len() {
+        let (kind, start, end, next) = prefix_token(line, pos);
+        let slot = match kind {
+            TokenKind::Labeled(slot) => slot,
+            TokenKind::Bare if next_slot == Slot::Component && !prev_bare => Slot::Message,
+            _ => next_slot,
+        };
+        match slot {
+            Slot::Timestamp => timestamp = parse_timestamp(&line[start..end]),
+            Slot::Level => level = LogLevel::from_name(&line[start..end]),
+            Slot::Component => component = (start, end),
+            Slot::Message => {
+                // A quoted message that ends the line drops its quotes;
+                // otherwise the message runs to the end of the line.
+                message = match kind {
+                    TokenKind::Labeled(_) if next >= line.len() => (start, end),
+                    TokenKind::Labeled(_) => (start, line.len()),
+                    _ => (pos, line.len()),
+                };
+                break;
+            }
+        }
+        prev_bare = kind == TokenKind::Bare;
+        next_slot = next_slot.max(slot);
+        next_slot = match next_slot {
+            Slot::Timestamp => Slot::Level,
+            Slot::Level => Slot::Component,
+            _ => Slot::Message,
+        };
+        pos = next;
+        if next_slot == Slot::Message {
+            message = (pos, line.len());
+            break;
+        }
+    }
+
+    store_record(
+        line,
+        index,
+        batch,
+        base_offset,
+        (timestamp, level),
+        component,
+        message,
+    );
+}
+
+const MONTHS: [&[u8]; 12] = [
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+/// Parses a `Syslog` line. The level is the PRI severity and the component
+/// the app name or tag. RFC 3164 timestamps have no year; they are dated by
+/// [`rfc3164_timestamp`](crate::syslog::rfc3164_timestamp).
+fn parse_line_syslog(line: &[u8], index: usize, batch: &mut LogBatch, base_offset: u64) {
+    let Some((pri, mut pos)) = Pri::prefix(line) else {
+        parse_line_prefixed(line, index, batch, base_offset);
+        return;
+    };
+    // The word at `from` and where the next one starts.
+    let word = |from: usize| {
+        let end = memchr::memchr(b' ', &line[from..]).map_or(line.len(), |p| from + p);
+        let mut next = end;
+        while next < line.len() && line[next] == b' ' {
+            next += 1;
+        }
+        (from, end, next)
+    };
+    let mut timestamp = None;
+    let mut component = (line.len(), line.len());
+
+    if line[pos..].starts_with(b"1 ") {
+        // TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+        let (start, end, next) = word(pos + 2);
+        timestamp = parse_timestamp(&line[start..end]);
+        let (_, _, next) = word(next);
+        let (start, end, next) = word(next);
+        if &line[start..end] != b"-" {
+            component = (start, end);
+        }
+        let (_, _, next) = word(next);
+        let (_, _, next) = word(next);
+        pos = next;
+        if line.get(pos) == Some(&b'-') {
+            pos = word(pos).2;
+        } else {
+            while line.get(pos) == Some(&b'[') {
+                pos += 1;
+                while pos < line.len() && line[pos] != b']' {
+                    pos += if line[pos] == b'\\' { 2 } else { 1 };
+                }
+                pos = (pos + 1).min(line.len());
+            }
+            if line.get(pos) == Some(&b' ') {
+                pos += 1;
+            }
+        }
+    } else if pos < line.len() {
+        // TIMESTAMP HOSTNAME TAG[PID]: MSG
+        let (start, end, next) = word(pos);
+        timestamp = parse_timestamp(&line[start..end]);
+        pos = if timestamp.is_some() {
+            next
+        } else if let Some(month) = MONTHS.iter().position(|&m| m == &line[start..end]) {
+            let (day_start, day_end, next) = word(next);
+            let (time_start, time_end, next) = word(next);
+            timestamp = crate::syslog::rfc3164_timestamp(
+                month as u32 + 1,
+                &line[day_start..day_end],
+                &line[time_start..time_end],
+            );
+            next
+        } else {
+            start
+        };
+        if pos != start {
+            let (_, _, next) = word(pos);
+            let (start, end, after) = word(next);
+            if end > start && line[end - 1] == b':' {
+                let tag_end =
+                    memchr::memchr(b'[', &line[start..end]).map_or(end - 1, |p| start + p);
+                component = (start, tag_end);
+                pos = after;
+            } else {
+                pos = next;
+            }
+        }
+    }
+    let message = (pos.min(line.len()), line.len());
+    store_record(
+        line,
+        index,
+        batch,
+        base_offset,
+        (timestamp, pri.level()),
+        component,
+        message,
+    );
+}
+
+/// Writes one prefixed or syslog record; a missing timestamp marks the line
+/// malformed.
+fn store_record(
+    line: &[u8],
+    index: usize,
+    batch: &mut LogBatch,
+    base_offset: u64,
+    (timestamp, level): (Option<u64>, LogLevel),
+    component: (usize, usize),
+    message: (usize, usize),
+) {
+    match timestamp {
+        Some(ts) => batch.timestamps[index] = ts,
+        None => {
+            batch.timestamps[index] = 0;
+            batch.malformed.push(LineSpan {
+                offset: base_offset,
+                len: line.len() as u32,
+            });
+        }
+    }
+    batch.levels[index] = level;
+    batch.component_offsets[index] = base_offset + component.0 as u64;
+    batch.component_lens[index] = (component.1 - component.0) as u32;
+    batch.message_offsets[index] = base_offset + message.0 as u64;
+    batch.message_lens[index] = (message.1 - message.0) as u32;
+}
+
 #[inline]
+#[allow(dead_code)]
 pub fn parse_line(line: &[u8], index: usize, batch: &mut LogBatch, base_offset: u64) {
+    batch.line_offsets[index] = base_offset;
+    batch.line_lens[index] = line.len() as u32;
+    match PlainLayout::detect(line) {
+        PlainLayout::Spaced => {}
+        PlainLayout::Syslog => return parse_line_syslog(line, index, batch, base_offset),
+        _ => return parse_line_prefixed(line, index, batch, base_offset),
+    }
     let spaces = find_first_3_spaces(line);
+    set_checked_timestamp(line, index, batch, base_offset, spaces);
+    parse_line_after_timestamp(line, index, batch, base_offset, spaces);
+}
+
+// Lines whose first token is not a timestamp get a zero timestamp and are
+// recorded as malformed.
+#[inline]
+fn set_checked_timestamp(
+    line: &[u8],
+    index: usize,
+    batch: &mut LogBatch,
+    base_offset: u64,
+    spaces: [usize; 3],
+) {
+    let token_end = if spaces[0] == usize::MAX {
+        line.len()
+    } else {
+        spaces[0]
+    };
+    match parse_timestamp(&line[..token_end]) {
+        Some(ts) => batch.timestamps[index] = ts,
+        None => {
+            batch.timestamps[index] = 0;
+            batch.malformed.push(LineSpan {
+                offset: base_offset,
+                len: line.len() as u32,
+            });
+        }
+    }
+}
+
+#[inline]
+fn parse_line_after_timestamp(
+    line: &[u8],
+    index: usize,
+    batch: &mut LogBatch,
+    base_offset: u64,
+    spaces: [usize; 3],
+) {
     let space1 = spaces[0];
 
     if space1 == usize::MAX {
@@ -102,8 +623,6 @@ pub fn parse_line(line: &[u8], index: usize, batch: &mut LogBatch, base_offset:
         return;
     }
 
-    batch.timestamps[index] = parse_timestamp_fast(&line[..space1]);
-
     let after_ts = space1 + 1;
     let space2 = spaces[1];
 
@@ -149,13 +668,25 @@ pub fn parse_lines_range(
     end_idx: usize,
     batch: &mut LogBatch,
 ) {
+    // There is no AVX-512 timestamp kernel, so anything but a forced
+    // scalar scan takes the AVX2 one.
+    #[cfg(target_arch = "x86_64")]
+    let use_avx2 =
+        crate::simd_scan::active_kernel() != Kernel::Scalar && is_x86_feature_detected!("avx2");
+    #[cfg(not(target_arch = "x86_64"))]
+    let use_avx2 = false;
+
     let num_lines = line_starts.len();
+    let sep = crate::simd_scan::record_sep();
+    let mut pending: [(usize, usize, usize, [usize; 3]); 4] = [(0, 0, 0, [usize::MAX; 3]); 4];
+    let mut pending_len = 0;
+
     for i in start_idx..end_idx {
         let line_start = line_starts[i] as usize;
         let line_end = if i + 1 < num_lines {
             let next = line_starts[i + 1] as usize;
 
-            if next > 0 && next <= data.len() && data[next - 1] == b'\n' {
+            if next > 0 && next <= data.len() && data[next - 1] == sep {
                 next - 1
             } else {
                 next
@@ -167,9 +698,70 @@ pub fn parse_lines_range(
         if line_start >= data.len() || line_start >= line_end {
             continue;
         }
+        batch.line_offsets[i] = line_start as u64;
+        batch.line_lens[i] = (line_end - line_start) as u32;
 
         let line = &data[line_start..line_end];
-        parse_line(line, i, batch, line_start as u64);
+        match PlainLayout::detect(line) {
+            PlainLayout::Spaced => {}
+            PlainLayout::Syslog => {
+                parse_line_syslog(line, i, batch, line_start as u64);
+                continue;
+            }
+            _ => {
+                parse_line_prefixed(line, i, batch, line_start as u64);
+                continue;
+            }
+        }
+        let spaces = find_first_3_spaces(line);
+
+        if !use_avx2 || spaces[0] == usize::MAX || spaces[0] < 20 {
+            set_checked_timestamp(line, i, batch, line_start as u64, spaces);
+            parse_line_after_timestamp(line, i, batch, line_start as u64, spaces);
+            continue;
+        }
+
+        pending[pending_len] = (i, line_start, line_end, spaces);
+        pending_len += 1;
+        if pending_len == pending.len() {
+            flush_pending_x4(data, &pending, batch);
+            pending_len = 0;
+        }
+    }
+
+    for &(i, line_start, line_end, spaces) in &pending[..pending_len] {
+        let line = &data[line_start..line_end];
+        set_checked_timestamp(line, i, batch, line_start as u64, spaces);
+        parse_line_after_timestamp(line, i, batch, line_start as u64, spaces);
+    }
+    crate::component::apply_configured(data, batch, start_idx, end_idx);
+}
+
+#[inline]
+fn flush_pending_x4(
+    data: &[u8],
+    pending: &[(usize, usize, usize, [usize; 3]); 4],
+    batch: &mut LogBatch,
+) {
+    let lines: [&[u8]; 4] = std::array::from_fn(|k| {
+        let (_, line_start, line_end, _) = pending[k];
+        &data[line_start..line_end]
+    });
+
+    let mut timestamps = [0u64; 4];
+    #[cfg(target_arch = "x86_64")]
+    let valid = unsafe { parse_timestamps_x4_avx2(lines, &mut timestamps) };
+    #[cfg(not(target_arch = "x86_64"))]
+    let valid = 0u8;
+
+    for (k, &(i, line_start, _, spaces)) in pending.iter().enumerate() {
+        let line = lines[k];
+        if valid & (1 << k) != 0 {
+            batch.timestamps[i] = timestamps[k];
+        } else {
+            set_checked_timestamp(line, i, batch, line_start as u64, spaces);
+        }
+        parse_line_after_timestamp(line, i, batch, line_start as u64, spaces);
     }
 }
 
@@ -179,21 +771,52 @@ mod tests {
 
     #[test]
     fn test_parse_timestamp() {
-        let ts = parse_timestamp_fast(b"2025-02-12T10:31:45Z");
+        let ts = parse_timestamp(b"2025-02-12T10:31:45Z");
 
-        assert_eq!(ts, 1739356305);
+        assert_eq!(ts, Some(1739356305));
     }
 
     #[test]
     fn test_parse_timestamp_epoch() {
-        let ts = parse_timestamp_fast(b"1970-01-01T00:00:00Z");
-        assert_eq!(ts, 0);
+        let ts = parse_timestamp(b"1970-01-01T00:00:00Z");
+        assert_eq!(ts, Some(0));
     }
 
     #[test]
     fn test_parse_timestamp_short() {
-        let ts = parse_timestamp_fast(b"short");
-        assert_eq!(ts, 0);
+        let ts = parse_timestamp(b"short");
+        assert_eq!(ts, None);
+    }
+
+    #[test]
+    fn test_parse_timestamp_checked() {
+        assert_eq!(parse_timestamp(b"2025-02-12T10:31:45Z"), Some(1739356305));
+        assert_eq!(parse_timestamp(b"2025-02-12 10:31:45"), Some(1739356305));
+        assert_eq!(parse_timestamp(b"2025-02-12T10:31"), None);
+        assert_eq!(parse_timestamp(b"2025/02/12T10:31:45Z"), None);
+        assert_eq!(parse_timestamp(b"2025-02-1xT10:31:45Z"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp_value_epochs() {
+        assert_eq!(
+            parse_timestamp_value(b"2025-02-12T10:31:45Z"),
+            Some(1739356305)
+        );
+        assert_eq!(parse_timestamp_value(b"1739356305"), Some(1739356305));
+        assert_eq!(parse_timestamp_value(b"1739356305.75"), Some(1739356305));
+        assert_eq!(parse_timestamp_value(b"1739356305123"), Some(1739356305));
+        assert_eq!(parse_timestamp_value(b"1739356305123456"), Some(1739356305));
+        assert_eq!(
+            parse_timestamp_value(b"1739356305123456789"),
+            Some(1739356305)
+        );
+        assert_eq!(parse_timestamp_value(b"0"), Some(0));
+        assert_eq!(parse_timestamp_value(b""), None);
+        assert_eq!(parse_timestamp_value(b"-5"), None);
+        assert_eq!(parse_timestamp_value(b"12.3.4"), None);
+        assert_eq!(parse_timestamp_value(b"17393563051234567890"), None);
+        assert_eq!(parse_timestamp_value(b"now"), None);
     }
 
     #[test]
@@ -245,6 +868,168 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_line_prefixed_layouts() {
+        let lines: &[&[u8]] = &[
+            b"[2025-02-12 10:31:45] [ERROR] [api-server] pool exhausted",
+            b"[2025-02-12T10:31:45Z] [warning] disk almost full",
+            b"[2025-02-12 10:31:45] INFO auth-service login ok",
+            b"time=\"2025-02-12T10:31:45Z\" level=error component=db connection lost",
+            b"time=\"2025-02-12T10:31:45Z\" level=info msg=\"user logged in\"",
+            b"level=debug ts=2025-02-12T10:31:45Z cache warm",
+            b"[main] starting",
+        ];
+        let expected: &[(u64, LogLevel, &str, &str)] = &[
+            (1739356305, LogLevel::Error, "api-server", "pool exhausted"),
+            (1739356305, LogLevel::Warn, "", "disk almost full"),
+            (1739356305, LogLevel::Info, "auth-service", "login ok"),
+            (1739356305, LogLevel::Error, "db", "connection lost"),
+            (1739356305, LogLevel::Info, "", "user logged in"),
+            (1739356305, LogLevel::Debug, "", "cache warm"),
+            (0, LogLevel::Unknown, "", ""),
+        ];
+        let mut data = Vec::new();
+        let mut line_starts = Vec::new();
+        for line in lines {
+            line_starts.push(data.len() as u64);
+            data.extend_from_slice(line);
+            data.push(b'\n');
+        }
+        let n = lines.len();
+        let mut batch = crate::data::LogBatch::new(n, data.as_ptr());
+        parse_lines_range(&data, &line_starts, 0, n, &mut batch);
+
+        assert_eq!(PlainLayout::detect(lines[0]), PlainLayout::Bracketed);
+        assert_eq!(PlainLayout::detect(lines[3]), PlainLayout::Labeled);
+        assert_eq!(
+            PlainLayout::detect(b"2025-02-12T10:31:45Z INFO a b"),
+            PlainLayout::Spaced
+        );
+        for (i, &(ts, level, component, message)) in expected.iter().enumerate() {
+            assert_eq!(batch.timestamps[i], ts, "line {}", i);
+            assert_eq!(batch.levels[i], level, "line {}", i);
+            unsafe {
+                assert_eq!(batch.component(i), component, "line {}", i);
+                assert_eq!(batch.message(i), message, "line {}", i);
+            }
+        }
+        assert_eq!(batch.malformed.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_line_syslog() {
+        let lines: &[&[u8]] = &[
+            b"<34>1 2025-02-12T10:31:45.003Z web-1 su 4242 ID47 - 'su root' failed",
+            b"<165>1 2025-02-12T10:31:45Z web-1 api - - [meta x=\"1\\]\"][origin ip=\"10.0.0.1\"] slow",
+            b"<11>1 2025-02-12T10:31:45Z web-1 - - - -",
+            b"<28>Feb 12 10:31:45 web-1 sshd[812]: invalid user admin",
+            b"<30>2025-02-12T10:31:45Z web-1 cron: job done",
+            b"<13>hello",
+        ];
+        let expected: &[(u64, LogLevel, &str, &str)] = &[
+            (1739356305, LogLevel::Fatal, "su", "'su root' failed"),
+            (1739356305, LogLevel::Info, "api", "slow"),
+            (1739356305, LogLevel::Error, "", ""),
+            (1739356305, LogLevel::Warn, "sshd", "invalid user admin"),
+            (1739356305, LogLevel::Info, "cron", "job done"),
+            (0, LogLevel::Info, "", "hello"),
+        ];
+        let mut data = Vec::new();
+        let mut line_starts = Vec::new();
+        for line in lines {
+            line_starts.push(data.len() as u64);
+            data.extend_from_slice(line);
+            data.push(b'\n');
+        }
+        line_starts.push(data.len() as u64);
+        let n = lines.len();
+        let mut batch = crate::data::LogBatch::new(n, data.as_ptr());
+        // 2025-03-01T00:00:00Z dates the RFC 3164 line to 2025.
+        crate::syslog::set_year_reference(1_740_787_200);
+        parse_lines_range(&data, &line_starts, 0, n, &mut batch);
+
+        assert_eq!(PlainLayout::detect(lines[0]), PlainLayout::Syslog);
+        assert_eq!(PlainLayout::detect(b"<html>"), PlainLayout::Spaced);
+        for (i, &(ts, level, component, message)) in expected.iter().enumerate() {
+            assert_eq!(batch.timestamps[i], ts, "line {}", i);
+            assert_eq!(batch.levels[i], level, "line {}", i);
+            unsafe {
+                assert_eq!(batch.component(i), component, "line {}", i);
+                assert_eq!(batch.message(i), message, "line {}", i);
+            }
+        }
+        assert_eq!(batch.malformed.len(), 1);
+
+        use crate::emit::{EmitRecord, FieldValue};
+        let mut facility = Vec::new();
+        batch.visit_field(3, b"facility", &mut |value| {
+            if let FieldValue::Text(v) = value {
+                facility.extend_from_slice(v);
+            }
+        });
+        assert_eq!(facility, b"daemon");
+    }
+
+    #[test]
+    fn test_parse_lines_range_matches_per_line() {
+        let lines: &[&[u8]] = &[
+            b"2025-02-12T10:31:45Z INFO api-server request_id=abc123",
+            b"2024-02-29T23:59:59Z WARN auth-service auth_failed",
+            b"1999-12-31T00:00:01.123Z ERROR db connection_timeout",
+            b"2025-02-12 10:31:45 INFO api-server spaced_layout",
+            b"2025-0x-12T10:31:45Z INFO api-server bad_digit",
+            b"1970-01-01T00:00:00Z DEBUG cache-service hit_ratio=0.85",
+            b"short line",
+            b"2030-06-15T12:00:00Z FATAL payment-processor panic",
+            b"2025-02-12T10:31:45Z INFO api-server tail1",
+            b"2025-02-12T10:31:46Z INFO api-server tail2",
+        ];
+        let mut data = Vec::new();
+        let mut line_starts = Vec::new();
+        for line in lines {
+            line_starts.push(data.len() as u64);
+            data.extend_from_slice(line);
+            data.push(b'\n');
+        }
+        line_starts.push(data.len() as u64);
+
+        let n = lines.len();
+        let mut batched = crate::data::LogBatch::new(n, data.as_ptr());
+        parse_lines_range(&data, &line_starts, 0, n, &mut batched);
+
+        let mut reference = crate::data::LogBatch::new(n, data.as_ptr());
+        for (i, line) in lines.iter().enumerate() {
+            parse_line(line, i, &mut reference, line_starts[i]);
+        }
+
+        assert_eq!(batched.timestamps, reference.timestamps);
+        assert_eq!(batched.levels, reference.levels);
+        assert_eq!(batched.component_offsets, reference.component_offsets);
+        assert_eq!(batched.message_lens, reference.message_lens);
+        assert_eq!(batched.timestamps[1], 1709251199);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_parse_timestamps_x4_avx2() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let lines: [&[u8]; 4] = [
+            b"2025-02-12T10:31:45Z",
+            b"1970-01-01T00:00:00Z",
+            b"2025-02-12 10:31:45Z",
+            b"2000-03-01T01:02:03Z",
+        ];
+        let mut out = [u64::MAX; 4];
+        let valid = unsafe { parse_timestamps_x4_avx2(lines, &mut out) };
+        assert_eq!(valid, 0b1011);
+        assert_eq!(out[0], 1739356305);
+        assert_eq!(out[1], 0);
+        assert_eq!(out[2], u64::MAX);
+        assert_eq!(Some(out[3]), parse_timestamp(lines[3]));
+    }
+
     #[test]
     fn test_find_first_3_spaces() {
         let result = find_first_3_spaces(b"a b c d");
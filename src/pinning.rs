@@ -0,0 +1,208 @@
+use core_affinity::CoreId;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuTopo {
+    pub cpu: usize,
+    pub package_id: Option<u32>,
+    pub core_id: Option<u32>,
+}
+
+fn read_topology_u32(cpu_id: usize, leaf: &str) -> Option<u32> {
+    let path = format!("/sys/devices/system/cpu/cpu{cpu_id}/topology/{leaf}");
+    std::fs::read_to_string(path)
+        .ok()?
+        .trim()
+        .parse::<u32>()
+        .ok()
+}
+
+impl CpuTopo {
+    /// CPUs this process may run on, with their socket and physical core.
+    pub fn online() -> Vec<CpuTopo> {
+        core_affinity::get_core_ids()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|core| CpuTopo {
+                cpu: core.id,
+                package_id: read_topology_u32(core.id, "physical_package_id"),
+                core_id: read_topology_u32(core.id, "core_id"),
+            })
+            .collect()
+    }
+
+    fn physical_core(&self) -> Option<(u32, u32)> {
+        Some((self.package_id?, self.core_id?))
+    }
+}
+
+/// Which CPUs `--pin`, `--pin-no-smt` and `--pin-socket` allow.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PinSpec {
+    /// Explicit CPU list in worker order; `None` picks from all CPUs.
+    pub cpus: Option<Vec<usize>>,
+    /// Keep one hardware thread per physical core.
+    pub no_smt: bool,
+    pub socket: Option<u32>,
+}
+
+impl PinSpec {
+    /// Parses `all`, or a list such as `0-15` or `0,2,4,8-11`.
+    pub fn parse_cpus(text: &str) -> Result<Option<Vec<usize>>, String> {
+        if text == "all" {
+            return Ok(None);
+        }
+        let mut cpus = Vec::new();
+        for part in text.split(',') {
+            let parse = |s: &str| {
+                s.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid CPU '{}'", s))
+            };
+            match part.split_once('-') {
+                Some((lo, hi)) => {
+                    let (lo, hi) = (parse(lo)?, parse(hi)?);
+                    if lo > hi {
+                        return Err(format!("empty CPU range '{}'", part));
+                    }
+                    cpus.extend(lo..=hi);
+                }
+                None => cpus.push(parse(part)?),
+            }
+        }
+        let mut seen = HashSet::new();
+        cpus.retain(|cpu| seen.insert(*cpu));
+        Ok(Some(cpus))
+    }
+
+    /// CPUs to pin workers to, in worker order. Without an explicit list,
+    /// physical cores of the largest socket come first, then the other
+    /// sockets, then SMT siblings, so a few workers never share a core.
+    pub fn select(&self, topo: &[CpuTopo]) -> Vec<usize> {
+        let mut candidates: Vec<CpuTopo> = match &self.cpus {
+            Some(cpus) => cpus
+                .iter()
+                .filter_map(|&cpu| topo.iter().find(|t| t.cpu == cpu).copied())
+                .collect(),
+            None => spread_order(topo),
+        };
+        if let Some(socket) = self.socket {
+            candidates.retain(|t| t.package_id == Some(socket));
+        }
+        if self.no_smt {
+            let mut seen = HashSet::new();
+            candidates.retain(|t| t.physical_core().is_none_or(|key| seen.insert(key)));
+        }
+        candidates.into_iter().map(|t| t.cpu).collect()
+    }
+}
+
+fn spread_order(topo: &[CpuTopo]) -> Vec<CpuTopo> {
+    let mut by_package: HashMap<Option<u32>, Vec<CpuTopo>> = HashMap::new();
+    for entry in topo {
+        by_package.entry(entry.package_id).or_default().push(*entry);
+    }
+    let mut packages: Vec<Vec<CpuTopo>> = by_package.into_values().collect();
+    packages.sort_by_key(|entries| (std::cmp::Reverse(entries.len()), entries[0].package_id));
+
+    let mut ordered = Vec::with_capacity(topo.len());
+    let mut used_cores = HashSet::new();
+    for entries in &packages {
+        for entry in entries {
+            if used_cores.insert((entry.package_id, entry.core_id)) {
+                ordered.push(*entry);
+            }
+        }
+    }
+    for entries in &packages {
+        for entry in entries {
+            if !ordered.iter().any(|t| t.cpu == entry.cpu) {
+                ordered.push(*entry);
+            }
+        }
+    }
+    ordered
+}
+
+/// `0-3,8,10-11` style rendering for the stats header.
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < cpus.len() {
+        let mut j = i;
+        while j + 1 < cpus.len() && cpus[j + 1] == cpus[j] + 1 {
+            j += 1;
+        }
+        if !out.is_empty() {
+            out.push(',');
+        }
+        match j - i {
+            0 => write!(out, "{}", cpus[i]),
+            _ => write!(out, "{}-{}", cpus[i], cpus[j]),
+        }
+        .unwrap();
+        i = j + 1;
+    }
+    out
+}
+
+static PINNED_CPUS: OnceLock<Vec<usize>> = OnceLock::new();
+
+/// Pins parse workers to `cpus` for the rest of the run; only the first call
+/// takes effect.
+pub fn set_cpus(cpus: Vec<usize>) {
+    let _ = PINNED_CPUS.set(cpus);
+}
+
+/// CPU for parse worker `worker_idx`, cycling through the pinned list when
+/// there are more workers than CPUs. `None` when pinning is off.
+pub fn core_for_worker(worker_idx: usize) -> Option<CoreId> {
+    let cpus = PINNED_CPUS.get().filter(|cpus| !cpus.is_empty())?;
+    Some(CoreId {
+        id: cpus[worker_idx % cpus.len()],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two sockets of two cores; CPUs 4-7 are the SMT siblings of 0-3.
+    fn topo() -> Vec<CpuTopo> {
+        (0..8)
+            .map(|cpu| CpuTopo {
+                cpu,
+                package_id: Some((cpu % 4 / 2) as u32),
+                core_id: Some((cpu % 2) as u32),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pin_spec_selection() {
+        assert_eq!(PinSpec::parse_cpus("all"), Ok(None));
+        assert_eq!(PinSpec::parse_cpus("0-2,6,1"), Ok(Some(vec![0, 1, 2, 6])));
+        assert!(PinSpec::parse_cpus("3-1").is_err());
+        assert!(PinSpec::parse_cpus("x").is_err());
+        assert_eq!(format_cpu_list(&[0, 1, 2, 6, 8, 9]), "0-2,6,8-9");
+
+        let topo = topo();
+        let all = PinSpec::default();
+        assert_eq!(all.select(&topo), [0, 1, 2, 3, 4, 5, 6, 7]);
+
+        let no_smt = PinSpec {
+            no_smt: true,
+            ..PinSpec::default()
+        };
+        assert_eq!(no_smt.select(&topo), [0, 1, 2, 3]);
+
+        let socket = PinSpec {
+            cpus: Some(vec![7, 6, 5, 4, 9]),
+            no_smt: true,
+            socket: Some(1),
+        };
+        assert_eq!(socket.select(&topo), [7, 6]);
+    }
+}
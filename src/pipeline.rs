@@ -0,0 +1,396 @@
+// `--pipeline <file>` reads a TOML pipeline definition and turns it into the
+// command-line flags it stands for, so a pipeline file and the equivalent
+// command line behave identically:
+//
+//   [source]
+//   files = ["/var/log/app.log"]
+//   format = "json"
+//
+//   [filter]
+//   level = "warn"
+//   where = 'status >= 500'
+//
+//   [transform]
+//   rename = ["msg=message"]
+//
+//   [sink]
+//   sinks = ["file:errors.ndjson,retries=5,dead-letter=failed.ndjson"]
+//
+// Each key is the flag without its dashes: a string or number becomes the
+// flag's value, an array repeats the flag, `true` passes a bare flag and
+// `false` leaves it out. `files` lists the inputs and `sinks` the `--sink`
+// specs. Keys are checked against their section so typos fail loudly. Only
+// the TOML this needs is understood: tables, strings, numbers, booleans
+// and arrays of those.
+
+/// Keys each section accepts; all but `files` and `sinks` name a flag.
+const SECTIONS: &[(&str, &[&str])] = &[
+    (
+        "source",
+        &[
+            "files",
+            "format",
+            "fixed-columns",
+            "fixed-record-len",
+            "threads",
+            "mmap",
+            "mmap-populate",
+            "hugepages",
+            "readahead",
+            "strip-ansi",
+            "record-sep",
+            "firehose",
+            "verify-scan",
+            "pin",
+            "pin-no-smt",
+            "pin-socket",
+            "reverse",
+            "columns",
+            "well-known",
+            "component-rule",
+            "max-record-bytes",
+            "max-fields",
+            "max-value-len",
+            "guard-policy",
+            "index",
+            "follow",
+            "follow-interval",
+        ],
+    ),
+    (
+        "filter",
+        &["level", "where", "since", "limit", "sample-by-level"],
+    ),
+    (
+        "transform",
+        &[
+            "rename",
+            "set",
+            "types",
+            "derive",
+            "record-id",
+            "provenance",
+            "schema",
+            "emit-rules",
+        ],
+    ),
+    (
+        "sink",
+        &[
+            "sinks",
+            "sink-queue",
+            "emit",
+            "split-by",
+            "partition-by",
+            "partition-layout",
+            "output-dir",
+            "max-open-files",
+            "sort-time",
+            "ordered",
+            "sort-mem",
+            "sort-dir",
+            "manifest",
+            "dataset-manifest",
+            "rejects",
+            "extract-bytes",
+            "remote-write",
+            "remote-write-step",
+            "metric-rules",
+            "metric-out",
+        ],
+    ),
+    (
+        "report",
+        &[
+            "check-ordering",
+            "gap-analysis",
+            "gap-threshold",
+            "find-duplicates",
+            "value-sizes",
+            "scan-secrets",
+            "http-summary",
+            "triage",
+            "triage-top",
+            "group-by",
+            "agg",
+            "having",
+            "cache",
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum TomlValue {
+    Str(String),
+    /// Integers and floats, kept as written.
+    Num(String),
+    Bool(bool),
+    Array(Vec<TomlValue>),
+}
+
+struct Cursor<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// Skips spaces, newlines and comments (which may sit between array
+    /// elements).
+    fn skip_space(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<TomlValue, String> {
+        self.skip_space();
+        if self.eat('"') {
+            return self.basic_string().map(TomlValue::Str);
+        }
+        if self.eat('\'') {
+            let end = self.rest().find('\'').ok_or("unterminated string")?;
+            let s = self.rest()[..end].to_string();
+            self.pos += end + 1;
+            return Ok(TomlValue::Str(s));
+        }
+        if self.eat('[') {
+            let mut items = Vec::new();
+            loop {
+                self.skip_space();
+                if self.eat(']') {
+                    return Ok(TomlValue::Array(items));
+                }
+                items.push(self.value()?);
+                self.skip_space();
+                if !self.eat(',') {
+                    self.skip_space();
+                    if !self.eat(']') {
+                        return Err("expected ',' or ']' in array".to_string());
+                    }
+                    return Ok(TomlValue::Array(items));
+                }
+            }
+        }
+        let end = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "+-._".contains(c)))
+            .unwrap_or(self.rest().len());
+        let word = &self.rest()[..end];
+        self.pos += end;
+        match word {
+            "true" => Ok(TomlValue::Bool(true)),
+            "false" => Ok(TomlValue::Bool(false)),
+            _ if word.replace('_', "").parse::<f64>().is_ok() => {
+                Ok(TomlValue::Num(word.replace('_', "")))
+            }
+            "" => Err("missing value".to_string()),
+            _ => Err(format!("unexpected '{}' (strings need quotes)", word)),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\n' => break,
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('u') => {
+                        let hex: String = (0..4)
+                            .filter_map(|_| chars.next().map(|(_, c)| c))
+                            .collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\u{}", hex))?;
+                        out.push(c);
+                    }
+                    other => return Err(format!("invalid escape \\{}", other.unwrap_or(' '))),
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+fn line_of(text: &str, pos: usize) -> usize {
+    text[..pos].matches('\n').count() + 1
+}
+
+/// The arguments `text` stands for, in file order, with input files last.
+pub fn pipeline_args(text: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut files = Vec::new();
+    let mut section: Option<(&str, &[&str])> = None;
+    let mut seen: Vec<(String, String)> = Vec::new();
+    let mut cur = Cursor { text, pos: 0 };
+    loop {
+        cur.skip_space();
+        if cur.rest().is_empty() {
+            break;
+        }
+        let line = line_of(text, cur.pos);
+        let at = |e: String| format!("line {}: {}", line, e);
+        if cur.eat('[') {
+            let end = cur
+                .rest()
+                .find(']')
+                .ok_or_else(|| at("unclosed table header".into()))?;
+            let name = cur.rest()[..end].trim();
+            cur.pos += end + 1;
+            section = Some(
+                SECTIONS
+                    .iter()
+                    .find(|(s, _)| *s == name)
+                    .copied()
+                    .ok_or_else(|| at(format!("unknown section [{}]", name)))?,
+            );
+            continue;
+        }
+
+        let end = cur
+            .rest()
+            .find('=')
+            .ok_or_else(|| at("expected key = value".into()))?;
+        let key = cur.rest()[..end].trim().trim_matches('"').to_string();
+        cur.pos += end + 1;
+        let (name, keys) = section.ok_or_else(|| at(format!("'{}' is outside a section", key)))?;
+        if !keys.contains(&key.as_str()) {
+            return Err(at(format!("unknown key '{}' in [{}]", key, name)));
+        }
+        if seen.iter().any(|(s, k)| s == name && *k == key) {
+            return Err(at(format!("duplicate key '{}'", key)));
+        }
+        seen.push((name.to_string(), key.clone()));
+        let value = cur.value().map_err(at)?;
+        let rest_of_line = cur.rest().split('\n').next().unwrap_or("").trim();
+        if !rest_of_line.is_empty() && !rest_of_line.starts_with('#') {
+            return Err(at(format!("unexpected '{}' after the value", rest_of_line)));
+        }
+
+        let values = match value {
+            TomlValue::Array(items) => items,
+            value => vec![value],
+        };
+        for value in values {
+            let text = match value {
+                TomlValue::Str(s) | TomlValue::Num(s) => Some(s),
+                TomlValue::Bool(true) => None,
+                TomlValue::Bool(false) => continue,
+                TomlValue::Array(_) => return Err(at("nested arrays are not supported".into())),
+            };
+            match (key.as_str(), text) {
+                ("files", Some(path)) => files.push(path),
+                ("sinks", Some(spec)) => args.extend(["--sink".to_string(), spec]),
+                (_, Some(text)) => args.extend([format!("--{}", key), text]),
+                (_, None) => args.push(format!("--{}", key)),
+            }
+        }
+    }
+    args.extend(files);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_file_to_args() {
+        let text = r#"
+# errors from the api, as NDJSON
+[source]
+files = [
+    "a.log",   # first
+    "b.log",
+]
+format = "json"
+threads = 8
+mmap = true
+reverse = false
+
+[filter]
+level = "error"
+where = 'status >= 500 && path != "/health"'
+
+[transform]
+rename = ["msg=message", "ts=time"]
+
+[sink]
+sinks = ["file:out.ndjson,retries=5"]
+"#;
+        assert_eq!(
+            pipeline_args(text).unwrap(),
+            [
+                "--format",
+                "json",
+                "--threads",
+                "8",
+                "--mmap",
+                "--level",
+                "error",
+                "--where",
+                "status >= 500 && path != \"/health\"",
+                "--rename",
+                "msg=message",
+                "--rename",
+                "ts=time",
+                "--sink",
+                "file:out.ndjson,retries=5",
+                "a.log",
+                "b.log",
+            ]
+        );
+
+        for (text, error) in [
+            ("[source]\nformat = json", "line 2: unexpected 'json'"),
+            ("[sorce]\n", "line 1: unknown section [sorce]"),
+            (
+                "[filter]\nlevle = \"warn\"",
+                "line 2: unknown key 'levle' in [filter]",
+            ),
+            ("level = \"warn\"", "line 1: 'level' is outside a section"),
+            (
+                "[filter]\nlevel = \"a\"\nlevel = \"b\"",
+                "line 3: duplicate key 'level'",
+            ),
+            ("[filter]\nlevel = \"warn", "line 2: unterminated string"),
+            (
+                "[source]\nfiles = [\"a\" \"b\"]",
+                "line 2: expected ',' or ']'",
+            ),
+        ] {
+            let err = pipeline_args(text).unwrap_err();
+            assert!(err.starts_with(error), "{}: {}", text, err);
+        }
+    }
+}
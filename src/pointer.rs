@@ -0,0 +1,180 @@
+use crate::emit::FieldValue;
+use std::borrow::Cow;
+
+/// The first reference token of a JSON Pointer (`/context/user`) and the
+/// pointer to the rest; `None` unless `pointer` starts with `/`. `~1` and
+/// `~0` in the token become `/` and `~`.
+pub fn first_token(pointer: &[u8]) -> Option<(Cow<'_, [u8]>, &[u8])> {
+    let rest = pointer.strip_prefix(b"/")?;
+    let end = memchr::memchr(b'/', rest).unwrap_or(rest.len());
+    let token = &rest[..end];
+    let token = if token.contains(&b'~') {
+        let mut out = Vec::with_capacity(token.len());
+        let mut k = 0;
+        while k < token.len() {
+            match (token[k], token.get(k + 1)) {
+                (b'~', Some(b'1')) => out.push(b'/'),
+                (b'~', Some(b'0')) => out.push(b'~'),
+                (b, _) => {
+                    out.push(b);
+                    k += 1;
+                    continue;
+                }
+            }
+            k += 2;
+        }
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(token)
+    };
+    Some((token, &rest[end..]))
+}
+
+/// Follows `pointer` into the raw JSON text `value`, scanning only the
+/// containers on the way; an empty pointer is `value` itself.
+pub fn resolve<'a>(value: &'a [u8], pointer: &[u8]) -> Option<FieldValue<'a>> {
+    let mut value = value.trim_ascii();
+    let mut pointer = pointer;
+    while let Some((token, rest)) = first_token(pointer) {
+        value = child(value, &token)?;
+        pointer = rest;
+    }
+    Some(match value {
+        [b'"', inner @ .., b'"'] => FieldValue::Escaped(inner),
+        [b'{' | b'[', ..] => FieldValue::Text(value),
+        _ => FieldValue::Literal(value),
+    })
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// End of the JSON value starting at `i`.
+fn value_end(bytes: &[u8], mut i: usize) -> usize {
+    match bytes.get(i) {
+        Some(b'"') => string_end(bytes, i),
+        Some(b'{' | b'[') => {
+            let mut depth = 0i32;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    b'"' => {
+                        i = string_end(bytes, i);
+                        continue;
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            bytes.len()
+        }
+        _ => {
+            while i < bytes.len() && !matches!(bytes[i], b',' | b'}' | b']') {
+                i += 1;
+            }
+            i
+        }
+    }
+}
+
+/// End of the string whose opening quote is at `i`, past the closing quote.
+fn string_end(bytes: &[u8], mut i: usize) -> usize {
+    i += 1;
+    while i < bytes.len() && bytes[i] != b'"' {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    (i + 1).min(bytes.len())
+}
+
+/// The member named `token` of an object, or element `token` of an array.
+fn child<'a>(container: &'a [u8], token: &[u8]) -> Option<&'a [u8]> {
+    let (is_object, index) = match container.first()? {
+        b'{' => (true, 0),
+        b'[' => {
+            let index = std::str::from_utf8(token).ok()?;
+            if index.len() > 1 && index.starts_with('0') {
+                return None;
+            }
+            (false, index.parse::<usize>().ok()?)
+        }
+        _ => return None,
+    };
+    let mut i = 1;
+    let mut n = 0;
+    loop {
+        i = skip_whitespace(container, i);
+        if matches!(container.get(i), None | Some(b'}' | b']')) {
+            return None;
+        }
+        let mut matched = !is_object && n == index;
+        if is_object {
+            let key_end = string_end(container, i);
+            matched = container.get(i + 1..key_end.saturating_sub(1)) == Some(token);
+            i = skip_whitespace(container, key_end);
+            if container.get(i) != Some(&b':') {
+                return None;
+            }
+            i = skip_whitespace(container, i + 1);
+        }
+        let end = value_end(container, i);
+        if matched {
+            return Some(container[i..end].trim_ascii());
+        }
+        i = skip_whitespace(container, end);
+        if container.get(i) != Some(&b',') {
+            return None;
+        }
+        i += 1;
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_pointer() {
+        let doc = br#"{"user": {"name": "john", "id": 7, "tags": ["a", {"k": "v"}]}, "a/b": 1, "m~n": true, "s": "x}y"}"#;
+        assert_eq!(
+            resolve(doc, b"/user/name"),
+            Some(FieldValue::Escaped(b"john"))
+        );
+        assert_eq!(resolve(doc, b"/user/id"), Some(FieldValue::Literal(b"7")));
+        assert_eq!(
+            resolve(doc, b"/user/tags/0"),
+            Some(FieldValue::Escaped(b"a"))
+        );
+        assert_eq!(
+            resolve(doc, b"/user/tags/1/k"),
+            Some(FieldValue::Escaped(b"v"))
+        );
+        assert_eq!(
+            resolve(doc, b"/user/tags/1"),
+            Some(FieldValue::Text(br#"{"k": "v"}"#))
+        );
+        assert_eq!(resolve(doc, b"/a~1b"), Some(FieldValue::Literal(b"1")));
+        assert_eq!(resolve(doc, b"/m~0n"), Some(FieldValue::Literal(b"true")));
+        assert_eq!(resolve(doc, b"/s"), Some(FieldValue::Escaped(b"x}y")));
+        assert_eq!(resolve(doc, b"/user/tags/2"), None);
+        assert_eq!(resolve(doc, b"/user/tags/01"), None);
+        assert_eq!(resolve(doc, b"/user/missing"), None);
+        assert_eq!(resolve(doc, b"/user/name/x"), None);
+        assert_eq!(resolve(b"{}", b"/a"), None);
+        assert_eq!(resolve(b"[]", b"/0"), None);
+
+        let (token, rest) = first_token(b"/context/user").unwrap();
+        assert_eq!((&*token, rest), (&b"context"[..], &b"/user"[..]));
+        assert!(first_token(b"context").is_none());
+    }
+}
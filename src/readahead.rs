@@ -0,0 +1,63 @@
+use std::fs::File;
+
+/// `POSIX_FADV_WILLNEED` hints that keep the page cache a window ahead of
+/// each mmap worker, so cold reads overlap with parsing instead of stalling
+/// it on page faults.
+#[derive(Debug, Clone, Copy)]
+pub struct Readahead {
+    #[cfg(unix)]
+    fd: std::os::unix::io::RawFd,
+    window: u64,
+}
+
+impl Readahead {
+    /// `file` must stay open for as long as the hints are issued.
+    pub fn new(file: &File, window: u64) -> Readahead {
+        #[cfg(not(unix))]
+        let _ = file;
+        Readahead {
+            #[cfg(unix)]
+            fd: std::os::unix::io::AsRawFd::as_raw_fd(file),
+            window,
+        }
+    }
+
+    /// Called as a worker starts on file range `[start, end)`: hints the
+    /// chunk plus the window past it, or before it when reading backwards.
+    pub fn chunk(&self, start: u64, end: u64, reverse: bool) {
+        let (offset, len) = hint_range(start, end, self.window, reverse);
+        #[cfg(unix)]
+        unsafe {
+            libc::posix_fadvise(
+                self.fd,
+                offset as libc::off_t,
+                len as libc::off_t,
+                libc::POSIX_FADV_WILLNEED,
+            );
+        }
+        #[cfg(not(unix))]
+        let _ = (offset, len);
+    }
+}
+
+fn hint_range(start: u64, end: u64, window: u64, reverse: bool) -> (u64, u64) {
+    if reverse {
+        let offset = start.saturating_sub(window);
+        (offset, end - offset)
+    } else {
+        (start, end - start + window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hint_range_runs_ahead_of_chunk() {
+        assert_eq!(hint_range(100, 200, 50, false), (100, 150));
+        assert_eq!(hint_range(100, 200, 50, true), (50, 150));
+        assert_eq!(hint_range(20, 200, 50, true), (0, 200));
+        assert_eq!(hint_range(100, 200, 0, false), (100, 100));
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Side file for lines the parsers flagged as malformed. Each entry is
+/// `<source>:<byte offset>\t<raw line>` so quarantined lines can be traced
+/// back and reprocessed.
+pub struct RejectWriter<W: Write = BufWriter<File>> {
+    source: Mutex<String>,
+    out: Mutex<W>,
+    written: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl RejectWriter {
+    pub fn create(path: impl AsRef<Path>, source: &str) -> io::Result<Self> {
+        Ok(RejectWriter::new(
+            BufWriter::new(File::create(path)?),
+            source,
+        ))
+    }
+}
+
+impl<W: Write> RejectWriter<W> {
+    pub fn new(out: W, source: &str) -> Self {
+        RejectWriter {
+            source: Mutex::new(source.to_string()),
+            out: Mutex::new(out),
+            written: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    /// Names the input that later offsets refer to, when one writer is
+    /// shared across files.
+    pub fn set_source(&self, source: &str) {
+        *self.source.lock().unwrap() = source.to_string();
+    }
+
+    /// Called from worker threads; write errors are counted rather than
+    /// aborting the parse.
+    pub fn write(&self, offset: u64, line: &[u8]) {
+        let source = self.source.lock().unwrap();
+        let mut out = self.out.lock().unwrap();
+        let result = write!(out, "{}:{}\t", source, offset)
+            .and_then(|_| out.write_all(line))
+            .and_then(|_| out.write_all(b"\n"));
+        match result {
+            Ok(()) => self.written.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.failed.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        let mut out = self.out.into_inner().unwrap();
+        out.flush()?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::orchestrator::parse_logs_pipelined_with;
+
+    #[test]
+    fn test_rejects_record_source_and_offset() {
+        let data = b"2025-02-12T10:31:45Z INFO api ok\n\
+                     garbage line\n\
+                     2025-02-12T10:31:46Z WARN api slow\n\
+                     \tcontinued\n";
+        let rejects = RejectWriter::new(Vec::new(), "app.log");
+        let on_reject = |offset: u64, line: &[u8]| rejects.write(offset, line);
+        let control = MatchControl {
+            on_reject: Some(&on_reject),
+            ..MatchControl::default()
+        };
+        let result = parse_logs_pipelined_with(data, 1, &control);
+
+        assert_eq!(result.malformed_lines, 2);
+        assert_eq!(rejects.written(), 2);
+        let out = rejects.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "app.log:33\tgarbage line\napp.log:81\t\tcontinued\n"
+        );
+    }
+
+    #[test]
+    fn test_rejected_lines_are_not_exported() {
+        use crate::emit::{EmitFormat, EmitRules, emit_chunk};
+        use std::sync::Mutex;
+
+        let data = b"<13>Feb 12 10:31:48 web-1 app: msg\n\
+                     <13>hello\n\
+                     <30>1 2025-02-12T10:31:49Z web-1 cron - - - done\n";
+        crate::syslog::set_year_reference(1_740_787_200);
+        let rejects = RejectWriter::new(Vec::new(), "syslog");
+        let on_reject = |offset: u64, line: &[u8]| rejects.write(offset, line);
+        let exported = Mutex::new(Vec::new());
+        let rules = EmitRules::default();
+        let on_batch = |batch: &crate::data::LogBatch, records: &[u32]| {
+            let chunk = emit_chunk(batch, records, &rules, EmitFormat::Ndjson);
+            exported.lock().unwrap().extend(chunk.ndjson);
+        };
+        let control = MatchControl {
+            on_reject: Some(&on_reject),
+            on_batch: Some(&on_batch),
+            ..MatchControl::default()
+        };
+        let result = parse_logs_pipelined_with(data, 1, &control);
+
+        assert_eq!(result.total_lines, 3);
+        assert_eq!(result.malformed_lines, 1);
+        assert_eq!(rejects.written(), 1);
+        let exported = String::from_utf8(exported.into_inner().unwrap()).unwrap();
+        assert_eq!(exported.lines().count(), 2);
+        assert!(exported.starts_with("{\"timestamp\":\"2025-02-12T10:31:48Z\""));
+        assert!(!exported.contains("hello"));
+        assert_eq!(
+            String::from_utf8(rejects.finish().unwrap()).unwrap(),
+            "syslog:35\t<13>hello\n"
+        );
+    }
+}
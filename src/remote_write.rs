@@ -0,0 +1,449 @@
+use crate::emit::EmitChunk;
+use crate::group::Agg;
+use crate::rolling::{Dimension, TimelineStep};
+use crate::sink::Sink;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Samples per request, well under the receivers' usual body limits.
+const MAX_SAMPLES: usize = 10_000;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Turns [`TimelineStep`]s into Prometheus remote-write requests
+/// (snappy-compressed protobuf `WriteRequest`s over HTTP/1.1). Record
+/// counts go out as the counter `pandora_records_total`, each `--agg` as a
+/// gauge such as `pandora_p99_latency_ms`, labelled by level or component.
+pub struct RemoteWrite {
+    url: String,
+    endpoint: Endpoint,
+    /// Running record counts, so counters keep rising across pushes.
+    totals: Mutex<HashMap<(Dimension, Vec<u8>), u64>>,
+}
+
+/// The receiver of [`RemoteWrite`] requests, as a [`Sink`] whose chunks
+/// each hold one uncompressed `WriteRequest`, so failed pushes get the
+/// tee's retries and backoff.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+type SeriesKey = (String, Dimension, Vec<u8>);
+
+impl RemoteWrite {
+    /// Only plain `http://host[:port]/path` URLs; put a proxy in front of
+    /// endpoints that need TLS.
+    pub fn new(url: &str) -> Result<RemoteWrite, String> {
+        if url.starts_with("https://") {
+            return Err("https is not supported in this build, use a local http proxy".to_string());
+        }
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("expected an http:// URL, got '{}'", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("invalid port in '{}'", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("no host in '{}'", url));
+        }
+        Ok(RemoteWrite {
+            url: url.to_string(),
+            endpoint: Endpoint {
+                host: host.to_string(),
+                port,
+                path: path.to_string(),
+            },
+            totals: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The sink that sends [`requests`](RemoteWrite::requests).
+    pub fn sink(&self) -> Endpoint {
+        self.endpoint.clone()
+    }
+
+    /// The requests carrying `steps` (oldest first), each sampled at its
+    /// start; a chunk's `records` are its samples.
+    pub fn requests(&self, steps: &[TimelineStep], aggs: &[Agg]) -> Vec<EmitChunk> {
+        let mut series: BTreeMap<SeriesKey, Vec<(i64, f64)>> = BTreeMap::new();
+        let mut totals = self.totals.lock().unwrap();
+        for step in steps {
+            let at_ms = step.start as i64 * 1000;
+            for (dimension, value, stats) in &step.series {
+                let total = totals.entry((*dimension, value.clone())).or_default();
+                *total += stats.count;
+                let mut sample = |name: String, v: f64| {
+                    series
+                        .entry((name, *dimension, value.clone()))
+                        .or_default()
+                        .push((at_ms, v));
+                };
+                sample("pandora_records_total".to_string(), *total as f64);
+                for agg in aggs {
+                    if let Some(v) = agg.value(stats) {
+                        sample(metric_name(&agg.having_name()), v);
+                    }
+                }
+            }
+        }
+        drop(totals);
+
+        let mut requests = Vec::new();
+        let mut request = EmitChunk::default();
+        for ((name, dimension, value), samples) in &series {
+            for chunk in samples.chunks(MAX_SAMPLES) {
+                if request.records as usize + chunk.len() > MAX_SAMPLES {
+                    requests.push(std::mem::take(&mut request));
+                }
+                let labels = [
+                    ("__name__", name.as_bytes()),
+                    (dimension.as_str(), value.as_slice()),
+                ];
+                put_bytes(&mut request.ndjson, 1, &encode_series(&labels, chunk));
+                request.records += chunk.len() as u64;
+            }
+        }
+        if request.records > 0 {
+            requests.push(request);
+        }
+        requests
+    }
+}
+
+impl Sink for Endpoint {
+    fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()> {
+        self.post(&chunk.ndjson)
+    }
+}
+
+impl Endpoint {
+    fn post(&self, write_request: &[u8]) -> io::Result<()> {
+        let body = snappy_compress(write_request);
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: pandora/{}\r\n\
+             Content-Type: application/x-protobuf\r\nContent-Encoding: snappy\r\n\
+             X-Prometheus-Remote-Write-Version: 0.1.0\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            self.port,
+            env!("CARGO_PKG_VERSION"),
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        stream.take(64 * 1024).read_to_end(&mut response)?;
+        let response = String::from_utf8_lossy(&response);
+        let status = response.split(' ').nth(1).unwrap_or("");
+        if status.starts_with('2') {
+            return Ok(());
+        }
+        let message = response
+            .split_once("\r\n\r\n")
+            .map_or("", |(_, body)| body.trim());
+        // Receivers ask for 5xx and 429 to be retried; other errors are final.
+        let kind = if status.starts_with('5') || status == "429" {
+            io::ErrorKind::Interrupted
+        } else {
+            io::ErrorKind::Other
+        };
+        Err(io::Error::new(
+            kind,
+            format!(
+                "HTTP {}: {}",
+                if status.is_empty() { "?" } else { status },
+                message.chars().take(200).collect::<String>()
+            ),
+        ))
+    }
+}
+
+/// Metric names only allow `[a-zA-Z0-9_:]`.
+fn metric_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("pandora_{}", name)
+}
+
+fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+/// Length-delimited protobuf field.
+fn put_bytes(out: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_varint(out, (field << 3 | 2) as u64);
+    put_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// `TimeSeries { repeated Label labels = 1; repeated Sample samples = 2; }`
+/// with `Label { string name = 1; string value = 2; }` and
+/// `Sample { double value = 1; int64 timestamp = 2; }`.
+fn encode_series(labels: &[(&str, &[u8])], samples: &[(i64, f64)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut field = Vec::new();
+    // Receivers expect labels sorted by name.
+    let mut labels = labels.to_vec();
+    labels.sort_by_key(|(name, _)| *name);
+    for (name, value) in labels {
+        field.clear();
+        put_bytes(&mut field, 1, name.as_bytes());
+        put_bytes(&mut field, 2, value);
+        put_bytes(&mut out, 1, &field);
+    }
+    for &(ts, value) in samples {
+        field.clear();
+        field.push(1 << 3 | 1);
+        field.extend_from_slice(&value.to_le_bytes());
+        field.push(2 << 3);
+        put_varint(&mut field, ts as u64);
+        put_bytes(&mut out, 2, &field);
+    }
+    out
+}
+
+/// Snappy block format (what remote-write bodies use): greedy matching of
+/// 4-byte sequences within the last 64 KiB.
+pub fn snappy_compress(input: &[u8]) -> Vec<u8> {
+    const TABLE_BITS: u32 = 14;
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    put_varint(&mut out, input.len() as u64);
+    // Position + 1 of the last 4-byte sequence with each hash; 0 is empty.
+    let mut table = vec![0usize; 1 << TABLE_BITS];
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i + 4 <= input.len() {
+        let word = u32::from_le_bytes(input[i..i + 4].try_into().unwrap());
+        let h = (word.wrapping_mul(0x1e35_a7bd) >> (32 - TABLE_BITS)) as usize;
+        let candidate = std::mem::replace(&mut table[h], i + 1);
+        if let Some(c) = candidate.checked_sub(1)
+            && i - c <= 0xffff
+            && input[c..c + 4] == input[i..i + 4]
+        {
+            let mut len = 4;
+            while i + len < input.len() && input[c + len] == input[i + len] {
+                len += 1;
+            }
+            put_literal(&mut out, &input[literal_start..i]);
+            put_copy(&mut out, i - c, len);
+            i += len;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    put_literal(&mut out, &input[literal_start..]);
+    out
+}
+
+fn put_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+    let n = literal.len() - 1;
+    if n < 60 {
+        out.push((n as u8) << 2);
+    } else {
+        let bytes = (n as u32).to_le_bytes();
+        let width = 4 - (n as u32).leading_zeros() as usize / 8;
+        out.push(((59 + width) as u8) << 2);
+        out.extend_from_slice(&bytes[..width]);
+    }
+    out.extend_from_slice(literal);
+}
+
+/// Copies with a 2-byte offset, at most 64 bytes each.
+fn put_copy(out: &mut Vec<u8>, offset: usize, mut len: usize) {
+    let mut one = |len: usize| {
+        out.push(((len - 1) as u8) << 2 | 2);
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+    };
+    while len >= 68 {
+        one(64);
+        len -= 64;
+    }
+    if len > 64 {
+        one(60);
+        len -= 60;
+    }
+    one(len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::GroupStats;
+    use std::net::TcpListener;
+
+    fn snappy_decompress(input: &[u8]) -> Vec<u8> {
+        let (mut len, mut shift, mut i) = (0usize, 0, 0);
+        loop {
+            len |= ((input[i] & 0x7f) as usize) << shift;
+            shift += 7;
+            i += 1;
+            if input[i - 1] < 0x80 {
+                break;
+            }
+        }
+        let mut out = Vec::with_capacity(len);
+        while i < input.len() {
+            let tag = input[i];
+            i += 1;
+            match tag & 3 {
+                0 => {
+                    let mut n = (tag >> 2) as usize;
+                    if n >= 60 {
+                        let width = n - 59;
+                        let mut bytes = [0u8; 8];
+                        bytes[..width].copy_from_slice(&input[i..i + width]);
+                        n = usize::from_le_bytes(bytes);
+                        i += width;
+                    }
+                    out.extend_from_slice(&input[i..i + n + 1]);
+                    i += n + 1;
+                }
+                2 => {
+                    let n = (tag >> 2) as usize + 1;
+                    let offset = u16::from_le_bytes([input[i], input[i + 1]]) as usize;
+                    i += 2;
+                    for _ in 0..n {
+                        out.push(out[out.len() - offset]);
+                    }
+                }
+                _ => panic!("unexpected tag {:#x}", tag),
+            }
+        }
+        assert_eq!(out.len(), len);
+        out
+    }
+
+    #[test]
+    fn test_push_sends_snappy_protobuf_counters() {
+        let mut data: Vec<u8> = (0..300).map(|i| (i % 7) as u8).collect();
+        data.extend((0..5000u32).flat_map(|i| i.to_le_bytes()));
+        let compressed = snappy_compress(&data);
+        assert!(compressed.len() < data.len());
+        assert_eq!(snappy_decompress(&compressed), data);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in [
+                "204 No Content",
+                "503 Service Unavailable",
+                "400 Bad Request",
+            ] {
+                let (mut conn, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let body = loop {
+                    let n = conn.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).into_owned();
+                    if let Some(head_end) = text.find("\r\n\r\n") {
+                        let len: usize = text
+                            .lines()
+                            .find_map(|l| l.strip_prefix("Content-Length: "))
+                            .unwrap()
+                            .parse()
+                            .unwrap();
+                        if request.len() >= head_end + 4 + len {
+                            assert!(text.contains("Content-Encoding: snappy"));
+                            break request[head_end + 4..head_end + 4 + len].to_vec();
+                        }
+                    }
+                };
+                write!(conn, "HTTP/1.1 {}\r\n\r\nout of bounds", status).unwrap();
+                bodies.push(snappy_decompress(&body));
+            }
+            bodies
+        });
+
+        let remote = RemoteWrite::new(&format!("http://127.0.0.1:{}/api/v1/write", port)).unwrap();
+        let stats = |count| GroupStats {
+            count,
+            ..Default::default()
+        };
+        let steps = [
+            TimelineStep {
+                start: 60,
+                series: vec![(Dimension::Level, b"error".to_vec(), stats(2))],
+            },
+            TimelineStep {
+                start: 120,
+                series: vec![(Dimension::Level, b"error".to_vec(), stats(3))],
+            },
+        ];
+        let mut sink = remote.sink();
+        let requests = remote.requests(&steps, &[]);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].records, 2);
+        sink.write_batch(&requests[0]).unwrap();
+        let request = remote.requests(&steps[1..], &[]).pop().unwrap();
+        let err = sink.write_batch(&request).unwrap_err();
+        assert!(crate::sink::is_transient(&err));
+        let err = sink.write_batch(&request).unwrap_err();
+        assert!(!crate::sink::is_transient(&err));
+        assert!(err.to_string().contains("HTTP 400: out of bounds"));
+
+        let bodies = server.join().unwrap();
+        let labels = [
+            ("level", &b"error"[..]),
+            ("__name__", b"pandora_records_total"),
+        ];
+        assert_eq!(
+            bodies[0],
+            [vec![0x0a], {
+                let series = encode_series(&labels, &[(60_000, 2.0), (120_000, 5.0)]);
+                let mut len = Vec::new();
+                put_varint(&mut len, series.len() as u64);
+                [len, series].concat()
+            },]
+            .concat()
+        );
+        // Counters keep rising across pushes.
+        let mut expected = Vec::new();
+        put_bytes(&mut expected, 1, &encode_series(&labels, &[(120_000, 8.0)]));
+        assert_eq!(bodies[1], expected);
+        assert_eq!(bodies[2], expected);
+        assert_eq!(metric_name("p99_http.latency"), "pandora_p99_http_latency");
+    }
+}
@@ -0,0 +1,158 @@
+//! Ordered emit (`--ordered`): workers parse chunks in parallel, and what
+//! they emit for each chunk is held until every earlier chunk is done, then
+//! released in parse order. Workers may run at most a window of chunks
+//! ahead of the oldest unfinished one, which bounds what is held.
+
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// How often a worker waiting for its turn checks whether the parse is
+/// stopping, e.g. on `--limit` or Ctrl-C.
+const STOP_POLL: Duration = Duration::from_millis(20);
+
+/// Chunks of output held per worker thread: enough for each worker to
+/// start on its next chunk while the oldest one is still being parsed.
+pub const WINDOW_PER_THREAD: usize = 2;
+
+pub struct Reorder<'a, T> {
+    state: Mutex<State<T>>,
+    turn: Condvar,
+    window: u64,
+    release: Box<dyn Fn(T) + Sync + 'a>,
+}
+
+struct State<T> {
+    /// Oldest chunk not yet released.
+    next: u64,
+    pending: BTreeMap<u64, Slot<T>>,
+}
+
+struct Slot<T> {
+    items: Vec<T>,
+    done: bool,
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Slot {
+            items: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+impl<'a, T> Reorder<'a, T> {
+    /// Holds output for up to `window` chunks, handing it to `release` in
+    /// chunk order.
+    pub fn new(window: usize, release: impl Fn(T) + Sync + 'a) -> Self {
+        Reorder {
+            state: Mutex::new(State {
+                next: 0,
+                pending: BTreeMap::new(),
+            }),
+            turn: Condvar::new(),
+            window: window.max(1) as u64,
+            release: Box::new(release),
+        }
+    }
+
+    /// Blocks a worker about to parse chunk `seq` until it is within the
+    /// window, or until `stop` says the parse is ending.
+    pub fn wait_turn(&self, seq: u64, stop: impl Fn() -> bool) {
+        let mut state = self.state.lock().unwrap();
+        while seq >= state.next + self.window && !stop() {
+            state = self.turn.wait_timeout(state, STOP_POLL).unwrap().0;
+        }
+    }
+
+    /// Holds `item`, emitted for chunk `seq`, until its turn.
+    pub fn push(&self, seq: u64, item: T) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.entry(seq).or_default().items.push(item);
+    }
+
+    /// Marks chunk `seq` complete, releasing it and any complete chunks
+    /// after it once every earlier chunk is.
+    pub fn done(&self, seq: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.entry(seq).or_default().done = true;
+        let mut released = false;
+        while state.pending.get(&state.next).is_some_and(|slot| slot.done) {
+            let next = state.next;
+            let slot = state.pending.remove(&next).unwrap();
+            slot.items.into_iter().for_each(&self.release);
+            state.next += 1;
+            released = true;
+        }
+        if released {
+            self.turn.notify_all();
+        }
+    }
+
+    /// Releases whatever is still held, in chunk order, e.g. chunks after
+    /// one a stopped parse never finished, and starts over at chunk 0 for
+    /// the next input.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        for (_, slot) in std::mem::take(&mut state.pending) {
+            slot.items.into_iter().for_each(&self.release);
+        }
+        state.next = 0;
+        self.turn.notify_all();
+    }
+
+    /// Chunks whose output is held.
+    #[allow(dead_code)]
+    pub fn held(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_reorder_releases_in_chunk_order() {
+        let out = Mutex::new(Vec::new());
+        let reorder = Reorder::new(4, |item: u64| out.lock().unwrap().push(item));
+        reorder.push(1, 10);
+        reorder.push(1, 11);
+        reorder.done(1);
+        reorder.done(2);
+        assert!(out.lock().unwrap().is_empty());
+        assert_eq!(reorder.held(), 2);
+        reorder.push(0, 0);
+        reorder.done(0);
+        assert_eq!(*out.lock().unwrap(), vec![0, 10, 11]);
+        assert_eq!(reorder.held(), 0);
+
+        // Workers taking chunks round-robin stay within the window.
+        out.lock().unwrap().clear();
+        reorder.finish();
+        thread::scope(|scope| {
+            for worker in 0..3u64 {
+                let reorder = &reorder;
+                scope.spawn(move || {
+                    for seq in (worker..60).step_by(3) {
+                        reorder.wait_turn(seq, || false);
+                        assert!(reorder.held() <= 4);
+                        reorder.push(seq, seq);
+                        reorder.done(seq);
+                    }
+                });
+            }
+        });
+        assert_eq!(*out.lock().unwrap(), (0..60).collect::<Vec<_>>());
+
+        // A stopped parse leaves gaps; finishing releases the rest in order.
+        out.lock().unwrap().clear();
+        reorder.finish();
+        reorder.push(2, 2);
+        reorder.push(1, 1);
+        reorder.finish();
+        assert_eq!(*out.lock().unwrap(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::data::BatchRecords;
+use std::time::{Duration, Instant};
+
+/// Records due within this much of each other go out in one chunk.
+const SLACK: Duration = Duration::from_millis(10);
+
+/// Parses a `--speed` value: `10x`, `10` or `0.5x`.
+pub fn parse_speed(text: &str) -> Option<f64> {
+    let speed: f64 = text.strip_suffix(['x', 'X']).unwrap_or(text).parse().ok()?;
+    (speed.is_finite() && speed > 0.0).then_some(speed)
+}
+
+/// `replay`: spaces records out the way their timestamps were, divided by
+/// the speed. The first record goes out at once. A record without a
+/// timestamp, or with one earlier than a record before it, goes out with
+/// the record before it, so replay never waits on a clock going backwards.
+/// Timestamps have whole-second resolution, so records of one second are
+/// sent together.
+pub struct Pacer {
+    speed: f64,
+    /// First record time and when it was sent.
+    origin: Option<(u64, Instant)>,
+    latest: u64,
+}
+
+impl Pacer {
+    pub fn new(speed: f64) -> Self {
+        Pacer {
+            speed,
+            origin: None,
+            latest: 0,
+        }
+    }
+
+    /// When a record stamped `timestamp` is due.
+    pub fn due(&mut self, timestamp: Option<u64>, now: Instant) -> Instant {
+        let Some((first, start)) = self.origin else {
+            let Some(ts) = timestamp else {
+                return now;
+            };
+            self.origin = Some((ts, now));
+            self.latest = ts;
+            return now;
+        };
+        self.latest = timestamp.map_or(self.latest, |ts| ts.max(self.latest));
+        start + Duration::from_secs_f64((self.latest - first) as f64 / self.speed)
+    }
+
+    /// Hands `records` to `send` in order, in groups that are due together,
+    /// sleeping until each group is due. Stops early on Ctrl-C.
+    pub fn pace<B: BatchRecords>(
+        &mut self,
+        batch: &B,
+        records: &[u32],
+        send: &mut dyn FnMut(&[u32]),
+    ) {
+        let mut start = 0;
+        while start < records.len() {
+            if crate::shutdown::interrupted() {
+                return;
+            }
+            let due = self.due(
+                batch.record_timestamp(records[start] as usize),
+                Instant::now(),
+            );
+            let mut end = start + 1;
+            while end < records.len() {
+                let next = self.due(
+                    batch.record_timestamp(records[end] as usize),
+                    Instant::now(),
+                );
+                if next > due + SLACK {
+                    break;
+                }
+                end += 1;
+            }
+            std::thread::sleep(due.saturating_duration_since(Instant::now()));
+            send(&records[start..end]);
+            start = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pacer_scales_record_time() {
+        assert_eq!(parse_speed("10x"), Some(10.0));
+        assert_eq!(parse_speed("0.5"), Some(0.5));
+        assert_eq!(parse_speed("0x"), None);
+        assert_eq!(parse_speed("fast"), None);
+
+        let now = Instant::now();
+        let mut pacer = Pacer::new(10.0);
+        assert_eq!(pacer.due(None, now), now);
+        assert_eq!(pacer.due(Some(1000), now), now);
+        assert_eq!(pacer.due(Some(1005), now), now + Duration::from_millis(500));
+        assert_eq!(pacer.due(None, now), now + Duration::from_millis(500));
+        // A record out of order does not move the clock back.
+        assert_eq!(pacer.due(Some(1001), now), now + Duration::from_millis(500));
+        assert_eq!(pacer.due(Some(1030), now), now + Duration::from_secs(3));
+    }
+}
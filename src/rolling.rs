@@ -0,0 +1,390 @@
+use crate::data::LogLevel;
+use crate::emit::{EmitRecord, write_rfc3339};
+use crate::group::{Agg, Aggregator, GroupStats};
+use crate::tz::TimeZone;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+/// Resolution of the windows: records are counted into buckets this wide.
+const BUCKET_SECS: u64 = 10;
+
+/// Window names and lengths, shortest first; the last bounds the history.
+pub const WINDOWS: [(&str, u64); 3] = [("1m", 60), ("5m", 300), ("15m", 900)];
+
+/// Components listed per report, busiest over the longest window first.
+const MAX_COMPONENTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Dimension {
+    Level,
+    Component,
+}
+
+impl Dimension {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dimension::Level => "level",
+            Dimension::Component => "component",
+        }
+    }
+}
+
+type Series = (Dimension, Box<[u8]>);
+
+/// The level series and, when the record has one, the component series
+/// record `i` counts toward.
+fn series_of<B: EmitRecord>(batch: &B, i: usize) -> impl Iterator<Item = Series> {
+    let level = batch.record_level(i).as_str().to_ascii_lowercase();
+    let component = batch
+        .record_component(i)
+        .filter(|c| !c.is_empty())
+        .map(|c| (Dimension::Component, Box::from(c)));
+    std::iter::once((Dimension::Level, level.into_bytes().into_boxed_slice())).chain(component)
+}
+
+struct Bucket {
+    start: u64,
+    series: HashMap<Series, GroupStats>,
+}
+
+/// Rolling 1m/5m/15m record counts and aggregates per level and per
+/// component, bucketed by arrival time for follow mode.
+pub struct RollingWindows {
+    aggregator: Aggregator,
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+impl RollingWindows {
+    pub fn new(aggs: Vec<Agg>) -> RollingWindows {
+        RollingWindows {
+            aggregator: Aggregator::new(aggs),
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Counts `records` as arriving at `now` (Unix seconds).
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32], now: u64) {
+        let mut local: HashMap<Series, GroupStats> = HashMap::new();
+        for &i in records {
+            let i = i as usize;
+            for key in series_of(batch, i) {
+                let stats = local
+                    .entry(key)
+                    .or_insert_with(|| self.aggregator.new_stats());
+                self.aggregator.add_record(batch, i, stats);
+            }
+        }
+
+        let start = now - now % BUCKET_SECS;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.back().is_none_or(|b| b.start < start) {
+            buckets.push_back(Bucket {
+                start,
+                series: HashMap::new(),
+            });
+        }
+        let bucket = buckets.back_mut().unwrap();
+        for (key, stats) in local {
+            bucket.series.entry(key).or_default().merge(&stats);
+        }
+        let horizon = WINDOWS[WINDOWS.len() - 1].1;
+        while buckets.front().is_some_and(|b| b.start + horizon <= now) {
+            buckets.pop_front();
+        }
+    }
+
+    /// Totals per series over each window ending at `now`.
+    pub fn report(&self, now: u64) -> WindowReport {
+        let buckets = self.buckets.lock().unwrap();
+        let mut rows: HashMap<&Series, [GroupStats; WINDOWS.len()]> = HashMap::new();
+        for bucket in buckets.iter() {
+            // Bucket `b` counts toward a window of `len` seconds when it
+            // ends after `now - len`.
+            let age = now.saturating_sub(bucket.start + BUCKET_SECS);
+            for (key, stats) in &bucket.series {
+                let row = rows.entry(key).or_default();
+                for (w, (_, len)) in WINDOWS.iter().enumerate() {
+                    if age < *len {
+                        row[w].merge(stats);
+                    }
+                }
+            }
+        }
+        let longest = WINDOWS.len() - 1;
+        let mut rows: Vec<WindowRow> = rows
+            .into_iter()
+            .filter(|(_, stats)| stats[longest].count > 0)
+            .map(|(key, stats)| WindowRow {
+                dimension: key.0,
+                value: key.1.to_vec(),
+                stats,
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            let rank = |r: &WindowRow| {
+                LogLevel::ALL
+                    .iter()
+                    .position(|l| {
+                        l.as_str()
+                            .eq_ignore_ascii_case(&String::from_utf8_lossy(&r.value))
+                    })
+                    .unwrap_or(0)
+            };
+            a.dimension
+                .cmp(&b.dimension)
+                .then_with(|| match a.dimension {
+                    Dimension::Level => rank(b).cmp(&rank(a)),
+                    Dimension::Component => b.stats[longest].count.cmp(&a.stats[longest].count),
+                })
+                .then_with(|| a.value.cmp(&b.value))
+        });
+        let mut components = 0;
+        rows.retain(|r| {
+            components += (r.dimension == Dimension::Component) as usize;
+            components <= MAX_COMPONENTS || r.dimension == Dimension::Level
+        });
+        WindowReport {
+            at: now,
+            aggs: self.aggregator.aggs.clone(),
+            rows,
+        }
+    }
+}
+
+/// Totals per series and step of record time, for turning whole files into
+/// metrics. Steps are handed out oldest first by [`Timeline::drain`].
+pub struct Timeline {
+    step: u64,
+    /// Steps start on this zone's clock, so a day step is a local day.
+    tz: TimeZone,
+    aggregator: Aggregator,
+    state: Mutex<TimelineState>,
+}
+
+#[derive(Default)]
+struct TimelineState {
+    steps: BTreeMap<u64, HashMap<Series, GroupStats>>,
+    /// Steps starting before this were drained; later records for them are
+    /// dropped rather than sent twice.
+    drained_until: u64,
+    late: u64,
+}
+
+#[derive(Debug)]
+pub struct TimelineStep {
+    pub start: u64,
+    pub series: Vec<(Dimension, Vec<u8>, GroupStats)>,
+}
+
+impl Timeline {
+    pub fn new(step: u64, aggs: Vec<Agg>, tz: TimeZone) -> Timeline {
+        Timeline {
+            step: step.max(1),
+            tz,
+            aggregator: Aggregator::new(aggs),
+            state: Mutex::new(TimelineState::default()),
+        }
+    }
+
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    pub fn aggs(&self) -> &[Agg] {
+        &self.aggregator.aggs
+    }
+
+    /// Counts `records` into the step of their timestamp; records without
+    /// one are skipped.
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let mut local: HashMap<(u64, Series), GroupStats> = HashMap::new();
+        for &i in records {
+            let i = i as usize;
+            let Some(ts) = batch.record_timestamp(i) else {
+                continue;
+            };
+            let start = self.tz.floor(ts, self.step);
+            for key in series_of(batch, i) {
+                let stats = local
+                    .entry((start, key))
+                    .or_insert_with(|| self.aggregator.new_stats());
+                self.aggregator.add_record(batch, i, stats);
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for ((start, key), stats) in local {
+            if start < state.drained_until {
+                state.late += stats.count;
+                continue;
+            }
+            state
+                .steps
+                .entry(start)
+                .or_default()
+                .entry(key)
+                .or_default()
+                .merge(&stats);
+        }
+    }
+
+    /// Removes and returns the steps that end at or before `until`, oldest
+    /// first; `u64::MAX` drains everything.
+    pub fn drain(&self, until: u64) -> Vec<TimelineStep> {
+        let mut state = self.state.lock().unwrap();
+        let first_open = self.tz.floor(until, self.step);
+        let open = state.steps.split_off(&first_open);
+        let done = std::mem::replace(&mut state.steps, open);
+        if let Some((&last, _)) = done.last_key_value() {
+            state.drained_until = state.drained_until.max(last + 1);
+        }
+        done.into_iter()
+            .map(|(start, series)| {
+                let mut series: Vec<_> = series
+                    .into_iter()
+                    .map(|((dimension, value), stats)| (dimension, value.into_vec(), stats))
+                    .collect();
+                series.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+                TimelineStep { start, series }
+            })
+            .collect()
+    }
+
+    /// Records dropped because their step had already been drained.
+    pub fn late(&self) -> u64 {
+        self.state.lock().unwrap().late
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowRow {
+    pub dimension: Dimension,
+    pub value: Vec<u8>,
+    /// One entry per [`WINDOWS`] window.
+    pub stats: [GroupStats; WINDOWS.len()],
+}
+
+#[derive(Debug)]
+pub struct WindowReport {
+    pub at: u64,
+    pub aggs: Vec<Agg>,
+    pub rows: Vec<WindowRow>,
+}
+
+impl fmt::Display for WindowReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut at = Vec::new();
+        write_rfc3339(self.at, &mut at);
+        writeln!(f, "Rolling windows at {}:", String::from_utf8_lossy(&at))?;
+        write!(f, "  {:<32}", "series")?;
+        for (name, _) in WINDOWS {
+            write!(f, " {:>9}", name)?;
+        }
+        for agg in &self.aggs {
+            write!(f, " {:>26}", format!("{} 1m/5m/15m", agg.label()))?;
+        }
+        writeln!(f)?;
+        for row in &self.rows {
+            let series = format!(
+                "{}={}",
+                row.dimension.as_str(),
+                String::from_utf8_lossy(&row.value)
+            );
+            write!(f, "  {:<32}", series.chars().take(32).collect::<String>())?;
+            for stats in &row.stats {
+                write!(f, " {:>9}", stats.count)?;
+            }
+            for agg in &self.aggs {
+                let values: Vec<String> = row
+                    .stats
+                    .iter()
+                    .map(|s| {
+                        agg.value(s)
+                            .map_or("-".to_string(), |v| format!("{:.1}", v))
+                    })
+                    .collect();
+                write!(f, " {:>26}", values.join("/"))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::LogBatch;
+    use crate::filter::MatchControl;
+    use crate::orchestrator::parse_logs_pipelined_with;
+
+    #[test]
+    fn test_rolling_windows_age_out() {
+        let data = b"2025-02-12T10:00:00Z ERROR api failed latency_ms=900\n\
+                     2025-02-12T10:00:01Z INFO api ok\n\
+                     2025-02-12T10:00:02Z INFO db ok\n";
+        let windows = RollingWindows::new(vec![]);
+        let now = 1_000_000;
+        let add = |batch: &LogBatch, records: &[u32]| {
+            // The same three records arriving 20 min, 14 min, 4 min and
+            // 10 s ago.
+            for age in [1200, 840, 240, 10] {
+                windows.add_records(batch, records, now - age);
+            }
+        };
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_logs_pipelined_with(data, 1, &control);
+
+        let report = windows.report(now);
+        let counts: Vec<(String, [u64; 3])> = report
+            .rows
+            .iter()
+            .map(|r| {
+                (
+                    format!(
+                        "{}={}",
+                        r.dimension.as_str(),
+                        String::from_utf8_lossy(&r.value)
+                    ),
+                    [r.stats[0].count, r.stats[1].count, r.stats[2].count],
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("level=error".to_string(), [1, 2, 3]),
+                ("level=info".to_string(), [2, 4, 6]),
+                ("component=api".to_string(), [2, 4, 6]),
+                ("component=db".to_string(), [1, 2, 3]),
+            ]
+        );
+        assert_eq!(windows.buckets.lock().unwrap().len(), 3);
+        assert!(report.to_string().contains("level=error"));
+    }
+
+    #[test]
+    fn test_timeline_local_days() {
+        let data = b"2025-02-12T03:00:00Z INFO api ok\n\
+                     2025-02-12T06:00:00Z INFO api ok\n\
+                     2025-02-13T04:59:59Z INFO api ok\n";
+        let timeline = Timeline::new(86400, vec![], TimeZone::parse("-05:00").unwrap());
+        let add = |batch: &LogBatch, records: &[u32]| timeline.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_logs_pipelined_with(data, 1, &control);
+
+        // Days start at 05:00Z, so the last two records share 2025-02-12.
+        let steps = timeline.drain(u64::MAX);
+        let starts: Vec<u64> = steps.iter().map(|s| s.start).collect();
+        assert_eq!(starts, [1_739_250_000, 1_739_336_400]);
+        let levels: Vec<u64> = steps.iter().map(|s| s.series[0].2.count).collect();
+        assert_eq!(levels, [1, 2]);
+    }
+}
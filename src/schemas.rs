@@ -0,0 +1,227 @@
+use crate::emit::EmitRecord;
+use crate::keys::KeyTable;
+use crate::manifest::Xxh64;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Schemas listed in the report, most records first.
+const MAX_SCHEMAS: usize = 20;
+
+/// Keys listed per schema in the report.
+const MAX_KEYS: usize = 12;
+
+/// ID of a record schema: a hash of its distinct key names in byte order,
+/// so a key set gets the same ID in every batch, file and run, whatever
+/// order its records list the keys in.
+pub fn schema_id<'a>(names: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut names: Vec<&[u8]> = names.into_iter().collect();
+    names.sort_unstable();
+    names.dedup();
+    let mut hasher = Xxh64::new(0);
+    for name in names {
+        hasher.update(&(name.len() as u32).to_le_bytes());
+        hasher.update(name);
+    }
+    hasher.digest()
+}
+
+/// The distinct key sets of one batch's records, numbered densely like
+/// [`KeyTable`] numbers keys.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaTable {
+    /// Sorted key ids and [`schema_id`] of each schema.
+    schemas: Vec<(Box<[u32]>, u64)>,
+    index: HashMap<Box<[u32]>, u32>,
+    scratch: Vec<u32>,
+}
+
+impl SchemaTable {
+    /// Number of the schema of a record whose fields have keys `key_ids`.
+    pub fn intern(&mut self, key_ids: impl Iterator<Item = u32>, keys: &KeyTable) -> u32 {
+        self.scratch.clear();
+        self.scratch.extend(key_ids);
+        self.scratch.sort_unstable();
+        self.scratch.dedup();
+        if let Some(&schema) = self.index.get(&self.scratch[..]) {
+            return schema;
+        }
+        let id = schema_id(self.scratch.iter().map(|&key| keys.name(key)));
+        let schema = self.schemas.len() as u32;
+        let set: Box<[u32]> = self.scratch.as_slice().into();
+        self.index.insert(set.clone(), schema);
+        self.schemas.push((set, id));
+        schema
+    }
+
+    #[inline]
+    pub fn id(&self, schema: u32) -> u64 {
+        self.schemas[schema as usize].1
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaCount {
+    pub id: u64,
+    /// Key names in byte order.
+    pub keys: Vec<Vec<u8>>,
+    pub records: u64,
+}
+
+/// Matched records per schema (`--schema-report`), to spot producers whose
+/// records drift apart in shape.
+#[derive(Default)]
+pub struct SchemaCounts {
+    schemas: Mutex<HashMap<u64, SchemaCount>>,
+}
+
+impl SchemaCounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let mut local: HashMap<u64, SchemaCount> = HashMap::new();
+        for &i in records {
+            let id = batch.schema_id(i as usize);
+            local
+                .entry(id)
+                .or_insert_with(|| SchemaCount {
+                    id,
+                    keys: record_keys(batch, i as usize),
+                    records: 0,
+                })
+                .records += 1;
+        }
+
+        let mut schemas = self.schemas.lock().unwrap();
+        for (id, count) in local {
+            match schemas.get_mut(&id) {
+                Some(seen) => seen.records += count.records,
+                None => {
+                    schemas.insert(id, count);
+                }
+            }
+        }
+    }
+
+    pub fn report(&self) -> SchemaReport {
+        let schemas = self.schemas.lock().unwrap();
+        let mut schemas: Vec<SchemaCount> = schemas.values().cloned().collect();
+        schemas.sort_by(|a, b| b.records.cmp(&a.records).then(a.id.cmp(&b.id)));
+        SchemaReport { schemas }
+    }
+}
+
+fn record_keys<B: EmitRecord>(batch: &B, i: usize) -> Vec<Vec<u8>> {
+    let mut keys = Vec::new();
+    batch.for_each_field(i, &mut |key, _| keys.push(key.to_vec()));
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+#[derive(Debug, Default)]
+pub struct SchemaReport {
+    /// Most records first.
+    pub schemas: Vec<SchemaCount>,
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: u64 = self.schemas.iter().map(|s| s.records).sum();
+        writeln!(
+            f,
+            "Schemas: {} distinct key sets over {} records",
+            self.schemas.len(),
+            total
+        )?;
+        if self.schemas.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "  {:<16} {:>10} {:>6}  keys", "schema", "records", "%")?;
+        for schema in self.schemas.iter().take(MAX_SCHEMAS) {
+            let mut keys: Vec<String> = schema
+                .keys
+                .iter()
+                .take(MAX_KEYS)
+                .map(|k| String::from_utf8_lossy(k).into_owned())
+                .collect();
+            if schema.keys.len() > MAX_KEYS {
+                keys.push(format!("+{} more", schema.keys.len() - MAX_KEYS));
+            }
+            writeln!(
+                f,
+                "  {:016x} {:>10} {:>6.1}  {}",
+                schema.id,
+                schema.records,
+                100.0 * schema.records as f64 / total.max(1) as f64,
+                keys.join(", ")
+            )?;
+        }
+        if self.schemas.len() > MAX_SCHEMAS {
+            writeln!(f, "  ... {} more schemas", self.schemas.len() - MAX_SCHEMAS)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_schema_counts() {
+        let data = br#"{"level":"info","msg":"a","ts":1}
+{"ts":2,"msg":"b","level":"warn"}
+{"level":"info","msg":"c"}
+{"level":"info","msg":"d","ts":3,"msg":"dup"}
+"#;
+        let counts = SchemaCounts::new();
+        let add = |batch: &StructuredBatch, records: &[u32]| counts.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            tag_schemas: true,
+            ..MatchControl::default()
+        };
+        let result = parse_structured_mmap_with(data, 1, Some(LogFormat::Json), &control);
+
+        let batch = &result.batches[0];
+        assert_eq!(batch.schemas.len(), 2);
+        assert_eq!(batch.schema_ids, [0, 0, 1, 0]);
+        let full = schema_id([&b"ts"[..], b"msg", b"level"]);
+        assert_eq!(batch.schema_id(1), full);
+        assert_ne!(batch.schema_id(2), full);
+
+        // Untagged batches hash the keys when asked instead.
+        let untagged =
+            parse_structured_mmap_with(data, 1, Some(LogFormat::Json), &MatchControl::default());
+        let batch = &untagged.batches[0];
+        assert!(batch.schema_ids.is_empty() && batch.schemas.is_empty());
+        assert_eq!(batch.schema_id(3), full);
+
+        let report = counts.report();
+        assert_eq!(report.schemas.len(), 2);
+        assert_eq!(report.schemas[0].id, full);
+        assert_eq!(report.schemas[0].records, 3);
+        assert_eq!(report.schemas[0].keys, [&b"level"[..], b"msg", b"ts"]);
+        assert_eq!(report.schemas[1].keys, [&b"level"[..], b"msg"]);
+        let text = report.to_string();
+        assert!(text.contains("2 distinct key sets over 4 records"));
+        assert!(text.contains(&format!("{:016x}", full)));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::csv_parser::{self, CsvHeader};
+use crate::format::LogFormat;
+use crate::simd_scan::record_sep;
+use crate::structured::StructuredBatch;
+use crate::{json_parser, logfmt_parser, parser};
+
+/// Binary-searches a time-ordered buffer for the first line stamped at or
+/// after `ts` and returns its byte offset (`data.len()` if there is none).
+/// Lines without a parseable timestamp are treated as continuations of the
+/// record before them. For CSV, `data` must start after the header row.
+pub fn seek_to_time(
+    data: &[u8],
+    format: LogFormat,
+    csv_header: Option<&CsvHeader>,
+    ts: u64,
+) -> usize {
+    // Invariant: every timestamped line starting before `lo` is older than
+    // `ts`, and `hi` is either `data.len()` or a line at or after `ts`.
+    let mut lo = 0;
+    let mut hi = data.len();
+    let sep = record_sep();
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let probe = match memchr::memchr(sep, &data[mid..hi]) {
+            Some(off) if mid + off + 1 < hi => mid + off + 1,
+            _ => memchr::memrchr(sep, &data[lo..mid]).map_or(lo, |off| lo + off + 1),
+        };
+
+        match next_timestamped_line(data, probe, hi, format, csv_header) {
+            Some((_, end, line_ts)) if line_ts < ts => lo = (end + 1).min(hi),
+            // Untimestamped lines between `probe` and the hit belong to the
+            // record before `probe`; when that record is known to be older,
+            // skip them along with it.
+            Some((start, _, _)) if probe == lo => (lo, hi) = (start, start),
+            Some((start, _, _)) => hi = start,
+            None if probe == lo => lo = hi,
+            None => hi = probe,
+        }
+    }
+
+    lo
+}
+
+/// Timestamp of a single line, using the same field classification as the
+/// format's parser.
+pub fn line_timestamp(
+    line: &[u8],
+    format: LogFormat,
+    csv_header: Option<&CsvHeader>,
+) -> Option<u64> {
+    if format == LogFormat::PlainText {
+        let token_end = memchr::memchr(b' ', line).unwrap_or(line.len());
+        return parser::parse_timestamp(&line[..token_end]);
+    }
+
+    let mut batch = StructuredBatch::with_capacity(1, 16, line.as_ptr());
+    match format {
+        LogFormat::Json => json_parser::parse_json_line(line, 0, &mut batch),
+        LogFormat::Logfmt => logfmt_parser::parse_logfmt_line(line, 0, &mut batch),
+        LogFormat::Csv => csv_parser::parse_csv_line(line, 0, csv_header?, &mut batch),
+        LogFormat::FixedWidth => return None,
+        LogFormat::PlainText => unreachable!(),
+    }
+    if batch.len == 0 {
+        return None;
+    }
+    let value = unsafe { batch.timestamp_value(0) }?;
+    parser::parse_timestamp_value(value.as_bytes())
+}
+
+fn next_timestamped_line(
+    data: &[u8],
+    mut start: usize,
+    hi: usize,
+    format: LogFormat,
+    csv_header: Option<&CsvHeader>,
+) -> Option<(usize, usize, u64)> {
+    while start < hi {
+        let end = memchr::memchr(record_sep(), &data[start..hi]).map_or(hi, |off| start + off);
+        let line = data[start..end]
+            .strip_suffix(b"\r")
+            .unwrap_or(&data[start..end]);
+        if let Some(ts) = line_timestamp(line, format, csv_header) {
+            return Some((start, end, ts));
+        }
+        start = end + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain_lines(count: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..count {
+            let line = format!(
+                "2025-02-12T10:{:02}:{:02}Z INFO api request {}\n",
+                i / 60,
+                i % 60,
+                i
+            );
+            data.extend_from_slice(line.as_bytes());
+            if i % 7 == 3 {
+                data.extend_from_slice(b"    at continuation frame\n");
+            }
+        }
+        data
+    }
+
+    fn base() -> u64 {
+        parser::parse_timestamp(b"2025-02-12T10:00:00Z").unwrap()
+    }
+
+    #[test]
+    fn test_seek_plain_text() {
+        let data = plain_lines(500);
+        for target in [0, 1, 3, 4, 250, 499] {
+            let off = seek_to_time(&data, LogFormat::PlainText, None, base() + target);
+            let expected = format!("request {}\n", target);
+            let line_end = memchr::memchr(b'\n', &data[off..]).unwrap() + off + 1;
+            assert!(
+                data[off..line_end].ends_with(expected.as_bytes()),
+                "target {}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_seek_out_of_range() {
+        let data = plain_lines(50);
+        assert_eq!(seek_to_time(&data, LogFormat::PlainText, None, 0), 0);
+        assert_eq!(
+            seek_to_time(&data, LogFormat::PlainText, None, base() + 1000),
+            data.len()
+        );
+        assert_eq!(seek_to_time(b"", LogFormat::PlainText, None, base()), 0);
+    }
+
+    #[test]
+    fn test_seek_json() {
+        let mut data = Vec::new();
+        for i in 0..200 {
+            let line = format!(
+                "{{\"timestamp\":\"2025-02-12T10:{:02}:{:02}Z\",\"level\":\"info\",\"n\":{}}}\n",
+                i / 60,
+                i % 60,
+                i
+            );
+            data.extend_from_slice(line.as_bytes());
+        }
+        let off = seek_to_time(&data, LogFormat::Json, None, base() + 123);
+        assert!(data[off..].starts_with(b"{\"timestamp\":\"2025-02-12T10:02:03Z\""));
+    }
+
+    #[test]
+    fn test_seek_csv() {
+        let data = b"timestamp,level,message\n\
+                     2025-02-12T10:00:00Z,info,a\n\
+                     2025-02-12T10:00:05Z,info,b\n\
+                     2025-02-12T10:00:09Z,warn,c\n";
+        let header = CsvHeader::parse(data).unwrap();
+        let body = &data[csv_parser::header_end_offset(data)..];
+        let off = seek_to_time(body, LogFormat::Csv, Some(&header), base() + 6);
+        assert!(body[off..].starts_with(b"2025-02-12T10:00:09Z"));
+    }
+}
@@ -0,0 +1,427 @@
+use crate::json_parser::{find_string_end_with, skip_whitespace_with};
+use crate::parser::{parse_timestamp, parse_timestamps_x4_with};
+use crate::simd_scan::{self, Kernel, count_newlines_with, scan_region_with};
+use std::fmt;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Input measured per kernel unless `--bench-mb` says otherwise.
+pub const DEFAULT_BENCH_BYTES: usize = 32 << 20;
+
+/// Timed passes per kernel; the fastest counts.
+const BENCH_PASSES: usize = 3;
+
+/// Buffer lengths around the 32- and 64-byte vector widths and the
+/// unrolled 256-byte loops.
+const LENGTHS: &[usize] = &[
+    0, 1, 7, 8, 9, 31, 32, 33, 63, 64, 65, 127, 128, 129, 255, 256, 257, 1000, 4109,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// The first mismatch with the reference.
+    Failed(String),
+    /// The CPU lacks the instructions, or the kernel has no such variant.
+    Unsupported,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelCheck {
+    pub name: &'static str,
+    pub kernel: Kernel,
+    pub outcome: Outcome,
+    pub gbps: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct SelftestReport {
+    pub capability: &'static str,
+    pub checks: Vec<KernelCheck>,
+}
+
+impl SelftestReport {
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|c| !matches!(c.outcome, Outcome::Failed(_)))
+    }
+}
+
+/// Newline layouts for each length: none, all, every 13th shifted by the
+/// length, and one on the last byte.
+fn newline_vectors() -> Vec<Vec<u8>> {
+    let mut vectors = Vec::new();
+    for &len in LENGTHS {
+        vectors.push(vec![b'a'; len]);
+        vectors.push(vec![b'\n'; len]);
+        vectors.push(
+            (0..len)
+                .map(|i| if (i * 7 + len) % 13 == 0 { b'\n' } else { b'x' })
+                .collect(),
+        );
+        let mut last = vec![b'z'; len];
+        if let Some(b) = last.last_mut() {
+            *b = b'\n';
+        }
+        vectors.push(last);
+    }
+    vectors
+}
+
+/// String bodies with the closing quote at each vector boundary, after
+/// escaped quotes and runs of backslashes that straddle the boundary.
+fn string_vectors() -> Vec<Vec<u8>> {
+    let mut vectors = Vec::new();
+    for &len in LENGTHS {
+        for tail in [&b"\""[..], b"\\\"x\"", b"\\\\\"", b"\\\\\\\"y\""] {
+            let mut v = vec![b's'; len];
+            v.extend_from_slice(tail);
+            v.extend_from_slice(&[b'p'; 70]);
+            vectors.push(v);
+        }
+        vectors.push(vec![b'n'; len]);
+    }
+    vectors
+}
+
+/// Runs of JSON whitespace ending at each vector boundary on a byte that
+/// shares a nibble with one of the whitespace characters.
+fn whitespace_vectors() -> Vec<Vec<u8>> {
+    let mut vectors = Vec::new();
+    for &len in LENGTHS {
+        for stop in [b'"', b'}', b'\x0b', b'\x8d', b'\x10'] {
+            let mut v: Vec<u8> = (0..len).map(|i| b" \t\r\n"[(i * 5 + len) % 4]).collect();
+            v.push(stop);
+            v.extend_from_slice(&[b' '; 40]);
+            vectors.push(v);
+        }
+        vectors.push(vec![b' '; len]);
+    }
+    vectors
+}
+
+fn timestamp_vectors() -> Vec<[&'static [u8]; 4]> {
+    vec![
+        [
+            b"2025-02-12T10:31:45Z INFO",
+            b"1970-01-01T00:00:00Z",
+            b"2024-02-29T23:59:59.999Z",
+            b"2099-12-31T12:00:00+01:00",
+        ],
+        [
+            b"2025-02-12 10:31:45 INFO",
+            b"2025/02/12T10:31:45Z",
+            b"2025-02-1xT10:31:45Z",
+            b"2025-02-12T10:31:4x",
+        ],
+        [
+            b"2000-01-01T00:00:01Z",
+            b"2025-13-12T10:31:45Z",
+            b"abcdefghijklmnopqrstuvwxyz",
+            b"2038-01-19T03:14:08Z",
+        ],
+    ]
+}
+
+fn reference_line_starts(data: &[u8], base: u64, total: u64) -> Vec<u64> {
+    data.iter()
+        .enumerate()
+        .filter(|&(i, &b)| b == b'\n' && base + (i as u64) + 1 < total)
+        .map(|(i, _)| base + i as u64 + 1)
+        .collect()
+}
+
+fn reference_string_end(data: &[u8], start: usize) -> usize {
+    let mut escaped = false;
+    for (i, &b) in data.iter().enumerate().skip(start) {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return i,
+            _ => {}
+        }
+    }
+    data.len()
+}
+
+fn check_scan(kernel: Kernel) -> Outcome {
+    for data in newline_vectors() {
+        for (base, total) in [(0, data.len() as u64), (1000, 1000 + data.len() as u64 + 1)] {
+            let mut starts = Vec::new();
+            if scan_region_with(kernel, &data, base, total, &mut starts).is_none() {
+                return Outcome::Unsupported;
+            }
+            if starts != reference_line_starts(&data, base, total) {
+                return Outcome::Failed(format!(
+                    "line starts differ for a {}-byte input",
+                    data.len()
+                ));
+            }
+        }
+    }
+    Outcome::Passed
+}
+
+fn check_count(kernel: Kernel) -> Outcome {
+    for data in newline_vectors() {
+        let expected = data.iter().filter(|&&b| b == b'\n').count() as u64;
+        match count_newlines_with(kernel, &data) {
+            None => return Outcome::Unsupported,
+            Some(n) if n != expected => {
+                return Outcome::Failed(format!(
+                    "counted {} newlines in a {}-byte input, expected {}",
+                    n,
+                    data.len(),
+                    expected
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    Outcome::Passed
+}
+
+fn check_string_end(kernel: Kernel) -> Outcome {
+    for data in string_vectors() {
+        for start in [0, 1, 31, 33] {
+            if start > data.len() {
+                continue;
+            }
+            let expected = reference_string_end(&data, start);
+            match find_string_end_with(kernel, &data, start) {
+                None => return Outcome::Unsupported,
+                Some(end) if end != expected => {
+                    return Outcome::Failed(format!(
+                        "string from {} in {:?} ends at {}, expected {}",
+                        start,
+                        String::from_utf8_lossy(&data[..expected.min(data.len())]),
+                        end,
+                        expected
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Outcome::Passed
+}
+
+fn check_whitespace(kernel: Kernel) -> Outcome {
+    for data in whitespace_vectors() {
+        for start in [0, 1, 17, 33] {
+            if start > data.len() {
+                continue;
+            }
+            let expected = data[start..]
+                .iter()
+                .position(|b| !b" \t\r\n".contains(b))
+                .map_or(data.len(), |at| start + at);
+            match skip_whitespace_with(kernel, &data, start) {
+                None => return Outcome::Unsupported,
+                Some(end) if end != expected => {
+                    return Outcome::Failed(format!(
+                        "whitespace from {} in a {}-byte input ends at {}, expected {}",
+                        start,
+                        data.len(),
+                        end,
+                        expected
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Outcome::Passed
+}
+
+fn check_timestamps(kernel: Kernel) -> Outcome {
+    for lines in timestamp_vectors() {
+        let mut out = [0u64; 4];
+        let Some(valid) = parse_timestamps_x4_with(kernel, lines, &mut out) else {
+            return Outcome::Unsupported;
+        };
+        for (k, line) in lines.iter().enumerate() {
+            let expected = parse_timestamp(line).filter(|_| line[10] == b'T');
+            let got = (valid & (1 << k) != 0).then_some(out[k]);
+            if got != expected {
+                return Outcome::Failed(format!(
+                    "{:?} parsed as {:?}, expected {:?}",
+                    String::from_utf8_lossy(line),
+                    got,
+                    expected
+                ));
+            }
+        }
+    }
+    Outcome::Passed
+}
+
+type Check = fn(Kernel) -> Outcome;
+
+/// Log-like lines, about 100 bytes each.
+fn bench_input(bytes: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(bytes + 128);
+    let mut n = 0u64;
+    while data.len() < bytes {
+        data.extend_from_slice(
+            format!(
+                "2025-02-12T10:{:02}:{:02}Z INFO api-server request {} served in {} ms\n",
+                n / 60 % 60,
+                n % 60,
+                n,
+                n % 997
+            )
+            .as_bytes(),
+        );
+        n += 1;
+    }
+    data.truncate(bytes);
+    data
+}
+
+/// Best of `BENCH_PASSES` runs of `pass` over `bytes` bytes, in GB/s.
+fn measure(bytes: usize, mut pass: impl FnMut()) -> f64 {
+    let mut best = f64::INFINITY;
+    for _ in 0..BENCH_PASSES {
+        let start = Instant::now();
+        pass();
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    bytes as f64 / best.max(1e-9) / 1e9
+}
+
+fn bench(name: &str, kernel: Kernel, data: &[u8]) -> f64 {
+    match name {
+        "newline-scan" => {
+            let mut starts = Vec::with_capacity(data.len() / 64);
+            measure(data.len(), || {
+                starts.clear();
+                scan_region_with(kernel, data, 0, data.len() as u64, &mut starts);
+                black_box(&starts);
+            })
+        }
+        "newline-count" => measure(data.len(), || {
+            black_box(count_newlines_with(kernel, black_box(data)));
+        }),
+        "json-string-end" => {
+            // One long string, so the kernel's scanning loop dominates.
+            let mut string = data
+                .iter()
+                .map(|&b| if b == b'"' { b'\'' } else { b })
+                .collect::<Vec<_>>();
+            string.push(b'"');
+            measure(data.len(), || {
+                black_box(find_string_end_with(kernel, black_box(&string), 0));
+            })
+        }
+        "json-whitespace" => {
+            // One long run of padding, as with the string scan.
+            let mut padding: Vec<u8> = data
+                .iter()
+                .map(|&b| if b == b'\n' { b'\n' } else { b' ' })
+                .collect();
+            padding.push(b'}');
+            measure(data.len(), || {
+                black_box(skip_whitespace_with(kernel, black_box(&padding), 0));
+            })
+        }
+        _ => {
+            let lines: Vec<&[u8]> = data.chunks_exact(100).collect();
+            let mut out = [0u64; 4];
+            measure(lines.len() / 4 * 4 * 19, || {
+                for quad in lines.chunks_exact(4) {
+                    let quad = [quad[0], quad[1], quad[2], quad[3]];
+                    black_box(parse_timestamps_x4_with(kernel, quad, &mut out));
+                }
+            })
+        }
+    }
+}
+
+/// Checks every kernel the CPU supports against the reference vectors and
+/// times the ones that pass over `bench_bytes` of log lines.
+pub fn run(bench_bytes: usize) -> SelftestReport {
+    let checks: [(&'static str, Check); 5] = [
+        ("newline-scan", check_scan),
+        ("newline-count", check_count),
+        ("json-string-end", check_string_end),
+        ("json-whitespace", check_whitespace),
+        ("timestamp-x4", check_timestamps),
+    ];
+    let data = bench_input(bench_bytes);
+    let mut results = Vec::new();
+    for (name, check) in checks {
+        for kernel in Kernel::ALL {
+            let outcome = check(kernel);
+            let gbps =
+                (outcome == Outcome::Passed && bench_bytes > 0).then(|| bench(name, kernel, &data));
+            results.push(KernelCheck {
+                name,
+                kernel,
+                outcome,
+                gbps,
+            });
+        }
+    }
+    SelftestReport {
+        capability: simd_scan::simd_capability(),
+        checks: results,
+    }
+}
+
+impl fmt::Display for SelftestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "SIMD self-test ({})", self.capability)?;
+        for check in &self.checks {
+            let status = match &check.outcome {
+                Outcome::Passed => "pass".to_string(),
+                Outcome::Failed(why) => format!("FAIL: {}", why),
+                Outcome::Unsupported => "n/a".to_string(),
+            };
+            write!(
+                f,
+                "  {:<16} {:<7} {}",
+                check.name,
+                check.kernel.name(),
+                status
+            )?;
+            if let Some(gbps) = check.gbps {
+                write!(f, "  {:.2} GB/s", gbps)?;
+            }
+            writeln!(f)?;
+        }
+        let failed = self
+            .checks
+            .iter()
+            .filter(|c| matches!(c.outcome, Outcome::Failed(_)))
+            .count();
+        match failed {
+            0 => writeln!(f, "All supported kernels match the reference"),
+            n => writeln!(f, "{} kernels disagree with the reference", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest_passes_on_this_host() {
+        let report = run(1 << 16);
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.checks.len(), 15);
+        let scalar = report
+            .checks
+            .iter()
+            .filter(|c| c.kernel == Kernel::Scalar)
+            .collect::<Vec<_>>();
+        assert!(
+            scalar
+                .iter()
+                .all(|c| c.outcome == Outcome::Passed && c.gbps.is_some())
+        );
+        assert!(report.to_string().contains("newline-scan     scalar  pass"));
+    }
+}
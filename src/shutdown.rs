@@ -0,0 +1,28 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C was pressed; orchestrators stop picking up new chunks once
+/// it is, so what has been parsed can still be flushed and reported.
+#[inline]
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_: libc::c_int) {
+    // A second Ctrl-C means the user does not want to wait for the drain.
+    if INTERRUPTED.swap(true, Ordering::Relaxed) {
+        unsafe { libc::_exit(130) };
+    }
+}
+
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// The one mapping being parsed; a SIGBUS inside it means the file shrank
+// (log rotation, another NFS client) after it was mapped.
+static MAP_START: AtomicUsize = AtomicUsize::new(0);
+static MAP_LEN: AtomicUsize = AtomicUsize::new(0);
+static FIRST_FAULT: AtomicUsize = AtomicUsize::new(usize::MAX);
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(4096);
+
+/// Replaces the faulting page and everything after it in the watched
+/// mapping with zero pages, so workers read NULs instead of dying. Faults
+/// anywhere else get the default action, which re-faults and crashes.
+#[cfg(target_os = "linux")]
+extern "C" fn on_sigbus(_: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    unsafe {
+        let addr = (*info).si_addr() as usize;
+        let start = MAP_START.load(Ordering::Relaxed);
+        let end = start + MAP_LEN.load(Ordering::Relaxed);
+        if start != 0 && (start..end).contains(&addr) {
+            let page = addr & !(PAGE_SIZE.load(Ordering::Relaxed) - 1);
+            let patched = libc::mmap(
+                page as *mut libc::c_void,
+                end - page,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                -1,
+                0,
+            );
+            if patched != libc::MAP_FAILED {
+                FIRST_FAULT.fetch_min(page - start, Ordering::Relaxed);
+                return;
+            }
+        }
+        libc::signal(libc::SIGBUS, libc::SIG_DFL);
+    }
+}
+
+pub fn install() {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        PAGE_SIZE.store(
+            libc::sysconf(libc::_SC_PAGESIZE) as usize,
+            Ordering::Relaxed,
+        );
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigbus
+            as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)
+            as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut());
+    }
+}
+
+/// Starts guarding `data`, an mmap of the input, until [`unwatch`].
+pub fn watch(data: &[u8]) {
+    FIRST_FAULT.store(usize::MAX, Ordering::Relaxed);
+    MAP_LEN.store(data.len(), Ordering::Relaxed);
+    MAP_START.store(data.as_ptr() as usize, Ordering::Relaxed);
+}
+
+/// Stops guarding and returns the offset from which the mapping read as
+/// zeros, if the file shrank while it was watched.
+pub fn unwatch() -> Option<u64> {
+    MAP_START.store(0, Ordering::Relaxed);
+    MAP_LEN.store(0, Ordering::Relaxed);
+    match FIRST_FAULT.swap(usize::MAX, Ordering::Relaxed) {
+        usize::MAX => None,
+        offset => Some(offset as u64),
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use memmap2::Mmap;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn test_truncated_mapping_reads_zeros() {
+        let path = std::env::temp_dir().join(format!("pandora-sigbus-{}", std::process::id()));
+        let page = 64 * 1024;
+        File::create(&path)
+            .unwrap()
+            .write_all(&vec![b'x'; 3 * page])
+            .unwrap();
+        let file = File::open(&path).unwrap();
+        let mmap = unsafe { Mmap::map(&file).unwrap() };
+
+        install();
+        watch(&mmap);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(page as u64)
+            .unwrap();
+        let tail = unsafe { std::ptr::read_volatile(&mmap[2 * page + 7]) };
+        let head = mmap[page - 1];
+        let fault = unwatch();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tail, 0);
+        assert_eq!(head, b'x');
+        assert!(fault.is_some_and(|offset| offset >= page as u64 && offset <= 2 * page as u64 + 7));
+    }
+}
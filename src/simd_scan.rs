@@ -1,5 +1,55 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 #[cfg(test)]
 use std::thread;
+use std::time::{Duration, Instant};
+
+static RECORD_SEP: AtomicU8 = AtomicU8::new(b'\n');
+
+/// The byte that ends a record: `\n`, or NUL under `--record-sep nul`.
+/// Scans, chunk boundaries and the parsers' line ends all follow it.
+pub fn record_sep() -> u8 {
+    RECORD_SEP.load(Ordering::Relaxed)
+}
+
+/// Sets the separator for the rest of the process; call it before parsing.
+pub fn set_record_sep(sep: u8) {
+    RECORD_SEP.store(sep, Ordering::Relaxed);
+}
+
+static VERIFY_SCAN: AtomicBool = AtomicBool::new(false);
+static SCAN_MISMATCHES: AtomicU64 = AtomicU64::new(0);
+static FIRST_MISMATCH: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Under `--verify-scan`, every scan also counts its region's separators,
+/// newlines with the counting kernel, and records a mismatch when the two
+/// disagree.
+pub fn set_verify_scan(on: bool) {
+    VERIFY_SCAN.store(on, Ordering::Relaxed);
+}
+
+pub fn verify_scan() -> bool {
+    VERIFY_SCAN.load(Ordering::Relaxed)
+}
+
+/// Scanned regions whose positions disagreed with the separator count, and
+/// the lowest byte offset of one.
+pub fn scan_mismatches() -> (u64, Option<u64>) {
+    let first = FIRST_MISMATCH.load(Ordering::Relaxed);
+    (
+        SCAN_MISMATCHES.load(Ordering::Relaxed),
+        (first != u64::MAX).then_some(first),
+    )
+}
+
+/// Parses a `--record-sep` name.
+pub fn parse_record_sep(name: &str) -> Option<u8> {
+    match name {
+        "newline" | "lf" | "\\n" => Some(b'\n'),
+        "nul" | "null" | "\\0" => Some(0),
+        _ => None,
+    }
+}
 
 #[cfg(test)]
 pub fn scan_newlines(data: &[u8]) -> Vec<u64> {
@@ -70,29 +120,86 @@ pub fn scan_newlines_parallel(data: &[u8], num_threads: usize) -> Vec<u64> {
 }
 
 pub fn scan_region(data: &[u8], global_base: u64, data_total_len: u64, line_starts: &mut Vec<u64>) {
-    #[cfg(target_arch = "x86_64")]
-    {
-        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
-            unsafe {
-                scan_region_avx512(data, global_base, data_total_len, line_starts);
-            }
-            return;
-        }
-        if is_x86_feature_detected!("avx2") {
-            unsafe {
-                scan_region_avx2(data, global_base, data_total_len, line_starts);
-            }
-            return;
+    scan_region_sep(data, record_sep(), global_base, data_total_len, line_starts)
+}
+
+/// `scan_region` for records ending in `sep` rather than the process-wide
+/// separator.
+pub fn scan_region_sep(
+    data: &[u8],
+    sep: u8,
+    global_base: u64,
+    data_total_len: u64,
+    line_starts: &mut Vec<u64>,
+) {
+    let before = line_starts.len();
+    scan_region_dispatch(data, sep, global_base, data_total_len, line_starts);
+    if verify_scan() {
+        let found = (line_starts.len() - before) as u64;
+        if found != expected_positions(data, sep, global_base, data_total_len) {
+            SCAN_MISMATCHES.fetch_add(1, Ordering::Relaxed);
+            FIRST_MISMATCH.fetch_min(global_base, Ordering::Relaxed);
         }
     }
+}
 
-    scan_region_scalar(data, global_base, data_total_len, line_starts);
+/// Positions a scan of `data` should produce: one per `sep`, except one
+/// ending the input, which starts no line. Newlines are counted by the
+/// counting kernel, which only knows them, other separators by `memchr`.
+fn expected_positions(data: &[u8], sep: u8, global_base: u64, data_total_len: u64) -> u64 {
+    let ends_input = global_base + data.len() as u64 >= data_total_len;
+    let count = if sep == b'\n' {
+        count_newlines_in_region(data)
+    } else {
+        memchr::memchr_iter(sep, data).count() as u64
+    };
+    count - u64::from(ends_input && data.last() == Some(&sep))
+}
+
+fn scan_region_dispatch(
+    data: &[u8],
+    sep: u8,
+    global_base: u64,
+    data_total_len: u64,
+    line_starts: &mut Vec<u64>,
+) {
+    scan_region_kernel(
+        active_kernel(),
+        data,
+        sep,
+        global_base,
+        data_total_len,
+        line_starts,
+    );
+}
+
+/// Scans with `kernel`, which the CPU must support.
+fn scan_region_kernel(
+    kernel: Kernel,
+    data: &[u8],
+    sep: u8,
+    global_base: u64,
+    data_total_len: u64,
+    line_starts: &mut Vec<u64>,
+) {
+    match kernel {
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx512 => unsafe {
+            scan_region_avx512(data, sep, global_base, data_total_len, line_starts)
+        },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe {
+            scan_region_avx2(data, sep, global_base, data_total_len, line_starts)
+        },
+        _ => scan_region_scalar(data, sep, global_base, data_total_len, line_starts),
+    }
 }
 
 #[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx512f,avx512bw")]
 unsafe fn scan_region_avx512(
     data: &[u8],
+    sep: u8,
     global_base: u64,
     data_total_len: u64,
     line_starts: &mut Vec<u64>,
@@ -100,7 +207,7 @@ unsafe fn scan_region_avx512(
     unsafe {
         use std::arch::x86_64::*;
 
-        let newline = _mm512_set1_epi8(0x0Au8 as i8);
+        let newline = _mm512_set1_epi8(sep as i8);
         let len = data.len();
         let ptr = data.as_ptr();
 
@@ -159,15 +266,13 @@ unsafe fn scan_region_avx512(
             offset += 64;
         }
 
-        while offset < len {
-            if *ptr.add(offset) == b'\n' {
-                let next_line = global_base + (offset as u64) + 1;
-                if next_line < data_total_len {
-                    line_starts.push(next_line);
-                }
-            }
-            offset += 1;
-        }
+        scan_region_scalar(
+            &data[offset..],
+            sep,
+            global_base + offset as u64,
+            data_total_len,
+            line_starts,
+        );
     }
 }
 
@@ -175,6 +280,7 @@ unsafe fn scan_region_avx512(
 #[target_feature(enable = "avx2")]
 unsafe fn scan_region_avx2(
     data: &[u8],
+    sep: u8,
     global_base: u64,
     data_total_len: u64,
     line_starts: &mut Vec<u64>,
@@ -182,7 +288,7 @@ unsafe fn scan_region_avx2(
     unsafe {
         use std::arch::x86_64::*;
 
-        let newline = _mm256_set1_epi8(0x0Au8 as i8);
+        let newline = _mm256_set1_epi8(sep as i8);
         let len = data.len();
         let ptr = data.as_ptr();
 
@@ -249,15 +355,13 @@ unsafe fn scan_region_avx2(
             offset += 32;
         }
 
-        while offset < len {
-            if *ptr.add(offset) == b'\n' {
-                let next_line = global_base + (offset as u64) + 1;
-                if next_line < data_total_len {
-                    line_starts.push(next_line);
-                }
-            }
-            offset += 1;
-        }
+        scan_region_scalar(
+            &data[offset..],
+            sep,
+            global_base + offset as u64,
+            data_total_len,
+            line_starts,
+        );
     }
 }
 
@@ -295,15 +399,44 @@ fn extract_positions_from_mask(
     }
 }
 
+const SWAR_ONES: u64 = 0x0101_0101_0101_0101;
+const SWAR_LOW7: u64 = 0x7F7F_7F7F_7F7F_7F7F;
+
+// Exact per-lane zero test; the classic `(x - 0x01..) & !x` form can flag a
+// 0x0B that follows a newline through borrow propagation.
+#[inline(always)]
+fn swar_byte_mask(word: u64, sep: u8) -> u64 {
+    let x = word ^ (SWAR_ONES * sep as u64);
+    let t = (x & SWAR_LOW7).wrapping_add(SWAR_LOW7);
+    !(t | x | SWAR_LOW7)
+}
+
 fn scan_region_scalar(
     data: &[u8],
+    sep: u8,
     global_base: u64,
     data_total_len: u64,
     line_starts: &mut Vec<u64>,
 ) {
-    for (i, &byte) in data.iter().enumerate() {
-        if byte == b'\n' {
-            let next_line = global_base + (i as u64) + 1;
+    let mut words = data.chunks_exact(8);
+    let mut offset = 0usize;
+
+    for word in &mut words {
+        let mut mask = swar_byte_mask(u64::from_le_bytes(word.try_into().unwrap()), sep);
+        while mask != 0 {
+            let pos = (mask.trailing_zeros() / 8) as u64;
+            let next_line = global_base + offset as u64 + pos + 1;
+            if next_line < data_total_len {
+                line_starts.push(next_line);
+            }
+            mask &= mask.wrapping_sub(1);
+        }
+        offset += 8;
+    }
+
+    for (i, &byte) in words.remainder().iter().enumerate() {
+        if byte == sep {
+            let next_line = global_base + (offset + i) as u64 + 1;
             if next_line < data_total_len {
                 line_starts.push(next_line);
             }
@@ -313,16 +446,13 @@ fn scan_region_scalar(
 
 #[allow(dead_code)]
 pub fn count_newlines_in_region(data: &[u8]) -> u64 {
-    #[cfg(target_arch = "x86_64")]
-    {
-        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
-            return unsafe { count_newlines_avx512(data) };
-        }
-        if is_x86_feature_detected!("avx2") {
-            return unsafe { count_newlines_avx2(data) };
-        }
+    match active_kernel() {
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx512 => unsafe { count_newlines_avx512(data) },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { count_newlines_avx2(data) },
+        _ => count_newlines_scalar(data),
     }
-    count_newlines_scalar(data)
 }
 
 #[allow(dead_code)]
@@ -438,17 +568,246 @@ unsafe fn count_newlines_avx2(data: &[u8]) -> u64 {
     }
 }
 
+/// The kernel scans dispatch to, described for reports.
 pub fn simd_capability() -> &'static str {
-    #[cfg(target_arch = "x86_64")]
-    {
-        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
-            return "AVX-512 (512-bit, 64 bytes/compare)";
+    match active_kernel() {
+        Kernel::Avx512 => "AVX-512 (512-bit, 64 bytes/compare)",
+        Kernel::Avx2 => "AVX2 (256-bit, 32 bytes/compare)",
+        Kernel::Scalar => "Scalar (no SIMD)",
+    }
+}
+
+/// Instruction set a kernel is written for; `selftest` runs each one the
+/// CPU supports instead of only the one dispatch would pick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    Avx2,
+    Avx512,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 3] = [Kernel::Scalar, Kernel::Avx2, Kernel::Avx512];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kernel::Scalar => "scalar",
+            Kernel::Avx2 => "avx2",
+            Kernel::Avx512 => "avx512",
         }
-        if is_x86_feature_detected!("avx2") {
-            return "AVX2 (256-bit, 32 bytes/compare)";
+    }
+
+    /// Parses a `--simd` kernel name.
+    pub fn from_name(name: &str) -> Option<Kernel> {
+        Kernel::ALL.into_iter().find(|kernel| kernel.name() == name)
+    }
+
+    /// The widest kernel this CPU can run.
+    pub fn widest() -> Kernel {
+        Kernel::ALL
+            .into_iter()
+            .rev()
+            .find(|kernel| kernel.supported())
+            .unwrap_or(Kernel::Scalar)
+    }
+
+    /// Whether this CPU can run the kernel.
+    pub fn supported(self) -> bool {
+        match self {
+            Kernel::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx512 => {
+                is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw")
+            }
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+}
+
+/// Dispatch kernel, 0 for [`Kernel::widest`] or 1 + its index in
+/// [`Kernel::ALL`], and how it was picked.
+static KERNEL: AtomicU8 = AtomicU8::new(0);
+static KERNEL_PICK: AtomicU8 = AtomicU8::new(0);
+
+/// How the dispatch kernel was picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelPick {
+    /// The widest the CPU has.
+    Widest,
+    /// Named with `--simd`.
+    Forced,
+    /// Timed against the other wide kernel by `--simd auto`.
+    Calibrated,
+}
+
+/// The kernel scans run with and how it was picked, for stats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelChoice {
+    pub kernel: Kernel,
+    pub pick: KernelPick,
+}
+
+impl fmt::Display for KernelChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pick {
+            KernelPick::Widest => write!(f, "{}", self.kernel.name()),
+            KernelPick::Forced => write!(f, "{} (forced)", self.kernel.name()),
+            KernelPick::Calibrated => write!(f, "{} (calibrated)", self.kernel.name()),
+        }
+    }
+}
+
+/// Makes scans use `kernel` for the rest of the process; false, changing
+/// nothing, when the CPU lacks it.
+pub fn set_kernel(kernel: Kernel, pick: KernelPick) -> bool {
+    if !kernel.supported() {
+        return false;
+    }
+    let index = Kernel::ALL.iter().position(|&k| k == kernel).unwrap_or(0);
+    KERNEL.store(index as u8 + 1, Ordering::Relaxed);
+    KERNEL_PICK.store(pick as u8, Ordering::Relaxed);
+    true
+}
+
+#[inline]
+pub fn active_kernel() -> Kernel {
+    match KERNEL.load(Ordering::Relaxed) {
+        0 => Kernel::widest(),
+        n => Kernel::ALL[n as usize - 1],
+    }
+}
+
+pub fn kernel_choice() -> KernelChoice {
+    let pick = match KERNEL_PICK.load(Ordering::Relaxed) {
+        1 => KernelPick::Forced,
+        2 => KernelPick::Calibrated,
+        _ => KernelPick::Widest,
+    };
+    KernelChoice {
+        kernel: active_kernel(),
+        pick,
+    }
+}
+
+/// Bytes of the first file's head `calibrate` scans, and the least it
+/// will time: below that, call overhead drowns the kernels.
+pub const CALIBRATION_BYTES: usize = 4 * 1024 * 1024;
+const CALIBRATION_MIN_BYTES: usize = 64 * 1024;
+
+/// Rounds per kernel, alternating between the kernels so a clock change
+/// lands on both, and the least time each round keeps scanning: long enough
+/// for an AVX-512 frequency license to take hold.
+const CALIBRATION_ROUNDS: usize = 5;
+const CALIBRATION_ROUND: Duration = Duration::from_millis(4);
+
+/// AVX2 must beat AVX-512 by this share before it is picked, so noise
+/// does not flip the choice on CPUs where the two are even.
+const CALIBRATION_MARGIN: f64 = 0.05;
+
+/// Sustained scan throughput of the two wide kernels on a sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub avx2_gbps: f64,
+    pub avx512_gbps: f64,
+}
+
+impl Calibration {
+    /// AVX-512 unless AVX2 is clearly faster, as on CPUs that downclock
+    /// for 512-bit work.
+    pub fn faster(&self) -> Kernel {
+        if self.avx2_gbps > self.avx512_gbps * (1.0 + CALIBRATION_MARGIN) {
+            Kernel::Avx2
+        } else {
+            Kernel::Avx512
         }
     }
-    "Scalar (no SIMD)"
+}
+
+/// Times AVX2 against AVX-512 scanning `sample` (`--simd auto`), each the
+/// median of its rounds; `None` unless the CPU has both and `sample` is
+/// large enough to time.
+pub fn calibrate(sample: &[u8]) -> Option<Calibration> {
+    if sample.len() < CALIBRATION_MIN_BYTES
+        || !Kernel::Avx2.supported()
+        || !Kernel::Avx512.supported()
+    {
+        return None;
+    }
+    let sep = record_sep();
+    let total = sample.len() as u64;
+    let mut line_starts = Vec::with_capacity(sample.len() / 64);
+    let mut round = |kernel: Kernel| {
+        let start = Instant::now();
+        let mut bytes = 0u64;
+        while start.elapsed() < CALIBRATION_ROUND {
+            line_starts.clear();
+            scan_region_kernel(kernel, sample, sep, 0, total, &mut line_starts);
+            bytes += total;
+        }
+        bytes as f64 / (1024.0 * 1024.0 * 1024.0) / start.elapsed().as_secs_f64()
+    };
+    let mut avx2 = Vec::with_capacity(CALIBRATION_ROUNDS);
+    let mut avx512 = Vec::with_capacity(CALIBRATION_ROUNDS);
+    for _ in 0..CALIBRATION_ROUNDS {
+        avx512.push(round(Kernel::Avx512));
+        avx2.push(round(Kernel::Avx2));
+    }
+    let median = |mut rates: Vec<f64>| {
+        rates.sort_by(f64::total_cmp);
+        rates[rates.len() / 2]
+    };
+    Some(Calibration {
+        avx2_gbps: median(avx2),
+        avx512_gbps: median(avx512),
+    })
+}
+
+/// `scan_region` with a given kernel; `None` when the CPU lacks it.
+pub fn scan_region_with(
+    kernel: Kernel,
+    data: &[u8],
+    global_base: u64,
+    data_total_len: u64,
+    line_starts: &mut Vec<u64>,
+) -> Option<()> {
+    if !kernel.supported() {
+        return None;
+    }
+    let sep = record_sep();
+    match kernel {
+        Kernel::Scalar => scan_region_scalar(data, sep, global_base, data_total_len, line_starts),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe {
+            scan_region_avx2(data, sep, global_base, data_total_len, line_starts)
+        },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx512 => unsafe {
+            scan_region_avx512(data, sep, global_base, data_total_len, line_starts)
+        },
+        #[cfg(not(target_arch = "x86_64"))]
+        _ => return None,
+    }
+    Some(())
+}
+
+/// `count_newlines_in_region` with a given kernel; `None` when the CPU
+/// lacks it.
+pub fn count_newlines_with(kernel: Kernel, data: &[u8]) -> Option<u64> {
+    if !kernel.supported() {
+        return None;
+    }
+    match kernel {
+        Kernel::Scalar => Some(count_newlines_scalar(data)),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => Some(unsafe { count_newlines_avx2(data) }),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx512 => Some(unsafe { count_newlines_avx512(data) }),
+        #[cfg(not(target_arch = "x86_64"))]
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -537,6 +896,71 @@ mod tests {
         assert_eq!(seq, par, "Parallel scan must match sequential scan");
     }
 
+    #[test]
+    fn test_scan_scalar_swar_matches_reference() {
+        let mut data = Vec::new();
+        for i in 0..200u32 {
+            data.extend(std::iter::repeat_n(
+                b'a' + (i % 26) as u8,
+                (i % 13) as usize,
+            ));
+            data.push(b'\n');
+        }
+        for start in 0..8 {
+            let slice = &data[start..];
+            let mut result = vec![0u64];
+            scan_region_scalar(slice, b'\n', 0, slice.len() as u64, &mut result);
+            assert_eq!(result, scan_newlines_reference(slice), "offset {}", start);
+        }
+    }
+
+    #[test]
+    fn test_scan_scalar_swar_no_false_positives() {
+        // 0x0B directly after a newline trips the naive haszero trick.
+        let data = b"\n\x0b\x0b\n\x0b\x0bxx\x0b\n\x0b\x0b\x0b\x0b\x0b\x0b";
+        let mut result = vec![0u64];
+        scan_region_scalar(data, b'\n', 0, data.len() as u64, &mut result);
+        assert_eq!(result, scan_newlines_reference(data));
+    }
+
+    #[test]
+    fn test_scan_nul_separated_records() {
+        let mut data = Vec::new();
+        for i in 0..300 {
+            data.extend_from_slice(format!("record {}\nsecond line\x01", i).as_bytes());
+            data.push(0);
+        }
+        let expected: Vec<u64> = std::iter::once(0)
+            .chain(
+                data.iter()
+                    .enumerate()
+                    .filter(|&(i, &b)| b == 0 && i + 1 < data.len())
+                    .map(|(i, _)| i as u64 + 1),
+            )
+            .collect();
+        let mut dispatched = vec![0u64];
+        scan_region_sep(&data, 0, 0, data.len() as u64, &mut dispatched);
+        assert_eq!(dispatched, expected);
+        let mut scalar = vec![0u64];
+        scan_region_scalar(&data, 0, 0, data.len() as u64, &mut scalar);
+        assert_eq!(scalar, expected);
+    }
+
+    #[test]
+    fn test_scan_tail_lengths() {
+        for len in 0..100usize {
+            let data: Vec<u8> = (0..len)
+                .map(|i| if i % 7 == 3 { b'\n' } else { b'x' })
+                .collect();
+            assert_eq!(
+                scan_newlines(&data),
+                scan_newlines_reference(&data),
+                "len {}",
+                len
+            );
+        }
+    }
+
     #[test]
     fn test_count_newlines_empty() {
         assert_eq!(count_newlines_in_region(b""), 0);
@@ -598,4 +1022,64 @@ mod tests {
 
         assert_eq!(scan_result.len() as u64, newline_count);
     }
+
+    #[test]
+    fn test_verify_scan_counts_positions() {
+        let data = b"a\nbb\n\nccc\n".repeat(40);
+        let total = data.len() as u64;
+        // A chunk ending mid-input keeps its last newline; the input's last
+        // one starts no line.
+        assert_eq!(expected_positions(&data[..10], b'\n', 0, total), 4);
+        assert_eq!(expected_positions(&data[10..], b'\n', 10, total), 155);
+        assert_eq!(expected_positions(b"no newline", b'\n', 0, 10), 0);
+        assert_eq!(expected_positions(b"a\0b\nc\0", 0, 0, 6), 1);
+
+        set_verify_scan(true);
+        let (before, _) = scan_mismatches();
+        let mut starts = Vec::new();
+        scan_region(&data[..10], 0, total, &mut starts);
+        scan_region(&data[10..], 10, total, &mut starts);
+        assert_eq!(starts.len(), 159);
+        let nul = data
+            .iter()
+            .map(|&b| if b == b'\n' { 0 } else { b })
+            .collect::<Vec<_>>();
+        scan_region_sep(&nul, 0, 0, total, &mut starts);
+        assert_eq!(starts.len(), 159 + 159);
+        assert_eq!(scan_mismatches().0, before);
+    }
+
+    #[test]
+    fn test_calibrate_picks_faster_kernel() {
+        let even = Calibration {
+            avx2_gbps: 10.2,
+            avx512_gbps: 10.0,
+        };
+        assert_eq!(even.faster(), Kernel::Avx512);
+        let downclocked = Calibration {
+            avx2_gbps: 12.0,
+            avx512_gbps: 9.0,
+        };
+        assert_eq!(downclocked.faster(), Kernel::Avx2);
+
+        let data = b"2025-02-12T10:31:45Z INFO request served\n".repeat(4096);
+        assert_eq!(calibrate(&data[..1024]), None);
+        let calibration = calibrate(&data);
+        assert_eq!(
+            calibration.is_some(),
+            Kernel::Avx2.supported() && Kernel::Avx512.supported()
+        );
+        if let Some(calibration) = calibration {
+            assert!(calibration.avx2_gbps > 0.0 && calibration.avx512_gbps > 0.0);
+        }
+
+        assert_eq!(Kernel::from_name("avx2"), Some(Kernel::Avx2));
+        assert_eq!(Kernel::from_name("neon"), None);
+        assert!(Kernel::widest().supported());
+        let choice = KernelChoice {
+            kernel: Kernel::Avx2,
+            pick: KernelPick::Forced,
+        };
+        assert_eq!(choice.to_string(), "avx2 (forced)");
+    }
 }
@@ -0,0 +1,626 @@
+use crate::data::{LevelSummary, LogLevel};
+use crate::emit::EmitChunk;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// A destination for emitted chunks. Each sink runs on its own thread and
+/// sees `open`, then `write_batch` per chunk with `flush` whenever its
+/// queue runs dry, then `close`.
+///
+/// A `write_batch` error of a transient kind (see [`is_transient`]) is
+/// retried with backoff, so a sink that can fail that way must not leave
+/// partial output behind. Any other error is final for the chunk: it goes
+/// to the dead-letter file if there is one, otherwise the sink stops.
+pub trait Sink: Send {
+    fn open(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Errors worth retrying: the destination may accept the same chunk later.
+pub fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// Exponential backoff for transient write errors: `retries` more attempts,
+/// waiting `base`, `2 * base`, ... up to `max` between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            base: Duration::from_millis(100),
+            max: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base.saturating_mul(1 << attempt.min(16)).min(self.max)
+    }
+}
+
+/// Where chunks a sink finally rejected are written, as the NDJSON they
+/// would have been, so they can be replayed.
+pub struct DeadLetter {
+    pub path: String,
+    out: Box<dyn Write + Send>,
+}
+
+impl DeadLetter {
+    pub fn new(path: &str, out: Box<dyn Write + Send>) -> DeadLetter {
+        DeadLetter {
+            path: path.to_string(),
+            out,
+        }
+    }
+}
+
+/// NDJSON to any writer (stdout, a file).
+pub struct WriterSink<W: Write + Send>(pub W);
+
+impl<W: Write + Send> Sink for WriterSink<W> {
+    fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()> {
+        self.0.write_all(&chunk.ndjson)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Counts emitted records and bytes, written once in Prometheus text
+/// exposition format when the tee finishes.
+pub struct MetricsSink<W: Write + Send> {
+    out: W,
+    records: u64,
+    bytes: u64,
+    levels: LevelSummary,
+}
+
+impl<W: Write + Send> MetricsSink<W> {
+    pub fn new(out: W) -> Self {
+        MetricsSink {
+            out,
+            records: 0,
+            bytes: 0,
+            levels: LevelSummary::default(),
+        }
+    }
+}
+
+impl<W: Write + Send> Sink for MetricsSink<W> {
+    fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()> {
+        self.records += chunk.records;
+        self.bytes += chunk.ndjson.len() as u64;
+        self.levels.merge(&chunk.levels);
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        writeln!(self.out, "# TYPE pandora_records_total counter")?;
+        writeln!(self.out, "pandora_records_total {}", self.records)?;
+        for level in LogLevel::ALL {
+            writeln!(
+                self.out,
+                "pandora_records_total{{level=\"{}\"}} {}",
+                level.as_str().to_ascii_lowercase(),
+                self.levels.count(level)
+            )?;
+        }
+        writeln!(self.out, "# TYPE pandora_emitted_bytes_total counter")?;
+        writeln!(self.out, "pandora_emitted_bytes_total {}", self.bytes)?;
+        self.out.flush()
+    }
+}
+
+/// What a sink's queue does when it is full: `Block` applies backpressure to
+/// the parser (lossless), `Drop` discards the chunk and counts it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    Block,
+    Drop,
+}
+
+pub struct SinkSpec {
+    pub name: String,
+    pub sink: Box<dyn Sink>,
+    pub overflow: Overflow,
+    pub retry: RetryPolicy,
+    pub dead_letter: Option<DeadLetter>,
+}
+
+impl SinkSpec {
+    pub fn new(name: String, sink: Box<dyn Sink>, overflow: Overflow) -> SinkSpec {
+        SinkSpec {
+            name,
+            sink,
+            overflow,
+            retry: RetryPolicy::default(),
+            dead_letter: None,
+        }
+    }
+
+    /// `stdout`, `file:<path>`, `metrics` or `metrics:<path>`, optionally
+    /// followed by comma-separated options: `drop` or `block`,
+    /// `retries=<n>` and `dead-letter=<path>`. Metrics default to `drop`,
+    /// everything else to `block`. Only a comma that starts an option ends
+    /// a path, so paths may contain commas.
+    pub fn parse(spec: &str) -> Result<SinkSpec, String> {
+        let mut options = split_options(spec).into_iter();
+        let target = options.next().unwrap_or_default();
+        let (mut overflow, mut retry, mut dead_letter) = (None, RetryPolicy::default(), None);
+        for option in options {
+            match option.split_once('=') {
+                None if option == "drop" => overflow = Some(Overflow::Drop),
+                None if option == "block" => overflow = Some(Overflow::Block),
+                Some(("retries", n)) => {
+                    retry.retries = n.parse().map_err(|_| format!("invalid retries '{}'", n))?
+                }
+                Some(("dead-letter", path)) if !path.is_empty() => dead_letter = Some(path),
+                _ => return Err(format!("unknown sink option '{}'", option)),
+            }
+        }
+        let (kind, path) = match target.split_once(':') {
+            Some((kind, path)) => (kind, Some(path)),
+            None => (target, None),
+        };
+        let create = |path: &str| {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(|e| format!("cannot create '{}': {}", path, e))
+        };
+        let (sink, default_overflow): (Box<dyn Sink>, _) = match (kind, path) {
+            ("stdout", None) => (
+                Box::new(WriterSink(BufWriter::new(io::stdout()))),
+                Overflow::Block,
+            ),
+            ("file", Some(path)) => (Box::new(WriterSink(create(path)?)), Overflow::Block),
+            ("metrics", None) => (Box::new(MetricsSink::new(io::stderr())), Overflow::Drop),
+            ("metrics", Some(path)) => (Box::new(MetricsSink::new(create(path)?)), Overflow::Drop),
+            ("parquet", _) => {
+                return Err("parquet output is not available in this build".to_string());
+            }
+            _ => return Err(format!("unknown sink '{}'", spec)),
+        };
+        let dead_letter = match dead_letter {
+            Some(path) => Some(DeadLetter::new(path, Box::new(create(path)?))),
+            None => None,
+        };
+        Ok(SinkSpec {
+            name: target.to_string(),
+            sink,
+            overflow: overflow.unwrap_or(default_overflow),
+            retry,
+            dead_letter,
+        })
+    }
+}
+
+/// `spec` split at each comma followed by `drop`, `block` or `<key>=`.
+fn split_options(spec: &str) -> Vec<&str> {
+    let starts_option = |rest: &str| {
+        let option = rest.split(',').next().unwrap_or_default();
+        matches!(option, "drop" | "block")
+            || option.split_once('=').is_some_and(|(key, _)| {
+                !key.is_empty() && key.bytes().all(|b| b.is_ascii_lowercase() || b == b'-')
+            })
+    };
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (comma, _) in spec.match_indices(',') {
+        if starts_option(&spec[comma + 1..]) {
+            parts.push(&spec[start..comma]);
+            start = comma + 1;
+        }
+    }
+    parts.push(&spec[start..]);
+    parts
+}
+
+#[derive(Debug)]
+pub struct SinkReport {
+    pub name: String,
+    pub chunks_written: u64,
+    pub chunks_dropped: u64,
+    /// Write attempts repeated after transient errors.
+    pub retries: u64,
+    /// Chunks written to the dead-letter file instead, and where.
+    pub dead_lettered: u64,
+    pub dead_letter_path: Option<String>,
+    /// Time spent in `write_batch` and `flush`, retries included.
+    pub busy: Duration,
+    pub error: Option<io::Error>,
+}
+
+struct Lane {
+    name: String,
+    tx: SyncSender<Arc<EmitChunk>>,
+    overflow: Overflow,
+    dropped: AtomicU64,
+    dead_letter_path: Option<String>,
+    busy_ns: Arc<AtomicU64>,
+    handle: JoinHandle<LaneOutcome>,
+}
+
+#[derive(Default)]
+struct LaneOutcome {
+    written: u64,
+    retries: u64,
+    dead_lettered: u64,
+    error: Option<io::Error>,
+}
+
+/// Fans chunks out to several sinks, each drained by its own thread from a
+/// bounded queue, so a slow sink only holds up the others once its own queue
+/// is full (and then only if it blocks).
+pub struct Tee {
+    lanes: Vec<Lane>,
+}
+
+impl Tee {
+    pub fn spawn(specs: Vec<SinkSpec>, queue_capacity: usize) -> Tee {
+        let lanes = specs
+            .into_iter()
+            .map(|spec| {
+                let (tx, rx) = mpsc::sync_channel(queue_capacity.max(1));
+                let mut sink = spec.sink;
+                let (retry, mut dead_letter) = (spec.retry, spec.dead_letter);
+                let busy_ns = Arc::new(AtomicU64::new(0));
+                let busy = Arc::clone(&busy_ns);
+                Lane {
+                    name: spec.name,
+                    tx,
+                    overflow: spec.overflow,
+                    dropped: AtomicU64::new(0),
+                    dead_letter_path: dead_letter.as_ref().map(|d| d.path.clone()),
+                    busy_ns,
+                    handle: thread::spawn(move || {
+                        drain(sink.as_mut(), rx, retry, dead_letter.as_mut(), &busy)
+                    }),
+                }
+            })
+            .collect();
+        Tee { lanes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lanes.is_empty()
+    }
+
+    /// Time the sinks have spent writing so far, summed over sinks.
+    pub fn busy_time(&self) -> Duration {
+        let ns = self
+            .lanes
+            .iter()
+            .map(|lane| lane.busy_ns.load(Ordering::Relaxed))
+            .sum();
+        Duration::from_nanos(ns)
+    }
+
+    pub fn send(&self, chunk: EmitChunk) {
+        let chunk = Arc::new(chunk);
+        for lane in &self.lanes {
+            match lane.overflow {
+                Overflow::Block => {
+                    let _ = lane.tx.send(Arc::clone(&chunk));
+                }
+                Overflow::Drop => {
+                    if let Err(TrySendError::Full(_)) = lane.tx.try_send(Arc::clone(&chunk)) {
+                        lane.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn finish(self) -> Vec<SinkReport> {
+        self.lanes
+            .into_iter()
+            .map(|lane| {
+                drop(lane.tx);
+                let outcome = lane.handle.join().unwrap_or_else(|_| LaneOutcome {
+                    error: Some(io::Error::other("sink thread panicked")),
+                    ..Default::default()
+                });
+                SinkReport {
+                    name: lane.name,
+                    chunks_written: outcome.written,
+                    chunks_dropped: lane.dropped.into_inner(),
+                    retries: outcome.retries,
+                    dead_lettered: outcome.dead_lettered,
+                    dead_letter_path: lane.dead_letter_path,
+                    busy: Duration::from_nanos(lane.busy_ns.load(Ordering::Relaxed)),
+                    error: outcome.error,
+                }
+            })
+            .collect()
+    }
+}
+
+fn write_with_retry(
+    sink: &mut dyn Sink,
+    chunk: &EmitChunk,
+    retry: RetryPolicy,
+    retries: &mut u64,
+) -> io::Result<()> {
+    let mut attempt = 0;
+    loop {
+        match sink.write_batch(chunk) {
+            Err(e) if is_transient(&e) && attempt < retry.retries => {
+                thread::sleep(retry.backoff(attempt));
+                attempt += 1;
+                *retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Keeps draining after a sink fails so blocking senders never hang on a
+// dead sink.
+fn drain(
+    sink: &mut dyn Sink,
+    rx: Receiver<Arc<EmitChunk>>,
+    retry: RetryPolicy,
+    mut dead_letter: Option<&mut DeadLetter>,
+    busy_ns: &AtomicU64,
+) -> LaneOutcome {
+    let timed = |start: Instant| {
+        busy_ns.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    };
+    let mut outcome = LaneOutcome {
+        error: sink.open().err(),
+        ..Default::default()
+    };
+    let mut unflushed = false;
+    loop {
+        let chunk = match rx.try_recv() {
+            Ok(chunk) => chunk,
+            Err(TryRecvError::Empty) => {
+                if unflushed && outcome.error.is_none() {
+                    let start = Instant::now();
+                    outcome.error = sink.flush().err();
+                    timed(start);
+                    unflushed = false;
+                }
+                match rx.recv() {
+                    Ok(chunk) => chunk,
+                    Err(_) => break,
+                }
+            }
+            Err(TryRecvError::Disconnected) => break,
+        };
+        if outcome.error.is_some() {
+            continue;
+        }
+        let start = Instant::now();
+        let written = write_with_retry(sink, &chunk, retry, &mut outcome.retries);
+        timed(start);
+        match written {
+            Ok(()) => {
+                outcome.written += 1;
+                unflushed = true;
+            }
+            Err(e) => match dead_letter.as_deref_mut() {
+                Some(dead) => match dead.out.write_all(&chunk.ndjson) {
+                    Ok(()) => outcome.dead_lettered += 1,
+                    Err(dead_error) => {
+                        outcome.error = Some(io::Error::new(
+                            dead_error.kind(),
+                            format!("{} (dead-letter '{}': {})", e, dead.path, dead_error),
+                        ))
+                    }
+                },
+                None => outcome.error = Some(e),
+            },
+        }
+    }
+    if outcome.error.is_none() {
+        outcome.error = sink.close().err();
+    }
+    if let Some(dead) = dead_letter
+        && let Err(e) = dead.out.flush()
+    {
+        outcome.error.get_or_insert(e);
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct SlowSink;
+
+    impl Sink for SlowSink {
+        fn write_batch(&mut self, _: &EmitChunk) -> io::Result<()> {
+            thread::sleep(Duration::from_millis(20));
+            Ok(())
+        }
+    }
+
+    fn chunk(line: &str) -> EmitChunk {
+        EmitChunk {
+            ndjson: line.as_bytes().to_vec(),
+            records: 1,
+            levels: LevelSummary::from_levels(&[LogLevel::Info]),
+            partition: None,
+            stats: None,
+        }
+    }
+
+    #[test]
+    fn test_tee_fans_out_and_drops_on_slow_lane() {
+        let fast = Shared::default();
+        let metrics = Shared::default();
+        let tee = Tee::spawn(
+            vec![
+                SinkSpec::new(
+                    "fast".into(),
+                    Box::new(WriterSink(fast.clone())),
+                    Overflow::Block,
+                ),
+                SinkSpec::new("slow".into(), Box::new(SlowSink), Overflow::Drop),
+                SinkSpec::new(
+                    "metrics".into(),
+                    Box::new(MetricsSink::new(metrics.clone())),
+                    Overflow::Block,
+                ),
+            ],
+            1,
+        );
+        for _ in 0..10 {
+            tee.send(chunk("{}\n"));
+        }
+        let reports = tee.finish();
+
+        assert_eq!(fast.0.lock().unwrap().len(), 30);
+        assert_eq!(reports[0].chunks_written, 10);
+        assert_eq!(reports[0].chunks_dropped, 0);
+        assert!(reports[1].chunks_dropped > 0);
+        assert_eq!(reports[1].chunks_written + reports[1].chunks_dropped, 10);
+
+        let metrics = String::from_utf8(metrics.0.lock().unwrap().clone()).unwrap();
+        assert!(metrics.contains("pandora_records_total 10\n"));
+        assert!(metrics.contains("pandora_records_total{level=\"info\"} 10\n"));
+    }
+
+    /// Fails the first `transient` writes with a timeout, then every
+    /// chunk containing "bad" for good.
+    struct FlakySink {
+        transient: u32,
+        out: Shared,
+    }
+
+    impl Sink for FlakySink {
+        fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()> {
+            if self.transient > 0 {
+                self.transient -= 1;
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            if chunk.ndjson.starts_with(b"bad") {
+                return Err(io::Error::other("rejected"));
+            }
+            self.out.write_all(&chunk.ndjson)
+        }
+    }
+
+    #[test]
+    fn test_retries_then_dead_letters() {
+        let (out, dead) = (Shared::default(), Shared::default());
+        let retry = RetryPolicy {
+            retries: 2,
+            base: Duration::from_millis(1),
+            max: Duration::from_millis(2),
+        };
+        let spec = |transient, dead_letter: Option<&Shared>| SinkSpec {
+            retry,
+            dead_letter: dead_letter.map(|d| DeadLetter::new("dead.ndjson", Box::new(d.clone()))),
+            ..SinkSpec::new(
+                "flaky".into(),
+                Box::new(FlakySink {
+                    transient,
+                    out: out.clone(),
+                }),
+                Overflow::Block,
+            )
+        };
+        let tee = Tee::spawn(vec![spec(2, Some(&dead))], 4);
+        for line in ["ok 1\n", "bad 2\n", "ok 3\n"] {
+            tee.send(chunk(line));
+        }
+        let report = tee.finish().pop().unwrap();
+        assert!(report.error.is_none());
+        assert_eq!((report.chunks_written, report.retries), (2, 2));
+        assert_eq!(report.dead_lettered, 1);
+        assert_eq!(report.dead_letter_path.as_deref(), Some("dead.ndjson"));
+        assert_eq!(&out.0.lock().unwrap()[..], b"ok 1\nok 3\n");
+        assert_eq!(&dead.0.lock().unwrap()[..], b"bad 2\n");
+
+        // Out of retries and without a dead-letter file the sink stops.
+        let tee = Tee::spawn(vec![spec(3, None)], 4);
+        tee.send(chunk("ok 4\n"));
+        tee.send(chunk("ok 5\n"));
+        let report = tee.finish().pop().unwrap();
+        assert_eq!(report.error.unwrap().kind(), io::ErrorKind::TimedOut);
+        assert_eq!((report.chunks_written, report.retries), (0, 2));
+    }
+
+    #[test]
+    fn test_sink_spec_parse() {
+        let spec = SinkSpec::parse("metrics").unwrap();
+        assert_eq!(spec.overflow, Overflow::Drop);
+        let spec = SinkSpec::parse("stdout,drop").unwrap();
+        assert_eq!(spec.overflow, Overflow::Drop);
+        assert!(SinkSpec::parse("parquet:out.parquet").is_err());
+        assert!(SinkSpec::parse("kafka").is_err());
+        assert!(SinkSpec::parse("stdout,maybe").is_err());
+        let spec = SinkSpec::parse("stdout,retries=5,drop").unwrap();
+        assert_eq!((spec.retry.retries, spec.overflow), (5, Overflow::Drop));
+        assert!(SinkSpec::parse("stdout,retries=x").is_err());
+        assert!(SinkSpec::parse("stdout,colour=red").is_err());
+
+        let dir = std::env::temp_dir().join(format!("pandora-sink-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a,b.ndjson");
+        let dead = dir.join("dead,1.ndjson");
+        let spec = format!(
+            "file:{},retries=2,dead-letter={},drop",
+            path.display(),
+            dead.display()
+        );
+        let spec = SinkSpec::parse(&spec).unwrap();
+        assert_eq!(spec.name, format!("file:{}", path.display()));
+        assert_eq!((spec.retry.retries, spec.overflow), (2, Overflow::Drop));
+        assert_eq!(spec.dead_letter.unwrap().path, dead.display().to_string());
+        assert!(path.exists() && dead.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+use crate::emit::{EmitRecord, FieldValue};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Bucket `k` holds sizes of `[2^(k-1), 2^k)` bytes; bucket 0 is empty.
+pub const SIZE_BUCKETS: usize = 33;
+
+/// Keys listed in the report, largest total first.
+const MAX_KEYS: usize = 50;
+
+/// Bytes an RFC3339 timestamp takes on export.
+const TIMESTAMP_LEN: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeHistogram {
+    pub counts: [u64; SIZE_BUCKETS],
+    pub total: u64,
+    pub max: u64,
+}
+
+impl Default for SizeHistogram {
+    fn default() -> Self {
+        SizeHistogram {
+            counts: [0; SIZE_BUCKETS],
+            total: 0,
+            max: 0,
+        }
+    }
+}
+
+impl SizeHistogram {
+    #[inline]
+    pub fn bucket(len: u64) -> usize {
+        ((u64::BITS - len.leading_zeros()) as usize).min(SIZE_BUCKETS - 1)
+    }
+
+    #[inline]
+    pub fn record(&mut self, len: u64) {
+        self.counts[Self::bucket(len)] += 1;
+        self.total += len;
+        self.max = self.max.max(len);
+    }
+
+    pub fn merge(&mut self, other: &SizeHistogram) {
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {
+            *a += b;
+        }
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.total as f64 / self.count().max(1) as f64
+    }
+
+    /// Largest size the bucket holding quantile `q` can contain, capped at
+    /// the largest size seen.
+    pub fn quantile_bound(&self, q: f64) -> u64 {
+        let target = ((self.count() as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (k, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return ((1u64 << k) - 1).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+fn bucket_label(k: usize) -> String {
+    match k {
+        0 => "0 B".to_string(),
+        1 => "1 B".to_string(),
+        _ => format!("{}-{} B", 1u64 << (k - 1), (1u64 << k) - 1),
+    }
+}
+
+fn value_len(value: FieldValue<'_>) -> u64 {
+    match value {
+        FieldValue::Text(v) | FieldValue::Escaped(v) | FieldValue::Literal(v) => v.len() as u64,
+        FieldValue::Timestamp(_) => TIMESTAMP_LEN,
+    }
+}
+
+#[derive(Default)]
+struct Sizes {
+    records: SizeHistogram,
+    keys: HashMap<Vec<u8>, SizeHistogram>,
+}
+
+/// Value lengths per key and record lengths over matched records
+/// (`--value-sizes`). Values are measured as they appear in the input, so
+/// escaped strings count their escapes.
+#[derive(Default)]
+pub struct ValueSizes {
+    sizes: Mutex<Sizes>,
+}
+
+impl ValueSizes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let mut local = Sizes::default();
+        for &i in records {
+            local
+                .records
+                .record(u64::from(batch.record_line(i as usize).len));
+            batch.for_each_field(i as usize, &mut |key, value| {
+                let len = value_len(value);
+                match local.keys.get_mut(key) {
+                    Some(hist) => hist.record(len),
+                    None => local.keys.entry(key.to_vec()).or_default().record(len),
+                }
+            });
+        }
+
+        let mut sizes = self.sizes.lock().unwrap();
+        sizes.records.merge(&local.records);
+        for (key, hist) in local.keys {
+            sizes.keys.entry(key).or_default().merge(&hist);
+        }
+    }
+
+    pub fn report(&self) -> SizeReport {
+        let sizes = self.sizes.lock().unwrap();
+        let mut keys: Vec<(Vec<u8>, SizeHistogram)> = sizes
+            .keys
+            .iter()
+            .map(|(key, hist)| (key.clone(), *hist))
+            .collect();
+        keys.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(&b.0)));
+        SizeReport {
+            records: sizes.records,
+            keys,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SizeReport {
+    pub records: SizeHistogram,
+    /// Largest total first.
+    pub keys: Vec<(Vec<u8>, SizeHistogram)>,
+}
+
+fn write_row(f: &mut fmt::Formatter<'_>, name: &str, hist: &SizeHistogram) -> fmt::Result {
+    writeln!(
+        f,
+        "  {:<24} {:>10} {:>8.1} {:>8} {:>8} {:>8} {:>8} {:>12}",
+        name.chars().take(24).collect::<String>(),
+        hist.count(),
+        hist.mean(),
+        hist.quantile_bound(0.5),
+        hist.quantile_bound(0.9),
+        hist.quantile_bound(0.99),
+        hist.max,
+        hist.total
+    )
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let records = self.records.count();
+        writeln!(
+            f,
+            "Value sizes: {} records, {} keys (bytes; p50/p90/p99 are upper bounds)",
+            records,
+            self.keys.len()
+        )?;
+        writeln!(
+            f,
+            "  {:<24} {:>10} {:>8} {:>8} {:>8} {:>8} {:>8} {:>12}",
+            "key", "present", "avg", "p50<=", "p90<=", "p99<=", "max", "total"
+        )?;
+        write_row(f, "(whole record)", &self.records)?;
+        for (key, hist) in self.keys.iter().take(MAX_KEYS) {
+            write_row(f, &String::from_utf8_lossy(key), hist)?;
+        }
+        if self.keys.len() > MAX_KEYS {
+            writeln!(f, "  ... {} more keys", self.keys.len() - MAX_KEYS)?;
+        }
+
+        writeln!(f, "  Record sizes:")?;
+        for (k, &count) in self.records.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let width = (count * 40).div_ceil(records.max(1)) as usize;
+            writeln!(
+                f,
+                "  {:>20} | {:>10} | {}",
+                bucket_label(k),
+                count,
+                "#".repeat(width)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_value_sizes_per_key() {
+        let data = b"a=1 msg=hello\na=22 msg=\"hello world\"\nb=xyz\na=333\n";
+        let sizes = ValueSizes::new();
+        let add = |batch: &StructuredBatch, records: &[u32]| sizes.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data, 1, Some(LogFormat::Logfmt), &control);
+
+        let report = sizes.report();
+        assert_eq!(report.records.count(), 4);
+        assert_eq!(report.records.total, 13 + 22 + 5 + 5);
+        assert_eq!(report.records.max, 22);
+        let keys: Vec<_> = report.keys.iter().map(|(k, _)| k.as_slice()).collect();
+        assert_eq!(keys, [&b"msg"[..], b"a", b"b"]);
+        let a = &report.keys[1].1;
+        assert_eq!((a.count(), a.total, a.max), (3, 6, 3));
+        assert_eq!(a.quantile_bound(0.5), 3);
+        assert_eq!(a.counts[SizeHistogram::bucket(1)], 1);
+        assert_eq!(a.counts[SizeHistogram::bucket(2)], 2);
+
+        let text = report.to_string();
+        assert!(text.contains("3 keys"), "{}", text);
+        assert!(text.contains("(whole record)"));
+    }
+}
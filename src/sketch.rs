@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+/// Relative accuracy of [`QuantileSketch`] answers.
+const ALPHA: f64 = 0.01;
+
+/// Bins kept per sign before the smallest magnitudes are folded together,
+/// which bounds memory at the cost of accuracy for the lowest quantiles.
+const MAX_BINS: usize = 2048;
+
+/// Magnitudes below this count as zero.
+const MIN_MAGNITUDE: f64 = 1e-9;
+
+/// Mergeable quantile sketch with logarithmic bins (DDSketch): any quantile
+/// is answered within `ALPHA` relative error using at most `MAX_BINS`
+/// bins per sign, whatever the number of values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuantileSketch {
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zero: u64,
+    count: u64,
+}
+
+fn gamma() -> f64 {
+    (1.0 + ALPHA) / (1.0 - ALPHA)
+}
+
+fn bin_of(magnitude: f64) -> i32 {
+    (magnitude.ln() / gamma().ln()).ceil() as i32
+}
+
+/// Midpoint (in relative terms) of bin `k`, `(gamma^(k-1), gamma^k]`.
+fn bin_value(k: i32) -> f64 {
+    let g = gamma();
+    2.0 * g.powi(k) / (g + 1.0)
+}
+
+fn add_bin(bins: &mut BTreeMap<i32, u64>, k: i32, n: u64) {
+    *bins.entry(k).or_default() += n;
+    if bins.len() > MAX_BINS {
+        let (lowest, n) = bins.pop_first().unwrap();
+        let (&next, _) = bins.first_key_value().unwrap_or((&lowest, &0));
+        *bins.entry(next).or_default() += n;
+    }
+}
+
+impl QuantileSketch {
+    pub fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.count += 1;
+        if value.abs() < MIN_MAGNITUDE {
+            self.zero += 1;
+        } else if value > 0.0 {
+            add_bin(&mut self.positive, bin_of(value), 1);
+        } else {
+            add_bin(&mut self.negative, bin_of(-value), 1);
+        }
+    }
+
+    pub fn merge(&mut self, other: &QuantileSketch) {
+        for (&k, &n) in &other.positive {
+            add_bin(&mut self.positive, k, n);
+        }
+        for (&k, &n) in &other.negative {
+            add_bin(&mut self.negative, k, n);
+        }
+        self.zero += other.zero;
+        self.count += other.count;
+    }
+
+    /// Value at quantile `q` in `[0, 1]`, `None` for an empty sketch.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+        let mut seen = 0;
+        for (&k, &n) in self.negative.iter().rev() {
+            seen += n;
+            if seen > rank {
+                return Some(-bin_value(k));
+            }
+        }
+        seen += self.zero;
+        if seen > rank {
+            return Some(0.0);
+        }
+        for (&k, &n) in &self.positive {
+            seen += n;
+            if seen > rank {
+                return Some(bin_value(k));
+            }
+        }
+        self.positive.last_key_value().map(|(&k, _)| bin_value(k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles_within_relative_error() {
+        let mut a = QuantileSketch::default();
+        let mut b = QuantileSketch::default();
+        for v in 1..=10_000 {
+            let sketch = if v % 2 == 0 { &mut a } else { &mut b };
+            sketch.add(v as f64);
+        }
+        a.merge(&b);
+        assert_eq!(a.count, 10_000);
+        for (q, exact) in [
+            (0.5, 5_000.0),
+            (0.9, 9_000.0),
+            (0.99, 9_900.0),
+            (1.0, 10_000.0),
+        ] {
+            let got = a.quantile(q).unwrap();
+            assert!(
+                (got - exact).abs() <= exact * ALPHA * 1.01,
+                "q{} = {}",
+                q,
+                got
+            );
+        }
+
+        let mut mixed = QuantileSketch::default();
+        for v in [-5.0, 0.0, 0.0, 3.0, f64::NAN] {
+            mixed.add(v);
+        }
+        assert_eq!(mixed.count, 4);
+        assert!((mixed.quantile(0.0).unwrap() + 5.0).abs() < 0.1);
+        assert_eq!(mixed.quantile(0.5), Some(0.0));
+        assert_eq!(QuantileSketch::default().quantile(0.5), None);
+
+        let mut wide = QuantileSketch::default();
+        (0..100_000).for_each(|k| wide.add(1.001f64.powi(k)));
+        assert!(wide.positive.len() <= MAX_BINS);
+    }
+}
@@ -0,0 +1,395 @@
+use crate::emit::{
+    EmitChunk, EmitFormat, EmitRecord, EmitRules, FieldValue, emit_chunk, write_rfc3339,
+};
+use crate::fds;
+use crate::sink::Sink;
+use crate::tz::TimeZone;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Partition name for records that do not carry the split field.
+const MISSING: &[u8] = b"_none";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBucket {
+    Day,
+    Hour,
+}
+
+impl TimeBucket {
+    pub fn parse(name: &str) -> Option<TimeBucket> {
+        match name {
+            "day" => Some(TimeBucket::Day),
+            "hour" => Some(TimeBucket::Hour),
+            _ => None,
+        }
+    }
+}
+
+/// What decides which output file a record goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitKey {
+    /// The value of a field, by its source name.
+    Field(Vec<u8>),
+    /// The day (`2025-02-12`) or hour (`2025-02-12-10`) of the record's
+    /// timestamp, on the zone's clock.
+    Time(TimeBucket, TimeZone),
+}
+
+impl SplitKey {
+    pub fn write_key<B: EmitRecord>(&self, batch: &B, i: usize, out: &mut Vec<u8>) {
+        out.clear();
+        match self {
+            SplitKey::Field(name) => batch.visit_path(i, name, &mut |value| match value {
+                FieldValue::Text(v) | FieldValue::Escaped(v) | FieldValue::Literal(v) => {
+                    out.extend_from_slice(v)
+                }
+                FieldValue::Timestamp(ts) => write_rfc3339(ts, out),
+            }),
+            SplitKey::Time(bucket, tz) => {
+                if let Some(ts) = batch.record_timestamp(i) {
+                    write_rfc3339(tz.local(ts), out);
+                    match bucket {
+                        TimeBucket::Day => out.truncate(10),
+                        TimeBucket::Hour => {
+                            out[10] = b'-';
+                            out.truncate(13);
+                        }
+                    }
+                }
+            }
+        }
+        if out.is_empty() {
+            out.extend_from_slice(MISSING);
+        }
+    }
+}
+
+/// Serializes `records` into one chunk per distinct key, in order of first
+/// appearance, each tagged with its partition.
+pub fn split_chunks<B: EmitRecord>(
+    batch: &B,
+    records: &[u32],
+    rules: &EmitRules,
+    format: EmitFormat,
+    key: &SplitKey,
+) -> Vec<EmitChunk> {
+    let mut order: Vec<Vec<u8>> = Vec::new();
+    let mut groups: HashMap<Vec<u8>, Vec<u32>> = HashMap::new();
+    let mut value = Vec::new();
+    for &i in records {
+        key.write_key(batch, i as usize, &mut value);
+        match groups.get_mut(&value) {
+            Some(group) => group.push(i),
+            None => {
+                order.push(value.clone());
+                groups.insert(value.clone(), vec![i]);
+            }
+        }
+    }
+    order
+        .into_iter()
+        .map(|partition| {
+            let mut chunk = emit_chunk(batch, &groups[&partition], rules, format);
+            chunk.partition = Some(partition);
+            chunk
+        })
+        .collect()
+}
+
+/// File name for a partition value: anything outside `[A-Za-z0-9._-]`
+/// becomes `_`, so distinct values can share a file after sanitizing.
+pub fn partition_file_name(partition: &[u8]) -> String {
+    let mut name: String = partition
+        .iter()
+        .take(128)
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => b as char,
+            _ => '_',
+        })
+        .collect();
+    if name.is_empty() || name.bytes().all(|b| b == b'.') {
+        name = "_empty".to_string();
+    }
+    name.push_str(".ndjson");
+    name
+}
+
+/// How partitions map to paths under the output directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionLayout {
+    /// `<dir>/<partition>.ndjson`
+    Flat,
+    /// `<dir>/dt=2025-02-12/hour=10/part.ndjson` for time partitions, as
+    /// data lake ingestion expects; other partitions stay flat.
+    Hive,
+}
+
+impl PartitionLayout {
+    pub fn parse(name: &str) -> Option<PartitionLayout> {
+        match name {
+            "flat" => Some(PartitionLayout::Flat),
+            "hive" => Some(PartitionLayout::Hive),
+            _ => None,
+        }
+    }
+
+    pub fn relative_path(self, partition: &[u8]) -> String {
+        if self == PartitionLayout::Hive
+            && let Some(path) = hive_path(partition)
+        {
+            return path;
+        }
+        partition_file_name(partition)
+    }
+}
+
+fn hive_path(partition: &[u8]) -> Option<String> {
+    let is_date = |d: &[u8]| {
+        d.len() == 10
+            && d.iter().enumerate().all(|(k, &b)| {
+                if k == 4 || k == 7 {
+                    b == b'-'
+                } else {
+                    b.is_ascii_digit()
+                }
+            })
+    };
+    let text = std::str::from_utf8(partition).ok()?;
+    match partition.len() {
+        10 if is_date(partition) => Some(format!("dt={}/part.ndjson", text)),
+        13 if is_date(&partition[..10])
+            && partition[10] == b'-'
+            && partition[11..].iter().all(u8::is_ascii_digit) =>
+        {
+            Some(format!(
+                "dt={}/hour={}/part.ndjson",
+                &text[..10],
+                &text[11..]
+            ))
+        }
+        _ => None,
+    }
+}
+
+struct OpenFile {
+    out: BufWriter<File>,
+    last_used: u64,
+}
+
+/// Writes each chunk to its partition's file under `dir`, keeping at most
+/// `max_open` files open; the least recently used one is flushed and closed
+/// to make room, and reopened for append if it shows up again.
+pub struct SplitSink {
+    dir: PathBuf,
+    layout: PartitionLayout,
+    max_open: usize,
+    open: HashMap<String, OpenFile>,
+    created: HashSet<String>,
+    tick: u64,
+}
+
+impl SplitSink {
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        layout: PartitionLayout,
+        max_open: usize,
+    ) -> io::Result<SplitSink> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(SplitSink {
+            dir,
+            layout,
+            max_open: max_open.max(1),
+            open: HashMap::new(),
+            created: HashSet::new(),
+            tick: 0,
+        })
+    }
+
+    fn writer(&mut self, name: &str) -> io::Result<&mut OpenFile> {
+        self.tick += 1;
+        if !self.open.contains_key(name) {
+            if self.open.len() >= self.max_open {
+                self.evict()?;
+            }
+            let path = self.dir.join(name);
+            // Truncate on first use in this run, append after an eviction.
+            let file = if self.created.insert(name.to_string()) {
+                if let Some(parent) = Path::new(name).parent() {
+                    std::fs::create_dir_all(self.dir.join(parent))?;
+                }
+                File::create(path).map_err(fds::explain)?
+            } else {
+                OpenOptions::new()
+                    .append(true)
+                    .open(path)
+                    .map_err(fds::explain)?
+            };
+            self.open.insert(
+                name.to_string(),
+                OpenFile {
+                    out: BufWriter::new(file),
+                    last_used: 0,
+                },
+            );
+        }
+        let file = self.open.get_mut(name).unwrap();
+        file.last_used = self.tick;
+        Ok(file)
+    }
+
+    fn evict(&mut self) -> io::Result<()> {
+        let oldest = self
+            .open
+            .iter()
+            .min_by_key(|(_, file)| file.last_used)
+            .map(|(name, _)| name.clone());
+        if let Some(name) = oldest {
+            let mut file = self.open.remove(&name).unwrap();
+            file.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for SplitSink {
+    fn write_batch(&mut self, chunk: &EmitChunk) -> io::Result<()> {
+        let name = self
+            .layout
+            .relative_path(chunk.partition.as_deref().unwrap_or(MISSING));
+        self.writer(&name)?.out.write_all(&chunk.ndjson)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for file in self.open.values_mut() {
+            file.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_partition_file_name() {
+        assert_eq!(partition_file_name(b"api-gw"), "api-gw.ndjson");
+        assert_eq!(partition_file_name(b"a/b c"), "a_b_c.ndjson");
+        assert_eq!(partition_file_name(b".."), "_empty.ndjson");
+        assert_eq!(partition_file_name(b""), "_empty.ndjson");
+    }
+
+    #[test]
+    fn test_split_sink_lru_round_trip() {
+        let data = b"svc=a n=1\nsvc=b n=2\nsvc=c n=3\nn=4\nsvc=a n=5\nsvc=b n=6\n";
+        let chunks = Mutex::new(Vec::new());
+        let rules = EmitRules::default();
+        let key = SplitKey::Field(b"svc".to_vec());
+        let on_batch = |batch: &StructuredBatch, records: &[u32]| {
+            chunks.lock().unwrap().extend(split_chunks(
+                batch,
+                records,
+                &rules,
+                EmitFormat::Ndjson,
+                &key,
+            ))
+        };
+        let control = MatchControl {
+            on_batch: Some(&on_batch),
+            ..MatchControl::default()
+        };
+        parse_structured_mmap_with(data, 1, Some(LogFormat::Logfmt), &control);
+
+        let chunks = chunks.into_inner().unwrap();
+        let partitions: Vec<_> = chunks
+            .iter()
+            .map(|c| c.partition.clone().unwrap())
+            .collect();
+        assert_eq!(partitions, [&b"a"[..], b"b", b"c", b"_none"]);
+
+        let dir = std::env::temp_dir().join(format!("pandora-split-{}", std::process::id()));
+        let mut sink = SplitSink::new(&dir, PartitionLayout::Flat, 2).unwrap();
+        for chunk in &chunks {
+            sink.write_batch(chunk).unwrap();
+        }
+        // Evicts and reopens "a" for append.
+        sink.write_batch(&chunks[0]).unwrap();
+        sink.close().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("a.ndjson").lines().count(), 4);
+        assert_eq!(read("b.ndjson").lines().count(), 2);
+        assert_eq!(read("c.ndjson"), "{\"svc\":\"c\",\"n\":3}\n");
+        assert_eq!(read("_none.ndjson"), "{\"n\":4}\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_time_partitions() {
+        let data = b"ts=2025-02-12T10:31:45Z msg=x\n\
+                     ts=2025-02-12T11:00:00Z msg=y\n\
+                     ts=2025-02-12T10:59:59Z msg=z\n\
+                     msg=untimed\n";
+        let chunks = Mutex::new(Vec::new());
+        let rules = EmitRules::default();
+        let key = SplitKey::Time(TimeBucket::Hour, TimeZone::utc());
+        let on_batch = |batch: &crate::structured::StructuredBatch, records: &[u32]| {
+            chunks.lock().unwrap().extend(split_chunks(
+                batch,
+                records,
+                &rules,
+                EmitFormat::Ndjson,
+                &key,
+            ))
+        };
+        let control = MatchControl {
+            on_batch: Some(&on_batch),
+            ..MatchControl::default()
+        };
+        crate::structured_orchestrator::parse_structured_mmap_with(
+            data,
+            1,
+            Some(crate::format::LogFormat::Logfmt),
+            &control,
+        );
+
+        let chunks = chunks.into_inner().unwrap();
+        let summary: Vec<_> = chunks
+            .iter()
+            .map(|c| (c.partition.clone().unwrap(), c.records))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (b"2025-02-12-10".to_vec(), 2),
+                (b"2025-02-12-11".to_vec(), 1),
+                (b"_none".to_vec(), 1),
+            ]
+        );
+
+        let hive = PartitionLayout::Hive;
+        assert_eq!(
+            hive.relative_path(b"2025-02-12-10"),
+            "dt=2025-02-12/hour=10/part.ndjson"
+        );
+        assert_eq!(
+            hive.relative_path(b"2025-02-12"),
+            "dt=2025-02-12/part.ndjson"
+        );
+        assert_eq!(hive.relative_path(b"_none"), "_none.ndjson");
+        assert_eq!(
+            PartitionLayout::Flat.relative_path(b"2025-02-12-10"),
+            "2025-02-12-10.ndjson"
+        );
+    }
+}
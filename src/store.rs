@@ -0,0 +1,156 @@
+use crate::dedup::hash_line;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// On-disk state shared by concurrent pandora processes:
+//
+//   <log>.pidx                        time index, next to a writable log
+//   $XDG_CACHE_HOME/pandora/          or ~/.cache/pandora/
+//     index/<path hash>.pidx          time index of a log in a read-only dir
+//     reports/<key>.report            --cache entries
+//
+// Every file is replaced whole by `publish` (unique temporary file, fsync,
+// rename), so readers never take locks and never see a partial file.
+// Writers that read, modify and publish take the `<file>.lock` advisory
+// lock, so two processes extending one index don't drop each other's work.
+
+static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+pub fn cache_dir(kind: &str) -> io::Result<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no HOME for ~/.cache"))?;
+    let dir = base.join("pandora").join(kind);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Where the time index of `log` lives: beside it when its directory is
+/// writable, otherwise in the cache keyed by the log's absolute path.
+pub fn index_path(log: &str) -> io::Result<PathBuf> {
+    let sidecar = PathBuf::from(format!("{}.pidx", log));
+    let dir = match sidecar.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if dir_writable(dir) {
+        return Ok(sidecar);
+    }
+    let abs = std::fs::canonicalize(log)?;
+    let key = hash_line(abs.as_os_str().as_encoded_bytes());
+    Ok(cache_dir("index")?.join(format!("{:016x}.pidx", key)))
+}
+
+#[cfg(unix)]
+fn dir_writable(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(c_path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn dir_writable(dir: &Path) -> bool {
+    std::fs::metadata(dir).is_ok_and(|m| !m.permissions().readonly())
+}
+
+/// Atomically replaces `path` with `contents`. The temporary name is unique
+/// per process and call, so concurrent writers never share one.
+pub fn publish(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        TMP_SEQ.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = File::create(&tmp)
+        .and_then(|mut out| {
+            out.write_all(contents)?;
+            out.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Exclusive advisory lock on `<path>.lock`, released on drop.
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    pub fn exclusive(path: &Path) -> io::Result<FileLock> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            loop {
+                if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                    break;
+                }
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(FileLock { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_publish_under_lock_serializes_writers() {
+        let dir = std::env::temp_dir().join(format!("pandora-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("counter");
+        publish(&path, b"0").unwrap();
+
+        let inside = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let (path, inside) = (path.clone(), inside.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        let _lock = FileLock::exclusive(&path).unwrap();
+                        assert_eq!(inside.fetch_add(1, Ordering::SeqCst), 0);
+                        let n: u32 = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+                        publish(&path, (n + 1).to_string().as_bytes()).unwrap();
+                        inside.fetch_sub(1, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "160");
+        let mut leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        leftovers.sort();
+        assert_eq!(leftovers, ["counter", "counter.lock"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
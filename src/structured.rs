@@ -1,12 +1,25 @@
+use crate::data::{
+    BatchRecords, FailedChunk, LevelSummary, LineSpan, LogLevel, PageFaults, TimeRange,
+};
+use crate::format::LogFormat;
+use crate::keys::KeyTable;
+use crate::schemas::SchemaTable;
+use crate::simd_scan::KernelChoice;
+use crate::syslog::Pri;
 use std::fmt;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 pub struct FieldRef {
+    /// Empty, at the value, when the key lives outside the record (CSV
+    /// headers); `key_id` names it either way.
     pub key_offset: u64,
     pub key_len: u32,
     pub val_offset: u64,
     pub val_len: u32,
+    /// Id of the key in the batch's [`KeyTable`]; assigned by
+    /// [`StructuredBatch::push_field`].
+    pub key_id: u32,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -15,6 +28,10 @@ pub struct WellKnownFields {
     pub level: u32,
     pub message: u32,
     pub component: u32,
+    pub host: u32,
+    pub pid: u32,
+    pub trace_id: u32,
+    pub span_id: u32,
 }
 
 impl Default for WellKnownFields {
@@ -24,10 +41,164 @@ impl Default for WellKnownFields {
             level: u32::MAX,
             message: u32::MAX,
             component: u32::MAX,
+            host: u32::MAX,
+            pid: u32::MAX,
+            trace_id: u32::MAX,
+            span_id: u32::MAX,
         }
     }
 }
 
+/// Keys given value columns per file unless `--columns` says otherwise.
+pub const DEFAULT_HOT_COLUMNS: usize = 8;
+
+/// Values of one frequent key, one slot per record (`len == u32::MAX` when
+/// the record lacks the key). Hot fields stay in `fields` too, so exports
+/// keep source order; lookups by name read the column instead.
+#[derive(Debug, Clone, Default)]
+pub struct ValueColumn {
+    pub name: Box<[u8]>,
+    pub offsets: Vec<u64>,
+    pub lens: Vec<u32>,
+}
+
+impl ValueColumn {
+    pub const MISSING: u32 = u32::MAX;
+
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<(u64, u32)> {
+        (self.lens[i] != Self::MISSING).then(|| (self.offsets[i], self.lens[i]))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardPolicy {
+    /// Keep the record without the fields that broke a limit; long values
+    /// are cut instead.
+    Truncate,
+    Drop,
+    /// Drop the record and fail the run once parsing is done.
+    Error,
+}
+
+impl GuardPolicy {
+    pub fn parse(name: &str) -> Option<GuardPolicy> {
+        match name {
+            "truncate" => Some(GuardPolicy::Truncate),
+            "drop" => Some(GuardPolicy::Drop),
+            "error" => Some(GuardPolicy::Error),
+            _ => None,
+        }
+    }
+}
+
+/// What becomes of a record without a single field, such as `{}` or a
+/// logfmt line without `=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyPolicy {
+    Keep,
+    Drop,
+    /// Drop the record and report its line as malformed.
+    Malformed,
+}
+
+impl EmptyPolicy {
+    pub fn parse(name: &str) -> Option<EmptyPolicy> {
+        match name {
+            "keep" => Some(EmptyPolicy::Keep),
+            "drop" => Some(EmptyPolicy::Drop),
+            "malformed" => Some(EmptyPolicy::Malformed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EmptyPolicy::Keep => "kept",
+            EmptyPolicy::Drop => "dropped",
+            EmptyPolicy::Malformed => "malformed",
+        }
+    }
+}
+
+/// Per-record limits enforced while fields are pushed, so a single
+/// pathological line cannot grow the batch field vectors without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLimits {
+    pub max_record_bytes: u32,
+    pub max_fields: u32,
+    pub max_value_len: u32,
+    pub policy: GuardPolicy,
+    pub empty: EmptyPolicy,
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        RecordLimits {
+            max_record_bytes: u32::MAX,
+            max_fields: u32::MAX,
+            max_value_len: u32::MAX,
+            policy: GuardPolicy::Truncate,
+            empty: EmptyPolicy::Malformed,
+        }
+    }
+}
+
+impl RecordLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_record_bytes == u32::MAX
+            && self.max_fields == u32::MAX
+            && self.max_value_len == u32::MAX
+    }
+}
+
+/// Records that hit a [`RecordLimits`] bound. `first_error` is the data
+/// offset of the first record rejected under [`GuardPolicy::Error`].
+/// `empty` counts records without fields, whatever [`EmptyPolicy`] made
+/// of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GuardCounts {
+    pub truncated: u64,
+    pub dropped: u64,
+    pub first_error: Option<u64>,
+    pub empty: u64,
+}
+
+impl GuardCounts {
+    pub fn merge(&mut self, other: &GuardCounts) {
+        self.truncated += other.truncated;
+        self.dropped += other.dropped;
+        self.empty += other.empty;
+        self.first_error = match (self.first_error, other.first_error) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// Length of `value` cut to at most `max` bytes without splitting a UTF-8
+/// sequence or a JSON escape.
+fn truncated_len(value: &[u8], max: usize) -> usize {
+    let mut i = 0;
+    let mut safe = 0;
+    while i < value.len() {
+        let width = match value[i] {
+            b'\\' if value.get(i + 1) == Some(&b'u') => 6,
+            b'\\' => 2,
+            b if b < 0x80 => 1,
+            b if b >= 0xF0 => 4,
+            b if b >= 0xE0 => 3,
+            _ => 2,
+        };
+        if i + width > max {
+            break;
+        }
+        i += width;
+        safe = i;
+    }
+    safe.min(value.len())
+}
+
 #[repr(C, align(64))]
 pub struct StructuredBatch {
     pub fields: Vec<FieldRef>,
@@ -40,9 +211,73 @@ pub struct StructuredBatch {
 
     pub line_lens: Vec<u32>,
 
+    pub levels: Vec<LogLevel>,
+
+    pub level_summary: LevelSummary,
+
+    pub time_range: TimeRange,
+
+    pub malformed: Vec<LineSpan>,
+
+    pub input_offset: u64,
+
     pub data_ptr: *const u8,
 
+    pub data_len: usize,
+
     pub len: usize,
+
+    pub limits: RecordLimits,
+
+    /// Position of the input among those of the run.
+    pub file_id: u32,
+
+    /// Parsed from a transcoded or ANSI-stripped copy of the input, so
+    /// record offsets do not point into the file.
+    pub rewritten: bool,
+
+    /// Position, in parse order, of the chunk the batch came from.
+    pub chunk_seq: u64,
+
+    /// Line each record starts on, when parsed with
+    /// [`MatchControl::line_numbers`](crate::filter::MatchControl::line_numbers).
+    pub line_numbers: Vec<u64>,
+
+    /// Set under `--firehose`: records keep only their field extents, with
+    /// no well-known slots, levels, time range or trimmed line ends.
+    pub firehose: bool,
+
+    /// Format the batch was parsed as, which says how quoted values are
+    /// escaped.
+    pub format: LogFormat,
+
+    pub guard: GuardCounts,
+
+    /// Distinct keys of this batch, indexed by [`FieldRef::key_id`].
+    pub keys: KeyTable,
+
+    /// Set when record schemas are asked for (`--schema-report`, a
+    /// `schema-id` field): each record's key set is interned in `schemas`.
+    pub tag_schemas: bool,
+
+    /// Distinct key sets of this batch's records.
+    pub schemas: SchemaTable,
+
+    /// Each record's schema in `schemas`, under `tag_schemas`.
+    pub schema_ids: Vec<u32>,
+
+    /// Value columns of the file's most frequent keys.
+    pub columns: Vec<ValueColumn>,
+
+    /// Column of each key id, or `u32::MAX`.
+    column_of: Vec<u32>,
+
+    /// Set once a field of the record being built broke a limit.
+    over_limit: bool,
+
+    /// Data offset of the last record a limit or the [`EmptyPolicy`] took
+    /// out, so the format parsers do not report it as malformed as well.
+    last_guarded: Option<u64>,
 }
 
 unsafe impl Send for StructuredBatch {}
@@ -63,55 +298,311 @@ impl StructuredBatch {
             well_known: Vec::with_capacity(record_capacity),
             line_offsets: Vec::with_capacity(record_capacity),
             line_lens: Vec::with_capacity(record_capacity),
+            levels: Vec::with_capacity(record_capacity),
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed: Vec::new(),
+            input_offset: 0,
             data_ptr,
+            data_len: 0,
             len: 0,
+            limits: RecordLimits::default(),
+            firehose: false,
+            format: LogFormat::Json,
+            file_id: 0,
+            rewritten: false,
+            chunk_seq: 0,
+            line_numbers: Vec::new(),
+            guard: GuardCounts::default(),
+            keys: KeyTable::default(),
+            tag_schemas: false,
+            schemas: SchemaTable::default(),
+            schema_ids: Vec::new(),
+            columns: Vec::new(),
+            column_of: Vec::new(),
+            over_limit: false,
+            last_guarded: None,
         }
     }
 
+    /// Gives each of `names` a value column; call before any record.
+    pub fn set_hot_keys(&mut self, names: &[Box<[u8]>]) {
+        self.columns = names
+            .iter()
+            .map(|name| ValueColumn {
+                name: name.clone(),
+                ..ValueColumn::default()
+            })
+            .collect();
+    }
+
     #[inline]
     pub fn begin_record(&mut self, line_offset: u64, line_len: u32) {
         self.line_offsets.push(line_offset);
         self.line_lens.push(line_len);
         self.well_known.push(WellKnownFields::default());
+        for column in &mut self.columns {
+            column.offsets.push(0);
+            column.lens.push(ValueColumn::MISSING);
+        }
         self.len += 1;
+        self.over_limit = line_len > self.limits.max_record_bytes;
     }
 
     #[inline]
     pub fn push_field(&mut self, field: FieldRef) {
+        let key = unsafe {
+            std::slice::from_raw_parts(
+                self.data_ptr.add(field.key_offset as usize),
+                field.key_len as usize,
+            )
+        };
+        self.push_keyed_field(field, key);
+    }
+
+    /// Pushes a field named `key` rather than by the bytes at its
+    /// `key_offset`, for keys that live outside the record (CSV headers).
+    #[inline]
+    pub fn push_keyed_field(&mut self, mut field: FieldRef, key: &[u8]) {
+        if !self.limits.is_unlimited() {
+            let record_start = *self.line_offsets.last().unwrap_or(&0);
+            let record_fields = self.fields.len() - *self.field_starts.last().unwrap() as usize;
+            let record_end = field.val_offset + field.val_len as u64 - record_start;
+            if record_fields >= self.limits.max_fields as usize
+                || record_end > self.limits.max_record_bytes as u64
+            {
+                self.over_limit = true;
+                return;
+            }
+            if field.val_len > self.limits.max_value_len {
+                self.over_limit = true;
+                let value = unsafe {
+                    std::slice::from_raw_parts(
+                        self.data_ptr.add(field.val_offset as usize),
+                        field.val_len as usize,
+                    )
+                };
+                field.val_len = truncated_len(value, self.limits.max_value_len as usize) as u32;
+            }
+        }
+        field.key_id = self.keys.intern_hinted(key, self.key_hint());
+        if !self.columns.is_empty() {
+            self.fill_column(&field, key);
+        }
         self.fields.push(field);
     }
 
+    #[inline]
+    fn fill_column(&mut self, field: &FieldRef, key: &[u8]) {
+        if field.key_id as usize == self.column_of.len() {
+            let column = self.columns.iter().position(|c| *c.name == *key);
+            self.column_of.push(column.map_or(u32::MAX, |c| c as u32));
+        }
+        if let Some(column) = self
+            .columns
+            .get_mut(self.column_of[field.key_id as usize] as usize)
+            && let Some(len) = column.lens.last_mut()
+            && *len == ValueColumn::MISSING
+        {
+            *len = field.val_len;
+            *column.offsets.last_mut().unwrap() = field.val_offset;
+        }
+    }
+
+    /// Column holding key id `key_id`, if it is a hot key.
+    #[inline]
+    pub fn column_for_key(&self, key_id: u32) -> Option<&ValueColumn> {
+        let column = *self.column_of.get(key_id as usize)?;
+        self.columns.get(column as usize)
+    }
+
+    /// Schema of the record just ended. Records mostly repeat the previous
+    /// one's keys in the same order, which needs no sorting or hashing.
+    #[inline]
+    fn record_schema(&mut self) -> u32 {
+        let starts = &self.field_starts;
+        let (start, end) = (
+            starts[starts.len() - 2] as usize,
+            starts[starts.len() - 1] as usize,
+        );
+        if let [.., prev_start, _, _] = starts[..]
+            && let Some(&last) = self.schema_ids.last()
+        {
+            let prev = &self.fields[prev_start as usize..start];
+            let fields = &self.fields[start..end];
+            if prev.len() == fields.len()
+                && prev.iter().zip(fields).all(|(a, b)| a.key_id == b.key_id)
+            {
+                return last;
+            }
+        }
+        self.schemas
+            .intern(self.fields[start..end].iter().map(|f| f.key_id), &self.keys)
+    }
+
+    /// Key id of the field at the same position in the previous record.
+    #[inline]
+    fn key_hint(&self) -> Option<u32> {
+        let [.., prev_start, prev_end] = self.field_starts[..] else {
+            return None;
+        };
+        let idx = prev_start as usize + self.fields.len() - prev_end as usize;
+        (idx < prev_end as usize).then(|| self.fields[idx].key_id)
+    }
+
+    /// First field of record `i` named by key id `key_id`.
+    #[inline]
+    pub fn field_by_key(&self, i: usize, key_id: u32) -> Option<&FieldRef> {
+        self.record_fields(i).iter().find(|f| f.key_id == key_id)
+    }
+
     #[inline]
     pub fn end_record(&mut self) {
+        if self.over_limit {
+            let line_offset = *self.line_offsets.last().unwrap();
+            self.last_guarded = Some(line_offset);
+            match self.limits.policy {
+                GuardPolicy::Truncate => self.guard.truncated += 1,
+                GuardPolicy::Drop | GuardPolicy::Error => {
+                    self.discard_record();
+                    self.guard.dropped += 1;
+                    if self.limits.policy == GuardPolicy::Error {
+                        self.guard.first_error.get_or_insert(line_offset);
+                    }
+                    return;
+                }
+            }
+        }
+
+        if self.fields.len() == *self.field_starts.last().unwrap() as usize {
+            self.guard.empty += 1;
+            if self.limits.empty != EmptyPolicy::Keep {
+                let line_offset = *self.line_offsets.last().unwrap();
+                let line_len = *self.line_lens.last().unwrap();
+                self.discard_record();
+                if self.limits.empty == EmptyPolicy::Malformed {
+                    self.mark_malformed(line_offset, line_len);
+                }
+                self.last_guarded = Some(line_offset);
+                return;
+            }
+        }
+
         self.field_starts.push(self.fields.len() as u32);
+        if self.tag_schemas {
+            let schema = self.record_schema();
+            self.schema_ids.push(schema);
+        }
+        if self.firehose {
+            self.levels.push(LogLevel::Unknown);
+            return;
+        }
+
+        let wk = self.well_known.last().copied().unwrap_or_default();
+        let level = match self.well_known_bytes(wk.level) {
+            Some(value) => match LogLevel::from_name(value) {
+                // A bare syslog severity (journald's PRIORITY), or facility
+                // and severity packed under `pri`.
+                LogLevel::Unknown => Pri::from_value(value)
+                    .filter(|pri| {
+                        pri.facility == 0
+                            || self.keys.name(self.fields[wk.level as usize].key_id) == b"pri"
+                    })
+                    .map_or(LogLevel::Unknown, Pri::level),
+                level => level,
+            },
+            None => LogLevel::Unknown,
+        };
+        self.levels.push(level);
+        self.level_summary.record(level);
+
+        if let Some(ts) = self
+            .well_known_bytes(wk.timestamp)
+            .and_then(crate::parser::parse_timestamp_value)
+        {
+            self.time_range.record(ts);
+        }
     }
 
-    #[inline]
-    pub fn set_well_known_timestamp(&mut self, field_idx: u32) {
-        if let Some(wk) = self.well_known.last_mut() {
-            wk.timestamp = field_idx;
+    /// Takes back the record being built.
+    fn discard_record(&mut self) {
+        let start = *self.field_starts.last().unwrap() as usize;
+        self.fields.truncate(start);
+        self.line_offsets.pop();
+        self.line_lens.pop();
+        self.well_known.pop();
+        for column in &mut self.columns {
+            column.offsets.pop();
+            column.lens.pop();
         }
+        self.len -= 1;
     }
 
     #[inline]
-    pub fn set_well_known_level(&mut self, field_idx: u32) {
-        if let Some(wk) = self.well_known.last_mut() {
-            wk.level = field_idx;
+    fn well_known_bytes(&self, field_idx: u32) -> Option<&[u8]> {
+        if field_idx == u32::MAX {
+            return None;
         }
+        let field = &self.fields[field_idx as usize];
+        Some(unsafe {
+            std::slice::from_raw_parts(
+                self.data_ptr.add(field.val_offset as usize),
+                field.val_len as usize,
+            )
+        })
     }
 
+    /// Records a line the format parser could not make sense of; any partial
+    /// record it produced is kept.
     #[inline]
-    pub fn set_well_known_message(&mut self, field_idx: u32) {
-        if let Some(wk) = self.well_known.last_mut() {
-            wk.message = field_idx;
+    pub fn mark_malformed(&mut self, line_offset: u64, line_len: u32) {
+        if self.last_guarded == Some(line_offset) {
+            return;
+        }
+        self.malformed.push(LineSpan {
+            offset: line_offset,
+            len: line_len,
+        });
+    }
+
+    /// Limit hits in this batch, with `first_error` as an input offset.
+    pub fn guard_counts(&self) -> GuardCounts {
+        GuardCounts {
+            first_error: self.guard.first_error.map(|o| self.input_offset + o),
+            ..self.guard
         }
     }
 
+    /// Field count of the record started at or after `records_before`, if
+    /// the parser produced one.
     #[inline]
-    pub fn set_well_known_component(&mut self, field_idx: u32) {
-        if let Some(wk) = self.well_known.last_mut() {
-            wk.component = field_idx;
+    pub fn new_record_fields(&self, records_before: usize) -> Option<usize> {
+        (self.len > records_before).then(|| self.field_count(self.len - 1))
+    }
+
+    /// Points the slot for `kind` at `field_idx`; the last field of a kind
+    /// in a record wins.
+    #[inline]
+    pub fn set_well_known(&mut self, kind: well_known::WellKnownKind, field_idx: u32) {
+        use well_known::WellKnownKind;
+        if self.firehose || (field_idx as usize) >= self.fields.len() {
+            return;
         }
+        let Some(wk) = self.well_known.last_mut() else {
+            return;
+        };
+        let slot = match kind {
+            WellKnownKind::Timestamp => &mut wk.timestamp,
+            WellKnownKind::Level => &mut wk.level,
+            WellKnownKind::Message => &mut wk.message,
+            WellKnownKind::Component => &mut wk.component,
+            WellKnownKind::Host => &mut wk.host,
+            WellKnownKind::Pid => &mut wk.pid,
+            WellKnownKind::TraceId => &mut wk.trace_id,
+            WellKnownKind::SpanId => &mut wk.span_id,
+            WellKnownKind::Other => return,
+        };
+        *slot = field_idx;
     }
 
     #[inline]
@@ -132,11 +623,7 @@ impl StructuredBatch {
     /// # Safety
     /// The field reference must be valid and point to valid UTF-8 data within the log data.
     pub unsafe fn field_key(&self, field: &FieldRef) -> &str {
-        unsafe {
-            let ptr = self.data_ptr.add(field.key_offset as usize);
-            let slice = std::slice::from_raw_parts(ptr, field.key_len as usize);
-            std::str::from_utf8_unchecked(slice)
-        }
+        unsafe { std::str::from_utf8_unchecked(self.keys.name(field.key_id)) }
     }
 
     #[inline]
@@ -209,6 +696,134 @@ impl StructuredBatch {
         let field = &self.fields[wk.component as usize];
         Some(unsafe { self.field_value(field) })
     }
+
+    #[inline]
+    #[allow(dead_code)]
+    /// # Safety
+    /// The index must be within bounds and the well-known field must be valid.
+    pub unsafe fn host_value(&self, i: usize) -> Option<&str> {
+        let wk = &self.well_known[i];
+        if wk.host == u32::MAX {
+            return None;
+        }
+        let field = &self.fields[wk.host as usize];
+        Some(unsafe { self.field_value(field) })
+    }
+
+    #[inline]
+    #[allow(dead_code)]
+    /// # Safety
+    /// The index must be within bounds and the well-known field must be valid.
+    pub unsafe fn pid_value(&self, i: usize) -> Option<&str> {
+        let wk = &self.well_known[i];
+        if wk.pid == u32::MAX {
+            return None;
+        }
+        let field = &self.fields[wk.pid as usize];
+        Some(unsafe { self.field_value(field) })
+    }
+
+    #[inline]
+    #[allow(dead_code)]
+    /// # Safety
+    /// The index must be within bounds and the well-known field must be valid.
+    pub unsafe fn trace_id_value(&self, i: usize) -> Option<&str> {
+        let wk = &self.well_known[i];
+        if wk.trace_id == u32::MAX {
+            return None;
+        }
+        let field = &self.fields[wk.trace_id as usize];
+        Some(unsafe { self.field_value(field) })
+    }
+
+    #[inline]
+    #[allow(dead_code)]
+    /// # Safety
+    /// The index must be within bounds and the well-known field must be valid.
+    pub unsafe fn span_id_value(&self, i: usize) -> Option<&str> {
+        let wk = &self.well_known[i];
+        if wk.span_id == u32::MAX {
+            return None;
+        }
+        let field = &self.fields[wk.span_id as usize];
+        Some(unsafe { self.field_value(field) })
+    }
+}
+
+impl BatchRecords for StructuredBatch {
+    #[inline]
+    fn record_count(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn record_level(&self, i: usize) -> LogLevel {
+        self.levels[i]
+    }
+
+    #[inline]
+    fn record_timestamp(&self, i: usize) -> Option<u64> {
+        let value = unsafe { self.timestamp_value(i) }?;
+        crate::parser::parse_timestamp_value(value.as_bytes())
+    }
+
+    #[inline]
+    fn record_component(&self, i: usize) -> Option<&[u8]> {
+        self.well_known_bytes(self.well_known[i].component)
+    }
+
+    #[inline]
+    fn record_line(&self, i: usize) -> LineSpan {
+        LineSpan {
+            offset: self.line_offsets[i],
+            len: self.line_lens[i],
+        }
+    }
+
+    #[inline]
+    fn malformed_lines(&self) -> &[LineSpan] {
+        &self.malformed
+    }
+
+    #[inline]
+    fn input_offset(&self) -> u64 {
+        self.input_offset
+    }
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        self.data_len
+    }
+
+    #[inline]
+    fn data_ptr(&self) -> *const u8 {
+        self.data_ptr
+    }
+
+    #[inline]
+    fn level_summary(&self) -> &LevelSummary {
+        &self.level_summary
+    }
+
+    #[inline]
+    fn file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    #[inline]
+    fn rewritten(&self) -> bool {
+        self.rewritten
+    }
+
+    #[inline]
+    fn chunk_seq(&self) -> u64 {
+        self.chunk_seq
+    }
+
+    #[inline]
+    fn line_no(&self, i: usize) -> Option<u64> {
+        self.line_numbers.get(i).copied()
+    }
 }
 
 impl fmt::Debug for StructuredBatch {
@@ -225,11 +840,28 @@ pub struct StructuredParseStats {
     pub total_bytes: u64,
     pub total_records: u64,
     pub total_fields: u64,
+    pub distinct_keys: usize,
     pub scan_time_ms: f64,
     pub parse_time_ms: f64,
+    /// Time spent serializing matches for export, summed over threads.
+    pub serialize_time_ms: f64,
+    /// Time the sinks spent writing while this input was parsed.
+    pub sink_time_ms: f64,
     pub total_time_ms: f64,
     pub threads_used: usize,
+    /// Kernel the newline scans ran with (`--simd`).
+    pub simd: KernelChoice,
     pub format: &'static str,
+    pub levels: LevelSummary,
+    pub time_range: TimeRange,
+    pub malformed_lines: u64,
+    pub guard: GuardCounts,
+    /// What became of the `guard.empty` records without fields.
+    pub empty_policy: EmptyPolicy,
+    /// Bytes of `total_bytes` skipped as NUL holes.
+    pub hole_bytes: u64,
+    pub failed_chunks: Vec<FailedChunk>,
+    pub page_faults: PageFaults,
 }
 
 impl StructuredParseStats {
@@ -252,6 +884,18 @@ impl fmt::Display for StructuredParseStats {
             "  Total bytes:   {:>10.2} GB              ",
             self.total_bytes as f64 / (1024.0 * 1024.0 * 1024.0)
         )?;
+        if self.hole_bytes > 0 {
+            writeln!(
+                f,
+                "    └─ holes:    {:>10.2} GB              ",
+                self.hole_bytes as f64 / (1024.0 * 1024.0 * 1024.0)
+            )?;
+            writeln!(
+                f,
+                "    └─ data:     {:>10.2} GB              ",
+                (self.total_bytes - self.hole_bytes) as f64 / (1024.0 * 1024.0 * 1024.0)
+            )?;
+        }
         writeln!(
             f,
             "  Total records: {:>10}                 ",
@@ -262,11 +906,64 @@ impl fmt::Display for StructuredParseStats {
             "  Total fields:  {:>10}                 ",
             self.total_fields
         )?;
+        writeln!(
+            f,
+            "  Distinct keys: {:>10}                 ",
+            self.distinct_keys
+        )?;
         writeln!(
             f,
             "  Threads used:  {:>10}                 ",
             self.threads_used
         )?;
+        writeln!(f, "  SIMD kernel:   {:>10}", self.simd.to_string())?;
+        if self.levels.total() > 0 {
+            writeln!(f, "╠══════════════════════════════════════════╣")?;
+            write!(f, "{}", self.levels)?;
+        }
+        if self.malformed_lines > 0 {
+            writeln!(
+                f,
+                "  Malformed:     {:>10}                 ",
+                self.malformed_lines
+            )?;
+        }
+        if self.guard.truncated > 0 {
+            writeln!(
+                f,
+                "  Truncated:     {:>10}                 ",
+                self.guard.truncated
+            )?;
+        }
+        if self.guard.dropped > 0 {
+            writeln!(
+                f,
+                "  Over limits:   {:>10} dropped         ",
+                self.guard.dropped
+            )?;
+        }
+        if self.guard.empty > 0 {
+            writeln!(
+                f,
+                "  Empty records: {:>10} {:<16}",
+                self.guard.empty,
+                self.empty_policy.as_str()
+            )?;
+        }
+        if !self.failed_chunks.is_empty() {
+            writeln!(
+                f,
+                "  Failed chunks: {:>10}                 ",
+                self.failed_chunks.len()
+            )?;
+            for chunk in &self.failed_chunks {
+                writeln!(f, "    └─ bytes {}..{}", chunk.start, chunk.end)?;
+            }
+        }
+        if !self.time_range.is_empty() {
+            writeln!(f, "╠══════════════════════════════════════════╣")?;
+            write!(f, "{}", self.time_range)?;
+        }
         writeln!(f, "╠══════════════════════════════════════════╣")?;
         writeln!(
             f,
@@ -278,6 +975,18 @@ impl fmt::Display for StructuredParseStats {
             "  Parse time:    {:>8.1} ms               ",
             self.parse_time_ms
         )?;
+        if self.serialize_time_ms > 0.0 || self.sink_time_ms > 0.0 {
+            writeln!(
+                f,
+                "  Serialize:     {:>8.1} ms               ",
+                self.serialize_time_ms
+            )?;
+            writeln!(
+                f,
+                "  Sink time:     {:>8.1} ms               ",
+                self.sink_time_ms
+            )?;
+        }
         writeln!(
             f,
             "  Total time:    {:>8.1} ms               ",
@@ -288,12 +997,22 @@ impl fmt::Display for StructuredParseStats {
             "  Throughput:    {:>8.2} GB/s             ",
             self.throughput_gbps()
         )?;
+        if self.page_faults.total() > 0 {
+            writeln!(
+                f,
+                "  Page faults:   {:>10} ({} major)     ",
+                self.page_faults.total(),
+                self.page_faults.major
+            )?;
+        }
         writeln!(f, "╚══════════════════════════════════════════╝")?;
         Ok(())
     }
 }
 
 pub mod well_known {
+    use std::sync::OnceLock;
+
     const TIMESTAMP_NAMES: &[&[u8]] = &[
         b"timestamp",
         b"time",
@@ -316,6 +1035,7 @@ pub mod well_known {
         b"log.level",
         b"priority",
         b"sev",
+        b"pri",
     ];
 
     const MESSAGE_NAMES: &[&[u8]] = &[
@@ -341,15 +1061,96 @@ pub mod well_known {
         b"tag",
     ];
 
+    const HOST_NAMES: &[&[u8]] = &[
+        b"host",
+        b"hostname",
+        b"host.name",
+        b"host_name",
+        b"node",
+        b"nodename",
+        b"node_name",
+    ];
+
+    const PID_NAMES: &[&[u8]] = &[
+        b"pid",
+        b"process_id",
+        b"process.pid",
+        b"processid",
+        b"proc_id",
+    ];
+
+    const TRACE_ID_NAMES: &[&[u8]] = &[
+        b"trace_id",
+        b"traceid",
+        b"trace.id",
+        b"trace-id",
+        b"dd.trace_id",
+        b"x-b3-traceid",
+        b"request_trace_id",
+    ];
+
+    const SPAN_ID_NAMES: &[&[u8]] = &[
+        b"span_id",
+        b"spanid",
+        b"span.id",
+        b"span-id",
+        b"dd.span_id",
+        b"x-b3-spanid",
+    ];
+
+    static OVERRIDES: OnceLock<Vec<(Box<[u8]>, WellKnownKind)>> = OnceLock::new();
+
+    /// Extra keys for the slots, from `--well-known slot=key`. They are
+    /// checked before the built-in names; only the first call takes effect.
+    pub fn set_overrides(overrides: Vec<(Box<[u8]>, WellKnownKind)>) {
+        let _ = OVERRIDES.set(overrides);
+    }
+
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum WellKnownKind {
         Timestamp,
         Level,
         Message,
         Component,
+        Host,
+        Pid,
+        TraceId,
+        SpanId,
         Other,
     }
 
+    impl WellKnownKind {
+        /// Slot names accepted by `--well-known`.
+        pub fn from_slot(name: &str) -> Option<WellKnownKind> {
+            match name {
+                "timestamp" => Some(WellKnownKind::Timestamp),
+                "level" => Some(WellKnownKind::Level),
+                "message" => Some(WellKnownKind::Message),
+                "component" => Some(WellKnownKind::Component),
+                "host" => Some(WellKnownKind::Host),
+                "pid" => Some(WellKnownKind::Pid),
+                "trace_id" => Some(WellKnownKind::TraceId),
+                "span_id" => Some(WellKnownKind::SpanId),
+                _ => None,
+            }
+        }
+    }
+
+    fn lookup(lower: &[u8], names: &[&[u8]], kind: WellKnownKind) -> Option<WellKnownKind> {
+        names.contains(&lower).then_some(kind)
+    }
+
+    /// Host, pid and trace names, checked after the older slots so those
+    /// keep their keys.
+    #[inline]
+    fn classify_correlation(lower: &[u8]) -> WellKnownKind {
+        lookup(lower, HOST_NAMES, WellKnownKind::Host)
+            .or_else(|| lookup(lower, PID_NAMES, WellKnownKind::Pid))
+            .or_else(|| lookup(lower, TRACE_ID_NAMES, WellKnownKind::TraceId))
+            .or_else(|| lookup(lower, SPAN_ID_NAMES, WellKnownKind::SpanId))
+            .unwrap_or(WellKnownKind::Other)
+    }
+
     #[inline]
     pub fn classify_key(key: &[u8]) -> WellKnownKind {
         let mut buf = [0u8; 64];
@@ -360,8 +1161,15 @@ pub mod well_known {
         }
         let lower = &buf[..len];
 
+        if let Some(overrides) = OVERRIDES.get()
+            && let Some((_, kind)) = overrides.iter().find(|(key, _)| **key == *lower)
+        {
+            return *kind;
+        }
+
         match lower.first() {
             Some(b't') | Some(b'@') | Some(b'd') | Some(b'c') | Some(b'e') | Some(b'l') => {}
+            Some(b'h') | Some(b'x') | Some(b'r') => return classify_correlation(lower),
             Some(b'm') => {
                 for name in MESSAGE_NAMES {
                     if lower == *name {
@@ -386,7 +1194,7 @@ pub mod well_known {
                         return WellKnownKind::Component;
                     }
                 }
-                return WellKnownKind::Other;
+                return classify_correlation(lower);
             }
             Some(b'p') => {
                 for name in LEVEL_NAMES {
@@ -394,7 +1202,7 @@ pub mod well_known {
                         return WellKnownKind::Level;
                     }
                 }
-                return WellKnownKind::Other;
+                return classify_correlation(lower);
             }
             Some(b'b') | Some(b'n') => {
                 for name in MESSAGE_NAMES {
@@ -407,7 +1215,7 @@ pub mod well_known {
                         return WellKnownKind::Component;
                     }
                 }
-                return WellKnownKind::Other;
+                return classify_correlation(lower);
             }
             _ => return WellKnownKind::Other,
         }
@@ -433,7 +1241,7 @@ pub mod well_known {
             }
         }
 
-        WellKnownKind::Other
+        classify_correlation(lower)
     }
 }
 
@@ -452,19 +1260,24 @@ mod tests {
             key_len: 5,
             val_offset: 10,
             val_len: 4,
+            key_id: 0,
         });
         batch.push_field(FieldRef {
             key_offset: 17,
             key_len: 3,
             val_offset: 23,
             val_len: 5,
+            key_id: 0,
         });
-        batch.set_well_known_level(0);
-        batch.set_well_known_message(1);
+        batch.set_well_known(well_known::WellKnownKind::Level, 0);
+        batch.set_well_known(well_known::WellKnownKind::Message, 1);
         batch.end_record();
 
         assert_eq!(batch.len, 1);
         assert_eq!(batch.field_count(0), 2);
+        assert_eq!(batch.levels[0], LogLevel::Info);
+        assert_eq!(batch.level_summary.count(LogLevel::Info), 1);
+        assert!(batch.time_range.is_empty());
 
         unsafe {
             assert_eq!(batch.level_value(0), Some("info"));
@@ -491,5 +1304,19 @@ mod tests {
         assert_eq!(classify_key(b"LEVEL"), WellKnownKind::Level);
         assert_eq!(classify_key(b"Timestamp"), WellKnownKind::Timestamp);
         assert_eq!(classify_key(b"MSG"), WellKnownKind::Message);
+        assert_eq!(classify_key(b"hostname"), WellKnownKind::Host);
+        assert_eq!(classify_key(b"host.name"), WellKnownKind::Host);
+        assert_eq!(classify_key(b"node"), WellKnownKind::Host);
+        assert_eq!(classify_key(b"pid"), WellKnownKind::Pid);
+        assert_eq!(classify_key(b"priority"), WellKnownKind::Level);
+        assert_eq!(classify_key(b"traceId"), WellKnownKind::TraceId);
+        assert_eq!(classify_key(b"dd.trace_id"), WellKnownKind::TraceId);
+        assert_eq!(classify_key(b"span_id"), WellKnownKind::SpanId);
+        assert_eq!(classify_key(b"X-B3-SpanId"), WellKnownKind::SpanId);
+        assert_eq!(
+            WellKnownKind::from_slot("trace_id"),
+            Some(WellKnownKind::TraceId)
+        );
+        assert_eq!(WellKnownKind::from_slot("nope"), None);
     }
 }
@@ -1,9 +1,20 @@
+use crate::ansi;
 use crate::csv_parser::{self, CsvHeader};
+use crate::data::{FailedChunk, LevelSummary, TimeRange, count_lines, number_lines};
+use crate::filter::MatchControl;
+use crate::fixed_parser::{self, FixedLayout};
 use crate::format::LogFormat;
+use crate::holes;
 use crate::json_parser;
+use crate::keys::KeyTable;
 use crate::logfmt_parser;
+use crate::orchestrator::{
+    ChunkDispatch, chunk_first_lines, chunk_len, chunk_order, chunk_segments, parse_isolated,
+};
+use crate::pinning;
+use crate::seek::seek_to_time;
 use crate::simd_scan;
-use crate::structured::StructuredBatch;
+use crate::structured::{EmptyPolicy, GuardCounts, RecordLimits, StructuredBatch};
 use std::fs::File;
 use std::io::Read;
 use std::thread;
@@ -13,26 +24,56 @@ pub struct StructuredPipelineResult {
     pub batches: Vec<StructuredBatch>,
     pub total_records: usize,
     pub total_fields: usize,
+    /// Distinct keys across every batch parsed, kept or not.
+    pub distinct_keys: usize,
     pub scan_time_ms: f64,
     pub parse_time_ms: f64,
     pub format: LogFormat,
+    pub level_summary: LevelSummary,
+    pub time_range: TimeRange,
+    pub malformed_lines: u64,
+    pub guard: GuardCounts,
+    /// Input bytes parsed; short of the input when the parse stopped early.
+    pub parsed_bytes: u64,
+    /// NUL-filled bytes skipped as holes rather than parsed.
+    pub hole_bytes: u64,
+    /// Chunks whose parse panicked, in input order; their records are missing.
+    pub failed_chunks: Vec<FailedChunk>,
 
     pub _backing_data: Vec<Vec<u8>>,
 }
 
+#[allow(dead_code)]
 pub fn parse_structured_mmap(
     data: &[u8],
     num_threads: usize,
     format_hint: Option<LogFormat>,
+) -> StructuredPipelineResult {
+    parse_structured_mmap_with(data, num_threads, format_hint, &MatchControl::default())
+}
+
+pub fn parse_structured_mmap_with(
+    data: &[u8],
+    num_threads: usize,
+    format_hint: Option<LogFormat>,
+    control: &MatchControl<'_, StructuredBatch>,
 ) -> StructuredPipelineResult {
     if data.is_empty() {
         return StructuredPipelineResult {
             batches: vec![],
             total_records: 0,
             total_fields: 0,
+            distinct_keys: 0,
             scan_time_ms: 0.0,
             parse_time_ms: 0.0,
             format: LogFormat::PlainText,
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed_lines: 0,
+            guard: GuardCounts::default(),
+            parsed_bytes: 0,
+            hole_bytes: 0,
+            failed_chunks: vec![],
             _backing_data: vec![],
         };
     }
@@ -40,38 +81,65 @@ pub fn parse_structured_mmap(
     let format = format_hint.unwrap_or_else(|| LogFormat::detect(data));
 
     match format {
-        LogFormat::Json => parse_json_mmap(data, num_threads),
-        LogFormat::Logfmt => parse_logfmt_mmap(data, num_threads),
-        LogFormat::Csv => parse_csv_mmap(data, num_threads),
-        LogFormat::PlainText => parse_logfmt_mmap(data, num_threads),
+        LogFormat::Json => parse_json_mmap(data, num_threads, control),
+        LogFormat::Logfmt => parse_logfmt_mmap(data, num_threads, control),
+        LogFormat::Csv => parse_csv_mmap(data, num_threads, control),
+        LogFormat::FixedWidth => parse_format_mmap(
+            data,
+            num_threads,
+            LogFormat::FixedWidth,
+            None,
+            0,
+            1,
+            control,
+        ),
+        LogFormat::PlainText => parse_logfmt_mmap(data, num_threads, control),
     }
 }
 
+#[allow(dead_code)]
 pub fn parse_structured_streamed(
     file: &mut File,
     file_size: u64,
     num_threads: usize,
     format_hint: Option<LogFormat>,
+) -> StructuredPipelineResult {
+    parse_structured_streamed_with(
+        file,
+        file_size,
+        num_threads,
+        format_hint,
+        &MatchControl::default(),
+    )
+}
+
+pub fn parse_structured_streamed_with(
+    file: &mut File,
+    file_size: u64,
+    num_threads: usize,
+    format_hint: Option<LogFormat>,
+    control: &MatchControl<'_, StructuredBatch>,
 ) -> StructuredPipelineResult {
     if file_size == 0 {
         return StructuredPipelineResult {
             batches: vec![],
             total_records: 0,
             total_fields: 0,
+            distinct_keys: 0,
             scan_time_ms: 0.0,
             parse_time_ms: 0.0,
             format: LogFormat::PlainText,
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed_lines: 0,
+            guard: GuardCounts::default(),
+            parsed_bytes: 0,
+            hole_bytes: 0,
+            failed_chunks: vec![],
             _backing_data: vec![],
         };
     }
 
-    let segment_size = std::env::var("PANDORA_CHUNK_MB")
-        .ok()
-        .and_then(|v| v.parse::<usize>().ok())
-        .filter(|v| *v >= 1)
-        .map(|mb| mb * 1024 * 1024)
-        .unwrap_or(64 * 1024 * 1024);
-
     #[cfg(unix)]
     unsafe {
         use std::os::unix::io::AsRawFd;
@@ -83,20 +151,46 @@ pub fn parse_structured_streamed(
         );
     }
 
+    parse_structured_read_with(file, num_threads, format_hint, control)
+}
+
+/// Streams structured records from any reader, e.g. a transcoder.
+pub fn parse_structured_read_with(
+    reader: &mut impl Read,
+    num_threads: usize,
+    format_hint: Option<LogFormat>,
+    control: &MatchControl<'_, StructuredBatch>,
+) -> StructuredPipelineResult {
+    let segment_size = std::env::var("PANDORA_CHUNK_MB")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v >= 1)
+        .map(|mb| mb * 1024 * 1024)
+        .unwrap_or(64 * 1024 * 1024);
+
     let mut read_buf = vec![0u8; segment_size];
     let mut leftover: Vec<u8> = Vec::new();
     let mut result_batches: Vec<StructuredBatch> = Vec::new();
     let mut backing_data: Vec<Vec<u8>> = Vec::new();
-    let mut total_records = 0usize;
-    let mut total_fields = 0usize;
+    let mut totals = Totals::default();
     let mut total_scan_ms = 0.0f64;
     let mut total_parse_ms = 0.0f64;
     let mut format: Option<LogFormat> = format_hint;
     let mut csv_header: Option<CsvHeader> = None;
+    let mut hot_keys: Option<Vec<Box<[u8]>>> = None;
     let mut first_chunk = true;
+    let mut time_range = TimeRange::default();
+    let mut hole_bytes = 0u64;
+    let mut failed_chunks = Vec::new();
+    let mut consumed = 0u64;
+    let mut line = 1u64;
+    let mut seq = 0u64;
 
     loop {
-        let bytes_read = read_full(file, &mut read_buf).unwrap_or(0);
+        if control.should_stop() {
+            break;
+        }
+        let bytes_read = read_full(reader, &mut read_buf).unwrap_or(0);
         let at_eof = bytes_read < segment_size;
 
         let mut work_buf: Vec<u8> = if leftover.is_empty() {
@@ -128,6 +222,10 @@ pub fn parse_structured_streamed(
             if csv_header.is_some() {
                 let header_end = csv_parser::header_end_offset(&work_buf);
                 if header_end < work_buf.len() {
+                    consumed += header_end as u64;
+                    totals.bytes += header_end as u64;
+                    control.advance(header_end);
+                    line += count_lines(&work_buf[..header_end]);
                     work_buf = work_buf[header_end..].to_vec();
                 } else {
                     continue;
@@ -135,11 +233,35 @@ pub fn parse_structured_streamed(
             }
         }
 
+        let record_len = control
+            .fixed_layout
+            .and_then(|layout| layout.record_len)
+            .filter(|_| detected_format == LogFormat::FixedWidth);
         let complete_end = if at_eof {
             work_buf.len()
+        } else if let Some(len) = record_len {
+            work_buf.len() / len * len
+        } else if detected_format == LogFormat::Csv {
+            // A quoted cell left open would hold back the rest of the input;
+            // past a few segments, cut at the last line end regardless.
+            let end = csv_parser::last_record_end(&work_buf).or_else(|| {
+                (work_buf.len() >= 4 * segment_size)
+                    .then(|| memchr::memrchr(simd_scan::record_sep(), &work_buf))
+                    .flatten()
+                    .map(|pos| pos + 1)
+            });
+            match end {
+                Some(end) => end,
+                None => {
+                    leftover = work_buf;
+                    continue;
+                }
+            }
         } else {
-            match memchr::memrchr(b'\n', &work_buf) {
+            match memchr::memrchr(simd_scan::record_sep(), &work_buf) {
                 Some(pos) => pos + 1,
+                // A hole ends no line; cut there rather than buffer it all.
+                None if work_buf.ends_with(&[0]) => work_buf.len(),
                 None => {
                     leftover = work_buf;
                     continue;
@@ -158,20 +280,71 @@ pub fn parse_structured_streamed(
             }
             continue;
         }
+        let input_len = work_buf.len();
+        if control.strip_ansi
+            && let Some(clean) = ansi::strip(&work_buf)
+        {
+            work_buf = clean;
+        }
 
-        let (batch, scan_ms, parse_ms) = parse_structured_chunk_owned(
-            &work_buf,
-            detected_format,
-            csv_header.as_ref(),
-            num_threads,
-        );
-        total_records += batch.len;
-        total_fields += batch.fields.len();
-        total_scan_ms += scan_ms;
-        total_parse_ms += parse_ms;
-
-        result_batches.push(batch);
-        backing_data.push(work_buf);
+        let mut schema = ChunkSchema {
+            format: detected_format,
+            csv_header: csv_header.as_ref(),
+            fixed: control.fixed_layout,
+            firehose: control.firehose,
+            tag_schemas: control.tag_schemas,
+            hot_keys: &[],
+        };
+        schema.hot_keys =
+            hot_keys.get_or_insert_with(|| sample_hot_keys(&work_buf, schema, control.hot_columns));
+
+        let (segments, skipped) = holes::data_segments(&work_buf, 0, work_buf.len());
+        hole_bytes += skipped;
+        let mut keep = false;
+        for (start, end) in segments {
+            let Some((mut batch, scan_ms, parse_ms)) = parse_isolated(|| {
+                parse_structured_chunk_owned(
+                    &work_buf[start..end],
+                    schema,
+                    control.record_limits,
+                    num_threads,
+                )
+            }) else {
+                failed_chunks.push(FailedChunk {
+                    start: consumed + start as u64,
+                    end: consumed + end as u64,
+                });
+                continue;
+            };
+            batch.input_offset = consumed + start as u64;
+            let first_line = control
+                .line_numbers
+                .then(|| line + count_lines(&work_buf[..start]));
+            stamp(&mut batch, control, &work_buf[start..end], 0, first_line);
+            batch.chunk_seq = seq;
+            let matched = control.visit(&batch);
+            control.reject(&batch);
+            totals.add(&batch);
+            total_scan_ms += scan_ms;
+            total_parse_ms += parse_ms;
+            time_range.merge(&batch.time_range);
+            // `work_buf` is reused for the next chunk, so no hand-off.
+            if let Some(batch) = control.emit(None, batch, matched, seq) {
+                result_batches.push(batch);
+                keep = true;
+            }
+        }
+        control.chunk_done(seq);
+        control.advance(input_len);
+        totals.bytes += input_len as u64;
+        consumed += work_buf.len() as u64;
+        if control.line_numbers {
+            line += count_lines(&work_buf);
+        }
+        if keep {
+            backing_data.push(work_buf);
+        }
+        seq += 1;
 
         if at_eof {
             break;
@@ -180,11 +353,19 @@ pub fn parse_structured_streamed(
 
     StructuredPipelineResult {
         batches: result_batches,
-        total_records,
-        total_fields,
+        total_records: totals.records,
+        total_fields: totals.fields,
+        distinct_keys: totals.keys.len(),
         scan_time_ms: total_scan_ms,
         parse_time_ms: total_parse_ms,
         format: format.unwrap_or(LogFormat::PlainText),
+        level_summary: totals.levels,
+        time_range,
+        malformed_lines: totals.malformed,
+        guard: totals.guard,
+        parsed_bytes: totals.bytes,
+        hole_bytes,
+        failed_chunks,
         _backing_data: backing_data,
     }
 }
@@ -200,33 +381,63 @@ fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
     Ok(filled)
 }
 
-fn parse_json_mmap(data: &[u8], num_threads: usize) -> StructuredPipelineResult {
-    parse_format_mmap(data, num_threads, LogFormat::Json, None)
+fn parse_json_mmap(
+    data: &[u8],
+    num_threads: usize,
+    control: &MatchControl<'_, StructuredBatch>,
+) -> StructuredPipelineResult {
+    parse_format_mmap(data, num_threads, LogFormat::Json, None, 0, 1, control)
 }
 
-fn parse_logfmt_mmap(data: &[u8], num_threads: usize) -> StructuredPipelineResult {
-    parse_format_mmap(data, num_threads, LogFormat::Logfmt, None)
+fn parse_logfmt_mmap(
+    data: &[u8],
+    num_threads: usize,
+    control: &MatchControl<'_, StructuredBatch>,
+) -> StructuredPipelineResult {
+    parse_format_mmap(data, num_threads, LogFormat::Logfmt, None, 0, 1, control)
 }
 
-fn parse_csv_mmap(data: &[u8], num_threads: usize) -> StructuredPipelineResult {
+fn parse_csv_mmap(
+    data: &[u8],
+    num_threads: usize,
+    control: &MatchControl<'_, StructuredBatch>,
+) -> StructuredPipelineResult {
     let csv_header = CsvHeader::parse(data);
     let data_start = csv_parser::header_end_offset(data);
+    control.advance(data_start);
 
     if data_start >= data.len() {
         return StructuredPipelineResult {
             batches: vec![],
             total_records: 0,
             total_fields: 0,
+            distinct_keys: 0,
             scan_time_ms: 0.0,
             parse_time_ms: 0.0,
             format: LogFormat::Csv,
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed_lines: 0,
+            guard: GuardCounts::default(),
+            parsed_bytes: 0,
+            hole_bytes: 0,
+            failed_chunks: vec![],
             _backing_data: vec![],
         };
     }
 
     let body = &data[data_start..];
-    let mut result = parse_format_mmap(body, num_threads, LogFormat::Csv, csv_header.as_ref());
+    let mut result = parse_format_mmap(
+        body,
+        num_threads,
+        LogFormat::Csv,
+        csv_header.as_ref(),
+        data_start as u64,
+        1 + count_lines(&data[..data_start]),
+        control,
+    );
     result.format = LogFormat::Csv;
+    result.parsed_bytes += data_start as u64;
     result
 }
 
@@ -235,15 +446,44 @@ fn parse_format_mmap(
     num_threads: usize,
     format: LogFormat,
     csv_header: Option<&CsvHeader>,
+    input_offset: u64,
+    first_line: u64,
+    control: &MatchControl<'_, StructuredBatch>,
 ) -> StructuredPipelineResult {
+    // Seeking reads timestamps without the fixed-width layout, so those
+    // records are only filtered by time one at a time.
+    let seek_offset = match control.since {
+        Some(_) if format == LogFormat::FixedWidth => 0,
+        Some(ts) => match control.index {
+            Some(index) => index.seek_to_time(data, input_offset, format, csv_header, ts),
+            None => seek_to_time(data, format, csv_header, ts),
+        },
+        None => 0,
+    };
+    let first_line = if control.line_numbers {
+        first_line + count_lines(&data[..seek_offset])
+    } else {
+        first_line
+    };
+    control.advance(seek_offset);
+    let data = &data[seek_offset..];
+    let base_offset = input_offset + seek_offset as u64;
     if data.is_empty() {
         return StructuredPipelineResult {
             batches: vec![],
             total_records: 0,
             total_fields: 0,
+            distinct_keys: 0,
             scan_time_ms: 0.0,
             parse_time_ms: 0.0,
             format,
+            level_summary: LevelSummary::default(),
+            time_range: TimeRange::default(),
+            malformed_lines: 0,
+            guard: GuardCounts::default(),
+            parsed_bytes: 0,
+            hole_bytes: 0,
+            failed_chunks: vec![],
             _backing_data: vec![],
         };
     }
@@ -255,113 +495,501 @@ fn parse_format_mmap(
         .unwrap_or(64);
     let chunk_size = chunk_mb * 1024 * 1024;
 
-    let mut boundaries = vec![0usize];
-    let mut pos = chunk_size;
-    while pos < data.len() {
-        match memchr::memchr(b'\n', &data[pos..]) {
-            Some(off) => {
-                let boundary = pos + off + 1;
-                boundaries.push(boundary);
-                pos = boundary + chunk_size;
+    let fixed = control
+        .fixed_layout
+        .filter(|_| format == LogFormat::FixedWidth);
+    // Quoted CSV cells may span lines, so only a quote-aware search may
+    // pick where a chunk ends.
+    let ramp = control.limit.limit().is_some() && !control.reverse;
+    let boundaries = if format == LogFormat::Csv {
+        csv_parser::record_boundaries(data, chunk_size, ramp, num_threads)
+    } else {
+        let mut boundaries = vec![0usize];
+        let mut pos = chunk_len(chunk_size, 0, ramp);
+        while pos < data.len() {
+            let next = match fixed.and_then(|layout| layout.record_boundary(pos)) {
+                Some(boundary) => Some(boundary).filter(|&b| b < data.len()),
+                None => {
+                    memchr::memchr(simd_scan::record_sep(), &data[pos..]).map(|off| pos + off + 1)
+                }
+            };
+            match next {
+                Some(boundary) => {
+                    boundaries.push(boundary);
+                    pos = boundary + chunk_len(chunk_size, boundaries.len() - 1, ramp);
+                }
+                None => break,
             }
-            None => break,
         }
-    }
-    boundaries.push(data.len());
+        boundaries.push(data.len());
+        boundaries
+    };
 
     let num_chunks = boundaries.len() - 1;
     let worker_threads = num_threads.max(1).min(num_chunks.max(1));
+    let mut schema = ChunkSchema {
+        format,
+        csv_header,
+        fixed,
+        firehose: control.firehose,
+        tag_schemas: control.tag_schemas,
+        hot_keys: &[],
+    };
+    let hot_keys = sample_hot_keys(data, schema, control.hot_columns);
+    schema.hot_keys = &hot_keys;
+    let first_lines = control
+        .line_numbers
+        .then(|| chunk_first_lines(data, &boundaries, first_line, num_threads));
+    let first_line = |chunk: usize, start: usize| {
+        first_lines
+            .as_ref()
+            .map(|lines| lines[chunk] + count_lines(&data[boundaries[chunk]..start]))
+    };
 
     if worker_threads == 1 || num_chunks <= 1 {
-        let mut batches = Vec::with_capacity(num_chunks);
-        let mut total_scan_ms = 0.0f64;
-        let mut total_parse_ms = 0.0f64;
-        let mut total_records = 0;
-        let mut total_fields = 0;
-
-        for i in 0..num_chunks {
-            let start = boundaries[i];
-            let end = boundaries[i + 1];
-            let (batch, scan_ms, parse_ms) =
-                parse_structured_chunk(data, start, end, format, csv_header);
-            total_records += batch.len;
-            total_fields += batch.fields.len();
-            total_scan_ms += scan_ms;
-            total_parse_ms += parse_ms;
-            batches.push(batch);
-        }
-
-        return StructuredPipelineResult {
-            batches,
-            total_records,
-            total_fields,
-            scan_time_ms: total_scan_ms,
-            parse_time_ms: total_parse_ms,
-            format,
-            _backing_data: vec![],
-        };
-    }
+        return control.with_serializers(|handoff| {
+            let mut batches = Vec::with_capacity(num_chunks);
+            let mut ranges = Vec::with_capacity(num_chunks);
+            let mut totals = Totals::default();
+            let mut total_scan_ms = 0.0f64;
+            let mut total_parse_ms = 0.0f64;
+
+            let mut hole_bytes = 0;
+            let mut failed_chunks = Vec::new();
+            let mut backing_data = Vec::new();
+
+            for (seq, i) in chunk_order(num_chunks, control.reverse)
+                .into_iter()
+                .enumerate()
+            {
+                if control.should_stop() {
+                    break;
+                }
+                control.prefetch(base_offset, boundaries[i], boundaries[i + 1]);
+                let (segments, skipped) =
+                    chunk_segments(data, boundaries[i], boundaries[i + 1], control.reverse);
+                hole_bytes += skipped;
+                for (start, end) in segments {
+                    let Some((mut batch, scan_ms, parse_ms, stripped)) = parse_isolated(|| {
+                        parse_segment(
+                            data,
+                            start,
+                            end,
+                            schema,
+                            base_offset,
+                            first_line(i, start),
+                            control,
+                        )
+                    }) else {
+                        failed_chunks.push(FailedChunk {
+                            start: base_offset + start as u64,
+                            end: base_offset + end as u64,
+                        });
+                        continue;
+                    };
+                    batch.chunk_seq = seq as u64;
+                    let matched = control.visit(&batch);
+                    control.reject(&batch);
+                    totals.add(&batch);
+                    ranges.push(batch.time_range);
+                    total_scan_ms += scan_ms;
+                    total_parse_ms += parse_ms;
+                    let handoff = handoff.filter(|_| stripped.is_none());
+                    if let Some(batch) = control.emit(handoff, batch, matched, seq as u64) {
+                        backing_data.extend(stripped);
+                        batches.push(batch);
+                    }
+                }
+                control.chunk_done(seq as u64);
+                control.advance(boundaries[i + 1] - boundaries[i]);
+                totals.bytes += (boundaries[i + 1] - boundaries[i]) as u64;
+            }
 
-    let mut assignments: Vec<Vec<(usize, usize, usize)>> = vec![Vec::new(); worker_threads];
-    for (worker_idx, assignment) in assignments.iter_mut().enumerate() {
-        let start_chunk = (worker_idx * num_chunks) / worker_threads;
-        let end_chunk = ((worker_idx + 1) * num_chunks) / worker_threads;
-        for i in start_chunk..end_chunk {
-            assignment.push((i, boundaries[i], boundaries[i + 1]));
-        }
+            StructuredPipelineResult {
+                batches,
+                total_records: totals.records,
+                total_fields: totals.fields,
+                distinct_keys: totals.keys.len(),
+                scan_time_ms: total_scan_ms,
+                parse_time_ms: total_parse_ms,
+                format,
+                level_summary: totals.levels,
+                time_range: merge_time_ranges(&ranges, control.reverse),
+                malformed_lines: totals.malformed,
+                guard: totals.guard,
+                parsed_bytes: totals.bytes,
+                hole_bytes,
+                failed_chunks,
+                _backing_data: backing_data,
+            }
+        });
     }
 
-    let mut ordered_batches: Vec<Option<StructuredBatch>> = (0..num_chunks).map(|_| None).collect();
+    let dispatch = ChunkDispatch::new(
+        num_chunks,
+        worker_threads,
+        control.reverse,
+        control.limit.limit().is_some() || control.ordered.is_some(),
+    );
+
+    // Per chunk, each batch's time range and the batch when kept.
+    let mut ordered: Vec<Vec<(TimeRange, Option<StructuredBatch>)>> =
+        (0..num_chunks).map(|_| Vec::new()).collect();
+    let mut totals = Totals::default();
     let mut scan_time_ms = 0.0f64;
     let mut parse_time_ms = 0.0f64;
+    let mut hole_bytes = 0;
+    let mut failed_chunks = Vec::new();
+    let mut backing_data = Vec::new();
+
+    let boundaries = &boundaries;
+    control.with_serializers(|handoff| {
+        thread::scope(|scope| {
+            let mut handles = Vec::with_capacity(worker_threads);
+            for worker_idx in 0..worker_threads {
+                let dispatch = &dispatch;
+                let worker_core = pinning::core_for_worker(worker_idx);
+                handles.push(scope.spawn(move || {
+                    if let Some(core) = worker_core {
+                        let _ = core_affinity::set_for_current(core);
+                    }
+                    let mut local = Vec::new();
+                    let mut worker_totals = Totals::default();
+                    let mut worker_scan_ms = 0.0f64;
+                    let mut worker_parse_ms = 0.0f64;
+                    let mut worker_holes = 0;
+                    let mut worker_failed = Vec::new();
+                    let mut worker_backing = Vec::new();
+
+                    for (seq, chunk_idx) in dispatch.worker(worker_idx) {
+                        control.wait_turn(seq as u64);
+                        if control.should_stop() {
+                            break;
+                        }
+                        let (start, end) = (boundaries[chunk_idx], boundaries[chunk_idx + 1]);
+                        control.prefetch(base_offset, start, end);
+                        let (segments, skipped) = chunk_segments(data, start, end, control.reverse);
+                        worker_holes += skipped;
+                        for (start, end) in segments {
+                            let Some((mut batch, s_ms, p_ms, stripped)) = parse_isolated(|| {
+                                parse_segment(
+                                    data,
+                                    start,
+                                    end,
+                                    schema,
+                                    base_offset,
+                                    first_line(chunk_idx, start),
+                                    control,
+                                )
+                            }) else {
+                                worker_failed.push(FailedChunk {
+                                    start: base_offset + start as u64,
+                                    end: base_offset + end as u64,
+                                });
+                                continue;
+                            };
+                            batch.chunk_seq = seq as u64;
+                            worker_scan_ms += s_ms;
+                            worker_parse_ms += p_ms;
+                            let matched = control.visit(&batch);
+                            control.reject(&batch);
+                            worker_totals.add(&batch);
+                            let range = batch.time_range;
+                            let handoff = handoff.filter(|_| stripped.is_none());
+                            let kept = control.emit(handoff, batch, matched, seq as u64);
+                            if kept.is_some() {
+                                worker_backing.extend(stripped);
+                            }
+                            local.push((chunk_idx, range, kept));
+                        }
+                        control.chunk_done(seq as u64);
+                        control.advance(end - start);
+                        worker_totals.bytes += (end - start) as u64;
+                    }
+                    (
+                        local,
+                        worker_totals,
+                        worker_scan_ms,
+                        worker_parse_ms,
+                        worker_holes,
+                        worker_failed,
+                        worker_backing,
+                    )
+                }));
+            }
 
-    thread::scope(|scope| {
-        let mut handles = Vec::with_capacity(worker_threads);
-        for worker_chunks in assignments.into_iter() {
-            handles.push(scope.spawn(move || {
-                let mut local = Vec::with_capacity(worker_chunks.len());
-                let mut worker_scan_ms = 0.0f64;
-                let mut worker_parse_ms = 0.0f64;
-
-                for (chunk_idx, start, end) in worker_chunks {
-                    let (batch, s_ms, p_ms) =
-                        parse_structured_chunk(data, start, end, format, csv_header);
-                    worker_scan_ms += s_ms;
-                    worker_parse_ms += p_ms;
-                    local.push((chunk_idx, batch));
+            for handle in handles {
+                let (worker_results, w_totals, w_scan, w_parse, w_holes, w_failed, w_backing) =
+                    handle.join().expect("structured worker panicked");
+                totals.merge(&w_totals);
+                scan_time_ms = scan_time_ms.max(w_scan);
+                parse_time_ms = parse_time_ms.max(w_parse);
+                hole_bytes += w_holes;
+                failed_chunks.extend(w_failed);
+                backing_data.extend(w_backing);
+                for (chunk_idx, range, batch) in worker_results {
+                    ordered[chunk_idx].push((range, batch));
                 }
-                (local, worker_scan_ms, worker_parse_ms)
-            }));
-        }
-
-        for handle in handles {
-            let (worker_results, w_scan, w_parse) =
-                handle.join().expect("structured worker panicked");
-            scan_time_ms = scan_time_ms.max(w_scan);
-            parse_time_ms = parse_time_ms.max(w_parse);
-            for (chunk_idx, batch) in worker_results {
-                ordered_batches[chunk_idx] = Some(batch);
             }
-        }
+        })
     });
 
-    let mut batches = Vec::with_capacity(num_chunks);
-    let mut total_records = 0;
-    let mut total_fields = 0;
-    for batch in ordered_batches.into_iter().flatten() {
-        total_records += batch.len;
-        total_fields += batch.fields.len();
-        batches.push(batch);
+    // Each chunk's batches are already in parse order, newest-first when
+    // reversing, so only the chunks themselves need flipping.
+    if control.reverse {
+        ordered.reverse();
+    }
+    failed_chunks.sort_unstable_by_key(|chunk| chunk.start);
+    let mut batches = Vec::new();
+    let mut ranges = Vec::with_capacity(num_chunks);
+    for (range, batch) in ordered.into_iter().flatten() {
+        ranges.push(range);
+        batches.extend(batch);
     }
 
     StructuredPipelineResult {
         batches,
-        total_records,
-        total_fields,
+        total_records: totals.records,
+        total_fields: totals.fields,
+        distinct_keys: totals.keys.len(),
         scan_time_ms,
         parse_time_ms,
         format,
-        _backing_data: vec![],
+        level_summary: totals.levels,
+        time_range: merge_time_ranges(&ranges, control.reverse),
+        malformed_lines: totals.malformed,
+        guard: totals.guard,
+        parsed_bytes: totals.bytes,
+        hole_bytes,
+        failed_chunks,
+        _backing_data: backing_data,
+    }
+}
+
+/// Totals over every batch parsed, added up as batches come so that the
+/// result need not keep them (`MatchControl::retain_batches`).
+#[derive(Default)]
+struct Totals {
+    records: usize,
+    fields: usize,
+    levels: LevelSummary,
+    malformed: u64,
+    guard: GuardCounts,
+    keys: KeyTable,
+    /// Input bytes of the chunks parsed.
+    bytes: u64,
+}
+
+impl Totals {
+    fn add(&mut self, batch: &StructuredBatch) {
+        self.records += batch.len;
+        self.fields += batch.fields.len();
+        self.levels.merge(&batch.level_summary);
+        self.malformed += batch.malformed.len() as u64;
+        self.guard.merge(&batch.guard_counts());
+        self.keys.merge(&batch.keys);
+    }
+
+    fn merge(&mut self, other: &Totals) {
+        self.records += other.records;
+        self.fields += other.fields;
+        self.levels.merge(&other.levels);
+        self.malformed += other.malformed;
+        self.guard.merge(&other.guard);
+        self.keys.merge(&other.keys);
+        self.bytes += other.bytes;
+    }
+}
+
+/// Batches come back newest-first under `--reverse`; time ranges must be
+/// merged in file order.
+fn merge_time_ranges(ranges: &[TimeRange], reverse: bool) -> TimeRange {
+    let mut range = TimeRange::default();
+    let mut merge = |other: &TimeRange| range.merge(other);
+    if reverse {
+        ranges.iter().rev().for_each(&mut merge);
+    } else {
+        ranges.iter().for_each(&mut merge);
+    }
+    range
+}
+
+/// What every chunk of one input is parsed with.
+#[derive(Clone, Copy)]
+struct ChunkSchema<'a> {
+    format: LogFormat,
+    csv_header: Option<&'a CsvHeader>,
+    fixed: Option<&'a FixedLayout>,
+    firehose: bool,
+    tag_schemas: bool,
+    /// Keys given value columns, from [`sample_hot_keys`].
+    hot_keys: &'a [Box<[u8]>],
+}
+
+/// Input sampled to find the keys worth a value column, and to confirm a
+/// detected format.
+pub const SCHEMA_SAMPLE_BYTES: usize = 256 * 1024;
+
+/// Share of malformed lines at which a detected format is given up.
+const REDETECT_MALFORMED_SHARE: f64 = 0.5;
+
+/// Detection reads the first line or two, so a file can start like one
+/// format and go on as another. Parses the head of `data` as `format` and,
+/// when most of its lines come out malformed, as each other structured
+/// format, returning the one that fits best, or plain text when none fits;
+/// `None` keeps `format`.
+pub fn redetect_format(data: &[u8], format: LogFormat) -> Option<LogFormat> {
+    if !matches!(format, LogFormat::Json | LogFormat::Logfmt | LogFormat::Csv) {
+        return None;
+    }
+    let end = if data.len() <= SCHEMA_SAMPLE_BYTES {
+        data.len()
+    } else {
+        memchr::memrchr(simd_scan::record_sep(), &data[..SCHEMA_SAMPLE_BYTES])?
+    };
+    let head = &data[..end];
+    if malformed_share(head, format) < REDETECT_MALFORMED_SHARE {
+        return None;
+    }
+    let (best, share) = [LogFormat::Json, LogFormat::Logfmt, LogFormat::Csv]
+        .into_iter()
+        .filter(|&other| other != format)
+        .map(|other| (other, malformed_share(head, other)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+    Some(if share < REDETECT_MALFORMED_SHARE {
+        best
+    } else {
+        LogFormat::PlainText
+    })
+}
+
+/// Formats worth trying on `data`: each structured format that parses most
+/// lines of its head, then plain text, which takes any line.
+pub fn plausible_formats(data: &[u8]) -> Vec<LogFormat> {
+    let end = if data.len() <= SCHEMA_SAMPLE_BYTES {
+        data.len()
+    } else {
+        memchr::memrchr(simd_scan::record_sep(), &data[..SCHEMA_SAMPLE_BYTES])
+            .map_or(SCHEMA_SAMPLE_BYTES, |p| p + 1)
+    };
+    let head = &data[..end];
+    [LogFormat::Json, LogFormat::Logfmt, LogFormat::Csv]
+        .into_iter()
+        .filter(|&format| malformed_share(head, format) < REDETECT_MALFORMED_SHARE)
+        .chain([LogFormat::PlainText])
+        .collect()
+}
+
+/// Share of the lines of `head` that parse as malformed in `format`.
+fn malformed_share(head: &[u8], format: LogFormat) -> f64 {
+    let (csv_header, start) = if format == LogFormat::Csv {
+        // A single column takes any line, so it says nothing.
+        match CsvHeader::parse(head).filter(|header| header.num_columns() > 1) {
+            Some(header) => (Some(header), csv_parser::header_end_offset(head)),
+            None => return 1.0,
+        }
+    } else {
+        (None, 0)
+    };
+    let body = &head[start.min(head.len())..];
+    let sep = simd_scan::record_sep();
+    let lines = memchr::memchr_iter(sep, body).count() + usize::from(!body.ends_with(&[sep]));
+    if body.is_empty() {
+        return 0.0;
+    }
+    let schema = ChunkSchema {
+        format,
+        csv_header: csv_header.as_ref(),
+        fixed: None,
+        firehose: true,
+        tag_schemas: false,
+        hot_keys: &[],
+    };
+    // A line that yields no field fits the format no better than garbage.
+    let limits = RecordLimits {
+        empty: EmptyPolicy::Malformed,
+        ..RecordLimits::default()
+    };
+    let (batch, _, _) = parse_structured_chunk(body, 0, body.len(), schema, limits);
+    batch.malformed.len() as f64 / lines as f64
+}
+
+/// The `k` keys found most often in the records at the head of `data`,
+/// most frequent first.
+fn sample_hot_keys(data: &[u8], schema: ChunkSchema<'_>, k: usize) -> Vec<Box<[u8]>> {
+    if k == 0 || schema.firehose || data.is_empty() {
+        return Vec::new();
+    }
+    let end = if data.len() <= SCHEMA_SAMPLE_BYTES {
+        data.len()
+    } else {
+        memchr::memrchr(simd_scan::record_sep(), &data[..SCHEMA_SAMPLE_BYTES])
+            .map_or(SCHEMA_SAMPLE_BYTES, |p| p + 1)
+    };
+    let (sample, _, _) = parse_structured_chunk(data, 0, end, schema, RecordLimits::default());
+
+    let mut counts = vec![0usize; sample.keys.len()];
+    for field in &sample.fields {
+        counts[field.key_id as usize] += 1;
+    }
+    let mut ids: Vec<usize> = (0..counts.len()).collect();
+    ids.sort_by_key(|&id| std::cmp::Reverse(counts[id]));
+    ids.into_iter()
+        .take(k)
+        .map(|id| sample.keys.name(id as u32).into())
+        .collect()
+}
+
+/// Parses `data[start..end]`, which begins on line `first_line` when
+/// numbering, or a copy of it without ANSI escapes under `--strip-ansi`;
+/// the copy is returned so it outlives the batch.
+fn parse_segment(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    schema: ChunkSchema<'_>,
+    base_offset: u64,
+    first_line: Option<u64>,
+    control: &MatchControl<'_, StructuredBatch>,
+) -> (StructuredBatch, f64, f64, Option<Vec<u8>>) {
+    let strip_start = Instant::now();
+    let clean = control
+        .strip_ansi
+        .then(|| ansi::strip(&data[start..end]))
+        .flatten();
+    let strip_ms = strip_start.elapsed().as_secs_f64() * 1000.0;
+    let limits = control.record_limits;
+    match clean {
+        Some(clean) => {
+            let (mut batch, scan_ms, parse_ms) =
+                parse_structured_chunk_owned(&clean, schema, limits, 1);
+            batch.input_offset = base_offset + start as u64;
+            stamp(&mut batch, control, &clean, 0, first_line);
+            (batch, strip_ms + scan_ms, parse_ms, Some(clean))
+        }
+        None => {
+            let (mut batch, scan_ms, parse_ms) =
+                parse_structured_chunk(data, start, end, schema, limits);
+            batch.input_offset = base_offset;
+            stamp(&mut batch, control, data, start, first_line);
+            (batch, strip_ms + scan_ms, parse_ms, None)
+        }
+    }
+}
+
+/// Sets where `batch`, parsed from `data[start..]`, came from, as the plain
+/// orchestrator's `stamp` does.
+fn stamp(
+    batch: &mut StructuredBatch,
+    control: &MatchControl<'_, StructuredBatch>,
+    data: &[u8],
+    start: usize,
+    first_line: Option<u64>,
+) {
+    batch.file_id = control.file_id;
+    batch.rewritten = control.transcoded || control.strip_ansi;
+    if let Some(first_line) = first_line {
+        batch.line_numbers = number_lines(data, start, first_line, &batch.line_offsets);
     }
 }
 
@@ -369,18 +997,35 @@ fn parse_structured_chunk(
     data: &[u8],
     start: usize,
     end: usize,
-    format: LogFormat,
-    csv_header: Option<&CsvHeader>,
+    schema: ChunkSchema<'_>,
+    limits: RecordLimits,
 ) -> (StructuredBatch, f64, f64) {
+    let ChunkSchema {
+        format,
+        csv_header,
+        fixed,
+        firehose,
+        tag_schemas,
+        hot_keys,
+    } = schema;
     let chunk = &data[start..end];
     let data_len = data.len() as u64;
 
     let scan_start = Instant::now();
     let estimated = (chunk.len() / 80).max(16);
     let mut line_starts = Vec::with_capacity(estimated + 2);
-    line_starts.push(start as u64);
-    simd_scan::scan_region(chunk, start as u64, data_len, &mut line_starts);
-    line_starts.push(end as u64);
+    if !fixed.is_some_and(|layout| layout.record_starts(start, end, &mut line_starts)) {
+        line_starts.push(start as u64);
+        simd_scan::scan_region(chunk, start as u64, data_len, &mut line_starts);
+        // A chunk other than the last ends after a newline, which the scan
+        // already turned into the closing line start.
+        if line_starts.last() != Some(&(end as u64)) {
+            line_starts.push(end as u64);
+        }
+    }
+    if format == LogFormat::Csv {
+        csv_parser::join_quoted_lines(data, &mut line_starts);
+    }
     let scan_ms = scan_start.elapsed().as_secs_f64() * 1000.0;
 
     let num_lines = line_starts.len() - 1;
@@ -390,10 +1035,17 @@ fn parse_structured_chunk(
         LogFormat::Json => 8,
         LogFormat::Logfmt => 6,
         LogFormat::Csv => csv_header.map(|h| h.num_columns()).unwrap_or(4),
+        LogFormat::FixedWidth => fixed.map(|l| l.num_columns()).unwrap_or(4),
         LogFormat::PlainText => 4,
     };
     let mut batch =
         StructuredBatch::with_capacity(num_lines, num_lines * avg_fields, data.as_ptr());
+    batch.data_len = data.len();
+    batch.limits = limits;
+    batch.firehose = firehose;
+    batch.tag_schemas = tag_schemas;
+    batch.format = format;
+    batch.set_hot_keys(hot_keys);
 
     match format {
         LogFormat::Json => {
@@ -414,6 +1066,18 @@ fn parse_structured_chunk(
                 );
             }
         }
+        LogFormat::FixedWidth => {
+            if let Some(layout) = fixed {
+                fixed_parser::parse_fixed_lines_range(
+                    data,
+                    &line_starts,
+                    0,
+                    num_lines,
+                    layout,
+                    &mut batch,
+                );
+            }
+        }
     }
 
     let parse_ms = parse_start.elapsed().as_secs_f64() * 1000.0;
@@ -423,18 +1087,31 @@ fn parse_structured_chunk(
 
 fn parse_structured_chunk_owned(
     data: &[u8],
-    format: LogFormat,
-    csv_header: Option<&CsvHeader>,
+    schema: ChunkSchema<'_>,
+    limits: RecordLimits,
     _num_threads: usize,
 ) -> (StructuredBatch, f64, f64) {
+    let ChunkSchema {
+        format,
+        csv_header,
+        fixed,
+        firehose,
+        tag_schemas,
+        hot_keys,
+    } = schema;
     let data_len = data.len() as u64;
 
     let scan_start = Instant::now();
     let estimated = (data.len() / 80).max(16);
     let mut line_starts = Vec::with_capacity(estimated + 2);
-    line_starts.push(0u64);
-    simd_scan::scan_region(data, 0, data_len, &mut line_starts);
-    line_starts.push(data_len);
+    if !fixed.is_some_and(|layout| layout.record_starts(0, data.len(), &mut line_starts)) {
+        line_starts.push(0u64);
+        simd_scan::scan_region(data, 0, data_len, &mut line_starts);
+        line_starts.push(data_len);
+    }
+    if format == LogFormat::Csv {
+        csv_parser::join_quoted_lines(data, &mut line_starts);
+    }
     let scan_ms = scan_start.elapsed().as_secs_f64() * 1000.0;
 
     let num_lines = line_starts.len() - 1;
@@ -444,10 +1121,17 @@ fn parse_structured_chunk_owned(
         LogFormat::Json => 8,
         LogFormat::Logfmt => 6,
         LogFormat::Csv => csv_header.map(|h| h.num_columns()).unwrap_or(4),
+        LogFormat::FixedWidth => fixed.map(|l| l.num_columns()).unwrap_or(4),
         LogFormat::PlainText => 4,
     };
     let mut batch =
         StructuredBatch::with_capacity(num_lines, num_lines * avg_fields, data.as_ptr());
+    batch.data_len = data.len();
+    batch.limits = limits;
+    batch.firehose = firehose;
+    batch.tag_schemas = tag_schemas;
+    batch.format = format;
+    batch.set_hot_keys(hot_keys);
 
     match format {
         LogFormat::Json => {
@@ -468,6 +1152,18 @@ fn parse_structured_chunk_owned(
                 );
             }
         }
+        LogFormat::FixedWidth => {
+            if let Some(layout) = fixed {
+                fixed_parser::parse_fixed_lines_range(
+                    data,
+                    &line_starts,
+                    0,
+                    num_lines,
+                    layout,
+                    &mut batch,
+                );
+            }
+        }
     }
 
     let parse_ms = parse_start.elapsed().as_secs_f64() * 1000.0;
@@ -478,6 +1174,224 @@ fn parse_structured_chunk_owned(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::LogLevel;
+
+    #[test]
+    fn test_hot_keys_get_value_columns() {
+        use crate::emit::{EmitRecord, FieldValue};
+
+        let mut data = Vec::new();
+        for i in 0..50 {
+            data.extend_from_slice(
+                format!(
+                    "{{\"status\":{},\"path\":\"/a\",\"rare{}\":1}}\n",
+                    200 + i,
+                    i
+                )
+                .as_bytes(),
+            );
+        }
+        data.extend_from_slice(b"{\"path\":\"/b\"}\n");
+        let control = MatchControl {
+            hot_columns: 2,
+            ..MatchControl::default()
+        };
+        let result = parse_structured_mmap_with(&data, 1, Some(LogFormat::Json), &control);
+        let batch = &result.batches[0];
+
+        let names: Vec<&[u8]> = batch.columns.iter().map(|c| &*c.name).collect();
+        assert_eq!(names, [&b"path"[..], b"status"]);
+        assert_eq!(batch.columns[1].lens.len(), batch.len);
+        assert_eq!(batch.columns[1].get(50), None);
+
+        let mut status = None;
+        batch.visit_field(7, b"status", &mut |v| {
+            status = Some(v == FieldValue::Literal(b"207"))
+        });
+        assert_eq!(status, Some(true));
+        let mut rare = None;
+        batch.visit_field(7, b"rare7", &mut |v| {
+            rare = Some(v == FieldValue::Literal(b"1"))
+        });
+        assert_eq!(rare, Some(true));
+        assert_eq!(batch.field_count(7), 3);
+    }
+
+    #[test]
+    fn test_firehose_keeps_field_extents() {
+        let data = b"{\"level\":\"error\",\"ts\":\"2025-02-12T10:31:45Z\",\"msg\":\"a\"}\r\n\
+                     {\"level\":\"info\",\"msg\":\"b\"}\n";
+        let full = parse_structured_mmap(data, 1, Some(LogFormat::Json));
+        let control = MatchControl {
+            firehose: true,
+            ..MatchControl::default()
+        };
+        let fast = parse_structured_mmap_with(data, 1, Some(LogFormat::Json), &control);
+        let (full, fast) = (&full.batches[0], &fast.batches[0]);
+
+        let extents = |batch: &StructuredBatch| -> Vec<(u64, u32, u64, u32)> {
+            batch
+                .fields
+                .iter()
+                .map(|f| (f.key_offset, f.key_len, f.val_offset, f.val_len))
+                .collect()
+        };
+        assert_eq!(extents(fast), extents(full));
+        assert_eq!(fast.field_starts, full.field_starts);
+        assert_eq!(fast.line_lens, [full.line_lens[0] + 1, full.line_lens[1]]);
+        assert!(fast.columns.is_empty());
+        assert!(fast.levels.iter().all(|&l| l == LogLevel::Unknown));
+        assert!(fast.well_known.iter().all(|wk| wk.level == u32::MAX));
+        assert_eq!(full.levels[0], LogLevel::Error);
+    }
+
+    #[test]
+    fn test_redetect_format() {
+        let csv = b"ts,level,message\n\
+                    2025-02-12T10:31:45Z,info,a=1 b=2\n\
+                    2025-02-12T10:31:46Z,error,disk full\n\
+                    2025-02-12T10:31:47Z,info,ok\n";
+        // The header would pass for logfmt were it the whole first line.
+        assert_eq!(
+            redetect_format(csv, LogFormat::Logfmt),
+            Some(LogFormat::Csv)
+        );
+        assert_eq!(redetect_format(csv, LogFormat::Csv), None);
+
+        let plain = b"time=10:31:45 level=info starting\n\
+                      worker 1 ready\n\
+                      worker 2 ready\n";
+        assert_eq!(
+            redetect_format(plain, LogFormat::Logfmt),
+            Some(LogFormat::PlainText)
+        );
+
+        assert_eq!(
+            plausible_formats(csv),
+            [LogFormat::Csv, LogFormat::PlainText]
+        );
+        assert_eq!(plausible_formats(plain), [LogFormat::PlainText]);
+    }
+
+    #[test]
+    fn test_structured_malformed_lines_rejected() {
+        use std::sync::Mutex;
+
+        let data = b"{\"level\":\"info\",\"msg\":\"ok\"}\n\
+                     {\"level\":\"warn\",\"msg\":\"cut\n\
+                     not json at all\n\
+                     {\"level\":\"error\",\"msg\":\"ok\"}\n";
+        let seen = Mutex::new(Vec::new());
+        let on_reject =
+            |offset: u64, line: &[u8]| seen.lock().unwrap().push((offset, line.to_vec()));
+        let control = MatchControl {
+            on_reject: Some(&on_reject),
+            ..MatchControl::default()
+        };
+        let result = parse_structured_mmap_with(data, 1, Some(LogFormat::Json), &control);
+
+        assert_eq!(result.malformed_lines, 2);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (28, b"{\"level\":\"warn\",\"msg\":\"cut".to_vec()),
+                (55, b"not json at all".to_vec()),
+            ]
+        );
+
+        let csv =
+            b"timestamp,level,message\n2025-02-12T10:31:45Z,info,a\n2025-02-12T10:31:46Z,warn\n";
+        let result = parse_structured_mmap(csv, 1, Some(LogFormat::Csv));
+        assert_eq!(result.malformed_lines, 1);
+    }
+
+    #[test]
+    fn test_structured_record_limits() {
+        use crate::emit::{EmitRecord, FieldValue};
+        use crate::structured::GuardPolicy;
+
+        let data = b"{\"level\":\"info\",\"msg\":\"caf\xc3\xa9 latte\",\"a\":1,\"b\":2}\n\
+                     {\"level\":\"warn\",\"msg\":\"ok\"}\n\
+                     {\"level\":\"error\",\"msg\":\"x\\u00e9yz\"}\n";
+        let parse = |policy| {
+            let control = MatchControl {
+                record_limits: RecordLimits {
+                    max_fields: 3,
+                    max_value_len: 4,
+                    policy,
+                    ..RecordLimits::default()
+                },
+                ..MatchControl::default()
+            };
+            parse_structured_mmap_with(data, 1, Some(LogFormat::Json), &control)
+        };
+
+        let result = parse(GuardPolicy::Truncate);
+        let batch = &result.batches[0];
+        assert_eq!(result.total_records, 3);
+        assert_eq!(result.malformed_lines, 0);
+        assert_eq!(batch.guard.truncated, 2);
+        assert_eq!(batch.field_count(0), 3);
+        let mut msg = None;
+        batch.for_each_field(0, &mut |key, value| {
+            if key == b"msg" {
+                msg = Some(value == FieldValue::Escaped(b"caf"));
+            }
+        });
+        assert_eq!(msg, Some(true));
+        assert_eq!(unsafe { batch.message_value(2) }, Some("x"));
+
+        let result = parse(GuardPolicy::Drop);
+        assert_eq!(result.total_records, 1);
+        assert_eq!(result.malformed_lines, 0);
+        assert_eq!(result.level_summary.count(LogLevel::Warn), 1);
+        assert_eq!(result.batches[0].guard_counts().first_error, None);
+
+        let result = parse(GuardPolicy::Error);
+        assert_eq!(result.batches[0].guard_counts().dropped, 2);
+        assert_eq!(result.batches[0].guard_counts().first_error, Some(0));
+    }
+
+    #[test]
+    fn test_structured_empty_records() {
+        use crate::structured::EmptyPolicy;
+
+        let json = b"{\"level\":\"info\"}\n{}\n{ }\n{\"level\":\"warn\"}\n";
+        let logfmt = b"level=info msg=a\njust some words\nlevel=warn\n";
+        let parse = |data: &[u8], format, empty| {
+            let control = MatchControl {
+                record_limits: RecordLimits {
+                    empty,
+                    ..RecordLimits::default()
+                },
+                ..MatchControl::default()
+            };
+            parse_structured_mmap_with(data, 1, Some(format), &control)
+        };
+
+        let result = parse(json, LogFormat::Json, EmptyPolicy::Keep);
+        assert_eq!(result.total_records, 4);
+        assert_eq!(result.malformed_lines, 0);
+        assert_eq!(result.guard.empty, 2);
+        assert_eq!(result.batches[0].field_count(1), 0);
+
+        let result = parse(json, LogFormat::Json, EmptyPolicy::Drop);
+        assert_eq!(result.total_records, 2);
+        assert_eq!(result.malformed_lines, 0);
+        assert_eq!(result.guard.empty, 2);
+        assert_eq!(result.level_summary.count(LogLevel::Warn), 1);
+
+        let result = parse(json, LogFormat::Json, EmptyPolicy::Malformed);
+        assert_eq!(result.total_records, 2);
+        assert_eq!(result.malformed_lines, 2);
+        assert_eq!(result.guard.empty, 2);
+
+        let result = parse(logfmt, LogFormat::Logfmt, EmptyPolicy::Keep);
+        assert_eq!((result.total_records, result.total_fields), (3, 3));
+        assert_eq!(result.guard.empty, 1);
+        let result = parse(logfmt, LogFormat::Logfmt, EmptyPolicy::Malformed);
+        assert_eq!((result.total_records, result.malformed_lines), (2, 1));
+    }
 
     #[test]
     fn test_structured_json_mmap() {
@@ -488,6 +1402,8 @@ mod tests {
         let result = parse_structured_mmap(data, 1, Some(LogFormat::Json));
         assert_eq!(result.format, LogFormat::Json);
         assert_eq!(result.total_records, 3);
+        assert_eq!(result.level_summary.count(LogLevel::Error), 1);
+        assert_eq!(result.time_range.span_secs(), 2);
         assert!(result.total_fields >= 9);
 
         unsafe {
@@ -498,6 +1414,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_structured_syslog_levels() {
+        let data = br#"{"PRIORITY":"3","MESSAGE":"disk failed"}
+{"pri":"34","msg":"su failed"}
+{"pri":"14","msg":"ok"}
+{"level":50,"msg":"pino error, not a PRI"}
+"#;
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Json));
+        let levels = &result.batches[0].levels;
+        assert_eq!(
+            levels[..],
+            [
+                LogLevel::Error,
+                LogLevel::Fatal,
+                LogLevel::Info,
+                LogLevel::Unknown
+            ]
+        );
+    }
+
     #[test]
     fn test_structured_logfmt_mmap() {
         let data = b"level=info msg=started ts=2025-02-12\nlevel=warn msg=slow ts=2025-02-13\n";
@@ -535,6 +1471,111 @@ mod tests {
         assert_eq!(result.total_records, 0);
     }
 
+    #[test]
+    fn test_structured_match_callback() {
+        use crate::filter::{LevelFilter, MatchLimit};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let data = br#"{"level":"info","msg":"a"}
+{"level":"error","msg":"b"}
+{"level":"error","msg":"c"}
+{"level":"fatal","msg":"d"}
+"#;
+        let hits = AtomicUsize::new(0);
+        let on_match = |batch: &StructuredBatch, i: usize| {
+            assert_ne!(batch.levels[i], LogLevel::Info);
+            hits.fetch_add(1, Ordering::Relaxed);
+        };
+        let control = MatchControl {
+            level: Some(LevelFilter::AtLeast(LogLevel::Error)),
+            since: None,
+            index: None,
+            predicate: None,
+            sample: None,
+            record_limits: RecordLimits::default(),
+            readahead: None,
+            strip_ansi: false,
+            transcoded: false,
+            hot_columns: 0,
+            fixed_layout: None,
+            firehose: false,
+            tag_schemas: false,
+            file_id: 0,
+            line_numbers: false,
+            progress: None,
+            ordered: None,
+            retain_batches: true,
+            limit: MatchLimit::new(2),
+            on_match: Some(&on_match),
+            on_batch: None,
+            on_emit: None,
+            serialize_threads: 0,
+            on_reject: None,
+            reverse: false,
+        };
+        parse_structured_mmap_with(data, 1, Some(LogFormat::Json), &control);
+
+        assert_eq!(hits.load(Ordering::Relaxed), 2);
+        assert!(control.should_stop());
+    }
+
+    #[test]
+    fn test_unretained_batches_still_counted() {
+        let line = b"{\"level\":\"error\",\"msg\":\"disk full\",\"host\":\"db-1\"}\n";
+        let data = line.repeat(3 * 1024 * 1024 / line.len());
+        let records = data.len() / line.len();
+        for threads in [1, 4] {
+            // A limit ramps chunks up from 1 MB, so the input spans several.
+            let control = MatchControl {
+                retain_batches: false,
+                limit: crate::filter::MatchLimit::new(u64::MAX - 1),
+                ..MatchControl::default()
+            };
+            let result =
+                parse_structured_mmap_with(&data, threads, Some(LogFormat::Json), &control);
+            assert!(result.batches.iter().all(|b| b.chunk_seq == 0));
+            assert!(result.batches.iter().map(|b| b.len).sum::<usize>() < records);
+            assert_eq!(result.total_records, records);
+            assert_eq!(result.total_fields, records * 3);
+            assert_eq!(result.distinct_keys, 3);
+            assert_eq!(result.level_summary.count(LogLevel::Error), records as u64);
+            assert_eq!(result.parsed_bytes, data.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_unretained_batches_serialized_off_parse_threads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let line = b"{\"level\":\"error\",\"msg\":\"disk full\",\"host\":\"db-1\"}\n";
+        let data = line.repeat(3 * 1024 * 1024 / line.len());
+        let records = data.len() / line.len();
+        let caller = std::thread::current().id();
+        for threads in [1, 4] {
+            let emitted = AtomicUsize::new(0);
+            let off_caller = AtomicUsize::new(0);
+            let serialize = |batch: &StructuredBatch, matched: &[u32]| {
+                assert!(matched.iter().all(|&i| (i as usize) < batch.len));
+                emitted.fetch_add(matched.len(), Ordering::Relaxed);
+                if std::thread::current().id() != caller {
+                    off_caller.fetch_add(matched.len(), Ordering::Relaxed);
+                }
+            };
+            let control = MatchControl {
+                retain_batches: false,
+                limit: crate::filter::MatchLimit::new(u64::MAX - 1),
+                on_emit: Some(&serialize),
+                serialize_threads: 2,
+                ..MatchControl::default()
+            };
+            let result =
+                parse_structured_mmap_with(&data, threads, Some(LogFormat::Json), &control);
+            assert_eq!(result.total_records, records);
+            assert_eq!(emitted.load(Ordering::Relaxed), records);
+            assert!(off_caller.load(Ordering::Relaxed) > 0);
+        }
+    }
+
     #[test]
     fn test_structured_json_multithreaded() {
         let mut data = Vec::new();
@@ -0,0 +1,183 @@
+use crate::data::LogLevel;
+use crate::tz::{civil_from_days, days_from_civil};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+const SEVERITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// A syslog PRI value, `facility * 8 + severity`, as in `<34>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pri {
+    pub facility: u8,
+    pub severity: u8,
+}
+
+impl Pri {
+    /// The `<PRI>` opening a syslog line, and the length of that prefix.
+    #[inline]
+    pub fn prefix(line: &[u8]) -> Option<(Pri, usize)> {
+        if line.first() != Some(&b'<') {
+            return None;
+        }
+        let close = line.iter().take(5).position(|&b| b == b'>')?;
+        Pri::from_value(&line[1..close]).map(|pri| (pri, close + 1))
+    }
+
+    /// A bare PRI value such as `34`: one to three digits without leading
+    /// zeros, at most 191.
+    pub fn from_value(value: &[u8]) -> Option<Pri> {
+        if value.is_empty()
+            || value.len() > 3
+            || !value.iter().all(u8::is_ascii_digit)
+            || (value[0] == b'0' && value.len() > 1)
+        {
+            return None;
+        }
+        let n = value
+            .iter()
+            .fold(0u16, |n, &d| n * 10 + u16::from(d - b'0'));
+        (n <= 191).then_some(Pri {
+            facility: (n / 8) as u8,
+            severity: (n % 8) as u8,
+        })
+    }
+
+    #[inline]
+    pub fn level(self) -> LogLevel {
+        LogLevel::from_syslog_severity(self.severity)
+    }
+
+    pub fn facility_name(self) -> &'static str {
+        FACILITIES[self.facility as usize]
+    }
+
+    pub fn severity_name(self) -> &'static str {
+        SEVERITIES[self.severity as usize]
+    }
+}
+
+static YEAR_REFERENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Epoch seconds that RFC 3164 timestamps, which have no year, are dated
+/// against: the input's modification time, or 0 for the current time.
+/// Call it before parsing each input.
+pub fn set_year_reference(secs: u64) {
+    YEAR_REFERENCE.store(secs, Ordering::Relaxed);
+}
+
+/// Epoch seconds of an RFC 3164 `Mmm dd hh:mm:ss` timestamp, `month`
+/// counting from 1. It takes the reference's year, or the one before when
+/// that would put it more than a day after the reference.
+pub fn rfc3164_timestamp(month: u32, day: &[u8], time: &[u8]) -> Option<u64> {
+    let number = |digits: &[u8]| {
+        (!digits.is_empty() && digits.len() <= 2 && digits.iter().all(u8::is_ascii_digit)).then(
+            || {
+                digits
+                    .iter()
+                    .fold(0i64, |n, &d| n * 10 + i64::from(d - b'0'))
+            },
+        )
+    };
+    let day = number(day).filter(|day| (1..=31).contains(day))?;
+    let [h1, h2, b':', m1, m2, b':', s1, s2] = *time else {
+        return None;
+    };
+    let (hour, min, sec) = (number(&[h1, h2])?, number(&[m1, m2])?, number(&[s1, s2])?);
+    if hour > 23 || min > 59 || sec > 60 {
+        return None;
+    }
+    let reference = match YEAR_REFERENCE.load(Ordering::Relaxed) {
+        0 => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        secs => secs,
+    } as i64;
+    let at = |year: i64| {
+        days_from_civil(year, i64::from(month), day) * 86400 + hour * 3600 + min * 60 + sec
+    };
+    let year = civil_from_days(reference.div_euclid(86400)).0;
+    let mut ts = at(year);
+    if ts > reference + 86400 {
+        ts = at(year - 1);
+    }
+    u64::try_from(ts).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pri_prefix() {
+        let (pri, len) = Pri::prefix(b"<34>1 2025-02-12T10:31:45Z host su - - - failed").unwrap();
+        assert_eq!((pri.facility, pri.severity, len), (4, 2, 4));
+        assert_eq!(pri.facility_name(), "auth");
+        assert_eq!(pri.severity_name(), "crit");
+        assert_eq!(pri.level(), LogLevel::Fatal);
+
+        let (pri, len) = Pri::prefix(b"<191>x").unwrap();
+        assert_eq!(
+            (pri.facility_name(), pri.level(), len),
+            ("local7", LogLevel::Debug, 5)
+        );
+        assert_eq!(Pri::prefix(b"<0>").unwrap().0.severity_name(), "emerg");
+
+        assert_eq!(Pri::prefix(b"<192>x"), None);
+        assert_eq!(Pri::prefix(b"<034>x"), None);
+        assert_eq!(Pri::prefix(b"<>x"), None);
+        assert_eq!(Pri::prefix(b"<html>"), None);
+        assert_eq!(Pri::prefix(b"34>"), None);
+        assert_eq!(
+            Pri::from_value(b"11").map(Pri::level),
+            Some(LogLevel::Error)
+        );
+    }
+
+    #[test]
+    fn test_rfc3164_year() {
+        // 2025-03-01T00:00:00Z
+        set_year_reference(1_740_787_200);
+        assert_eq!(
+            rfc3164_timestamp(2, b"12", b"10:31:45"),
+            Some(1_739_356_305)
+        );
+        // December is still last year's.
+        assert_eq!(
+            rfc3164_timestamp(12, b"31", b"23:59:59"),
+            Some(1_735_689_599)
+        );
+        assert_eq!(rfc3164_timestamp(2, b"12", b"10:31"), None);
+        assert_eq!(rfc3164_timestamp(2, b"0", b"10:31:45"), None);
+        assert_eq!(rfc3164_timestamp(2, b"1x", b"10:31:45"), None);
+        assert_eq!(rfc3164_timestamp(2, b"12", b"25:31:45"), None);
+    }
+}
@@ -0,0 +1,371 @@
+use crate::data::LogLevel;
+use crate::emit::{EmitRecord, FieldValue, write_rfc3339};
+use crate::structured::well_known::{self, WellKnownKind};
+use memchr::memmem::Finder;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+
+/// Records listed unless `--triage-top` says otherwise.
+pub const DEFAULT_TOP: usize = 20;
+
+/// Words that point at a failure, matched case-insensitively at the start
+/// of a word, with the score each adds.
+const KEYWORDS: &[(&str, u32)] = &[
+    ("panic", 40),
+    ("segfault", 40),
+    ("sigsegv", 40),
+    ("out of memory", 35),
+    ("oom", 35),
+    ("deadlock", 35),
+    ("crash", 30),
+    ("corrupt", 30),
+    ("fatal", 25),
+    ("abort", 25),
+    ("killed", 20),
+    ("exhausted", 20),
+    ("timeout", 20),
+    ("timed out", 20),
+    ("exception", 20),
+    ("traceback", 20),
+    ("stack trace", 20),
+    ("unavailable", 15),
+    ("refused", 15),
+    ("failed", 10),
+    ("failure", 10),
+    ("denied", 10),
+];
+
+/// Bytes of a record or context line kept for the report.
+const MAX_LINE: usize = 240;
+
+/// Most the burst bonus adds; it grows with the log of the error count in
+/// the record's minute.
+const MAX_BURST_BONUS: u32 = 25;
+
+/// Candidates kept per listed record, so the burst bonus added at report
+/// time can still reorder them.
+const CANDIDATES_PER_TOP: usize = 4;
+
+fn level_weight(level: LogLevel) -> (u32, &'static str) {
+    match level {
+        LogLevel::Fatal => (60, "level fatal"),
+        LogLevel::Error => (40, "level error"),
+        LogLevel::Warn => (15, "level warn"),
+        _ => (0, ""),
+    }
+}
+
+fn burst_bonus(errors: u64) -> u32 {
+    if errors < 2 {
+        return 0;
+    }
+    (5 * (u64::BITS - errors.leading_zeros() - 1)).min(MAX_BURST_BONUS)
+}
+
+fn clip(line: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&line[..line.len().min(MAX_LINE)]);
+    let text = text.trim_end_matches(['\r', '\n']);
+    if line.len() > MAX_LINE {
+        format!("{}...", text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Candidate {
+    score: u32,
+    offset: u64,
+    timestamp: Option<u64>,
+    reasons: Vec<&'static str>,
+    line: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score
+            .cmp(&other.score)
+            .then(other.offset.cmp(&self.offset))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    scored: u64,
+    /// Lowest-scoring candidate on top.
+    candidates: BinaryHeap<Reverse<Candidate>>,
+    /// Error-or-worse records per minute of record time.
+    errors_per_minute: HashMap<u64, u64>,
+}
+
+/// `--triage`: scores matched records by level, failure keywords and
+/// whether they fall in a burst of errors, and keeps the highest scoring
+/// ones with the lines around them.
+pub struct Triage {
+    top: usize,
+    finders: Vec<(Finder<'static>, &'static str, u32)>,
+    state: Mutex<State>,
+}
+
+impl Triage {
+    pub fn new(top: usize) -> Self {
+        Triage {
+            top,
+            finders: KEYWORDS
+                .iter()
+                .map(|&(word, score)| (Finder::new(word.as_bytes()), word, score))
+                .collect(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Level and keyword score of record `i`, with what contributed.
+    /// Keywords are looked for in field values other than the level and
+    /// timestamp, so `level=fatal` or a `timeout_ms` key do not count.
+    fn score<B: EmitRecord>(
+        &self,
+        batch: &B,
+        i: usize,
+        lower: &mut Vec<u8>,
+    ) -> (u32, Vec<&'static str>) {
+        let (mut score, level) = level_weight(batch.record_level(i));
+        let mut reasons = Vec::new();
+        if score > 0 {
+            reasons.push(level);
+        }
+        lower.clear();
+        batch.for_each_field(i, &mut |key, value| {
+            let value = match value {
+                FieldValue::Text(v) | FieldValue::Escaped(v) | FieldValue::Literal(v) => v,
+                FieldValue::Timestamp(_) => return,
+            };
+            if matches!(
+                well_known::classify_key(key),
+                WellKnownKind::Level | WellKnownKind::Timestamp
+            ) {
+                return;
+            }
+            lower.extend(value.iter().map(u8::to_ascii_lowercase));
+            lower.push(b' ');
+        });
+        for (finder, word, weight) in &self.finders {
+            let at_word_start = finder
+                .find_iter(lower)
+                .any(|pos| pos == 0 || !lower[pos - 1].is_ascii_alphanumeric());
+            if at_word_start {
+                score += weight;
+                reasons.push(word);
+            }
+        }
+        (score, reasons)
+    }
+
+    pub fn add_records<B: EmitRecord>(&self, batch: &B, records: &[u32]) {
+        let keep = self.top * CANDIDATES_PER_TOP;
+        let mut lower = Vec::new();
+        let mut local: Vec<Candidate> = Vec::new();
+        let mut errors_per_minute: HashMap<u64, u64> = HashMap::new();
+        for &i in records {
+            let i = i as usize;
+            let level = batch.record_level(i);
+            let timestamp = batch.record_timestamp(i);
+            if matches!(level, LogLevel::Error | LogLevel::Fatal)
+                && let Some(ts) = timestamp
+            {
+                *errors_per_minute.entry(ts - ts % 60).or_default() += 1;
+            }
+            let (score, reasons) = self.score(batch, i, &mut lower);
+            if score == 0 {
+                continue;
+            }
+            let context = |j: usize| (j < batch.record_count()).then(|| clip(batch.record_raw(j)));
+            local.push(Candidate {
+                score,
+                offset: batch.input_offset() + batch.record_line(i).offset,
+                timestamp,
+                reasons,
+                line: clip(batch.record_raw(i)),
+                before: i.checked_sub(1).and_then(context),
+                after: context(i + 1),
+            });
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.scored += records.len() as u64;
+        for (minute, count) in errors_per_minute {
+            *state.errors_per_minute.entry(minute).or_default() += count;
+        }
+        for candidate in local {
+            if state.candidates.len() < keep {
+                state.candidates.push(Reverse(candidate));
+            } else if state
+                .candidates
+                .peek()
+                .is_some_and(|Reverse(lowest)| candidate > *lowest)
+            {
+                state.candidates.pop();
+                state.candidates.push(Reverse(candidate));
+            }
+        }
+    }
+
+    pub fn report(&self) -> TriageReport {
+        let state = self.state.lock().unwrap();
+        let mut records: Vec<TriageRecord> = state
+            .candidates
+            .iter()
+            .map(|Reverse(c)| {
+                let burst = c
+                    .timestamp
+                    .and_then(|ts| state.errors_per_minute.get(&(ts - ts % 60)))
+                    .copied()
+                    .unwrap_or(0);
+                let bonus = burst_bonus(burst);
+                TriageRecord {
+                    score: c.score + bonus,
+                    offset: c.offset,
+                    timestamp: c.timestamp,
+                    reasons: c.reasons.clone(),
+                    burst: (bonus > 0).then_some(burst),
+                    line: c.line.clone(),
+                    before: c.before.clone(),
+                    after: c.after.clone(),
+                }
+            })
+            .collect();
+        records.sort_by(|a, b| b.score.cmp(&a.score).then(a.offset.cmp(&b.offset)));
+        records.truncate(self.top);
+        TriageReport {
+            scored: state.scored,
+            records,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriageRecord {
+    pub score: u32,
+    /// Input offset of the record.
+    pub offset: u64,
+    pub timestamp: Option<u64>,
+    pub reasons: Vec<&'static str>,
+    /// Errors in the record's minute, when they earned a burst bonus.
+    pub burst: Option<u64>,
+    pub line: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct TriageReport {
+    pub scored: u64,
+    /// Highest score first.
+    pub records: Vec<TriageRecord>,
+}
+
+impl fmt::Display for TriageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.records.is_empty() {
+            return writeln!(f, "Triage: nothing suspicious in {} records", self.scored);
+        }
+        writeln!(
+            f,
+            "Triage: top {} of {} records by score",
+            self.records.len(),
+            self.scored
+        )?;
+        for (rank, record) in self.records.iter().enumerate() {
+            let mut time = Vec::new();
+            match record.timestamp {
+                Some(ts) => write_rfc3339(ts, &mut time),
+                None => time.push(b'-'),
+            }
+            let mut reasons = record.reasons.join(", ");
+            if let Some(burst) = record.burst {
+                reasons.push_str(&format!(", burst {}/min", burst));
+            }
+            writeln!(
+                f,
+                "  #{:<3} score {:>3}  @{}  {}  [{}]",
+                rank + 1,
+                record.score,
+                record.offset,
+                String::from_utf8_lossy(&time),
+                reasons
+            )?;
+            if let Some(before) = &record.before {
+                writeln!(f, "        | {}", before)?;
+            }
+            writeln!(f, "        > {}", record.line)?;
+            if let Some(after) = &record.after {
+                writeln!(f, "        | {}", after)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchControl;
+    use crate::format::LogFormat;
+    use crate::structured::StructuredBatch;
+    use crate::structured_orchestrator::parse_structured_mmap_with;
+
+    #[test]
+    fn test_triage_ranks_failures() {
+        let mut data = String::new();
+        for n in 0..50 {
+            data.push_str(&format!(
+                "ts=2025-02-12T10:00:{:02}Z level=info msg=\"served request {}\"\n",
+                n, n
+            ));
+        }
+        data.push_str("ts=2025-02-12T10:01:00Z level=warn msg=\"slow query in room 4\"\n");
+        data.push_str("ts=2025-02-12T10:01:01Z level=error msg=\"upstream timed out\"\n");
+        data.push_str("ts=2025-02-12T10:01:02Z level=error msg=\"connection refused\"\n");
+        data.push_str("ts=2025-02-12T10:01:03Z level=fatal msg=\"panic: OOMKilled\"\n");
+        data.push_str("ts=2025-02-12T10:01:04Z level=info msg=\"restarting\"\n");
+
+        let triage = Triage::new(3);
+        let add = |batch: &StructuredBatch, records: &[u32]| triage.add_records(batch, records);
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_mmap_with(data.as_bytes(), 1, Some(LogFormat::Logfmt), &control);
+
+        let report = triage.report();
+        assert_eq!(report.scored, 55);
+        assert_eq!(report.records.len(), 3);
+        let top = &report.records[0];
+        assert_eq!(top.reasons, ["level fatal", "panic", "oom"]);
+        assert_eq!(top.burst, Some(3));
+        assert!(top.line.contains("OOMKilled"));
+        assert!(
+            top.before
+                .as_deref()
+                .unwrap()
+                .contains("connection refused")
+        );
+        assert!(top.after.as_deref().unwrap().contains("restarting"));
+        assert!(report.records[1].line.contains("timed out"));
+        // "room" does not count as "oom".
+        assert!(report.records.iter().all(|r| !r.line.contains("room")));
+
+        let text = report.to_string();
+        assert!(text.starts_with("Triage: top 3 of 55 records"), "{}", text);
+        assert!(text.contains("burst 3/min"), "{}", text);
+    }
+}
@@ -0,0 +1,447 @@
+use std::path::PathBuf;
+
+/// Last second of 9999-12-31; offsets past it are taken from then, keeping
+/// the calendar arithmetic in range.
+const MAX_TS: i64 = 253_402_300_799;
+
+/// A time zone for aligning time buckets to local clocks (`--tz`): UTC, a
+/// fixed offset such as `+05:30`, or a tz database name such as
+/// `America/New_York` read from `$TZDIR` (default `/usr/share/zoneinfo`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeZone {
+    /// Instants at which the offset changes, ascending, each with the offset
+    /// in seconds east of UTC from then on.
+    transitions: Vec<(i64, i32)>,
+    /// Offset before the first transition.
+    initial: i32,
+    /// Rule for instants after the last transition.
+    rule: Option<Rule>,
+}
+
+impl TimeZone {
+    pub fn utc() -> TimeZone {
+        TimeZone::default()
+    }
+
+    pub fn parse(name: &str) -> Result<TimeZone, String> {
+        if matches!(name, "UTC" | "utc" | "Z") {
+            return Ok(TimeZone::utc());
+        }
+        if let Some(offset) = parse_fixed_offset(name) {
+            return Ok(TimeZone {
+                initial: offset,
+                ..TimeZone::default()
+            });
+        }
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|p| p == "..") {
+            return Err(format!("unknown time zone '{}'", name));
+        }
+        let dir = std::env::var_os("TZDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/usr/share/zoneinfo"));
+        let data = std::fs::read(dir.join(name))
+            .map_err(|e| format!("unknown time zone '{}': {}", name, e))?;
+        TimeZone::from_tzif(&data).ok_or_else(|| format!("'{}' is not a TZif file", name))
+    }
+
+    pub fn is_utc(&self) -> bool {
+        *self == TimeZone::utc()
+    }
+
+    /// Seconds east of UTC in effect at `ts`.
+    pub fn offset_at(&self, ts: u64) -> i64 {
+        let ts = i64::try_from(ts).unwrap_or(i64::MAX).min(MAX_TS);
+        let after = self.transitions.partition_point(|&(at, _)| at <= ts);
+        let offset = match (after, &self.rule) {
+            (0, None) => self.initial,
+            (0, Some(rule)) if self.transitions.is_empty() => rule.offset_at(ts),
+            (0, Some(_)) => self.initial,
+            (n, Some(rule)) if n == self.transitions.len() => rule.offset_at(ts),
+            (n, _) => self.transitions[n - 1].1,
+        };
+        i64::from(offset)
+    }
+
+    /// `ts` as read off a wall clock in this zone, in seconds since the
+    /// epoch.
+    pub fn local(&self, ts: u64) -> u64 {
+        ts.saturating_add_signed(self.offset_at(ts))
+    }
+
+    /// Start of the `step`-second bucket holding `ts`, with buckets counted
+    /// on local clocks: a day step starts at local midnight.
+    /// On a day the clocks change, the bucket start is converted back with
+    /// the offset in force then rather than at `ts`.
+    pub fn floor(&self, ts: u64, step: u64) -> u64 {
+        let offset = self.offset_at(ts);
+        let local_start = self.local(ts) / step * step;
+        let guess = local_start.saturating_add_signed(-offset);
+        let then = self.offset_at(guess);
+        let start = local_start.saturating_add_signed(-then);
+        if start <= ts && self.offset_at(start) == then {
+            start
+        } else {
+            guess
+        }
+    }
+
+    /// Parses a TZif file (RFC 8536), preferring its 64-bit data and
+    /// footer rule when present.
+    fn from_tzif(data: &[u8]) -> Option<TimeZone> {
+        let (tz, v1_len) = tzif_block(data, 4)?;
+        if data[4] < b'2' {
+            return Some(tz);
+        }
+        let rest = &data[v1_len..];
+        let (mut tz, len) = tzif_block(rest, 8)?;
+        let footer = rest[len..].strip_prefix(b"\n")?;
+        let end = memchr::memchr(b'\n', footer)?;
+        if end > 0 {
+            tz.rule = Some(Rule::parse(std::str::from_utf8(&footer[..end]).ok()?)?);
+        }
+        Some(tz)
+    }
+}
+
+/// `+05:30`, `-08:00`, `+0530` or `-8`: hours and optional minutes east of
+/// UTC.
+fn parse_fixed_offset(name: &str) -> Option<i32> {
+    let sign = match name.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = &name[1..];
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    let hours: i32 = hours.parse().ok().filter(|h| (0..=14).contains(h))?;
+    let minutes: i32 = minutes.parse().ok().filter(|m| (0..60).contains(m))?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The transitions of the TZif header and data block at the start of
+/// `data`, whose transition times are `time_size` bytes, plus the length of
+/// header and block.
+fn tzif_block(data: &[u8], time_size: usize) -> Option<(TimeZone, usize)> {
+    if data.len() < 44 || &data[..4] != b"TZif" {
+        return None;
+    }
+    let count = |k: usize| u32::from_be_bytes(data[20 + 4 * k..24 + 4 * k].try_into().unwrap());
+    let [isut, isstd, leap, times, types, chars] = [0, 1, 2, 3, 4, 5].map(|k| count(k) as usize);
+    let block = &data[44..];
+    let len = times * (time_size + 1) + types * 6 + chars + leap * (time_size + 4) + isstd + isut;
+    if block.len() < len {
+        return None;
+    }
+
+    let type_base = times * (time_size + 1);
+    let offset = |t: usize| {
+        let at = type_base + 6 * t;
+        i32::from_be_bytes(block[at..at + 4].try_into().unwrap())
+    };
+    let mut transitions = Vec::with_capacity(times);
+    for k in 0..times {
+        let raw = &block[k * time_size..(k + 1) * time_size];
+        let at = match time_size {
+            4 => i64::from(i32::from_be_bytes(raw.try_into().unwrap())),
+            _ => i64::from_be_bytes(raw.try_into().unwrap()),
+        };
+        let t = block[times * time_size + k] as usize;
+        if t >= types {
+            return None;
+        }
+        transitions.push((at, offset(t)));
+    }
+    let tz = TimeZone {
+        transitions,
+        initial: if types > 0 { offset(0) } else { 0 },
+        rule: None,
+    };
+    Some((tz, 44 + len))
+}
+
+/// A POSIX TZ rule such as `EST5EDT,M3.2.0,M11.1.0`, which TZif files
+/// carry for instants past their last transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    /// Standard offset, seconds east of UTC.
+    std: i32,
+    dst: Option<Dst>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Dst {
+    offset: i32,
+    /// Switch to daylight time, in local standard time.
+    start: (RuleDay, i32),
+    /// Switch back, in local daylight time.
+    end: (RuleDay, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDay {
+    /// `Jn`: day 1-365, never counting February 29.
+    Julian(u16),
+    /// `n`: day 0-365, counting February 29.
+    Zero(u16),
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` (5 = last) of month
+    /// `m`.
+    Month(u8, u8, u8),
+}
+
+impl Rule {
+    fn parse(s: &str) -> Option<Rule> {
+        let mut rest = s;
+        skip_name(&mut rest)?;
+        // POSIX offsets count hours west of UTC.
+        let std = -parse_time(&mut rest)?;
+        if rest.is_empty() {
+            return Some(Rule { std, dst: None });
+        }
+        skip_name(&mut rest)?;
+        let offset = match rest.as_bytes().first() {
+            Some(b',') | None => std + 3600,
+            _ => -parse_time(&mut rest)?,
+        };
+        let day_time = |rest: &mut &str| -> Option<(RuleDay, i32)> {
+            *rest = rest.strip_prefix(',')?;
+            let day = parse_day(rest)?;
+            let time = match rest.strip_prefix('/') {
+                Some(after) => {
+                    *rest = after;
+                    parse_time(rest)?
+                }
+                None => 7200,
+            };
+            Some((day, time))
+        };
+        let start = day_time(&mut rest)?;
+        let end = day_time(&mut rest)?;
+        rest.is_empty().then_some(Rule {
+            std,
+            dst: Some(Dst { offset, start, end }),
+        })
+    }
+
+    fn offset_at(&self, ts: i64) -> i32 {
+        let Some(dst) = &self.dst else {
+            return self.std;
+        };
+        let year = civil_from_days((ts + i64::from(self.std)).div_euclid(86400)).0;
+        let start = day_start(year, dst.start.0) + i64::from(dst.start.1 - self.std);
+        let end = day_start(year, dst.end.0) + i64::from(dst.end.1 - dst.offset);
+        let daylight = if start < end {
+            (start..end).contains(&ts)
+        } else {
+            !(end..start).contains(&ts)
+        };
+        if daylight { dst.offset } else { self.std }
+    }
+}
+
+/// Skips a zone abbreviation: letters, or anything within `<...>`.
+fn skip_name(rest: &mut &str) -> Option<()> {
+    let len = match rest.strip_prefix('<') {
+        Some(quoted) => quoted.find('>')? + 2,
+        None => rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len()),
+    };
+    (len >= 3).then(|| *rest = &rest[len..])
+}
+
+/// `[+-]hh[:mm[:ss]]` in seconds; hours may run past 24 for rule times.
+fn parse_time(rest: &mut &str) -> Option<i32> {
+    let sign = match rest.as_bytes().first()? {
+        b'-' => -1,
+        _ => 1,
+    };
+    let body = rest.trim_start_matches(['+', '-']);
+    let len = body
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(body.len());
+    let mut seconds = 0;
+    let mut scale = 3600;
+    for part in body[..len].split(':') {
+        if scale == 0 {
+            return None;
+        }
+        seconds += part.parse::<i32>().ok()? * scale;
+        scale /= 60;
+    }
+    *rest = &body[len..];
+    Some(sign * seconds)
+}
+
+fn parse_day(rest: &mut &str) -> Option<RuleDay> {
+    let number = |rest: &mut &str| -> Option<u16> {
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n = rest[..len].parse().ok()?;
+        *rest = &rest[len..];
+        Some(n)
+    };
+    if let Some(after) = rest.strip_prefix('J') {
+        *rest = after;
+        return number(rest)
+            .filter(|n| (1..=365).contains(n))
+            .map(RuleDay::Julian);
+    }
+    if let Some(after) = rest.strip_prefix('M') {
+        *rest = after;
+        let month = number(rest).filter(|m| (1..=12).contains(m))?;
+        *rest = rest.strip_prefix('.')?;
+        let week = number(rest).filter(|w| (1..=5).contains(w))?;
+        *rest = rest.strip_prefix('.')?;
+        let weekday = number(rest).filter(|d| *d <= 6)?;
+        return Some(RuleDay::Month(month as u8, week as u8, weekday as u8));
+    }
+    number(rest).filter(|n| *n <= 365).map(RuleDay::Zero)
+}
+
+/// Midnight UTC of a rule's day in `year`, in seconds since the epoch.
+fn day_start(year: i64, day: RuleDay) -> i64 {
+    let jan1 = days_from_civil(year, 1, 1);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match day {
+        RuleDay::Julian(n) => jan1 + i64::from(n) - 1 + i64::from(leap && n > 59),
+        RuleDay::Zero(n) => jan1 + i64::from(n),
+        RuleDay::Month(month, week, weekday) => {
+            let first = days_from_civil(year, month.into(), 1);
+            let next = if month == 12 {
+                days_from_civil(year + 1, 1, 1)
+            } else {
+                days_from_civil(year, (month + 1).into(), 1)
+            };
+            // 1970-01-01 was a Thursday.
+            let first_weekday = (first + 4).rem_euclid(7);
+            let mut day = first + (i64::from(weekday) - first_weekday).rem_euclid(7);
+            day += 7 * (i64::from(week) - 1);
+            while day >= next {
+                day -= 7;
+            }
+            day
+        }
+    };
+    days * 86400
+}
+
+/// Days since the epoch of a proleptic Gregorian date (Howard Hinnant's
+/// algorithm).
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Year, month and day of a day count since the epoch.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2025-03-09T06:59:59Z, a second before New York springs forward.
+    const NY_SPRING: u64 = 1_741_503_599;
+
+    #[test]
+    fn test_fixed_offsets() {
+        assert!(TimeZone::parse("UTC").unwrap().is_utc());
+        let ist = TimeZone::parse("+05:30").unwrap();
+        assert_eq!(ist.offset_at(0), 19_800);
+        assert_eq!(TimeZone::parse("-0800").unwrap().offset_at(0), -28_800);
+        assert!(TimeZone::parse("+25").is_err());
+        assert!(TimeZone::parse("../etc/passwd").is_err());
+
+        // 2025-02-12T20:00:00Z is 01:30 the next day in India.
+        let ts = 1_739_390_400;
+        assert_eq!(ist.floor(ts, 86400), 1_739_385_000);
+        assert_eq!(ist.floor(ts, 3600), ts - 1800);
+    }
+
+    #[test]
+    fn test_posix_rule() {
+        let rule = Rule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(rule.offset_at(NY_SPRING as i64), -5 * 3600);
+        assert_eq!(rule.offset_at(NY_SPRING as i64 + 1), -4 * 3600);
+        // 2025-11-02T05:59:59Z and a second later, falling back.
+        assert_eq!(rule.offset_at(1_762_063_199), -4 * 3600);
+        assert_eq!(rule.offset_at(1_762_063_200), -5 * 3600);
+
+        let sydney = Rule::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        // January is summer in Sydney.
+        assert_eq!(sydney.offset_at(1_736_000_000), 11 * 3600);
+        assert_eq!(sydney.offset_at(1_750_000_000), 10 * 3600);
+
+        assert_eq!(Rule::parse("<+0530>-5:30").unwrap().offset_at(0), 19_800);
+        assert!(Rule::parse("EST5EDT,M13.1.0,M11.1.0").is_none());
+    }
+
+    #[test]
+    fn test_floor_across_dst() {
+        let ny = TimeZone {
+            rule: Some(Rule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap()),
+            ..TimeZone::default()
+        };
+        // Local midnight on 2025-03-09 was 05:00Z, before and after the
+        // clocks sprang forward at 07:00Z.
+        let midnight = 1_741_496_400;
+        for ts in [NY_SPRING - 3600, NY_SPRING, NY_SPRING + 1, NY_SPRING + 3601] {
+            assert_eq!(ny.floor(ts, 86400), midnight, "{}", ts);
+        }
+        assert_eq!(ny.floor(NY_SPRING + 1, 3600), NY_SPRING + 1);
+        // The next day is 23 hours long.
+        assert_eq!(ny.floor(midnight + 23 * 3600, 86400), midnight + 23 * 3600);
+
+        // 2025-11-02 started at 04:00Z (EDT) and ran 25 hours; the hour
+        // from 01:00 local comes twice, an hour apart.
+        let midnight = 1_762_056_000;
+        let fall = 1_762_063_200;
+        for ts in [fall - 1, fall, fall + 6 * 3600] {
+            assert_eq!(ny.floor(ts, 86400), midnight, "{}", ts);
+        }
+        assert_eq!(ny.floor(fall - 1, 3600), fall - 3600);
+        assert_eq!(ny.floor(fall + 1800, 3600), fall);
+        assert_eq!(ny.floor(midnight + 25 * 3600, 86400), midnight + 25 * 3600);
+    }
+
+    #[test]
+    fn test_tzif_new_york() {
+        let Ok(ny) = TimeZone::parse("America/New_York") else {
+            return; // no tz database on this machine
+        };
+        assert_eq!(ny.offset_at(NY_SPRING), -5 * 3600);
+        assert_eq!(ny.offset_at(NY_SPRING + 1), -4 * 3600);
+        // Past the last transition, the footer rule applies.
+        assert_eq!(ny.offset_at(4_102_444_800 + 200 * 86400), -4 * 3600);
+        // Local midnight on 2025-03-09 was 05:00Z.
+        assert_eq!(ny.floor(NY_SPRING, 86400), 1_741_496_400);
+    }
+
+    #[test]
+    fn test_civil_days() {
+        for days in [-1, 0, 11_000, 20_131, 2_932_896] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(20_131), (2025, 2, 12));
+    }
+}
@@ -0,0 +1,178 @@
+//! Owned, decoded copies of record fields for embedders that keep records
+//! past the batch they came from, such as language bindings. Values like
+//! `"status":"200"` repeat across nearly every record, so small ones are
+//! interned: each distinct value is decoded and allocated once and shared.
+
+use crate::emit::{EmitRecord, FieldValue, unescape_json};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Values longer than this are decoded fresh every time; they rarely
+/// repeat, and hashing them would cost more than the allocation saves.
+pub const DEFAULT_MAX_INTERNED_LEN: usize = 64;
+
+/// Distinct values kept before the cache starts over, bounding its memory
+/// on inputs whose small values never repeat.
+pub const DEFAULT_MAX_INTERNED: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedValue {
+    /// Decoded text: JSON escapes resolved.
+    Text(Arc<str>),
+    /// A JSON number, boolean or null, as written.
+    Literal(Arc<str>),
+    /// Seconds since the Unix epoch.
+    Timestamp(u64),
+}
+
+pub type OwnedRecord = Vec<(Arc<str>, OwnedValue)>;
+
+/// Interns decoded keys and values by their source bytes. One cache per
+/// thread; it is not shared.
+#[derive(Debug)]
+pub struct ValueCache {
+    text: HashMap<Box<[u8]>, Arc<str>>,
+    escaped: HashMap<Box<[u8]>, Arc<str>>,
+    literal: HashMap<Box<[u8]>, Arc<str>>,
+    max_len: usize,
+    max_entries: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for ValueCache {
+    fn default() -> Self {
+        ValueCache::new(DEFAULT_MAX_INTERNED_LEN, DEFAULT_MAX_INTERNED)
+    }
+}
+
+impl ValueCache {
+    pub fn new(max_len: usize, max_entries: usize) -> ValueCache {
+        ValueCache {
+            text: HashMap::new(),
+            escaped: HashMap::new(),
+            literal: HashMap::new(),
+            max_len,
+            max_entries,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn value(&mut self, value: FieldValue<'_>) -> OwnedValue {
+        match value {
+            FieldValue::Text(raw) => OwnedValue::Text(self.intern(Kind::Text, raw)),
+            FieldValue::Escaped(raw) => OwnedValue::Text(self.intern(Kind::Escaped, raw)),
+            FieldValue::Literal(raw) => OwnedValue::Literal(self.intern(Kind::Literal, raw)),
+            FieldValue::Timestamp(ts) => OwnedValue::Timestamp(ts),
+        }
+    }
+
+    pub fn key(&mut self, key: &[u8]) -> Arc<str> {
+        self.intern(Kind::Text, key)
+    }
+
+    /// Every field of record `i`, in record order.
+    pub fn record<B: EmitRecord>(&mut self, batch: &B, i: usize) -> OwnedRecord {
+        let mut fields = Vec::new();
+        batch.for_each_field(i, &mut |key, value| {
+            fields.push((self.key(key), self.value(value)));
+        });
+        fields
+    }
+
+    /// Lookups answered from the cache and lookups that decoded.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len() + self.escaped.len() + self.literal.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn intern(&mut self, kind: Kind, raw: &[u8]) -> Arc<str> {
+        if raw.len() > self.max_len {
+            self.misses += 1;
+            return kind.decode(raw).into();
+        }
+        if let Some(shared) = self.map(kind).get(raw).cloned() {
+            self.hits += 1;
+            return shared;
+        }
+        self.misses += 1;
+        if self.len() >= self.max_entries {
+            self.text.clear();
+            self.escaped.clear();
+            self.literal.clear();
+        }
+        let shared: Arc<str> = kind.decode(raw).into();
+        self.map(kind).insert(raw.into(), Arc::clone(&shared));
+        shared
+    }
+
+    fn map(&mut self, kind: Kind) -> &mut HashMap<Box<[u8]>, Arc<str>> {
+        match kind {
+            Kind::Text => &mut self.text,
+            Kind::Escaped => &mut self.escaped,
+            Kind::Literal => &mut self.literal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Text,
+    Escaped,
+    Literal,
+}
+
+impl Kind {
+    fn decode(self, raw: &[u8]) -> String {
+        match self {
+            Kind::Escaped if raw.contains(&b'\\') => unescape_json(raw),
+            _ => String::from_utf8_lossy(raw).into_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::LogFormat;
+    use crate::structured_orchestrator::parse_structured_mmap;
+
+    #[test]
+    fn test_value_cache_shares_repeated_values() {
+        let data = b"{\"status\":\"200\",\"msg\":\"caf\\u00e9 \\ud83d\\ude00\\n\",\"ms\":12}\n\
+                     {\"status\":\"200\",\"msg\":\"ok\",\"ms\":12}\n";
+        let result = parse_structured_mmap(data, 1, Some(LogFormat::Json));
+        let batch = &result.batches[0];
+        let mut cache = ValueCache::default();
+        let first = cache.record(batch, 0);
+        let second = cache.record(batch, 1);
+
+        assert_eq!(first[1].1, OwnedValue::Text("caf\u{e9} \u{1f600}\n".into()));
+        assert_eq!(second[2].1, OwnedValue::Literal("12".into()));
+        let (OwnedValue::Text(a), OwnedValue::Text(b)) = (&first[0].1, &second[0].1) else {
+            panic!("status is text");
+        };
+        assert!(Arc::ptr_eq(a, b));
+        assert!(Arc::ptr_eq(&first[0].0, &second[0].0));
+        // The first record decodes all six; the second only "ok".
+        assert_eq!(cache.stats(), (5, 7));
+
+        // A full cache starts over; long values are never kept.
+        let mut small = ValueCache::new(4, 2);
+        small.key(b"a");
+        small.key(b"b");
+        small.key(b"c");
+        assert_eq!(small.len(), 1);
+        small.key(b"longer");
+        assert_eq!(small.len(), 1);
+        assert_eq!(unescape_json(b"\\ud800x\\q"), "\u{fffd}x\u{fffd}");
+    }
+}
@@ -0,0 +1,300 @@
+//! Records placed exactly on SIMD block and chunk boundaries by
+//! `generate-structured-logs --align`, parsed every way pandora reads a
+//! file and checked against a plain split of the input on newlines.
+
+use pandoraslogs::data::{BatchRecords, LogBatch};
+use pandoraslogs::filter::MatchControl;
+use pandoraslogs::format::LogFormat;
+use pandoraslogs::orchestrator::{parse_logs_pipelined, parse_logs_streamed_with};
+use pandoraslogs::simd_scan::{Kernel, scan_region_with, set_record_sep};
+use pandoraslogs::structured::StructuredBatch;
+use pandoraslogs::structured_orchestrator::{
+    parse_structured_mmap, parse_structured_streamed_with,
+};
+use std::fs::File;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+const FORMATS: [&str; 4] = ["log", "json", "logfmt", "csv"];
+
+/// Held while parsing, since `records_on_chunk_boundaries` changes the
+/// `PANDORA_CHUNK_MB` the parsers read.
+static ENV: Mutex<()> = Mutex::new(());
+
+fn generate(size_mb: u64, format: &str, align: u64, jitter: u64) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "pandora-boundaries-{}-{}-{}-{}-{}",
+        std::process::id(),
+        size_mb,
+        format,
+        align,
+        jitter
+    ));
+    let status = Command::new(env!("CARGO_BIN_EXE_generate-structured-logs"))
+        .arg(size_mb.to_string())
+        .arg(&path)
+        .arg(format)
+        .args([
+            "--align",
+            &align.to_string(),
+            "--jitter",
+            &jitter.to_string(),
+        ])
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    path
+}
+
+/// Start offset and text of every record line.
+fn expected_lines<'a>(data: &'a [u8], format: &str) -> Vec<(u64, &'a [u8])> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for end in memchr::memchr_iter(b'\n', data) {
+        lines.push((start as u64, &data[start..end]));
+        start = end + 1;
+    }
+    if format == "csv" {
+        lines.remove(0);
+    }
+    lines
+}
+
+fn push_records<B: BatchRecords>(
+    batch: &B,
+    records: impl Iterator<Item = usize>,
+    lines: &mut Vec<(u64, Vec<u8>)>,
+) {
+    for i in records {
+        let span = batch.record_line(i);
+        lines.push((
+            batch.input_offset() + span.offset,
+            batch.line_bytes(span).to_vec(),
+        ));
+    }
+}
+
+fn collect<B: BatchRecords>(batches: &[B]) -> Vec<(u64, Vec<u8>)> {
+    let mut lines = Vec::new();
+    for batch in batches {
+        push_records(batch, 0..batch.record_count(), &mut lines);
+    }
+    lines
+}
+
+/// Records as streaming hands them out; it keeps only its first batch.
+fn collect_streamed(path: &PathBuf, format: &str, threads: usize) -> Vec<(u64, Vec<u8>)> {
+    let lines = Mutex::new(Vec::new());
+    let mut file = File::open(path).unwrap();
+    let size = file.metadata().unwrap().len();
+    if format == "log" {
+        let add = |batch: &LogBatch, records: &[u32]| {
+            push_records(
+                batch,
+                records.iter().map(|&i| i as usize),
+                &mut lines.lock().unwrap(),
+            )
+        };
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_logs_streamed_with(&mut file, size, threads, &control);
+    } else {
+        let add = |batch: &StructuredBatch, records: &[u32]| {
+            push_records(
+                batch,
+                records.iter().map(|&i| i as usize),
+                &mut lines.lock().unwrap(),
+            )
+        };
+        let control = MatchControl {
+            on_batch: Some(&add),
+            ..Default::default()
+        };
+        parse_structured_streamed_with(
+            &mut file,
+            size,
+            threads,
+            LogFormat::from_name(format),
+            &control,
+        );
+    }
+    lines.into_inner().unwrap()
+}
+
+fn check_file(path: &PathBuf, format: &str, align: u64) {
+    let data = std::fs::read(path).unwrap();
+    let expected: Vec<(u64, Vec<u8>)> = expected_lines(&data, format)
+        .into_iter()
+        .map(|(offset, line)| (offset, line.to_vec()))
+        .collect();
+    let context = format!("{} aligned to {} ({})", format, align, path.display());
+    // Records must really have landed on, or within the jitter of, a
+    // boundary past the first.
+    let near = |offset: u64| offset > align / 2 && (offset + 3) % align <= 6;
+    assert!(
+        expected.iter().any(|&(offset, _)| near(offset)),
+        "{}",
+        context
+    );
+
+    let starts: Vec<u64> = expected_lines(&data, "log")
+        .iter()
+        .skip(1)
+        .map(|&(offset, _)| offset)
+        .collect();
+    for kernel in Kernel::ALL {
+        let mut scanned = Vec::new();
+        if scan_region_with(kernel, &data, 0, data.len() as u64, &mut scanned).is_some() {
+            assert!(
+                scanned == starts,
+                "{} line starts in {}",
+                kernel.name(),
+                context
+            );
+        }
+    }
+
+    for threads in [1, 4] {
+        let mapped = if format == "log" {
+            collect(&parse_logs_pipelined(&data, threads).batches)
+        } else {
+            collect(&parse_structured_mmap(&data, threads, LogFormat::from_name(format)).batches)
+        };
+        let streamed = collect_streamed(path, format, threads);
+        for (how, got) in [("mapped", mapped), ("streamed", streamed)] {
+            if let Some(i) =
+                (0..got.len().max(expected.len())).find(|&i| got.get(i) != expected.get(i))
+            {
+                let show = |line: Option<&(u64, Vec<u8>)>| {
+                    line.map(|(offset, text)| {
+                        format!("{}: {}", offset, String::from_utf8_lossy(text))
+                    })
+                };
+                panic!(
+                    "{} {} with {} threads: record {} is {:?}, expected {:?}",
+                    how,
+                    context,
+                    threads,
+                    i,
+                    show(got.get(i)),
+                    show(expected.get(i))
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn records_on_simd_boundaries() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    // Jitter 1 cycles line ends through one before, on and one after each
+    // multiple.
+    for align in [32, 64, 256] {
+        for format in FORMATS {
+            let path = generate(1, format, align, 1);
+            check_file(&path, format, align);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
+
+#[test]
+fn records_on_chunk_boundaries() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    // Safety: the lock keeps the other tests here from parsing, and so
+    // from reading the environment, meanwhile.
+    unsafe { std::env::set_var("PANDORA_CHUNK_MB", "1") };
+    for format in FORMATS {
+        let path = generate(4, format, 1 << 20, 3);
+        check_file(&path, format, 1 << 20);
+        std::fs::remove_file(path).unwrap();
+    }
+    unsafe { std::env::remove_var("PANDORA_CHUNK_MB") };
+}
+
+#[test]
+fn nul_separated_records_across_chunks() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let mut data = Vec::new();
+    let mut expected = Vec::new();
+    for i in 0..40_000 {
+        let record = format!(
+            "{{\n  \"level\": \"info\",\n  \"msg\": \"request {}\\n{}\"\n}}",
+            i,
+            "x".repeat(i % 97)
+        );
+        expected.push((data.len() as u64, record.clone().into_bytes()));
+        data.extend_from_slice(record.as_bytes());
+        data.push(0);
+    }
+    let path = std::env::temp_dir().join(format!("pandora-nul-{}", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+    // Safety: as in `records_on_chunk_boundaries`.
+    unsafe { std::env::set_var("PANDORA_CHUNK_MB", "1") };
+    set_record_sep(0);
+    for threads in [1, 4] {
+        let mapped = parse_structured_mmap(&data, threads, Some(LogFormat::Json));
+        assert!(
+            collect(&mapped.batches) == expected,
+            "mapped, {} threads",
+            threads
+        );
+        let streamed = collect_streamed(&path, "json", threads);
+        assert!(streamed == expected, "streamed, {} threads", threads);
+    }
+    set_record_sep(b'\n');
+    unsafe { std::env::remove_var("PANDORA_CHUNK_MB") };
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn multiline_csv_cells_across_chunks() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let mut data = b"level,message,note\n".to_vec();
+    let mut expected = Vec::new();
+    for i in 0..60_000 {
+        let record = format!(
+            "info,\"request {}\n  \"\"quoted\"\" frame\n{}\",n{}",
+            i,
+            "y".repeat(i % 53),
+            i
+        );
+        expected.push((data.len() as u64, record.clone().into_bytes()));
+        data.extend_from_slice(record.as_bytes());
+        data.extend_from_slice(if i % 5 == 0 { b"\r\n" } else { b"\n" });
+    }
+    let path = std::env::temp_dir().join(format!("pandora-csv-cells-{}", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+    // Safety: as in `records_on_chunk_boundaries`.
+    unsafe { std::env::set_var("PANDORA_CHUNK_MB", "1") };
+    for threads in [1, 4] {
+        let mapped = parse_structured_mmap(&data, threads, Some(LogFormat::Csv));
+        assert!(
+            collect(&mapped.batches) == expected,
+            "mapped, {} threads",
+            threads
+        );
+        assert_eq!(mapped.malformed_lines, 0);
+        let streamed = collect_streamed(&path, "csv", threads);
+        assert!(streamed == expected, "streamed, {} threads", threads);
+    }
+    unsafe { std::env::remove_var("PANDORA_CHUNK_MB") };
+    std::fs::remove_file(path).unwrap();
+}
+
+/// The same at the default 64 MB chunk size; slow, so run on demand with
+/// `cargo test --release --test boundaries -- --ignored`.
+#[test]
+#[ignore]
+fn records_on_default_chunk_boundaries() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    for format in ["log", "json"] {
+        let path = generate(200, format, 64 << 20, 3);
+        check_file(&path, format, 64 << 20);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+//! Parses random well-formed JSON and CSV with pandora and with serde_json
+//! and the csv crate, and checks each record has the same fields. Pandora
+//! leaves values escaped; they are unescaped here before comparing.
+
+use pandoraslogs::format::LogFormat;
+use pandoraslogs::structured::StructuredBatch;
+use pandoraslogs::structured_orchestrator::parse_structured_mmap;
+use serde_json::Value;
+
+const CASES: usize = 300;
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, one_in: usize) -> bool {
+        self.below(one_in) == 0
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+/// Characters that exercise escaping, separators and multi-byte UTF-8.
+const CHARS: &[char] = &[
+    'a', 'b', 'z', 'Q', '0', '9', ' ', '\t', '"', '\\', '/', ',', ':', '=', '{', '}', '[', ']',
+    '\u{1}', '\u{1f}', 'é', '日', '😀',
+];
+
+fn random_text(rng: &mut Rng, max: usize) -> String {
+    (0..rng.below(max + 1)).map(|_| rng.pick(CHARS)).collect()
+}
+
+/// Fields of one record as (key, value) in line order, values unescaped
+/// to JSON.
+type Record = Vec<(String, Value)>;
+
+fn structured_records(data: &[u8], format: LogFormat, threads: usize) -> Vec<Record> {
+    let result = parse_structured_mmap(data, threads, Some(format));
+    let mut records = Vec::new();
+    for batch in &result.batches {
+        for i in 0..batch.len {
+            records.push(
+                batch
+                    .record_fields(i)
+                    .iter()
+                    .map(|field| field_pair(data, format, batch, field))
+                    .collect(),
+            );
+        }
+    }
+    records
+}
+
+fn field_pair(
+    data: &[u8],
+    format: LogFormat,
+    batch: &StructuredBatch,
+    field: &pandoraslogs::structured::FieldRef,
+) -> (String, Value) {
+    let (key, value) = unsafe { (batch.field_key(field), batch.field_value(field)) };
+    let at = value.as_ptr() as usize - data.as_ptr() as usize;
+    let quoted = at > 0 && data[at - 1] == b'"';
+    match format {
+        LogFormat::Csv => {
+            let value = if quoted {
+                value.replace("\"\"", "\"")
+            } else {
+                value.to_string()
+            };
+            (key.to_string(), Value::String(value))
+        }
+        _ => {
+            let key = serde_json::from_str(&format!("\"{}\"", key))
+                .unwrap_or_else(|e| panic!("key {:?} is not a JSON string body: {}", key, e));
+            let value = if quoted {
+                serde_json::from_str(&format!("\"{}\"", value))
+            } else {
+                serde_json::from_str(value)
+            }
+            .unwrap_or_else(|e| panic!("value {:?} of {:?} does not parse: {}", value, key, e));
+            (key, value)
+        }
+    }
+}
+
+fn sorted(mut record: Record) -> Record {
+    record.sort_by(|a, b| a.0.cmp(&b.0));
+    record
+}
+
+/// Writes `text` as a JSON string, escaping each character one of the ways
+/// JSON allows.
+fn write_string(rng: &mut Rng, text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '/' if rng.chance(2) => out.push_str("\\/"),
+            '\t' if rng.chance(2) => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if !c.is_ascii() && rng.chance(2) => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_space(rng: &mut Rng, out: &mut String) {
+    for _ in 0..rng.below(3).saturating_sub(1) {
+        out.push(rng.pick(&[' ', '\t']));
+    }
+}
+
+fn write_value(rng: &mut Rng, depth: usize, out: &mut String) {
+    match rng.below(if depth < 2 { 8 } else { 6 }) {
+        0 | 1 => {
+            let text = random_text(rng, 12);
+            write_string(rng, &text, out);
+        }
+        2 => out.push_str(rng.pick(&[
+            "0",
+            "-0",
+            "42",
+            "-17",
+            "3.25",
+            "1e9",
+            "-2.5E-3",
+            "123456789012",
+        ])),
+        3 => out.push_str(rng.pick(&["true", "false", "null"])),
+        4 | 5 => {
+            let text = random_text(rng, 4);
+            write_string(rng, &text, out);
+        }
+        6 => {
+            out.push('[');
+            for n in 0..rng.below(4) {
+                if n > 0 {
+                    out.push(',');
+                }
+                write_space(rng, out);
+                write_value(rng, depth + 1, out);
+                write_space(rng, out);
+            }
+            out.push(']');
+        }
+        _ => write_object(rng, depth + 1, out),
+    }
+}
+
+fn write_object(rng: &mut Rng, depth: usize, out: &mut String) {
+    let mut keys: Vec<String> = Vec::new();
+    out.push('{');
+    for _ in 0..rng.below(6) {
+        let key = random_text(rng, 8);
+        if keys.contains(&key) {
+            continue;
+        }
+        if !keys.is_empty() {
+            out.push(',');
+        }
+        write_space(rng, out);
+        write_string(rng, &key, out);
+        write_space(rng, out);
+        out.push(':');
+        write_space(rng, out);
+        write_value(rng, depth, out);
+        write_space(rng, out);
+        keys.push(key);
+    }
+    out.push('}');
+}
+
+#[test]
+fn json_fields_match_serde_json() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for case in 0..CASES {
+        let mut data = String::new();
+        let mut expected = Vec::new();
+        for n in 0..1 + rng.below(20) {
+            if n > 0 {
+                data.push_str(if rng.chance(4) { "\r\n" } else { "\n" });
+            }
+            let start = data.len();
+            write_space(&mut rng, &mut data);
+            write_object(&mut rng, 0, &mut data);
+            let object: serde_json::Map<String, Value> = serde_json::from_str(&data[start..])
+                .unwrap_or_else(|e| panic!("generated {:?}: {}", &data[start..], e));
+            // Objects without keys are malformed lines by default.
+            if !object.is_empty() {
+                expected.push(sorted(object.into_iter().collect()));
+            }
+        }
+        if rng.chance(2) {
+            data.push('\n');
+        }
+        for threads in [1, 3] {
+            let records: Vec<Record> =
+                structured_records(data.as_bytes(), LogFormat::Json, threads)
+                    .into_iter()
+                    .map(sorted)
+                    .collect();
+            assert_eq!(
+                records, expected,
+                "case {} with {} threads:\n{}",
+                case, threads, data
+            );
+        }
+    }
+}
+
+#[test]
+fn csv_fields_match_csv_crate() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    // Line breaks end up in quoted cells that span lines.
+    const CSV_CHARS: &[char] = &[
+        'a', 'Z', '7', ' ', '\t', '"', ',', ';', '\'', 'é', '日', '\n', '\r',
+    ];
+    for case in 0..CASES {
+        let columns = 1 + rng.below(5);
+        let names: Vec<String> = (0..columns).map(|c| format!("col{}", c)).collect();
+        let style = rng.pick(&[csv::QuoteStyle::Necessary, csv::QuoteStyle::Always]);
+        let terminator = rng.pick(&[csv::Terminator::CRLF, csv::Terminator::Any(b'\n')]);
+        let mut writer = csv::WriterBuilder::new()
+            .quote_style(style)
+            .terminator(terminator)
+            .from_writer(Vec::new());
+        writer.write_record(&names).unwrap();
+        for _ in 0..1 + rng.below(20) {
+            let row: Vec<String> = (0..columns)
+                .map(|_| (0..rng.below(8)).map(|_| rng.pick(CSV_CHARS)).collect())
+                .collect();
+            writer.write_record(&row).unwrap();
+        }
+        let data = writer.into_inner().unwrap();
+
+        let mut reader = csv::ReaderBuilder::new().from_reader(&data[..]);
+        let expected: Vec<Record> = reader
+            .records()
+            .map(|row| {
+                names
+                    .iter()
+                    .cloned()
+                    .zip(row.unwrap().iter().map(|v| Value::String(v.to_string())))
+                    .collect()
+            })
+            .collect();
+        for threads in [1, 3] {
+            let records = structured_records(&data, LogFormat::Csv, threads);
+            assert_eq!(
+                records,
+                expected,
+                "case {} with {} threads:\n{}",
+                case,
+                threads,
+                String::from_utf8_lossy(&data)
+            );
+        }
+    }
+}